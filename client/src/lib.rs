@@ -9,6 +9,7 @@ pub mod error;
 // Reexports
 pub use crate::error::Error;
 pub use authc::AuthClientError;
+pub use network::ChannelStats;
 pub use specs::{
     join::Join,
     saveload::{Marker, MarkerAllocator},
@@ -25,12 +26,13 @@ use common::{
         group,
         invite::{InviteKind, InviteResponse},
         skills::Skill,
-        slot::Slot,
+        slot::{InvSlotId, Slot},
         ChatMode, ControlAction, ControlEvent, Controller, ControllerInputs, GroupManip, InputKind,
         InventoryAction, InventoryEvent, InventoryUpdateEvent,
     },
     event::{EventBus, LocalEvent},
     grid::Grid,
+    mail::{Mail, MailAction, MailError, MailId, MailUpdate},
     outcome::Outcome,
     recipe::RecipeBook,
     resources::PlayerEntity,
@@ -49,19 +51,21 @@ use common_net::{
         PresenceKind, RegisterError, ServerGeneral, ServerInfo, ServerInit, ServerRegisterAnswer,
         MAX_BYTES_CHAT_MSG,
     },
-    sync::WorldSyncExt,
+    sync::{PhysicsDeltaDecoder, WorldSyncExt},
 };
 use common_sys::state::State;
 use comp::BuffKind;
 use futures_util::FutureExt;
 use hashbrown::{HashMap, HashSet};
 use image::DynamicImage;
-use network::{Network, Participant, Pid, ProtocolAddr, Stream};
+use network::{Network, NetworkError, Participant, Pid, ProtocolAddr, Stream};
 use num::traits::FloatConst;
 use rayon::prelude::*;
 use specs::Component;
 use std::{
     collections::{BTreeSet, VecDeque},
+    future::Future,
+    pin::Pin,
     sync::Arc,
     time::{Duration, Instant},
 };
@@ -71,6 +75,8 @@ use vek::*;
 
 const PING_ROLLING_AVERAGE_SECS: usize = 10;
 
+type Reconnect = Pin<Box<dyn Future<Output = Result<(), NetworkError>> + Send>>;
+
 #[derive(Debug)]
 pub enum Event {
     Chat(comp::ChatMsg),
@@ -92,6 +98,10 @@ pub enum Event {
     Outcome(Outcome),
     CharacterCreated(CharacterId),
     CharacterError(String),
+    MailSent,
+    MailError(MailError),
+    NewMail,
+    BankOpened,
 }
 
 pub struct WorldData {
@@ -157,8 +167,12 @@ pub struct Client {
     pending_invites: HashSet<Uid>,
     // The pending trade the client is involved in, and it's id
     pending_trade: Option<(TradeId, PendingTrade)>,
+    // The mailbox of the current character, empty until requested
+    mailbox: Vec<Mail>,
+    // The bank of the current character while it is open
+    bank: Option<comp::Bank>,
 
-    _network: Network,
+    network: Arc<Network>,
     participant: Option<Participant>,
     general_stream: Stream,
     ping_stream: Stream,
@@ -172,9 +186,13 @@ pub struct Client {
     last_server_pong: f64,
     last_ping_delta: f64,
     ping_deltas: VecDeque<f64>,
+    /// Attaches a new connection to our session on the server
+    reconnect: Option<Reconnect>,
+    last_reconnect: f64,
 
     tick: u64,
     state: State,
+    physics_delta: PhysicsDeltaDecoder,
 
     view_distance: Option<u32>,
     // TODO: move into voxygen
@@ -192,20 +210,32 @@ pub struct CharacterList {
 }
 
 impl Client {
-    /// Create a new `Client`.
+    /// Create a new `Client`. If `server_identity` is given the server has to
+    /// prove that it owns this key, e.g. the one returned by
+    /// [`Client::server_identity`] the last time, otherwise connecting fails.
     pub async fn new(
         addr: ConnectionArgs,
         view_distance: Option<u32>,
         runtime: Arc<Runtime>,
+        server_identity: Option<[u8; 32]>,
     ) -> Result<Self, Error> {
-        let network = Network::new(Pid::new(), &runtime);
+        let network = Arc::new(Network::new(Pid::new(), &runtime));
+        let connect = |address| {
+            let network = Arc::clone(&network);
+            async move {
+                match server_identity {
+                    Some(identity) => network.connect_authenticated(address, identity).await,
+                    None => network.connect(address).await,
+                }
+            }
+        };
 
         let participant = match addr {
             ConnectionArgs::IpAndPort(addrs) => {
                 // Try to connect to all IP's and return the first that works
                 let mut participant = None;
                 for addr in addrs {
-                    match network.connect(ProtocolAddr::Tcp(addr)).await {
+                    match connect(ProtocolAddr::Tcp(addr)).await {
                         Ok(p) => {
                             participant = Some(Ok(p));
                             break;
@@ -216,7 +246,7 @@ impl Client {
                 participant
                     .unwrap_or_else(|| Err(Error::Other("No Ip Addr provided".to_string())))?
             },
-            ConnectionArgs::Mpsc(id) => network.connect(ProtocolAddr::Mpsc(id)).await?,
+            ConnectionArgs::Mpsc(id) => connect(ProtocolAddr::Mpsc(id)).await?,
         };
 
         let stream = participant.opened().await?;
@@ -436,6 +466,9 @@ impl Client {
             ServerInit::TooManyPlayers => Err(Error::TooManyPlayers),
         }?;
         ping_stream.send(PingMsg::Ping)?;
+        // The server keeps our session as long, so we can reconnect if our connection
+        // breaks
+        network.set_resume_timeout(client_timeout).await;
 
         debug!("Initial sync done");
 
@@ -471,8 +504,10 @@ impl Client {
             group_members: HashMap::new(),
             pending_invites: HashSet::new(),
             pending_trade: None,
+            mailbox: Vec::new(),
+            bank: None,
 
-            _network: network,
+            network,
             participant: Some(participant),
             general_stream: stream,
             ping_stream,
@@ -487,9 +522,12 @@ impl Client {
             last_server_pong: 0.0,
             last_ping_delta: 0.0,
             ping_deltas: VecDeque::new(),
+            reconnect: None,
+            last_reconnect: 0.0,
 
             tick: 0,
             state,
+            physics_delta: PhysicsDeltaDecoder::default(),
             view_distance,
             loaded_distance: 0.0,
 
@@ -592,9 +630,11 @@ impl Client {
                     | ClientGeneral::PlaceBlock(_, _)
                     | ClientGeneral::ExitInGame
                     | ClientGeneral::PlayerPhysics { .. }
+                    | ClientGeneral::AckPhysicsDelta(_)
                     | ClientGeneral::UnlockSkill(_)
                     | ClientGeneral::RefundSkill(_)
                     | ClientGeneral::RequestSiteInfo(_)
+                    | ClientGeneral::MailAction(_)
                     | ClientGeneral::UnlockSkillGroup(_) => &mut self.in_game_stream,
                     //Only in game, terrain
                     ClientGeneral::TerrainChunkRequest { .. } => &mut self.terrain_stream,
@@ -671,6 +711,9 @@ impl Client {
 
     pub fn swap_slots(&mut self, a: Slot, b: Slot) {
         match (a, b) {
+            (Slot::Bank(_), _) | (_, Slot::Bank(_)) => self.send_msg(ClientGeneral::ControlEvent(
+                ControlEvent::InventoryEvent(InventoryEvent::BankSwap(a, b)),
+            )),
             (Slot::Equip(equip), slot) | (slot, Slot::Equip(equip)) => self.control_action(
                 ControlAction::InventoryAction(InventoryAction::Swap(equip, slot)),
             ),
@@ -690,6 +733,8 @@ impl Client {
             Slot::Inventory(inv) => self.send_msg(ClientGeneral::ControlEvent(
                 ControlEvent::InventoryEvent(InventoryEvent::Drop(inv)),
             )),
+            // Items have to be taken out of the bank before they can be dropped
+            Slot::Bank(_) => {},
         }
     }
 
@@ -704,10 +749,48 @@ impl Client {
         }
     }
 
+    /// Requests the mailbox of the current character from the server
+    pub fn request_mailbox(&mut self) {
+        self.send_msg(ClientGeneral::MailAction(MailAction::RequestMailbox));
+    }
+
+    /// Sends a mail to the character named `recipient`, with the items in the
+    /// given inventory slots attached
+    pub fn send_mail(
+        &mut self,
+        recipient: String,
+        subject: String,
+        body: String,
+        items: Vec<InvSlotId>,
+    ) {
+        self.send_msg(ClientGeneral::MailAction(MailAction::Send {
+            recipient,
+            subject,
+            body,
+            items,
+        }));
+    }
+
+    pub fn read_mail(&mut self, id: MailId) {
+        self.send_msg(ClientGeneral::MailAction(MailAction::Read(id)));
+    }
+
+    /// Moves the items attached to a mail into the inventory
+    pub fn claim_mail(&mut self, id: MailId) {
+        self.send_msg(ClientGeneral::MailAction(MailAction::Claim(id)));
+    }
+
+    pub fn delete_mail(&mut self, id: MailId) {
+        self.send_msg(ClientGeneral::MailAction(MailAction::Delete(id)));
+    }
+
     pub fn is_dead(&self) -> bool { self.current::<comp::Health>().map_or(false, |h| h.is_dead) }
 
     pub fn split_swap_slots(&mut self, a: comp::slot::Slot, b: comp::slot::Slot) {
         match (a, b) {
+            (Slot::Bank(_), _) | (_, Slot::Bank(_)) => self.send_msg(ClientGeneral::ControlEvent(
+                ControlEvent::InventoryEvent(InventoryEvent::BankSplitSwap(a, b)),
+            )),
             (Slot::Equip(equip), slot) | (slot, Slot::Equip(equip)) => self.control_action(
                 ControlAction::InventoryAction(InventoryAction::Swap(equip, slot)),
             ),
@@ -727,6 +810,7 @@ impl Client {
             Slot::Inventory(inv) => self.send_msg(ClientGeneral::ControlEvent(
                 ControlEvent::InventoryEvent(InventoryEvent::SplitDrop(inv)),
             )),
+            Slot::Bank(_) => {},
         }
     }
 
@@ -762,6 +846,12 @@ impl Client {
 
     pub fn server_info(&self) -> &ServerInfo { &self.server_info }
 
+    /// The public key the server proved to own when connecting, which can be
+    /// remembered to recognize the server the next time
+    pub fn server_identity(&self) -> Option<[u8; 32]> {
+        self.participant.as_ref().map(|p| p.remote_identity())
+    }
+
     pub fn world_data(&self) -> &WorldData { &self.world_data }
 
     pub fn recipe_book(&self) -> &RecipeBook { &self.recipe_book }
@@ -835,6 +925,10 @@ impl Client {
 
     pub fn pending_trade(&self) -> &Option<(TradeId, PendingTrade)> { &self.pending_trade }
 
+    pub fn mailbox(&self) -> &[Mail] { &self.mailbox }
+
+    pub fn bank(&self) -> Option<&comp::Bank> { self.bank.as_ref() }
+
     pub fn send_invite(&mut self, invitee: Uid, kind: InviteKind) {
         self.send_msg(ClientGeneral::ControlEvent(ControlEvent::InitiateInvite(
             invitee, kind,
@@ -1108,6 +1202,21 @@ impl Client {
         )));
     }
 
+    /// Opens the bank of the current character at the bank sprite at `pos`
+    pub fn open_bank(&mut self, pos: Vec3<i32>) {
+        self.send_msg(ClientGeneral::ControlEvent(ControlEvent::InventoryEvent(
+            InventoryEvent::OpenBank(pos),
+        )));
+    }
+
+    pub fn close_bank(&mut self) {
+        if self.bank.take().is_some() {
+            self.send_msg(ClientGeneral::ControlEvent(ControlEvent::InventoryEvent(
+                InventoryEvent::CloseBank,
+            )));
+        }
+    }
+
     /// Execute a single client tick, handle input and update the game state by
     /// the given duration.
     pub fn tick(
@@ -1370,9 +1479,9 @@ impl Client {
                     );
                 }
             },
-            ServerGeneral::PlayerListUpdate(PlayerListUpdate::Admin(uid, admin)) => {
+            ServerGeneral::PlayerListUpdate(PlayerListUpdate::Admin(uid, admin_role)) => {
                 if let Some(player_info) = self.player_list.get_mut(&uid) {
-                    player_info.is_admin = admin;
+                    player_info.admin_role = admin_role;
                 } else {
                     warn!(
                         "Received msg to update admin status of uid {}, but they were not in the \
@@ -1473,6 +1582,15 @@ impl Client {
                     .ecs_mut()
                     .apply_comp_sync_package(comp_sync_package);
             },
+            ServerGeneral::PhysicsDelta(package) => match self.physics_delta.decode(&package) {
+                Ok(updates) => {
+                    self.state
+                        .ecs_mut()
+                        .apply_physics_updates::<msg::EcsCompPacket>(updates);
+                    self.send_msg_err(ClientGeneral::AckPhysicsDelta(package.tick))?;
+                },
+                Err(e) => warn!(?e, "Couldn't decode physics delta"),
+            },
             ServerGeneral::CreateEntity(entity_package) => {
                 self.state.ecs_mut().apply_entity_package(entity_package);
             },
@@ -1602,6 +1720,8 @@ impl Client {
             // Cleanup for when the client goes back to the `presence = None`
             ServerGeneral::ExitInGameSuccess => {
                 self.presence = None;
+                self.mailbox.clear();
+                self.bank = None;
                 self.clean_state();
             },
             ServerGeneral::InventoryUpdate(inventory, event) => {
@@ -1643,6 +1763,18 @@ impl Client {
                     frontend_events.push(Event::TradeComplete { result, trade })
                 }
             },
+            ServerGeneral::Mail(update) => match update {
+                MailUpdate::Mailbox(mailbox) => self.mailbox = mailbox,
+                MailUpdate::Sent => frontend_events.push(Event::MailSent),
+                MailUpdate::NewMail => frontend_events.push(Event::NewMail),
+                MailUpdate::Error(error) => frontend_events.push(Event::MailError(error)),
+            },
+            ServerGeneral::BankUpdate(bank) => {
+                if self.bank.is_none() && bank.is_some() {
+                    frontend_events.push(Event::BankOpened);
+                }
+                self.bank = bank;
+            },
             ServerGeneral::SiteEconomy(economy) => {
                 if let Some(rich) = self.sites_mut().get_mut(&economy.id) {
                     rich.economy = Some(economy);
@@ -1783,6 +1915,8 @@ impl Client {
             }
         }
 
+        self.reconnect_if_silent();
+
         let mut handles_msg = 0;
 
         let runtime = Arc::clone(&self.runtime);
@@ -1803,6 +1937,46 @@ impl Client {
         Ok(frontend_events)
     }
 
+    /// Our connection might be broken without us noticing, e.g. because our IP
+    /// address changed. As the server keeps our session till `client_timeout`,
+    /// a new connection is attached to it when the server stays silent for a
+    /// while.
+    fn reconnect_if_silent(&mut self) {
+        const RECONNECT_AFTER_REL_TO_TIMEOUT_FRACTION: f64 = 0.25;
+        let reconnect_after =
+            self.client_timeout.as_secs() as f64 * RECONNECT_AFTER_REL_TO_TIMEOUT_FRACTION;
+        let time = self.state.get_time();
+        if self.reconnect.is_none()
+            && time - self.last_server_pong > reconnect_after
+            && time - self.last_reconnect > reconnect_after
+        {
+            // Singleplayer can't lose its connection
+            let participant = self
+                .participant
+                .as_ref()
+                .filter(|p| !matches!(p.remote_address(), ProtocolAddr::Mpsc(_)));
+            if let Some(participant) = participant {
+                debug!("Server is silent, attaching a new connection");
+                let network = Arc::clone(&self.network);
+                let remote_pid = participant.remote_pid();
+                let addr = participant.remote_address().clone();
+                self.reconnect = Some(Box::pin(async move {
+                    network.reconnect(remote_pid, addr).await
+                }));
+                self.last_reconnect = time;
+            }
+        }
+
+        if let Some(reconnect) = &mut self.reconnect {
+            match reconnect.as_mut().now_or_never() {
+                Some(Ok(())) => debug!("Attached a new connection to the server"),
+                Some(Err(e)) => warn!(?e, "Failed to reconnect to the server"),
+                None => return,
+            }
+            self.reconnect = None;
+        }
+    }
+
     pub fn entity(&self) -> EcsEntity {
         self.state
             .ecs()
@@ -1821,6 +1995,14 @@ impl Client {
 
     pub fn get_ping_ms(&self) -> f64 { self.last_ping_delta * 1000.0 }
 
+    /// Link statistics of the channel currently used to talk to the server,
+    /// e.g. its round trip time as measured by the network layer
+    pub fn get_link_stats(&self) -> Option<Arc<ChannelStats>> {
+        self.participant
+            .as_ref()
+            .and_then(|p| p.channel_stats().pop())
+    }
+
     pub fn get_ping_ms_rolling_avg(&self) -> f64 {
         let mut total_weight = 0.;
         let pings = self.ping_deltas.len() as f64;
@@ -1860,8 +2042,11 @@ impl Client {
             .collect()
     }
 
-    /// Return true if this client is an admin on the server
-    pub fn is_admin(&self) -> bool {
+    /// Return true if this client has any role on the server
+    pub fn is_admin(&self) -> bool { self.admin_role().is_some() }
+
+    /// Return the role this client has on the server, if any
+    pub fn admin_role(&self) -> Option<comp::AdminRole> {
         let client_uid = self
             .state
             .read_component_copied::<Uid>(self.entity())
@@ -1869,7 +2054,7 @@ impl Client {
 
         self.player_list
             .get(&client_uid)
-            .map_or(false, |info| info.is_admin)
+            .and_then(|info| info.admin_role)
     }
 
     /// Clean client ECS state
@@ -1914,9 +2099,10 @@ impl Client {
             self.player_list
                 .get(uid)
                 .map_or("<?>".to_string(), |player_info| {
-                    if player_info.is_admin {
+                    if let Some(admin_role) = player_info.admin_role {
                         format!(
-                            "ADMIN - {}",
+                            "{} - {}",
+                            admin_role.name().to_uppercase(),
                             self.personalize_alias(*uid, player_info.player_alias.clone())
                         )
                     } else {
@@ -2183,6 +2369,7 @@ mod tests {
             ConnectionArgs::IpAndPort(vec![socket]),
             view_distance,
             runtime2,
+            None,
         ));
 
         let _ = veloren_client.map(|mut client| {
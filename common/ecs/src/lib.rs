@@ -1,7 +1,7 @@
 mod metrics;
 mod system;
 
-pub use metrics::{PhysicsMetrics, SysMetrics};
+pub use metrics::{PhysicsMetrics, PluginStats, SysMetrics};
 pub use system::{
     dispatch, gen_stats, run_now, CpuTimeStats, CpuTimeline, Job, Origin, ParMode, Phase, System,
 };
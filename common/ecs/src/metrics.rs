@@ -4,6 +4,23 @@ use std::{collections::HashMap, sync::Mutex};
 #[derive(Default)]
 pub struct SysMetrics {
     pub stats: Mutex<HashMap<String, CpuTimeline>>,
+    /// Stats of the last tick of each plugin by name
+    pub plugins: Mutex<HashMap<String, PluginStats>>,
+}
+
+/// What a plugin did in a tick
+#[derive(Clone, Debug, Default)]
+pub struct PluginStats {
+    /// Number of events the plugin handled
+    pub calls: u64,
+    /// Time in ns the plugin spent handling events
+    pub length_ns: u64,
+    /// Number of wasm instructions the plugin executed
+    pub fuel: u64,
+    /// Whether the plugin is skipped for exceeding its budget
+    pub throttled: bool,
+    /// Whether the plugin was disabled for exceeding its budget too often
+    pub disabled: bool,
 }
 
 #[derive(Default)]
@@ -0,0 +1,126 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use common::{
+    comp::{Ori, Pos, Vel},
+    uid::Uid,
+};
+use vek::*;
+use veloren_common_net::{
+    msg::EcsCompPacket,
+    sync::{CompSyncPackage, PhysicsDeltaEncoder, PhysicsUpdate},
+};
+
+/// Ticks until the client's acknowledgement reaches the server
+const ACK_LATENCY: u64 = 3;
+const TICKS: u64 = 300;
+
+struct Scenario {
+    name: &'static str,
+    entities: u64,
+    /// Every n-th entity moves, the others stand still
+    moving_every: u64,
+    speed: f32,
+}
+
+const SCENARIOS: [Scenario; 2] = [
+    // Everyone is running around in a small area
+    Scenario {
+        name: "fight",
+        entities: 100,
+        moving_every: 1,
+        speed: 8.0,
+    },
+    // Lots of npcs, most of them idle, some walking through the streets
+    Scenario {
+        name: "town",
+        entities: 400,
+        moving_every: 5,
+        speed: 2.0,
+    },
+];
+
+fn updates(scenario: &Scenario, tick: u64) -> Vec<PhysicsUpdate> {
+    (0..scenario.entities)
+        .map(|uid| {
+            let t = tick as f32 / 30.0;
+            let phase = uid as f32;
+            let home = Vec3::new((uid % 20) as f32 * 4.0, (uid / 20) as f32 * 4.0, 200.0);
+            let (pos, vel, ori) = if uid % scenario.moving_every == 0 {
+                let dir = Vec2::new((t + phase).cos(), (t + phase).sin());
+                let vel = Vec3::from(dir * scenario.speed);
+                let pos = home + Vec3::from(dir.yx() * scenario.speed);
+                (pos, vel, Ori::default().yawed_left(t + phase))
+            } else {
+                (home, Vec3::zero(), Ori::default().yawed_left(phase))
+            };
+            PhysicsUpdate {
+                uid: Uid(uid),
+                pos: Some(Pos(pos)),
+                vel: Some(Vel(vel)),
+                ori: Some(ori),
+            }
+        })
+        .collect()
+}
+
+fn comp_sync_package(updates: Vec<PhysicsUpdate>) -> CompSyncPackage<EcsCompPacket> {
+    let mut package = CompSyncPackage::new();
+    for update in updates {
+        if let Some(pos) = update.pos {
+            package.comp_modified(update.uid, pos);
+        }
+        if let Some(vel) = update.vel {
+            package.comp_modified(update.uid, vel);
+        }
+        if let Some(ori) = update.ori {
+            package.comp_modified(update.uid, ori);
+        }
+    }
+    package
+}
+
+/// Average bytes per tick of the full and the delta encoded updates
+fn bytes_per_tick(scenario: &Scenario) -> (u64, u64) {
+    let mut encoder = PhysicsDeltaEncoder::default();
+    let (mut full, mut delta) = (0, 0);
+    for tick in 0..TICKS {
+        full += bincode::serialized_size(&comp_sync_package(updates(scenario, tick))).unwrap();
+        let package = encoder.encode(tick, updates(scenario, tick));
+        delta += bincode::serialized_size(&package).unwrap();
+        if let Some(acked) = tick.checked_sub(ACK_LATENCY) {
+            encoder.ack(acked);
+        }
+    }
+    (full / TICKS, delta / TICKS)
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    for scenario in &SCENARIOS {
+        let (full, delta) = bytes_per_tick(scenario);
+        println!(
+            "sync {}: {} entities, {} bytes/tick before, {} bytes/tick delta encoded",
+            scenario.name, scenario.entities, full, delta
+        );
+    }
+
+    for scenario in &SCENARIOS {
+        let updates = updates(scenario, 1);
+        c.bench_function(&format!("sync: comp sync {}", scenario.name), |b| {
+            b.iter(|| {
+                let package = comp_sync_package(black_box(updates.clone()));
+                black_box(bincode::serialize(&package).unwrap());
+            })
+        });
+        c.bench_function(&format!("sync: delta encode {}", scenario.name), |b| {
+            let mut encoder = PhysicsDeltaEncoder::default();
+            encoder.encode(0, updates.clone());
+            encoder.ack(0);
+            b.iter(|| {
+                let package = encoder.encode(1, black_box(updates.clone()));
+                black_box(bincode::serialize(&package).unwrap());
+            })
+        });
+    }
+}
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -3,6 +3,7 @@ use common::{
     character::CharacterId,
     comp,
     comp::{Skill, SkillGroupKind},
+    mail::MailAction,
     terrain::block::Block,
 };
 use serde::{Deserialize, Serialize};
@@ -70,10 +71,13 @@ pub enum ClientGeneral {
         vel: comp::Vel,
         ori: comp::Ori,
     },
+    /// The `PhysicsDelta` of this tick was applied
+    AckPhysicsDelta(u64),
     UnlockSkill(Skill),
     RefundSkill(Skill),
     UnlockSkillGroup(SkillGroupKind),
     RequestSiteInfo(SiteId),
+    MailAction(MailAction),
     //Only in Game, via terrain stream
     TerrainChunkRequest {
         key: Vec2<i32>,
@@ -113,10 +117,12 @@ impl ClientMsg {
                         | ClientGeneral::PlaceBlock(_, _)
                         | ClientGeneral::ExitInGame
                         | ClientGeneral::PlayerPhysics { .. }
+                        | ClientGeneral::AckPhysicsDelta(_)
                         | ClientGeneral::TerrainChunkRequest { .. }
                         | ClientGeneral::UnlockSkill(_)
                         | ClientGeneral::RefundSkill(_)
                         | ClientGeneral::RequestSiteInfo(_)
+                        | ClientGeneral::MailAction(_)
                         | ClientGeneral::UnlockSkillGroup(_) => {
                             c_type == ClientType::Game && presence.is_some()
                         },
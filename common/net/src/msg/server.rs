@@ -4,6 +4,7 @@ use authc::AuthClientError;
 use common::{
     character::{self, CharacterItem},
     comp::{self, invite::InviteKind, item::MaterialStatManifest},
+    mail::MailUpdate,
     outcome::Outcome,
     recipe::RecipeBook,
     resources::TimeOfDay,
@@ -120,6 +121,8 @@ pub enum ServerGeneral {
     TimeOfDay(TimeOfDay),
     EntitySync(sync::EntitySyncPackage),
     CompSync(sync::CompSyncPackage<EcsCompPacket>),
+    /// `Pos`, `Vel` and `Ori` updates, the client acknowledges every one
+    PhysicsDelta(sync::PhysicsDeltaPackage),
     CreateEntity(sync::EntityPackage<EcsCompPacket>),
     DeleteEntity(Uid),
     Disconnect(DisconnectReason),
@@ -129,6 +132,10 @@ pub enum ServerGeneral {
     FinishedTrade(TradeResult),
     /// Economic information about sites
     SiteEconomy(EconomyInfo),
+    Mail(MailUpdate),
+    /// The contents of the bank the client has open, or `None` once it has
+    /// been closed
+    BankUpdate(Option<comp::Bank>),
 }
 
 impl ServerGeneral {
@@ -151,14 +158,14 @@ pub enum PlayerListUpdate {
     Add(Uid, PlayerInfo),
     SelectedCharacter(Uid, CharacterInfo),
     LevelChange(Uid, u32),
-    Admin(Uid, bool),
+    Admin(Uid, Option<comp::AdminRole>),
     Remove(Uid),
     Alias(Uid, String),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlayerInfo {
-    pub is_admin: bool,
+    pub admin_role: Option<comp::AdminRole>,
     pub is_online: bool,
     pub player_alias: String,
     pub character: Option<CharacterInfo>,
@@ -238,7 +245,9 @@ impl ServerMsg {
                         | ServerGeneral::Knockback(_)
                         | ServerGeneral::UpdatePendingTrade(_, _)
                         | ServerGeneral::FinishedTrade(_)
-                        | ServerGeneral::SiteEconomy(_) => {
+                        | ServerGeneral::SiteEconomy(_)
+                        | ServerGeneral::Mail(_)
+                        | ServerGeneral::BankUpdate(_) => {
                             c_type == ClientType::Game && presence.is_some()
                         },
                         // Always possible
@@ -249,6 +258,7 @@ impl ServerMsg {
                         | ServerGeneral::TimeOfDay(_)
                         | ServerGeneral::EntitySync(_)
                         | ServerGeneral::CompSync(_)
+                        | ServerGeneral::PhysicsDelta(_)
                         | ServerGeneral::CreateEntity(_)
                         | ServerGeneral::DeleteEntity(_)
                         | ServerGeneral::Disconnect(_)
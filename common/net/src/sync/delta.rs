@@ -0,0 +1,428 @@
+//! Delta compression of the physics components (`Pos`, `Vel` and `Ori`),
+//! which make up most of the traffic of [`CompSyncPackage`]s.
+//!
+//! The server keeps a [`PhysicsDeltaEncoder`] per client and the client a
+//! [`PhysicsDeltaDecoder`]. Every package is encoded against the last state
+//! the client acknowledged, so both sides know the base. Values are quantized
+//! and only what changed since the base is sent:
+//! - `Pos` and `Vel` in 1/64 units, as zigzag varints of the difference
+//! - `Ori` as the smallest three components of the quaternion, in 32 bits
+//! - a byte of flags per entity with what is included and what changed
+//!
+//! [`CompSyncPackage`]: super::CompSyncPackage
+use common::{
+    comp::{Ori, Pos, Vel},
+    uid::Uid,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    f32::consts::SQRT_2,
+};
+use vek::*;
+
+/// Units per block and per block/s
+const POS_SCALE: f32 = 64.0;
+const VEL_SCALE: f32 = 64.0;
+const ORI_BITS: u32 = 10;
+const ORI_MAX: f32 = ((1 << ORI_BITS) - 1) as f32;
+/// Entities that weren't sent for this many ticks are forgotten by both
+/// sides, the next update of them is sent in full
+const EXPIRE_TICKS: u64 = 30 * 60;
+/// Snapshots the encoder keeps while waiting for an ack, older ones can't be
+/// used as base anymore
+const MAX_UNACKED: usize = 128;
+
+const HAS_POS: u8 = 1 << 0;
+const HAS_VEL: u8 = 1 << 1;
+const HAS_ORI: u8 = 1 << 2;
+const POS_CHANGED: u8 = 1 << 3;
+const VEL_CHANGED: u8 = 1 << 4;
+const ORI_CHANGED: u8 = 1 << 5;
+
+/// New physics components of an entity. Components that are `None` keep
+/// their previous value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PhysicsUpdate {
+    pub uid: Uid,
+    pub pos: Option<Pos>,
+    pub vel: Option<Vel>,
+    pub ori: Option<Ori>,
+}
+
+/// Physics updates of one tick, delta encoded against the state of the tick
+/// `base`, or against nothing if `None`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PhysicsDeltaPackage {
+    pub tick: u64,
+    pub base: Option<u64>,
+    data: Vec<u8>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeltaError {
+    /// The state of this tick isn't known (anymore)
+    UnknownBase(u64),
+    Truncated,
+}
+
+/// Quantized components of an entity as both sides know them
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Entry {
+    pos: Option<Vec3<i32>>,
+    vel: Option<Vec3<i32>>,
+    ori: Option<u32>,
+    last_sent: u64,
+}
+
+type Snapshot = HashMap<u64, Entry>;
+
+/// Server side, one per client
+#[derive(Debug, Default)]
+pub struct PhysicsDeltaEncoder {
+    /// sent but not yet acknowledged, oldest first
+    sent: VecDeque<(u64, Snapshot)>,
+    acked: Option<(u64, Snapshot)>,
+}
+
+/// Client side
+#[derive(Debug, Default)]
+pub struct PhysicsDeltaDecoder {
+    /// decoded states the server might use as base, oldest first
+    states: VecDeque<(u64, Snapshot)>,
+}
+
+impl PhysicsDeltaEncoder {
+    pub fn encode(&mut self, tick: u64, mut updates: Vec<PhysicsUpdate>) -> PhysicsDeltaPackage {
+        let (base, mut state) = match &self.acked {
+            Some((base, snapshot)) => (Some(*base), expired(snapshot, tick)),
+            None => (None, Snapshot::new()),
+        };
+        updates.sort_unstable_by_key(|u| u.uid.0);
+
+        let mut data = Vec::with_capacity(updates.len() * 4);
+        let mut last_uid = 0;
+        for update in updates {
+            let uid: u64 = update.uid.into();
+            let old = state.get(&uid).copied().unwrap_or_default();
+            let pos = update.pos.map(|p| quantize(p.0, POS_SCALE));
+            let vel = update.vel.map(|v| quantize(v.0, VEL_SCALE));
+            let ori = update.ori.map(quantize_ori);
+
+            let mut flags = 0;
+            for &(has, changed, included, differs) in [
+                (
+                    HAS_POS,
+                    POS_CHANGED,
+                    pos.is_some(),
+                    pos.is_some() && pos != old.pos,
+                ),
+                (
+                    HAS_VEL,
+                    VEL_CHANGED,
+                    vel.is_some(),
+                    vel.is_some() && vel != old.vel,
+                ),
+                (
+                    HAS_ORI,
+                    ORI_CHANGED,
+                    ori.is_some(),
+                    ori.is_some() && ori != old.ori,
+                ),
+            ]
+            .iter()
+            {
+                if included {
+                    flags |= has;
+                }
+                if differs {
+                    flags |= changed;
+                }
+            }
+
+            write_varint(&mut data, uid.wrapping_sub(last_uid));
+            last_uid = uid;
+            data.push(flags);
+            if flags & POS_CHANGED != 0 {
+                write_vec(&mut data, pos.unwrap(), old.pos.unwrap_or_default());
+            }
+            if flags & VEL_CHANGED != 0 {
+                write_vec(&mut data, vel.unwrap(), old.vel.unwrap_or_default());
+            }
+            if flags & ORI_CHANGED != 0 {
+                data.extend_from_slice(&ori.unwrap().to_le_bytes());
+            }
+
+            state.insert(uid, Entry {
+                pos: pos.or(old.pos),
+                vel: vel.or(old.vel),
+                ori: ori.or(old.ori),
+                last_sent: tick,
+            });
+        }
+
+        if self.sent.len() >= MAX_UNACKED {
+            self.sent.pop_front();
+        }
+        self.sent.push_back((tick, state));
+        PhysicsDeltaPackage { tick, base, data }
+    }
+
+    /// The client decoded the package of `tick`, following packages are
+    /// encoded against it. Acks of unknown or outdated ticks are ignored.
+    pub fn ack(&mut self, tick: u64) {
+        if let Some(i) = self.sent.iter().position(|(t, _)| *t == tick) {
+            self.acked = self.sent.drain(..=i).next_back();
+        }
+    }
+}
+
+impl PhysicsDeltaDecoder {
+    /// On success the client should acknowledge `package.tick`
+    pub fn decode(
+        &mut self,
+        package: &PhysicsDeltaPackage,
+    ) -> Result<Vec<PhysicsUpdate>, DeltaError> {
+        let mut state = match package.base {
+            Some(base) => self
+                .states
+                .iter()
+                .find(|(t, _)| *t == base)
+                .map(|(_, snapshot)| expired(snapshot, package.tick))
+                .ok_or(DeltaError::UnknownBase(base))?,
+            None => Snapshot::new(),
+        };
+
+        let mut data = &package.data[..];
+        let mut updates = Vec::new();
+        let mut last_uid = 0u64;
+        while !data.is_empty() {
+            let uid = last_uid.wrapping_add(read_varint(&mut data)?);
+            last_uid = uid;
+            let flags = read_u8(&mut data)?;
+            let old = state.get(&uid).copied().unwrap_or_default();
+
+            let pos = if flags & POS_CHANGED != 0 {
+                Some(read_vec(&mut data, old.pos.unwrap_or_default())?)
+            } else {
+                old.pos.filter(|_| flags & HAS_POS != 0)
+            };
+            let vel = if flags & VEL_CHANGED != 0 {
+                Some(read_vec(&mut data, old.vel.unwrap_or_default())?)
+            } else {
+                old.vel.filter(|_| flags & HAS_VEL != 0)
+            };
+            let ori = if flags & ORI_CHANGED != 0 {
+                let mut bytes = [0; 4];
+                for b in bytes.iter_mut() {
+                    *b = read_u8(&mut data)?;
+                }
+                Some(u32::from_le_bytes(bytes))
+            } else {
+                old.ori.filter(|_| flags & HAS_ORI != 0)
+            };
+
+            updates.push(PhysicsUpdate {
+                uid: Uid(uid),
+                pos: pos.map(|p| Pos(dequantize(p, POS_SCALE))),
+                vel: vel.map(|v| Vel(dequantize(v, VEL_SCALE))),
+                ori: ori.map(dequantize_ori),
+            });
+            state.insert(uid, Entry {
+                pos: pos.or(old.pos),
+                vel: vel.or(old.vel),
+                ori: ori.or(old.ori),
+                last_sent: package.tick,
+            });
+        }
+
+        // the server only moves its base forward
+        if let Some(base) = package.base {
+            self.states.retain(|(t, _)| *t >= base);
+        }
+        if self.states.len() > MAX_UNACKED {
+            self.states.pop_front();
+        }
+        self.states.push_back((package.tick, state));
+        Ok(updates)
+    }
+}
+
+fn expired(snapshot: &Snapshot, tick: u64) -> Snapshot {
+    snapshot
+        .iter()
+        .filter(|(_, e)| e.last_sent + EXPIRE_TICKS >= tick)
+        .map(|(uid, e)| (*uid, *e))
+        .collect()
+}
+
+fn quantize(v: Vec3<f32>, scale: f32) -> Vec3<i32> { v.map(|e| (e * scale).round() as i32) }
+
+fn dequantize(v: Vec3<i32>, scale: f32) -> Vec3<f32> { v.map(|e| e as f32 / scale) }
+
+/// The largest component of a normalized quaternion can be computed from the
+/// others, which are within ±1/√2. So its index takes 2 bits and the other
+/// three `ORI_BITS` each.
+fn quantize_ori(ori: Ori) -> u32 {
+    let q = ori.to_quat();
+    let comps = [q.x, q.y, q.z, q.w];
+    let largest = (0..4)
+        .max_by(|a, b| comps[*a].abs().partial_cmp(&comps[*b].abs()).unwrap())
+        .unwrap_or(3);
+    // q and -q are the same rotation, so the largest is made positive
+    let sign = if comps[largest] < 0.0 { -1.0 } else { 1.0 };
+    (0..4)
+        .filter(|i| *i != largest)
+        .fold(largest as u32, |packed, i| {
+            let unit = (comps[i] * sign * SQRT_2 + 1.0) * 0.5;
+            (packed << ORI_BITS) | (unit * ORI_MAX).round().clamp(0.0, ORI_MAX) as u32
+        })
+}
+
+fn dequantize_ori(packed: u32) -> Ori {
+    let largest = (packed >> (3 * ORI_BITS)) as usize;
+    let mut comps = [0.0; 4];
+    let mut shift = 3 * ORI_BITS;
+    for (i, c) in comps.iter_mut().enumerate() {
+        if i != largest {
+            shift -= ORI_BITS;
+            let unit = ((packed >> shift) & ((1 << ORI_BITS) - 1)) as f32 / ORI_MAX;
+            *c = (unit * 2.0 - 1.0) / SQRT_2;
+        }
+    }
+    comps[largest] = (1.0 - comps.iter().map(|c| c * c).sum::<f32>())
+        .max(0.0)
+        .sqrt();
+    Ori::new(Quaternion::from_xyzw(comps[0], comps[1], comps[2], comps[3]).normalized())
+}
+
+fn write_varint(data: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        data.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    data.push(v as u8);
+}
+
+fn read_varint(data: &mut &[u8]) -> Result<u64, DeltaError> {
+    let mut v = 0;
+    for shift in (0..64).step_by(7) {
+        let byte = read_u8(data)?;
+        v |= u64::from(byte & 0x7F) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(v);
+        }
+    }
+    Err(DeltaError::Truncated)
+}
+
+fn read_u8(data: &mut &[u8]) -> Result<u8, DeltaError> {
+    let (byte, rest) = data.split_first().ok_or(DeltaError::Truncated)?;
+    *data = rest;
+    Ok(*byte)
+}
+
+fn write_vec(data: &mut Vec<u8>, v: Vec3<i32>, base: Vec3<i32>) {
+    for (e, b) in v.into_array().iter().zip(base.into_array().iter()) {
+        let d = e.wrapping_sub(*b);
+        write_varint(data, ((d << 1) ^ (d >> 31)) as u32 as u64);
+    }
+}
+
+fn read_vec(data: &mut &[u8], base: Vec3<i32>) -> Result<Vec3<i32>, DeltaError> {
+    let mut v = base;
+    for e in v.iter_mut() {
+        let z = read_varint(data)? as u32;
+        *e = e.wrapping_add((z >> 1) as i32 ^ -((z & 1) as i32));
+    }
+    Ok(v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(uid: u64, pos: Vec3<f32>) -> PhysicsUpdate {
+        PhysicsUpdate {
+            uid: Uid(uid),
+            pos: Some(Pos(pos)),
+            vel: Some(Vel(Vec3::new(1.0, 0.0, -2.5))),
+            ori: Some(Ori::default().yawed_left(uid as f32)),
+        }
+    }
+
+    fn assert_close(a: &PhysicsUpdate, b: &PhysicsUpdate) {
+        assert_eq!(a.uid, b.uid);
+        let (a_pos, b_pos) = (a.pos.unwrap().0, b.pos.unwrap().0);
+        assert!(a_pos.distance(b_pos) < 0.02, "{} {}", a_pos, b_pos);
+        assert_eq!(a.vel, b.vel);
+        let (a_ori, b_ori) = (a.ori.unwrap().look_vec(), b.ori.unwrap().look_vec());
+        assert!(a_ori.distance(b_ori) < 0.01, "{} {}", a_ori, b_ori);
+    }
+
+    #[test]
+    fn round_trip_with_acks() {
+        let mut encoder = PhysicsDeltaEncoder::default();
+        let mut decoder = PhysicsDeltaDecoder::default();
+        for tick in 0..20 {
+            let updates = (0..10)
+                .map(|uid| update(uid * 1000, Vec3::new(100.0 + tick as f32 * 0.3, 5.0, 60.0)))
+                .collect::<Vec<_>>();
+            let package = encoder.encode(tick, updates.clone());
+            assert_eq!(package.base, tick.checked_sub(1));
+            let decoded = decoder.decode(&package).unwrap();
+            assert_eq!(decoded.len(), updates.len());
+            decoded
+                .iter()
+                .zip(updates.iter())
+                .for_each(|(a, b)| assert_close(a, b));
+            encoder.ack(tick);
+        }
+    }
+
+    #[test]
+    fn unchanged_entities_are_small() {
+        let mut encoder = PhysicsDeltaEncoder::default();
+        let mut decoder = PhysicsDeltaDecoder::default();
+        let updates = (0..100)
+            .map(|uid| update(uid, Vec3::new(1000.0, 2000.0, 300.0)))
+            .collect::<Vec<_>>();
+        let full = encoder.encode(1, updates.clone());
+        decoder.decode(&full).unwrap();
+        encoder.ack(1);
+        let delta = encoder.encode(2, updates.clone());
+        // uid difference and flags
+        assert_eq!(delta.data.len(), 200);
+        let decoded = decoder.decode(&delta).unwrap();
+        decoded
+            .iter()
+            .zip(updates.iter())
+            .for_each(|(a, b)| assert_close(a, b));
+    }
+
+    #[test]
+    fn lost_acks_keep_the_old_base() {
+        let mut encoder = PhysicsDeltaEncoder::default();
+        let mut decoder = PhysicsDeltaDecoder::default();
+        decoder
+            .decode(&encoder.encode(1, vec![update(7, Vec3::zero())]))
+            .unwrap();
+        encoder.ack(1);
+        // the acks of 2 and 3 are still in flight
+        let p2 = encoder.encode(2, vec![update(7, Vec3::one())]);
+        let p3 = encoder.encode(3, vec![update(8, Vec3::one())]);
+        assert_eq!((p2.base, p3.base), (Some(1), Some(1)));
+        decoder.decode(&p2).unwrap();
+        let decoded = decoder.decode(&p3).unwrap();
+        assert_close(&decoded[0], &update(8, Vec3::one()));
+        encoder.ack(3);
+        encoder.ack(2);
+        let p4 = encoder.encode(4, vec![update(7, Vec3::one())]);
+        assert_eq!(p4.base, Some(3));
+        assert_close(&decoder.decode(&p4).unwrap()[0], &update(7, Vec3::one()));
+        assert_eq!(
+            PhysicsDeltaDecoder::default().decode(&p4),
+            Err(DeltaError::UnknownBase(3))
+        );
+    }
+}
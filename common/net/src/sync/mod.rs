@@ -1,5 +1,6 @@
 // Note: Currently only one-way sync is supported until a usecase for two-way
 // sync arises
+mod delta;
 pub mod interpolation;
 mod packet;
 mod sync_ext;
@@ -7,6 +8,9 @@ mod track;
 
 // Reexports
 pub use common::uid::{Uid, UidAllocator};
+pub use delta::{
+    DeltaError, PhysicsDeltaDecoder, PhysicsDeltaEncoder, PhysicsDeltaPackage, PhysicsUpdate,
+};
 pub use packet::{
     handle_insert, handle_interp_insert, handle_interp_modify, handle_interp_remove, handle_modify,
     handle_remove, CompPacket, CompSyncPackage, EntityPackage, EntitySyncPackage,
@@ -1,17 +1,19 @@
 use super::{
+    delta::PhysicsUpdate,
     packet::{
         CompPacket, CompSyncPackage, CompUpdateKind, EntityPackage, EntitySyncPackage, StatePackage,
     },
     track::UpdateTracker,
 };
 use common::{
+    comp::{Ori, Pos, Vel},
     resources::PlayerEntity,
     uid::{Uid, UidAllocator},
 };
 use specs::{
     saveload::{MarkedBuilder, MarkerAllocator},
     world::Builder,
-    WorldExt,
+    Component, ReadStorage, WorldExt,
 };
 use tracing::error;
 
@@ -34,6 +36,9 @@ pub trait WorldSyncExt {
     fn apply_state_package<P: CompPacket>(&mut self, state_package: StatePackage<P>);
     fn apply_entity_sync_package(&mut self, package: EntitySyncPackage);
     fn apply_comp_sync_package<P: CompPacket>(&mut self, package: CompSyncPackage<P>);
+    fn apply_physics_updates<P>(&mut self, updates: Vec<PhysicsUpdate>)
+    where
+        P: CompPacket + From<Pos> + From<Vel> + From<Ori>;
 }
 
 impl WorldSyncExt for specs::World {
@@ -153,9 +158,47 @@ impl WorldSyncExt for specs::World {
             }
         });
     }
+
+    /// Decoded physics deltas are applied like a [`CompSyncPackage`], so they
+    /// are interpolated the same way
+    fn apply_physics_updates<P>(&mut self, updates: Vec<PhysicsUpdate>)
+    where
+        P: CompPacket + From<Pos> + From<Vel> + From<Ori>,
+    {
+        let mut package = CompSyncPackage::<P>::new();
+        {
+            let positions = self.read_storage::<Pos>();
+            let velocities = self.read_storage::<Vel>();
+            let orientations = self.read_storage::<Ori>();
+            for PhysicsUpdate { uid, pos, vel, ori } in updates {
+                if let Some(entity) = self.entity_from_uid(uid.into()) {
+                    push_update(&mut package, &positions, entity, uid, pos);
+                    push_update(&mut package, &velocities, entity, uid, vel);
+                    push_update(&mut package, &orientations, entity, uid, ori);
+                }
+            }
+        }
+        self.apply_comp_sync_package(package);
+    }
 }
 
 // Private utilities
+fn push_update<P: CompPacket + From<C>, C: Component>(
+    package: &mut CompSyncPackage<P>,
+    storage: &ReadStorage<C>,
+    entity: specs::Entity,
+    uid: Uid,
+    comp: Option<C>,
+) {
+    if let Some(comp) = comp {
+        if storage.contains(entity) {
+            package.comp_modified(uid, comp);
+        } else {
+            package.comp_inserted(uid, comp);
+        }
+    }
+}
+
 fn create_entity_with_uid(specs_world: &mut specs::World, entity_uid: u64) -> specs::Entity {
     let existing_entity = specs_world
         .read_resource::<UidAllocator>()
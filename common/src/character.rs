@@ -24,3 +24,53 @@ pub struct CharacterItem {
     pub body: comp::Body,
     pub inventory: Inventory,
 }
+
+/// The state of a character that changes during play (as opposed to its
+/// progression), restored when the character is loaded again so that logging
+/// out doesn't heal the character or move it back to its waypoint.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Debug, Default)]
+pub struct PersistedState {
+    pub health: Option<u32>,
+    pub energy: Option<u32>,
+    pub buffs: Vec<comp::Buff>,
+    pub position: Option<comp::Pos>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl PersistedState {
+    pub fn from_components(
+        health: Option<&comp::Health>,
+        energy: Option<&comp::Energy>,
+        buffs: Option<&comp::Buffs>,
+        pos: Option<&comp::Pos>,
+    ) -> Self {
+        // Dead characters come back at their waypoint, as if they had respawned
+        if health.map_or(false, |h| h.is_dead) {
+            return Self::default();
+        }
+
+        Self {
+            health: health.map(|h| h.current()),
+            energy: energy.map(|e| e.current()),
+            buffs: buffs
+                .map(|buffs| {
+                    buffs
+                        .buffs
+                        .values()
+                        .filter(|buff| {
+                            !buff.cat_ids.iter().any(|cat| {
+                                matches!(
+                                    cat,
+                                    comp::BuffCategory::Natural | comp::BuffCategory::FromAura(_)
+                                )
+                            })
+                        })
+                        .cloned()
+                        .collect()
+                })
+                .unwrap_or_default(),
+            position: pos.copied(),
+        }
+    }
+}
@@ -14,20 +14,21 @@ pub struct ChatCommandData {
     pub args: Vec<ArgumentSpec>,
     /// A one-line message that explains what the command does
     pub description: &'static str,
-    /// Whether the command requires administrator permissions.
-    pub needs_admin: IsAdminOnly,
+    /// The least privileged role that may use the command by default, `None`
+    /// if everyone can use it. Servers can override this per command.
+    pub needs_role: Option<comp::AdminRole>,
 }
 
 impl ChatCommandData {
     pub fn new(
         args: Vec<ArgumentSpec>,
         description: &'static str,
-        needs_admin: IsAdminOnly,
+        needs_role: Option<comp::AdminRole>,
     ) -> Self {
         Self {
             args,
             description,
-            needs_admin,
+            needs_role,
         }
     }
 }
@@ -69,8 +70,10 @@ pub enum ChatCommand {
     Motd,
     Object,
     Players,
+    Plugin,
     Region,
     RemoveLights,
+    ResetTerrain,
     Safezone,
     Say,
     SetMotd,
@@ -82,6 +85,7 @@ pub enum ChatCommand {
     Tp,
     Unban,
     Version,
+    Violations,
     Waypoint,
     Whitelist,
     World,
@@ -123,8 +127,10 @@ pub static CHAT_COMMANDS: &[ChatCommand] = &[
     ChatCommand::Motd,
     ChatCommand::Object,
     ChatCommand::Players,
+    ChatCommand::Plugin,
     ChatCommand::Region,
     ChatCommand::RemoveLights,
+    ChatCommand::ResetTerrain,
     ChatCommand::Safezone,
     ChatCommand::Say,
     ChatCommand::SetMotd,
@@ -136,6 +142,7 @@ pub static CHAT_COMMANDS: &[ChatCommand] = &[
     ChatCommand::Tp,
     ChatCommand::Unban,
     ChatCommand::Version,
+    ChatCommand::Violations,
     ChatCommand::Waypoint,
     ChatCommand::Whitelist,
     ChatCommand::World,
@@ -151,6 +158,16 @@ lazy_static! {
         ('w', ChatCommand::World),
     ].iter().cloned().collect();
 
+    static ref ROLES: Vec<String> = vec!["moderator", "admin", "owner"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+    static ref PLUGIN_ACTIONS: Vec<String> = vec!["list", "load", "unload", "reload"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
     static ref ALIGNMENTS: Vec<String> = vec!["wild", "enemy", "npc", "pet"]
         .iter()
         .map(|s| s.to_string())
@@ -214,46 +231,51 @@ lazy_static! {
 
 impl ChatCommand {
     pub fn data(&self) -> ChatCommandData {
+        use comp::AdminRole::*;
         use ArgumentSpec::*;
-        use IsAdminOnly::*;
         use Requirement::*;
         let cmd = ChatCommandData::new;
         match self {
             ChatCommand::Adminify => cmd(
-                vec![PlayerName(Required)],
-                "Temporarily gives a player admin permissions or removes them",
-                Admin,
+                vec![PlayerName(Required), Enum("role", ROLES.clone(), Optional)],
+                "Temporarily gives a player a role (admin by default) or removes it",
+                Some(Admin),
             ),
             ChatCommand::Airship => cmd(
                 vec![Float("destination_degrees_ccw_of_east", 90.0, Optional)],
                 "Spawns an airship",
-                Admin,
+                Some(Admin),
             ),
-            ChatCommand::Alias => cmd(vec![Any("name", Required)], "Change your alias", NoAdmin),
+            ChatCommand::Alias => cmd(vec![Any("name", Required)], "Change your alias", None),
             ChatCommand::Ban => cmd(
-                vec![Any("username", Required), Message(Optional)],
-                "Ban a player with a given username",
-                Admin,
+                vec![
+                    Any("username", Required),
+                    Any("duration", Optional),
+                    Message(Optional),
+                ],
+                "Ban a player with a given username, for a duration like 30m, 12h or 7d if given, \
+                 permanently otherwise",
+                Some(Moderator),
             ),
-            ChatCommand::Build => cmd(vec![], "Toggles build mode on and off", Admin),
-            ChatCommand::Campfire => cmd(vec![], "Spawns a campfire", Admin),
-            ChatCommand::Debug => cmd(vec![], "Place all debug items into your pack.", Admin),
+            ChatCommand::Build => cmd(vec![], "Toggles build mode on and off", Some(Admin)),
+            ChatCommand::Campfire => cmd(vec![], "Spawns a campfire", Some(Admin)),
+            ChatCommand::Debug => cmd(vec![], "Place all debug items into your pack.", Some(Admin)),
             ChatCommand::DebugColumn => cmd(
                 vec![Integer("x", 15000, Required), Integer("y", 15000, Required)],
                 "Prints some debug information about a column",
-                NoAdmin,
+                None,
             ),
-            ChatCommand::DropAll => cmd(vec![], "Drops all your items on the ground", Admin),
-            ChatCommand::Dummy => cmd(vec![], "Spawns a training dummy", Admin),
+            ChatCommand::DropAll => cmd(vec![], "Drops all your items on the ground", Some(Admin)),
+            ChatCommand::Dummy => cmd(vec![], "Spawns a training dummy", Some(Admin)),
             ChatCommand::Explosion => cmd(
                 vec![Float("radius", 5.0, Required)],
                 "Explodes the ground around you",
-                Admin,
+                Some(Admin),
             ),
             ChatCommand::Faction => cmd(
                 vec![Message(Optional)],
                 "Send messages to your faction",
-                NoAdmin,
+                None,
             ),
             ChatCommand::GiveItem => cmd(
                 vec![
@@ -261,7 +283,7 @@ impl ChatCommand {
                     Integer("num", 1, Optional),
                 ],
                 "Give yourself some items",
-                Admin,
+                Some(Admin),
             ),
             ChatCommand::Goto => cmd(
                 vec![
@@ -270,44 +292,40 @@ impl ChatCommand {
                     Float("z", 0.0, Required),
                 ],
                 "Teleport to a position",
-                Admin,
-            ),
-            ChatCommand::Group => cmd(
-                vec![Message(Optional)],
-                "Send messages to your group",
-                NoAdmin,
+                Some(Admin),
             ),
+            ChatCommand::Group => cmd(vec![Message(Optional)], "Send messages to your group", None),
             ChatCommand::GroupInvite => cmd(
                 vec![PlayerName(Required)],
                 "Invite a player to join a group",
-                NoAdmin,
+                None,
             ),
             ChatCommand::GroupKick => cmd(
                 vec![PlayerName(Required)],
                 "Remove a player from a group",
-                NoAdmin,
+                None,
             ),
-            ChatCommand::GroupLeave => cmd(vec![], "Leave the current group", NoAdmin),
+            ChatCommand::GroupLeave => cmd(vec![], "Leave the current group", None),
             ChatCommand::GroupPromote => cmd(
                 vec![PlayerName(Required)],
                 "Promote a player to group leader",
-                NoAdmin,
+                None,
             ),
             ChatCommand::Health => cmd(
                 vec![Integer("hp", 100, Required)],
                 "Set your current health",
-                Admin,
+                Some(Admin),
             ),
             ChatCommand::Help => ChatCommandData::new(
                 vec![Command(Optional)],
                 "Display information about commands",
-                NoAdmin,
+                None,
             ),
-            ChatCommand::Home => cmd(vec![], "Return to the home town", NoAdmin),
+            ChatCommand::Home => cmd(vec![], "Return to the home town", None),
             ChatCommand::JoinFaction => ChatCommandData::new(
                 vec![Any("faction", Optional)],
                 "Join/leave the specified faction",
-                NoAdmin,
+                None,
             ),
             ChatCommand::Jump => cmd(
                 vec![
@@ -316,15 +334,15 @@ impl ChatCommand {
                     Float("z", 0.0, Required),
                 ],
                 "Offset your current position",
-                Admin,
+                Some(Admin),
             ),
             ChatCommand::Kick => cmd(
                 vec![Any("username", Required), Message(Optional)],
                 "Kick a player with a given username",
-                Admin,
+                Some(Moderator),
             ),
-            ChatCommand::Kill => cmd(vec![], "Kill yourself", NoAdmin),
-            ChatCommand::KillNpcs => cmd(vec![], "Kill the NPCs", Admin),
+            ChatCommand::Kill => cmd(vec![], "Kill yourself", None),
+            ChatCommand::KillNpcs => cmd(vec![], "Kill the NPCs", Some(Admin)),
             ChatCommand::Lantern => cmd(
                 vec![
                     Float("strength", 5.0, Required),
@@ -333,7 +351,7 @@ impl ChatCommand {
                     Float("b", 1.0, Optional),
                 ],
                 "Change your lantern's strength and color",
-                Admin,
+                Some(Admin),
             ),
             ChatCommand::Light => cmd(
                 vec![
@@ -346,59 +364,71 @@ impl ChatCommand {
                     Float("strength", 5.0, Optional),
                 ],
                 "Spawn entity with light",
-                Admin,
+                Some(Admin),
             ),
             ChatCommand::MakeBlock => cmd(
                 vec![Enum("block", BLOCK_KINDS.clone(), Required)],
                 "Make a block at your location",
-                Admin,
+                Some(Admin),
             ),
             ChatCommand::MakeSprite => cmd(
                 vec![Enum("sprite", SPRITE_KINDS.clone(), Required)],
                 "Make a sprite at your location",
-                Admin,
-            ),
-            ChatCommand::Motd => cmd(
-                vec![Message(Optional)],
-                "View the server description",
-                NoAdmin,
+                Some(Admin),
             ),
+            ChatCommand::Motd => cmd(vec![Message(Optional)], "View the server description", None),
             ChatCommand::Object => cmd(
                 vec![Enum("object", OBJECTS.clone(), Required)],
                 "Spawn an object",
-                Admin,
+                Some(Admin),
+            ),
+            ChatCommand::Players => cmd(vec![], "Lists players currently online", None),
+            ChatCommand::Plugin => cmd(
+                vec![
+                    Enum("action", PLUGIN_ACTIONS.clone(), Required),
+                    Any("name", Optional),
+                ],
+                "List, load, unload or reload plugins",
+                Some(Admin),
             ),
-            ChatCommand::Players => cmd(vec![], "Lists players currently online", NoAdmin),
             ChatCommand::RemoveLights => cmd(
                 vec![Float("radius", 20.0, Optional)],
                 "Removes all lights spawned by players",
-                Admin,
+                Some(Admin),
             ),
             ChatCommand::Region => cmd(
                 vec![Message(Optional)],
                 "Send messages to everyone in your region of the world",
-                NoAdmin,
+                None,
+            ),
+            ChatCommand::ResetTerrain => cmd(
+                vec![Integer("radius", 1, Optional)],
+                "Discards terrain modifications in chunks around you (at most 8 chunks away) and \
+                 regenerates them",
+                Some(Admin),
             ),
             ChatCommand::Safezone => cmd(
                 vec![Float("range", 100.0, Optional)],
                 "Creates a safezone",
-                Admin,
+                Some(Admin),
             ),
             ChatCommand::Say => cmd(
                 vec![Message(Optional)],
                 "Send messages to everyone within shouting distance",
-                NoAdmin,
+                None,
+            ),
+            ChatCommand::SetMotd => cmd(
+                vec![Message(Optional)],
+                "Set the server description",
+                Some(Admin),
             ),
-            ChatCommand::SetMotd => {
-                cmd(vec![Message(Optional)], "Set the server description", Admin)
-            },
             ChatCommand::SkillPoint => cmd(
                 vec![
                     Enum("skill tree", SKILL_TREES.clone(), Required),
                     Integer("amount", 1, Optional),
                 ],
                 "Give yourself skill points for a particular skill tree",
-                Admin,
+                Some(Admin),
             ),
             ChatCommand::Spawn => cmd(
                 vec![
@@ -408,46 +438,53 @@ impl ChatCommand {
                     Boolean("ai", "true".to_string(), Optional),
                 ],
                 "Spawn a test entity",
-                Admin,
+                Some(Admin),
             ),
             ChatCommand::Sudo => cmd(
                 vec![PlayerName(Required), SubCommand],
                 "Run command as if you were another player",
-                Admin,
+                Some(Admin),
             ),
             ChatCommand::Tell => cmd(
                 vec![PlayerName(Required), Message(Optional)],
                 "Send a message to another player",
-                NoAdmin,
+                None,
             ),
             ChatCommand::Time => cmd(
                 vec![Enum("time", TIMES.clone(), Optional)],
                 "Set the time of day",
-                Admin,
+                Some(Admin),
             ),
             ChatCommand::Tp => cmd(
                 vec![PlayerName(Optional)],
                 "Teleport to another player",
-                Admin,
+                Some(Moderator),
             ),
             ChatCommand::Unban => cmd(
                 vec![Any("username", Required)],
                 "Remove the ban for the given username",
-                Admin,
+                Some(Moderator),
+            ),
+            ChatCommand::Version => cmd(vec![], "Prints server version", None),
+            ChatCommand::Violations => cmd(
+                vec![],
+                "Lists players whose movement was rejected by the server",
+                Some(Moderator),
+            ),
+            ChatCommand::Waypoint => cmd(
+                vec![],
+                "Set your waypoint to your current position",
+                Some(Admin),
             ),
-            ChatCommand::Version => cmd(vec![], "Prints server version", NoAdmin),
-            ChatCommand::Waypoint => {
-                cmd(vec![], "Set your waypoint to your current position", Admin)
-            },
             ChatCommand::Whitelist => cmd(
                 vec![Any("add/remove", Required), Any("username", Required)],
                 "Adds/removes username to whitelist",
-                Admin,
+                Some(Admin),
             ),
             ChatCommand::World => cmd(
                 vec![Message(Optional)],
                 "Send messages to everyone on the server",
-                NoAdmin,
+                None,
             ),
         }
     }
@@ -489,8 +526,10 @@ impl ChatCommand {
             ChatCommand::Motd => "motd",
             ChatCommand::Object => "object",
             ChatCommand::Players => "players",
+            ChatCommand::Plugin => "plugin",
             ChatCommand::Region => "region",
             ChatCommand::RemoveLights => "remove_lights",
+            ChatCommand::ResetTerrain => "reset_terrain",
             ChatCommand::Safezone => "safezone",
             ChatCommand::Say => "say",
             ChatCommand::SetMotd => "set_motd",
@@ -502,6 +541,7 @@ impl ChatCommand {
             ChatCommand::Tp => "tp",
             ChatCommand::Unban => "unban",
             ChatCommand::Version => "version",
+            ChatCommand::Violations => "violations",
             ChatCommand::Waypoint => "waypoint",
             ChatCommand::Whitelist => "whitelist",
             ChatCommand::World => "world",
@@ -518,9 +558,9 @@ impl ChatCommand {
         format!("{}: {}", usage, data.description)
     }
 
-    /// A boolean that is used to check whether the command requires
-    /// administrator permissions or not.
-    pub fn needs_admin(&self) -> bool { IsAdminOnly::Admin == self.data().needs_admin }
+    /// The least privileged role that may use the command by default, `None`
+    /// if everyone can use it.
+    pub fn needs_role(&self) -> Option<comp::AdminRole> { self.data().needs_role }
 
     /// Returns a format string for parsing arguments with scan_fmt
     pub fn arg_fmt(&self) -> String {
@@ -578,12 +618,6 @@ impl FromStr for ChatCommand {
     }
 }
 
-#[derive(Eq, PartialEq, Debug)]
-pub enum IsAdminOnly {
-    Admin,
-    NoAdmin,
-}
-
 #[derive(Eq, PartialEq, Debug)]
 pub enum Requirement {
     Required,
@@ -1,8 +1,47 @@
-use specs::{Component, NullStorage};
+use serde::{Deserialize, Serialize};
+use specs::Component;
+use specs_idvs::IdvStorage;
+use std::{fmt, str::FromStr};
 
-#[derive(Clone, Copy, Default)]
-pub struct Admin;
+/// Roles that can be given to players on a server, ordered from the least to
+/// the most privileged. Every role can do everything the roles below it can.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum AdminRole {
+    Moderator,
+    Admin,
+    Owner,
+}
+
+impl AdminRole {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AdminRole::Moderator => "moderator",
+            AdminRole::Admin => "admin",
+            AdminRole::Owner => "owner",
+        }
+    }
+}
+
+impl fmt::Display for AdminRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "{}", self.name()) }
+}
+
+impl FromStr for AdminRole {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s.to_lowercase().as_str() {
+            "moderator" => Ok(AdminRole::Moderator),
+            "admin" => Ok(AdminRole::Admin),
+            "owner" => Ok(AdminRole::Owner),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Admin(pub AdminRole);
 
 impl Component for Admin {
-    type Storage = NullStorage<Self>;
+    type Storage = IdvStorage<Self>;
 }
@@ -23,6 +23,12 @@ pub enum InventoryEvent {
     Drop(InvSlotId),
     SplitDrop(InvSlotId),
     CraftRecipe(String),
+    OpenBank(Vec3<i32>),
+    CloseBank,
+    /// Move items between two slots, at least one of which is in the bank
+    BankSwap(Slot, Slot),
+    /// Split a stack from or into the bank
+    BankSplitSwap(Slot, Slot),
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
@@ -42,6 +48,8 @@ pub enum InventoryManip {
     Drop(Slot),
     SplitDrop(Slot),
     CraftRecipe(String),
+    OpenBank(Vec3<i32>),
+    CloseBank,
 }
 
 impl From<InventoryAction> for InventoryManip {
@@ -68,6 +76,10 @@ impl From<InventoryEvent> for InventoryManip {
             InventoryEvent::Drop(inv) => Self::Drop(Slot::Inventory(inv)),
             InventoryEvent::SplitDrop(inv) => Self::SplitDrop(Slot::Inventory(inv)),
             InventoryEvent::CraftRecipe(recipe) => Self::CraftRecipe(recipe),
+            InventoryEvent::OpenBank(pos) => Self::OpenBank(pos),
+            InventoryEvent::CloseBank => Self::CloseBank,
+            InventoryEvent::BankSwap(a, b) => Self::Swap(a, b),
+            InventoryEvent::BankSplitSwap(slot, target) => Self::SplitSwap(slot, target),
         }
     }
 }
@@ -0,0 +1,167 @@
+use serde::{Deserialize, Serialize};
+use specs::Component;
+use specs_idvs::IdvStorage;
+use std::{convert::TryFrom, mem};
+
+use crate::comp::{
+    inventory::{slot::BankSlotId, InvSlot},
+    item::MaterialStatManifest,
+    Item,
+};
+
+/// The number of slots in a bank
+pub const BANK_SLOTS: usize = 48;
+
+/// Storage for items beyond a character's inventory, which can only be
+/// accessed at the banks found in towns. Unlike the inventory, the size of a
+/// bank is fixed.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Bank {
+    slots: Vec<InvSlot>,
+}
+
+impl Default for Bank {
+    fn default() -> Self {
+        Self {
+            slots: (0..BANK_SLOTS).map(|_| None).collect(),
+        }
+    }
+}
+
+impl Bank {
+    pub fn capacity(&self) -> usize { self.slots.len() }
+
+    pub fn populated_slots(&self) -> usize { self.slots.iter().filter(|s| s.is_some()).count() }
+
+    /// An iterator of all bank slots and their position
+    pub fn slots_with_id(&self) -> impl Iterator<Item = (BankSlotId, &InvSlot)> {
+        self.slots
+            .iter()
+            .enumerate()
+            .map(|(i, slot)| (BankSlotId::new(u16::try_from(i).unwrap()), slot))
+    }
+
+    pub fn slot(&self, bank_slot_id: BankSlotId) -> Option<&InvSlot> {
+        self.slots.get(bank_slot_id.slot_idx())
+    }
+
+    pub fn slot_mut(&mut self, bank_slot_id: BankSlotId) -> Option<&mut InvSlot> {
+        self.slots.get_mut(bank_slot_id.slot_idx())
+    }
+
+    /// Get content of a slot
+    pub fn get(&self, bank_slot_id: BankSlotId) -> Option<&Item> {
+        self.slot(bank_slot_id).and_then(Option::as_ref)
+    }
+
+    /// Replaces the item in a slot of the bank. Returns the old item or the
+    /// same item again if that slot was not found.
+    pub fn insert_at(
+        &mut self,
+        bank_slot_id: BankSlotId,
+        item: Item,
+    ) -> Result<Option<Item>, Item> {
+        match self.slot_mut(bank_slot_id) {
+            Some(slot) => Ok(mem::replace(slot, Some(item))),
+            None => Err(item),
+        }
+    }
+
+    /// Remove an item from the slot
+    pub fn remove(&mut self, bank_slot_id: BankSlotId) -> Option<Item> {
+        self.slot_mut(bank_slot_id).and_then(Option::take)
+    }
+
+    /// Takes half of the items from a slot in the bank
+    pub fn take_half(
+        &mut self,
+        bank_slot_id: BankSlotId,
+        msm: &MaterialStatManifest,
+    ) -> Option<Item> {
+        if let Some(Some(item)) = self.slot_mut(bank_slot_id) {
+            if item.is_stackable() && item.amount() > 1 {
+                let mut return_item = item.duplicate(msm);
+                let returning_amount = item.amount() / 2;
+                item.decrease_amount(returning_amount).ok()?;
+                return_item
+                    .set_amount(returning_amount)
+                    .expect("Items duplicated from a stackable item must be stackable.");
+                Some(return_item)
+            } else {
+                self.remove(bank_slot_id)
+            }
+        } else {
+            None
+        }
+    }
+
+    /// Moves the item at `src` onto `dst` with [`stack_or_swap`]
+    pub fn move_slot(&mut self, src: BankSlotId, dst: BankSlotId) {
+        if src == dst || self.slot(src).is_none() || self.slot(dst).is_none() {
+            return;
+        }
+        let mut src_slot = mem::take(self.slot_mut(src).unwrap());
+        stack_or_swap(&mut src_slot, self.slot_mut(dst).unwrap());
+        *self.slot_mut(src).unwrap() = src_slot;
+    }
+}
+
+/// Merges the stack of items in `src` into the one in `dst` if they are
+/// compatible and stackable, otherwise swaps the contents of the two slots
+pub fn stack_or_swap(src: &mut InvSlot, dst: &mut InvSlot) {
+    if let (Some(src_item), Some(dst_item)) = (src.as_ref(), dst.as_mut()) {
+        // Same checks as `Inventory::merge_stack_into`
+        if src_item == dst_item
+            && src_item.is_stackable()
+            && dst_item.increase_amount(src_item.amount()).is_ok()
+        {
+            *src = None;
+            return;
+        }
+    }
+    mem::swap(src, dst);
+}
+
+impl Component for Bank {
+    type Storage = IdvStorage<Self>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moving_within_bank_stacks_or_swaps() {
+        let mut bank = Bank::default();
+        let (a, b, c) = (BankSlotId::new(0), BankSlotId::new(1), BankSlotId::new(2));
+        bank.insert_at(a, Item::new_from_asset_expect("common.items.utility.coins"))
+            .unwrap();
+        bank.insert_at(b, Item::new_from_asset_expect("common.items.utility.coins"))
+            .unwrap();
+        bank.insert_at(
+            c,
+            Item::new_from_asset_expect("common.items.weapons.sword.steel-8"),
+        )
+        .unwrap();
+
+        bank.move_slot(a, b);
+        assert!(bank.get(a).is_none());
+        assert_eq!(bank.get(b).map(Item::amount), Some(2));
+
+        bank.move_slot(c, a);
+        assert!(bank.get(c).is_none());
+        assert!(bank.get(a).is_some());
+        assert_eq!(bank.populated_slots(), 2);
+    }
+
+    #[test]
+    fn moving_to_missing_slot_keeps_item() {
+        let mut bank = Bank::default();
+        let a = BankSlotId::new(0);
+        bank.insert_at(a, Item::new_from_asset_expect("common.items.utility.coins"))
+            .unwrap();
+
+        bank.move_slot(a, BankSlotId::new(BANK_SLOTS as u16));
+        assert!(bank.get(a).is_some());
+    }
+}
@@ -20,6 +20,7 @@ use crate::{
     LoadoutBuilder,
 };
 
+pub mod bank;
 pub mod item;
 pub mod loadout;
 pub mod loadout_builder;
@@ -571,6 +572,12 @@ impl Inventory {
                 self.loadout.swap_slots(slot_a, slot_b);
                 Vec::new()
             },
+            // Bank slots aren't part of the inventory, swapping with them requires access to
+            // the bank as well
+            (Slot::Bank(_), _) | (_, Slot::Bank(_)) => {
+                warn!("swap called with bank slot(s)");
+                Vec::new()
+            },
         }
     }
 
@@ -18,6 +18,7 @@ pub enum SlotError {
 pub enum Slot {
     Inventory(InvSlotId),
     Equip(EquipSlot),
+    Bank(BankSlotId),
 }
 
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
@@ -61,6 +62,18 @@ impl Ord for InvSlotId {
     fn cmp(&self, other: &InvSlotId) -> Ordering { self.idx().cmp(&other.idx()) }
 }
 
+/// A slot in a character's bank
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BankSlotId {
+    slot_idx: u16,
+}
+
+impl BankSlotId {
+    pub const fn new(slot_idx: u16) -> Self { Self { slot_idx } }
+
+    pub fn slot_idx(&self) -> usize { usize::from(self.slot_idx) }
+}
+
 pub(super) enum SlotId {
     Inventory(usize),
     Loadout(LoadoutSlotId),
@@ -112,6 +125,7 @@ impl Slot {
         match (self, item_kind) {
             (Self::Inventory(_), _) => true,
             (Self::Equip(slot), item_kind) => slot.can_hold(item_kind),
+            (Self::Bank(_), _) => true,
         }
     }
 }
@@ -42,7 +42,7 @@ pub mod visual;
 #[cfg(not(target_arch = "wasm32"))]
 pub use self::{
     ability::{CharacterAbility, CharacterAbilityType},
-    admin::Admin,
+    admin::{Admin, AdminRole},
     agent::{Agent, Alignment},
     aura::{Aura, AuraChange, AuraKind, Auras},
     beam::{Beam, BeamSegment},
@@ -69,6 +69,7 @@ pub use self::{
     home_chunk::HomeChunk,
     inputs::CanBuild,
     inventory::{
+        bank::Bank,
         item,
         item::{Item, ItemConfig, ItemDrop},
         slot, Inventory, InventoryUpdate, InventoryUpdateEvent,
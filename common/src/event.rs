@@ -1,7 +1,9 @@
 use crate::{
-    character::CharacterId,
+    character::{CharacterId, PersistedState},
     comp,
+    mail::MailAction,
     rtsim::RtSimEntity,
+    terrain::Block,
     trade::{TradeAction, TradeId},
     uid::Uid,
     util::Dir,
@@ -103,6 +105,7 @@ pub enum ServerEvent {
             comp::Stats,
             comp::Inventory,
             Option<comp::Waypoint>,
+            PersistedState,
         ),
     },
     ExitIngame {
@@ -149,6 +152,21 @@ pub enum ServerEvent {
         entity: EcsEntity,
         id: SiteId,
     },
+    MailAction {
+        entity: EcsEntity,
+        action: MailAction,
+    },
+    /// A player that is allowed to build breaks the block at `pos`
+    BreakBlock {
+        entity: EcsEntity,
+        pos: Vec3<i32>,
+    },
+    /// A player that is allowed to build places `block` at `pos`
+    PlaceBlock {
+        entity: EcsEntity,
+        pos: Vec3<i32>,
+        block: Block,
+    },
 }
 
 pub struct EventBus<E> {
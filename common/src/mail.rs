@@ -0,0 +1,131 @@
+//! Mail that players can send to each other's characters, even when the
+//! recipient is offline. Mail is stored by the server and may carry items,
+//! which are removed from the sender's inventory when the mail is sent.
+
+use crate::comp::{inventory::slot::InvSlotId, Item};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+pub type MailId = i64;
+
+/// The maximum number of items that can be attached to a single mail
+pub const MAX_MAIL_ITEMS: usize = 8;
+pub const MAX_MAIL_SUBJECT_LENGTH: usize = 64;
+pub const MAX_MAIL_BODY_LENGTH: usize = 1024;
+/// The maximum number of mails a character's mailbox can hold. Further mail is
+/// refused until the recipient deletes some.
+pub const MAX_MAILBOX_SIZE: usize = 50;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Mail {
+    pub id: MailId,
+    /// Name of the character that sent the mail
+    pub sender: String,
+    pub subject: String,
+    pub body: String,
+    /// Time the mail was sent, in seconds since the unix epoch
+    pub sent_at: i64,
+    pub read: bool,
+    /// Items that haven't been claimed yet
+    pub items: Vec<Item>,
+}
+
+/// Clients submit `MailAction` to the server to manage the mailbox of their
+/// current character
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MailAction {
+    RequestMailbox,
+    Send {
+        /// Name of the receiving character
+        recipient: String,
+        subject: String,
+        body: String,
+        items: Vec<InvSlotId>,
+    },
+    Read(MailId),
+    Claim(MailId),
+    Delete(MailId),
+}
+
+/// Sent by the server in response to a `MailAction`, or when new mail arrives
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum MailUpdate {
+    Mailbox(Vec<Mail>),
+    Sent,
+    NewMail,
+    Error(MailError),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MailError {
+    RecipientNotFound,
+    /// There is more than one character with the recipient's name
+    RecipientAmbiguous,
+    RecipientMailboxFull,
+    SubjectTooLong,
+    BodyTooLong,
+    TooManyItems,
+    /// Items were attached that the sender doesn't have (anymore), or that
+    /// can't be sent
+    InvalidItems,
+    NotEnoughSpace,
+    /// The mail has items that have to be claimed first
+    HasItems,
+    MailNotFound,
+    /// The mailbox isn't available, e.g. because it is still being loaded
+    Unavailable,
+}
+
+impl fmt::Display for MailError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", match self {
+            Self::RecipientNotFound => "There is no character with that name",
+            Self::RecipientAmbiguous => "There are several characters with that name",
+            Self::RecipientMailboxFull => "The recipient's mailbox is full",
+            Self::SubjectTooLong => "The subject is too long",
+            Self::BodyTooLong => "The message is too long",
+            Self::TooManyItems => "Too many items attached",
+            Self::InvalidItems => "Some of the attached items can't be sent",
+            Self::NotEnoughSpace => "Not enough space in your inventory",
+            Self::HasItems => "Claim the attached items first",
+            Self::MailNotFound => "This mail doesn't exist anymore",
+            Self::Unavailable => "The mailbox is not available right now",
+        })
+    }
+}
+
+/// Checks the parts of an outgoing mail that don't depend on the state of the
+/// sender or recipient
+pub fn validate_mail(subject: &str, body: &str, item_count: usize) -> Result<(), MailError> {
+    if subject.chars().count() > MAX_MAIL_SUBJECT_LENGTH {
+        Err(MailError::SubjectTooLong)
+    } else if body.chars().count() > MAX_MAIL_BODY_LENGTH {
+        Err(MailError::BodyTooLong)
+    } else if item_count > MAX_MAIL_ITEMS {
+        Err(MailError::TooManyItems)
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mail_limits() {
+        assert_eq!(validate_mail("Hello", "There", MAX_MAIL_ITEMS), Ok(()));
+        assert_eq!(
+            validate_mail(&"a".repeat(MAX_MAIL_SUBJECT_LENGTH + 1), "", 0),
+            Err(MailError::SubjectTooLong)
+        );
+        assert_eq!(
+            validate_mail("", &"a".repeat(MAX_MAIL_BODY_LENGTH + 1), 0),
+            Err(MailError::BodyTooLong)
+        );
+        assert_eq!(
+            validate_mail("", "", MAX_MAIL_ITEMS + 1),
+            Err(MailError::TooManyItems)
+        );
+    }
+}
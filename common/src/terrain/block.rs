@@ -238,6 +238,10 @@ impl Block {
             .unwrap_or(false)
     }
 
+    /// Whether the block is a bank that players can access their bank at
+    #[inline]
+    pub fn is_bank(&self) -> bool { self.get_sprite() == Some(SpriteKind::Bank) }
+
     #[inline]
     pub fn is_opaque(&self) -> bool { self.kind().is_filled() }
 
@@ -143,6 +143,7 @@ make_case_elim!(
         RedAlgae = 0x74,
         UnderwaterVent = 0x75,
         Lantern = 0x76,
+        Bank = 0x77,
     }
 );
 
@@ -158,6 +159,7 @@ impl SpriteKind {
             SpriteKind::Pumpkin => 0.81,
             SpriteKind::Cabbage => 0.45,
             SpriteKind::Chest => 1.09,
+            SpriteKind::Bank => 1.09,
             SpriteKind::StreetLamp => 2.65,
             SpriteKind::Carrot => 0.18,
             SpriteKind::Radish => 0.18,
@@ -292,6 +294,7 @@ impl SpriteKind {
                 | SpriteKind::VialEmpty
                 | SpriteKind::FireBowlGround
                 | SpriteKind::Lantern
+                | SpriteKind::Bank
         )
     }
 }
@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+use common_ecs::PluginStats;
+
+/// Time a plugin may spend handling events in a single tick
+pub const TICK_BUDGET: Duration = Duration::from_millis(5);
+/// A plugin is disabled once it exceeded its budget this many times without
+/// enough ticks within the budget in between
+pub const MAX_OVERRUNS: u32 = 10;
+
+/// What changed for a plugin at the end of a tick
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BudgetOutcome {
+    Unchanged,
+    /// The plugin exceeded its budget and is skipped for this many ticks
+    Throttled(u32),
+    /// The plugin exceeded its budget too often and won't run anymore
+    Disabled,
+}
+
+/// Tracks the time a plugin spends each tick, it is throttled for longer and
+/// longer when it keeps exceeding the [`TICK_BUDGET`] and eventually disabled
+#[derive(Debug, Default)]
+pub struct PluginBudget {
+    used: Duration,
+    calls: u64,
+    out_of_fuel: bool,
+    overruns: u32,
+    throttled: u32,
+    disabled: bool,
+}
+
+impl PluginBudget {
+    pub fn can_run(&self) -> bool { !self.disabled && self.throttled == 0 }
+
+    pub fn is_disabled(&self) -> bool { self.disabled }
+
+    /// Records an event the plugin handled
+    pub fn record(&mut self, time: Duration, out_of_fuel: bool) {
+        self.used += time;
+        self.calls += 1;
+        self.out_of_fuel |= out_of_fuel;
+    }
+
+    /// Checks the time used in this tick against the budget and starts the
+    /// next tick
+    pub fn end_tick(&mut self, fuel: u64) -> (BudgetOutcome, PluginStats) {
+        let outcome = if self.disabled {
+            BudgetOutcome::Unchanged
+        } else if self.throttled > 0 {
+            self.throttled -= 1;
+            BudgetOutcome::Unchanged
+        } else if self.used > TICK_BUDGET || self.out_of_fuel {
+            self.overruns += 1;
+            if self.overruns >= MAX_OVERRUNS {
+                self.disabled = true;
+                BudgetOutcome::Disabled
+            } else {
+                self.throttled = 1 << self.overruns;
+                BudgetOutcome::Throttled(self.throttled)
+            }
+        } else {
+            self.overruns = self.overruns.saturating_sub(1);
+            BudgetOutcome::Unchanged
+        };
+        let stats = PluginStats {
+            calls: self.calls,
+            length_ns: self.used.as_nanos() as u64,
+            fuel,
+            throttled: self.throttled > 0,
+            disabled: self.disabled,
+        };
+        self.used = Duration::default();
+        self.calls = 0;
+        self.out_of_fuel = false;
+        (outcome, stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn throttle_then_disable() {
+        let mut budget = PluginBudget::default();
+        budget.record(TICK_BUDGET / 2, false);
+        assert_eq!(budget.end_tick(0).0, BudgetOutcome::Unchanged);
+        assert!(budget.can_run());
+
+        for overrun in 1..MAX_OVERRUNS {
+            budget.record(TICK_BUDGET * 2, false);
+            let (outcome, stats) = budget.end_tick(0);
+            assert_eq!(outcome, BudgetOutcome::Throttled(1 << overrun));
+            assert!(stats.throttled);
+            for _ in 0..(1 << overrun) {
+                assert!(!budget.can_run());
+                budget.end_tick(0);
+            }
+            assert!(budget.can_run());
+        }
+
+        budget.record(Duration::default(), true);
+        let (outcome, stats) = budget.end_tick(0);
+        assert_eq!(outcome, BudgetOutcome::Disabled);
+        assert!(stats.disabled);
+        assert!(!budget.can_run());
+        assert_eq!(budget.end_tick(0).0, BudgetOutcome::Unchanged);
+    }
+
+    #[test]
+    fn overruns_are_forgiven() {
+        let mut budget = PluginBudget::default();
+        budget.record(TICK_BUDGET * 2, false);
+        assert_eq!(budget.end_tick(0).0, BudgetOutcome::Throttled(2));
+        budget.end_tick(0);
+        budget.end_tick(0);
+        budget.end_tick(0);
+        budget.record(TICK_BUDGET * 2, false);
+        assert_eq!(budget.end_tick(0).0, BudgetOutcome::Throttled(2));
+    }
+}
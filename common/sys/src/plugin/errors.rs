@@ -1,5 +1,5 @@
 use bincode::ErrorKind;
-use wasmer::{ExportError, InstantiationError, RuntimeError};
+use wasmer::{CompileError, ExportError, InstantiationError, RuntimeError};
 
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
@@ -10,10 +10,16 @@ pub enum PluginError {
     NoSuchModule,
     Encoding(Box<ErrorKind>),
     PluginModuleError(String, String, PluginModuleError),
+    NoSuchPlugin(String),
+    AlreadyLoaded(String),
+    /// Plugins can only be loaded by name if they were loaded from a directory
+    NoPluginDir,
+    Watch(notify::Error),
 }
 
 #[derive(Debug)]
 pub enum PluginModuleError {
+    CompileError(CompileError),
     InstantiationError(InstantiationError),
     MemoryAllocation(MemoryAllocationError),
     MemoryUninit(ExportError),
@@ -21,6 +27,9 @@ pub enum PluginModuleError {
     RunFunction(RuntimeError),
     InvalidArgumentType(),
     Encoding(Box<ErrorKind>),
+    /// The module executed more than [`super::module::FUEL_PER_EVENT`]
+    /// instructions for a single event
+    OutOfFuel,
 }
 
 #[derive(Debug)]
@@ -1,24 +1,39 @@
-use std::sync::atomic::{AtomicPtr, AtomicU32, AtomicU64, Ordering};
+use std::sync::{
+    atomic::{AtomicPtr, AtomicU32, AtomicU64, Ordering},
+    Mutex,
+};
 
 use serde::{de::DeserializeOwned, Serialize};
 use specs::{
-    storage::GenericReadStorage, Component, Entities, Entity, Read, ReadStorage, WriteStorage,
+    storage::GenericReadStorage, Component, Entities, Entity, Read, ReadExpect, ReadStorage,
+    WriteStorage,
 };
 use wasmer::{Function, Memory, Value};
 
 use common::{
-    comp::{Health, Player},
+    comp::{Health, Inventory, Player, Pos},
+    resources::TimeOfDay,
+    terrain::TerrainGrid,
     uid::{Uid, UidAllocator},
 };
+use plugin_api::Action;
 
-use super::errors::{MemoryAllocationError, PluginModuleError};
+use super::{
+    errors::{MemoryAllocationError, PluginModuleError},
+    storage::PluginStorage,
+};
 
 pub struct EcsWorld<'a, 'b> {
     pub entities: &'b Entities<'a>,
     pub health: EcsComponentAccess<'a, 'b, Health>,
     pub uid: EcsComponentAccess<'a, 'b, Uid>,
     pub player: EcsComponentAccess<'a, 'b, Player>,
+    pub pos: EcsComponentAccess<'a, 'b, Pos>,
+    pub inventory: EcsComponentAccess<'a, 'b, Inventory>,
     pub uid_allocator: &'b Read<'a, UidAllocator>,
+    pub terrain: &'b ReadExpect<'a, TerrainGrid>,
+    pub time_of_day: &'b Read<'a, TimeOfDay>,
+    pub storage: &'b Read<'a, PluginStorage>,
 }
 
 pub enum EcsComponentAccess<'a, 'b, T: Component> {
@@ -59,15 +74,19 @@ impl<'a, 'b, T: Component> From<WriteStorage<'a, T>> for EcsComponentAccess<'a,
 //     Read(Read<'a, T>),
 // }
 
-/// This structure wraps the ECS pointer to ensure safety
+/// This structure wraps the ECS pointer to ensure safety. It also holds the
+/// actions a plugin emitted until the server applies them, as plugins can't
+/// write to the ECS themselves.
 pub struct EcsAccessManager {
     ecs_pointer: AtomicPtr<EcsWorld<'static, 'static>>,
+    actions: Mutex<Vec<Action>>,
 }
 
 impl Default for EcsAccessManager {
     fn default() -> Self {
         Self {
             ecs_pointer: AtomicPtr::new(std::ptr::null_mut()),
+            actions: Mutex::new(Vec::new()),
         }
     }
 }
@@ -102,6 +121,14 @@ impl EcsAccessManager {
         // ptr::as_ref will automatically check for null
         self.ecs_pointer.load(Ordering::Relaxed).as_ref()
     }
+
+    /// Queues actions to be applied by the server in the next tick
+    pub fn push_actions(&self, actions: impl IntoIterator<Item = Action>) {
+        self.actions.lock().unwrap().extend(actions);
+    }
+
+    /// Returns the queued actions in the order they were emitted
+    pub fn take_actions(&self) -> Vec<Action> { std::mem::take(&mut *self.actions.lock().unwrap()) }
 }
 
 pub struct MemoryManager {
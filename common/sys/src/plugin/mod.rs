@@ -1,26 +1,40 @@
+pub mod budget;
 pub mod errors;
 pub mod memory_manager;
 pub mod module;
+pub mod storage;
 pub mod wasm_env;
+pub mod watcher;
 
-use common::assets::ASSETS_PATH;
+use common::{assets::ASSETS_PATH, resources::GameMode};
+use common_ecs::SysMetrics;
 use serde::{Deserialize, Serialize};
 use std::{
     collections::{HashMap, HashSet},
     fs,
     io::Read,
     path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Instant,
 };
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
-use plugin_api::Event;
+use plugin_api::{
+    event::{PluginLoadEvent, PluginUnloadEvent},
+    Action, Event,
+};
 
 use self::{
-    errors::PluginError,
+    budget::{BudgetOutcome, PluginBudget},
+    errors::{PluginError, PluginModuleError},
     memory_manager::EcsWorld,
     module::{PluginModule, PreparedEventQuery},
+    watcher::PluginWatcher,
 };
 
+/// Extension of the files plugins are loaded from
+const PLUGIN_EXTENSION: &str = ".plugin.tar";
+
 use rayon::prelude::*;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -35,9 +49,18 @@ pub struct Plugin {
     data: PluginData,
     modules: Vec<PluginModule>,
     files: HashMap<PathBuf, Vec<u8>>,
+    budget: Arc<Mutex<PluginBudget>>,
+    /// The file the plugin was loaded from, used to reload it
+    path: Option<PathBuf>,
 }
 
 impl Plugin {
+    pub fn from_path(path: &Path) -> Result<Self, PluginError> {
+        let mut plugin = Self::from_reader(fs::File::open(path).map_err(PluginError::Io)?)?;
+        plugin.path = Some(path.to_owned());
+        Ok(plugin)
+    }
+
     pub fn from_reader<R: Read>(mut reader: R) -> Result<Self, PluginError> {
         let mut buf = Vec::new();
         reader.read_to_end(&mut buf).map_err(PluginError::Io)?;
@@ -78,6 +101,8 @@ impl Plugin {
             data,
             modules,
             files,
+            budget: Arc::new(Mutex::new(PluginBudget::default())),
+            path: None,
         })
     }
 
@@ -89,26 +114,111 @@ impl Plugin {
     where
         T: Event,
     {
-        self.modules
+        if !self.budget.lock().unwrap().can_run() {
+            return Ok(Vec::new());
+        }
+        let start = Instant::now();
+        let mut out_of_fuel = false;
+        let responses = self
+            .modules
             .iter()
-            .flat_map(|module| {
-                module.try_execute(ecs, event).map(|x| {
-                    x.map_err(|e| {
-                        PluginError::PluginModuleError(
-                            self.data.name.to_owned(),
-                            event.get_function_name().to_owned(),
-                            e,
-                        )
-                    })
-                })
+            .flat_map(|module| module.try_execute(ecs, event))
+            .filter_map(|response| match response {
+                // A module that runs out of fuel only loses its own response
+                Err(PluginModuleError::OutOfFuel) => {
+                    out_of_fuel = true;
+                    None
+                },
+                response => Some(response.map_err(|e| {
+                    PluginError::PluginModuleError(
+                        self.data.name.to_owned(),
+                        event.get_function_name().to_owned(),
+                        e,
+                    )
+                })),
             })
-            .collect::<Result<Vec<_>, _>>()
+            .collect::<Result<Vec<_>, _>>();
+        if out_of_fuel {
+            warn!(
+                "Plugin '{}' ran out of fuel in {}",
+                self.data.name,
+                event.get_function_name()
+            );
+        }
+        self.budget
+            .lock()
+            .unwrap()
+            .record(start.elapsed(), out_of_fuel);
+        responses
+    }
+
+    /// Applies the budget of this tick and returns what the plugin did
+    fn end_tick(&self) -> common_ecs::PluginStats {
+        let fuel = self
+            .modules
+            .iter()
+            .map(|module| module.take_fuel_used())
+            .sum();
+        let (outcome, stats) = self.budget.lock().unwrap().end_tick(fuel);
+        match outcome {
+            BudgetOutcome::Unchanged => {},
+            BudgetOutcome::Throttled(ticks) => warn!(
+                "Plugin '{}' exceeded its tick budget and is paused for {} ticks",
+                self.data.name, ticks
+            ),
+            BudgetOutcome::Disabled => error!(
+                "Plugin '{}' exceeded its tick budget too often and was disabled",
+                self.data.name
+            ),
+        }
+        stats
+    }
+
+    pub fn take_actions(&self) -> Vec<Action> {
+        self.modules
+            .iter()
+            .flat_map(|module| module.take_actions())
+            .collect()
+    }
+
+    pub fn name(&self) -> &str { &self.data.name }
+
+    pub fn module_count(&self) -> usize { self.modules.len() }
+
+    pub fn is_disabled(&self) -> bool { self.budget.lock().unwrap().is_disabled() }
+
+    fn execute_event<T>(&self, ecs: &EcsWorld, event: &T) -> Result<Vec<T::Response>, PluginError>
+    where
+        T: Event,
+    {
+        self.execute_prepared(ecs, &PreparedEventQuery::new(event)?)
+    }
+
+    /// Runs the `on_unload` event and frees the modules, returning the actions
+    /// the plugin emitted that weren't applied yet
+    fn unload(self, ecs: &EcsWorld, reloading: bool) -> Vec<Action> {
+        if let Err(e) = self.execute_event(ecs, &PluginUnloadEvent { reloading }) {
+            error!(
+                ?e,
+                "Failed to run unload event of plugin '{}'", self.data.name
+            );
+        }
+        let actions = self.take_actions();
+        for module in self.modules {
+            module.teardown();
+        }
+        actions
     }
 }
 
-#[derive(Clone, Default)]
+#[derive(Default)]
 pub struct PluginMgr {
     plugins: Vec<Plugin>,
+    /// The directory plugins are loaded from by name
+    dir: Option<PathBuf>,
+    /// Actions of unloaded plugins that weren't applied yet
+    unloaded_actions: Vec<Action>,
+    watcher: Option<PluginWatcher>,
 }
 
 impl PluginMgr {
@@ -148,8 +258,133 @@ impl PluginMgr {
         self.execute_prepared(ecs, &PreparedEventQuery::new(event)?)
     }
 
+    /// Applies the tick budget of every plugin and records their stats
+    pub fn end_tick(&self, metrics: &SysMetrics) {
+        let mut stats = metrics.plugins.lock().unwrap();
+        for plugin in &self.plugins {
+            stats.insert(plugin.data.name.clone(), plugin.end_tick());
+        }
+    }
+
+    /// Returns the actions emitted by all plugins that need to be applied
+    /// by the server
+    pub fn take_actions(&mut self) -> Vec<Action> {
+        std::mem::take(&mut self.unloaded_actions)
+            .into_iter()
+            .chain(self.plugins.iter().flat_map(|plugin| plugin.take_actions()))
+            .collect()
+    }
+
+    pub fn plugins(&self) -> impl Iterator<Item = &Plugin> { self.plugins.iter() }
+
+    /// Loads the plugin from the file `<name>.plugin.tar` in the plugin
+    /// directory
+    pub fn load(
+        &mut self,
+        ecs: &EcsWorld,
+        game_mode: GameMode,
+        name: &str,
+    ) -> Result<(), PluginError> {
+        let dir = self.dir.as_ref().ok_or(PluginError::NoPluginDir)?;
+        let plugin = Plugin::from_path(&dir.join(format!("{}{}", name, PLUGIN_EXTENSION)))?;
+        if self.plugins.iter().any(|p| p.data.name == plugin.data.name) {
+            return Err(PluginError::AlreadyLoaded(plugin.data.name));
+        }
+        plugin.execute_event(ecs, &PluginLoadEvent { game_mode })?;
+        info!(
+            "Loaded plugin '{}' with {} module(s)",
+            plugin.data.name,
+            plugin.modules.len()
+        );
+        self.plugins.push(plugin);
+        Ok(())
+    }
+
+    pub fn unload(&mut self, ecs: &EcsWorld, name: &str) -> Result<(), PluginError> {
+        let index = self.index_of(name)?;
+        let actions = self.plugins.remove(index).unload(ecs, false);
+        self.unloaded_actions.extend(actions);
+        info!("Unloaded plugin '{}'", name);
+        Ok(())
+    }
+
+    /// Unloads all plugins, e.g. when the game is shut down
+    pub fn unload_all(&mut self, ecs: &EcsWorld) {
+        for plugin in std::mem::take(&mut self.plugins) {
+            let actions = plugin.unload(ecs, false);
+            self.unloaded_actions.extend(actions);
+        }
+    }
+
+    /// Loads a plugin again from the file it was loaded from, the old version
+    /// is kept if the new one fails to load
+    pub fn reload(
+        &mut self,
+        ecs: &EcsWorld,
+        game_mode: GameMode,
+        name: &str,
+    ) -> Result<(), PluginError> {
+        let index = self.index_of(name)?;
+        let path = self.plugins[index]
+            .path
+            .clone()
+            .ok_or_else(|| PluginError::NoSuchPlugin(name.to_owned()))?;
+        let plugin = Plugin::from_path(&path)?;
+        let old = std::mem::replace(&mut self.plugins[index], plugin);
+        let actions = old.unload(ecs, true);
+        self.unloaded_actions.extend(actions);
+        self.plugins[index].execute_event(ecs, &PluginLoadEvent { game_mode })?;
+        info!("Reloaded plugin '{}'", name);
+        Ok(())
+    }
+
+    /// Starts watching the plugin directory, changed plugins are reloaded by
+    /// [`PluginMgr::reload_changed`]
+    pub fn watch(&mut self) -> Result<(), PluginError> {
+        let dir = self.dir.as_ref().ok_or(PluginError::NoPluginDir)?;
+        self.watcher = Some(PluginWatcher::new(dir).map_err(PluginError::Watch)?);
+        info!("Watching {:?} for plugin changes", dir);
+        Ok(())
+    }
+
+    /// Loads or reloads the plugins whose files changed since the last call
+    pub fn reload_changed(&mut self, ecs: &EcsWorld, game_mode: GameMode) {
+        let changed = match &self.watcher {
+            Some(watcher) => watcher.changed_plugins(),
+            None => return,
+        };
+        for path in changed {
+            let loaded = self
+                .plugins
+                .iter()
+                .find(|plugin| plugin.path.as_ref() == Some(&path))
+                .map(|plugin| plugin.data.name.clone());
+            let result = match loaded {
+                Some(name) => self.reload(ecs, game_mode, &name),
+                None => match path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .and_then(|n| n.strip_suffix(PLUGIN_EXTENSION))
+                {
+                    Some(name) if path.exists() => self.load(ecs, game_mode, name),
+                    _ => Ok(()),
+                },
+            };
+            if let Err(e) = result {
+                error!(?e, ?path, "Failed to hot reload plugin");
+            }
+        }
+    }
+
+    fn index_of(&self, name: &str) -> Result<usize, PluginError> {
+        self.plugins
+            .iter()
+            .position(|plugin| plugin.data.name == name)
+            .ok_or_else(|| PluginError::NoSuchPlugin(name.to_owned()))
+    }
+
     pub fn from_dir<P: AsRef<Path>>(path: P) -> Result<Self, PluginError> {
-        let plugins = fs::read_dir(path)
+        let plugins = fs::read_dir(&path)
             .map_err(PluginError::Io)?
             .filter_map(|e| e.ok())
             .map(|entry| {
@@ -158,12 +393,11 @@ impl PluginMgr {
                         .path()
                         .file_name()
                         .and_then(|n| n.to_str())
-                        .map(|s| s.ends_with(".plugin.tar"))
+                        .map(|s| s.ends_with(PLUGIN_EXTENSION))
                         .unwrap_or(false)
                 {
                     info!("Loading plugin at {:?}", entry.path());
-                    Plugin::from_reader(fs::File::open(entry.path()).map_err(PluginError::Io)?)
-                        .map(Some)
+                    Plugin::from_path(&entry.path()).map(Some)
                 } else {
                     Ok(None)
                 }
@@ -182,6 +416,10 @@ impl PluginMgr {
             );
         }
 
-        Ok(Self { plugins })
+        Ok(Self {
+            plugins,
+            dir: Some(path.as_ref().to_owned()),
+            ..Default::default()
+        })
     }
 }
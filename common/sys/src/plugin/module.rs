@@ -2,11 +2,22 @@ use std::{
     collections::HashSet,
     convert::TryInto,
     marker::PhantomData,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
 };
 
-use specs::saveload::MarkerAllocator;
-use wasmer::{imports, Cranelift, Function, Instance, Memory, Module, Store, Value, JIT};
+use common::{uid::Uid, vol::ReadVol};
+use specs::{saveload::MarkerAllocator, Join};
+use wasmer::{
+    imports, wasmparser::Operator, CompilerConfig, Cranelift, Function, Instance, Memory, Module,
+    Store, Value, JIT,
+};
+use wasmer_middlewares::{
+    metering::{get_remaining_points, set_remaining_points, MeteringPoints},
+    Metering,
+};
 
 use super::{
     errors::{PluginError, PluginModuleError},
@@ -14,12 +25,20 @@ use super::{
     wasm_env::HostFunctionEnvironement,
 };
 
-use plugin_api::{Action, EcsAccessError, Event, Retrieve, RetrieveError, RetrieveResult};
+use plugin_api::{
+    Action, EcsAccessError, Event, ItemStack, Retrieve, RetrieveError, RetrieveResult,
+};
+
+/// Number of wasm instructions a module may execute to handle a single event,
+/// the event is aborted when they run out
+pub const FUEL_PER_EVENT: u64 = 50_000_000;
 
 #[derive(Clone)]
 /// This structure represent the WASM State of the plugin.
 pub struct PluginModule {
     ecs: Arc<EcsAccessManager>,
+    env: HostFunctionEnvironement,
+    fuel_used: Arc<AtomicU64>,
     wasm_state: Arc<Mutex<Instance>>,
     memory_manager: Arc<MemoryManager>,
     events: HashSet<String>,
@@ -31,27 +50,34 @@ pub struct PluginModule {
 impl PluginModule {
     /// This function takes bytes from a WASM File and compile them
     pub fn new(name: String, wasm_data: &[u8]) -> Result<Self, PluginModuleError> {
+        // Every instruction costs one point of fuel so a plugin can't loop forever
+        fn operator_cost(_: &Operator) -> u64 { 1 }
+        let mut compiler = Cranelift::default();
+        compiler.push_middleware(Arc::new(Metering::new(FUEL_PER_EVENT, operator_cost)));
         // This is creating the engine is this case a JIT based on Cranelift
-        let engine = JIT::new(Cranelift::default()).engine();
+        let engine = JIT::new(compiler).engine();
         // We are creating an enironnement
         let store = Store::new(&engine);
         // We are compiling the WASM file in the previously generated environement
-        let module = Module::new(&store, &wasm_data).expect("Can't compile");
+        let module = Module::new(&store, &wasm_data).map_err(PluginModuleError::CompileError)?;
 
         // This is the function imported into the wasm environement
         fn raw_emit_actions(env: &HostFunctionEnvironement, ptr: i64, len: i64) {
-            handle_actions(match env.read_data(from_i64(ptr), from_i64(len)) {
-                Ok(e) => e,
-                Err(e) => {
-                    tracing::error!(?e, "Can't decode action");
-                    return;
+            handle_actions(
+                &env.ecs,
+                match env.read_data(from_i64(ptr), from_i64(len)) {
+                    Ok(e) => e,
+                    Err(e) => {
+                        tracing::error!(?e, "Can't decode action");
+                        return;
+                    },
                 },
-            });
+            );
         }
 
         fn raw_retrieve_action(env: &HostFunctionEnvironement, ptr: i64, len: i64) -> i64 {
             let out = match env.read_data(from_i64(ptr), from_i64(len)) {
-                Ok(data) => retrieve_action(&env.ecs, data),
+                Ok(data) => retrieve_action(&env.ecs, &env.name, data),
                 Err(e) => Err(RetrieveError::BincodeError(e.to_string())),
             };
 
@@ -66,12 +92,13 @@ impl PluginModule {
 
         let ecs = Arc::new(EcsAccessManager::default());
         let memory_manager = Arc::new(MemoryManager::default());
+        let env = HostFunctionEnvironement::new(name.clone(), ecs.clone(), memory_manager.clone());
 
         // Create an import object.
         let import_object = imports! {
             "env" => {
-                "raw_emit_actions" => Function::new_native_with_env(&store, HostFunctionEnvironement::new(name.clone(), ecs.clone(),memory_manager.clone()), raw_emit_actions),
-                "raw_retrieve_action" => Function::new_native_with_env(&store, HostFunctionEnvironement::new(name.clone(), ecs.clone(),memory_manager.clone()), raw_retrieve_action),
+                "raw_emit_actions" => Function::new_native_with_env(&store, env.clone(), raw_emit_actions),
+                "raw_retrieve_action" => Function::new_native_with_env(&store, env.clone(), raw_retrieve_action),
                 "dbg" => Function::new_native(&store, dbg),
             }
         };
@@ -82,6 +109,8 @@ impl PluginModule {
         Ok(Self {
             memory_manager,
             ecs,
+            env,
+            fuel_used: Arc::new(AtomicU64::new(0)),
             memory: instance
                 .exports
                 .get_memory("memory")
@@ -118,13 +147,35 @@ impl PluginModule {
         // Store the ECS Pointer for later use in `retreives`
         let bytes = match self.ecs.execute_with(ecs, || {
             let mut state = self.wasm_state.lock().unwrap();
-            execute_raw(self, &mut state, &request.function_name, &request.bytes)
+            set_remaining_points(&state, FUEL_PER_EVENT);
+            let result = execute_raw(self, &mut state, &request.function_name, &request.bytes);
+            match get_remaining_points(&state) {
+                MeteringPoints::Remaining(left) => {
+                    self.fuel_used
+                        .fetch_add(FUEL_PER_EVENT - left, Ordering::Relaxed);
+                    result
+                },
+                MeteringPoints::Exhausted => {
+                    self.fuel_used.fetch_add(FUEL_PER_EVENT, Ordering::Relaxed);
+                    Err(PluginModuleError::OutOfFuel)
+                },
+            }
         }) {
             Ok(e) => e,
             Err(e) => return Some(Err(e)),
         };
         Some(bincode::deserialize(&bytes).map_err(PluginModuleError::Encoding))
     }
+
+    /// Returns the actions this module emitted since the last call
+    pub fn take_actions(&self) -> Vec<Action> { self.ecs.take_actions() }
+
+    /// Returns the number of wasm instructions executed since the last call
+    pub fn take_fuel_used(&self) -> u64 { self.fuel_used.swap(0, Ordering::Relaxed) }
+
+    /// Breaks the references between the instance and its host functions so
+    /// the memory of the module is freed, clones of it can't be used anymore
+    pub fn teardown(self) { self.env.teardown(); }
 }
 
 /// This structure represent a Pre-encoded event object (Useful to avoid
@@ -229,57 +280,113 @@ fn execute_raw(
 
 fn retrieve_action(
     ecs: &EcsAccessManager,
+    plugin: &str,
     action: Retrieve,
 ) -> Result<RetrieveResult, RetrieveError> {
+    // Safety: No reference is leaked out the function so it is safe.
+    let world = unsafe {
+        ecs.get().ok_or(RetrieveError::EcsAccessError(
+            EcsAccessError::EcsPointerNotAvailable,
+        ))?
+    };
+    let entity = |uid: Uid| {
+        let error = RetrieveError::EcsAccessError(EcsAccessError::EcsEntityNotFound(uid));
+        world
+            .uid_allocator
+            .retrieve_entity_internal(uid.0)
+            .ok_or(error)
+    };
+    let not_found = |uid: Uid, component: &str| {
+        RetrieveError::EcsAccessError(EcsAccessError::EcsComponentNotFound(
+            uid,
+            component.to_owned(),
+        ))
+    };
     match action {
-        Retrieve::GetPlayerName(e) => {
-            // Safety: No reference is leaked out the function so it is safe.
-            let world = unsafe {
-                ecs.get().ok_or(RetrieveError::EcsAccessError(
-                    EcsAccessError::EcsPointerNotAvailable,
-                ))?
-            };
-            let player = world.uid_allocator.retrieve_entity_internal(e.0).ok_or(
-                RetrieveError::EcsAccessError(EcsAccessError::EcsEntityNotFound(e)),
-            )?;
-
-            Ok(RetrieveResult::GetPlayerName(
-                world
-                    .player
-                    .get(player)
-                    .ok_or_else(|| {
-                        RetrieveError::EcsAccessError(EcsAccessError::EcsComponentNotFound(
-                            e,
-                            "Player".to_owned(),
-                        ))
-                    })?
-                    .alias
-                    .to_owned(),
-            ))
-        },
-        Retrieve::GetEntityHealth(e) => {
-            // Safety: No reference is leaked out the function so it is safe.
-            let world = unsafe {
-                ecs.get().ok_or(RetrieveError::EcsAccessError(
-                    EcsAccessError::EcsPointerNotAvailable,
-                ))?
-            };
-            let player = world.uid_allocator.retrieve_entity_internal(e.0).ok_or(
-                RetrieveError::EcsAccessError(EcsAccessError::EcsEntityNotFound(e)),
-            )?;
-            Ok(RetrieveResult::GetEntityHealth(
-                *world.health.get(player).ok_or_else(|| {
-                    RetrieveError::EcsAccessError(EcsAccessError::EcsComponentNotFound(
-                        e,
-                        "Health".to_owned(),
-                    ))
-                })?,
+        Retrieve::GetPlayerName(e) => Ok(RetrieveResult::GetPlayerName(
+            world
+                .player
+                .get(entity(e)?)
+                .ok_or_else(|| not_found(e, "Player"))?
+                .alias
+                .to_owned(),
+        )),
+        Retrieve::GetEntityHealth(e) => Ok(RetrieveResult::GetEntityHealth(
+            *world
+                .health
+                .get(entity(e)?)
+                .ok_or_else(|| not_found(e, "Health"))?,
+        )),
+        Retrieve::GetEntityPosition(e) => Ok(RetrieveResult::GetEntityPosition(
+            world
+                .pos
+                .get(entity(e)?)
+                .ok_or_else(|| not_found(e, "Pos"))?
+                .0,
+        )),
+        Retrieve::GetInventory(e) => Ok(RetrieveResult::GetInventory(
+            world
+                .inventory
+                .get(entity(e)?)
+                .ok_or_else(|| not_found(e, "Inventory"))?
+                .slots()
+                .flatten()
+                .map(|item| ItemStack {
+                    item: item.item_definition_id().to_owned(),
+                    amount: item.amount(),
+                })
+                .collect(),
+        )),
+        Retrieve::GetNearbyEntities(pos, radius) => {
+            let mut nearby = world
+                .entities
+                .join()
+                .filter_map(|entity| {
+                    let distance_sq = world.pos.get(entity)?.0.distance_squared(pos);
+                    let uid = world.uid.get(entity)?;
+                    (distance_sq <= radius.powi(2)).then(|| (distance_sq, *uid))
+                })
+                .collect::<Vec<_>>();
+            nearby.sort_unstable_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap());
+            Ok(RetrieveResult::GetNearbyEntities(
+                nearby.into_iter().map(|(_, uid)| uid).collect(),
             ))
         },
+        Retrieve::GetBlock(pos) => Ok(RetrieveResult::GetBlock(
+            (*world
+                .terrain
+                .get(pos)
+                .map_err(|e| RetrieveError::OtherError(format!("No block at {}: {:?}", pos, e)))?)
+            .into(),
+        )),
+        Retrieve::GetTimeOfDay => Ok(RetrieveResult::GetTimeOfDay(world.time_of_day.0)),
+        Retrieve::GetStorage(key) => world
+            .storage
+            .get(plugin, &key)
+            .map(RetrieveResult::GetStorage)
+            .map_err(RetrieveError::StorageError),
+        Retrieve::SetStorage(key, value) => world
+            .storage
+            .set(plugin, &key, &value)
+            .map(|()| RetrieveResult::SetStorage)
+            .map_err(RetrieveError::StorageError),
+        Retrieve::RemoveStorage(key) => world
+            .storage
+            .remove(plugin, &key)
+            .map(RetrieveResult::RemoveStorage)
+            .map_err(RetrieveError::StorageError),
+        Retrieve::GetStorageKeys(prefix) => world
+            .storage
+            .keys(plugin, &prefix)
+            .map(RetrieveResult::GetStorageKeys)
+            .map_err(RetrieveError::StorageError),
     }
 }
 
-fn handle_actions(actions: Vec<Action>) {
+/// Applies the actions that don't need the ECS right away and queues the
+/// others for the server
+fn handle_actions(ecs: &EcsAccessManager, actions: Vec<Action>) {
+    let mut queued = Vec::new();
     for action in actions {
         match action {
             Action::ServerClose => {
@@ -289,12 +396,126 @@ fn handle_actions(actions: Vec<Action>) {
             Action::Print(e) => {
                 tracing::info!("{}", e);
             },
-            Action::PlayerSendMessage(a, b) => {
-                tracing::info!("SendMessage {} -> {}", a, b);
-            },
-            Action::KillEntity(e) => {
-                tracing::info!("Kill Entity {}", e);
-            },
+            action => queued.push(action),
+        }
+    }
+    ecs.push_actions(queued);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::storage::PluginStorage, *};
+    use common::{
+        comp::{Health, Inventory, Player, Pos},
+        resources::TimeOfDay,
+        terrain::TerrainGrid,
+        uid::UidAllocator,
+    };
+    use specs::{Builder, World, WorldExt};
+    use vek::Vec3;
+
+    fn world() -> World {
+        let mut ecs = World::new();
+        ecs.register::<Uid>();
+        ecs.register::<Health>();
+        ecs.register::<Player>();
+        ecs.register::<Pos>();
+        ecs.register::<Inventory>();
+        ecs.insert(UidAllocator::new());
+        ecs.insert(TerrainGrid::new().unwrap());
+        ecs.insert(TimeOfDay(3600.0));
+        ecs.insert(PluginStorage::default());
+        ecs
+    }
+
+    fn spawn(ecs: &mut World, pos: Vec3<f32>) -> Uid {
+        let entity = ecs.create_entity().with(Pos(pos)).build();
+        let uid = ecs.write_resource::<UidAllocator>().allocate(entity, None);
+        ecs.write_storage().insert(entity, uid).unwrap();
+        uid
+    }
+
+    fn retrieve(ecs: &World, retrieve: Retrieve) -> Result<RetrieveResult, RetrieveError> {
+        let ecs_world = EcsWorld {
+            entities: &ecs.entities(),
+            health: ecs.read_component().into(),
+            uid: ecs.read_component().into(),
+            player: ecs.read_component().into(),
+            pos: ecs.read_component().into(),
+            inventory: ecs.read_component().into(),
+            uid_allocator: &ecs.read_resource::<UidAllocator>().into(),
+            terrain: &ecs.read_resource::<TerrainGrid>().into(),
+            time_of_day: &ecs.read_resource::<TimeOfDay>().into(),
+            storage: &ecs.read_resource::<PluginStorage>().into(),
+        };
+        let manager = EcsAccessManager::default();
+        manager.execute_with(&ecs_world, || retrieve_action(&manager, "test", retrieve))
+    }
+
+    #[test]
+    fn retrieve_entity_position() {
+        let mut ecs = world();
+        let uid = spawn(&mut ecs, Vec3::new(1.0, 2.0, 3.0));
+
+        assert!(matches!(
+            retrieve(&ecs, Retrieve::GetEntityPosition(uid)),
+            Ok(RetrieveResult::GetEntityPosition(pos)) if pos == Vec3::new(1.0, 2.0, 3.0)
+        ));
+        assert!(matches!(
+            retrieve(&ecs, Retrieve::GetEntityPosition(Uid(uid.0 + 1))),
+            Err(RetrieveError::EcsAccessError(
+                EcsAccessError::EcsEntityNotFound(_)
+            ))
+        ));
+        assert!(matches!(
+            retrieve(&ecs, Retrieve::GetInventory(uid)),
+            Err(RetrieveError::EcsAccessError(
+                EcsAccessError::EcsComponentNotFound(_, _)
+            ))
+        ));
+    }
+
+    #[test]
+    fn retrieve_nearby_entities_sorted() {
+        let mut ecs = world();
+        let far = spawn(&mut ecs, Vec3::new(8.0, 0.0, 0.0));
+        let near = spawn(&mut ecs, Vec3::new(0.0, 2.0, 0.0));
+        spawn(&mut ecs, Vec3::new(0.0, 0.0, 20.0));
+
+        match retrieve(&ecs, Retrieve::GetNearbyEntities(Vec3::zero(), 10.0)) {
+            Ok(RetrieveResult::GetNearbyEntities(uids)) => assert_eq!(uids, vec![near, far]),
+            e => panic!("Unexpected result: {:?}", e),
         }
     }
+
+    #[test]
+    fn retrieve_time_of_day() {
+        assert!(matches!(
+            retrieve_action(&EcsAccessManager::default(), Retrieve::GetTimeOfDay),
+            Err(RetrieveError::EcsAccessError(
+                EcsAccessError::EcsPointerNotAvailable
+            ))
+        ));
+        assert!(matches!(
+            retrieve(&world(), Retrieve::GetTimeOfDay),
+            Ok(RetrieveResult::GetTimeOfDay(t)) if (t - 3600.0).abs() < f64::EPSILON
+        ));
+    }
+
+    #[test]
+    fn actions_are_queued_in_order() {
+        let manager = EcsAccessManager::default();
+        let actions = vec![
+            Action::BroadcastMessage("Hello".to_owned()),
+            Action::Print("Not queued".to_owned()),
+            Action::KillEntity(Uid(1)),
+        ];
+        handle_actions(&manager, actions);
+
+        assert_eq!(manager.take_actions(), vec![
+            Action::BroadcastMessage("Hello".to_owned()),
+            Action::KillEntity(Uid(1)),
+        ]);
+        assert!(manager.take_actions().is_empty());
+    }
 }
@@ -0,0 +1,170 @@
+use plugin_api::StorageError;
+
+/// Longest key a plugin can store a value under, in bytes
+pub const MAX_KEY_LEN: usize = 256;
+/// Number of keys a plugin can store at most
+pub const MAX_ENTRIES: u64 = 10_000;
+/// Bytes of keys and values a plugin can store at most
+pub const MAX_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Where the values of [`PluginStorage`] are kept, the server stores them in
+/// its database. Errors are only reported to the plugin.
+pub trait StorageBackend: Send + Sync {
+    fn get(&self, plugin: &str, key: &str) -> Result<Option<Vec<u8>>, String>;
+
+    fn set(&self, plugin: &str, key: &str, value: &[u8]) -> Result<(), String>;
+
+    /// Returns whether the key was stored
+    fn remove(&self, plugin: &str, key: &str) -> Result<bool, String>;
+
+    fn keys(&self, plugin: &str, prefix: &str) -> Result<Vec<String>, String>;
+
+    /// Returns the number of entries of a plugin and the bytes of their keys
+    /// and values
+    fn usage(&self, plugin: &str) -> Result<(u64, u64), String>;
+}
+
+/// Persistent key value storage of plugins, each plugin only sees its own keys.
+///
+/// Without a backend, e.g. on clients, all operations fail with
+/// [`StorageError::NotAvailable`].
+#[derive(Default)]
+pub struct PluginStorage {
+    backend: Option<Box<dyn StorageBackend>>,
+}
+
+impl PluginStorage {
+    pub fn new(backend: impl StorageBackend + 'static) -> Self {
+        Self {
+            backend: Some(Box::new(backend)),
+        }
+    }
+
+    fn backend(&self) -> Result<&dyn StorageBackend, StorageError> {
+        self.backend.as_deref().ok_or(StorageError::NotAvailable)
+    }
+
+    pub fn get(&self, plugin: &str, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        self.backend()?
+            .get(plugin, key)
+            .map_err(StorageError::BackendError)
+    }
+
+    pub fn set(&self, plugin: &str, key: &str, value: &[u8]) -> Result<(), StorageError> {
+        if key.len() > MAX_KEY_LEN {
+            return Err(StorageError::KeyTooLong(MAX_KEY_LEN));
+        }
+        let backend = self.backend()?;
+        let (entries, bytes) = backend.usage(plugin).map_err(StorageError::BackendError)?;
+        let (entries, bytes) = match backend
+            .get(plugin, key)
+            .map_err(StorageError::BackendError)?
+        {
+            Some(old) => (
+                entries,
+                bytes.saturating_sub((key.len() + old.len()) as u64),
+            ),
+            None => (entries + 1, bytes),
+        };
+        if entries > MAX_ENTRIES || bytes + (key.len() + value.len()) as u64 > MAX_BYTES {
+            return Err(StorageError::QuotaExceeded);
+        }
+        backend
+            .set(plugin, key, value)
+            .map_err(StorageError::BackendError)
+    }
+
+    pub fn remove(&self, plugin: &str, key: &str) -> Result<bool, StorageError> {
+        self.backend()?
+            .remove(plugin, key)
+            .map_err(StorageError::BackendError)
+    }
+
+    pub fn keys(&self, plugin: &str, prefix: &str) -> Result<Vec<String>, StorageError> {
+        self.backend()?
+            .keys(plugin, prefix)
+            .map_err(StorageError::BackendError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{collections::BTreeMap, sync::Mutex};
+
+    #[derive(Default)]
+    struct MemoryBackend(Mutex<BTreeMap<(String, String), Vec<u8>>>);
+
+    impl StorageBackend for MemoryBackend {
+        fn get(&self, plugin: &str, key: &str) -> Result<Option<Vec<u8>>, String> {
+            let map = self.0.lock().unwrap();
+            Ok(map.get(&(plugin.to_owned(), key.to_owned())).cloned())
+        }
+
+        fn set(&self, plugin: &str, key: &str, value: &[u8]) -> Result<(), String> {
+            let mut map = self.0.lock().unwrap();
+            map.insert((plugin.to_owned(), key.to_owned()), value.to_vec());
+            Ok(())
+        }
+
+        fn remove(&self, plugin: &str, key: &str) -> Result<bool, String> {
+            let mut map = self.0.lock().unwrap();
+            Ok(map.remove(&(plugin.to_owned(), key.to_owned())).is_some())
+        }
+
+        fn keys(&self, plugin: &str, prefix: &str) -> Result<Vec<String>, String> {
+            let map = self.0.lock().unwrap();
+            Ok(map
+                .keys()
+                .filter(|(p, k)| p == plugin && k.starts_with(prefix))
+                .map(|(_, k)| k.clone())
+                .collect())
+        }
+
+        fn usage(&self, plugin: &str) -> Result<(u64, u64), String> {
+            let map = self.0.lock().unwrap();
+            Ok(map
+                .iter()
+                .filter(|((p, _), _)| p == plugin)
+                .fold((0, 0), |(entries, bytes), ((_, k), v)| {
+                    (entries + 1, bytes + (k.len() + v.len()) as u64)
+                }))
+        }
+    }
+
+    #[test]
+    fn plugins_are_namespaced() {
+        let storage = PluginStorage::new(MemoryBackend::default());
+        storage.set("a", "score.bob", &[1]).unwrap();
+        storage.set("a", "level.bob", &[2]).unwrap();
+        storage.set("b", "score.bob", &[3]).unwrap();
+
+        assert_eq!(storage.get("a", "score.bob").unwrap(), Some(vec![1]));
+        assert_eq!(storage.get("b", "score.bob").unwrap(), Some(vec![3]));
+        assert_eq!(storage.keys("a", "score.").unwrap(), vec!["score.bob"]);
+        assert!(storage.remove("a", "score.bob").unwrap());
+        assert!(!storage.remove("a", "score.bob").unwrap());
+        assert_eq!(storage.get("b", "score.bob").unwrap(), Some(vec![3]));
+    }
+
+    #[test]
+    fn quota_counts_replaced_values_once() {
+        let storage = PluginStorage::new(MemoryBackend::default());
+        let half = vec![0; MAX_BYTES as usize / 2];
+        storage.set("a", "x", &half).unwrap();
+        storage.set("a", "x", &half).unwrap();
+        assert!(matches!(
+            storage.set("a", "y", &half),
+            Err(StorageError::QuotaExceeded)
+        ));
+        storage.set("b", "y", &half).unwrap();
+        assert!(matches!(
+            storage.set("a", &"k".repeat(MAX_KEY_LEN + 1), &[]),
+            Err(StorageError::KeyTooLong(MAX_KEY_LEN))
+        ));
+        assert!(matches!(
+            PluginStorage::default().get("a", "x"),
+            Err(StorageError::NotAvailable)
+        ));
+    }
+}
@@ -1,7 +1,7 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use serde::{de::DeserializeOwned, Serialize};
-use wasmer::{Function, HostEnvInitError, Instance, LazyInit, Memory, WasmerEnv};
+use wasmer::{Function, HostEnvInitError, Instance, Memory, WasmerEnv};
 
 use super::{
     errors::PluginModuleError,
@@ -13,10 +13,11 @@ pub struct HostFunctionEnvironement {
     pub ecs: Arc<EcsAccessManager>, /* This represent the pointer to the ECS object (set to
                                      * i32::MAX if to ECS is
                                      * availible) */
-    pub memory: LazyInit<Memory>, // This object represent the WASM Memory
-    pub allocator: LazyInit<Function>, // Linked to: wasm_prepare_buffer
+    /* This represent the WASM Memory and the allocator linked to: wasm_prepare_buffer. They
+     * are removed on teardown because they keep the instance alive */
+    pub exports: Arc<Mutex<Option<(Memory, Function)>>>,
     pub memory_manager: Arc<MemoryManager>, /* This object represent the current buffer size and
-                                   * pointer */
+                                             * pointer */
     pub name: String, // This represent the plugin name
 }
 
@@ -29,20 +30,32 @@ impl HostFunctionEnvironement {
         Self {
             memory_manager,
             ecs,
-            allocator: LazyInit::new(),
-            memory: LazyInit::new(),
+            exports: Arc::new(Mutex::new(None)),
             name,
         }
     }
 
+    /// Releases the exports of the instance so it can be dropped, the host
+    /// functions can't be used afterwards
+    pub fn teardown(&self) { *self.exports.lock().unwrap() = None; }
+
+    fn with_exports<T>(&self, f: impl FnOnce(&Memory, &Function) -> T) -> T {
+        // Cloned so the lock isn't held while the allocator runs
+        let (memory, allocator) = self
+            .exports
+            .lock()
+            .unwrap()
+            .clone()
+            .expect("Host function called after teardown");
+        f(&memory, &allocator)
+    }
+
     /// This function is a safe interface to WASM memory that writes data to the
     /// memory returning a pointer and length
     pub fn write_data<T: Serialize>(&self, object: &T) -> Result<(u64, u64), PluginModuleError> {
-        self.memory_manager.write_data(
-            self.memory.get_ref().unwrap(),
-            self.allocator.get_ref().unwrap(),
-            object,
-        )
+        self.with_exports(|memory, allocator| {
+            self.memory_manager.write_data(memory, allocator, object)
+        })
     }
 
     /// This function is a safe interface to WASM memory that writes data to the
@@ -51,11 +64,10 @@ impl HostFunctionEnvironement {
         &self,
         object: &T,
     ) -> Result<u64, PluginModuleError> {
-        self.memory_manager.write_data_as_pointer(
-            self.memory.get_ref().unwrap(),
-            self.allocator.get_ref().unwrap(),
-            object,
-        )
+        self.with_exports(|memory, allocator| {
+            self.memory_manager
+                .write_data_as_pointer(memory, allocator, object)
+        })
     }
 
     /// This function is a safe interface to WASM memory that reads memory from
@@ -65,19 +77,18 @@ impl HostFunctionEnvironement {
         position: u64,
         length: u64,
     ) -> Result<T, bincode::Error> {
-        memory_manager::read_data(self.memory.get_ref().unwrap(), position, length)
+        self.with_exports(|memory, _| memory_manager::read_data(memory, position, length))
     }
 }
 
 impl WasmerEnv for HostFunctionEnvironement {
     fn init_with_instance(&mut self, instance: &Instance) -> Result<(), HostEnvInitError> {
         let memory = instance.exports.get_memory("memory").unwrap();
-        self.memory.initialize(memory.clone());
         let allocator = instance
             .exports
             .get_function("wasm_prepare_buffer")
             .expect("Can't get allocator");
-        self.allocator.initialize(allocator.clone());
+        *self.exports.lock().unwrap() = Some((memory.clone(), allocator.clone()));
         Ok(())
     }
 }
@@ -0,0 +1,80 @@
+use notify::{immediate_watcher, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{channel, Receiver},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tracing::error;
+
+use super::PLUGIN_EXTENSION;
+
+/// Time a file has to stay unchanged before it is reloaded, so archives that
+/// are still being written aren't loaded
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+
+struct Inner {
+    _watcher: RecommendedWatcher,
+    recv: Receiver<PathBuf>,
+    /// Changed files with the time of their last change
+    pending: HashMap<PathBuf, Instant>,
+}
+
+/// Watches the plugin directory for created or modified plugin files
+pub struct PluginWatcher {
+    // Kept in a mutex because the plugin manager is shared between systems
+    inner: Mutex<Inner>,
+}
+
+impl PluginWatcher {
+    pub fn new(dir: &Path) -> Result<Self, notify::Error> {
+        let (send, recv) = channel();
+        let mut watcher = immediate_watcher(move |res: notify::Result<notify::Event>| match res {
+            Ok(event) => {
+                if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                    for path in event.paths.into_iter().filter(|p| is_plugin(p)) {
+                        let _ = send.send(path);
+                    }
+                }
+            },
+            Err(e) => error!(?e, "Plugin hot reload watcher error."),
+        })?;
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        Ok(Self {
+            inner: Mutex::new(Inner {
+                _watcher: watcher,
+                recv,
+                pending: HashMap::new(),
+            }),
+        })
+    }
+
+    /// Returns the plugin files that changed and weren't modified for
+    /// [`SETTLE_TIME`] since
+    pub fn changed_plugins(&self) -> Vec<PathBuf> {
+        let mut inner = self.inner.lock().unwrap();
+        let now = Instant::now();
+        while let Ok(path) = inner.recv.try_recv() {
+            inner.pending.insert(path, now);
+        }
+        let settled = inner
+            .pending
+            .iter()
+            .filter(|(_, changed)| now.duration_since(**changed) >= SETTLE_TIME)
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<_>>();
+        for path in &settled {
+            inner.pending.remove(path);
+        }
+        settled
+    }
+}
+
+fn is_plugin(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map_or(false, |name| name.ends_with(PLUGIN_EXTENSION))
+}
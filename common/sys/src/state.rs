@@ -1,7 +1,7 @@
 #[cfg(feature = "plugins")]
 use crate::plugin::memory_manager::EcsWorld;
 #[cfg(feature = "plugins")]
-use crate::plugin::PluginMgr;
+use crate::plugin::{storage::PluginStorage, PluginMgr};
 #[cfg(feature = "plugins")]
 use common::uid::UidAllocator;
 use common::{
@@ -222,6 +222,10 @@ impl State {
         ecs.insert(PhysicsMetrics::default());
         ecs.insert(Trades::default());
 
+        // Plugins can only persist data if the server provides a storage backend
+        #[cfg(feature = "plugins")]
+        ecs.insert(PluginStorage::default());
+
         // Load plugins from asset directory
         #[cfg(feature = "plugins")]
         ecs.insert(match PluginMgr::from_assets() {
@@ -232,6 +236,11 @@ impl State {
                     uid: ecs.read_component().into(),
                     uid_allocator: &ecs.read_resource::<UidAllocator>().into(),
                     player: ecs.read_component().into(),
+                    pos: ecs.read_component().into(),
+                    inventory: ecs.read_component().into(),
+                    terrain: &ecs.read_resource::<TerrainGrid>().into(),
+                    time_of_day: &ecs.read_resource::<TimeOfDay>().into(),
+                    storage: &ecs.read_resource::<PluginStorage>().into(),
                 };
                 if let Err(e) = plugin_mgr
                     .execute_event(&ecs_world, &plugin_api::event::PluginLoadEvent {
@@ -495,6 +504,48 @@ impl State {
             }
         }
         drop(guard);
+
+        #[cfg(feature = "plugins")]
+        self.tick_plugins(dt);
+    }
+
+    /// Gives access to the plugin manager together with the view of the ECS
+    /// plugins run their events on, e.g. to load or unload plugins.
+    #[cfg(feature = "plugins")]
+    pub fn with_plugins<T>(&self, f: impl FnOnce(&mut PluginMgr, &EcsWorld) -> T) -> T {
+        let mut plugin_mgr = self.ecs.write_resource::<PluginMgr>();
+        let ecs_world = EcsWorld {
+            entities: &self.ecs.entities(),
+            health: self.ecs.read_component().into(),
+            uid: self.ecs.read_component().into(),
+            uid_allocator: &self.ecs.read_resource::<UidAllocator>().into(),
+            player: self.ecs.read_component().into(),
+            pos: self.ecs.read_component().into(),
+            inventory: self.ecs.read_component().into(),
+            terrain: &self.ecs.read_resource::<TerrainGrid>().into(),
+            time_of_day: &self.ecs.read_resource::<TimeOfDay>().into(),
+            storage: &self.ecs.read_resource::<PluginStorage>().into(),
+        };
+        f(&mut plugin_mgr, &ecs_world)
+    }
+
+    /// Reload changed plugins, run the `on_tick` event of plugins and apply
+    /// their tick budgets.
+    #[cfg(feature = "plugins")]
+    fn tick_plugins(&self, dt: Duration) {
+        span!(_guard, "tick plugins", "State::tick_plugins");
+        let game_mode = *self.ecs.read_resource::<GameMode>();
+        let time = self.ecs.read_resource::<Time>().0;
+        self.with_plugins(|plugin_mgr, ecs_world| {
+            plugin_mgr.reload_changed(ecs_world, game_mode);
+            if let Err(e) = plugin_mgr.execute_event(ecs_world, &plugin_api::event::TickEvent {
+                dt: dt.as_secs_f32(),
+                time,
+            }) {
+                tracing::error!(?e, "Failed to run plugin tick");
+            }
+            plugin_mgr.end_tick(&self.ecs.read_resource::<SysMetrics>());
+        });
     }
 
     /// Clean up the state after a tick.
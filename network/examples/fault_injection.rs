@@ -0,0 +1,172 @@
+//!run with
+//! ```bash
+//! RUST_BACKTRACE=1 cargo run --example fault_injection --features fault_injection -- --drop 0.05 --delay 30 --jitter 20
+//! ```
+use clap::{App, Arg};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::runtime::Runtime;
+use tracing::*;
+use tracing_subscriber::EnvFilter;
+use veloren_network::{Delay, FaultConfig, Network, Pid, Promises, ProtocolAddr};
+
+///This example runs a server and a client in one process and connects them
+/// over a link that drops, delays and reorders messages, to see how the
+/// network behaves on a bad connection without needing one
+fn main() {
+    let matches = App::new("Fault injection example")
+        .version("0.1.0")
+        .about("runs a server and client of veloren-network over a lossy link")
+        .arg(
+            Arg::with_name("drop")
+                .long("drop")
+                .takes_value(true)
+                .default_value("0.05")
+                .help("probability that a datagram is lost, 0.0 - 1.0"),
+        )
+        .arg(
+            Arg::with_name("delay")
+                .long("delay")
+                .takes_value(true)
+                .default_value("30")
+                .help("minimal delay of a datagram in ms"),
+        )
+        .arg(
+            Arg::with_name("jitter")
+                .long("jitter")
+                .takes_value(true)
+                .default_value("10")
+                .help("additional uniformly distributed delay in ms"),
+        )
+        .arg(
+            Arg::with_name("reorder")
+                .long("reorder")
+                .takes_value(true)
+                .default_value("0.01")
+                .help("probability that a datagram is overtaken by later ones, 0.0 - 1.0"),
+        )
+        .arg(
+            Arg::with_name("bandwidth")
+                .long("bandwidth")
+                .takes_value(true)
+                .help("bytes per second the link can transfer, unlimited by default"),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .takes_value(true)
+                .default_value("0")
+                .help("seed of the faults, the same seed results in the same faults"),
+        )
+        .arg(
+            Arg::with_name("messages")
+                .long("messages")
+                .takes_value(true)
+                .default_value("200")
+                .help("how many messages the client sends"),
+        )
+        .arg(
+            Arg::with_name("size")
+                .long("size")
+                .takes_value(true)
+                .default_value("10000")
+                .help("size of each message in bytes"),
+        )
+        .arg(
+            Arg::with_name("trace")
+                .short("t")
+                .long("trace")
+                .takes_value(true)
+                .default_value("warn")
+                .possible_values(&["trace", "debug", "info", "warn", "error"])
+                .help("set trace level, not this has a performance impact!"),
+        )
+        .get_matches();
+
+    let trace = matches.value_of("trace").unwrap();
+    let filter = EnvFilter::from_default_env().add_directive(trace.parse().unwrap());
+    tracing_subscriber::FmtSubscriber::builder()
+        .with_max_level(Level::TRACE)
+        .with_env_filter(filter)
+        .init();
+
+    let ms = |name| Duration::from_millis(matches.value_of(name).unwrap().parse().unwrap());
+    let delay = ms("delay");
+    let config = FaultConfig {
+        drop_rate: matches.value_of("drop").unwrap().parse().unwrap(),
+        delay: Delay::Uniform(delay, delay + ms("jitter")),
+        reorder_rate: matches.value_of("reorder").unwrap().parse().unwrap(),
+        bandwidth: matches.value_of("bandwidth").map(|b| b.parse().unwrap()),
+        seed: matches.value_of("seed").unwrap().parse().unwrap(),
+        ..Default::default()
+    };
+    let messages: usize = matches.value_of("messages").unwrap().parse().unwrap();
+    let size: usize = matches.value_of("size").unwrap().parse().unwrap();
+    println!("{:?}", config);
+
+    let runtime = Arc::new(Runtime::new().unwrap());
+    let server = Network::new(Pid::new(), &runtime);
+    let client = Network::new(Pid::new(), &runtime);
+    let address = ProtocolAddr::Mpsc(52000);
+
+    let participants = runtime.block_on(async {
+        client.set_fault_injection(Some(config)).await;
+        server.listen(address.clone()).await.unwrap();
+        let p_client = client.connect(address).await.unwrap();
+        let p_server = server.connected().await.unwrap();
+        let mut s_client = p_client
+            .open(4, Promises::ORDERED | Promises::GUARANTEED_DELIVERY, 0)
+            .await
+            .unwrap();
+        let mut s_server = p_server.opened().await.unwrap();
+
+        // the server answers every message with its length
+        let echo = tokio::spawn(async move {
+            while let Ok(msg) = s_server.recv::<Vec<u8>>().await {
+                if s_server.send(msg.len()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let start = Instant::now();
+        let mut rtts = Vec::with_capacity(messages);
+        for _ in 0..messages {
+            let send = Instant::now();
+            s_client.send(vec![0u8; size]).unwrap();
+            assert_eq!(s_client.recv::<usize>().await, Ok(size));
+            rtts.push(send.elapsed());
+        }
+        let elapsed = start.elapsed();
+
+        rtts.sort_unstable();
+        println!(
+            "{} messages in {:.2}s, {:.0} bytes/s",
+            messages,
+            elapsed.as_secs_f64(),
+            (messages * size) as f64 / elapsed.as_secs_f64()
+        );
+        if !rtts.is_empty() {
+            println!(
+                "round trip: median {:?}, 99th percentile {:?}, max {:?}",
+                rtts[rtts.len() / 2],
+                rtts[rtts.len() * 99 / 100],
+                rtts[rtts.len() - 1]
+            );
+        }
+        for stats in p_client.channel_stats() {
+            println!(
+                "client channel: rtt {:?}, retransmissions {}",
+                stats.rtt(),
+                stats.retransmissions()
+            );
+        }
+
+        drop(s_client);
+        let _ = echo.await;
+        (p_client, p_server)
+    });
+    drop(participants); //clean teardown
+}
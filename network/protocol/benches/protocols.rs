@@ -5,9 +5,10 @@ use criterion::{criterion_group, criterion_main, Criterion, Throughput};
 use std::{sync::Arc, time::Duration};
 use tokio::runtime::Runtime;
 use veloren_network_protocol::{
-    InitProtocol, MpscMsg, MpscRecvProtocol, MpscSendProtocol, Pid, Promises, ProtocolError,
-    ProtocolEvent, ProtocolMetricCache, ProtocolMetrics, RecvProtocol, SendProtocol, Sid,
-    TcpRecvProtocol, TcpSendProtocol, UnreliableDrain, UnreliableSink, _internal::OTFrame,
+    Identity, InitProtocol, MpscMsg, MpscRecvProtocol, MpscSendProtocol, Pid, Promises,
+    ProtocolError, ProtocolEvent, ProtocolMetricCache, ProtocolMetrics, RecvProtocol, SendProtocol,
+    Sid, TcpRecvProtocol, TcpSendProtocol, UnreliableDrain, UnreliableSink, _internal::OTFrame,
+    tcp_protocols,
 };
 
 fn frame_serialize(frame: OTFrame, buffer: &mut BytesMut) { frame.write_bytes(buffer); }
@@ -21,11 +22,15 @@ where
     let [mut p1, mut p2] = p;
     tokio::join!(
         async {
-            p1.initialize(true, Pid::fake(2), 1337).await.unwrap();
+            p1.initialize(true, Pid::fake(2), 1337, &Identity::generate())
+                .await
+                .unwrap();
             p1
         },
         async {
-            p2.initialize(false, Pid::fake(3), 42).await.unwrap();
+            p2.initialize(false, Pid::fake(3), 42, &Identity::generate())
+                .await
+                .unwrap();
             p2
         }
     );
@@ -199,14 +204,8 @@ mod utils {
             ProtocolMetricCache::new("tcp", Arc::new(ProtocolMetrics::new().unwrap()))
         });
         [
-            (
-                TcpSendProtocol::new(TcpDrain { sender: s1 }, m.clone()),
-                TcpRecvProtocol::new(TcpSink { receiver: r2 }, m.clone()),
-            ),
-            (
-                TcpSendProtocol::new(TcpDrain { sender: s2 }, m.clone()),
-                TcpRecvProtocol::new(TcpSink { receiver: r1 }, m),
-            ),
+            tcp_protocols(TcpDrain { sender: s1 }, TcpSink { receiver: r2 }, m.clone()),
+            tcp_protocols(TcpDrain { sender: s2 }, TcpSink { receiver: r1 }, m),
         ]
     }
 
@@ -0,0 +1,377 @@
+//! Encryption of [`Streams`] with [`Promises::ENCRYPTED`]
+//!
+//! During the handshake both sides create an ephemeral X25519 key and send its
+//! public part together with the public key of their long-term [`Identity`].
+//! The `Init` frames are signed with the identity over a hash of all these
+//! keys, so a man in the middle can't replace them without the signature
+//! check failing. From the shared secret of the key exchange one ChaCha20-
+//! Poly1305 key per direction is derived.
+//!
+//! Messages of encrypted streams are sealed as a whole, before being split into
+//! frames. Every sealed message starts with a counter, which is unique per
+//! direction and used as nonce:
+//! `[counter: u64][ciphertext][tag: 16 bytes]`.
+//!
+//! [`Streams`]: crate::api::Stream
+//! [`Promises::ENCRYPTED`]: crate::Promises::ENCRYPTED
+use crate::types::{Pid, Promises, Sid};
+use bytes::{BufMut, Bytes, BytesMut};
+use ring::{
+    aead::{self, Aad, LessSafeKey, Nonce, UnboundKey},
+    agreement::{self, EphemeralPrivateKey},
+    digest, hkdf,
+    rand::SystemRandom,
+    signature::{self, Ed25519KeyPair, KeyPair},
+};
+use std::{
+    collections::{BTreeSet, HashSet},
+    sync::{Arc, Mutex},
+};
+
+/// Size of the counter in front of every sealed message
+const COUNTER_SIZE: usize = 8;
+/// Number of counters remembered to detect replayed messages, older ones are
+/// rejected
+const REPLAY_WINDOW: usize = 4096;
+const INITIATOR_KEY_INFO: &[u8] = b"veloren initiator key";
+const RESPONDER_KEY_INFO: &[u8] = b"veloren responder key";
+
+/// Long-term key pair of a `Network`, used to sign its side of the handshake.
+/// The remote side can check the public key, e.g. against a key it saw before.
+pub struct Identity {
+    key_pair: Ed25519KeyPair,
+    pkcs8: Vec<u8>,
+}
+
+impl Identity {
+    /// Generates a new random identity
+    pub fn generate() -> Self {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+            .expect("Failed to generate a key pair");
+        Self::from_pkcs8(pkcs8.as_ref()).unwrap()
+    }
+
+    /// Loads an identity that was stored with [`to_pkcs8`]
+    ///
+    /// [`to_pkcs8`]: Identity::to_pkcs8
+    pub fn from_pkcs8(pkcs8: &[u8]) -> Option<Self> {
+        Ed25519KeyPair::from_pkcs8(pkcs8).ok().map(|key_pair| Self {
+            key_pair,
+            pkcs8: pkcs8.to_vec(),
+        })
+    }
+
+    /// The private key, keep it secret
+    pub fn to_pkcs8(&self) -> &[u8] { &self.pkcs8 }
+
+    pub fn public_key(&self) -> [u8; 32] {
+        let mut public_key = [0u8; 32];
+        public_key.copy_from_slice(self.key_pair.public_key().as_ref());
+        public_key
+    }
+
+    pub(crate) fn sign(&self, message: &[u8]) -> [u8; 64] {
+        let mut signature = [0u8; 64];
+        signature.copy_from_slice(self.key_pair.sign(message).as_ref());
+        signature
+    }
+}
+
+impl std::fmt::Debug for Identity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Identity")
+            .field("public_key", &self.public_key())
+            .finish()
+    }
+}
+
+pub(crate) fn verify(identity: &[u8; 32], message: &[u8], signature: &[u8; 64]) -> bool {
+    signature::UnparsedPublicKey::new(&signature::ED25519, identity)
+        .verify(message, signature)
+        .is_ok()
+}
+
+/// Hash over the keys both sides sent during the handshake, `initiator` is the
+/// pair of ephemeral and identity key of the side that sent the first frame.
+pub(crate) fn transcript(
+    initiator: (&[u8; 32], &[u8; 32]),
+    responder: (&[u8; 32], &[u8; 32]),
+) -> [u8; 32] {
+    let mut context = digest::Context::new(&digest::SHA256);
+    context.update(&crate::types::VELOREN_MAGIC_NUMBER);
+    context.update(initiator.0);
+    context.update(initiator.1);
+    context.update(responder.0);
+    context.update(responder.1);
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(context.finish().as_ref());
+    hash
+}
+
+/// What each side signs in its `Init` frame. `initiator` is part of it, so that
+/// a signature can't be reflected back to its sender.
+pub(crate) fn init_message(
+    transcript: &[u8; 32],
+    initiator: bool,
+    pid: Pid,
+    secret: u128,
+) -> Vec<u8> {
+    let mut message = BytesMut::with_capacity(32 + 1 + 16 + 16);
+    message.put_slice(transcript);
+    message.put_u8(initiator as u8);
+    pid.to_bytes(&mut message);
+    message.put_u128_le(secret);
+    message.to_vec()
+}
+
+/// The ephemeral part of the key exchange
+pub(crate) struct KeyExchange {
+    private_key: EphemeralPrivateKey,
+    public_key: [u8; 32],
+}
+
+impl KeyExchange {
+    pub(crate) fn new() -> Self {
+        let private_key = EphemeralPrivateKey::generate(&agreement::X25519, &SystemRandom::new())
+            .expect("Failed to generate a key");
+        let mut public_key = [0u8; 32];
+        public_key.copy_from_slice(private_key.compute_public_key().unwrap().as_ref());
+        Self {
+            private_key,
+            public_key,
+        }
+    }
+
+    pub(crate) fn public_key(&self) -> [u8; 32] { self.public_key }
+
+    /// Derives the keys of both directions, fails for invalid public keys
+    pub(crate) fn finish(
+        self,
+        remote_public_key: &[u8; 32],
+        transcript: &[u8; 32],
+        initiator: bool,
+    ) -> Option<(SendCipher, RecvCipher)> {
+        agreement::agree_ephemeral(
+            self.private_key,
+            &agreement::UnparsedPublicKey::new(&agreement::X25519, remote_public_key),
+            (),
+            |shared_secret| {
+                let prk = hkdf::Salt::new(hkdf::HKDF_SHA256, transcript).extract(shared_secret);
+                let key = |info: &[u8]| -> Result<LessSafeKey, ()> {
+                    let info = [info];
+                    let okm = prk
+                        .expand(&info, &aead::CHACHA20_POLY1305)
+                        .map_err(|_| ())?;
+                    Ok(LessSafeKey::new(UnboundKey::from(okm)))
+                };
+                let (send_info, recv_info) = if initiator {
+                    (INITIATOR_KEY_INFO, RESPONDER_KEY_INFO)
+                } else {
+                    (RESPONDER_KEY_INFO, INITIATOR_KEY_INFO)
+                };
+                Ok((key(send_info)?, key(recv_info)?))
+            },
+        )
+        .ok()
+        .map(|(send_key, recv_key)| {
+            let streams = EncryptedStreams::default();
+            (
+                SendCipher {
+                    key: send_key,
+                    counter: 0,
+                    streams: Arc::clone(&streams),
+                },
+                RecvCipher {
+                    key: recv_key,
+                    floor: 0,
+                    seen: BTreeSet::new(),
+                    streams,
+                },
+            )
+        })
+    }
+}
+
+/// Streams of a channel with [`Promises::ENCRYPTED`], shared by both ciphers,
+/// as each side of the channel only sees the streams that were opened by
+/// one side.
+///
+/// [`Promises::ENCRYPTED`]: crate::Promises::ENCRYPTED
+type EncryptedStreams = Arc<Mutex<HashSet<Sid>>>;
+
+fn nonce(counter: u64) -> Nonce {
+    let mut nonce = [0u8; aead::NONCE_LEN];
+    nonce[4..].copy_from_slice(&counter.to_le_bytes());
+    Nonce::assume_unique_for_key(nonce)
+}
+
+/// Binds a message to its stream, so it can't be moved to another one
+fn aad(sid: Sid) -> Aad<[u8; 8]> {
+    let mut bytes = BytesMut::with_capacity(8);
+    sid.to_bytes(&mut bytes);
+    let mut aad = [0u8; 8];
+    aad.copy_from_slice(&bytes);
+    Aad::from(aad)
+}
+
+/// Seals the messages of encrypted streams
+#[derive(Debug)]
+pub struct SendCipher {
+    key: LessSafeKey,
+    counter: u64,
+    streams: EncryptedStreams,
+}
+
+impl SendCipher {
+    pub(crate) fn open_stream(&self, sid: Sid, promises: Promises) {
+        if promises.contains(Promises::ENCRYPTED) {
+            self.streams.lock().unwrap().insert(sid);
+        }
+    }
+
+    /// Returns the data unchanged if the stream isn't encrypted
+    pub(crate) fn seal(&mut self, sid: Sid, data: Bytes) -> Bytes {
+        if !self.streams.lock().unwrap().contains(&sid) {
+            return data;
+        }
+        let counter = self.counter;
+        self.counter += 1;
+        let mut sealed = Vec::with_capacity(COUNTER_SIZE + data.len() + aead::MAX_TAG_LEN);
+        sealed.extend_from_slice(&counter.to_le_bytes());
+        sealed.extend_from_slice(&data);
+        let tag = self
+            .key
+            .seal_in_place_separate_tag(nonce(counter), aad(sid), &mut sealed[COUNTER_SIZE..])
+            .expect("Message too long to be encrypted");
+        sealed.extend_from_slice(tag.as_ref());
+        Bytes::from(sealed)
+    }
+}
+
+/// Opens the messages of encrypted streams
+#[derive(Debug)]
+pub struct RecvCipher {
+    key: LessSafeKey,
+    /// Messages with a lower counter are rejected
+    floor: u64,
+    seen: BTreeSet<u64>,
+    streams: EncryptedStreams,
+}
+
+/// Why a message couldn't be opened
+#[derive(Debug, PartialEq)]
+pub(crate) enum OpenError {
+    /// The message was altered or not sealed with our key, the channel can't be
+    /// trusted anymore
+    Tampered,
+    /// The message was received before, it should be ignored
+    Replayed,
+}
+
+impl RecvCipher {
+    pub(crate) fn open_stream(&self, sid: Sid, promises: Promises) {
+        if promises.contains(Promises::ENCRYPTED) {
+            self.streams.lock().unwrap().insert(sid);
+        }
+    }
+
+    /// The remote side doesn't send on the stream anymore
+    pub(crate) fn close_stream(&self, sid: Sid) { self.streams.lock().unwrap().remove(&sid); }
+
+    /// Returns the data unchanged if the stream isn't encrypted
+    pub(crate) fn open(&mut self, sid: Sid, data: Bytes) -> Result<Bytes, OpenError> {
+        if !self.streams.lock().unwrap().contains(&sid) {
+            return Ok(data);
+        }
+        if data.len() < COUNTER_SIZE + aead::MAX_TAG_LEN {
+            return Err(OpenError::Tampered);
+        }
+        let mut counter = [0u8; COUNTER_SIZE];
+        counter.copy_from_slice(&data[..COUNTER_SIZE]);
+        let counter = u64::from_le_bytes(counter);
+        if counter < self.floor || self.seen.contains(&counter) {
+            return Err(OpenError::Replayed);
+        }
+        let mut opened = data[COUNTER_SIZE..].to_vec();
+        let len = self
+            .key
+            .open_in_place(nonce(counter), aad(sid), &mut opened)
+            .map_err(|_| OpenError::Tampered)?
+            .len();
+        opened.truncate(len);
+
+        // Only authentic messages may move the window
+        self.seen.insert(counter);
+        while self.seen.remove(&self.floor) {
+            self.floor += 1;
+        }
+        while self.seen.len() > REPLAY_WINDOW {
+            let oldest = *self.seen.iter().next().unwrap();
+            self.seen.remove(&oldest);
+            self.floor = oldest + 1;
+        }
+        Ok(Bytes::from(opened))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ciphers() -> ((SendCipher, RecvCipher), (SendCipher, RecvCipher)) {
+        let (a, b) = (KeyExchange::new(), KeyExchange::new());
+        let (a_pub, b_pub) = (a.public_key(), b.public_key());
+        let transcript = [7u8; 32];
+        (
+            a.finish(&b_pub, &transcript, true).unwrap(),
+            b.finish(&a_pub, &transcript, false).unwrap(),
+        )
+    }
+
+    #[test]
+    fn seal_and_open() {
+        let ((mut send, _), (_, mut recv)) = ciphers();
+        let sid = Sid::new(3);
+        send.open_stream(sid, Promises::ENCRYPTED | Promises::ORDERED);
+        recv.open_stream(sid, Promises::ENCRYPTED | Promises::ORDERED);
+        let data = Bytes::from(&b"Hello World"[..]);
+        let sealed = send.seal(sid, data.clone());
+        assert_ne!(sealed, data);
+        assert_eq!(recv.open(sid, sealed.clone()), Ok(data));
+        assert_eq!(recv.open(sid, sealed), Err(OpenError::Replayed));
+
+        let mut tampered = send.seal(sid, Bytes::from(&b"Hello World"[..])).to_vec();
+        tampered[10] ^= 1;
+        assert_eq!(
+            recv.open(sid, Bytes::from(tampered)),
+            Err(OpenError::Tampered)
+        );
+        // Moving a message to another encrypted stream is detected as well
+        let other = Sid::new(4);
+        recv.open_stream(other, Promises::ENCRYPTED);
+        let sealed = send.seal(sid, Bytes::from(&b"Hello World"[..]));
+        assert_eq!(recv.open(other, sealed), Err(OpenError::Tampered));
+    }
+
+    #[test]
+    fn unencrypted_streams_are_untouched() {
+        let ((mut send, _), (_, mut recv)) = ciphers();
+        let sid = Sid::new(3);
+        send.open_stream(sid, Promises::ORDERED);
+        let data = Bytes::from(&b"Hello World"[..]);
+        assert_eq!(send.seal(sid, data.clone()), data);
+        assert_eq!(recv.open(sid, data.clone()), Ok(data));
+    }
+
+    #[test]
+    fn init_signature() {
+        let identity = Identity::generate();
+        let message = init_message(&[1u8; 32], true, Pid::fake(1), 42);
+        let signature = identity.sign(&message);
+        assert!(verify(&identity.public_key(), &message, &signature));
+        let message = init_message(&[1u8; 32], false, Pid::fake(1), 42);
+        assert!(!verify(&identity.public_key(), &message, &signature));
+        let loaded = Identity::from_pkcs8(identity.to_pkcs8()).unwrap();
+        assert_eq!(loaded.public_key(), identity.public_key());
+    }
+}
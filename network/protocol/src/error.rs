@@ -6,6 +6,8 @@ pub enum InitProtocolError {
     Closed,
     WrongMagicNumber([u8; 7]),
     WrongVersion([u32; 3]),
+    /// The remote side couldn't prove that it owns the identity it claimed
+    AuthenticationFailed,
 }
 
 /// When you return closed you must stay closed!
@@ -38,6 +40,9 @@ impl core::fmt::Display for InitProtocolError {
                 &r,
                 &crate::types::VELOREN_NETWORK_VERSION
             ),
+            InitProtocolError::AuthenticationFailed => {
+                write!(f, "Remote side failed to authenticate the key exchange")
+            },
         }
     }
 }
@@ -0,0 +1,326 @@
+//! Channels that behave like a bad network connection, to test the protocols
+//! and everything built on top of them without real sockets.
+//!
+//! The same [`FaultConfig`], including its seed, results in the same faults
+//! when the same messages are sent.
+use crate::{error::ProtocolError, types::Bandwidth, UnreliableDrain, UnreliableSink};
+use async_trait::async_trait;
+use bytes::BytesMut;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    sync::{
+        atomic::{self, AtomicBool},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::{sync::Notify, time::Instant};
+
+/// How long a message takes from the [`FaultDrain`] to the [`FaultSink`],
+/// after it was transferred according to the bandwidth.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Delay {
+    Constant(Duration),
+    /// uniformly distributed between both
+    Uniform(Duration, Duration),
+    /// `min` plus an exponentially distributed part with mean `mean`, so a
+    /// few messages take way longer than most
+    Exponential {
+        min: Duration,
+        mean: Duration,
+    },
+}
+
+/// Faults a channel created by [`fault_channel`] injects. The default
+/// injects none.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaultConfig {
+    /// probability that a message is lost, 0.0 - 1.0
+    pub drop_rate: f64,
+    pub delay: Delay,
+    /// probability that a message is held back by an additional
+    /// `reorder_delay`, so messages sent after it overtake it. Otherwise
+    /// messages arrive in the order they were sent.
+    pub reorder_rate: f64,
+    pub reorder_delay: Duration,
+    /// bytes per second, when exceeded `send` waits till the previous
+    /// messages are transferred, like a full socket buffer
+    pub bandwidth: Option<Bandwidth>,
+    pub seed: u64,
+}
+
+impl Default for FaultConfig {
+    fn default() -> Self {
+        Self {
+            drop_rate: 0.0,
+            delay: Delay::Constant(Duration::from_secs(0)),
+            reorder_rate: 0.0,
+            reorder_delay: Duration::from_millis(20),
+            bandwidth: None,
+            seed: 0,
+        }
+    }
+}
+
+impl FaultConfig {
+    /// Without drops and reordering, as the TCP and MPSC protocols expect
+    /// every message to arrive in order.
+    pub fn reliable(self) -> Self {
+        Self {
+            drop_rate: 0.0,
+            reorder_rate: 0.0,
+            ..self
+        }
+    }
+}
+
+impl Delay {
+    fn sample(&self, rng: &mut StdRng) -> Duration {
+        match *self {
+            Delay::Constant(d) => d,
+            Delay::Uniform(min, max) if max > min => min + (max - min).mul_f64(rng.gen()),
+            Delay::Uniform(min, _) => min,
+            Delay::Exponential { min, mean } => min + mean.mul_f64(-(1.0 - rng.gen::<f64>()).ln()),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Pending {
+    at: Instant,
+    seq: u64,
+    data: BytesMut,
+}
+
+// BinaryHeap is a max heap, the message to deliver next has to be the greatest
+impl Ord for Pending {
+    fn cmp(&self, other: &Self) -> Ordering { (other.at, other.seq).cmp(&(self.at, self.seq)) }
+}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool { (self.at, self.seq) == (other.at, other.seq) }
+}
+
+impl Eq for Pending {}
+
+#[derive(Debug, Default)]
+struct Link {
+    pending: Mutex<BinaryHeap<Pending>>,
+    notify: Notify,
+    drain_closed: AtomicBool,
+    sink_closed: AtomicBool,
+}
+
+#[derive(Debug)]
+pub struct FaultDrain {
+    config: FaultConfig,
+    rng: StdRng,
+    link: Arc<Link>,
+    /// till then the link transfers previous messages
+    busy_until: Instant,
+    /// delivery of the last message that wasn't reordered
+    last_delivery: Instant,
+    seq: u64,
+}
+
+#[derive(Debug)]
+pub struct FaultSink {
+    link: Arc<Link>,
+}
+
+/// Creates a channel of chunks of bytes, like the TCP and UDP protocols use,
+/// injecting the faults of `config`. Needs a tokio runtime with timers.
+pub fn fault_channel(config: FaultConfig) -> (FaultDrain, FaultSink) {
+    let link = Arc::new(Link::default());
+    let now = Instant::now();
+    (
+        FaultDrain {
+            rng: StdRng::seed_from_u64(config.seed),
+            config,
+            link: Arc::clone(&link),
+            busy_until: now,
+            last_delivery: now,
+            seq: 0,
+        },
+        FaultSink { link },
+    )
+}
+
+#[async_trait]
+impl UnreliableDrain for FaultDrain {
+    type DataFormat = BytesMut;
+
+    async fn send(&mut self, data: Self::DataFormat) -> Result<(), ProtocolError> {
+        if self.link.sink_closed.load(atomic::Ordering::Relaxed) {
+            return Err(ProtocolError::Closed);
+        }
+        let now = Instant::now();
+        let start = self.busy_until.max(now);
+        if start > now {
+            tokio::time::sleep_until(start).await;
+        }
+        if let Some(bandwidth) = self.config.bandwidth {
+            let transfer = data.len() as f64 / bandwidth.max(1) as f64;
+            self.busy_until = start + Duration::from_secs_f64(transfer);
+        }
+        // always roll all dice, so the faults only depend on the seed
+        let dropped = self.rng.gen::<f64>() < self.config.drop_rate;
+        let delay = self.config.delay.sample(&mut self.rng);
+        let reordered = self.rng.gen::<f64>() < self.config.reorder_rate;
+        if dropped {
+            return Ok(());
+        }
+        let mut at = self.busy_until.max(start) + delay;
+        if reordered {
+            at += self.config.reorder_delay;
+        } else {
+            at = at.max(self.last_delivery);
+            self.last_delivery = at;
+        }
+        self.seq += 1;
+        self.link.pending.lock().unwrap().push(Pending {
+            at,
+            seq: self.seq,
+            data,
+        });
+        self.link.notify.notify_one();
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl UnreliableSink for FaultSink {
+    type DataFormat = BytesMut;
+
+    async fn recv(&mut self) -> Result<Self::DataFormat, ProtocolError> {
+        loop {
+            let next = {
+                let mut pending = self.link.pending.lock().unwrap();
+                match pending.peek() {
+                    Some(p) if p.at <= Instant::now() => return Ok(pending.pop().unwrap().data),
+                    Some(p) => Some(p.at),
+                    None => None,
+                }
+            };
+            match next {
+                Some(at) => {
+                    let _ = tokio::time::timeout_at(at, self.link.notify.notified()).await;
+                },
+                None if self.link.drain_closed.load(atomic::Ordering::Relaxed) => {
+                    return Err(ProtocolError::Closed);
+                },
+                None => self.link.notify.notified().await,
+            }
+        }
+    }
+}
+
+impl Drop for FaultDrain {
+    fn drop(&mut self) {
+        self.link
+            .drain_closed
+            .store(true, atomic::Ordering::Relaxed);
+        self.link.notify.notify_one();
+    }
+}
+
+impl Drop for FaultSink {
+    fn drop(&mut self) { self.link.sink_closed.store(true, atomic::Ordering::Relaxed); }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(i: u8) -> BytesMut { BytesMut::from(&[i; 100][..]) }
+
+    async fn transfer(config: FaultConfig, cnt: u8) -> Vec<u8> {
+        let (mut drain, mut sink) = fault_channel(config);
+        for i in 0..cnt {
+            drain.send(msg(i)).await.unwrap();
+        }
+        drop(drain);
+        let mut received = vec![];
+        while let Ok(data) = sink.recv().await {
+            received.push(data[0]);
+        }
+        received
+    }
+
+    #[tokio::test]
+    async fn no_faults() {
+        let received = transfer(FaultConfig::default(), 50).await;
+        assert_eq!(received, (0..50).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn drops_are_seeded() {
+        let config = FaultConfig {
+            drop_rate: 0.3,
+            seed: 42,
+            ..Default::default()
+        };
+        let first = transfer(config.clone(), 100).await;
+        assert!(first.len() > 50 && first.len() < 90, "{}", first.len());
+        assert_eq!(first, transfer(config.clone(), 100).await);
+        let other_seed = FaultConfig { seed: 43, ..config };
+        assert_ne!(first, transfer(other_seed, 100).await);
+    }
+
+    #[tokio::test]
+    async fn reordering() {
+        let config = FaultConfig {
+            reorder_rate: 0.2,
+            reorder_delay: Duration::from_millis(5),
+            delay: Delay::Constant(Duration::from_millis(1)),
+            ..Default::default()
+        };
+        let (mut drain, mut sink) = fault_channel(config);
+        for i in 0..50 {
+            drain.send(msg(i)).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        drop(drain);
+        let mut received = vec![];
+        while let Ok(data) = sink.recv().await {
+            received.push(data[0]);
+        }
+        let mut sorted = received.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..50).collect::<Vec<_>>());
+        assert_ne!(received, sorted);
+    }
+
+    #[tokio::test]
+    async fn delay_and_bandwidth() {
+        let config = FaultConfig {
+            delay: Delay::Uniform(Duration::from_millis(20), Duration::from_millis(30)),
+            bandwidth: Some(10_000),
+            ..Default::default()
+        };
+        let (mut drain, mut sink) = fault_channel(config);
+        let start = Instant::now();
+        for i in 0..5 {
+            drain.send(msg(i)).await.unwrap();
+        }
+        // 500 bytes at 10kB/s take 50ms, the first 40ms of it blocked `send`
+        assert!(start.elapsed() >= Duration::from_millis(40));
+        for i in 0..5 {
+            assert_eq!(sink.recv().await.unwrap()[0], i);
+        }
+        assert!(start.elapsed() >= Duration::from_millis(70));
+    }
+
+    #[tokio::test]
+    async fn closed_sink() {
+        let (mut drain, sink) = fault_channel(FaultConfig::default());
+        drop(sink);
+        assert_eq!(drain.send(msg(0)).await, Err(ProtocolError::Closed));
+    }
+}
@@ -10,8 +10,13 @@ const FRAME_CLOSE_STREAM: u8 = 5;
 const FRAME_DATA_HEADER: u8 = 6;
 const FRAME_DATA: u8 = 7;
 const FRAME_RAW: u8 = 8;
+const FRAME_ACK: u8 = 9;
 //const FRAME_RESERVED_2: u8 = 10;
+const FRAME_FRAGMENT: u8 = 11;
+const FRAME_KEY_EXCHANGE: u8 = 12;
 //const FRAME_RESERVED_3: u8 = 13;
+const FRAME_PING: u8 = 14;
+const FRAME_PONG: u8 = 15;
 
 /// Used for Communication between Channel <----(TCP/UDP)----> Channel
 #[derive(Debug, PartialEq, Clone)]
@@ -20,9 +25,16 @@ pub enum InitFrame {
         magic_number: [u8; 7],
         version: [u32; 3],
     },
+    /// Public keys of the ephemeral key exchange and of the long-term identity
+    KeyExchange {
+        public_key: [u8; 32],
+        identity: [u8; 32],
+    },
+    /// `signature` is made with the identity sent in `KeyExchange`
     Init {
         pid: Pid,
         secret: u128,
+        signature: [u8; 64],
     },
     /// WARNING: sending RAW is only for debug purposes and will drop the
     /// connection
@@ -52,6 +64,14 @@ pub enum OTFrame {
         mid: Mid,
         data: Bytes,
     },
+    /// Asks the remote side to return `token` in a `Pong`, to measure the round
+    /// trip time
+    Ping {
+        token: u64,
+    },
+    Pong {
+        token: u64,
+    },
 }
 
 /// Used for IN TCP Communication between Channel <--(TCP)-- Channel
@@ -77,12 +97,51 @@ pub enum ITFrame {
         mid: Mid,
         data: BytesMut,
     },
+    /// Asks the remote side to return `token` in a `Pong`, to measure the round
+    /// trip time
+    Ping {
+        token: u64,
+    },
+    Pong {
+        token: u64,
+    },
+}
+
+/// Used for Communication between Channel <--(UDP)--> Channel. Unlike the TCP
+/// frames, every frame is self-contained, as packets can get lost or arrive out
+/// of order.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) enum UdpFrame {
+    Shutdown,
+    OpenStream {
+        sid: Sid,
+        prio: Prio,
+        promises: Promises,
+        guaranteed_bandwidth: Bandwidth,
+    },
+    CloseStream {
+        sid: Sid,
+    },
+    /// Confirms that the reliable packet with sequence number `seq` arrived
+    Ack {
+        seq: u64,
+    },
+    /// Part of the message with the stream-local id `smid`, starting at
+    /// `offset` of the `length` bytes of the message
+    Fragment {
+        sid: Sid,
+        smid: Mid,
+        length: u64,
+        offset: u64,
+        data: Bytes,
+    },
 }
 
 impl InitFrame {
     // Size WITHOUT the 1rst indicating byte
     pub(crate) const HANDSHAKE_CNS: usize = 19;
-    pub(crate) const INIT_CNS: usize = 32;
+    pub(crate) const INIT_CNS: usize = 96;
+    pub(crate) const KEY_EXCHANGE_CNS: usize = 64;
     /// const part of the RAW frame, actual size is variable
     pub(crate) const RAW_CNS: usize = 2;
 
@@ -99,10 +158,23 @@ impl InitFrame {
                 bytes.put_u32_le(version[1]);
                 bytes.put_u32_le(version[2]);
             },
-            InitFrame::Init { pid, secret } => {
+            InitFrame::KeyExchange {
+                public_key,
+                identity,
+            } => {
+                bytes.put_u8(FRAME_KEY_EXCHANGE);
+                bytes.put_slice(&public_key);
+                bytes.put_slice(&identity);
+            },
+            InitFrame::Init {
+                pid,
+                secret,
+                signature,
+            } => {
                 bytes.put_u8(FRAME_INIT);
                 pid.to_bytes(bytes);
                 bytes.put_u128_le(secret);
+                bytes.put_slice(&signature);
             },
             InitFrame::Raw(data) => {
                 bytes.put_u8(FRAME_RAW);
@@ -136,9 +208,28 @@ impl InitFrame {
                     return None;
                 }
                 bytes.advance(1);
+                let pid = Pid::from_bytes(bytes);
+                let secret = bytes.get_u128_le();
+                let mut signature = [0u8; 64];
+                bytes.copy_to_slice(&mut signature);
                 InitFrame::Init {
-                    pid: Pid::from_bytes(bytes),
-                    secret: bytes.get_u128_le(),
+                    pid,
+                    secret,
+                    signature,
+                }
+            },
+            FRAME_KEY_EXCHANGE => {
+                if bytes.len() < Self::KEY_EXCHANGE_CNS + 1 {
+                    return None;
+                }
+                bytes.advance(1);
+                let mut public_key = [0u8; 32];
+                bytes.copy_to_slice(&mut public_key);
+                let mut identity = [0u8; 32];
+                bytes.copy_to_slice(&mut identity);
+                InitFrame::KeyExchange {
+                    public_key,
+                    identity,
                 }
             },
             FRAME_RAW => {
@@ -164,6 +255,7 @@ pub(crate) const TCP_CLOSE_STREAM_CNS: usize = 8;
 pub(crate) const TCP_DATA_CNS: usize = 10;
 pub(crate) const TCP_DATA_HEADER_CNS: usize = 24;
 pub(crate) const TCP_OPEN_STREAM_CNS: usize = 18;
+pub(crate) const TCP_PING_CNS: usize = 8;
 // Size WITHOUT the 1rst indicating byte
 pub(crate) const TCP_SHUTDOWN_CNS: usize = 0;
 
@@ -201,6 +293,14 @@ impl OTFrame {
                 bytes.put_u16_le(data.len() as u16);
                 bytes.put_slice(&data);
             },
+            Self::Ping { token } => {
+                bytes.put_u8(FRAME_PING);
+                bytes.put_u64_le(token);
+            },
+            Self::Pong { token } => {
+                bytes.put_u8(FRAME_PONG);
+                bytes.put_u64_le(token);
+            },
         }
     }
 }
@@ -222,6 +322,7 @@ impl ITFrame {
                 }
                 u16::from_le_bytes([bytes[8 + 1], bytes[9 + 1]]) as usize + TCP_DATA_CNS
             },
+            FRAME_PING | FRAME_PONG => TCP_PING_CNS,
             _ => return None,
         };
 
@@ -268,6 +369,138 @@ impl ITFrame {
                 let data = bytes.split_to(length as usize);
                 Self::Data { mid, data }
             },
+            FRAME_PING => {
+                let mut bytes = bytes.split_to(size + 1);
+                bytes.advance(1);
+                Self::Ping {
+                    token: bytes.get_u64_le(),
+                }
+            },
+            FRAME_PONG => {
+                let mut bytes = bytes.split_to(size + 1);
+                bytes.advance(1);
+                Self::Pong {
+                    token: bytes.get_u64_le(),
+                }
+            },
+            _ => unreachable!("Frame::to_frame should be handled before!"),
+        };
+        Some(frame)
+    }
+}
+
+pub(crate) const UDP_ACK_CNS: usize = 8;
+/// const part of the FRAGMENT frame, actual size is variable
+pub(crate) const UDP_FRAGMENT_CNS: usize = 34;
+
+impl UdpFrame {
+    /// Size of the frame including the first indicating byte
+    pub(crate) fn size(&self) -> usize {
+        1 + match self {
+            Self::Shutdown => TCP_SHUTDOWN_CNS,
+            Self::OpenStream { .. } => TCP_OPEN_STREAM_CNS,
+            Self::CloseStream { .. } => TCP_CLOSE_STREAM_CNS,
+            Self::Ack { .. } => UDP_ACK_CNS,
+            Self::Fragment { data, .. } => UDP_FRAGMENT_CNS + data.len(),
+        }
+    }
+
+    pub(crate) fn write_bytes(&self, bytes: &mut BytesMut) {
+        match self {
+            Self::Shutdown => {
+                bytes.put_u8(FRAME_SHUTDOWN);
+            },
+            Self::OpenStream {
+                sid,
+                prio,
+                promises,
+                guaranteed_bandwidth,
+            } => {
+                bytes.put_u8(FRAME_OPEN_STREAM);
+                sid.to_bytes(bytes);
+                bytes.put_u8(*prio);
+                bytes.put_u8(promises.to_le_bytes()[0]);
+                bytes.put_u64_le(*guaranteed_bandwidth);
+            },
+            Self::CloseStream { sid } => {
+                bytes.put_u8(FRAME_CLOSE_STREAM);
+                sid.to_bytes(bytes);
+            },
+            Self::Ack { seq } => {
+                bytes.put_u8(FRAME_ACK);
+                bytes.put_u64_le(*seq);
+            },
+            Self::Fragment {
+                sid,
+                smid,
+                length,
+                offset,
+                data,
+            } => {
+                bytes.put_u8(FRAME_FRAGMENT);
+                sid.to_bytes(bytes);
+                bytes.put_u64_le(*smid);
+                bytes.put_u64_le(*length);
+                bytes.put_u64_le(*offset);
+                bytes.put_u16_le(data.len() as u16);
+                bytes.put_slice(data);
+            },
+        }
+    }
+
+    /// Reads the next frame of a packet, returns `None` if the rest of the
+    /// packet is empty or invalid
+    pub(crate) fn read_frame(bytes: &mut BytesMut) -> Option<Self> {
+        let frame_no = *bytes.first()?;
+        let size = match frame_no {
+            FRAME_SHUTDOWN => TCP_SHUTDOWN_CNS,
+            FRAME_OPEN_STREAM => TCP_OPEN_STREAM_CNS,
+            FRAME_CLOSE_STREAM => TCP_CLOSE_STREAM_CNS,
+            FRAME_ACK => UDP_ACK_CNS,
+            FRAME_FRAGMENT => {
+                if bytes.len() < UDP_FRAGMENT_CNS + 1 {
+                    return None;
+                }
+                u16::from_le_bytes([bytes[UDP_FRAGMENT_CNS - 1], bytes[UDP_FRAGMENT_CNS]]) as usize
+                    + UDP_FRAGMENT_CNS
+            },
+            _ => return None,
+        };
+
+        if bytes.len() < size + 1 {
+            return None;
+        }
+
+        let mut bytes = bytes.split_to(size + 1);
+        bytes.advance(1);
+        let frame = match frame_no {
+            FRAME_SHUTDOWN => Self::Shutdown,
+            FRAME_OPEN_STREAM => Self::OpenStream {
+                sid: Sid::from_bytes(&mut bytes),
+                prio: bytes.get_u8(),
+                promises: Promises::from_bits_truncate(bytes.get_u8()),
+                guaranteed_bandwidth: bytes.get_u64_le(),
+            },
+            FRAME_CLOSE_STREAM => Self::CloseStream {
+                sid: Sid::from_bytes(&mut bytes),
+            },
+            FRAME_ACK => Self::Ack {
+                seq: bytes.get_u64_le(),
+            },
+            FRAME_FRAGMENT => {
+                let sid = Sid::from_bytes(&mut bytes);
+                let smid = bytes.get_u64_le();
+                let length = bytes.get_u64_le();
+                let offset = bytes.get_u64_le();
+                let data_length = bytes.get_u16_le() as usize;
+                Self::Fragment {
+                    sid,
+                    smid,
+                    length,
+                    offset,
+                    data: bytes.split_to(data_length).freeze(),
+                }
+            },
             _ => unreachable!("Frame::to_frame should be handled before!"),
         };
         Some(frame)
@@ -295,6 +528,8 @@ impl PartialEq<ITFrame> for OTFrame {
                 matches!(other, ITFrame::DataHeader { mid, sid, length })
             },
             Self::Data { mid, data } => matches!(other, ITFrame::Data { mid, data }),
+            Self::Ping { token } => matches!(other, ITFrame::Ping { token }),
+            Self::Pong { token } => matches!(other, ITFrame::Pong { token }),
         }
     }
 }
@@ -310,9 +545,14 @@ mod tests {
                 magic_number: VELOREN_MAGIC_NUMBER,
                 version: VELOREN_NETWORK_VERSION,
             },
+            InitFrame::KeyExchange {
+                public_key: [1u8; 32],
+                identity: [2u8; 32],
+            },
             InitFrame::Init {
                 pid: Pid::fake(0),
                 secret: 0u128,
+                signature: [3u8; 64],
             },
             InitFrame::Raw(vec![1, 2, 3]),
         ]
@@ -339,6 +579,8 @@ mod tests {
                 mid: 0,
                 data: Bytes::from(&[42u8; 16][..]),
             },
+            OTFrame::Ping { token: 42 },
+            OTFrame::Pong { token: 42 },
             OTFrame::CloseStream {
                 sid: Sid::new(1337),
             },
@@ -420,6 +662,58 @@ mod tests {
         }
     }
 
+    #[test]
+    fn udpframe_multiple() {
+        let frames = vec![
+            UdpFrame::OpenStream {
+                sid: Sid::new(1337),
+                prio: 3,
+                promises: Promises::GUARANTEED_DELIVERY | Promises::ORDERED,
+                guaranteed_bandwidth: 1_000,
+            },
+            UdpFrame::Ack { seq: 42 },
+            UdpFrame::Fragment {
+                sid: Sid::new(1337),
+                smid: 7,
+                length: 100,
+                offset: 80,
+                data: Bytes::from(&[77u8; 20][..]),
+            },
+            UdpFrame::CloseStream {
+                sid: Sid::new(1337),
+            },
+            UdpFrame::Shutdown,
+        ];
+        let mut buffer = BytesMut::with_capacity(1500);
+        for f in &frames {
+            f.write_bytes(&mut buffer);
+        }
+        assert_eq!(
+            buffer.len(),
+            frames.iter().map(UdpFrame::size).sum::<usize>()
+        );
+
+        for f in frames {
+            assert_eq!(Some(f), UdpFrame::read_frame(&mut buffer));
+        }
+        assert_eq!(UdpFrame::read_frame(&mut buffer), None);
+    }
+
+    #[test]
+    fn udpframe_truncated_fragment() {
+        let mut buffer = BytesMut::with_capacity(100);
+        UdpFrame::Fragment {
+            sid: Sid::new(1),
+            smid: 0,
+            length: 20,
+            offset: 0,
+            data: Bytes::from(&[1u8; 20][..]),
+        }
+        .write_bytes(&mut buffer);
+        buffer.truncate(40);
+        assert_eq!(UdpFrame::read_frame(&mut buffer), None);
+    }
+
     #[test]
     fn frame_exact_size() {
         const SIZE: usize = TCP_CLOSE_STREAM_CNS+1/*first byte*/;
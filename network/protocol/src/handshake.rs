@@ -1,4 +1,5 @@
 use crate::{
+    crypto::{self, Identity, KeyExchange, RecvCipher, SendCipher},
     error::{InitProtocolError, ProtocolError},
     frame::InitFrame,
     types::{
@@ -22,6 +23,11 @@ use tracing::{debug, error, info, trace};
 #[async_trait]
 pub trait ReliableDrain {
     async fn send(&mut self, frame: InitFrame) -> Result<(), ProtocolError>;
+    /// Called once the handshake succeeded, use it to seal messages of streams
+    /// with [`Promises::ENCRYPTED`]
+    ///
+    /// [`Promises::ENCRYPTED`]: crate::Promises::ENCRYPTED
+    fn set_cipher(&mut self, cipher: SendCipher);
 }
 
 /// Implement this for auto Handshake with [`ReliableDrain`]. See
@@ -31,6 +37,24 @@ pub trait ReliableDrain {
 #[async_trait]
 pub trait ReliableSink {
     async fn recv(&mut self) -> Result<InitFrame, ProtocolError>;
+    /// Counterpart of [`ReliableDrain::set_cipher`]
+    ///
+    /// [`ReliableDrain::set_cipher`]: crate::ReliableDrain::set_cipher
+    fn set_cipher(&mut self, cipher: RecvCipher);
+}
+
+const ERR_S: &str = "Got A Raw Message, these are usually Debug Messages indicating that \
+                     something went wrong on network layer and connection will be closed";
+
+fn unexpected_frame(frame: InitFrame) -> InitProtocolError {
+    match frame {
+        InitFrame::Raw(bytes) => match std::str::from_utf8(bytes.as_slice()) {
+            Ok(string) => error!(?string, ERR_S),
+            _ => error!(?bytes, ERR_S),
+        },
+        _ => info!("Handshake failed"),
+    }
+    InitProtocolError::Closed
 }
 
 #[async_trait]
@@ -44,7 +68,8 @@ where
         initializer: bool,
         local_pid: Pid,
         local_secret: u128,
-    ) -> Result<(Pid, Sid, u128), InitProtocolError> {
+        identity: &Identity,
+    ) -> Result<(Pid, Sid, u128, [u8; 32]), InitProtocolError> {
         #[cfg(debug_assertions)]
         const WRONG_NUMBER: &str = "Handshake does not contain the magic number required by \
                                     veloren server.\nWe are not sure if you are a valid veloren \
@@ -53,11 +78,11 @@ where
         const WRONG_VERSION: &str = "Handshake does contain a correct magic number, but invalid \
                                      version.\nWe don't know how to communicate with \
                                      you.\nClosing the connection";
-        const ERR_S: &str = "Got A Raw Message, these are usually Debug Messages indicating that \
-                             something went wrong on network layer and connection will be closed";
 
         let drain = &mut self.0;
         let sink = &mut self.1;
+        let key_exchange = KeyExchange::new();
+        let local_keys = (key_exchange.public_key(), identity.public_key());
 
         if initializer {
             drain
@@ -99,14 +124,7 @@ where
                     Err(InitProtocolError::WrongVersion(version))
                 } else {
                     trace!("Handshake Frame completed");
-                    if initializer {
-                        drain
-                            .send(InitFrame::Init {
-                                pid: local_pid,
-                                secret: local_secret,
-                            })
-                            .await?;
-                    } else {
+                    if !initializer {
                         drain
                             .send(InitFrame::Handshake {
                                 magic_number: VELOREN_MAGIC_NUMBER,
@@ -117,47 +135,84 @@ where
                     Ok(())
                 }
             },
-            InitFrame::Raw(bytes) => {
-                match std::str::from_utf8(bytes.as_slice()) {
-                    Ok(string) => error!(?string, ERR_S),
-                    _ => error!(?bytes, ERR_S),
-                }
-                Err(InitProtocolError::Closed)
-            },
-            _ => {
-                info!("Handshake failed");
-                Err(InitProtocolError::Closed)
-            },
+            frame => Err(unexpected_frame(frame)),
         }?;
 
+        // Only one frame is in flight at any time, so the handshake also works if
+        // the frames of the underlying protocol can get reordered
+        let local_key_exchange = InitFrame::KeyExchange {
+            public_key: local_keys.0,
+            identity: local_keys.1,
+        };
+        if initializer {
+            drain.send(local_key_exchange.clone()).await?;
+        }
+        let remote_keys = match sink.recv().await? {
+            InitFrame::KeyExchange {
+                public_key,
+                identity,
+            } => (public_key, identity),
+            frame => return Err(unexpected_frame(frame)),
+        };
+        if !initializer {
+            drain.send(local_key_exchange).await?;
+        }
+        let transcript = if initializer {
+            crypto::transcript(
+                (&local_keys.0, &local_keys.1),
+                (&remote_keys.0, &remote_keys.1),
+            )
+        } else {
+            crypto::transcript(
+                (&remote_keys.0, &remote_keys.1),
+                (&local_keys.0, &local_keys.1),
+            )
+        };
+        let local_init = InitFrame::Init {
+            pid: local_pid,
+            secret: local_secret,
+            signature: identity.sign(&crypto::init_message(
+                &transcript,
+                initializer,
+                local_pid,
+                local_secret,
+            )),
+        };
+
+        if initializer {
+            drain.send(local_init.clone()).await?;
+        }
+
         match sink.recv().await? {
-            InitFrame::Init { pid, secret } => {
+            InitFrame::Init {
+                pid,
+                secret,
+                signature,
+            } => {
                 debug!(?pid, "Participant send their ID");
+                let message = crypto::init_message(&transcript, !initializer, pid, secret);
+                if !crypto::verify(&remote_keys.1, &message, &signature) {
+                    error!(
+                        ?pid,
+                        "Handshake of remote side isn't signed by its identity"
+                    );
+                    return Err(InitProtocolError::AuthenticationFailed);
+                }
+                let (send_cipher, recv_cipher) = key_exchange
+                    .finish(&remote_keys.0, &transcript, initializer)
+                    .ok_or(InitProtocolError::AuthenticationFailed)?;
                 let stream_id_offset = if initializer {
                     STREAM_ID_OFFSET1
                 } else {
-                    drain
-                        .send(InitFrame::Init {
-                            pid: local_pid,
-                            secret: local_secret,
-                        })
-                        .await?;
+                    drain.send(local_init).await?;
                     STREAM_ID_OFFSET2
                 };
+                drain.set_cipher(send_cipher);
+                sink.set_cipher(recv_cipher);
                 info!(?pid, "This Handshake is now configured!");
-                Ok((pid, stream_id_offset, secret))
-            },
-            InitFrame::Raw(bytes) => {
-                match std::str::from_utf8(bytes.as_slice()) {
-                    Ok(string) => error!(?string, ERR_S),
-                    _ => error!(?bytes, ERR_S),
-                }
-                Err(InitProtocolError::Closed)
-            },
-            _ => {
-                info!("Handshake failed");
-                Err(InitProtocolError::Closed)
+                Ok((pid, stream_id_offset, secret, remote_keys.1))
             },
+            frame => Err(unexpected_frame(frame)),
         }
     }
 }
@@ -170,7 +225,10 @@ mod tests {
     #[tokio::test]
     async fn handshake_drop_start() {
         let [mut p1, p2] = ac_bound(10, None);
-        let r1 = tokio::spawn(async move { p1.initialize(true, Pid::fake(2), 1337).await });
+        let r1 = tokio::spawn(async move {
+            p1.initialize(true, Pid::fake(2), 1337, &Identity::generate())
+                .await
+        });
         let r2 = tokio::spawn(async move {
             let _ = p2;
         });
@@ -181,7 +239,10 @@ mod tests {
     #[tokio::test]
     async fn handshake_wrong_magic_number() {
         let [mut p1, mut p2] = ac_bound(10, None);
-        let r1 = tokio::spawn(async move { p1.initialize(true, Pid::fake(2), 1337).await });
+        let r1 = tokio::spawn(async move {
+            p1.initialize(true, Pid::fake(2), 1337, &Identity::generate())
+                .await
+        });
         let r2 = tokio::spawn(async move {
             let _ = p2.1.recv().await?;
             p2.0.send(InitFrame::Handshake {
@@ -203,7 +264,10 @@ mod tests {
     #[tokio::test]
     async fn handshake_wrong_version() {
         let [mut p1, mut p2] = ac_bound(10, None);
-        let r1 = tokio::spawn(async move { p1.initialize(true, Pid::fake(2), 1337).await });
+        let r1 = tokio::spawn(async move {
+            p1.initialize(true, Pid::fake(2), 1337, &Identity::generate())
+                .await
+        });
         let r2 = tokio::spawn(async move {
             let _ = p2.1.recv().await?;
             p2.0.send(InitFrame::Handshake {
@@ -223,7 +287,10 @@ mod tests {
     #[tokio::test]
     async fn handshake_unexpected_raw() {
         let [mut p1, mut p2] = ac_bound(10, None);
-        let r1 = tokio::spawn(async move { p1.initialize(true, Pid::fake(2), 1337).await });
+        let r1 = tokio::spawn(async move {
+            p1.initialize(true, Pid::fake(2), 1337, &Identity::generate())
+                .await
+        });
         let r2 = tokio::spawn(async move {
             let _ = p2.1.recv().await?;
             p2.0.send(InitFrame::Handshake {
@@ -239,4 +306,38 @@ mod tests {
         assert_eq!(r1.unwrap(), Err(InitProtocolError::Closed));
         assert_eq!(r2.unwrap(), Ok(()));
     }
+
+    #[tokio::test]
+    async fn handshake_forged_signature() {
+        let [mut p1, mut p2] = ac_bound(10, None);
+        let r1 = tokio::spawn(async move {
+            p1.initialize(true, Pid::fake(2), 1337, &Identity::generate())
+                .await
+        });
+        let r2 = tokio::spawn(async move {
+            let _ = p2.1.recv().await?;
+            p2.0.send(InitFrame::Handshake {
+                magic_number: VELOREN_MAGIC_NUMBER,
+                version: VELOREN_NETWORK_VERSION,
+            })
+            .await?;
+            let _ = p2.1.recv().await?;
+            p2.0.send(InitFrame::KeyExchange {
+                public_key: KeyExchange::new().public_key(),
+                identity: Identity::generate().public_key(),
+            })
+            .await?;
+            let _ = p2.1.recv().await?;
+            p2.0.send(InitFrame::Init {
+                pid: Pid::fake(3),
+                secret: 42,
+                signature: [0u8; 64],
+            })
+            .await?;
+            Result::<(), InitProtocolError>::Ok(())
+        });
+        let (r1, r2) = tokio::join!(r1, r2);
+        assert_eq!(r1.unwrap(), Err(InitProtocolError::AuthenticationFailed));
+        assert_eq!(r2.unwrap(), Ok(()));
+    }
 }
@@ -13,9 +13,8 @@
 //! This crate currently defines:
 //!  - TCP
 //!  - MPSC
-//!
-//! a UDP implementation will quickly follow, and it's also possible to abstract
-//! over QUIC.
+//!  - UDP
+//!  - QUIC
 //!
 //! warning: don't mix protocol, using the TCP variant for actual UDP socket
 //! will result in dropped data  using UDP with a TCP socket will be a waste of
@@ -37,10 +36,16 @@
 //! implement `ReliableDrain` and `ReliableSink`, by this, you use the default
 //! Handshake.
 //!
+//! The default Handshake also authenticates both sides with their [`Identity`]
+//! and agrees on keys used for streams with [`Promises::ENCRYPTED`].
+//!
 //! This crate also contains consts and definitions for the network protocol.
 //!
 //! For an *example* see `TcpDrain` and `TcpSink` in the [tcp.rs](tcp.rs)
 //!
+//! With the `fault_injection` feature, [`fault_channel`] creates a Drain and
+//! Sink that drop, delay and reorder data like a bad connection would.
+//!
 //! [`UnreliableDrain`]: crate::UnreliableDrain
 //! [`UnreliableSink`]: crate::UnreliableSink
 //! [`Vec<u8>`]: std::vec::Vec
@@ -48,26 +53,39 @@
 //! [`SendProtocol`]: crate::SendProtocol
 //! [`RecvProtocol`]: crate::RecvProtocol
 //! [`InitProtocol`]: crate::InitProtocol
+//! [`Identity`]: crate::Identity
+//! [`Promises::ENCRYPTED`]: crate::Promises::ENCRYPTED
+//! [`fault_channel`]: crate::fault_channel
 
+mod crypto;
 mod error;
 mod event;
+#[cfg(any(test, feature = "fault_injection"))]
+mod fault;
 mod frame;
 mod handshake;
 mod message;
 mod metrics;
 mod mpsc;
 mod prio;
+mod quic;
 mod tcp;
 mod types;
+mod udp;
 
+pub use crypto::Identity;
 pub use error::{InitProtocolError, ProtocolError};
 pub use event::ProtocolEvent;
-pub use metrics::ProtocolMetricCache;
+#[cfg(any(test, feature = "fault_injection"))]
+pub use fault::{fault_channel, Delay, FaultConfig, FaultDrain, FaultSink};
 #[cfg(feature = "metrics")]
 pub use metrics::ProtocolMetrics;
+pub use metrics::{ChannelStats, ProtocolMetricCache};
 pub use mpsc::{MpscMsg, MpscRecvProtocol, MpscSendProtocol};
-pub use tcp::{TcpRecvProtocol, TcpSendProtocol};
+pub use quic::{QuicDataFormat, QuicRecvProtocol, QuicSendProtocol};
+pub use tcp::{tcp_protocols, TcpRecvProtocol, TcpSendProtocol};
 pub use types::{Bandwidth, Cid, Pid, Prio, Promises, Sid, HIGHEST_PRIO, VELOREN_NETWORK_VERSION};
+pub use udp::{udp_protocols, UdpRecvProtocol, UdpSendProtocol, MAX_PACKET_SIZE};
 
 ///use at own risk, might change any time, for internal benchmarks
 pub mod _internal {
@@ -84,7 +102,8 @@ pub trait InitProtocol {
         initializer: bool,
         local_pid: Pid,
         secret: u128,
-    ) -> Result<(Pid, Sid, u128), InitProtocolError>;
+        identity: &Identity,
+    ) -> Result<(Pid, Sid, u128, [u8; 32]), InitProtocolError>;
 }
 
 /// Generic Network Send Protocol.
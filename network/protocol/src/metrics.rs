@@ -1,4 +1,4 @@
-use crate::types::Sid;
+use crate::types::{Prio, Sid, HIGHEST_PRIO};
 #[cfg(feature = "metrics")]
 use prometheus::{
     core::{AtomicI64, AtomicU64, GenericCounter, GenericGauge},
@@ -6,7 +6,14 @@ use prometheus::{
 };
 #[cfg(feature = "metrics")]
 use std::collections::HashMap;
-use std::{error::Error, sync::Arc};
+use std::{
+    error::Error,
+    sync::{
+        atomic::{self, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 #[allow(dead_code)]
 pub enum RemoveReason {
@@ -56,8 +63,32 @@ pub struct ProtocolMetrics {
     rdata_frames_t: IntCounterVec,
     /// data frames bytes send by prio by CHANNEL,
     rdata_frames_b: IntCounterVec,
-    /// ping per CHANNEL //TODO: implement
+    /// round trip time in microseconds per CHANNEL,
     ping: IntGaugeVec,
+    /// packets send again, because they weren't acknowledged in time, by
+    /// CHANNEL,
+    retransmissions: IntCounterVec,
+    /// messages queued to be send by CHANNEL,
+    squeue_t: IntGaugeVec,
+    /// messages bytes queued to be send by CHANNEL,
+    squeue_b: IntGaugeVec,
+    /// data frames bytes send by CHANNEL AND PRIO,
+    sdata_prio_b: IntCounterVec,
+}
+
+/// Link quality of a single channel, shared by its send and recv protocol.
+/// Unlike the [`ProtocolMetrics`] they are always collected, so they can be
+/// shown to the user.
+///
+/// [`ProtocolMetrics`]: crate::ProtocolMetrics
+#[derive(Debug, Default)]
+pub struct ChannelStats {
+    /// in microseconds, 0 as long as it wasn't measured
+    rtt: atomic::AtomicU64,
+    retransmissions: atomic::AtomicU64,
+    queued_messages: atomic::AtomicU64,
+    queued_bytes: atomic::AtomicU64,
+    sent_bytes: [atomic::AtomicU64; HIGHEST_PRIO as usize + 1],
 }
 
 /// Cache for [`ProtocolMetrics`], more optimized and cleared up after channel
@@ -74,12 +105,15 @@ pub struct ProtocolMetricCache {
     sdata_frames_b: GenericCounter<AtomicU64>,
     rdata_frames_t: GenericCounter<AtomicU64>,
     rdata_frames_b: GenericCounter<AtomicU64>,
-    ping: GenericGauge<AtomicI64>,
+    channel: Arc<ChannelLine>,
+    stats: Arc<ChannelStats>,
 }
 
 #[cfg(not(feature = "metrics"))]
 #[derive(Debug, Clone)]
-pub struct ProtocolMetricCache {}
+pub struct ProtocolMetricCache {
+    stats: Arc<ChannelStats>,
+}
 
 #[cfg(feature = "metrics")]
 impl ProtocolMetrics {
@@ -173,7 +207,39 @@ impl ProtocolMetrics {
             ),
             &["channel"],
         )?;
-        let ping = IntGaugeVec::new(Opts::new("ping", "Ping per channel"), &["channel"])?;
+        let ping = IntGaugeVec::new(
+            Opts::new("ping", "Round trip time per channel in microseconds"),
+            &["channel"],
+        )?;
+        let retransmissions = IntCounterVec::new(
+            Opts::new(
+                "send_retransmissions_total",
+                "Number of packets send again per channel, because they weren't acknowledged in \
+                 time",
+            ),
+            &["channel"],
+        )?;
+        let squeue_t = IntGaugeVec::new(
+            Opts::new(
+                "send_queue_messages",
+                "Number of messages queued to be send per channel",
+            ),
+            &["channel"],
+        )?;
+        let squeue_b = IntGaugeVec::new(
+            Opts::new(
+                "send_queue_bytes",
+                "Number of message bytes queued to be send per channel",
+            ),
+            &["channel"],
+        )?;
+        let sdata_prio_b = IntCounterVec::new(
+            Opts::new(
+                "send_data_prio_throughput",
+                "Number of data frames bytes send per channel and prio",
+            ),
+            &["channel", "prio"],
+        )?;
 
         Ok(Self {
             smsg_it,
@@ -189,6 +255,10 @@ impl ProtocolMetrics {
             rdata_frames_t,
             rdata_frames_b,
             ping,
+            retransmissions,
+            squeue_t,
+            squeue_b,
+            sdata_prio_b,
         })
     }
 
@@ -206,6 +276,10 @@ impl ProtocolMetrics {
         registry.register(Box::new(self.rdata_frames_t.clone()))?;
         registry.register(Box::new(self.rdata_frames_b.clone()))?;
         registry.register(Box::new(self.ping.clone()))?;
+        registry.register(Box::new(self.retransmissions.clone()))?;
+        registry.register(Box::new(self.squeue_t.clone()))?;
+        registry.register(Box::new(self.squeue_b.clone()))?;
+        registry.register(Box::new(self.sdata_prio_b.clone()))?;
         Ok(())
     }
 }
@@ -226,6 +300,102 @@ pub(crate) struct CacheLine {
     pub rmsg_ob: [GenericCounter<AtomicU64>; 2],
 }
 
+/// Metrics of a whole channel, they are removed once the last clone of the
+/// [`ProtocolMetricCache`] is dropped.
+#[cfg(feature = "metrics")]
+#[derive(Debug)]
+struct ChannelLine {
+    cid: String,
+    m: Arc<ProtocolMetrics>,
+    ping: GenericGauge<AtomicI64>,
+    retransmissions: GenericCounter<AtomicU64>,
+    squeue_t: GenericGauge<AtomicI64>,
+    squeue_b: GenericGauge<AtomicI64>,
+    sdata_prio_b: Vec<GenericCounter<AtomicU64>>,
+}
+
+impl ChannelStats {
+    /// Round trip time of the channel, `None` as long as it wasn't measured.
+    /// MPSC channels never measure it.
+    pub fn rtt(&self) -> Option<Duration> {
+        match self.rtt.load(Ordering::Relaxed) {
+            0 => None,
+            micros => Some(Duration::from_micros(micros)),
+        }
+    }
+
+    /// Packets that were send again, because they weren't acknowledged in
+    /// time. Only counted by UDP, TCP and QUIC retransmit below the protocol.
+    pub fn retransmissions(&self) -> u64 { self.retransmissions.load(Ordering::Relaxed) }
+
+    /// Messages that wait to be send
+    pub fn queued_messages(&self) -> u64 { self.queued_messages.load(Ordering::Relaxed) }
+
+    /// Bytes of the messages that wait to be send
+    pub fn queued_bytes(&self) -> u64 { self.queued_bytes.load(Ordering::Relaxed) }
+
+    /// Message bytes that were send on streams with `prio`
+    pub fn sent_bytes(&self, prio: Prio) -> u64 {
+        self.sent_bytes
+            .get(prio as usize)
+            .map_or(0, |b| b.load(Ordering::Relaxed))
+    }
+
+    /// Message bytes that were send on all streams
+    pub fn sent_bytes_total(&self) -> u64 {
+        self.sent_bytes
+            .iter()
+            .map(|b| b.load(Ordering::Relaxed))
+            .sum()
+    }
+}
+
+impl ProtocolMetricCache {
+    /// Link quality of this channel, shared by all clones of this cache
+    pub fn stats(&self) -> Arc<ChannelStats> { Arc::clone(&self.stats) }
+
+    /// Protocols measure the round trip time on their own. This is only public
+    /// for channels whose transport knows it better, e.g. QUIC.
+    pub fn ping(&mut self, rtt: Duration) {
+        let micros = (rtt.as_micros() as u64).max(1);
+        self.stats.rtt.store(micros, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        self.channel.ping.set(micros as i64);
+    }
+
+    pub(crate) fn retransmits(&mut self, cnt: u64) {
+        self.stats.retransmissions.fetch_add(cnt, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        self.channel.retransmissions.inc_by(cnt);
+    }
+
+    pub(crate) fn squeue_in(&mut self, bytes: u64) {
+        self.stats.queued_messages.fetch_add(1, Ordering::Relaxed);
+        self.stats.queued_bytes.fetch_add(bytes, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        {
+            self.channel.squeue_t.inc();
+            self.channel.squeue_b.add(bytes as i64);
+        }
+    }
+
+    pub(crate) fn squeue_out(&mut self, cnt: u64, bytes: u64) {
+        self.stats.queued_messages.fetch_sub(cnt, Ordering::Relaxed);
+        self.stats.queued_bytes.fetch_sub(bytes, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        {
+            self.channel.squeue_t.sub(cnt as i64);
+            self.channel.squeue_b.sub(bytes as i64);
+        }
+    }
+
+    pub(crate) fn sdata_prio_b(&mut self, prio: Prio, bytes: u64) {
+        self.stats.sent_bytes[prio as usize].fetch_add(bytes, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        self.channel.sdata_prio_b[prio as usize].inc_by(bytes);
+    }
+}
+
 #[cfg(feature = "metrics")]
 impl ProtocolMetricCache {
     pub fn new(channel_key: &str, metrics: Arc<ProtocolMetrics>) -> Self {
@@ -234,7 +404,21 @@ impl ProtocolMetricCache {
         let sdata_frames_b = metrics.sdata_frames_b.with_label_values(&[&cid]);
         let rdata_frames_t = metrics.rdata_frames_t.with_label_values(&[&cid]);
         let rdata_frames_b = metrics.rdata_frames_b.with_label_values(&[&cid]);
-        let ping = metrics.ping.with_label_values(&[&cid]);
+        let channel = Arc::new(ChannelLine {
+            cid: cid.clone(),
+            m: Arc::clone(&metrics),
+            ping: metrics.ping.with_label_values(&[&cid]),
+            retransmissions: metrics.retransmissions.with_label_values(&[&cid]),
+            squeue_t: metrics.squeue_t.with_label_values(&[&cid]),
+            squeue_b: metrics.squeue_b.with_label_values(&[&cid]),
+            sdata_prio_b: (0..=HIGHEST_PRIO)
+                .map(|prio| {
+                    metrics
+                        .sdata_prio_b
+                        .with_label_values(&[&cid, &prio.to_string()])
+                })
+                .collect(),
+        });
         Self {
             cid,
             m: metrics,
@@ -243,7 +427,8 @@ impl ProtocolMetricCache {
             sdata_frames_b,
             rdata_frames_t,
             rdata_frames_b,
-            ping,
+            channel,
+            stats: Arc::new(ChannelStats::default()),
         }
     }
 
@@ -369,6 +554,23 @@ impl Drop for ProtocolMetricCache {
     }
 }
 
+#[cfg(feature = "metrics")]
+impl Drop for ChannelLine {
+    fn drop(&mut self) {
+        let cid = self.cid.as_str();
+        let m = &self.m;
+        let _ = m.ping.remove_label_values(&[cid]);
+        let _ = m.retransmissions.remove_label_values(&[cid]);
+        let _ = m.squeue_t.remove_label_values(&[cid]);
+        let _ = m.squeue_b.remove_label_values(&[cid]);
+        for prio in 0..=HIGHEST_PRIO {
+            let _ = m
+                .sdata_prio_b
+                .remove_label_values(&[cid, &prio.to_string()]);
+        }
+    }
+}
+
 #[cfg(feature = "metrics")]
 impl std::fmt::Debug for ProtocolMetrics {
     #[inline]
@@ -379,7 +581,11 @@ impl std::fmt::Debug for ProtocolMetrics {
 
 #[cfg(not(feature = "metrics"))]
 impl ProtocolMetricCache {
-    pub fn new(_channel_key: &str, _metrics: Arc<ProtocolMetrics>) -> Self { Self {} }
+    pub fn new(_channel_key: &str, _metrics: Arc<ProtocolMetrics>) -> Self {
+        Self {
+            stats: Arc::new(ChannelStats::default()),
+        }
+    }
 
     pub(crate) fn smsg_ib(&mut self, _sid: Sid, _b: u64) {}
 
@@ -1,16 +1,20 @@
 #[cfg(feature = "metrics")]
 use crate::metrics::RemoveReason;
 use crate::{
+    crypto::{RecvCipher, SendCipher},
     error::ProtocolError,
     event::ProtocolEvent,
     frame::InitFrame,
     handshake::{ReliableDrain, ReliableSink},
-    metrics::ProtocolMetricCache,
+    metrics::{ChannelStats, ProtocolMetricCache},
     types::Bandwidth,
     RecvProtocol, SendProtocol, UnreliableDrain, UnreliableSink,
 };
 use async_trait::async_trait;
-use std::time::{Duration, Instant};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 #[cfg(feature = "trace_pedantic")]
 use tracing::trace;
 
@@ -57,6 +61,9 @@ where
             metrics,
         }
     }
+
+    /// Link quality of this channel, see [`ChannelStats`]
+    pub fn stats(&self) -> Arc<ChannelStats> { self.metrics.stats() }
 }
 
 impl<S> MpscRecvProtocol<S>
@@ -143,6 +150,9 @@ where
     async fn send(&mut self, frame: InitFrame) -> Result<(), ProtocolError> {
         self.drain.send(MpscMsg::InitFrame(frame)).await
     }
+
+    /// Messages never leave the process, so there is nothing to encrypt
+    fn set_cipher(&mut self, _cipher: SendCipher) {}
 }
 
 #[async_trait]
@@ -156,6 +166,8 @@ where
             MpscMsg::InitFrame(f) => Ok(f),
         }
     }
+
+    fn set_cipher(&mut self, _cipher: RecvCipher) {}
 }
 
 #[cfg(test)]
@@ -224,16 +236,18 @@ mod tests {
     use crate::{
         mpsc::test_utils::*,
         types::{Pid, STREAM_ID_OFFSET1, STREAM_ID_OFFSET2},
-        InitProtocol,
+        Identity, InitProtocol,
     };
 
     #[tokio::test]
     async fn handshake_all_good() {
         let [mut p1, mut p2] = ac_bound(10, None);
-        let r1 = tokio::spawn(async move { p1.initialize(true, Pid::fake(2), 1337).await });
-        let r2 = tokio::spawn(async move { p2.initialize(false, Pid::fake(3), 42).await });
+        let (i1, i2) = (Identity::generate(), Identity::generate());
+        let (k1, k2) = (i1.public_key(), i2.public_key());
+        let r1 = tokio::spawn(async move { p1.initialize(true, Pid::fake(2), 1337, &i1).await });
+        let r2 = tokio::spawn(async move { p2.initialize(false, Pid::fake(3), 42, &i2).await });
         let (r1, r2) = tokio::join!(r1, r2);
-        assert_eq!(r1.unwrap(), Ok((Pid::fake(3), STREAM_ID_OFFSET1, 42)));
-        assert_eq!(r2.unwrap(), Ok((Pid::fake(2), STREAM_ID_OFFSET2, 1337)));
+        assert_eq!(r1.unwrap(), Ok((Pid::fake(3), STREAM_ID_OFFSET1, 42, k2)));
+        assert_eq!(r2.unwrap(), Ok((Pid::fake(2), STREAM_ID_OFFSET2, 1337, k1)));
     }
 }
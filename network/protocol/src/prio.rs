@@ -22,10 +22,21 @@ struct StreamInfo {
 /// every stream has a guaranteed bandwidth and a prio 0-7.
 /// when `n` Bytes are available in the buffer, first the guaranteed bandwidth
 /// is used. Then remaining bandwidth is used to fill up the prios.
+/// When the bandwidth is limited, lower prios are deferred till higher prios
+/// are sent.
 #[derive(Debug)]
 pub(crate) struct PrioManager {
     streams: HashMap<Sid, StreamInfo>,
     metrics: ProtocolMetricCache,
+    /// unused bytes of previous grabs, negative if frames exceeded the budget
+    credit: i64,
+}
+
+/// How much unused bandwidth can be saved up for later grabs
+const MAX_BURST: Duration = Duration::from_millis(100);
+
+fn bytes_in(bandwidth: Bandwidth, dt: Duration) -> i64 {
+    (bandwidth as f64 * dt.as_secs_f64()) as i64
 }
 
 // Send everything ONCE, then keep it till it's confirmed
@@ -35,6 +46,7 @@ impl PrioManager {
         Self {
             streams: HashMap::new(),
             metrics,
+            credit: 0,
         }
     }
 
@@ -66,6 +78,7 @@ impl PrioManager {
     pub fn is_empty(&self) -> bool { self.streams.is_empty() }
 
     pub fn add(&mut self, buffer: Bytes, mid: Mid, sid: Sid) {
+        self.metrics.squeue_in(buffer.len() as u64);
         self.streams
             .get_mut(&sid)
             .unwrap()
@@ -73,22 +86,33 @@ impl PrioManager {
             .push_back(OTMessage::new(buffer, mid, sid));
     }
 
-    /// bandwidth might be extended, as for technical reasons
-    /// guaranteed_bandwidth is used and frames are always 1400 bytes.
+    /// bandwidth might be extended, as for technical reasons frames are always
+    /// 1400 bytes. Those extra bytes are deducted from the next grab.
     pub fn grab(&mut self, bandwidth: Bandwidth, dt: Duration) -> (Vec<OTFrame>, Bandwidth) {
-        let total_bytes = (bandwidth as f64 * dt.as_secs_f64()) as u64;
+        let refill = bytes_in(bandwidth, dt);
+        let budget = self
+            .credit
+            .saturating_add(refill)
+            .min(bytes_in(bandwidth, MAX_BURST).max(refill));
+        let total_bytes = budget.max(0) as u64;
         let mut cur_bytes = 0u64;
         let mut frames = vec![];
 
         let mut prios = [0u64; (HIGHEST_PRIO + 1) as usize];
+        let mut prio_bytes = [0u64; (HIGHEST_PRIO + 1) as usize];
+        let mut finished_msgs = 0u64;
         let metrics = &mut self.metrics;
 
         let mut process_stream =
             |stream: &mut StreamInfo, mut bandwidth: i64, cur_bytes: &mut u64| {
+                if bandwidth <= 0 {
+                    return;
+                }
                 let mut finished = None;
                 'outer: for (i, msg) in stream.messages.iter_mut().enumerate() {
                     while let Some(frame) = msg.next() {
                         let b = if let OTFrame::Data { data, .. } = &frame {
+                            prio_bytes[stream.prio as usize] += data.len() as u64;
                             crate::frame::TCP_DATA_CNS + 1 + data.len()
                         } else {
                             crate::frame::TCP_DATA_HEADER_CNS + 1
@@ -102,6 +126,7 @@ impl PrioManager {
                     }
                     let (sid, bytes) = msg.get_sid_len();
                     metrics.smsg_ob(sid, RemoveReason::Finished, bytes);
+                    finished_msgs += 1;
                     finished = Some(i);
                 }
                 if let Some(i) = finished {
@@ -110,17 +135,20 @@ impl PrioManager {
                 }
             };
 
-        // Add guaranteed bandwidth
-        for stream in self.streams.values_mut() {
+        // Add guaranteed bandwidth, higher prios first in case it exceeds the budget
+        let mut streams = self.streams.values_mut().collect::<Vec<_>>();
+        streams.sort_unstable_by_key(|stream| stream.prio);
+        for stream in streams {
             prios[stream.prio as usize] += 1;
-            let stream_byte_cnt = (stream.guaranteed_bandwidth as f64 * dt.as_secs_f64()) as u64;
-            process_stream(stream, stream_byte_cnt as i64, &mut cur_bytes);
+            let stream_byte_cnt = bytes_in(stream.guaranteed_bandwidth, dt)
+                .min(total_bytes.saturating_sub(cur_bytes) as i64);
+            process_stream(stream, stream_byte_cnt, &mut cur_bytes);
         }
 
         if cur_bytes < total_bytes {
             // Add optional bandwidth
             for prio in 0..=HIGHEST_PRIO {
-                if prios[prio as usize] == 0 {
+                if prios[prio as usize] == 0 || cur_bytes >= total_bytes {
                     continue;
                 }
                 let per_stream_bytes = ((total_bytes - cur_bytes) / prios[prio as usize]) as i64;
@@ -132,6 +160,97 @@ impl PrioManager {
                 }
             }
         }
+
+        self.credit = budget.saturating_sub(cur_bytes as i64);
+        let sent_bytes = prio_bytes.iter().sum();
+        self.metrics.squeue_out(finished_msgs, sent_bytes);
+        for (prio, &bytes) in prio_bytes.iter().enumerate() {
+            if bytes > 0 {
+                self.metrics.sdata_prio_b(prio as Prio, bytes);
+            }
+        }
         (frames, cur_bytes)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::ProtocolMetrics;
+    use std::sync::Arc;
+
+    const TICK: Duration = Duration::from_millis(5);
+
+    fn prio_manager() -> PrioManager {
+        let metrics = Arc::new(ProtocolMetrics::new().unwrap());
+        PrioManager::new(ProtocolMetricCache::new("prio", metrics))
+    }
+
+    fn queue(mgr: &mut PrioManager, sid: Sid, mids: std::ops::Range<Mid>, size: usize) {
+        for mid in mids {
+            mgr.add(Bytes::from(vec![1u8; size]), mid, sid);
+        }
+    }
+
+    fn data_of(frames: &[OTFrame], mids: std::ops::Range<Mid>) -> usize {
+        frames
+            .iter()
+            .map(|frame| match frame {
+                OTFrame::Data { mid, data } if mids.contains(mid) => data.len(),
+                _ => 0,
+            })
+            .sum()
+    }
+
+    #[test]
+    fn bandwidth_is_kept_over_ticks() {
+        let mut mgr = prio_manager();
+        let sid = Sid::new(1);
+        mgr.open_stream(sid, 4, Promises::empty(), 0);
+        queue(&mut mgr, sid, 0..200, 1000);
+        let sent: u64 = (0..200).map(|_| mgr.grab(100_000, TICK).1).sum();
+        // one second worth of ticks, a frame might exceed the budget
+        assert!(sent > 98_000, "sent {}", sent);
+        assert!(sent < 100_000 + 1500, "sent {}", sent);
+    }
+
+    #[test]
+    fn higher_prio_is_not_deferred() {
+        let mut mgr = prio_manager();
+        let (terrain, ecs) = (Sid::new(1), Sid::new(2));
+        // like the server's in game and terrain streams
+        mgr.open_stream(terrain, 4, Promises::empty(), 20_000);
+        mgr.open_stream(ecs, 3, Promises::empty(), 100_000);
+        queue(&mut mgr, terrain, 0..100, 1000);
+        let mut terrain_bytes = 0;
+        for _ in 0..20 {
+            terrain_bytes += data_of(&mgr.grab(50_000, TICK).0, 0..100);
+        }
+        queue(&mut mgr, ecs, 100..101, 500);
+        let mut ticks = 0;
+        let mut ecs_bytes = 0;
+        while ecs_bytes < 500 {
+            let frames = mgr.grab(50_000, TICK).0;
+            ecs_bytes += data_of(&frames, 100..101);
+            terrain_bytes += data_of(&frames, 0..100);
+            ticks += 1;
+            assert!(ticks <= 20, "ecs message was deferred");
+        }
+        // 100kB at 50kB/s take 2 seconds, only a fraction of that has passed
+        assert!(terrain_bytes < 20_000, "terrain sent {}", terrain_bytes);
+    }
+
+    #[test]
+    fn guaranteed_bandwidth_is_limited_by_budget() {
+        let mut mgr = prio_manager();
+        let (high, low) = (Sid::new(1), Sid::new(2));
+        mgr.open_stream(high, 2, Promises::empty(), 1_000_000);
+        mgr.open_stream(low, 5, Promises::empty(), 1_000_000);
+        queue(&mut mgr, high, 0..20, 1000);
+        queue(&mut mgr, low, 20..40, 1000);
+        let (frames, sent) = mgr.grab(10_000, Duration::from_secs(1));
+        assert!(sent < 10_000 + 1500, "sent {}", sent);
+        assert_eq!(data_of(&frames, 20..40), 0);
+        assert!(data_of(&frames, 0..20) >= 8_000);
+    }
+}
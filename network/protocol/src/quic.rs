@@ -0,0 +1,721 @@
+//! QUIC protocol
+//!
+//! The connecting side opens a bidirectional *main* QUIC stream, which carries
+//! the handshake and the final `Shutdown`. Every `Stream` is mapped onto a
+//! unidirectional QUIC stream per direction, which carries its `OpenStream`,
+//! its messages and its `CloseStream`, in the frame format of TCP. So a lost
+//! packet only delays the `Stream` it belongs to, and everything belonging to
+//! one `Stream` arrives in order.
+//!
+//! The [`Prio`] of a `Stream` decides the order in which it is flushed, like
+//! with TCP, and is also used as the priority of its QUIC stream. QUIC
+//! encrypts all data with TLS, so streams with [`Promises::ENCRYPTED`] need no
+//! further treatment.
+//!
+//! [`Prio`]: crate::Prio
+//! [`Promises::ENCRYPTED`]: crate::Promises::ENCRYPTED
+use crate::{
+    crypto::{RecvCipher, SendCipher},
+    error::ProtocolError,
+    event::ProtocolEvent,
+    frame::{ITFrame, InitFrame, OTFrame},
+    handshake::{ReliableDrain, ReliableSink},
+    message::{ITMessage, ALLOC_BLOCK},
+    metrics::{ChannelStats, ProtocolMetricCache, RemoveReason},
+    prio::PrioManager,
+    types::{Bandwidth, Mid, Prio, Sid},
+    RecvProtocol, SendProtocol, UnreliableDrain, UnreliableSink,
+};
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+#[cfg(feature = "trace_pedantic")]
+use tracing::trace;
+use tracing::{debug, info};
+
+/// Data of one of the QUIC streams of a connection, used for implementing your
+/// own QUIC `Sink` and `Drain`
+#[derive(Debug, PartialEq)]
+pub enum QuicDataFormat {
+    /// Data on the main stream
+    Main(BytesMut),
+    /// Data on a unidirectional stream. The sending side identifies it by a
+    /// number it chose, the receiving side by the id QUIC assigned to it
+    Reliable(u64, BytesMut),
+    /// [`Prio`] of a unidirectional stream, only send before its first data.
+    /// Lower values are more important
+    ///
+    /// [`Prio`]: crate::Prio
+    Priority(u64, Prio),
+    /// No more data follows on the unidirectional stream. The `Drain` must
+    /// only return once all data of the stream was received by the remote
+    /// side, as the `Shutdown` might overtake it otherwise
+    Finished(u64),
+}
+
+/// QUIC implementation of [`SendProtocol`]
+///
+/// [`SendProtocol`]: crate::SendProtocol
+#[derive(Debug)]
+pub struct QuicSendProtocol<D>
+where
+    D: UnreliableDrain<DataFormat = QuicDataFormat>,
+{
+    buffer: BytesMut,
+    store: PrioManager,
+    next_mid: Mid,
+    /// Streams and remaining bytes of the messages the `PrioManager` is
+    /// sending
+    sending: HashMap<Mid, (Sid, u64)>,
+    /// Streams we sent data on, their QUIC streams need to be finished
+    reliable_streams: HashSet<Sid>,
+    prios: HashMap<Sid, Prio>,
+    closing_streams: Vec<Sid>,
+    notify_closing_streams: Vec<Sid>,
+    pending_shutdown: bool,
+    drain: D,
+    metrics: ProtocolMetricCache,
+}
+
+/// QUIC implementation of [`RecvProtocol`]
+///
+/// [`RecvProtocol`]: crate::RecvProtocol
+#[derive(Debug)]
+pub struct QuicRecvProtocol<S>
+where
+    S: UnreliableSink<DataFormat = QuicDataFormat>,
+{
+    main_buffer: BytesMut,
+    /// Buffers of the QUIC streams and whether they are finished
+    reliable_buffers: HashMap<u64, (BytesMut, bool)>,
+    itmsg_allocator: BytesMut,
+    incoming: HashMap<Mid, ITMessage>,
+    sink: S,
+    metrics: ProtocolMetricCache,
+}
+
+impl<D> QuicSendProtocol<D>
+where
+    D: UnreliableDrain<DataFormat = QuicDataFormat>,
+{
+    pub fn new(drain: D, metrics: ProtocolMetricCache) -> Self {
+        Self {
+            buffer: BytesMut::new(),
+            store: PrioManager::new(metrics.clone()),
+            next_mid: 0u64,
+            sending: HashMap::new(),
+            reliable_streams: HashSet::new(),
+            prios: HashMap::new(),
+            closing_streams: vec![],
+            notify_closing_streams: vec![],
+            pending_shutdown: false,
+            drain,
+            metrics,
+        }
+    }
+
+    /// Link quality of this channel, see [`ChannelStats`]
+    pub fn stats(&self) -> Arc<ChannelStats> { self.metrics.stats() }
+
+    /// Sends the data on the QUIC stream of `sid`
+    async fn send_reliable(&mut self, sid: Sid, data: BytesMut) -> Result<(), ProtocolError> {
+        if self.reliable_streams.insert(sid) {
+            if let Some(&prio) = self.prios.get(&sid) {
+                self.drain
+                    .send(QuicDataFormat::Priority(sid.get_u64(), prio))
+                    .await?;
+            }
+        }
+        self.drain
+            .send(QuicDataFormat::Reliable(sid.get_u64(), data))
+            .await
+    }
+
+    async fn send_frame(&mut self, sid: Sid, frame: OTFrame) -> Result<(), ProtocolError> {
+        frame.write_bytes(&mut self.buffer);
+        let data = self.buffer.split();
+        self.send_reliable(sid, data).await
+    }
+
+    async fn finish(&mut self, sid: Sid) -> Result<(), ProtocolError> {
+        self.prios.remove(&sid);
+        if self.reliable_streams.remove(&sid) {
+            self.drain
+                .send(QuicDataFormat::Finished(sid.get_u64()))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// The `Shutdown` must not overtake any data, so all QUIC streams are
+    /// finished first
+    async fn shutdown(&mut self) -> Result<(), ProtocolError> {
+        let streams = self.reliable_streams.iter().copied().collect::<Vec<_>>();
+        for sid in streams {
+            self.finish(sid).await?;
+        }
+        OTFrame::Shutdown {}.write_bytes(&mut self.buffer);
+        self.drain
+            .send(QuicDataFormat::Main(self.buffer.split()))
+            .await
+    }
+}
+
+impl<S> QuicRecvProtocol<S>
+where
+    S: UnreliableSink<DataFormat = QuicDataFormat>,
+{
+    pub fn new(sink: S, metrics: ProtocolMetricCache) -> Self {
+        Self {
+            main_buffer: BytesMut::new(),
+            reliable_buffers: HashMap::new(),
+            itmsg_allocator: BytesMut::with_capacity(ALLOC_BLOCK),
+            incoming: HashMap::new(),
+            sink,
+            metrics,
+        }
+    }
+
+    fn store(&mut self, data: QuicDataFormat) {
+        match data {
+            QuicDataFormat::Main(data) => self.main_buffer.extend_from_slice(&data),
+            QuicDataFormat::Reliable(id, data) => self
+                .reliable_buffers
+                .entry(id)
+                .or_default()
+                .0
+                .extend_from_slice(&data),
+            QuicDataFormat::Priority(..) => {},
+            QuicDataFormat::Finished(id) => {
+                if let Some((_, finished)) = self.reliable_buffers.get_mut(&id) {
+                    *finished = true;
+                }
+            },
+        }
+    }
+
+    /// Must only be called once all frames were read from the buffers
+    fn remove_finished(&mut self) {
+        self.reliable_buffers.retain(|id, (buffer, finished)| {
+            if *finished && !buffer.is_empty() {
+                debug!(?id, "QUIC stream finished within a frame, dropping it");
+            }
+            !*finished
+        });
+    }
+}
+
+#[async_trait]
+impl<D> SendProtocol for QuicSendProtocol<D>
+where
+    D: UnreliableDrain<DataFormat = QuicDataFormat>,
+{
+    fn notify_from_recv(&mut self, event: ProtocolEvent) {
+        match event {
+            ProtocolEvent::OpenStream {
+                sid,
+                prio,
+                promises,
+                guaranteed_bandwidth,
+            } => {
+                self.store
+                    .open_stream(sid, prio, promises, guaranteed_bandwidth);
+                self.prios.insert(sid, prio);
+            },
+            ProtocolEvent::CloseStream { sid } => {
+                // our QUIC stream of it is finished with the next flush
+                #[cfg(feature = "trace_pedantic")]
+                trace!(?sid, "hold back notify close stream");
+                self.notify_closing_streams.push(sid);
+            },
+            _ => {},
+        }
+    }
+
+    async fn send(&mut self, event: ProtocolEvent) -> Result<(), ProtocolError> {
+        #[cfg(feature = "trace_pedantic")]
+        trace!(?event, "send");
+        match event {
+            ProtocolEvent::OpenStream {
+                sid,
+                prio,
+                promises,
+                guaranteed_bandwidth,
+            } => {
+                self.store
+                    .open_stream(sid, prio, promises, guaranteed_bandwidth);
+                self.prios.insert(sid, prio);
+                self.send_frame(sid, event.to_frame()).await?;
+            },
+            ProtocolEvent::CloseStream { sid } => {
+                if self.store.try_close_stream(sid) {
+                    self.send_frame(sid, event.to_frame()).await?;
+                    self.finish(sid).await?;
+                } else {
+                    #[cfg(feature = "trace_pedantic")]
+                    trace!(?sid, "hold back close stream");
+                    self.closing_streams.push(sid);
+                }
+            },
+            ProtocolEvent::Shutdown => {
+                if self.store.is_empty() {
+                    self.shutdown().await?;
+                } else {
+                    #[cfg(feature = "trace_pedantic")]
+                    trace!("hold back shutdown");
+                    self.pending_shutdown = true;
+                }
+            },
+            ProtocolEvent::Message { data, sid } => {
+                self.metrics.smsg_ib(sid, data.len() as u64);
+                if !data.is_empty() {
+                    self.sending.insert(self.next_mid, (sid, data.len() as u64));
+                }
+                self.store.add(data, self.next_mid, sid);
+                self.next_mid += 1;
+            },
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self, bandwidth: Bandwidth, dt: Duration) -> Result<(), ProtocolError> {
+        let (frames, _) = self.store.grab(bandwidth, dt);
+        let mut buffers = HashMap::<Sid, BytesMut>::new();
+        let mut data_frames = 0;
+        let mut data_bandwidth = 0;
+        for frame in frames {
+            let sid = match &frame {
+                OTFrame::DataHeader { sid, .. } => *sid,
+                OTFrame::Data { mid, data } => {
+                    data_frames += 1;
+                    data_bandwidth += data.len();
+                    let (sid, remaining) = match self.sending.get_mut(mid) {
+                        Some(sending) => sending,
+                        None => continue,
+                    };
+                    let sid = *sid;
+                    *remaining = remaining.saturating_sub(data.len() as u64);
+                    if *remaining == 0 {
+                        self.sending.remove(mid);
+                    }
+                    sid
+                },
+                _ => continue,
+            };
+            frame.write_bytes(buffers.entry(sid).or_default());
+        }
+        for (sid, buffer) in buffers {
+            self.send_reliable(sid, buffer).await?;
+        }
+        self.metrics
+            .sdata_frames_b(data_frames, data_bandwidth as u64);
+
+        let mut finished_streams = vec![];
+        for (i, &sid) in self.closing_streams.iter().enumerate() {
+            if self.store.try_close_stream(sid) {
+                finished_streams.push(i);
+            }
+        }
+        for i in finished_streams.iter().rev() {
+            let sid = self.closing_streams.remove(*i);
+            #[cfg(feature = "trace_pedantic")]
+            trace!(?sid, "close stream, as it's now empty");
+            self.send_frame(sid, OTFrame::CloseStream { sid }).await?;
+            self.finish(sid).await?;
+        }
+
+        let mut finished_streams = vec![];
+        for (i, &sid) in self.notify_closing_streams.iter().enumerate() {
+            if self.store.try_close_stream(sid) {
+                finished_streams.push(i);
+            }
+        }
+        for i in finished_streams.iter().rev() {
+            let sid = self.notify_closing_streams.remove(*i);
+            #[cfg(feature = "trace_pedantic")]
+            trace!(?sid, "close stream, as it's now empty");
+            self.finish(sid).await?;
+        }
+
+        if self.pending_shutdown && self.store.is_empty() {
+            #[cfg(feature = "trace_pedantic")]
+            trace!("shutdown, as it's now empty");
+            self.shutdown().await?;
+            self.pending_shutdown = false;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<S> RecvProtocol for QuicRecvProtocol<S>
+where
+    S: UnreliableSink<DataFormat = QuicDataFormat>,
+{
+    async fn recv(&mut self) -> Result<ProtocolEvent, ProtocolError> {
+        loop {
+            // the main stream is read last, so a `Shutdown` can't overtake the data
+            // of any stream
+            for buffer in self
+                .reliable_buffers
+                .values_mut()
+                .map(|(buffer, _)| buffer)
+                .chain(std::iter::once(&mut self.main_buffer))
+            {
+                if let Some(event) = read_event(
+                    buffer,
+                    &mut self.incoming,
+                    &mut self.itmsg_allocator,
+                    &mut self.metrics,
+                )? {
+                    return Ok(event);
+                }
+            }
+            self.remove_finished();
+            let data = self.sink.recv().await?;
+            self.store(data);
+        }
+    }
+}
+
+/// Reads frames from `buffer` till one of them completes an event
+fn read_event(
+    buffer: &mut BytesMut,
+    incoming: &mut HashMap<Mid, ITMessage>,
+    itmsg_allocator: &mut BytesMut,
+    metrics: &mut ProtocolMetricCache,
+) -> Result<Option<ProtocolEvent>, ProtocolError> {
+    while let Some(frame) = ITFrame::read_frame(buffer) {
+        #[cfg(feature = "trace_pedantic")]
+        trace!(?frame, "recv");
+        match frame {
+            ITFrame::Shutdown => return Ok(Some(ProtocolEvent::Shutdown)),
+            ITFrame::OpenStream {
+                sid,
+                prio,
+                promises,
+                guaranteed_bandwidth,
+            } => {
+                return Ok(Some(ProtocolEvent::OpenStream {
+                    sid,
+                    prio: prio.min(crate::types::HIGHEST_PRIO),
+                    promises,
+                    guaranteed_bandwidth,
+                }));
+            },
+            ITFrame::CloseStream { sid } => return Ok(Some(ProtocolEvent::CloseStream { sid })),
+            ITFrame::DataHeader { sid, mid, length } => {
+                metrics.rmsg_ib(sid, length);
+                if length == 0 {
+                    metrics.rmsg_ob(sid, RemoveReason::Finished, 0);
+                    return Ok(Some(ProtocolEvent::Message {
+                        sid,
+                        data: Bytes::new(),
+                    }));
+                }
+                let m = ITMessage::new(sid, length, itmsg_allocator);
+                incoming.insert(mid, m);
+            },
+            ITFrame::Data { mid, data } => {
+                metrics.rdata_frames_b(data.len() as u64);
+                let m = match incoming.get_mut(&mid) {
+                    Some(m) => m,
+                    None => {
+                        info!(
+                            ?mid,
+                            "protocol violation by remote side: send Data before Header"
+                        );
+                        return Err(ProtocolError::Closed);
+                    },
+                };
+                m.data.extend_from_slice(&data);
+                if m.data.len() == m.length as usize {
+                    // finished, yay
+                    let m = incoming.remove(&mid).unwrap();
+                    metrics.rmsg_ob(m.sid, RemoveReason::Finished, m.data.len() as u64);
+                    return Ok(Some(ProtocolEvent::Message {
+                        sid: m.sid,
+                        data: m.data.freeze(),
+                    }));
+                }
+            },
+            // QUIC measures the round trip time on its own, so they are never sent
+            ITFrame::Ping { .. } | ITFrame::Pong { .. } => {},
+        }
+    }
+    Ok(None)
+}
+
+#[async_trait]
+impl<D> ReliableDrain for QuicSendProtocol<D>
+where
+    D: UnreliableDrain<DataFormat = QuicDataFormat>,
+{
+    async fn send(&mut self, frame: InitFrame) -> Result<(), ProtocolError> {
+        let mut buffer = BytesMut::with_capacity(500);
+        frame.write_bytes(&mut buffer);
+        self.drain.send(QuicDataFormat::Main(buffer)).await
+    }
+
+    /// QUIC is encrypted already
+    fn set_cipher(&mut self, _cipher: SendCipher) {}
+}
+
+#[async_trait]
+impl<S> ReliableSink for QuicRecvProtocol<S>
+where
+    S: UnreliableSink<DataFormat = QuicDataFormat>,
+{
+    async fn recv(&mut self) -> Result<InitFrame, ProtocolError> {
+        while self.main_buffer.len() < 500 {
+            if let Some(frame) = InitFrame::read_frame(&mut self.main_buffer) {
+                return Ok(frame);
+            }
+            // the remote side might finish the handshake first and already use its
+            // streams, that data is kept for `RecvProtocol::recv`
+            let data = self.sink.recv().await?;
+            self.store(data);
+        }
+        Err(ProtocolError::Closed)
+    }
+
+    fn set_cipher(&mut self, _cipher: RecvCipher) {}
+}
+
+#[cfg(test)]
+mod test_utils {
+    //QUIC protocol based on Channel
+    use super::*;
+    use crate::metrics::{ProtocolMetricCache, ProtocolMetrics};
+    use async_channel::*;
+    use std::sync::Arc;
+
+    pub struct QuicDrain {
+        pub sender: Sender<QuicDataFormat>,
+    }
+
+    pub struct QuicSink {
+        pub receiver: Receiver<QuicDataFormat>,
+    }
+
+    /// emulate Quic protocol on Channels, the ids of the streams are passed
+    /// through unchanged
+    pub fn quic_bound(
+        cap: usize,
+        metrics: Option<ProtocolMetricCache>,
+    ) -> [(QuicSendProtocol<QuicDrain>, QuicRecvProtocol<QuicSink>); 2] {
+        let (s1, r1) = async_channel::bounded(cap);
+        let (s2, r2) = async_channel::bounded(cap);
+        let m = metrics.unwrap_or_else(|| {
+            ProtocolMetricCache::new("quic", Arc::new(ProtocolMetrics::new().unwrap()))
+        });
+        [
+            (
+                QuicSendProtocol::new(QuicDrain { sender: s1 }, m.clone()),
+                QuicRecvProtocol::new(QuicSink { receiver: r2 }, m.clone()),
+            ),
+            (
+                QuicSendProtocol::new(QuicDrain { sender: s2 }, m.clone()),
+                QuicRecvProtocol::new(QuicSink { receiver: r1 }, m),
+            ),
+        ]
+    }
+
+    #[async_trait]
+    impl UnreliableDrain for QuicDrain {
+        type DataFormat = QuicDataFormat;
+
+        async fn send(&mut self, data: Self::DataFormat) -> Result<(), ProtocolError> {
+            self.sender
+                .send(data)
+                .await
+                .map_err(|_| ProtocolError::Closed)
+        }
+    }
+
+    #[async_trait]
+    impl UnreliableSink for QuicSink {
+        type DataFormat = QuicDataFormat;
+
+        async fn recv(&mut self) -> Result<Self::DataFormat, ProtocolError> {
+            self.receiver
+                .recv()
+                .await
+                .map_err(|_| ProtocolError::Closed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        metrics::{ProtocolMetricCache, ProtocolMetrics, RemoveReason},
+        quic::{test_utils::*, QuicDataFormat},
+        types::{Pid, Promises, Sid, STREAM_ID_OFFSET1, STREAM_ID_OFFSET2},
+        Identity, InitProtocol, ProtocolEvent, RecvProtocol, SendProtocol,
+    };
+    use bytes::Bytes;
+    use std::{sync::Arc, time::Duration};
+
+    fn open(sid: Sid) -> ProtocolEvent {
+        ProtocolEvent::OpenStream {
+            sid,
+            prio: 5u8,
+            promises: Promises::ORDERED,
+            guaranteed_bandwidth: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn handshake_all_good() {
+        let [mut p1, mut p2] = quic_bound(10, None);
+        let (i1, i2) = (Identity::generate(), Identity::generate());
+        let (k1, k2) = (i1.public_key(), i2.public_key());
+        let r1 = tokio::spawn(async move { p1.initialize(true, Pid::fake(2), 1337, &i1).await });
+        let r2 = tokio::spawn(async move { p2.initialize(false, Pid::fake(3), 42, &i2).await });
+        let (r1, r2) = tokio::join!(r1, r2);
+        assert_eq!(r1.unwrap(), Ok((Pid::fake(3), STREAM_ID_OFFSET1, 42, k2)));
+        assert_eq!(r2.unwrap(), Ok((Pid::fake(2), STREAM_ID_OFFSET2, 1337, k1)));
+    }
+
+    #[tokio::test]
+    async fn send_short_msg() {
+        let [p1, p2] = quic_bound(10, None);
+        let (mut s, mut r) = (p1.0, p2.1);
+        let sid = Sid::new(10);
+        s.send(open(sid)).await.unwrap();
+        assert_eq!(r.recv().await.unwrap(), open(sid));
+        for data in vec![&[188u8; 600][..], &[7u8; 30][..], &[][..]] {
+            let event = ProtocolEvent::Message {
+                sid,
+                data: Bytes::from(data),
+            };
+            s.send(event.clone()).await.unwrap();
+            s.flush(1_000_000, Duration::from_secs(1)).await.unwrap();
+            assert_eq!(r.recv().await.unwrap(), event);
+        }
+    }
+
+    #[tokio::test]
+    async fn send_long_msg() {
+        let mut metrics =
+            ProtocolMetricCache::new("long_quic", Arc::new(ProtocolMetrics::new().unwrap()));
+        let sid = Sid::new(1);
+        let [p1, p2] = quic_bound(10000, Some(metrics.clone()));
+        let (mut s, mut r) = (p1.0, p2.1);
+        s.send(open(sid)).await.unwrap();
+        let _ = r.recv().await.unwrap();
+        let event = ProtocolEvent::Message {
+            sid,
+            data: Bytes::from(&[99u8; 500_000][..]),
+        };
+        s.send(event.clone()).await.unwrap();
+        s.flush(1_000_000, Duration::from_secs(1)).await.unwrap();
+        assert_eq!(r.recv().await.unwrap(), event);
+        metrics.assert_msg(sid, 1, RemoveReason::Finished);
+        metrics.assert_msg_bytes(sid, 500_000, RemoveReason::Finished);
+        metrics.assert_data_frames(358);
+        metrics.assert_data_frames_bytes(500_000);
+    }
+
+    #[tokio::test]
+    async fn msg_finishes_after_close() {
+        let sid = Sid::new(1);
+        let [p1, p2] = quic_bound(10000, None);
+        let (mut s, mut r) = (p1.0, p2.1);
+        s.send(open(sid)).await.unwrap();
+        s.send(ProtocolEvent::Message {
+            sid,
+            data: Bytes::from(&[99u8; 500_000][..]),
+        })
+        .await
+        .unwrap();
+        s.send(ProtocolEvent::CloseStream { sid }).await.unwrap();
+        s.flush(1_000_000, Duration::from_secs(1)).await.unwrap();
+        assert_eq!(r.recv().await.unwrap(), open(sid));
+        let e = r.recv().await.unwrap();
+        assert!(matches!(e, ProtocolEvent::Message { .. }));
+        let e = r.recv().await.unwrap();
+        assert!(matches!(e, ProtocolEvent::CloseStream { .. }));
+    }
+
+    #[tokio::test]
+    async fn streams_finished_before_shutdown() {
+        let [p1, p2] = quic_bound(10000, None);
+        let (mut s, r) = (p1.0, p2.1);
+        s.send(open(Sid::new(1))).await.unwrap();
+        s.send(open(Sid::new(2))).await.unwrap();
+        s.send(ProtocolEvent::Message {
+            sid: Sid::new(1),
+            data: Bytes::from(&[99u8; 500_000][..]),
+        })
+        .await
+        .unwrap();
+        s.send(ProtocolEvent::Shutdown).await.unwrap();
+        s.send(ProtocolEvent::CloseStream { sid: Sid::new(1) })
+            .await
+            .unwrap();
+        s.send(ProtocolEvent::CloseStream { sid: Sid::new(2) })
+            .await
+            .unwrap();
+        s.flush(1_000_000, Duration::from_secs(1)).await.unwrap();
+        drop(s);
+        let mut prios = vec![];
+        let mut finished = vec![];
+        let mut shutdown = false;
+        while let Ok(data) = r.sink.receiver.recv().await {
+            assert!(!shutdown, "nothing may follow the shutdown");
+            match data {
+                QuicDataFormat::Priority(id, prio) => prios.push((id, prio)),
+                QuicDataFormat::Reliable(id, _) => {
+                    assert!(prios.contains(&(id, 5)));
+                    assert!(!finished.contains(&id));
+                },
+                QuicDataFormat::Finished(id) => finished.push(id),
+                QuicDataFormat::Main(_) => shutdown = true,
+            }
+        }
+        finished.sort_unstable();
+        assert_eq!(finished, vec![1, 2]);
+        assert!(shutdown);
+    }
+
+    #[tokio::test]
+    async fn streams_dont_block_each_other() {
+        let [p1, p2] = quic_bound(10000, None);
+        let (mut s, mut r) = (p1.0, p2.1);
+        let (sender, receiver) = async_channel::unbounded();
+        for sid in vec![Sid::new(1), Sid::new(2)] {
+            s.send(open(sid)).await.unwrap();
+            s.send(ProtocolEvent::Message {
+                sid,
+                data: Bytes::from(vec![sid.get_u64() as u8; 5_000]),
+            })
+            .await
+            .unwrap();
+        }
+        s.flush(1_000_000, Duration::from_secs(1)).await.unwrap();
+        // the data of stream 1 got lost and is only retransmitted after stream 2
+        let mut delayed = vec![];
+        while let Ok(data) = r.sink.receiver.try_recv() {
+            match data {
+                QuicDataFormat::Reliable(1, _) => delayed.push(data),
+                data => sender.send(data).await.unwrap(),
+            }
+        }
+        for data in delayed {
+            sender.send(data).await.unwrap();
+        }
+        r.sink.receiver = receiver;
+        assert_eq!(r.recv().await.unwrap(), open(Sid::new(2)));
+        let e = r.recv().await.unwrap();
+        assert!(matches!(e, ProtocolEvent::Message { sid, .. } if sid == Sid::new(2)));
+        assert_eq!(r.recv().await.unwrap(), open(Sid::new(1)));
+        let e = r.recv().await.unwrap();
+        assert!(matches!(e, ProtocolEvent::Message { sid, .. } if sid == Sid::new(1)));
+    }
+}
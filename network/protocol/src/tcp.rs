@@ -1,23 +1,37 @@
 use crate::{
+    crypto::{OpenError, RecvCipher, SendCipher},
     error::ProtocolError,
     event::ProtocolEvent,
     frame::{ITFrame, InitFrame, OTFrame},
     handshake::{ReliableDrain, ReliableSink},
     message::{ITMessage, ALLOC_BLOCK},
-    metrics::{ProtocolMetricCache, RemoveReason},
+    metrics::{ChannelStats, ProtocolMetricCache, RemoveReason},
     prio::PrioManager,
-    types::{Bandwidth, Mid, Sid},
+    types::{Bandwidth, Mid, Promises, Sid},
     RecvProtocol, SendProtocol, UnreliableDrain, UnreliableSink,
 };
 use async_trait::async_trait;
 use bytes::BytesMut;
 use std::{
     collections::HashMap,
+    sync::{Arc, Mutex},
     time::{Duration, Instant},
 };
-use tracing::info;
 #[cfg(feature = "trace_pedantic")]
 use tracing::trace;
+use tracing::{debug, info};
+
+/// Pings are sent this often to measure the round trip time
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+
+/// State shared by both halves of a channel, the sending half answers the
+/// pings the receiving half got
+#[derive(Debug)]
+struct Shared {
+    /// Ping tokens are the microseconds since `epoch` they were sent at
+    epoch: Instant,
+    pongs_to_send: Vec<u64>,
+}
 
 /// TCP implementation of [`SendProtocol`]
 ///
@@ -34,7 +48,10 @@ where
     notify_closing_streams: Vec<Sid>,
     pending_shutdown: bool,
     drain: D,
+    cipher: Option<SendCipher>,
     last: Instant,
+    next_ping: Instant,
+    shared: Arc<Mutex<Shared>>,
     metrics: ProtocolMetricCache,
 }
 
@@ -50,15 +67,29 @@ where
     itmsg_allocator: BytesMut,
     incoming: HashMap<Mid, ITMessage>,
     sink: S,
+    cipher: Option<RecvCipher>,
+    shared: Arc<Mutex<Shared>>,
     metrics: ProtocolMetricCache,
 }
 
-impl<D> TcpSendProtocol<D>
+/// Creates both halves of a TCP channel, which share the pings they measure
+/// the round trip time with.
+pub fn tcp_protocols<D, S>(
+    drain: D,
+    sink: S,
+    metrics: ProtocolMetricCache,
+) -> (TcpSendProtocol<D>, TcpRecvProtocol<S>)
 where
     D: UnreliableDrain<DataFormat = BytesMut>,
+    S: UnreliableSink<DataFormat = BytesMut>,
 {
-    pub fn new(drain: D, metrics: ProtocolMetricCache) -> Self {
-        Self {
+    let now = Instant::now();
+    let shared = Arc::new(Mutex::new(Shared {
+        epoch: now,
+        pongs_to_send: vec![],
+    }));
+    (
+        TcpSendProtocol {
             buffer: BytesMut::new(),
             store: PrioManager::new(metrics.clone()),
             next_mid: 0u64,
@@ -66,25 +97,30 @@ where
             notify_closing_streams: vec![],
             pending_shutdown: false,
             drain,
-            last: Instant::now(),
-            metrics,
-        }
-    }
-}
-
-impl<S> TcpRecvProtocol<S>
-where
-    S: UnreliableSink<DataFormat = BytesMut>,
-{
-    pub fn new(sink: S, metrics: ProtocolMetricCache) -> Self {
-        Self {
+            cipher: None,
+            last: now,
+            next_ping: now,
+            shared: Arc::clone(&shared),
+            metrics: metrics.clone(),
+        },
+        TcpRecvProtocol {
             buffer: BytesMut::new(),
             itmsg_allocator: BytesMut::with_capacity(ALLOC_BLOCK),
             incoming: HashMap::new(),
             sink,
+            cipher: None,
+            shared,
             metrics,
-        }
-    }
+        },
+    )
+}
+
+impl<D> TcpSendProtocol<D>
+where
+    D: UnreliableDrain<DataFormat = BytesMut>,
+{
+    /// Link quality of this channel, see [`ChannelStats`]
+    pub fn stats(&self) -> Arc<ChannelStats> { self.metrics.stats() }
 }
 
 #[async_trait]
@@ -100,6 +136,9 @@ where
                 promises,
                 guaranteed_bandwidth,
             } => {
+                if let Some(cipher) = &self.cipher {
+                    cipher.open_stream(sid, promises);
+                }
                 self.store
                     .open_stream(sid, prio, promises, guaranteed_bandwidth);
             },
@@ -124,6 +163,14 @@ where
                 promises,
                 guaranteed_bandwidth,
             } => {
+                match &self.cipher {
+                    Some(cipher) => cipher.open_stream(sid, promises),
+                    None if promises.contains(Promises::ENCRYPTED) => {
+                        info!(?sid, "no keys were exchanged to encrypt this stream");
+                        return Err(ProtocolError::Closed);
+                    },
+                    None => {},
+                }
                 self.store
                     .open_stream(sid, prio, promises, guaranteed_bandwidth);
                 event.to_frame().write_bytes(&mut self.buffer);
@@ -151,6 +198,10 @@ where
             },
             ProtocolEvent::Message { data, sid } => {
                 self.metrics.smsg_ib(sid, data.len() as u64);
+                let data = match &mut self.cipher {
+                    Some(cipher) => cipher.seal(sid, data),
+                    None => data,
+                };
                 self.store.add(data, self.next_mid, sid);
                 self.next_mid += 1;
             },
@@ -159,6 +210,19 @@ where
     }
 
     async fn flush(&mut self, bandwidth: Bandwidth, dt: Duration) -> Result<(), ProtocolError> {
+        let now = Instant::now();
+        {
+            let mut shared = self.shared.lock().unwrap();
+            for token in shared.pongs_to_send.drain(..) {
+                OTFrame::Pong { token }.write_bytes(&mut self.buffer);
+            }
+            if now >= self.next_ping {
+                let token = now.duration_since(shared.epoch).as_micros() as u64;
+                OTFrame::Ping { token }.write_bytes(&mut self.buffer);
+                self.next_ping = now + PING_INTERVAL;
+            }
+        }
+
         let (frames, total_bytes) = self.store.grab(bandwidth, dt);
         self.buffer.reserve(total_bytes as usize);
         let mut data_frames = 0;
@@ -229,6 +293,9 @@ where
                         promises,
                         guaranteed_bandwidth,
                     } => {
+                        if let Some(cipher) = &self.cipher {
+                            cipher.open_stream(sid, promises);
+                        }
                         break 'outer Ok(ProtocolEvent::OpenStream {
                             sid,
                             prio: prio.min(crate::types::HIGHEST_PRIO),
@@ -237,6 +304,9 @@ where
                         });
                     },
                     ITFrame::CloseStream { sid } => {
+                        if let Some(cipher) = &self.cipher {
+                            cipher.close_stream(sid);
+                        }
                         break 'outer Ok(ProtocolEvent::CloseStream { sid });
                     },
                     ITFrame::DataHeader { sid, mid, length } => {
@@ -265,10 +335,33 @@ where
                                 RemoveReason::Finished,
                                 m.data.len() as u64,
                             );
-                            break 'outer Ok(ProtocolEvent::Message {
-                                sid: m.sid,
-                                data: m.data.freeze(),
-                            });
+                            let data = match &mut self.cipher {
+                                Some(cipher) => match cipher.open(m.sid, m.data.freeze()) {
+                                    Ok(data) => data,
+                                    Err(OpenError::Replayed) => {
+                                        debug!(?mid, "dropping replayed message");
+                                        continue;
+                                    },
+                                    Err(OpenError::Tampered) => {
+                                        info!(
+                                            ?mid,
+                                            "protocol violation: message was tampered with"
+                                        );
+                                        break 'outer Err(ProtocolError::Closed);
+                                    },
+                                },
+                                None => m.data.freeze(),
+                            };
+                            break 'outer Ok(ProtocolEvent::Message { sid: m.sid, data });
+                        }
+                    },
+                    ITFrame::Ping { token } => {
+                        self.shared.lock().unwrap().pongs_to_send.push(token);
+                    },
+                    ITFrame::Pong { token } => {
+                        let elapsed = self.shared.lock().unwrap().epoch.elapsed();
+                        if let Some(rtt) = elapsed.checked_sub(Duration::from_micros(token)) {
+                            self.metrics.ping(rtt);
                         }
                     },
                 };
@@ -293,6 +386,8 @@ where
         frame.write_bytes(&mut buffer);
         self.drain.send(buffer).await
     }
+
+    fn set_cipher(&mut self, cipher: SendCipher) { self.cipher = Some(cipher); }
 }
 
 #[async_trait]
@@ -301,7 +396,11 @@ where
     S: UnreliableSink<DataFormat = BytesMut>,
 {
     async fn recv(&mut self) -> Result<InitFrame, ProtocolError> {
-        while self.buffer.len() < 100 {
+        // the remote side might have sent multiple frames at once
+        if let Some(frame) = InitFrame::read_frame(&mut self.buffer) {
+            return Ok(frame);
+        }
+        while self.buffer.len() < 500 {
             let chunk = self.sink.recv().await?;
             self.buffer.extend_from_slice(&chunk);
             if let Some(frame) = InitFrame::read_frame(&mut self.buffer) {
@@ -310,6 +409,8 @@ where
         }
         Err(ProtocolError::Closed)
     }
+
+    fn set_cipher(&mut self, cipher: RecvCipher) { self.cipher = Some(cipher); }
 }
 
 #[cfg(test)]
@@ -339,14 +440,8 @@ mod test_utils {
             ProtocolMetricCache::new("tcp", Arc::new(ProtocolMetrics::new().unwrap()))
         });
         [
-            (
-                TcpSendProtocol::new(TcpDrain { sender: s1 }, m.clone()),
-                TcpRecvProtocol::new(TcpSink { receiver: r2 }, m.clone()),
-            ),
-            (
-                TcpSendProtocol::new(TcpDrain { sender: s2 }, m.clone()),
-                TcpRecvProtocol::new(TcpSink { receiver: r1 }, m),
-            ),
+            tcp_protocols(TcpDrain { sender: s1 }, TcpSink { receiver: r2 }, m.clone()),
+            tcp_protocols(TcpDrain { sender: s2 }, TcpSink { receiver: r1 }, m),
         ]
     }
 
@@ -383,7 +478,7 @@ mod tests {
         metrics::{ProtocolMetricCache, ProtocolMetrics, RemoveReason},
         tcp::test_utils::*,
         types::{Pid, Promises, Sid, STREAM_ID_OFFSET1, STREAM_ID_OFFSET2},
-        InitProtocol, ProtocolEvent, RecvProtocol, SendProtocol,
+        Identity, InitProtocol, ProtocolEvent, RecvProtocol, SendProtocol,
     };
     use bytes::{Bytes, BytesMut};
     use std::{sync::Arc, time::Duration};
@@ -391,11 +486,92 @@ mod tests {
     #[tokio::test]
     async fn handshake_all_good() {
         let [mut p1, mut p2] = tcp_bound(10, None);
-        let r1 = tokio::spawn(async move { p1.initialize(true, Pid::fake(2), 1337).await });
-        let r2 = tokio::spawn(async move { p2.initialize(false, Pid::fake(3), 42).await });
+        let (i1, i2) = (Identity::generate(), Identity::generate());
+        let (k1, k2) = (i1.public_key(), i2.public_key());
+        let r1 = tokio::spawn(async move { p1.initialize(true, Pid::fake(2), 1337, &i1).await });
+        let r2 = tokio::spawn(async move { p2.initialize(false, Pid::fake(3), 42, &i2).await });
         let (r1, r2) = tokio::join!(r1, r2);
-        assert_eq!(r1.unwrap(), Ok((Pid::fake(3), STREAM_ID_OFFSET1, 42)));
-        assert_eq!(r2.unwrap(), Ok((Pid::fake(2), STREAM_ID_OFFSET2, 1337)));
+        assert_eq!(r1.unwrap(), Ok((Pid::fake(3), STREAM_ID_OFFSET1, 42, k2)));
+        assert_eq!(r2.unwrap(), Ok((Pid::fake(2), STREAM_ID_OFFSET2, 1337, k1)));
+    }
+
+    /// Does the handshake and opens an encrypted stream from `s` to `r`.
+    /// Everything `s` sends afterwards ends up in the returned channel, so it
+    /// can be inspected before it's forwarded to `r`
+    async fn encrypted_stream(
+        sid: Sid,
+    ) -> (
+        super::TcpSendProtocol<TcpDrain>,
+        super::TcpRecvProtocol<TcpSink>,
+        async_channel::Receiver<BytesMut>,
+        async_channel::Sender<BytesMut>,
+    ) {
+        let [mut p1, mut p2] = tcp_bound(10, None);
+        let (i1, i2) = (Identity::generate(), Identity::generate());
+        let (r1, r2) = tokio::join!(
+            p1.initialize(true, Pid::fake(2), 1337, &i1),
+            p2.initialize(false, Pid::fake(3), 42, &i2)
+        );
+        assert!(r1.is_ok() && r2.is_ok());
+        let (mut s, mut r) = (p1.0, p2.1);
+        let event = ProtocolEvent::OpenStream {
+            sid,
+            prio: 3u8,
+            promises: Promises::ENCRYPTED,
+            guaranteed_bandwidth: 1_000_000,
+        };
+        s.send(event.clone()).await.unwrap();
+        assert_eq!(r.recv().await.unwrap(), event);
+        let (tap_s, tap_r) = async_channel::bounded(10);
+        let forward = std::mem::replace(&mut s.drain.sender, tap_s);
+        (s, r, tap_r, forward)
+    }
+
+    #[tokio::test]
+    async fn encrypted_stream_hides_data() {
+        const SECRET: &[u8] = b"the quick brown fox jumps over the lazy dog";
+        let sid = Sid::new(10);
+        let (mut s, mut r, tap, forward) = encrypted_stream(sid).await;
+        let event = ProtocolEvent::Message {
+            sid,
+            data: Bytes::from(SECRET),
+        };
+        s.send(event.clone()).await.unwrap();
+        s.flush(1_000_000, Duration::from_secs(1)).await.unwrap();
+        let wire = tap.recv().await.unwrap();
+        assert!(!wire.windows(SECRET.len()).any(|w| w == SECRET));
+        forward.send(wire).await.unwrap();
+        assert_eq!(r.recv().await.unwrap(), event);
+    }
+
+    #[tokio::test]
+    async fn encrypted_stream_detects_tampering() {
+        let sid = Sid::new(10);
+        let (mut s, mut r, tap, forward) = encrypted_stream(sid).await;
+        let event = ProtocolEvent::Message {
+            sid,
+            data: Bytes::from(&[42u8; 100][..]),
+        };
+        s.send(event).await.unwrap();
+        s.flush(1_000_000, Duration::from_secs(1)).await.unwrap();
+        let mut wire = tap.recv().await.unwrap();
+        // the last byte belongs to the authentication tag
+        let last = wire.len() - 1;
+        wire[last] ^= 1;
+        forward.send(wire).await.unwrap();
+        assert_eq!(r.recv().await, Err(ProtocolError::Closed));
+    }
+
+    #[tokio::test]
+    async fn encrypted_stream_needs_handshake() {
+        let [mut p1, _p2] = tcp_bound(10, None);
+        let event = ProtocolEvent::OpenStream {
+            sid: Sid::new(10),
+            prio: 3u8,
+            promises: Promises::ENCRYPTED,
+            guaranteed_bandwidth: 1_000_000,
+        };
+        assert_eq!(p1.0.send(event).await, Err(ProtocolError::Closed));
     }
 
     #[tokio::test]
@@ -566,13 +742,162 @@ mod tests {
         assert!(matches!(e, ProtocolEvent::Message { .. }));
     }
 
+    #[tokio::test]
+    async fn queue_stats() {
+        let metrics =
+            ProtocolMetricCache::new("queue_tcp", Arc::new(ProtocolMetrics::new().unwrap()));
+        let stats = metrics.stats();
+        let sid = Sid::new(1);
+        let [p1, p2] = tcp_bound(10, Some(metrics));
+        let (mut s, mut r) = (p1.0, p2.1);
+        let event = ProtocolEvent::OpenStream {
+            sid,
+            prio: 3u8,
+            promises: Promises::ORDERED,
+            guaranteed_bandwidth: 1_000_000,
+        };
+        s.send(event).await.unwrap();
+        let _ = r.recv().await.unwrap();
+        for _ in 0..3 {
+            let event = ProtocolEvent::Message {
+                sid,
+                data: Bytes::from(&[42u8; 100][..]),
+            };
+            s.send(event).await.unwrap();
+        }
+        assert_eq!(stats.queued_messages(), 3);
+        assert_eq!(stats.queued_bytes(), 300);
+        s.flush(1_000_000, Duration::from_secs(1)).await.unwrap();
+        assert_eq!(stats.queued_messages(), 0);
+        assert_eq!(stats.queued_bytes(), 0);
+        assert_eq!(stats.sent_bytes(3), 300);
+        assert_eq!(stats.sent_bytes(4), 0);
+    }
+
+    #[tokio::test]
+    async fn ping_measures_rtt() {
+        let metrics =
+            ProtocolMetricCache::new("ping_tcp", Arc::new(ProtocolMetrics::new().unwrap()));
+        let stats = metrics.stats();
+        let [mut p1, mut p2] = tcp_bound(10, Some(metrics));
+        let open = |sid| ProtocolEvent::OpenStream {
+            sid,
+            prio: 3u8,
+            promises: Promises::ORDERED,
+            guaranteed_bandwidth: 1_000_000,
+        };
+        // the first flush sends a ping, which p2 receives along with the stream
+        p1.0.flush(1_000_000, Duration::from_secs(1)).await.unwrap();
+        p1.0.send(open(Sid::new(1))).await.unwrap();
+        let _ = p2.1.recv().await.unwrap();
+        assert_eq!(stats.rtt(), None);
+        // p2 answers with its next flush
+        p2.0.flush(1_000_000, Duration::from_secs(1)).await.unwrap();
+        p2.0.send(open(Sid::new(2))).await.unwrap();
+        let _ = p1.1.recv().await.unwrap();
+        assert!(stats.rtt().is_some());
+    }
+
+    #[tokio::test]
+    async fn slow_sink_does_not_defer_higher_prio() {
+        const TICK: Duration = Duration::from_millis(5);
+        const SINK_BANDWIDTH: f64 = 60_000.0;
+        let (terrain, ecs) = (Sid::new(1), Sid::new(2));
+        let (s1, r1) = async_channel::bounded::<BytesMut>(1);
+        let (s2, r2) = async_channel::bounded(1);
+        let m = ProtocolMetricCache::new("slow_tcp", Arc::new(ProtocolMetrics::new().unwrap()));
+        let (mut s, _) = super::tcp_protocols(
+            TcpDrain { sender: s1 },
+            TcpSink {
+                receiver: async_channel::bounded(10).1,
+            },
+            m.clone(),
+        );
+        let (_, mut r) = super::tcp_protocols(
+            TcpDrain {
+                sender: async_channel::bounded(10).0,
+            },
+            TcpSink { receiver: r2 },
+            m,
+        );
+        // the sink only forwards SINK_BANDWIDTH bytes per second
+        tokio::spawn(async move {
+            while let Ok(data) = r1.recv().await {
+                tokio::time::sleep(Duration::from_secs_f64(data.len() as f64 / SINK_BANDWIDTH))
+                    .await;
+                if s2.send(data).await.is_err() {
+                    break;
+                }
+            }
+        });
+        let done = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let done2 = Arc::clone(&done);
+        let receiver = tokio::spawn(async move {
+            let mut order = vec![];
+            while order.len() < 61 {
+                if let ProtocolEvent::Message { sid, .. } = r.recv().await.unwrap() {
+                    order.push(sid);
+                }
+            }
+            done2.store(true, std::sync::atomic::Ordering::Relaxed);
+            order
+        });
+
+        // like the server's in game and terrain streams
+        for (sid, prio, guaranteed_bandwidth) in [(terrain, 4, 20_000), (ecs, 3, 100_000)].iter() {
+            s.send(ProtocolEvent::OpenStream {
+                sid: *sid,
+                prio: *prio,
+                promises: Promises::ORDERED,
+                guaranteed_bandwidth: *guaranteed_bandwidth,
+            })
+            .await
+            .unwrap();
+        }
+        for _ in 0..60 {
+            s.send(ProtocolEvent::Message {
+                sid: terrain,
+                data: Bytes::from(&[4u8; 1000][..]),
+            })
+            .await
+            .unwrap();
+        }
+        let mut tick = 0;
+        while !done.load(std::sync::atomic::Ordering::Relaxed) {
+            if tick == 20 {
+                s.send(ProtocolEvent::Message {
+                    sid: ecs,
+                    data: Bytes::from(&[3u8; 100][..]),
+                })
+                .await
+                .unwrap();
+            }
+            s.flush(50_000, TICK).await.unwrap();
+            tokio::time::sleep(TICK).await;
+            tick += 1;
+        }
+        let order = receiver.await.unwrap();
+        let pos = order.iter().position(|sid| *sid == ecs).unwrap();
+        // 60 terrain messages take over a second, the ecs message waited for a few only
+        assert!(
+            pos < 30,
+            "ecs message was deferred behind {} terrain messages",
+            pos
+        );
+    }
+
     #[tokio::test]
     async fn header_and_data_in_seperate_msg() {
         let sid = Sid::new(1);
         let (s, r) = async_channel::bounded(10);
         let m = ProtocolMetricCache::new("tcp", Arc::new(ProtocolMetrics::new().unwrap()));
-        let mut r =
-            super::TcpRecvProtocol::new(super::test_utils::TcpSink { receiver: r }, m.clone());
+        let (_, mut r) = super::tcp_protocols(
+            TcpDrain {
+                sender: async_channel::bounded(10).0,
+            },
+            TcpSink { receiver: r },
+            m,
+        );
 
         const DATA1: &[u8; 69] =
             b"We need to make sure that its okay to send OPEN_STREAM and DATA_HEAD ";
@@ -621,8 +946,13 @@ mod tests {
         let sid = Sid::new(1);
         let (s, r) = async_channel::bounded(10);
         let m = ProtocolMetricCache::new("tcp", Arc::new(ProtocolMetrics::new().unwrap()));
-        let mut r =
-            super::TcpRecvProtocol::new(super::test_utils::TcpSink { receiver: r }, m.clone());
+        let (_, mut r) = super::tcp_protocols(
+            TcpDrain {
+                sender: async_channel::bounded(10).0,
+            },
+            TcpSink { receiver: r },
+            m,
+        );
 
         let mut bytes = BytesMut::with_capacity(1500);
         OTFrame::OpenStream {
@@ -1,37 +1,1467 @@
-// TODO: quick and dirty which activly waits for an ack!
-/*
-UDP protocol
-
-All Good Case:
-S --HEADER--> R
-S --DATA--> R
-S --DATA--> R
-S <--FINISHED-- R
-
-
-Delayed HEADER:
-S --HEADER-->
-S --DATA--> R // STORE IT
-  --HEADER--> R // apply left data and continue
-S --DATA--> R
-S <--FINISHED-- R
-
-
-NO HEADER:
-S --HEADER--> !
-S --DATA--> R // STORE IT
-S --DATA--> R // STORE IT
-S <--MISSING_HEADER-- R // SEND AFTER 10 ms after DATA1
-S --HEADER--> R
-S <--FINISHED-- R
-
-
-NO DATA:
-S --HEADER--> R
-S --DATA--> R
-S --DATA--> !
-S --STATUS--> R
-S <--MISSING_DATA -- R
-S --DATA--> R
-S <--FINISHED-- R
-*/
+//! UDP protocol
+//!
+//! Every datagram is a *packet* of its own, starting with its kind. Handshake
+//! packets contain a single [`InitFrame`], data packets a sequence number and
+//! as many [`UdpFrame`]s as fit in [`MAX_PACKET_SIZE`]. Messages are split into
+//! fragments which know their position in the message, so they can be put
+//! together no matter in which order they arrive.
+//!
+//! Packets containing stream control frames or fragments of messages on
+//! streams with [`Promises::GUARANTEED_DELIVERY`] are *reliable*: the receiver
+//! acknowledges them and the sender sends their frames again in a new packet
+//! if no acknowledgement arrives in time. Fragments of other streams are sent
+//! only once, so a lost packet doesn't hold back newer data.
+//!
+//! [`Promises::ORDERED`] is honored per stream, so a lost packet only delays
+//! messages of the streams it contained. On ordered streams without guaranteed
+//! delivery, messages that are older than the last delivered one are dropped.
+//!
+//! The acknowledgements are collected by the [`UdpRecvProtocol`] and sent by
+//! the [`UdpSendProtocol`] with its next `flush`, so both halves of a channel
+//! have to be created together with [`udp_protocols`].
+//!
+//! Messages of streams with [`Promises::ENCRYPTED`] are sealed before they are
+//! split into fragments and opened once they are complete again.
+//!
+//! [`InitFrame`]: crate::frame::InitFrame
+//! [`UdpFrame`]: crate::frame::UdpFrame
+//! [`Promises::GUARANTEED_DELIVERY`]: crate::Promises::GUARANTEED_DELIVERY
+//! [`Promises::ORDERED`]: crate::Promises::ORDERED
+//! [`Promises::ENCRYPTED`]: crate::Promises::ENCRYPTED
+use crate::{
+    crypto::{OpenError, RecvCipher, SendCipher},
+    error::ProtocolError,
+    event::ProtocolEvent,
+    frame::{InitFrame, OTFrame, UdpFrame},
+    handshake::{ReliableDrain, ReliableSink},
+    message::ALLOC_BLOCK,
+    metrics::{ChannelStats, ProtocolMetricCache, RemoveReason},
+    prio::PrioManager,
+    types::{Bandwidth, Mid, Promises, Sid},
+    RecvProtocol, SendProtocol, UnreliableDrain, UnreliableSink,
+};
+use async_trait::async_trait;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::{
+    collections::{hash_map::Entry, BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+#[cfg(feature = "trace_pedantic")]
+use tracing::trace;
+use tracing::{debug, info};
+
+const PACKET_INIT: u8 = 0;
+const PACKET_UNRELIABLE: u8 = 1;
+const PACKET_RELIABLE: u8 = 2;
+/// kind and sequence number
+const PACKET_HEADER_SIZE: usize = 9;
+
+/// Largest packet that is sent, chosen so that packets don't get fragmented
+/// on common links (1500 bytes MTU minus IPv6 and UDP headers)
+pub const MAX_PACKET_SIZE: usize = 1452;
+/// Handshake frames are sent this many times, as the handshake has no
+/// acknowledgements. Duplicates are ignored by the receiver.
+const HANDSHAKE_REDUNDANCY: usize = 3;
+/// Shutdown is the last frame sent on a channel, so there is no one left to
+/// send it again if it gets lost.
+const SHUTDOWN_REDUNDANCY: usize = 3;
+/// Send an empty packet when nothing was sent for this long, so the remote side
+/// can tell that the channel is still alive
+pub const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(1);
+const INITIAL_RTT: Duration = Duration::from_millis(100);
+const MIN_RETRANSMIT_TIMEOUT: Duration = Duration::from_millis(20);
+const MAX_RETRANSMIT_TIMEOUT: Duration = Duration::from_secs(2);
+/// Number of incomplete messages kept per stream without guaranteed delivery,
+/// older ones are dropped
+const MAX_INCOMPLETE_UNRELIABLE: usize = 64;
+/// Number of out of order message ids remembered per stream without guaranteed
+/// delivery to detect duplicates
+const MAX_DELIVERED_UNRELIABLE: usize = 1024;
+/// Number of frames kept for streams which are not opened yet
+const MAX_EARLY_FRAMES: usize = 4096;
+/// Largest message the remote side may send, the buffer for a message is
+/// allocated as soon as its first fragment arrives
+const MAX_MESSAGE_SIZE: u64 = ALLOC_BLOCK as u64;
+/// Bytes of incomplete messages, and of complete messages waiting for earlier
+/// ones, which are kept per channel
+const MAX_BUFFERED_BYTES: u64 = 4 * MAX_MESSAGE_SIZE;
+/// Number of incomplete or waiting messages kept per stream with guaranteed
+/// delivery
+const MAX_BUFFERED_GUARANTEED: usize = 4096;
+
+/// State shared between the sending and receiving half of a UDP channel
+#[derive(Debug, Default)]
+struct Shared {
+    /// Sequence numbers of received reliable packets which need to be
+    /// acknowledged
+    acks_to_send: Vec<u64>,
+    /// Sequence numbers of our packets the remote side acknowledged
+    acks_received: Vec<u64>,
+    /// Streams opened by the local side or on a previous channel, the remote
+    /// side may send on them without us receiving an `OpenStream`
+    local_streams: HashMap<Sid, Promises>,
+}
+
+#[derive(Debug)]
+struct SendStream {
+    promises: Promises,
+    next_smid: Mid,
+}
+
+/// A message that the `PrioManager` is sending
+#[derive(Debug)]
+struct SendingMessage {
+    sid: Sid,
+    smid: Mid,
+    length: u64,
+    offset: u64,
+}
+
+/// The reliable frames of a packet that wasn't acknowledged yet
+#[derive(Debug)]
+struct SentPacket {
+    sent: Instant,
+    frames: Vec<UdpFrame>,
+}
+
+/// UDP implementation of [`SendProtocol`]
+///
+/// [`SendProtocol`]: crate::SendProtocol
+#[derive(Debug)]
+pub struct UdpSendProtocol<D>
+where
+    D: UnreliableDrain<DataFormat = BytesMut>,
+{
+    buffer: BytesMut,
+    store: PrioManager,
+    next_mid: Mid,
+    sending: HashMap<Mid, SendingMessage>,
+    streams: HashMap<Sid, SendStream>,
+    /// Reliable frames which were not sent yet
+    control: VecDeque<UdpFrame>,
+    unacked: HashMap<u64, SentPacket>,
+    next_seq: u64,
+    rtt: Duration,
+    closing_streams: Vec<Sid>,
+    notify_closing_streams: Vec<Sid>,
+    pending_shutdown: bool,
+    shared: Arc<Mutex<Shared>>,
+    drain: D,
+    cipher: Option<SendCipher>,
+    last: Instant,
+    metrics: ProtocolMetricCache,
+}
+
+#[derive(Debug)]
+struct IncomingMessage {
+    data: BytesMut,
+    received: u64,
+    offsets: HashSet<u64>,
+}
+
+#[derive(Debug)]
+struct RecvStream {
+    promises: Promises,
+    /// All messages before this one were delivered or skipped
+    next_smid: Mid,
+    /// Messages after `next_smid` that were delivered already
+    delivered: BTreeSet<Mid>,
+    /// Complete messages waiting for earlier ones on ordered streams
+    complete: BTreeMap<Mid, Bytes>,
+    incoming: BTreeMap<Mid, IncomingMessage>,
+    /// Size of the messages in `incoming` and `complete`
+    buffered: u64,
+}
+
+/// UDP implementation of [`RecvProtocol`]
+///
+/// [`RecvProtocol`]: crate::RecvProtocol
+#[derive(Debug)]
+pub struct UdpRecvProtocol<S>
+where
+    S: UnreliableSink<DataFormat = BytesMut>,
+{
+    streams: HashMap<Sid, RecvStream>,
+    closed_streams: HashSet<Sid>,
+    /// Fragments of streams whose `OpenStream` didn't arrive yet
+    early: Vec<UdpFrame>,
+    events: VecDeque<ProtocolEvent>,
+    shutdown: bool,
+    /// The remote side violated the protocol, e.g. a message failed to
+    /// authenticate, so the channel can't be trusted anymore
+    violated: bool,
+    /// Init frames that were received already, to skip the redundant copies
+    received_init: Vec<InitFrame>,
+    shared: Arc<Mutex<Shared>>,
+    sink: S,
+    cipher: Option<RecvCipher>,
+    metrics: ProtocolMetricCache,
+}
+
+/// Creates both halves of a UDP channel, which share the acknowledgements of
+/// received packets.
+pub fn udp_protocols<D, S>(
+    drain: D,
+    sink: S,
+    metrics: ProtocolMetricCache,
+) -> (UdpSendProtocol<D>, UdpRecvProtocol<S>)
+where
+    D: UnreliableDrain<DataFormat = BytesMut>,
+    S: UnreliableSink<DataFormat = BytesMut>,
+{
+    let shared = Arc::new(Mutex::new(Shared::default()));
+    (
+        UdpSendProtocol {
+            buffer: BytesMut::new(),
+            store: PrioManager::new(metrics.clone()),
+            next_mid: 0u64,
+            sending: HashMap::new(),
+            streams: HashMap::new(),
+            control: VecDeque::new(),
+            unacked: HashMap::new(),
+            next_seq: 0u64,
+            rtt: INITIAL_RTT,
+            closing_streams: vec![],
+            notify_closing_streams: vec![],
+            pending_shutdown: false,
+            shared: Arc::clone(&shared),
+            drain,
+            cipher: None,
+            last: Instant::now(),
+            metrics: metrics.clone(),
+        },
+        UdpRecvProtocol {
+            streams: HashMap::new(),
+            closed_streams: HashSet::new(),
+            early: vec![],
+            events: VecDeque::new(),
+            shutdown: false,
+            violated: false,
+            received_init: vec![],
+            shared,
+            sink,
+            cipher: None,
+            metrics,
+        },
+    )
+}
+
+impl<D> UdpSendProtocol<D>
+where
+    D: UnreliableDrain<DataFormat = BytesMut>,
+{
+    /// Link quality of this channel, see [`ChannelStats`]
+    pub fn stats(&self) -> Arc<ChannelStats> { self.metrics.stats() }
+
+    fn retransmit_timeout(&self) -> Duration {
+        (self.rtt * 2)
+            .max(MIN_RETRANSMIT_TIMEOUT)
+            .min(MAX_RETRANSMIT_TIMEOUT)
+    }
+
+    fn open_stream(&mut self, sid: Sid, prio: u8, promises: Promises, guaranteed_bandwidth: u64) {
+        self.store
+            .open_stream(sid, prio, promises, guaranteed_bandwidth);
+        self.streams.insert(sid, SendStream {
+            promises,
+            next_smid: 0,
+        });
+    }
+
+    /// Whether frames belonging to the stream still wait for being sent or
+    /// acknowledged
+    fn has_pending_frames(&self, sid: Sid) -> bool {
+        let of_stream = |frame: &UdpFrame| match frame {
+            UdpFrame::OpenStream { sid: s, .. } | UdpFrame::Fragment { sid: s, .. } => *s == sid,
+            _ => false,
+        };
+        self.control.iter().any(of_stream)
+            || self
+                .unacked
+                .values()
+                .any(|packet| packet.frames.iter().any(of_stream))
+    }
+
+    fn try_close_stream(&mut self, sid: Sid) -> bool {
+        if self.has_pending_frames(sid) || !self.store.try_close_stream(sid) {
+            return false;
+        }
+        self.streams.remove(&sid);
+        true
+    }
+
+    /// Applies the acknowledgements the receiving half got and queues the
+    /// frames of packets that weren't acknowledged in time again
+    fn process_acks(&mut self, now: Instant) {
+        let acks = std::mem::take(&mut self.shared.lock().unwrap().acks_received);
+        let mut sampled = false;
+        for seq in acks {
+            if let Some(packet) = self.unacked.remove(&seq) {
+                // Frames are sent again in new packets, so every ack belongs to exactly one
+                // transmission
+                let sample = now.duration_since(packet.sent);
+                self.rtt = (self.rtt * 7 + sample) / 8;
+                sampled = true;
+            }
+        }
+        if sampled {
+            self.metrics.ping(self.rtt);
+        }
+
+        let timeout = self.retransmit_timeout();
+        let mut lost = self
+            .unacked
+            .iter()
+            .filter(|(_, packet)| now.duration_since(packet.sent) >= timeout)
+            .map(|(seq, _)| *seq)
+            .collect::<Vec<_>>();
+        // Keep the order in which frames were sent the first time
+        lost.sort_unstable();
+        if !lost.is_empty() {
+            self.metrics.retransmits(lost.len() as u64);
+        }
+        for seq in lost {
+            #[cfg(feature = "trace_pedantic")]
+            trace!(?seq, "resend packet");
+            let packet = self.unacked.remove(&seq).unwrap();
+            self.control.extend(packet.frames);
+        }
+    }
+
+    /// Converts the frames of the `PrioManager` to fragments, returning them
+    /// with whether they need to be sent reliably
+    fn fragments_of(&mut self, frames: Vec<OTFrame>) -> Vec<(UdpFrame, bool)> {
+        let mut fragments = Vec::with_capacity(frames.len());
+        let mut data_frames = 0;
+        let mut data_bandwidth = 0;
+        for frame in frames {
+            let (mid, data) = match frame {
+                OTFrame::DataHeader { mid, length: 0, .. } => (mid, Bytes::new()),
+                OTFrame::Data { mid, data } => (mid, data),
+                _ => continue,
+            };
+            let msg = match self.sending.get_mut(&mid) {
+                Some(msg) => msg,
+                None => continue,
+            };
+            data_frames += 1;
+            data_bandwidth += data.len() as u64;
+            let offset = msg.offset;
+            msg.offset += data.len() as u64;
+            let reliable = self.streams.get(&msg.sid).map_or(false, |s| {
+                s.promises.contains(Promises::GUARANTEED_DELIVERY)
+            });
+            fragments.push((
+                UdpFrame::Fragment {
+                    sid: msg.sid,
+                    smid: msg.smid,
+                    length: msg.length,
+                    offset,
+                    data,
+                },
+                reliable,
+            ));
+            if msg.offset >= msg.length {
+                self.sending.remove(&mid);
+            }
+        }
+        self.metrics.sdata_frames_b(data_frames, data_bandwidth);
+        fragments
+    }
+
+    async fn send_packet(
+        &mut self,
+        payload: BytesMut,
+        reliable_frames: Vec<UdpFrame>,
+        now: Instant,
+    ) -> Result<(), ProtocolError> {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.buffer.reserve(PACKET_HEADER_SIZE + payload.len());
+        self.buffer.put_u8(if reliable_frames.is_empty() {
+            PACKET_UNRELIABLE
+        } else {
+            PACKET_RELIABLE
+        });
+        self.buffer.put_u64_le(seq);
+        self.buffer.extend_from_slice(&payload);
+        if !reliable_frames.is_empty() {
+            self.unacked.insert(seq, SentPacket {
+                sent: now,
+                frames: reliable_frames,
+            });
+        }
+        self.last = now;
+        self.drain.send(self.buffer.split()).await
+    }
+
+    /// Packs the frames into as few packets as possible and sends them
+    async fn send_frames(
+        &mut self,
+        frames: Vec<(UdpFrame, bool)>,
+        now: Instant,
+    ) -> Result<(), ProtocolError> {
+        let mut payload = BytesMut::with_capacity(MAX_PACKET_SIZE);
+        let mut reliable_frames = vec![];
+        for (frame, reliable) in frames {
+            if PACKET_HEADER_SIZE + payload.len() + frame.size() > MAX_PACKET_SIZE
+                && !payload.is_empty()
+            {
+                let reliable_frames = std::mem::take(&mut reliable_frames);
+                self.send_packet(payload.split(), reliable_frames, now)
+                    .await?;
+            }
+            frame.write_bytes(&mut payload);
+            if reliable {
+                reliable_frames.push(frame);
+            }
+        }
+        if !payload.is_empty() {
+            self.send_packet(payload, reliable_frames, now).await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<D> SendProtocol for UdpSendProtocol<D>
+where
+    D: UnreliableDrain<DataFormat = BytesMut>,
+{
+    fn notify_from_recv(&mut self, event: ProtocolEvent) {
+        match event {
+            ProtocolEvent::OpenStream {
+                sid,
+                prio,
+                promises,
+                guaranteed_bandwidth,
+            } => {
+                if let Some(cipher) = &self.cipher {
+                    cipher.open_stream(sid, promises);
+                }
+                self.open_stream(sid, prio, promises, guaranteed_bandwidth);
+                self.shared
+                    .lock()
+                    .unwrap()
+                    .local_streams
+                    .insert(sid, promises);
+            },
+            ProtocolEvent::CloseStream { sid } => {
+                if !self.store.try_close_stream(sid) {
+                    #[cfg(feature = "trace_pedantic")]
+                    trace!(?sid, "hold back notify close stream");
+                    self.notify_closing_streams.push(sid);
+                } else {
+                    self.streams.remove(&sid);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    async fn send(&mut self, event: ProtocolEvent) -> Result<(), ProtocolError> {
+        #[cfg(feature = "trace_pedantic")]
+        trace!(?event, "send");
+        match event {
+            ProtocolEvent::OpenStream {
+                sid,
+                prio,
+                promises,
+                guaranteed_bandwidth,
+            } => {
+                match &self.cipher {
+                    Some(cipher) => cipher.open_stream(sid, promises),
+                    None if promises.contains(Promises::ENCRYPTED) => {
+                        info!(?sid, "no keys were exchanged to encrypt this stream");
+                        return Err(ProtocolError::Closed);
+                    },
+                    None => {},
+                }
+                self.open_stream(sid, prio, promises, guaranteed_bandwidth);
+                self.shared
+                    .lock()
+                    .unwrap()
+                    .local_streams
+                    .insert(sid, promises);
+                self.control.push_back(UdpFrame::OpenStream {
+                    sid,
+                    prio,
+                    promises,
+                    guaranteed_bandwidth,
+                });
+            },
+            ProtocolEvent::CloseStream { sid } => {
+                if self.try_close_stream(sid) {
+                    self.control.push_back(UdpFrame::CloseStream { sid });
+                } else {
+                    #[cfg(feature = "trace_pedantic")]
+                    trace!(?sid, "hold back close stream");
+                    self.closing_streams.push(sid);
+                }
+            },
+            ProtocolEvent::Shutdown => {
+                if self.store.is_empty() && self.control.is_empty() && self.unacked.is_empty() {
+                    let now = Instant::now();
+                    for _ in 0..SHUTDOWN_REDUNDANCY {
+                        self.send_frames(vec![(UdpFrame::Shutdown, false)], now)
+                            .await?;
+                    }
+                } else {
+                    #[cfg(feature = "trace_pedantic")]
+                    trace!("hold back shutdown");
+                    self.pending_shutdown = true;
+                }
+            },
+            ProtocolEvent::Message { data, sid } => {
+                self.metrics.smsg_ib(sid, data.len() as u64);
+                let data = match &mut self.cipher {
+                    Some(cipher) => cipher.seal(sid, data),
+                    None => data,
+                };
+                let stream = self.streams.get_mut(&sid).unwrap();
+                let smid = stream.next_smid;
+                stream.next_smid += 1;
+                self.sending.insert(self.next_mid, SendingMessage {
+                    sid,
+                    smid,
+                    length: data.len() as u64,
+                    offset: 0,
+                });
+                self.store.add(data, self.next_mid, sid);
+                self.next_mid += 1;
+            },
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self, bandwidth: Bandwidth, dt: Duration) -> Result<(), ProtocolError> {
+        let now = Instant::now();
+        self.process_acks(now);
+
+        let mut finished_streams = vec![];
+        for (i, &sid) in self.closing_streams.iter().enumerate() {
+            if !self.has_pending_frames(sid) && self.store.try_close_stream(sid) {
+                #[cfg(feature = "trace_pedantic")]
+                trace!(?sid, "close stream, as it's now empty");
+                finished_streams.push(i);
+            }
+        }
+        for i in finished_streams.iter().rev() {
+            let sid = self.closing_streams.remove(*i);
+            self.streams.remove(&sid);
+            self.control.push_back(UdpFrame::CloseStream { sid });
+        }
+
+        let mut finished_streams = vec![];
+        for (i, sid) in self.notify_closing_streams.iter().enumerate() {
+            if self.store.try_close_stream(*sid) {
+                #[cfg(feature = "trace_pedantic")]
+                trace!(?sid, "close stream, as it's now empty");
+                finished_streams.push(i);
+            }
+        }
+        for i in finished_streams.iter().rev() {
+            let sid = self.notify_closing_streams.remove(*i);
+            self.streams.remove(&sid);
+        }
+
+        let acks = std::mem::take(&mut self.shared.lock().unwrap().acks_to_send);
+        let mut frames = acks
+            .into_iter()
+            .map(|seq| (UdpFrame::Ack { seq }, false))
+            .chain(self.control.drain(..).map(|frame| (frame, true)))
+            .collect::<Vec<_>>();
+        let (data_frames, _) = self.store.grab(bandwidth, dt);
+        frames.extend(self.fragments_of(data_frames));
+
+        if !frames.is_empty() {
+            self.send_frames(frames, now).await?;
+        } else if now.duration_since(self.last) >= KEEPALIVE_INTERVAL {
+            self.send_packet(BytesMut::new(), vec![], now).await?;
+        }
+
+        if self.pending_shutdown
+            && self.store.is_empty()
+            && self.control.is_empty()
+            && self.unacked.is_empty()
+        {
+            #[cfg(feature = "trace_pedantic")]
+            trace!("shutdown, as it's now empty");
+            for _ in 0..SHUTDOWN_REDUNDANCY {
+                self.send_frames(vec![(UdpFrame::Shutdown, false)], now)
+                    .await?;
+            }
+            self.pending_shutdown = false;
+        }
+        Ok(())
+    }
+}
+
+impl RecvStream {
+    fn new(promises: Promises) -> Self {
+        Self {
+            promises,
+            next_smid: 0,
+            delivered: BTreeSet::new(),
+            complete: BTreeMap::new(),
+            incoming: BTreeMap::new(),
+            buffered: 0,
+        }
+    }
+
+    fn guaranteed(&self) -> bool { self.promises.contains(Promises::GUARANTEED_DELIVERY) }
+
+    fn is_delivered(&self, smid: Mid) -> bool {
+        smid < self.next_smid || self.delivered.contains(&smid)
+    }
+
+    /// Stores a fragment, returning the message if it is complete now.
+    /// `available` is how many bytes this stream may buffer in addition to
+    /// the other streams of the channel. Fails if the fragment violates the
+    /// protocol.
+    fn insert_fragment(
+        &mut self,
+        smid: Mid,
+        length: u64,
+        offset: u64,
+        data: &[u8],
+        available: u64,
+    ) -> Result<Option<Bytes>, &'static str> {
+        if length > MAX_MESSAGE_SIZE {
+            return Err("message exceeds the maximum size");
+        }
+        let end = match offset.checked_add(data.len() as u64) {
+            Some(end) if end <= length => end,
+            _ => return Err("fragment exceeds message"),
+        };
+        if self.is_delivered(smid) {
+            return Ok(None);
+        }
+        if !self.incoming.contains_key(&smid) {
+            if self.guaranteed() {
+                if self.incoming.len() + self.complete.len() >= MAX_BUFFERED_GUARANTEED
+                    || self.buffered + length > available
+                {
+                    return Err("too many buffered messages");
+                }
+            } else {
+                while !self.incoming.is_empty()
+                    && (self.incoming.len() >= MAX_INCOMPLETE_UNRELIABLE
+                        || self.buffered + length > available)
+                {
+                    let oldest = *self.incoming.keys().next().unwrap();
+                    self.remove_incoming(oldest);
+                }
+                if self.buffered + length > available {
+                    return Ok(None);
+                }
+            }
+            self.buffered += length;
+        }
+        let msg = self
+            .incoming
+            .entry(smid)
+            .or_insert_with(|| IncomingMessage {
+                data: BytesMut::from(&vec![0u8; length as usize][..]),
+                received: 0,
+                offsets: HashSet::new(),
+            });
+        if msg.data.len() as u64 != length {
+            return Ok(None);
+        }
+        if msg.offsets.insert(offset) {
+            msg.data[offset as usize..end as usize].copy_from_slice(data);
+            msg.received += data.len() as u64;
+        }
+        if msg.received < length {
+            return Ok(None);
+        }
+        Ok(self.remove_incoming(smid).map(|msg| msg.data.freeze()))
+    }
+
+    fn remove_incoming(&mut self, smid: Mid) -> Option<IncomingMessage> {
+        let msg = self.incoming.remove(&smid)?;
+        self.buffered -= msg.data.len() as u64;
+        Some(msg)
+    }
+
+    /// Hands out complete messages in the order the stream requires
+    fn complete(&mut self, smid: Mid, data: Bytes) -> Vec<Bytes> {
+        let ordered = self.promises.contains(Promises::ORDERED);
+        match (ordered, self.guaranteed()) {
+            (true, true) => {
+                self.buffered += data.len() as u64;
+                self.complete.insert(smid, data);
+                let mut ready = vec![];
+                while let Some(data) = self.complete.remove(&self.next_smid) {
+                    self.buffered -= data.len() as u64;
+                    ready.push(data);
+                    self.next_smid += 1;
+                }
+                ready
+            },
+            (true, false) => {
+                self.next_smid = smid + 1;
+                // Older messages are never delivered, so stop collecting them
+                let newer = self.incoming.split_off(&self.next_smid);
+                self.incoming = newer;
+                self.buffered = self
+                    .incoming
+                    .values()
+                    .map(|msg| msg.data.len() as u64)
+                    .sum();
+                vec![data]
+            },
+            (false, _) => {
+                self.delivered.insert(smid);
+                while self.delivered.remove(&self.next_smid) {
+                    self.next_smid += 1;
+                }
+                if self.delivered.len() > MAX_DELIVERED_UNRELIABLE {
+                    // Messages that got lost are not coming anymore
+                    self.next_smid = *self.delivered.iter().next().unwrap();
+                    while self.delivered.remove(&self.next_smid) {
+                        self.next_smid += 1;
+                    }
+                }
+                vec![data]
+            },
+        }
+    }
+}
+
+impl<S> UdpRecvProtocol<S>
+where
+    S: UnreliableSink<DataFormat = BytesMut>,
+{
+    /// Handles the frames of a data packet, returns whether all frames could
+    /// be handled
+    fn handle_packet(&mut self, mut packet: BytesMut) -> bool {
+        let mut accepted = true;
+        while let Some(frame) = UdpFrame::read_frame(&mut packet) {
+            #[cfg(feature = "trace_pedantic")]
+            trace!(?frame, "recv");
+            accepted &= self.handle_frame(frame);
+        }
+        accepted
+    }
+
+    fn handle_frame(&mut self, frame: UdpFrame) -> bool {
+        match frame {
+            UdpFrame::Ack { seq } => self.shared.lock().unwrap().acks_received.push(seq),
+            UdpFrame::Shutdown => {
+                if !self.shutdown {
+                    self.shutdown = true;
+                    self.events.push_back(ProtocolEvent::Shutdown);
+                }
+            },
+            UdpFrame::OpenStream {
+                sid,
+                prio,
+                promises,
+                guaranteed_bandwidth,
+            } => {
+                if !self.streams.contains_key(&sid) && !self.closed_streams.contains(&sid) {
+                    if let Some(cipher) = &self.cipher {
+                        cipher.open_stream(sid, promises);
+                    }
+                    self.streams.insert(sid, RecvStream::new(promises));
+                    self.events.push_back(ProtocolEvent::OpenStream {
+                        sid,
+                        prio: prio.min(crate::types::HIGHEST_PRIO),
+                        promises,
+                        guaranteed_bandwidth,
+                    });
+                    // Handle fragments that overtook the `OpenStream`
+                    let (early, rest) = std::mem::take(&mut self.early)
+                        .into_iter()
+                        .partition::<Vec<_>, _>(
+                            |frame| matches!(frame, UdpFrame::Fragment { sid: s, .. } if *s == sid),
+                        );
+                    self.early = rest;
+                    for frame in early {
+                        self.handle_frame(frame);
+                    }
+                }
+            },
+            UdpFrame::CloseStream { sid } => {
+                if self.closed_streams.insert(sid) {
+                    if let Some(cipher) = &self.cipher {
+                        cipher.close_stream(sid);
+                    }
+                    self.streams.remove(&sid);
+                    self.events.push_back(ProtocolEvent::CloseStream { sid });
+                }
+            },
+            UdpFrame::Fragment {
+                sid,
+                smid,
+                length,
+                offset,
+                data,
+            } => {
+                if self.closed_streams.contains(&sid) {
+                    return true;
+                }
+                if let Entry::Vacant(entry) = self.streams.entry(sid) {
+                    let local = self.shared.lock().unwrap().local_streams.get(&sid).copied();
+                    match local {
+                        Some(promises) => {
+                            entry.insert(RecvStream::new(promises));
+                        },
+                        None if self.early.len() < MAX_EARLY_FRAMES => {
+                            self.early.push(UdpFrame::Fragment {
+                                sid,
+                                smid,
+                                length,
+                                offset,
+                                data,
+                            });
+                            return true;
+                        },
+                        None => {
+                            debug!(?sid, "dropping fragment of unknown stream");
+                            return false;
+                        },
+                    }
+                }
+                let buffered_by_others = self
+                    .streams
+                    .iter()
+                    .filter(|(s, _)| **s != sid)
+                    .map(|(_, stream)| stream.buffered)
+                    .sum::<u64>();
+                let available = MAX_BUFFERED_BYTES.saturating_sub(buffered_by_others);
+                let stream = self.streams.get_mut(&sid).unwrap();
+                if offset == 0 && !stream.incoming.contains_key(&smid) {
+                    self.metrics.rmsg_ib(sid, length);
+                }
+                self.metrics.rdata_frames_b(data.len() as u64);
+                let complete = match stream.insert_fragment(smid, length, offset, &data, available)
+                {
+                    Ok(complete) => complete,
+                    Err(violation) => {
+                        info!(
+                            ?sid,
+                            ?smid,
+                            "protocol violation by remote side: {}",
+                            violation
+                        );
+                        self.violated = true;
+                        return false;
+                    },
+                };
+                if let Some(msg) = complete {
+                    for data in stream.complete(smid, msg) {
+                        self.metrics
+                            .rmsg_ob(sid, RemoveReason::Finished, data.len() as u64);
+                        let data = match &mut self.cipher {
+                            Some(cipher) => match cipher.open(sid, data) {
+                                Ok(data) => data,
+                                Err(OpenError::Replayed) => {
+                                    debug!(?sid, ?smid, "dropping replayed message");
+                                    continue;
+                                },
+                                Err(OpenError::Tampered) => {
+                                    info!(
+                                        ?sid,
+                                        ?smid,
+                                        "protocol violation: message was tampered with"
+                                    );
+                                    self.violated = true;
+                                    return false;
+                                },
+                            },
+                            None => data,
+                        };
+                        self.events.push_back(ProtocolEvent::Message { sid, data });
+                    }
+                }
+            },
+        }
+        true
+    }
+}
+
+#[async_trait]
+impl<S> RecvProtocol for UdpRecvProtocol<S>
+where
+    S: UnreliableSink<DataFormat = BytesMut>,
+{
+    async fn recv(&mut self) -> Result<ProtocolEvent, ProtocolError> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(event);
+            }
+            if self.violated {
+                return Err(ProtocolError::Closed);
+            }
+            let mut packet = self.sink.recv().await?;
+            if packet.len() < PACKET_HEADER_SIZE {
+                // Left over handshake packets or garbage
+                continue;
+            }
+            let kind = packet.get_u8();
+            let seq = packet.get_u64_le();
+            match kind {
+                PACKET_UNRELIABLE => {
+                    self.handle_packet(packet);
+                },
+                PACKET_RELIABLE => {
+                    // Packets that arrive multiple times are acknowledged every time, in case
+                    // the previous acknowledgement got lost
+                    if self.handle_packet(packet) {
+                        self.shared.lock().unwrap().acks_to_send.push(seq);
+                    }
+                },
+                _ => {},
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<D> ReliableDrain for UdpSendProtocol<D>
+where
+    D: UnreliableDrain<DataFormat = BytesMut>,
+{
+    async fn send(&mut self, frame: InitFrame) -> Result<(), ProtocolError> {
+        let mut buffer = BytesMut::with_capacity(500);
+        buffer.put_u8(PACKET_INIT);
+        frame.write_bytes(&mut buffer);
+        for _ in 1..HANDSHAKE_REDUNDANCY {
+            self.drain.send(buffer.clone()).await?;
+        }
+        self.drain.send(buffer).await
+    }
+
+    fn set_cipher(&mut self, cipher: SendCipher) { self.cipher = Some(cipher); }
+}
+
+#[async_trait]
+impl<S> ReliableSink for UdpRecvProtocol<S>
+where
+    S: UnreliableSink<DataFormat = BytesMut>,
+{
+    async fn recv(&mut self) -> Result<InitFrame, ProtocolError> {
+        loop {
+            let mut packet = self.sink.recv().await?;
+            match packet.first() {
+                Some(&PACKET_INIT) => {
+                    packet.advance(1);
+                    let frame = match InitFrame::read_frame(&mut packet) {
+                        Some(frame) => frame,
+                        None => return Err(ProtocolError::Closed),
+                    };
+                    // Skip the redundant copies, which might arrive late
+                    if !self.received_init.contains(&frame) {
+                        self.received_init.push(frame.clone());
+                        return Ok(frame);
+                    }
+                },
+                // The remote side finished the handshake before us, the reliable content of
+                // this packet will be sent again
+                Some(_) => {},
+                None => return Err(ProtocolError::Closed),
+            }
+        }
+    }
+
+    fn set_cipher(&mut self, cipher: RecvCipher) { self.cipher = Some(cipher); }
+}
+
+#[cfg(test)]
+mod test_utils {
+    //UDP protocol based on Channel
+    use super::*;
+    use crate::metrics::{ProtocolMetricCache, ProtocolMetrics};
+    use async_channel::*;
+
+    pub struct UdpDrain {
+        pub sender: Sender<BytesMut>,
+        /// Decides from the number of the datagram whether it gets lost
+        pub lose: fn(u64) -> bool,
+        pub sent: u64,
+    }
+
+    pub struct UdpSink {
+        pub receiver: Receiver<BytesMut>,
+    }
+
+    /// emulate Udp protocol on a link injecting the faults of `config` in both
+    /// directions
+    pub fn fault_bound(
+        config: crate::FaultConfig,
+    ) -> [(
+        UdpSendProtocol<crate::FaultDrain>,
+        UdpRecvProtocol<crate::FaultSink>,
+    ); 2] {
+        let (d1, s1) = crate::fault_channel(config.clone());
+        let (d2, s2) = crate::fault_channel(crate::FaultConfig {
+            seed: config.seed + 1,
+            ..config
+        });
+        let m = ProtocolMetricCache::new("udp", Arc::new(ProtocolMetrics::new().unwrap()));
+        [udp_protocols(d1, s2, m.clone()), udp_protocols(d2, s1, m)]
+    }
+
+    /// emulate Udp protocol on Channels, losing the datagrams `lose` selects
+    pub fn udp_bound(
+        lose: fn(u64) -> bool,
+        metrics: Option<ProtocolMetricCache>,
+    ) -> [(UdpSendProtocol<UdpDrain>, UdpRecvProtocol<UdpSink>); 2] {
+        let (s1, r1) = async_channel::unbounded();
+        let (s2, r2) = async_channel::unbounded();
+        let m = metrics.unwrap_or_else(|| {
+            ProtocolMetricCache::new("udp", Arc::new(ProtocolMetrics::new().unwrap()))
+        });
+        [
+            udp_protocols(
+                UdpDrain {
+                    sender: s1,
+                    lose,
+                    sent: 0,
+                },
+                UdpSink { receiver: r2 },
+                m.clone(),
+            ),
+            udp_protocols(
+                UdpDrain {
+                    sender: s2,
+                    lose,
+                    sent: 0,
+                },
+                UdpSink { receiver: r1 },
+                m,
+            ),
+        ]
+    }
+
+    #[async_trait]
+    impl UnreliableDrain for UdpDrain {
+        type DataFormat = BytesMut;
+
+        async fn send(&mut self, data: Self::DataFormat) -> Result<(), ProtocolError> {
+            self.sent += 1;
+            if (self.lose)(self.sent) {
+                return Ok(());
+            }
+            self.sender
+                .send(data)
+                .await
+                .map_err(|_| ProtocolError::Closed)
+        }
+    }
+
+    #[async_trait]
+    impl UnreliableSink for UdpSink {
+        type DataFormat = BytesMut;
+
+        async fn recv(&mut self) -> Result<Self::DataFormat, ProtocolError> {
+            self.receiver
+                .recv()
+                .await
+                .map_err(|_| ProtocolError::Closed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        test_utils::*, UdpRecvProtocol, UdpSendProtocol, MAX_BUFFERED_BYTES,
+        MAX_BUFFERED_GUARANTEED, MAX_MESSAGE_SIZE, PACKET_RELIABLE,
+    };
+    use crate::{
+        error::ProtocolError,
+        frame::UdpFrame,
+        metrics::{ProtocolMetricCache, ProtocolMetrics, RemoveReason},
+        types::{Pid, Promises, Sid, STREAM_ID_OFFSET1, STREAM_ID_OFFSET2},
+        Delay, FaultConfig, Identity, InitProtocol, ProtocolEvent, RecvProtocol, SendProtocol,
+        UnreliableDrain, UnreliableSink,
+    };
+    use bytes::{BufMut, Bytes, BytesMut};
+    use std::{sync::Arc, time::Duration};
+
+    fn lose_none(_: u64) -> bool { false }
+
+    fn lose_every_third(n: u64) -> bool { n % 3 == 0 }
+
+    fn open_event(sid: Sid, promises: Promises) -> ProtocolEvent {
+        ProtocolEvent::OpenStream {
+            sid,
+            prio: 3u8,
+            promises,
+            guaranteed_bandwidth: 1_000_000,
+        }
+    }
+
+    /// Opens a stream from p1 to p2 and sends `msgs` followed by closing the
+    /// stream. Both sides are flushed regularly, so that acknowledgements and
+    /// retransmissions happen. Returns all events p2 received until the stream
+    /// was closed. Encrypted streams require a handshake first.
+    async fn transfer(
+        lose: fn(u64) -> bool,
+        promises: Promises,
+        msgs: Vec<Bytes>,
+    ) -> Vec<ProtocolEvent> {
+        transfer_with_metrics(lose, promises, msgs, None).await
+    }
+
+    async fn transfer_with_metrics(
+        lose: fn(u64) -> bool,
+        promises: Promises,
+        msgs: Vec<Bytes>,
+        metrics: Option<ProtocolMetricCache>,
+    ) -> Vec<ProtocolEvent> {
+        transfer_on(udp_bound(lose, metrics), promises, msgs).await
+    }
+
+    async fn transfer_on<D, S>(
+        [mut p1, mut p2]: [(UdpSendProtocol<D>, UdpRecvProtocol<S>); 2],
+        promises: Promises,
+        msgs: Vec<Bytes>,
+    ) -> Vec<ProtocolEvent>
+    where
+        D: UnreliableDrain<DataFormat = BytesMut> + 'static,
+        S: UnreliableSink<DataFormat = BytesMut> + 'static,
+    {
+        if promises.contains(Promises::ENCRYPTED) {
+            let (i1, i2) = (Identity::generate(), Identity::generate());
+            let (h1, h2) = tokio::join!(
+                p1.initialize(true, Pid::fake(2), 1337, &i1),
+                p2.initialize(false, Pid::fake(3), 42, &i2)
+            );
+            assert!(h1.is_ok() && h2.is_ok());
+        }
+        let (mut s1, mut r1) = p1;
+        let (mut s2, mut r2) = p2;
+        let sid = Sid::new(1);
+
+        let mut receiver = tokio::spawn(async move {
+            let mut events = vec![];
+            loop {
+                tokio::select! {
+                    e = r2.recv() => {
+                        let e = e.unwrap();
+                        if let ProtocolEvent::OpenStream { .. } = &e {
+                            s2.notify_from_recv(e.clone());
+                        }
+                        let closed = matches!(e, ProtocolEvent::CloseStream { .. });
+                        events.push(e);
+                        if closed {
+                            break events;
+                        }
+                    },
+                    _ = tokio::time::sleep(Duration::from_millis(5)) => {
+                        s2.flush(1_000_000, Duration::from_millis(5)).await.unwrap();
+                    },
+                }
+            }
+        });
+        // Keep processing acks of p2
+        let acks = tokio::spawn(async move { while r1.recv().await.is_ok() {} });
+
+        s1.send(open_event(sid, promises)).await.unwrap();
+        for data in msgs {
+            s1.send(ProtocolEvent::Message { sid, data }).await.unwrap();
+        }
+        s1.send(ProtocolEvent::CloseStream { sid }).await.unwrap();
+        let events = loop {
+            s1.flush(1_000_000, Duration::from_millis(5)).await.unwrap();
+            tokio::select! {
+                r = &mut receiver => break r.unwrap(),
+                _ = tokio::time::sleep(Duration::from_millis(5)) => {},
+            }
+        };
+        acks.abort();
+        events
+    }
+
+    fn messages(events: &[ProtocolEvent]) -> Vec<Bytes> {
+        events
+            .iter()
+            .filter_map(|e| match e {
+                ProtocolEvent::Message { data, .. } => Some(data.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn handshake_all_good() {
+        let [mut p1, mut p2] = udp_bound(lose_none, None);
+        let (i1, i2) = (Identity::generate(), Identity::generate());
+        let (k1, k2) = (i1.public_key(), i2.public_key());
+        let r1 = tokio::spawn(async move { p1.initialize(true, Pid::fake(2), 1337, &i1).await });
+        let r2 = tokio::spawn(async move { p2.initialize(false, Pid::fake(3), 42, &i2).await });
+        let (r1, r2) = tokio::join!(r1, r2);
+        assert_eq!(r1.unwrap(), Ok((Pid::fake(3), STREAM_ID_OFFSET1, 42, k2)));
+        assert_eq!(r2.unwrap(), Ok((Pid::fake(2), STREAM_ID_OFFSET2, 1337, k1)));
+    }
+
+    #[tokio::test]
+    async fn handshake_survives_lost_datagrams() {
+        // Drops one of the redundant copies of every handshake frame
+        let [mut p1, mut p2] = udp_bound(|n| n % 3 == 1, None);
+        let (i1, i2) = (Identity::generate(), Identity::generate());
+        let (k1, k2) = (i1.public_key(), i2.public_key());
+        let r1 = tokio::spawn(async move { p1.initialize(true, Pid::fake(2), 1337, &i1).await });
+        let r2 = tokio::spawn(async move { p2.initialize(false, Pid::fake(3), 42, &i2).await });
+        let (r1, r2) = tokio::join!(r1, r2);
+        assert_eq!(r1.unwrap(), Ok((Pid::fake(3), STREAM_ID_OFFSET1, 42, k2)));
+        assert_eq!(r2.unwrap(), Ok((Pid::fake(2), STREAM_ID_OFFSET2, 1337, k1)));
+    }
+
+    #[tokio::test]
+    async fn send_short_and_long_msg() {
+        let metrics =
+            ProtocolMetricCache::new("long_udp", Arc::new(ProtocolMetrics::new().unwrap()));
+        let [p1, p2] = udp_bound(lose_none, Some(metrics.clone()));
+        let (mut s, mut r) = (p1.0, p2.1);
+        let sid = Sid::new(1);
+        let event = open_event(sid, Promises::GUARANTEED_DELIVERY);
+        s.send(event.clone()).await.unwrap();
+        s.flush(1_000_000, Duration::from_secs(1)).await.unwrap();
+        assert_eq!(r.recv().await.unwrap(), event);
+
+        for data in vec![
+            Bytes::from(&[188u8; 600][..]),
+            Bytes::from(&[99u8; 500_000][..]),
+            Bytes::new(),
+        ] {
+            let event = ProtocolEvent::Message { sid, data };
+            s.send(event.clone()).await.unwrap();
+            s.flush(1_000_000, Duration::from_secs(1)).await.unwrap();
+            assert_eq!(r.recv().await.unwrap(), event);
+        }
+        let mut metrics = metrics;
+        metrics.assert_msg(sid, 3, RemoveReason::Finished);
+        metrics.assert_msg_bytes(sid, 500_600, RemoveReason::Finished);
+    }
+
+    #[tokio::test]
+    async fn stats_with_loss() {
+        let metrics =
+            ProtocolMetricCache::new("lossy_udp", Arc::new(ProtocolMetrics::new().unwrap()));
+        let stats = metrics.stats();
+        let msgs = (0..20u32)
+            .map(|i| Bytes::from(vec![i as u8; 1000]))
+            .collect::<Vec<_>>();
+        transfer_with_metrics(
+            lose_every_third,
+            Promises::GUARANTEED_DELIVERY,
+            msgs,
+            Some(metrics),
+        )
+        .await;
+        assert!(stats.retransmissions() > 0);
+        assert!(stats.rtt().is_some());
+        assert_eq!(stats.queued_messages(), 0);
+        assert_eq!(stats.queued_bytes(), 0);
+        assert_eq!(stats.sent_bytes(3), 20_000);
+        assert_eq!(stats.sent_bytes(0), 0);
+    }
+
+    #[tokio::test]
+    async fn guaranteed_ordered_with_loss() {
+        let msgs = (0..100u32)
+            .map(|i| Bytes::from(vec![i as u8; 10 + i as usize * 100]))
+            .collect::<Vec<_>>();
+        let events = transfer(
+            lose_every_third,
+            Promises::GUARANTEED_DELIVERY | Promises::ORDERED,
+            msgs.clone(),
+        )
+        .await;
+        assert!(matches!(events[0], ProtocolEvent::OpenStream { .. }));
+        assert_eq!(messages(&events), msgs);
+    }
+
+    #[tokio::test]
+    async fn guaranteed_unordered_with_loss() {
+        let msgs = (0..100u32)
+            .map(|i| Bytes::from(vec![i as u8; 10 + i as usize * 100]))
+            .collect::<Vec<_>>();
+        let events = transfer(
+            lose_every_third,
+            Promises::GUARANTEED_DELIVERY,
+            msgs.clone(),
+        )
+        .await;
+        let mut received = messages(&events);
+        received.sort_by_key(|data| data.len());
+        assert_eq!(received, msgs);
+    }
+
+    #[tokio::test]
+    async fn encrypted_with_loss() {
+        let msgs = (0..50u32)
+            .map(|i| Bytes::from(vec![i as u8; 10 + i as usize * 100]))
+            .collect::<Vec<_>>();
+        let events = transfer(
+            lose_every_third,
+            Promises::GUARANTEED_DELIVERY | Promises::ORDERED | Promises::ENCRYPTED,
+            msgs.clone(),
+        )
+        .await;
+        assert_eq!(messages(&events), msgs);
+    }
+
+    #[tokio::test]
+    async fn unreliable_drops_lost_messages() {
+        // Every message fits in a datagram of its own, some of which get lost
+        let msgs = (0..10u8)
+            .map(|i| Bytes::from(vec![i; 1300]))
+            .collect::<Vec<_>>();
+        let events = transfer(lose_every_third, Promises::ORDERED, msgs).await;
+        let received = messages(&events);
+        assert!(!received.is_empty());
+        assert!(received.len() < 10);
+        // Never delivered out of order
+        let mut sorted = received.clone();
+        sorted.sort();
+        assert_eq!(received, sorted);
+    }
+
+    #[tokio::test]
+    async fn close_stream_after_guaranteed_msgs() {
+        let msgs = (0..20u8)
+            .map(|i| Bytes::from(vec![i; 3000]))
+            .collect::<Vec<_>>();
+        let events = transfer(lose_every_third, Promises::GUARANTEED_DELIVERY, msgs).await;
+        assert_eq!(events.len(), 22);
+        assert_eq!(messages(&events).len(), 20);
+    }
+
+    #[tokio::test]
+    async fn faulty_link() {
+        let config = FaultConfig {
+            drop_rate: 0.1,
+            delay: Delay::Uniform(Duration::from_millis(1), Duration::from_millis(10)),
+            reorder_rate: 0.1,
+            reorder_delay: Duration::from_millis(15),
+            seed: 7,
+            ..Default::default()
+        };
+        let msgs = (0..30u8)
+            .map(|i| Bytes::from(vec![i; 3000]))
+            .collect::<Vec<_>>();
+        let promises = Promises::ORDERED | Promises::GUARANTEED_DELIVERY;
+        let events = transfer_on(fault_bound(config), promises, msgs.clone()).await;
+        assert_eq!(messages(&events), msgs);
+    }
+
+    #[tokio::test]
+    async fn send_on_stream_from_remote() {
+        //remote opens stream
+        //we send on it
+        let [mut p1, mut p2] = udp_bound(lose_none, None);
+        let event = open_event(Sid::new(10), Promises::ORDERED);
+        p1.0.send(event).await.unwrap();
+        p1.0.flush(1_000_000, Duration::from_secs(1)).await.unwrap();
+        let e = p2.1.recv().await.unwrap();
+        p2.0.notify_from_recv(e);
+        let event = ProtocolEvent::Message {
+            sid: Sid::new(10),
+            data: Bytes::from(&[188u8; 600][..]),
+        };
+        p2.0.send(event.clone()).await.unwrap();
+        p2.0.flush(1_000_000, Duration::from_secs(1)).await.unwrap();
+        let e = p1.1.recv().await.unwrap();
+        assert_eq!(event, e);
+    }
+
+    /// Sends a reliable packet containing `frames` on `drain`, bypassing the
+    /// sending protocol so that invalid frames can be sent
+    async fn send_raw(drain: &mut UdpDrain, frames: Vec<UdpFrame>) {
+        let mut packet = BytesMut::new();
+        packet.put_u8(PACKET_RELIABLE);
+        packet.put_u64_le(0);
+        for frame in frames {
+            frame.write_bytes(&mut packet);
+        }
+        drain.send(packet).await.unwrap();
+    }
+
+    fn open_frame(sid: Sid) -> UdpFrame {
+        UdpFrame::OpenStream {
+            sid,
+            prio: 3,
+            promises: Promises::GUARANTEED_DELIVERY,
+            guaranteed_bandwidth: 0,
+        }
+    }
+
+    fn fragment(sid: Sid, smid: u64, length: u64, offset: u64) -> UdpFrame {
+        UdpFrame::Fragment {
+            sid,
+            smid,
+            length,
+            offset,
+            data: Bytes::from_static(&[1, 2, 3]),
+        }
+    }
+
+    async fn assert_closed_after_open(recv: &mut UdpRecvProtocol<UdpSink>) {
+        assert!(matches!(
+            recv.recv().await,
+            Ok(ProtocolEvent::OpenStream { .. })
+        ));
+        assert_eq!(recv.recv().await, Err(ProtocolError::Closed));
+    }
+
+    #[tokio::test]
+    async fn oversized_message_is_a_violation() {
+        let [(mut s1, _), (_, mut r2)] = udp_bound(lose_none, None);
+        let sid = Sid::new(1);
+        send_raw(&mut s1.drain, vec![
+            open_frame(sid),
+            fragment(sid, 0, u64::MAX, 0),
+        ])
+        .await;
+        assert_closed_after_open(&mut r2).await;
+    }
+
+    #[tokio::test]
+    async fn fragment_past_message_is_a_violation() {
+        let [(mut s1, _), (_, mut r2)] = udp_bound(lose_none, None);
+        let sid = Sid::new(1);
+        send_raw(&mut s1.drain, vec![
+            open_frame(sid),
+            fragment(sid, 0, 10, u64::MAX - 1),
+        ])
+        .await;
+        assert_closed_after_open(&mut r2).await;
+    }
+
+    #[tokio::test]
+    async fn too_many_buffered_messages_is_a_violation() {
+        let [(mut s1, _), (_, mut r2)] = udp_bound(lose_none, None);
+        let sid = Sid::new(1);
+        send_raw(&mut s1.drain, vec![open_frame(sid)]).await;
+        // Incomplete messages are buffered until they are complete
+        for smid in 0..=MAX_BUFFERED_GUARANTEED as u64 {
+            send_raw(&mut s1.drain, vec![fragment(sid, smid, 10, 0)]).await;
+        }
+        assert_closed_after_open(&mut r2).await;
+    }
+
+    #[tokio::test]
+    async fn buffered_bytes_are_limited() {
+        let [(mut s1, _), (_, mut r2)] = udp_bound(lose_none, None);
+        let sid = Sid::new(1);
+        send_raw(&mut s1.drain, vec![open_frame(sid)]).await;
+        for smid in 0..=MAX_BUFFERED_BYTES / MAX_MESSAGE_SIZE {
+            send_raw(&mut s1.drain, vec![fragment(
+                sid,
+                smid,
+                MAX_MESSAGE_SIZE,
+                0,
+            )])
+            .await;
+        }
+        assert_closed_after_open(&mut r2).await;
+    }
+}
@@ -1,18 +1,25 @@
+#[cfg(feature = "quic")]
+use crate::scheduler::QuicConfig;
 use crate::{
     message::{partial_eq_bincode, Message},
-    participant::{A2bStreamOpen, S2bShutdownBparticipant},
-    scheduler::{A2sConnect, Scheduler},
+    participant::{A2bStreamOpen, BandwidthLimit, ChannelStatsMap, S2bShutdownBparticipant},
+    scheduler::{A2sConnect, BandwidthConfig, S2aConnectResult, Scheduler},
 };
 use bytes::Bytes;
 #[cfg(feature = "compression")]
 use lz_fear::raw::DecodeError;
-use network_protocol::{Bandwidth, InitProtocolError, Pid, Prio, Promises, Sid};
+#[cfg(feature = "fault_injection")]
+use network_protocol::FaultConfig;
+use network_protocol::{
+    Bandwidth, ChannelStats, Identity, InitProtocolError, Pid, Prio, Promises, Sid,
+};
 #[cfg(feature = "metrics")]
 use prometheus::Registry;
 use serde::{de::DeserializeOwned, Serialize};
 use std::{
     collections::HashMap,
     net::SocketAddr,
+    path::PathBuf,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
@@ -28,12 +35,26 @@ use tracing::*;
 
 type A2sDisconnect = Arc<Mutex<Option<mpsc::UnboundedSender<(Pid, S2bShutdownBparticipant)>>>>;
 
-/// Represents a Tcp or Udp or Mpsc address
+/// Represents a Tcp or Udp or Mpsc or Quic address
+///
+/// To use Quic the TLS configuration needs to be set on the [`Network`] first,
+/// see [`set_quic_server_config`] and [`set_quic_client_config`]
+///
+/// [`set_quic_server_config`]: Network::set_quic_server_config
+/// [`set_quic_client_config`]: Network::set_quic_client_config
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
 pub enum ProtocolAddr {
     Tcp(SocketAddr),
     Udp(SocketAddr),
     Mpsc(u64),
+    #[cfg(feature = "quic")]
+    Quic(SocketAddr),
+    /// A recording of a [`Participant`] which is played back as remote side,
+    /// can only be [`connect`]ed to. See [`set_record_dir`]
+    ///
+    /// [`connect`]: Network::connect
+    /// [`set_record_dir`]: Network::set_record_dir
+    Replay(PathBuf),
 }
 
 /// `Participants` are generated by the [`Network`] and represent a connection
@@ -46,9 +67,13 @@ pub enum ProtocolAddr {
 pub struct Participant {
     local_pid: Pid,
     remote_pid: Pid,
+    remote_addr: ProtocolAddr,
+    remote_identity: [u8; 32],
     a2b_open_stream_s: Mutex<mpsc::UnboundedSender<A2bStreamOpen>>,
     b2a_stream_opened_r: Mutex<mpsc::UnboundedReceiver<Stream>>,
     a2s_disconnect_s: A2sDisconnect,
+    channel_stats: ChannelStatsMap,
+    bandwidth: Arc<BandwidthLimit>,
 }
 
 /// `Streams` represents a channel to send `n` messages with a certain priority
@@ -93,6 +118,12 @@ pub enum NetworkConnectError {
     InvalidSecret,
     Handshake(InitProtocolError),
     Io(std::io::Error),
+    /// The remote side doesn't know the session anymore, it either waited too
+    /// long for us or got restarted. See [`Network::reconnect`]
+    SessionExpired,
+    /// The remote side proved to own another identity than the expected one,
+    /// see [`Network::connect_authenticated`]
+    UnexpectedIdentity([u8; 32]),
 }
 
 /// Error type thrown by [`Participants`](Participant) methods
@@ -166,6 +197,14 @@ pub struct Network {
     connect_sender: Mutex<mpsc::UnboundedSender<A2sConnect>>,
     connected_receiver: Mutex<mpsc::UnboundedReceiver<Participant>>,
     shutdown_network_s: Option<oneshot::Sender<oneshot::Sender<()>>>,
+    identity: Arc<Mutex<Arc<Identity>>>,
+    #[cfg(feature = "quic")]
+    quic_config: Arc<Mutex<QuicConfig>>,
+    resume_timeout: Arc<Mutex<Duration>>,
+    bandwidth_config: Arc<Mutex<BandwidthConfig>>,
+    #[cfg(feature = "fault_injection")]
+    fault_config: Arc<Mutex<Option<FaultConfig>>>,
+    record_dir: Arc<Mutex<Option<PathBuf>>>,
 }
 
 impl Network {
@@ -241,11 +280,27 @@ impl Network {
         let p = participant_id;
         let span = tracing::info_span!("network", ?p);
         span.in_scope(|| trace!("Starting Network"));
+        let identity = Arc::new(Mutex::new(Arc::new(Identity::generate())));
+        #[cfg(feature = "quic")]
+        let quic_config = Arc::new(Mutex::new(QuicConfig::default()));
+        let resume_timeout = Arc::new(Mutex::new(Duration::from_secs(0)));
+        let bandwidth_config = Arc::new(Mutex::new(BandwidthConfig::default()));
+        #[cfg(feature = "fault_injection")]
+        let fault_config = Arc::new(Mutex::new(None));
+        let record_dir = Arc::new(Mutex::new(None));
         let (scheduler, listen_sender, connect_sender, connected_receiver, shutdown_sender) =
             Scheduler::new(
                 participant_id,
+                Arc::clone(&identity),
                 #[cfg(feature = "metrics")]
                 registry,
+                #[cfg(feature = "quic")]
+                Arc::clone(&quic_config),
+                Arc::clone(&resume_timeout),
+                Arc::clone(&bandwidth_config),
+                #[cfg(feature = "fault_injection")]
+                Arc::clone(&fault_config),
+                Arc::clone(&record_dir),
             );
         let participant_disconnect_sender = Arc::new(Mutex::new(HashMap::new()));
         let (shutdown_network_s, shutdown_network_r) = oneshot::channel();
@@ -271,9 +326,120 @@ impl Network {
             connect_sender: Mutex::new(connect_sender),
             connected_receiver: Mutex::new(connected_receiver),
             shutdown_network_s: Some(shutdown_network_s),
+            identity,
+            #[cfg(feature = "quic")]
+            quic_config,
+            resume_timeout,
+            bandwidth_config,
+            #[cfg(feature = "fault_injection")]
+            fault_config,
+            record_dir,
         }
     }
 
+    /// Sets how long a [`Participant`] whose last channel broke, e.g. because
+    /// the network of one side went down for a moment or its IP address
+    /// changed, waits for a new channel before it fails. Till then its
+    /// [`Streams`] stay open and messages sent on them are kept, see
+    /// [`reconnect`]. Messages which were already in transit on the broken
+    /// channel are lost. The default is zero, so the `Participant` fails
+    /// immediately.
+    ///
+    /// [`Streams`]: crate::api::Stream
+    /// [`reconnect`]: Network::reconnect
+    pub async fn set_resume_timeout(&self, timeout: Duration) {
+        *self.resume_timeout.lock().await = timeout;
+    }
+
+    /// Limits how many bytes per second are sent to each [`Participant`]
+    /// connected afterwards, `None` means unlimited, which is the default. It
+    /// can be changed per `Participant` via
+    /// [`Participant::set_bandwidth_limit`]. When the limit is reached,
+    /// [`Streams`] with a lower [`Prio`] are deferred, while the guaranteed
+    /// bandwidth of every `Stream` is used first.
+    ///
+    /// [`Streams`]: crate::api::Stream
+    /// [`Prio`]: network_protocol::Prio
+    pub async fn set_participant_bandwidth_limit(&self, limit: Option<Bandwidth>) {
+        self.bandwidth_config.lock().await.participant = limit;
+    }
+
+    /// Limits how many bytes per second are sent to all [`Participants`]
+    /// together, `None` means unlimited, which is the default. The limit is
+    /// split fairly: `Participants` that need less than an equal share keep
+    /// their bandwidth, the others split the rest.
+    ///
+    /// [`Participants`]: crate::api::Participant
+    pub async fn set_global_bandwidth_limit(&self, limit: Option<Bandwidth>) {
+        self.bandwidth_config.lock().await.global = limit;
+    }
+
+    /// Makes channels to a [`ProtocolAddr::Mpsc`] [`connect`]ed afterwards
+    /// behave like a bad network connection, `None` turns it off again, which
+    /// is the default. Such channels use the UDP protocol, so messages still
+    /// arrive as promised. Only the connecting `Network` needs to set it, which
+    /// allows running a server and client in one process over a lossy link.
+    ///
+    /// [`connect`]: Network::connect
+    #[cfg(feature = "fault_injection")]
+    pub async fn set_fault_injection(&self, config: Option<FaultConfig>) {
+        *self.fault_config.lock().await = config;
+    }
+
+    /// Sets the long-term key pair this `Network` proves to own during the
+    /// handshake of channels opened afterwards, so that the remote side can
+    /// recognize it, see [`connect_authenticated`]. Without it a new key pair
+    /// is generated for every `Network`.
+    ///
+    /// [`connect_authenticated`]: Network::connect_authenticated
+    pub async fn set_identity(&self, identity: Identity) {
+        *self.identity.lock().await = Arc::new(identity);
+    }
+
+    /// Records every message sent and received by [`Participants`] connected
+    /// afterwards, with the time and the [`Stream`] and its [`Promises`] it
+    /// belongs to. Every `Participant` gets its own file in `dir`, which can
+    /// be played back via [`ProtocolAddr::Replay`] or read with
+    /// [`read_records`]. `None` stops recording new `Participants`, which is
+    /// the default.
+    ///
+    /// [`Participants`]: crate::api::Participant
+    /// [`Promises`]: network_protocol::Promises
+    /// [`read_records`]: crate::read_records
+    pub async fn set_record_dir(&self, dir: Option<PathBuf>) {
+        *self.record_dir.lock().await = dir;
+    }
+
+    /// Sets the TLS configuration, containing the certificate and private
+    /// key, which is used when [`listen`]ing on a [`ProtocolAddr::Quic`].
+    /// Listening fails without it. Already running listeners keep the
+    /// configuration they were started with.
+    ///
+    /// [`listen`]: Network::listen
+    #[cfg(feature = "quic")]
+    pub async fn set_quic_server_config(&self, config: quinn::ServerConfig) {
+        self.quic_config.lock().await.server = Some(config);
+    }
+
+    /// Sets the TLS configuration which is used when [`connect`]ing to a
+    /// [`ProtocolAddr::Quic`]. The certificate of the remote side must be
+    /// trusted by `config` and be valid for `server_name`.
+    ///
+    /// # Examples
+    /// ```ignore
+    /// let mut config = quinn::ClientConfigBuilder::default();
+    /// config.add_certificate_authority(server_cert)?;
+    /// network
+    ///     .set_quic_client_config(config.build(), "localhost".to_string())
+    ///     .await;
+    /// ```
+    ///
+    /// [`connect`]: Network::connect
+    #[cfg(feature = "quic")]
+    pub async fn set_quic_client_config(&self, config: quinn::ClientConfig, server_name: String) {
+        self.quic_config.lock().await.client = Some((config, server_name));
+    }
+
     /// starts listening on an [`ProtocolAddr`].
     /// When the method returns the `Network` is ready to listen for incoming
     /// connections OR has returned a [`NetworkError`] (e.g. port already used).
@@ -370,7 +536,7 @@ impl Network {
         self.connect_sender
             .lock()
             .await
-            .send((address, pid_sender))?;
+            .send((address, S2aConnectResult::Participant(pid_sender)))?;
         let participant = match pid_receiver.await? {
             Ok(p) => p,
             Err(e) => return Err(NetworkError::ConnectFailed(e)),
@@ -384,6 +550,70 @@ impl Network {
         Ok(participant)
     }
 
+    /// Like [`connect`], but fails with
+    /// [`NetworkConnectError::UnexpectedIdentity`] unless the remote side
+    /// proves to own `identity` during the handshake, e.g. a public key that
+    /// was seen the first time a server was connected to. This prevents a man
+    /// in the middle from completing the handshake with a key of its own.
+    ///
+    /// [`connect`]: Network::connect
+    pub async fn connect_authenticated(
+        &self,
+        address: ProtocolAddr,
+        identity: [u8; 32],
+    ) -> Result<Participant, NetworkError> {
+        let participant = self.connect(address).await?;
+        let remote_identity = participant.remote_identity();
+        if remote_identity != identity {
+            warn!(
+                ?remote_identity,
+                "Remote side has an unexpected identity, disconnecting"
+            );
+            let _ = participant.disconnect().await;
+            return Err(NetworkError::ConnectFailed(
+                NetworkConnectError::UnexpectedIdentity(remote_identity),
+            ));
+        }
+        Ok(participant)
+    }
+
+    /// opens a new channel to an [`ProtocolAddr`] and attaches it to the
+    /// existing [`Participant`] with `remote_pid`, e.g. after its old
+    /// channel broke. The `Participant` and its [`Streams`] can be used like
+    /// before and the remote side doesn't notice anything but a delay.
+    /// `address` doesn't need to be the one used in [`connect`], so a
+    /// `Participant` can move to another protocol.
+    ///
+    /// Both sides need to wait for the session to be resumed, see
+    /// [`set_resume_timeout`]. If the remote side doesn't know the session
+    /// anymore this fails with [`NetworkConnectError::SessionExpired`].
+    ///
+    /// # Examples
+    /// ```ignore
+    /// network.set_resume_timeout(Duration::from_secs(30)).await;
+    /// let participant = network.connect(address.clone()).await?;
+    /// // ... the channel broke
+    /// network.reconnect(participant.remote_pid(), address).await?;
+    /// ```
+    ///
+    /// [`Streams`]: crate::api::Stream
+    /// [`connect`]: Network::connect
+    /// [`set_resume_timeout`]: Network::set_resume_timeout
+    #[instrument(name="network", skip(self, address), fields(p = %self.local_pid))]
+    pub async fn reconnect(
+        &self,
+        remote_pid: Pid,
+        address: ProtocolAddr,
+    ) -> Result<(), NetworkError> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        debug!(?address, ?remote_pid, "Reconnect to address");
+        self.connect_sender
+            .lock()
+            .await
+            .send((address, S2aConnectResult::Resume(remote_pid, result_sender)))?;
+        result_receiver.await?.map_err(NetworkError::ConnectFailed)
+    }
+
     /// returns a [`Participant`] created from a [`ProtocolAddr`] you called
     /// [`listen`] on before. This function will either return a working
     /// [`Participant`] ready to open [`Streams`] on OR has returned a
@@ -482,19 +712,28 @@ impl Network {
 }
 
 impl Participant {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         local_pid: Pid,
         remote_pid: Pid,
+        remote_addr: ProtocolAddr,
+        remote_identity: [u8; 32],
         a2b_open_stream_s: mpsc::UnboundedSender<A2bStreamOpen>,
         b2a_stream_opened_r: mpsc::UnboundedReceiver<Stream>,
         a2s_disconnect_s: mpsc::UnboundedSender<(Pid, S2bShutdownBparticipant)>,
+        channel_stats: ChannelStatsMap,
+        bandwidth: Arc<BandwidthLimit>,
     ) -> Self {
         Self {
             local_pid,
             remote_pid,
+            remote_addr,
+            remote_identity,
             a2b_open_stream_s: Mutex::new(a2b_open_stream_s),
             b2a_stream_opened_r: Mutex::new(b2a_stream_opened_r),
             a2s_disconnect_s: Arc::new(Mutex::new(Some(a2s_disconnect_s))),
+            channel_stats,
+            bandwidth,
         }
     }
 
@@ -717,6 +956,64 @@ impl Participant {
 
     /// Returns the remote [`Pid`](network_protocol::Pid)
     pub fn remote_pid(&self) -> Pid { self.remote_pid }
+
+    /// Returns the [`ProtocolAddr`] of the channel this `Participant` was
+    /// first connected with. For listening `Networks` this is the address of
+    /// the remote side, e.g. the socket address of a connecting tcp client.
+    pub fn remote_address(&self) -> &ProtocolAddr { &self.remote_addr }
+
+    /// Returns the public key the remote side proved to own during the
+    /// handshake, see [`Network::set_identity`]
+    pub fn remote_identity(&self) -> [u8; 32] { self.remote_identity }
+
+    /// Returns the link statistics of all channels this `Participant` is
+    /// currently connected with, e.g. round trip time, retransmissions, queue
+    /// depth and sent bytes per [`Prio`]. They are ordered by creation,
+    /// messages are sent on the last one.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use tokio::runtime::Runtime;
+    /// use veloren_network::{Network, Pid, ProtocolAddr};
+    ///
+    /// # fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    /// // Create a Network, connect on port 2080 and print the round trip time
+    /// let runtime = Runtime::new().unwrap();
+    /// let network = Network::new(Pid::new(), &runtime);
+    /// # let remote = Network::new(Pid::new(), &runtime);
+    /// runtime.block_on(async {
+    ///     # remote.listen(ProtocolAddr::Tcp("127.0.0.1:2080".parse().unwrap())).await?;
+    ///     let p = network
+    ///         .connect(ProtocolAddr::Tcp("127.0.0.1:2080".parse().unwrap()))
+    ///         .await?;
+    ///     if let Some(stats) = p.channel_stats().last() {
+    ///         println!("rtt: {:?}", stats.rtt());
+    ///     }
+    ///     # Ok(())
+    /// })
+    /// # }
+    /// ```
+    ///
+    /// [`Prio`]: network_protocol::Prio
+    pub fn channel_stats(&self) -> Vec<Arc<ChannelStats>> {
+        self.channel_stats
+            .lock()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    /// Limits how many bytes per second are sent to this `Participant`,
+    /// `None` means unlimited. The initial limit is set via
+    /// [`Network::set_participant_bandwidth_limit`]. A global limit of the
+    /// [`Network`] still applies.
+    pub fn set_bandwidth_limit(&self, limit: Option<Bandwidth>) { self.bandwidth.set_cap(limit); }
+
+    /// Returns the limit set via [`set_bandwidth_limit`]
+    ///
+    /// [`set_bandwidth_limit`]: Participant::set_bandwidth_limit
+    pub fn bandwidth_limit(&self) -> Option<Bandwidth> { self.bandwidth.cap() }
 }
 
 impl Stream {
@@ -1222,6 +1519,12 @@ impl core::fmt::Display for NetworkConnectError {
             NetworkConnectError::InvalidSecret => {
                 write!(f, "You specified the wrong secret on your second channel")
             },
+            NetworkConnectError::SessionExpired => {
+                write!(f, "The remote doesn't know the session to resume")
+            },
+            NetworkConnectError::UnexpectedIdentity(_) => {
+                write!(f, "The remote has an unexpected identity")
+            },
         }
     }
 }
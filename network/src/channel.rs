@@ -1,33 +1,69 @@
 use async_trait::async_trait;
 use bytes::BytesMut;
+#[cfg(feature = "quic")]
+use futures_util::{
+    future::{poll_fn, BoxFuture},
+    stream::FuturesUnordered,
+    FutureExt, StreamExt,
+};
 use network_protocol::{
-    Cid, InitProtocolError, MpscMsg, MpscRecvProtocol, MpscSendProtocol, Pid, ProtocolError,
-    ProtocolEvent, ProtocolMetricCache, ProtocolMetrics, Sid, TcpRecvProtocol, TcpSendProtocol,
-    UnreliableDrain, UnreliableSink,
+    ChannelStats, Cid, Identity, InitProtocolError, MpscMsg, MpscRecvProtocol, MpscSendProtocol,
+    Pid, ProtocolError, ProtocolEvent, ProtocolMetricCache, ProtocolMetrics, Sid, TcpRecvProtocol,
+    TcpSendProtocol, UdpRecvProtocol, UdpSendProtocol, UnreliableDrain, UnreliableSink,
+};
+#[cfg(feature = "fault_injection")]
+use network_protocol::{FaultDrain, FaultSink};
+#[cfg(feature = "quic")]
+use network_protocol::{QuicDataFormat, QuicRecvProtocol, QuicSendProtocol};
+#[cfg(feature = "quic")]
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    task::Poll,
+    time::Instant,
 };
-use std::{sync::Arc, time::Duration};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
-    net::tcp::{OwnedReadHalf, OwnedWriteHalf},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        UdpSocket,
+    },
     sync::mpsc,
 };
 
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
 pub(crate) enum Protocols {
     Tcp((TcpSendProtocol<TcpDrain>, TcpRecvProtocol<TcpSink>)),
     Mpsc((MpscSendProtocol<MpscDrain>, MpscRecvProtocol<MpscSink>)),
+    Udp((UdpSendProtocol<UdpDrain>, UdpRecvProtocol<UdpSink>)),
+    #[cfg(feature = "quic")]
+    Quic((QuicSendProtocol<QuicDrain>, QuicRecvProtocol<QuicSink>)),
+    #[cfg(feature = "fault_injection")]
+    Fault((UdpSendProtocol<FaultDrain>, UdpRecvProtocol<FaultSink>)),
 }
 
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
 pub(crate) enum SendProtocols {
     Tcp(TcpSendProtocol<TcpDrain>),
     Mpsc(MpscSendProtocol<MpscDrain>),
+    Udp(UdpSendProtocol<UdpDrain>),
+    #[cfg(feature = "quic")]
+    Quic(QuicSendProtocol<QuicDrain>),
+    #[cfg(feature = "fault_injection")]
+    Fault(UdpSendProtocol<FaultDrain>),
 }
 
 #[derive(Debug)]
 pub(crate) enum RecvProtocols {
     Tcp(TcpRecvProtocol<TcpSink>),
     Mpsc(MpscRecvProtocol<MpscSink>),
+    Udp(UdpRecvProtocol<UdpSink>),
+    #[cfg(feature = "quic")]
+    Quic(QuicRecvProtocol<QuicSink>),
+    #[cfg(feature = "fault_injection")]
+    Fault(UdpRecvProtocol<FaultSink>),
 }
 
 impl Protocols {
@@ -39,15 +75,14 @@ impl Protocols {
         let (r, w) = stream.into_split();
         let metrics = ProtocolMetricCache::new(&cid.to_string(), metrics);
 
-        let sp = TcpSendProtocol::new(TcpDrain { half: w }, metrics.clone());
-        let rp = TcpRecvProtocol::new(
+        Protocols::Tcp(network_protocol::tcp_protocols(
+            TcpDrain { half: w },
             TcpSink {
                 half: r,
                 buffer: BytesMut::new(),
             },
             metrics,
-        );
-        Protocols::Tcp((sp, rp))
+        ))
     }
 
     pub(crate) fn new_mpsc(
@@ -63,10 +98,96 @@ impl Protocols {
         Protocols::Mpsc((sp, rp))
     }
 
+    /// UDP sockets are shared by all channels of a listener, so the datagrams
+    /// from `remote_addr` have to be received by the caller and passed via
+    /// `receiver`.
+    pub(crate) fn new_udp(
+        socket: Arc<UdpSocket>,
+        remote_addr: SocketAddr,
+        receiver: mpsc::Receiver<BytesMut>,
+        cid: Cid,
+        metrics: Arc<ProtocolMetrics>,
+    ) -> Self {
+        let metrics = ProtocolMetricCache::new(&cid.to_string(), metrics);
+
+        Protocols::Udp(network_protocol::udp_protocols(
+            UdpDrain {
+                socket,
+                remote_addr,
+            },
+            UdpSink { receiver },
+            metrics,
+        ))
+    }
+
+    /// `main` is the bidirectional stream opened by the connecting side
+    #[cfg(feature = "quic")]
+    pub(crate) fn new_quic(
+        connection: quinn::NewConnection,
+        main: (quinn::SendStream, quinn::RecvStream),
+        cid: Cid,
+        metrics: Arc<ProtocolMetrics>,
+    ) -> Self {
+        let metrics = ProtocolMetricCache::new(&cid.to_string(), metrics);
+
+        let sp = QuicSendProtocol::new(
+            QuicDrain {
+                connection: connection.connection,
+                main: main.0,
+                reliables: HashMap::new(),
+                next_rtt_report: Instant::now(),
+                metrics: metrics.clone(),
+            },
+            metrics.clone(),
+        );
+        let rp = QuicRecvProtocol::new(
+            QuicSink {
+                main: main.1,
+                uni_streams: connection.uni_streams,
+                uni_streams_closed: false,
+                reliables: FuturesUnordered::new(),
+            },
+            metrics,
+        );
+        Protocols::Quic((sp, rp))
+    }
+
+    /// The UDP protocol is used, as the [`FaultDrain`] might drop and reorder
+    /// messages.
+    #[cfg(feature = "fault_injection")]
+    pub(crate) fn new_fault(
+        drain: FaultDrain,
+        sink: FaultSink,
+        cid: Cid,
+        metrics: Arc<ProtocolMetrics>,
+    ) -> Self {
+        let metrics = ProtocolMetricCache::new(&cid.to_string(), metrics);
+
+        Protocols::Fault(network_protocol::udp_protocols(drain, sink, metrics))
+    }
+
+    /// Link quality of the channel, shared by both halves
+    pub(crate) fn stats(&self) -> Arc<ChannelStats> {
+        match self {
+            Protocols::Tcp((s, _)) => s.stats(),
+            Protocols::Mpsc((s, _)) => s.stats(),
+            Protocols::Udp((s, _)) => s.stats(),
+            #[cfg(feature = "quic")]
+            Protocols::Quic((s, _)) => s.stats(),
+            #[cfg(feature = "fault_injection")]
+            Protocols::Fault((s, _)) => s.stats(),
+        }
+    }
+
     pub(crate) fn split(self) -> (SendProtocols, RecvProtocols) {
         match self {
             Protocols::Tcp((s, r)) => (SendProtocols::Tcp(s), RecvProtocols::Tcp(r)),
             Protocols::Mpsc((s, r)) => (SendProtocols::Mpsc(s), RecvProtocols::Mpsc(r)),
+            Protocols::Udp((s, r)) => (SendProtocols::Udp(s), RecvProtocols::Udp(r)),
+            #[cfg(feature = "quic")]
+            Protocols::Quic((s, r)) => (SendProtocols::Quic(s), RecvProtocols::Quic(r)),
+            #[cfg(feature = "fault_injection")]
+            Protocols::Fault((s, r)) => (SendProtocols::Fault(s), RecvProtocols::Fault(r)),
         }
     }
 }
@@ -78,10 +199,16 @@ impl network_protocol::InitProtocol for Protocols {
         initializer: bool,
         local_pid: Pid,
         secret: u128,
-    ) -> Result<(Pid, Sid, u128), InitProtocolError> {
+        identity: &Identity,
+    ) -> Result<(Pid, Sid, u128, [u8; 32]), InitProtocolError> {
         match self {
-            Protocols::Tcp(p) => p.initialize(initializer, local_pid, secret).await,
-            Protocols::Mpsc(p) => p.initialize(initializer, local_pid, secret).await,
+            Protocols::Tcp(p) => p.initialize(initializer, local_pid, secret, identity).await,
+            Protocols::Mpsc(p) => p.initialize(initializer, local_pid, secret, identity).await,
+            Protocols::Udp(p) => p.initialize(initializer, local_pid, secret, identity).await,
+            #[cfg(feature = "quic")]
+            Protocols::Quic(p) => p.initialize(initializer, local_pid, secret, identity).await,
+            #[cfg(feature = "fault_injection")]
+            Protocols::Fault(p) => p.initialize(initializer, local_pid, secret, identity).await,
         }
     }
 }
@@ -92,6 +219,11 @@ impl network_protocol::SendProtocol for SendProtocols {
         match self {
             SendProtocols::Tcp(s) => s.notify_from_recv(event),
             SendProtocols::Mpsc(s) => s.notify_from_recv(event),
+            SendProtocols::Udp(s) => s.notify_from_recv(event),
+            #[cfg(feature = "quic")]
+            SendProtocols::Quic(s) => s.notify_from_recv(event),
+            #[cfg(feature = "fault_injection")]
+            SendProtocols::Fault(s) => s.notify_from_recv(event),
         }
     }
 
@@ -99,6 +231,11 @@ impl network_protocol::SendProtocol for SendProtocols {
         match self {
             SendProtocols::Tcp(s) => s.send(event).await,
             SendProtocols::Mpsc(s) => s.send(event).await,
+            SendProtocols::Udp(s) => s.send(event).await,
+            #[cfg(feature = "quic")]
+            SendProtocols::Quic(s) => s.send(event).await,
+            #[cfg(feature = "fault_injection")]
+            SendProtocols::Fault(s) => s.send(event).await,
         }
     }
 
@@ -106,6 +243,11 @@ impl network_protocol::SendProtocol for SendProtocols {
         match self {
             SendProtocols::Tcp(s) => s.flush(bandwidth, dt).await,
             SendProtocols::Mpsc(s) => s.flush(bandwidth, dt).await,
+            SendProtocols::Udp(s) => s.flush(bandwidth, dt).await,
+            #[cfg(feature = "quic")]
+            SendProtocols::Quic(s) => s.flush(bandwidth, dt).await,
+            #[cfg(feature = "fault_injection")]
+            SendProtocols::Fault(s) => s.flush(bandwidth, dt).await,
         }
     }
 }
@@ -116,6 +258,11 @@ impl network_protocol::RecvProtocol for RecvProtocols {
         match self {
             RecvProtocols::Tcp(r) => r.recv().await,
             RecvProtocols::Mpsc(r) => r.recv().await,
+            RecvProtocols::Udp(r) => r.recv().await,
+            #[cfg(feature = "quic")]
+            RecvProtocols::Quic(r) => r.recv().await,
+            #[cfg(feature = "fault_injection")]
+            RecvProtocols::Fault(r) => r.recv().await,
         }
     }
 }
@@ -192,6 +339,204 @@ impl UnreliableSink for MpscSink {
     }
 }
 
+///////////////////////////////////////
+//// UDP
+/// The remote side sends a keepalive every second, so after this long without
+/// any datagram it is gone
+const UDP_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+pub struct UdpDrain {
+    socket: Arc<UdpSocket>,
+    remote_addr: SocketAddr,
+}
+
+#[derive(Debug)]
+pub struct UdpSink {
+    receiver: mpsc::Receiver<BytesMut>,
+}
+
+#[async_trait]
+impl UnreliableDrain for UdpDrain {
+    type DataFormat = BytesMut;
+
+    async fn send(&mut self, data: Self::DataFormat) -> Result<(), ProtocolError> {
+        match self.socket.send_to(&data, self.remote_addr).await {
+            Ok(_) => Ok(()),
+            Err(_) => Err(ProtocolError::Closed),
+        }
+    }
+}
+
+#[async_trait]
+impl UnreliableSink for UdpSink {
+    type DataFormat = BytesMut;
+
+    async fn recv(&mut self) -> Result<Self::DataFormat, ProtocolError> {
+        match tokio::time::timeout(UDP_TIMEOUT, self.receiver.recv()).await {
+            Ok(Some(data)) => Ok(data),
+            Ok(None) | Err(_) => Err(ProtocolError::Closed),
+        }
+    }
+}
+
+///////////////////////////////////////
+//// QUIC
+#[cfg(feature = "quic")]
+type UniStreamRead = BoxFuture<'static, (u64, quinn::RecvStream, Option<BytesMut>)>;
+
+/// quinn measures the round trip time, it's reported this often
+#[cfg(feature = "quic")]
+const QUIC_RTT_INTERVAL: Duration = Duration::from_secs(1);
+
+#[cfg(feature = "quic")]
+pub struct QuicDrain {
+    connection: quinn::Connection,
+    main: quinn::SendStream,
+    reliables: HashMap<u64, quinn::SendStream>,
+    next_rtt_report: Instant,
+    metrics: ProtocolMetricCache,
+}
+
+#[cfg(feature = "quic")]
+pub struct QuicSink {
+    main: quinn::RecvStream,
+    uni_streams: quinn::IncomingUniStreams,
+    uni_streams_closed: bool,
+    reliables: FuturesUnordered<UniStreamRead>,
+}
+
+#[cfg(feature = "quic")]
+impl std::fmt::Debug for QuicDrain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QuicDrain")
+            .field("remote_addr", &self.connection.remote_address())
+            .field("reliables", &self.reliables.len())
+            .finish()
+    }
+}
+
+#[cfg(feature = "quic")]
+impl std::fmt::Debug for QuicSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QuicSink")
+            .field("reliables", &self.reliables.len())
+            .finish()
+    }
+}
+
+#[cfg(feature = "quic")]
+impl QuicDrain {
+    async fn open(&mut self, id: u64) -> Result<&mut quinn::SendStream, ProtocolError> {
+        Ok(match self.reliables.entry(id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(
+                self.connection
+                    .open_uni()
+                    .await
+                    .map_err(|_| ProtocolError::Closed)?,
+            ),
+        })
+    }
+}
+
+#[cfg(feature = "quic")]
+#[async_trait]
+impl UnreliableDrain for QuicDrain {
+    type DataFormat = QuicDataFormat;
+
+    async fn send(&mut self, data: Self::DataFormat) -> Result<(), ProtocolError> {
+        let now = Instant::now();
+        if now >= self.next_rtt_report {
+            self.metrics.ping(self.connection.rtt());
+            self.next_rtt_report = now + QUIC_RTT_INTERVAL;
+        }
+        let result = match data {
+            QuicDataFormat::Main(data) => self.main.write_all(&data).await,
+            QuicDataFormat::Reliable(id, data) => self.open(id).await?.write_all(&data).await,
+            QuicDataFormat::Priority(id, prio) => {
+                // quinn sends streams with higher values first
+                let _ = self.open(id).await?.set_priority(-i32::from(prio));
+                Ok(())
+            },
+            QuicDataFormat::Finished(id) => match self.reliables.remove(&id) {
+                Some(mut stream) => stream.finish().await,
+                None => Ok(()),
+            },
+        };
+        result.map_err(|_| ProtocolError::Closed)
+    }
+}
+
+#[cfg(feature = "quic")]
+enum QuicSinkEvent {
+    UniStream(Option<Result<quinn::RecvStream, quinn::ConnectionError>>),
+    Reliable((u64, quinn::RecvStream, Option<BytesMut>)),
+    Main(Result<Option<quinn::Chunk>, quinn::ReadError>),
+}
+
+/// Reads the next chunk of an unidirectional stream, `None` once it's finished
+#[cfg(feature = "quic")]
+fn read_uni(mut stream: quinn::RecvStream) -> UniStreamRead {
+    async move {
+        let id = stream.id().index();
+        let data = match stream.read_chunk(usize::MAX, true).await {
+            Ok(Some(chunk)) => Some(BytesMut::from(&chunk.bytes[..])),
+            Ok(None) | Err(_) => None,
+        };
+        (id, stream, data)
+    }
+    .boxed()
+}
+
+#[cfg(feature = "quic")]
+#[async_trait]
+impl UnreliableSink for QuicSink {
+    type DataFormat = QuicDataFormat;
+
+    async fn recv(&mut self) -> Result<Self::DataFormat, ProtocolError> {
+        loop {
+            // polled in order: the main stream is read last, so the `Shutdown` doesn't
+            // overtake data
+            let next = poll_fn(|cx| {
+                if !self.uni_streams_closed {
+                    if let Poll::Ready(stream) = self.uni_streams.poll_next_unpin(cx) {
+                        return Poll::Ready(QuicSinkEvent::UniStream(stream));
+                    }
+                }
+                if let Poll::Ready(Some(read)) = self.reliables.poll_next_unpin(cx) {
+                    return Poll::Ready(QuicSinkEvent::Reliable(read));
+                }
+                self.main
+                    .read_chunk(usize::MAX, true)
+                    .poll_unpin(cx)
+                    .map(QuicSinkEvent::Main)
+            })
+            .await;
+            match next {
+                QuicSinkEvent::UniStream(Some(Ok(stream))) => self.reliables.push(read_uni(stream)),
+                // data might still be buffered in the other streams
+                QuicSinkEvent::UniStream(Some(Err(_))) | QuicSinkEvent::UniStream(None) => {
+                    self.uni_streams_closed = true
+                },
+                QuicSinkEvent::Reliable((id, stream, data)) => match data {
+                    Some(data) => {
+                        self.reliables.push(read_uni(stream));
+                        return Ok(QuicDataFormat::Reliable(id, data));
+                    },
+                    None => return Ok(QuicDataFormat::Finished(id)),
+                },
+                QuicSinkEvent::Main(Ok(Some(chunk))) => {
+                    return Ok(QuicDataFormat::Main(BytesMut::from(&chunk.bytes[..])));
+                },
+                QuicSinkEvent::Main(Ok(None)) | QuicSinkEvent::Main(Err(_)) => {
+                    return Err(ProtocolError::Closed);
+                },
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
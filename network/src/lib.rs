@@ -103,6 +103,7 @@ mod channel;
 mod message;
 mod metrics;
 mod participant;
+mod record;
 mod scheduler;
 
 pub use api::{
@@ -110,4 +111,7 @@ pub use api::{
     Stream, StreamError,
 };
 pub use message::Message;
-pub use network_protocol::{InitProtocolError, Pid, Promises};
+pub use network_protocol::{ChannelStats, Identity, InitProtocolError, Pid, Promises};
+#[cfg(feature = "fault_injection")]
+pub use network_protocol::{Delay, FaultConfig};
+pub use record::{read_records, Record, RecordEvent};
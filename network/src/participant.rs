@@ -2,16 +2,18 @@ use crate::{
     api::{ParticipantError, Stream},
     channel::{Protocols, RecvProtocols, SendProtocols},
     metrics::NetworkMetrics,
+    record::{RecordEvent, Recorder},
 };
 use bytes::Bytes;
 use futures_util::{FutureExt, StreamExt};
 use network_protocol::{
-    Bandwidth, Cid, Pid, Prio, Promises, ProtocolEvent, RecvProtocol, SendProtocol, Sid,
+    Bandwidth, ChannelStats, Cid, Pid, Prio, Promises, ProtocolEvent, RecvProtocol, SendProtocol,
+    Sid,
 };
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     sync::{
-        atomic::{AtomicBool, AtomicI32, Ordering},
+        atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering},
         Arc,
     },
     time::{Duration, Instant},
@@ -27,7 +29,47 @@ use tracing::*;
 pub(crate) type A2bStreamOpen = (Prio, Promises, Bandwidth, oneshot::Sender<Stream>);
 pub(crate) type S2bCreateChannel = (Cid, Sid, Protocols, oneshot::Sender<()>);
 pub(crate) type S2bShutdownBparticipant = (Duration, oneshot::Sender<Result<(), ParticipantError>>);
+/// Pid, sent bytes per second and bytes still queued, used to split the
+/// global bandwidth between participants
 pub(crate) type B2sPrioStatistic = (Pid, u64, u64);
+/// Shared with the [`Participant`](crate::api::Participant), ordered by `Cid`
+/// and thereby by creation of the channels
+pub(crate) type ChannelStatsMap = Arc<std::sync::Mutex<BTreeMap<Cid, Arc<ChannelStats>>>>;
+
+/// Upload caps of a participant in bytes per second, `u64::MAX` if unlimited
+#[derive(Debug)]
+pub(crate) struct BandwidthLimit {
+    /// set via the api
+    cap: AtomicU64,
+    /// part of the global cap the scheduler assigned to this participant
+    share: AtomicU64,
+}
+
+impl BandwidthLimit {
+    pub(crate) fn new(cap: Option<Bandwidth>) -> Self {
+        Self {
+            cap: AtomicU64::new(cap.unwrap_or(u64::MAX)),
+            share: AtomicU64::new(u64::MAX),
+        }
+    }
+
+    pub(crate) fn cap(&self) -> Option<Bandwidth> {
+        Some(self.cap.load(Ordering::Relaxed)).filter(|&cap| cap != u64::MAX)
+    }
+
+    pub(crate) fn set_cap(&self, cap: Option<Bandwidth>) {
+        self.cap.store(cap.unwrap_or(u64::MAX), Ordering::Relaxed);
+    }
+
+    pub(crate) fn set_share(&self, share: Bandwidth) { self.share.store(share, Ordering::Relaxed); }
+
+    fn get(&self) -> Bandwidth {
+        std::cmp::min(
+            self.cap.load(Ordering::Relaxed),
+            self.share.load(Ordering::Relaxed),
+        )
+    }
+}
 
 #[derive(Debug)]
 struct ChannelInfo {
@@ -39,6 +81,7 @@ struct ChannelInfo {
 struct StreamInfo {
     prio: Prio,
     promises: Promises,
+    guaranteed_bandwidth: Bandwidth,
     send_closed: Arc<AtomicBool>,
     b2a_msg_recv_s: Mutex<async_channel::Sender<Bytes>>,
 }
@@ -67,8 +110,13 @@ pub struct BParticipant {
     streams: RwLock<HashMap<Sid, StreamInfo>>,
     run_channels: Option<ControlChannels>,
     shutdown_barrier: AtomicI32,
+    shutting_down: AtomicBool,
     metrics: Arc<NetworkMetrics>,
     open_stream_channels: Arc<Mutex<Option<OpenStreamInfo>>>,
+    resume_timeout: Arc<Mutex<Duration>>,
+    channel_stats: ChannelStatsMap,
+    bandwidth: Arc<BandwidthLimit>,
+    recorder: Option<Recorder>,
 }
 
 impl BParticipant {
@@ -76,15 +124,20 @@ impl BParticipant {
     const BARR_CHANNEL: i32 = 1;
     const BARR_RECV: i32 = 4;
     const BARR_SEND: i32 = 2;
+    const PRIO_STATISTIC_INTERVAL: Duration = Duration::from_millis(100);
     const TICK_TIME: Duration = Duration::from_millis(Self::TICK_TIME_MS);
     const TICK_TIME_MS: u64 = 5;
 
-    #[allow(clippy::type_complexity)]
+    #[allow(clippy::type_complexity, clippy::too_many_arguments)]
     pub(crate) fn new(
         local_pid: Pid,
         remote_pid: Pid,
         offset_sid: Sid,
         metrics: Arc<NetworkMetrics>,
+        resume_timeout: Arc<Mutex<Duration>>,
+        channel_stats: ChannelStatsMap,
+        bandwidth: Arc<BandwidthLimit>,
+        recorder: Option<Recorder>,
     ) -> (
         Self,
         mpsc::UnboundedSender<A2bStreamOpen>,
@@ -115,9 +168,14 @@ impl BParticipant {
                 shutdown_barrier: AtomicI32::new(
                     Self::BARR_CHANNEL + Self::BARR_SEND + Self::BARR_RECV,
                 ),
+                shutting_down: AtomicBool::new(false),
                 run_channels,
                 metrics,
                 open_stream_channels: Arc::new(Mutex::new(None)),
+                resume_timeout,
+                channel_stats,
+                bandwidth,
+                recorder,
             },
             a2b_open_stream_s,
             b2a_stream_opened_r,
@@ -135,6 +193,8 @@ impl BParticipant {
             async_channel::unbounded::<Cid>();
         let (b2b_force_close_recv_protocol_s, b2b_force_close_recv_protocol_r) =
             async_channel::unbounded::<Cid>();
+        let (b2b_lost_send_protocol_s, b2b_lost_send_protocol_r) =
+            async_channel::unbounded::<Cid>();
         let (b2b_notify_send_of_recv_s, b2b_notify_send_of_recv_r) =
             crossbeam_channel::unbounded::<ProtocolEvent>();
 
@@ -154,6 +214,7 @@ impl BParticipant {
                 a2b_msg_r,
                 b2b_add_send_protocol_r,
                 b2b_close_send_protocol_r,
+                b2b_lost_send_protocol_r,
                 b2b_notify_send_of_recv_r,
                 b2s_prio_statistic_s,
             )
@@ -163,6 +224,7 @@ impl BParticipant {
                 b2b_add_recv_protocol_r,
                 b2b_force_close_recv_protocol_r,
                 b2b_close_send_protocol_s.clone(),
+                b2b_lost_send_protocol_s,
                 b2b_notify_send_of_recv_s,
             )
             .instrument(tracing::info_span!("recv")),
@@ -188,119 +250,172 @@ impl BParticipant {
         a2b_msg_r: crossbeam_channel::Receiver<(Sid, Bytes)>,
         mut b2b_add_protocol_r: mpsc::UnboundedReceiver<(Cid, SendProtocols)>,
         b2b_close_send_protocol_r: async_channel::Receiver<Cid>,
+        b2b_lost_send_protocol_r: async_channel::Receiver<Cid>,
         b2b_notify_send_of_recv_r: crossbeam_channel::Receiver<ProtocolEvent>,
-        _b2s_prio_statistic_s: mpsc::UnboundedSender<B2sPrioStatistic>,
+        b2s_prio_statistic_s: mpsc::UnboundedSender<B2sPrioStatistic>,
     ) {
-        let mut send_protocols: HashMap<Cid, SendProtocols> = HashMap::new();
+        // the newest protocol is the active one, older ones might be stale after the
+        // remote migrated to another connection
+        let mut send_protocols: Vec<(Cid, SendProtocols)> = Vec::new();
         let mut interval = tokio::time::interval(Self::TICK_TIME);
         let mut last_instant = Instant::now();
+        let mut last_statistic = (last_instant, 0);
         let mut stream_ids = self.offset_sid;
+        // messages are kept in `a2b_msg_r` while we wait for a new channel
+        let mut resume_deadline = None;
         trace!("workaround, actively wait for first protocol");
-        b2b_add_protocol_r
-            .recv()
-            .await
-            .map(|(c, p)| send_protocols.insert(c, p));
+        if let Some(p) = b2b_add_protocol_r.recv().await {
+            send_protocols.push(p);
+        }
         loop {
-            let (open, close, _, addp, remp) = select!(
-                Some(n) = a2b_open_stream_r.recv().fuse() => (Some(n), None, None, None, None),
-                Some(n) = a2b_close_stream_r.recv().fuse() => (None, Some(n), None, None, None),
-                _ = interval.tick() => (None, None, Some(()), None, None),
-                Some(n) = b2b_add_protocol_r.recv().fuse() => (None, None, None, Some(n), None),
-                Ok(n) = b2b_close_send_protocol_r.recv().fuse() => (None, None, None, None, Some(n)),
+            let connected = !send_protocols.is_empty();
+            let resume_timeout =
+                tokio::time::sleep_until(resume_deadline.unwrap_or_else(tokio::time::Instant::now));
+            let (open, close, _, addp, remp, lostp) = select!(
+                Some(n) = a2b_open_stream_r.recv().fuse(), if connected => (Some(n), None, None, None, None, None),
+                Some(n) = a2b_close_stream_r.recv().fuse(), if connected => (None, Some(n), None, None, None, None),
+                _ = interval.tick(), if connected => (None, None, Some(()), None, None, None),
+                Some(n) = b2b_add_protocol_r.recv().fuse() => (None, None, None, Some(n), None, None),
+                Ok(n) = b2b_close_send_protocol_r.recv().fuse() => (None, None, None, None, Some(n), None),
+                Ok(n) = b2b_lost_send_protocol_r.recv().fuse() => (None, None, None, None, None, Some(n)),
+                _ = resume_timeout, if resume_deadline.is_some() => {
+                    info!("no channel was attached in time, participant failed");
+                    break;
+                },
+                else => break,
             );
 
-            addp.map(|(cid, p)| {
+            if let Some((cid, mut p)) = addp {
                 debug!(?cid, "add protocol");
-                send_protocols.insert(cid, p)
-            });
-
-            let (cid, active) = match send_protocols.iter_mut().next() {
-                Some((cid, a)) => (*cid, a),
-                None => {
-                    warn!("no channel");
-                    tokio::time::sleep(Self::TICK_TIME * 1000).await; //TODO: failover
-                    continue;
-                },
-            };
+                // create_channel_mgr did this already, but streams might have been opened
+                // since then
+                self.notify_streams(&mut p).await;
+                if resume_deadline.take().is_some() {
+                    info!(?cid, "participant resumed on a new channel");
+                }
+                send_protocols.push((cid, p));
+            }
 
-            let active_err = async {
-                if let Some((prio, promises, guaranteed_bandwidth, return_s)) = open {
-                    let sid = stream_ids;
-                    trace!(?sid, "open stream");
-                    stream_ids += Sid::from(1);
-                    let stream = self
-                        .create_stream(sid, prio, promises, guaranteed_bandwidth)
-                        .await;
+            if let Some(((cid, active), others)) = send_protocols.split_last_mut() {
+                let cid = *cid;
+                let active_err = async {
+                    if let Some((prio, promises, guaranteed_bandwidth, return_s)) = open {
+                        let sid = stream_ids;
+                        trace!(?sid, "open stream");
+                        stream_ids += Sid::from(1);
+                        let stream = self
+                            .create_stream(sid, prio, promises, guaranteed_bandwidth)
+                            .await;
+                        self.record(|| RecordEvent::Opened {
+                            sid: sid.get_u64(),
+                            prio,
+                            promises: promises.bits(),
+                            guaranteed_bandwidth,
+                            local: true,
+                        });
+
+                        let event = ProtocolEvent::OpenStream {
+                            sid,
+                            prio,
+                            promises,
+                            guaranteed_bandwidth,
+                        };
 
-                    let event = ProtocolEvent::OpenStream {
-                        sid,
-                        prio,
-                        promises,
-                        guaranteed_bandwidth,
-                    };
+                        return_s.send(stream).unwrap();
+                        for (_, p) in others.iter_mut() {
+                            p.notify_from_recv(event.clone());
+                        }
+                        active.send(event).await?;
+                    }
 
-                    return_s.send(stream).unwrap();
-                    active.send(event).await?;
-                }
+                    // process recv content first
+                    let mut closeevents = b2b_notify_send_of_recv_r
+                        .try_iter()
+                        .map(|e| {
+                            if matches!(e, ProtocolEvent::OpenStream { .. }) {
+                                for (_, p) in others.iter_mut() {
+                                    p.notify_from_recv(e.clone());
+                                }
+                                active.notify_from_recv(e);
+                                None
+                            } else {
+                                Some(e)
+                            }
+                        })
+                        .collect::<Vec<_>>();
+
+                    // get all messages and assign it to a channel
+                    for (sid, buffer) in a2b_msg_r.try_iter() {
+                        self.record(|| RecordEvent::Sent {
+                            sid: sid.get_u64(),
+                            data: buffer.to_vec(),
+                        });
+                        active
+                            .send(ProtocolEvent::Message { data: buffer, sid })
+                            .await?
+                    }
 
-                // process recv content first
-                let mut closeevents = b2b_notify_send_of_recv_r
-                    .try_iter()
-                    .map(|e| {
-                        if matches!(e, ProtocolEvent::OpenStream { .. }) {
+                    // process recv content afterwards
+                    let _ = closeevents.drain(..).map(|e| {
+                        if let Some(e) = e {
                             active.notify_from_recv(e);
-                            None
-                        } else {
-                            Some(e)
                         }
-                    })
-                    .collect::<Vec<_>>();
-
-                // get all messages and assign it to a channel
-                for (sid, buffer) in a2b_msg_r.try_iter() {
-                    active
-                        .send(ProtocolEvent::Message { data: buffer, sid })
-                        .await?
-                }
+                    });
+
+                    if let Some(sid) = close {
+                        trace!(?stream_ids, "delete stream");
+                        self.record(|| RecordEvent::Closed {
+                            sid: sid.get_u64(),
+                            local: true,
+                        });
+                        self.delete_stream(sid).await;
+                        // Fire&Forget the protocol will take care to verify that this Frame is
+                        // delayed till the last msg was received!
+                        active.send(ProtocolEvent::CloseStream { sid }).await?;
+                    }
 
-                // process recv content afterwards
-                let _ = closeevents.drain(..).map(|e| {
-                    if let Some(e) = e {
-                        active.notify_from_recv(e);
+                    let send_time = Instant::now();
+                    let diff = send_time.duration_since(last_instant);
+                    last_instant = send_time;
+                    active.flush(self.bandwidth.get(), diff).await?; //this actually blocks, so we cant set streams while it.
+                    let r: Result<(), network_protocol::ProtocolError> = Ok(());
+                    r
+                }
+                .await;
+                if let Err(e) = active_err {
+                    info!(?cid, ?e, "protocol failed, shutting down channel");
+                    // remote recv will now fail, which will trigger remote send which will
+                    // trigger recv
+                    self.remove_protocol(&mut send_protocols, cid);
+                    self.metrics.channels_disconnected(&self.remote_pid_string);
+                    if send_protocols.is_empty() {
+                        match self.resume_deadline().await {
+                            Some(deadline) => resume_deadline = Some(deadline),
+                            None => break,
+                        }
                     }
-                });
-
-                if let Some(sid) = close {
-                    trace!(?stream_ids, "delete stream");
-                    self.delete_stream(sid).await;
-                    // Fire&Forget the protocol will take care to verify that this Frame is delayed
-                    // till the last msg was received!
-                    active.send(ProtocolEvent::CloseStream { sid }).await?;
                 }
-
-                let send_time = Instant::now();
-                let diff = send_time.duration_since(last_instant);
-                last_instant = send_time;
-                active.flush(1_000_000_000, diff).await?; //this actually blocks, so we cant set streams while it.
-                let r: Result<(), network_protocol::ProtocolError> = Ok(());
-                r
             }
-            .await;
-            if let Err(e) = active_err {
-                info!(?cid, ?e, "protocol failed, shutting down channel");
-                // remote recv will now fail, which will trigger remote send which will trigger
-                // recv
-                trace!("TODO: for now decide to FAIL this participant and not wait for a failover");
-                send_protocols.remove(&cid).unwrap();
-                self.metrics.channels_disconnected(&self.remote_pid_string);
-                if send_protocols.is_empty() {
-                    break;
-                }
+
+            let dt = last_instant.duration_since(last_statistic.0);
+            if dt >= Self::PRIO_STATISTIC_INTERVAL {
+                let (sent, queued) = self.channel_stats.lock().unwrap().values().fold(
+                    (0, 0),
+                    |(sent, queued), stats| {
+                        (
+                            sent + stats.sent_bytes_total(),
+                            queued + stats.queued_bytes(),
+                        )
+                    },
+                );
+                let rate = (sent.saturating_sub(last_statistic.1) as f64 / dt.as_secs_f64()) as u64;
+                let _ = b2s_prio_statistic_s.send((self.remote_pid, rate, queued));
+                last_statistic = (last_instant, sent);
             }
 
             if let Some(cid) = remp {
                 debug!(?cid, "remove protocol");
-                match send_protocols.remove(&cid) {
+                match self.remove_protocol(&mut send_protocols, cid) {
                     Some(mut prot) => {
                         self.metrics.channels_disconnected(&self.remote_pid_string);
                         trace!("blocking flush");
@@ -314,6 +429,20 @@ impl BParticipant {
                     break;
                 }
             }
+
+            if let Some(cid) = lostp {
+                // the channel is broken, so there is no point in a graceful shutdown
+                if self.remove_protocol(&mut send_protocols, cid).is_some() {
+                    debug!(?cid, "remove lost protocol");
+                    self.metrics.channels_disconnected(&self.remote_pid_string);
+                    if send_protocols.is_empty() {
+                        match self.resume_deadline().await {
+                            Some(deadline) => resume_deadline = Some(deadline),
+                            None => break,
+                        }
+                    }
+                }
+            }
         }
         trace!("stop sending in api!");
         self.open_stream_channels.lock().await.take();
@@ -322,6 +451,43 @@ impl BParticipant {
             .fetch_sub(Self::BARR_SEND, Ordering::Relaxed);
     }
 
+    fn remove_protocol(
+        &self,
+        send_protocols: &mut Vec<(Cid, SendProtocols)>,
+        cid: Cid,
+    ) -> Option<SendProtocols> {
+        let i = send_protocols.iter().position(|(c, _)| *c == cid)?;
+        self.channel_stats.lock().unwrap().remove(&cid);
+        Some(send_protocols.remove(i).1)
+    }
+
+    /// After the last channel broke we wait till the returned deadline for a
+    /// new one, `None` if the participant should fail immediately
+    async fn resume_deadline(&self) -> Option<tokio::time::Instant> {
+        let timeout = *self.resume_timeout.lock().await;
+        if timeout == Duration::from_secs(0) || self.shutting_down.load(Ordering::Relaxed) {
+            return None;
+        }
+        info!(
+            ?timeout,
+            "lost the last channel, waiting for the participant to resume"
+        );
+        Some(tokio::time::Instant::now() + timeout)
+    }
+
+    /// A new channel of a resumed participant needs to know about all streams
+    /// that were opened before
+    async fn notify_streams(&self, protocol: &mut SendProtocols) {
+        for (sid, si) in self.streams.read().await.iter() {
+            protocol.notify_from_recv(ProtocolEvent::OpenStream {
+                sid: *sid,
+                prio: si.prio,
+                promises: si.promises,
+                guaranteed_bandwidth: si.guaranteed_bandwidth,
+            });
+        }
+    }
+
     #[allow(clippy::too_many_arguments)]
     async fn recv_mgr(
         &self,
@@ -329,9 +495,11 @@ impl BParticipant {
         mut b2b_add_protocol_r: mpsc::UnboundedReceiver<(Cid, RecvProtocols)>,
         b2b_force_close_recv_protocol_r: async_channel::Receiver<Cid>,
         b2b_close_send_protocol_s: async_channel::Sender<Cid>,
+        b2b_lost_send_protocol_s: async_channel::Sender<Cid>,
         b2b_notify_send_of_recv_s: crossbeam_channel::Sender<ProtocolEvent>,
     ) {
         let mut recv_protocols: HashMap<Cid, JoinHandle<()>> = HashMap::new();
+        let mut resume_deadline = None;
         // we should be able to directly await futures imo
         let (hacky_recv_s, mut hacky_recv_r) = mpsc::unbounded_channel();
 
@@ -357,10 +525,16 @@ impl BParticipant {
         };
 
         loop {
+            let resume_timeout =
+                tokio::time::sleep_until(resume_deadline.unwrap_or_else(tokio::time::Instant::now));
             let (event, addp, remp) = select!(
                 Some(n) = hacky_recv_r.recv().fuse() => (Some(n), None, None),
                 Some(n) = b2b_add_protocol_r.recv().fuse() => (None, Some(n), None),
                 Ok(n) = b2b_force_close_recv_protocol_r.recv().fuse() => (None, None, Some(n)),
+                _ = resume_timeout, if resume_deadline.is_some() => {
+                    debug!("no channel was attached in time, end recv_mgr");
+                    break;
+                },
                 else => {
                     error!("recv_mgr -> something is seriously wrong!, end recv_mgr");
                     break;
@@ -369,6 +543,7 @@ impl BParticipant {
 
             if let Some((cid, p)) = addp {
                 debug!(?cid, "add protocol");
+                resume_deadline = None;
                 retrigger(cid, p, &mut recv_protocols);
             };
             if let Some(cid) = remp {
@@ -387,6 +562,13 @@ impl BParticipant {
                         guaranteed_bandwidth,
                     }) => {
                         trace!(?sid, "open stream");
+                        self.record(|| RecordEvent::Opened {
+                            sid: sid.get_u64(),
+                            prio,
+                            promises: promises.bits(),
+                            guaranteed_bandwidth,
+                            local: false,
+                        });
                         let _ = b2b_notify_send_of_recv_s.send(r.unwrap());
                         // waiting for receiving is not necessary, because the send_mgr will first
                         // process this before process messages!
@@ -398,11 +580,19 @@ impl BParticipant {
                     },
                     Ok(ProtocolEvent::CloseStream { sid }) => {
                         trace!(?sid, "close stream");
+                        self.record(|| RecordEvent::Closed {
+                            sid: sid.get_u64(),
+                            local: false,
+                        });
                         let _ = b2b_notify_send_of_recv_s.send(r.unwrap());
                         self.delete_stream(sid).await;
                         retrigger(cid, p, &mut recv_protocols);
                     },
                     Ok(ProtocolEvent::Message { data, sid }) => {
+                        self.record(|| RecordEvent::Received {
+                            sid: sid.get_u64(),
+                            data: data.to_vec(),
+                        });
                         let lock = self.streams.read().await;
                         match lock.get(&sid) {
                             Some(stream) => {
@@ -423,11 +613,14 @@ impl BParticipant {
                     },
                     Err(e) => {
                         info!(?e, ?cid, "protocol failed, shutting down channel");
-                        if let Err(e) = b2b_close_send_protocol_s.send(cid).await {
+                        if let Err(e) = b2b_lost_send_protocol_s.send(cid).await {
                             debug!(?e, ?cid, "send_mgr was already closed simultaneously");
                         }
                         if remove_c(&mut recv_protocols, &cid) {
-                            break;
+                            match self.resume_deadline().await {
+                                Some(deadline) => resume_deadline = Some(deadline),
+                                None => break,
+                            }
                         }
                     },
                 }
@@ -468,9 +661,20 @@ impl BParticipant {
                         }),
                     );
                     drop(lock);
-                    let (send, recv) = protocol.split();
-                    b2b_add_send_protocol_s.send((cid, send)).unwrap();
-                    b2b_add_recv_protocol_s.send((cid, recv)).unwrap();
+                    self.channel_stats
+                        .lock()
+                        .unwrap()
+                        .insert(cid, protocol.stats());
+                    let (mut send, recv) = protocol.split();
+                    // before anything is received, e.g. so encrypted streams are known
+                    self.notify_streams(&mut send).await;
+                    if b2b_add_send_protocol_s.send((cid, send)).is_err()
+                        || b2b_add_recv_protocol_s.send((cid, recv)).is_err()
+                    {
+                        debug!(?cid, "participant already failed, dropping channel");
+                        self.channel_stats.lock().unwrap().remove(&cid);
+                        return;
+                    }
                     b2s_create_channel_done_s.send(()).unwrap();
                     if channel_no > 5 {
                         debug!(?channel_no, "metrics will overwrite channel #5");
@@ -530,6 +734,7 @@ impl BParticipant {
         };
 
         let (timeout_time, sender) = s2b_shutdown_bparticipant_r.await.unwrap();
+        self.shutting_down.store(true, Ordering::Relaxed);
         debug!("participant_shutdown_mgr triggered. Closing all streams for send");
         {
             let lock = self.streams.read().await;
@@ -585,6 +790,12 @@ impl BParticipant {
         trace!("Stop participant_shutdown_mgr");
     }
 
+    fn record(&self, event: impl FnOnce() -> RecordEvent) {
+        if let Some(recorder) = &self.recorder {
+            recorder.record(event());
+        }
+    }
+
     /// Stopping API and participant usage
     /// Protocol will take care of the order of the frame
     async fn delete_stream(&self, sid: Sid) {
@@ -613,6 +824,7 @@ impl BParticipant {
         self.streams.write().await.insert(sid, StreamInfo {
             prio,
             promises,
+            guaranteed_bandwidth,
             send_closed: Arc::clone(&send_closed),
             b2a_msg_recv_s: Mutex::new(b2a_msg_recv_s),
         });
@@ -669,6 +881,21 @@ mod tests {
         oneshot::Sender<S2bShutdownBparticipant>,
         mpsc::UnboundedReceiver<B2sPrioStatistic>,
         JoinHandle<()>,
+    ) {
+        mock_resumable_bparticipant(Duration::from_secs(0))
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn mock_resumable_bparticipant(
+        resume_timeout: Duration,
+    ) -> (
+        Arc<Runtime>,
+        mpsc::UnboundedSender<A2bStreamOpen>,
+        mpsc::UnboundedReceiver<Stream>,
+        mpsc::UnboundedSender<S2bCreateChannel>,
+        oneshot::Sender<S2bShutdownBparticipant>,
+        mpsc::UnboundedReceiver<B2sPrioStatistic>,
+        JoinHandle<()>,
     ) {
         let runtime = Arc::new(tokio::runtime::Runtime::new().unwrap());
         let runtime_clone = Arc::clone(&runtime);
@@ -688,7 +915,16 @@ mod tests {
             let sid = Sid::new(1000);
             let metrics = Arc::new(NetworkMetrics::new(&local_pid).unwrap());
 
-            BParticipant::new(local_pid, remote_pid, sid, Arc::clone(&metrics))
+            BParticipant::new(
+                local_pid,
+                remote_pid,
+                sid,
+                Arc::clone(&metrics),
+                Arc::new(Mutex::new(resume_timeout)),
+                ChannelStatsMap::default(),
+                Arc::new(BandwidthLimit::new(None)),
+                None,
+            )
         });
 
         let handle = runtime_clone.spawn(bparticipant.run(b2s_prio_statistic_s));
@@ -889,4 +1125,121 @@ mod tests {
         drop((a2b_open_stream_s, b2a_stream_opened_r, b2s_prio_statistic_r));
         drop(runtime);
     }
+
+    #[test]
+    fn resume_on_new_channel() {
+        let (
+            runtime,
+            a2b_open_stream_s,
+            mut b2a_stream_opened_r,
+            mut s2b_create_channel_s,
+            s2b_shutdown_bparticipant_s,
+            b2s_prio_statistic_r,
+            handle,
+        ) = mock_resumable_bparticipant(Duration::from_secs(10));
+
+        let remote = runtime.block_on(mock_mpsc(0, &runtime, &mut s2b_create_channel_s));
+        std::thread::sleep(Duration::from_millis(50));
+
+        let (mut rs, rr) = remote.split();
+        runtime
+            .block_on(rs.send(ProtocolEvent::OpenStream {
+                sid: Sid::new(1000),
+                prio: 9u8,
+                promises: Promises::ORDERED,
+                guaranteed_bandwidth: 1_000_000,
+            }))
+            .unwrap();
+        let mut stream = runtime.block_on(b2a_stream_opened_r.recv()).unwrap();
+
+        // channel breaks, the stream stays usable
+        drop((rs, rr));
+        std::thread::sleep(Duration::from_millis(50));
+        stream.send("sent while disconnected").unwrap();
+
+        let remote = runtime.block_on(mock_mpsc(1, &runtime, &mut s2b_create_channel_s));
+        let (mut rs, mut rr) = remote.split();
+        let (sid, data) = match runtime.block_on(rr.recv()).unwrap() {
+            ProtocolEvent::Message { sid, data } => (sid, data),
+            _ => panic!("wrong event"),
+        };
+        assert_eq!(sid, Sid::new(1000));
+        // and receives on the new channel
+        runtime
+            .block_on(rs.send(ProtocolEvent::Message { sid, data }))
+            .unwrap();
+        let msg: String = runtime.block_on(stream.recv()).unwrap();
+        assert_eq!(msg, "sent while disconnected");
+
+        let (s, r) = oneshot::channel();
+        runtime.block_on(async {
+            drop(s2b_create_channel_s);
+            s2b_shutdown_bparticipant_s
+                .send((Duration::from_secs(1), s))
+                .unwrap();
+            drop((rs, rr));
+            r.await.unwrap().unwrap();
+        });
+
+        runtime.block_on(handle).unwrap();
+
+        drop((a2b_open_stream_s, b2a_stream_opened_r, b2s_prio_statistic_r));
+        drop((stream, runtime));
+    }
+
+    #[test]
+    fn fail_when_not_resumed_in_time() {
+        let (
+            runtime,
+            a2b_open_stream_s,
+            mut b2a_stream_opened_r,
+            mut s2b_create_channel_s,
+            s2b_shutdown_bparticipant_s,
+            b2s_prio_statistic_r,
+            handle,
+        ) = mock_resumable_bparticipant(Duration::from_millis(100));
+
+        let remote = runtime.block_on(mock_mpsc(0, &runtime, &mut s2b_create_channel_s));
+        std::thread::sleep(Duration::from_millis(50));
+
+        let (mut rs, rr) = remote.split();
+        runtime
+            .block_on(rs.send(ProtocolEvent::OpenStream {
+                sid: Sid::new(1000),
+                prio: 9u8,
+                promises: Promises::ORDERED,
+                guaranteed_bandwidth: 1_000_000,
+            }))
+            .unwrap();
+        let mut stream = runtime.block_on(b2a_stream_opened_r.recv()).unwrap();
+
+        drop((rs, rr));
+        std::thread::sleep(Duration::from_millis(300));
+        assert!(runtime.block_on(stream.recv::<String>()).is_err());
+
+        // a late channel isn't attached anymore
+        let (s1, _r1) = mpsc::channel(100);
+        let (_s2, r2) = mpsc::channel(100);
+        let metrics = Arc::new(ProtocolMetrics::new().unwrap());
+        let p1 = Protocols::new_mpsc(s1, r2, 1, metrics);
+        let (complete_s, complete_r) = oneshot::channel();
+        s2b_create_channel_s
+            .send((1, Sid::new(0), p1, complete_s))
+            .unwrap();
+        assert!(runtime.block_on(complete_r).is_err());
+
+        let (s, r) = oneshot::channel();
+        runtime.block_on(async {
+            drop(s2b_create_channel_s);
+            s2b_shutdown_bparticipant_s
+                .send((Duration::from_secs(1), s))
+                .unwrap();
+            r.await.unwrap().unwrap();
+        });
+
+        runtime.block_on(handle).unwrap();
+
+        drop((a2b_open_stream_s, b2a_stream_opened_r, b2s_prio_statistic_r));
+        drop((stream, runtime));
+    }
 }
@@ -0,0 +1,282 @@
+//! Recording of everything a [`Participant`] sends and receives, and playing
+//! a recording back as if it were the remote side.
+//!
+//! Recordings are written when [`Network::set_record_dir`] is set, one file
+//! per [`Participant`]. Connecting to [`ProtocolAddr::Replay`] with such a
+//! file returns a [`Participant`] that opens the same streams and receives the
+//! same messages at the same time as the recorded one did. What is sent to it
+//! is ignored.
+//!
+//! [`Participant`]: crate::api::Participant
+//! [`Network::set_record_dir`]: crate::api::Network::set_record_dir
+//! [`ProtocolAddr::Replay`]: crate::api::ProtocolAddr::Replay
+use crate::channel::{Protocols, SendProtocols};
+use bytes::Bytes;
+use network_protocol::{
+    Bandwidth, Identity, InitProtocol, Pid, Prio, Promises, ProtocolEvent, RecvProtocol,
+    SendProtocol, Sid,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::mpsc;
+use tracing::*;
+
+const MAGIC_NUMBER: [u8; 8] = *b"VNETREC1";
+const FILE_EXTENSION: &str = "netrec";
+
+/// Something that happened on a [`Participant`]. Sids are the ones of the
+/// recorded session.
+///
+/// [`Participant`]: crate::api::Participant
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RecordEvent {
+    /// A stream was opened, by the recorded side if `local`
+    Opened {
+        sid: u64,
+        prio: Prio,
+        promises: u8,
+        guaranteed_bandwidth: Bandwidth,
+        local: bool,
+    },
+    /// A stream was closed, by the recorded side if `local`
+    Closed {
+        sid: u64,
+        local: bool,
+    },
+    Sent {
+        sid: u64,
+        data: Vec<u8>,
+    },
+    Received {
+        sid: u64,
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Record {
+    /// since the [`Participant`](crate::api::Participant) was created
+    pub time: Duration,
+    pub event: RecordEvent,
+}
+
+/// Reads all records of a recording, e.g. to inspect them
+pub fn read_records(path: &Path) -> io::Result<Vec<Record>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut magic_number = [0u8; 8];
+    reader.read_exact(&mut magic_number)?;
+    if magic_number != MAGIC_NUMBER {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a network recording",
+        ));
+    }
+    let mut records = Vec::new();
+    loop {
+        match bincode::deserialize_from(&mut reader) {
+            Ok(record) => records.push(record),
+            Err(e) => match *e {
+                // a recording that wasn't closed properly ends mid record
+                bincode::ErrorKind::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                e => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+            },
+        }
+    }
+    Ok(records)
+}
+
+/// Writes the records of one participant from a separate thread, so that
+/// recording doesn't block the participant
+#[derive(Debug)]
+pub(crate) struct Recorder {
+    start: Instant,
+    record_s: crossbeam_channel::Sender<Record>,
+}
+
+impl Recorder {
+    pub(crate) fn create(dir: &Path, remote_pid: Pid) -> io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let unix_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let path = dir
+            .join(format!("{}_{}", unix_time, remote_pid))
+            .with_extension(FILE_EXTENSION);
+        let mut writer = BufWriter::new(File::create(&path)?);
+        writer.write_all(&MAGIC_NUMBER)?;
+        let (record_s, record_r) = crossbeam_channel::unbounded::<Record>();
+        std::thread::Builder::new()
+            .name("network-recorder".to_owned())
+            .spawn(move || {
+                for record in record_r {
+                    if let Err(e) = bincode::serialize_into(&mut writer, &record) {
+                        warn!(?e, ?path, "Couldn't write recording, stop recording");
+                        return;
+                    }
+                }
+                if let Err(e) = writer.flush() {
+                    warn!(?e, ?path, "Couldn't write recording");
+                }
+                debug!(?path, "recording finished");
+            })?;
+        Ok(Self {
+            start: Instant::now(),
+            record_s,
+        })
+    }
+
+    pub(crate) fn record(&self, event: RecordEvent) {
+        let _ = self.record_s.send(Record {
+            time: self.start.elapsed(),
+            event,
+        });
+    }
+}
+
+/// Plays a recording back as remote side of `protocol`
+pub(crate) async fn replay(path: PathBuf, mut protocol: Protocols) {
+    let records = match read_records(&path) {
+        Ok(records) => records,
+        Err(e) => {
+            warn!(?e, ?path, "Couldn't read recording");
+            return;
+        },
+    };
+    // like a listener, the replay starts the handshake
+    let identity = Identity::generate();
+    let offset_sid = match protocol
+        .initialize(true, Pid::new(), rand::random(), &identity)
+        .await
+    {
+        Ok((_, offset_sid, _, _)) => offset_sid,
+        Err(e) => {
+            debug!(?e, "Handshake with replayed participant failed");
+            return;
+        },
+    };
+    let (mut send, mut recv) = protocol.split();
+    let (local_event_s, mut local_event_r) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while let Ok(event) = recv.recv().await {
+            if local_event_s.send(event).is_err() {
+                break;
+            }
+        }
+    });
+
+    // streams opened by the local side get their Sids in the same order as in the
+    // recording, the ones the replay opens get new ones
+    let mut local_opened = records
+        .iter()
+        .filter_map(|record| match record.event {
+            RecordEvent::Opened {
+                sid, local: true, ..
+            } => Some(sid),
+            _ => None,
+        })
+        .collect::<VecDeque<_>>();
+    let mut sids = HashMap::new();
+    let mut next_sid = offset_sid;
+    let start = tokio::time::Instant::now();
+    let mut last_flush = Instant::now();
+
+    for record in records {
+        let deadline = start + record.time;
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep_until(deadline) => break,
+                event = local_event_r.recv() => {
+                    if !follow_local(event, &mut sids, &mut local_opened) {
+                        debug!("local side of the replay closed");
+                        return;
+                    }
+                },
+            }
+        }
+
+        let sid = match &record.event {
+            RecordEvent::Opened {
+                sid, local: false, ..
+            } => {
+                sids.insert(*sid, next_sid);
+                next_sid += Sid::from(1);
+                *sid
+            },
+            RecordEvent::Received { sid, .. } | RecordEvent::Closed { sid, local: false } => *sid,
+            _ => continue,
+        };
+        // wait till the local side opened the stream
+        let live_sid = loop {
+            if let Some(live_sid) = sids.get(&sid) {
+                break *live_sid;
+            }
+            if !follow_local(local_event_r.recv().await, &mut sids, &mut local_opened) {
+                debug!(?sid, "replayed stream was never opened");
+                return;
+            }
+        };
+        let event = match record.event {
+            RecordEvent::Opened {
+                prio,
+                promises,
+                guaranteed_bandwidth,
+                ..
+            } => ProtocolEvent::OpenStream {
+                sid: live_sid,
+                prio,
+                promises: Promises::from_bits_truncate(promises),
+                guaranteed_bandwidth,
+            },
+            RecordEvent::Received { data, .. } => ProtocolEvent::Message {
+                sid: live_sid,
+                data: Bytes::from(data),
+            },
+            _ => ProtocolEvent::CloseStream { sid: live_sid },
+        };
+        if let Err(e) = send_flushed(&mut send, event, &mut last_flush).await {
+            debug!(?e, "local side of the replay failed");
+            return;
+        }
+    }
+    debug!("replay finished");
+    let _ = send_flushed(&mut send, ProtocolEvent::Shutdown, &mut last_flush).await;
+}
+
+/// Whatever the local side sends is only needed to follow its streams, returns
+/// false once it is closed
+fn follow_local(
+    event: Option<ProtocolEvent>,
+    sids: &mut HashMap<u64, Sid>,
+    local_opened: &mut VecDeque<u64>,
+) -> bool {
+    match event {
+        Some(ProtocolEvent::OpenStream { sid, .. }) => {
+            if let Some(recorded) = local_opened.pop_front() {
+                sids.insert(recorded, sid);
+            }
+            true
+        },
+        Some(ProtocolEvent::Shutdown) | None => false,
+        Some(_) => true,
+    }
+}
+
+async fn send_flushed(
+    send: &mut SendProtocols,
+    event: ProtocolEvent,
+    last_flush: &mut Instant,
+) -> Result<(), network_protocol::ProtocolError> {
+    send.send(event).await?;
+    let now = Instant::now();
+    send.flush(u64::MAX, now.duration_since(*last_flush))
+        .await?;
+    *last_flush = now;
+    Ok(())
+}
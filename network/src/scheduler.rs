@@ -2,20 +2,33 @@ use crate::{
     api::{NetworkConnectError, Participant, ProtocolAddr},
     channel::Protocols,
     metrics::NetworkMetrics,
-    participant::{B2sPrioStatistic, BParticipant, S2bCreateChannel, S2bShutdownBparticipant},
+    participant::{
+        B2sPrioStatistic, BParticipant, BandwidthLimit, ChannelStatsMap, S2bCreateChannel,
+        S2bShutdownBparticipant,
+    },
+    record::{self, Recorder},
 };
+use bytes::BytesMut;
+#[cfg(feature = "quic")]
+use futures_util::stream::FuturesUnordered;
 use futures_util::{FutureExt, StreamExt};
-use network_protocol::{Cid, MpscMsg, Pid, ProtocolMetrics};
+#[cfg(feature = "fault_injection")]
+use network_protocol::{fault_channel, FaultConfig, FaultDrain, FaultSink};
+use network_protocol::{
+    Bandwidth, Cid, Identity, InitProtocolError, MpscMsg, Pid, ProtocolMetrics,
+};
 #[cfg(feature = "metrics")]
 use prometheus::Registry;
 use rand::Rng;
 use std::{
     collections::HashMap,
+    net::SocketAddr,
+    path::PathBuf,
     sync::{
-        atomic::{AtomicBool, AtomicU64, Ordering},
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
         Arc,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 use tokio::{
     io, net, select,
@@ -42,20 +55,108 @@ lazy_static::lazy_static! {
 #[derive(Debug)]
 struct ParticipantInfo {
     secret: u128,
+    identity: [u8; 32],
     s2b_create_channel_s: mpsc::UnboundedSender<S2bCreateChannel>,
     s2b_shutdown_bparticipant_s: Option<oneshot::Sender<S2bShutdownBparticipant>>,
+    bandwidth: Arc<BandwidthLimit>,
+}
+
+/// No datagram is larger than this, including jumbo frames
+const UDP_MAXIMUM_SINGLE_PACKET_SIZE_EVER: usize = 9216;
+/// Handshakes of new channels fail if they don't finish in time, so that
+/// connection attempts that are never completed don't pile up
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Datagrams from new addresses are ignored while this many handshakes of a
+/// UDP listener are in progress, as the source address of a datagram can be
+/// spoofed
+const MAX_PENDING_UDP_HANDSHAKES: usize = 64;
+/// Datagrams that are buffered per UDP channel, further datagrams are dropped
+/// until the channel catches up
+const UDP_CHANNEL_BUFFER: usize = 1024;
+/// How often a UDP listener forgets the addresses of closed channels
+const UDP_LISTENER_CLEANUP_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Counts a handshake of a UDP listener as in progress while it is alive
+struct PendingHandshake(Arc<AtomicUsize>);
+
+impl PendingHandshake {
+    fn new(pending: &Arc<AtomicUsize>) -> Self {
+        pending.fetch_add(1, Ordering::Relaxed);
+        Self(Arc::clone(pending))
+    }
+}
+
+impl Drop for PendingHandshake {
+    fn drop(&mut self) { self.0.fetch_sub(1, Ordering::Relaxed); }
 }
 
+/// TLS configuration for QUIC, set via the [`Network`]
+///
+/// [`Network`]: crate::api::Network
+#[cfg(feature = "quic")]
+#[derive(Default)]
+pub(crate) struct QuicConfig {
+    pub(crate) server: Option<quinn::ServerConfig>,
+    /// Config and the name the certificate of the server must be valid for
+    pub(crate) client: Option<(quinn::ClientConfig, String)>,
+}
+
+#[cfg(feature = "quic")]
+impl std::fmt::Debug for QuicConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QuicConfig")
+            .field("server", &self.server.is_some())
+            .field("client", &self.client.as_ref().map(|(_, name)| name))
+            .finish()
+    }
+}
+
+/// Upload caps in bytes per second, set via the [`Network`]
+///
+/// [`Network`]: crate::api::Network
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct BandwidthConfig {
+    /// initial cap of new participants
+    pub(crate) participant: Option<Bandwidth>,
+    /// split between all participants
+    pub(crate) global: Option<Bandwidth>,
+}
+
+#[cfg(feature = "quic")]
+type QuicConnection = (quinn::NewConnection, (quinn::SendStream, quinn::RecvStream));
+
 type A2sListen = (ProtocolAddr, oneshot::Sender<io::Result<()>>);
-pub(crate) type A2sConnect = (
-    ProtocolAddr,
-    oneshot::Sender<Result<Participant, NetworkConnectError>>,
-);
+pub(crate) type A2sConnect = (ProtocolAddr, S2aConnectResult);
 type A2sDisconnect = (Pid, S2bShutdownBparticipant);
-type S2sMpscConnect = (
-    mpsc::Sender<MpscMsg>,
-    oneshot::Sender<mpsc::Sender<MpscMsg>>,
-);
+/// Sent by a connecting [`Scheduler`] to the mpsc listener
+#[derive(Debug)]
+enum S2sMpscConnect {
+    Mpsc(
+        mpsc::Sender<MpscMsg>,
+        oneshot::Sender<mpsc::Sender<MpscMsg>>,
+    ),
+    /// Drain and sink for the listener, the connecting side set a
+    /// [`FaultConfig`]
+    #[cfg(feature = "fault_injection")]
+    Fault(Box<FaultDrain>, FaultSink),
+}
+
+/// Whoever called `connect` waits on this for the outcome
+#[derive(Debug)]
+pub(crate) enum S2aConnectResult {
+    Participant(oneshot::Sender<Result<Participant, NetworkConnectError>>),
+    /// The channel is attached to the existing participant with this `Pid`
+    Resume(Pid, oneshot::Sender<Result<(), NetworkConnectError>>),
+}
+
+impl S2aConnectResult {
+    fn fail(self, e: NetworkConnectError) {
+        let _ = match self {
+            Self::Participant(s) => s.send(Err(e)).map_err(|_| ()),
+            Self::Resume(_, s) => s.send(Err(e)).map_err(|_| ()),
+        };
+    }
+}
 
 #[derive(Debug)]
 struct ControlChannels {
@@ -77,6 +178,7 @@ struct ParticipantChannels {
 pub struct Scheduler {
     local_pid: Pid,
     local_secret: u128,
+    local_identity: Arc<Mutex<Arc<Identity>>>,
     closed: AtomicBool,
     run_channels: Option<ControlChannels>,
     participant_channels: Arc<Mutex<Option<ParticipantChannels>>>,
@@ -85,14 +187,29 @@ pub struct Scheduler {
     channel_listener: Mutex<HashMap<ProtocolAddr, oneshot::Sender<()>>>,
     metrics: Arc<NetworkMetrics>,
     protocol_metrics: Arc<ProtocolMetrics>,
+    #[cfg(feature = "quic")]
+    quic_config: Arc<Mutex<QuicConfig>>,
+    resume_timeout: Arc<Mutex<Duration>>,
+    bandwidth_config: Arc<Mutex<BandwidthConfig>>,
+    #[cfg(feature = "fault_injection")]
+    fault_config: Arc<Mutex<Option<FaultConfig>>>,
+    record_dir: Arc<Mutex<Option<PathBuf>>>,
 }
 
 impl Scheduler {
     const MPSC_CHANNEL_BOUND: usize = 1000;
+    const PRIO_ADJUST_INTERVAL: Duration = Duration::from_millis(100);
 
-    pub fn new(
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
         local_pid: Pid,
+        local_identity: Arc<Mutex<Arc<Identity>>>,
         #[cfg(feature = "metrics")] registry: Option<&Registry>,
+        #[cfg(feature = "quic")] quic_config: Arc<Mutex<QuicConfig>>,
+        resume_timeout: Arc<Mutex<Duration>>,
+        bandwidth_config: Arc<Mutex<BandwidthConfig>>,
+        #[cfg(feature = "fault_injection")] fault_config: Arc<Mutex<Option<FaultConfig>>>,
+        record_dir: Arc<Mutex<Option<PathBuf>>>,
     ) -> (
         Self,
         mpsc::UnboundedSender<A2sListen>,
@@ -140,6 +257,7 @@ impl Scheduler {
             Self {
                 local_pid,
                 local_secret,
+                local_identity,
                 closed: AtomicBool::new(false),
                 run_channels,
                 participant_channels: Arc::new(Mutex::new(Some(participant_channels))),
@@ -148,6 +266,13 @@ impl Scheduler {
                 channel_listener: Mutex::new(HashMap::new()),
                 metrics,
                 protocol_metrics,
+                #[cfg(feature = "quic")]
+                quic_config,
+                resume_timeout,
+                bandwidth_config,
+                #[cfg(feature = "fault_injection")]
+                fault_config,
+                record_dir,
             },
             a2s_listen_s,
             a2s_connect_s,
@@ -184,6 +309,9 @@ impl Scheduler {
                             ProtocolAddr::Tcp(_) => "tcp",
                             ProtocolAddr::Udp(_) => "udp",
                             ProtocolAddr::Mpsc(_) => "mpsc",
+                            #[cfg(feature = "quic")]
+                            ProtocolAddr::Quic(_) => "quic",
+                            ProtocolAddr::Replay(_) => "replay",
                         }])
                         .inc();
                     let (end_sender, end_receiver) = oneshot::channel::<()>();
@@ -202,6 +330,7 @@ impl Scheduler {
     async fn connect_mgr(&self, mut a2s_connect_r: mpsc::UnboundedReceiver<A2sConnect>) {
         trace!("Start connect_mgr");
         while let Some((addr, pid_sender)) = a2s_connect_r.recv().await {
+            let remote_addr = addr.clone();
             let (protocol, cid, handshake) = match addr {
                 ProtocolAddr::Tcp(addr) => {
                     #[cfg(feature = "metrics")]
@@ -212,7 +341,7 @@ impl Scheduler {
                     let stream = match net::TcpStream::connect(addr).await {
                         Ok(stream) => stream,
                         Err(e) => {
-                            pid_sender.send(Err(NetworkConnectError::Io(e))).unwrap();
+                            pid_sender.fail(NetworkConnectError::Io(e));
                             continue;
                         },
                     };
@@ -228,73 +357,132 @@ impl Scheduler {
                     let mpsc_s = match MPSC_POOL.lock().await.get(&addr) {
                         Some(s) => s.clone(),
                         None => {
-                            pid_sender
-                                .send(Err(NetworkConnectError::Io(std::io::Error::new(
-                                    std::io::ErrorKind::NotConnected,
-                                    "no mpsc listen on this addr",
-                                ))))
-                                .unwrap();
+                            pid_sender.fail(NetworkConnectError::Io(std::io::Error::new(
+                                std::io::ErrorKind::NotConnected,
+                                "no mpsc listen on this addr",
+                            )));
                             continue;
                         },
                     };
-                    let (remote_to_local_s, remote_to_local_r) =
-                        mpsc::channel(Self::MPSC_CHANNEL_BOUND);
-                    let (local_to_remote_oneshot_s, local_to_remote_oneshot_r) = oneshot::channel();
-                    mpsc_s
-                        .send((remote_to_local_s, local_to_remote_oneshot_s))
-                        .unwrap();
-                    let local_to_remote_s = local_to_remote_oneshot_r.await.unwrap();
-
                     let cid = self.channel_ids.fetch_add(1, Ordering::Relaxed);
                     info!(?addr, "Connecting Mpsc");
+                    (self.connect_mpsc(&mpsc_s, cid).await, cid, false)
+                },
+                ProtocolAddr::Udp(addr) => {
+                    #[cfg(feature = "metrics")]
+                    self.metrics
+                        .connect_requests_total
+                        .with_label_values(&["udp"])
+                        .inc();
+                    let bind_addr = if addr.is_ipv4() {
+                        "0.0.0.0:0"
+                    } else {
+                        "[::]:0"
+                    };
+                    let socket = match net::UdpSocket::bind(bind_addr).await {
+                        Ok(socket) => Arc::new(socket),
+                        Err(e) => {
+                            pid_sender.fail(NetworkConnectError::Io(e));
+                            continue;
+                        },
+                    };
+                    let cid = self.channel_ids.fetch_add(1, Ordering::Relaxed);
+                    info!("Connecting Udp to: {}", addr);
+                    let (udp_data_sender, udp_data_receiver) = mpsc::channel(UDP_CHANNEL_BUFFER);
+                    tokio::spawn(
+                        Self::udp_single_channel_connect(
+                            Arc::clone(&socket),
+                            addr,
+                            udp_data_sender,
+                        )
+                        .instrument(tracing::info_span!("udp", ?addr)),
+                    );
+                    (
+                        Protocols::new_udp(
+                            socket,
+                            addr,
+                            udp_data_receiver,
+                            cid,
+                            Arc::clone(&self.protocol_metrics),
+                        ),
+                        cid,
+                        true,
+                    )
+                },
+                #[cfg(feature = "quic")]
+                ProtocolAddr::Quic(addr) => {
+                    #[cfg(feature = "metrics")]
+                    self.metrics
+                        .connect_requests_total
+                        .with_label_values(&["quic"])
+                        .inc();
+                    let client = self.quic_config.lock().await.client.clone();
+                    let (config, server_name) = match client {
+                        Some(client) => client,
+                        None => {
+                            pid_sender.fail(NetworkConnectError::Io(io::Error::new(
+                                io::ErrorKind::InvalidInput,
+                                "no QUIC client config set",
+                            )));
+                            continue;
+                        },
+                    };
+                    let (connection, main) =
+                        match Self::quic_connect(addr, config, &server_name).await {
+                            Ok(connection) => connection,
+                            Err(e) => {
+                                pid_sender.fail(NetworkConnectError::Io(e));
+                                continue;
+                            },
+                        };
+                    let cid = self.channel_ids.fetch_add(1, Ordering::Relaxed);
+                    info!("Connecting Quic to: {}", addr);
+                    // the main stream is only visible to the remote side once we send on it
                     (
-                        Protocols::new_mpsc(
-                            local_to_remote_s,
-                            remote_to_local_r,
+                        Protocols::new_quic(
+                            connection,
+                            main,
                             cid,
                             Arc::clone(&self.protocol_metrics),
                         ),
                         cid,
+                        true,
+                    )
+                },
+                ProtocolAddr::Replay(path) => {
+                    let cid = self.channel_ids.fetch_add(1, Ordering::Relaxed);
+                    let replay_cid = self.channel_ids.fetch_add(1, Ordering::Relaxed);
+                    info!(?path, "Connecting to replay");
+                    let metrics = Arc::clone(&self.protocol_metrics);
+                    let (local_to_remote_s, local_to_remote_r) =
+                        mpsc::channel(Self::MPSC_CHANNEL_BOUND);
+                    let (remote_to_local_s, remote_to_local_r) =
+                        mpsc::channel(Self::MPSC_CHANNEL_BOUND);
+                    let replay = Protocols::new_mpsc(
+                        remote_to_local_s,
+                        local_to_remote_r,
+                        replay_cid,
+                        Arc::clone(&metrics),
+                    );
+                    tokio::spawn(
+                        record::replay(path, replay).instrument(tracing::info_span!("replay")),
+                    );
+                    (
+                        Protocols::new_mpsc(local_to_remote_s, remote_to_local_r, cid, metrics),
+                        cid,
                         false,
                     )
                 },
-                /* */
-                //ProtocolAddr::Udp(addr) => {
-                //#[cfg(feature = "metrics")]
-                //self.metrics
-                //.connect_requests_total
-                //.with_label_values(&["udp"])
-                //.inc();
-                //let socket = match net::UdpSocket::bind("0.0.0.0:0").await {
-                //Ok(socket) => Arc::new(socket),
-                //Err(e) => {
-                //pid_sender.send(Err(e)).unwrap();
-                //continue;
-                //},
-                //};
-                //if let Err(e) = socket.connect(addr).await {
-                //pid_sender.send(Err(e)).unwrap();
-                //continue;
-                //};
-                //info!("Connecting Udp to: {}", addr);
-                //let (udp_data_sender, udp_data_receiver) = mpsc::unbounded_channel::<Vec<u8>>();
-                //let protocol = UdpProtocol::new(
-                //Arc::clone(&socket),
-                //addr,
-                //#[cfg(feature = "metrics")]
-                //Arc::clone(&self.metrics),
-                //udp_data_receiver,
-                //);
-                //self.runtime.spawn(
-                //Self::udp_single_channel_connect(Arc::clone(&socket), udp_data_sender)
-                //.instrument(tracing::info_span!("udp", ?addr)),
-                //);
-                //(Protocols::Udp(protocol), true)
-                //},
-                _ => unimplemented!(),
             };
-            self.init_protocol(protocol, cid, Some(pid_sender), handshake)
-                .await;
+            self.init_protocol(
+                protocol,
+                cid,
+                remote_addr,
+                Some(pid_sender),
+                handshake,
+                None,
+            )
+            .await;
         }
         trace!("Stop connect_mgr");
     }
@@ -346,9 +534,44 @@ impl Scheduler {
         mut b2s_prio_statistic_r: mpsc::UnboundedReceiver<B2sPrioStatistic>,
     ) {
         trace!("Start prio_adj_mgr");
-        while let Some((_pid, _frame_cnt, _unused)) = b2s_prio_statistic_r.recv().await {
-
-            //TODO adjust prios in participants here!
+        let mut usage = HashMap::new();
+        let mut last_adjust = Instant::now();
+        while let Some((pid, rate, queued)) = b2s_prio_statistic_r.recv().await {
+            usage.insert(pid, (rate, queued));
+            if last_adjust.elapsed() < Self::PRIO_ADJUST_INTERVAL {
+                continue;
+            }
+            last_adjust = Instant::now();
+            let global = self.bandwidth_config.lock().await.global;
+            let participants = self.participants.lock().await;
+            usage.retain(|pid, _| participants.contains_key(pid));
+            match global {
+                Some(global) => {
+                    // participants with queued messages get as much as possible, the others
+                    // what they used plus some headroom to grow
+                    let floor = global / usage.len().max(1) as u64 / 4;
+                    let demands = usage
+                        .iter()
+                        .map(|(pid, &(rate, queued))| {
+                            let demand = if queued > 0 {
+                                u64::MAX
+                            } else {
+                                (rate + rate / 4).max(floor)
+                            };
+                            let cap = participants[pid].bandwidth.cap();
+                            (*pid, demand.min(cap.unwrap_or(u64::MAX)))
+                        })
+                        .collect::<Vec<_>>();
+                    for (pid, share) in fair_shares(global, demands) {
+                        participants[&pid].bandwidth.set_share(share);
+                    }
+                },
+                None => {
+                    for info in participants.values() {
+                        info.bandwidth.set_share(u64::MAX);
+                    }
+                },
+            }
         }
         trace!("Stop prio_adj_mgr");
     }
@@ -415,11 +638,7 @@ impl Scheduler {
                         listener
                     },
                     Err(e) => {
-                        info!(
-                            ?addr,
-                            ?e,
-                            "Tcp bind error durin listener startup"
-                        );
+                        info!(?addr, ?e, "Tcp bind error durin listener startup");
                         s2a_listen_result_s.send(Err(e)).unwrap();
                         return;
                     },
@@ -439,8 +658,15 @@ impl Scheduler {
                     };
                     info!("Accepting Tcp from: {}", remote_addr);
                     let cid = self.channel_ids.fetch_add(1, Ordering::Relaxed);
-                    self.init_protocol(Protocols::new_tcp(stream, cid, Arc::clone(&self.protocol_metrics)), cid, None, true)
-                        .await;
+                    self.init_protocol(
+                        Protocols::new_tcp(stream, cid, Arc::clone(&self.protocol_metrics)),
+                        cid,
+                        ProtocolAddr::Tcp(remote_addr),
+                        None,
+                        true,
+                        None,
+                    )
+                    .await;
                 }
             },
             ProtocolAddr::Mpsc(addr) => {
@@ -450,19 +676,30 @@ impl Scheduler {
                 trace!(?addr, "Listener bound");
 
                 let mut end_receiver = s2s_stop_listening_r.fuse();
-                while let Some((local_to_remote_s, local_remote_to_local_s)) = select! {
+                while let Some(connect) = select! {
                     next = mpsc_r.recv().fuse() => next,
                     _ = &mut end_receiver => None,
                 } {
-                    let (remote_to_local_s, remote_to_local_r) = mpsc::channel(Self::MPSC_CHANNEL_BOUND);
-                    local_remote_to_local_s.send(remote_to_local_s).unwrap();
                     info!(?addr, "Accepting Mpsc from");
                     let cid = self.channel_ids.fetch_add(1, Ordering::Relaxed);
-                    self.init_protocol(Protocols::new_mpsc(local_to_remote_s, remote_to_local_r, cid, Arc::clone(&self.protocol_metrics)), cid, None, true)
+                    let metrics = Arc::clone(&self.protocol_metrics);
+                    let protocol = match connect {
+                        S2sMpscConnect::Mpsc(local_to_remote_s, local_remote_to_local_s) => {
+                            let (remote_to_local_s, remote_to_local_r) =
+                                mpsc::channel(Self::MPSC_CHANNEL_BOUND);
+                            local_remote_to_local_s.send(remote_to_local_s).unwrap();
+                            Protocols::new_mpsc(local_to_remote_s, remote_to_local_r, cid, metrics)
+                        },
+                        #[cfg(feature = "fault_injection")]
+                        S2sMpscConnect::Fault(drain, sink) => {
+                            Protocols::new_fault(*drain, sink, cid, metrics)
+                        },
+                    };
+                    self.init_protocol(protocol, cid, ProtocolAddr::Mpsc(addr), None, true, None)
                         .await;
                 }
                 warn!("MpscStream Failed, stopping");
-            },/*
+            },
             ProtocolAddr::Udp(addr) => {
                 let socket = match net::UdpSocket::bind(addr).await {
                     Ok(socket) => {
@@ -482,74 +719,280 @@ impl Scheduler {
                 trace!(?addr, "Listener bound");
                 // receiving is done from here and will be piped to protocol as UDP does not
                 // have any state
-                let mut listeners = HashMap::new();
+                let mut listeners = HashMap::<SocketAddr, mpsc::Sender<BytesMut>>::new();
+                let pending_handshakes = Arc::new(AtomicUsize::new(0));
+                let mut cleanup_interval = tokio::time::interval(UDP_LISTENER_CLEANUP_INTERVAL);
                 let mut end_receiver = s2s_stop_listening_r.fuse();
-                const UDP_MAXIMUM_SINGLE_PACKET_SIZE_EVER: usize = 9216;
                 let mut data = [0u8; UDP_MAXIMUM_SINGLE_PACKET_SIZE_EVER];
-                while let Ok((size, remote_addr)) = select! {
-                    next = socket.recv_from(&mut data).fuse() => next,
-                    _ = &mut end_receiver => Err(std::io::Error::new(std::io::ErrorKind::Other, "")),
+                while let Some(next) = select! {
+                    next = socket.recv_from(&mut data).fuse() => Some(Some(next)),
+                    _ = cleanup_interval.tick() => Some(None),
+                    _ = &mut end_receiver => None,
                 } {
-                    let mut datavec = Vec::with_capacity(size);
-                    datavec.extend_from_slice(&data[0..size]);
-                    //Due to the async nature i cannot make of .entry() as it would lead to a still
-                    // borrowed in another branch situation
-                    #[allow(clippy::map_entry)]
-                    if !listeners.contains_key(&remote_addr) {
-                        info!("Accepting Udp from: {}", &remote_addr);
-                        let (udp_data_sender, udp_data_receiver) =
-                            mpsc::unbounded_channel::<Vec<u8>>();
-                        listeners.insert(remote_addr, udp_data_sender);
-                        let protocol = UdpProtocol::new(
+                    let (size, remote_addr) = match next {
+                        Some(Ok(next)) => next,
+                        Some(Err(e)) => {
+                            warn!(?e, "UdpSocket Error, ignoring datagram");
+                            continue;
+                        },
+                        None => {
+                            // the receivers of channels that were closed or failed their handshake
+                            // are dropped
+                            listeners.retain(|_, sender| !sender.is_closed());
+                            continue;
+                        },
+                    };
+                    let datagram = BytesMut::from(&data[..size]);
+                    // once a channel is closed, its receiver is dropped. Datagrams from the same
+                    // address then belong to a new connection attempt
+                    let datagram = match listeners.get(&remote_addr) {
+                        Some(sender) => match sender.try_send(datagram) {
+                            Ok(()) => continue,
+                            Err(mpsc::error::TrySendError::Full(_)) => {
+                                trace!(?remote_addr, "Udp channel is full, dropping datagram");
+                                continue;
+                            },
+                            Err(mpsc::error::TrySendError::Closed(datagram)) => {
+                                listeners.remove(&remote_addr);
+                                datagram
+                            },
+                        },
+                        None => datagram,
+                    };
+                    if pending_handshakes.load(Ordering::Relaxed) >= MAX_PENDING_UDP_HANDSHAKES {
+                        debug!(
+                            ?remote_addr,
+                            "Too many pending Udp handshakes, ignoring datagram"
+                        );
+                        continue;
+                    }
+                    info!("Accepting Udp from: {}", remote_addr);
+                    let (udp_data_sender, udp_data_receiver) = mpsc::channel(UDP_CHANNEL_BUFFER);
+                    udp_data_sender.try_send(datagram).unwrap();
+                    listeners.insert(remote_addr, udp_data_sender);
+                    let cid = self.channel_ids.fetch_add(1, Ordering::Relaxed);
+                    self.init_protocol(
+                        Protocols::new_udp(
                             Arc::clone(&socket),
                             remote_addr,
-                            #[cfg(feature = "metrics")]
-                            Arc::clone(&self.metrics),
                             udp_data_receiver,
+                            cid,
+                            Arc::clone(&self.protocol_metrics),
+                        ),
+                        cid,
+                        ProtocolAddr::Udp(remote_addr),
+                        None,
+                        false,
+                        Some(PendingHandshake::new(&pending_handshakes)),
+                    )
+                    .await;
+                }
+            },
+            #[cfg(feature = "quic")]
+            ProtocolAddr::Quic(addr) => {
+                let server = self.quic_config.lock().await.server.clone();
+                let config = match server {
+                    Some(config) => config,
+                    None => {
+                        info!(
+                            ?addr,
+                            "Listener couldn't be started without a QUIC server config"
                         );
-                        self.init_protocol(Protocols::Udp(protocol), None, false)
-                            .await;
+                        s2a_listen_result_s
+                            .send(Err(io::Error::new(
+                                io::ErrorKind::InvalidInput,
+                                "no QUIC server config set",
+                            )))
+                            .unwrap();
+                        return;
+                    },
+                };
+                let mut builder = quinn::Endpoint::builder();
+                builder.listen(config);
+                let (_endpoint, mut incoming) = match builder.bind(&addr) {
+                    Ok(endpoint) => {
+                        s2a_listen_result_s.send(Ok(())).unwrap();
+                        endpoint
+                    },
+                    Err(quinn::EndpointError::Socket(e)) => {
+                        info!(
+                            ?addr,
+                            ?e,
+                            "Listener couldn't be started due to error on quic bind"
+                        );
+                        s2a_listen_result_s.send(Err(e)).unwrap();
+                        return;
+                    },
+                };
+                trace!(?addr, "Listener bound");
+                // connections are accepted concurrently, so a slow TLS handshake doesn't block
+                // others
+                let mut accepting = FuturesUnordered::new();
+                let mut end_receiver = s2s_stop_listening_r.fuse();
+                loop {
+                    select! {
+                        next = incoming.next() => match next {
+                            Some(connecting) => accepting.push(Self::quic_accept(connecting)),
+                            None => break,
+                        },
+                        Some(accepted) = accepting.next() => match accepted {
+                            Ok((connection, main)) => {
+                                let remote_addr = connection.connection.remote_address();
+                                info!("Accepting Quic from: {}", remote_addr);
+                                let cid = self.channel_ids.fetch_add(1, Ordering::Relaxed);
+                                self.init_protocol(
+                                    Protocols::new_quic(
+                                        connection,
+                                        main,
+                                        cid,
+                                        Arc::clone(&self.protocol_metrics),
+                                    ),
+                                    cid,
+                                    ProtocolAddr::Quic(remote_addr),
+                                    None,
+                                    false,
+                                    None,
+                                )
+                                .await;
+                            },
+                            Err(e) => warn!(?e, "Quic Error, ignoring connection attempt"),
+                        },
+                        _ = &mut end_receiver => break,
                     }
-                    let udp_data_sender = listeners.get_mut(&remote_addr).unwrap();
-                    udp_data_sender.send(datavec).unwrap();
                 }
-            },*/
-            _ => unimplemented!(),
+            },
+            ProtocolAddr::Replay(_) => {
+                s2a_listen_result_s
+                    .send(Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "a replay can only be connected to",
+                    )))
+                    .unwrap();
+            },
         }
         trace!(?addr, "Ending channel creator");
     }
 
-    #[allow(dead_code)]
+    /// Waits for the TLS handshake and the main stream of an incoming QUIC
+    /// connection
+    #[cfg(feature = "quic")]
+    async fn quic_accept(
+        connecting: quinn::Connecting,
+    ) -> Result<QuicConnection, quinn::ConnectionError> {
+        let mut connection = connecting.await?;
+        match connection.bi_streams.next().await {
+            Some(main) => Ok((connection, main?)),
+            None => Err(quinn::ConnectionError::LocallyClosed),
+        }
+    }
+
+    #[cfg(feature = "quic")]
+    async fn quic_connect(
+        addr: SocketAddr,
+        config: quinn::ClientConfig,
+        server_name: &str,
+    ) -> io::Result<QuicConnection> {
+        let bind_addr = if addr.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+        let (endpoint, _) = quinn::Endpoint::builder()
+            .bind(&bind_addr.parse().unwrap())
+            .map_err(|quinn::EndpointError::Socket(e)| e)?;
+        let connection = endpoint
+            .connect_with(config, &addr, server_name)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::ConnectionRefused, e))?;
+        let main = connection
+            .connection
+            .open_bi()
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::ConnectionAborted, e))?;
+        Ok((connection, main))
+    }
+
+    /// Hands the local ends of a new channel to the mpsc listener. With a
+    /// [`FaultConfig`] set, both directions inject faults, each seeded
+    /// differently.
+    async fn connect_mpsc(
+        &self,
+        listener: &mpsc::UnboundedSender<S2sMpscConnect>,
+        cid: Cid,
+    ) -> Protocols {
+        let metrics = Arc::clone(&self.protocol_metrics);
+        #[cfg(feature = "fault_injection")]
+        if let Some(config) = self.fault_config.lock().await.clone() {
+            let remote_config = FaultConfig {
+                seed: config.seed.wrapping_add(1),
+                ..config
+            };
+            let (local_to_remote_s, local_to_remote_r) = fault_channel(config);
+            let (remote_to_local_s, remote_to_local_r) = fault_channel(remote_config);
+            listener
+                .send(S2sMpscConnect::Fault(
+                    Box::new(remote_to_local_s),
+                    local_to_remote_r,
+                ))
+                .unwrap();
+            return Protocols::new_fault(local_to_remote_s, remote_to_local_r, cid, metrics);
+        }
+        let (remote_to_local_s, remote_to_local_r) = mpsc::channel(Self::MPSC_CHANNEL_BOUND);
+        let (local_to_remote_oneshot_s, local_to_remote_oneshot_r) = oneshot::channel();
+        listener
+            .send(S2sMpscConnect::Mpsc(
+                remote_to_local_s,
+                local_to_remote_oneshot_s,
+            ))
+            .unwrap();
+        let local_to_remote_s = local_to_remote_oneshot_r.await.unwrap();
+        Protocols::new_mpsc(local_to_remote_s, remote_to_local_r, cid, metrics)
+    }
+
+    /// Receives the datagrams of a connected UDP channel, until the channel is
+    /// closed
     async fn udp_single_channel_connect(
         socket: Arc<net::UdpSocket>,
-        w2p_udp_package_s: mpsc::UnboundedSender<Vec<u8>>,
+        remote_addr: SocketAddr,
+        w2p_udp_package_s: mpsc::Sender<BytesMut>,
     ) {
-        let addr = socket.local_addr();
-        trace!(?addr, "Start udp_single_channel_connect");
-        //TODO: implement real closing
-        let (_end_sender, end_receiver) = oneshot::channel::<()>();
-
+        trace!(?remote_addr, "Start udp_single_channel_connect");
         // receiving is done from here and will be piped to protocol as UDP does not
         // have any state
-        let mut end_receiver = end_receiver.fuse();
-        let mut data = [0u8; 9216];
-        while let Ok(size) = select! {
-            next = socket.recv(&mut data).fuse() => next,
-            _ = &mut end_receiver => Err(std::io::Error::new(std::io::ErrorKind::Other, "")),
+        let mut data = [0u8; UDP_MAXIMUM_SINGLE_PACKET_SIZE_EVER];
+        while let Some(next) = select! {
+            next = socket.recv_from(&mut data) => Some(next),
+            _ = w2p_udp_package_s.closed() => None,
         } {
-            let mut datavec = Vec::with_capacity(size);
-            datavec.extend_from_slice(&data[0..size]);
-            w2p_udp_package_s.send(datavec).unwrap();
+            match next {
+                Ok((size, addr)) if addr == remote_addr => {
+                    match w2p_udp_package_s.try_send(BytesMut::from(&data[..size])) {
+                        Ok(()) => {},
+                        Err(mpsc::error::TrySendError::Full(_)) => {
+                            trace!(?remote_addr, "Udp channel is full, dropping datagram")
+                        },
+                        Err(mpsc::error::TrySendError::Closed(_)) => break,
+                    }
+                },
+                Ok((_, addr)) => debug!(?addr, "Ignoring datagram from unknown address"),
+                Err(e) => {
+                    debug!(?e, "UdpSocket Error, closing channel");
+                    break;
+                },
+            }
         }
-        trace!(?addr, "Stop udp_single_channel_connect");
+        trace!(?remote_addr, "Stop udp_single_channel_connect");
     }
 
     async fn init_protocol(
         &self,
         mut protocol: Protocols,
         cid: Cid,
-        s2a_return_pid_s: Option<oneshot::Sender<Result<Participant, NetworkConnectError>>>,
+        remote_addr: ProtocolAddr,
+        s2a_return_pid_s: Option<S2aConnectResult>,
         send_handshake: bool,
+        pending_handshake: Option<PendingHandshake>,
     ) {
         //channels are unknown till PID is known!
         /* When A connects to a NETWORK, we, the listener answers with a Handshake.
@@ -566,47 +1009,100 @@ impl Scheduler {
         let metrics = Arc::clone(&self.metrics);
         let local_pid = self.local_pid;
         let local_secret = self.local_secret;
+        let local_identity = Arc::clone(&*self.local_identity.lock().await);
+        let resume_timeout = Arc::clone(&self.resume_timeout);
+        let bandwidth_config = Arc::clone(&self.bandwidth_config);
+        let record_dir = Arc::clone(&self.record_dir);
         // this is necessary for UDP to work at all and to remove code duplication
         tokio::spawn(
             async move {
                 trace!(?cid, "Open channel and be ready for Handshake");
                 use network_protocol::InitProtocol;
-                let init_result = protocol
-                    .initialize(send_handshake, local_pid, local_secret)
-                    .instrument(tracing::info_span!("handshake", ?cid))
-                    .await;
+                let init_result = tokio::time::timeout(
+                    HANDSHAKE_TIMEOUT,
+                    protocol
+                        .initialize(send_handshake, local_pid, local_secret, &local_identity)
+                        .instrument(tracing::info_span!("handshake", ?cid)),
+                )
+                .await
+                .unwrap_or_else(|_| {
+                    debug!(?cid, "Handshake timed out");
+                    Err(InitProtocolError::Closed)
+                });
+                drop(pending_handshake);
                 match init_result {
-                    Ok((pid, sid, secret)) => {
+                    Ok((pid, sid, secret, identity)) => {
                         trace!(
                             ?cid,
                             ?pid,
                             "Detected that my channel is ready!, activating it :)"
                         );
                         let mut participants = participants.lock().await;
+                        if let Some(S2aConnectResult::Resume(expected, _)) = &s2a_return_pid_s {
+                            if *expected != pid || !participants.contains_key(&pid) {
+                                debug!(?cid, ?pid, "remote doesn't continue our session");
+                                if let Some(s2a_return_pid_s) = s2a_return_pid_s {
+                                    s2a_return_pid_s.fail(NetworkConnectError::SessionExpired);
+                                }
+                                return;
+                            }
+                        }
                         if !participants.contains_key(&pid) {
                             debug!(?cid, "New participant connected via a channel");
+                            let channel_stats = ChannelStatsMap::default();
+                            let config = *bandwidth_config.lock().await;
+                            let bandwidth = Arc::new(BandwidthLimit::new(config.participant));
+                            if let Some(global) = config.global {
+                                // till the next adjustment
+                                bandwidth.set_share(global / (participants.len() as u64 + 1));
+                            }
+                            let recorder = match &*record_dir.lock().await {
+                                Some(dir) => match Recorder::create(dir, pid) {
+                                    Ok(recorder) => Some(recorder),
+                                    Err(e) => {
+                                        warn!(?e, ?dir, "Couldn't record participant");
+                                        None
+                                    },
+                                },
+                                None => None,
+                            };
                             let (
                                 bparticipant,
                                 a2b_open_stream_s,
                                 b2a_stream_opened_r,
                                 s2b_create_channel_s,
                                 s2b_shutdown_bparticipant_s,
-                            ) = BParticipant::new(local_pid, pid, sid, Arc::clone(&metrics));
+                            ) = BParticipant::new(
+                                local_pid,
+                                pid,
+                                sid,
+                                Arc::clone(&metrics),
+                                resume_timeout,
+                                Arc::clone(&channel_stats),
+                                Arc::clone(&bandwidth),
+                                recorder,
+                            );
 
                             let participant = Participant::new(
                                 local_pid,
                                 pid,
+                                remote_addr,
+                                identity,
                                 a2b_open_stream_s,
                                 b2a_stream_opened_r,
                                 participant_channels.a2s_disconnect_s,
+                                channel_stats,
+                                Arc::clone(&bandwidth),
                             );
 
                             #[cfg(feature = "metrics")]
                             metrics.participants_connected_total.inc();
                             participants.insert(pid, ParticipantInfo {
                                 secret,
+                                identity,
                                 s2b_create_channel_s: s2b_create_channel_s.clone(),
                                 s2b_shutdown_bparticipant_s: Some(s2b_shutdown_bparticipant_s),
+                                bandwidth,
                             });
                             drop(participants);
                             trace!("dropped participants lock");
@@ -624,7 +1120,9 @@ impl Scheduler {
                                 .send((cid, sid, protocol, b2s_create_channel_done_s))
                                 .unwrap();
                             b2s_create_channel_done_r.await.unwrap();
-                            if let Some(pid_oneshot) = s2a_return_pid_s {
+                            if let Some(S2aConnectResult::Participant(pid_oneshot)) =
+                                s2a_return_pid_s
+                            {
                                 // someone is waiting with `connect`, so give them their PID
                                 pid_oneshot.send(Ok(participant)).unwrap();
                             } else {
@@ -643,7 +1141,7 @@ impl Scheduler {
                                 ?cid,
                                 "2nd+ channel of participant, going to compare security ids"
                             );
-                            if pi.secret != secret {
+                            if pi.secret != secret || pi.identity != identity {
                                 warn!(
                                     ?cid,
                                     ?pid,
@@ -654,17 +1152,39 @@ impl Scheduler {
                                 //TODO
                                 if let Some(pid_oneshot) = s2a_return_pid_s {
                                     // someone is waiting with `connect`, so give them their Error
-                                    pid_oneshot
-                                        .send(Err(NetworkConnectError::InvalidSecret))
-                                        .unwrap();
+                                    pid_oneshot.fail(NetworkConnectError::InvalidSecret);
                                 }
                                 return;
                             }
-                            error!(
-                                ?cid,
-                                "Ufff i cant answer the pid_oneshot. as i need to create the SAME \
-                                 participant. maybe switch to ARC"
-                            );
+                            if let Some(S2aConnectResult::Participant(_)) = s2a_return_pid_s {
+                                error!(
+                                    ?cid,
+                                    "Ufff i cant answer the pid_oneshot. as i need to create the \
+                                     SAME participant. maybe switch to ARC"
+                                );
+                                return;
+                            }
+                            // the session is resumed, either by the remote or by us
+                            let s2b_create_channel_s = pi.s2b_create_channel_s.clone();
+                            drop(participants);
+                            let (b2s_create_channel_done_s, b2s_create_channel_done_r) =
+                                oneshot::channel();
+                            let sent = s2b_create_channel_s
+                                .send((cid, sid, protocol, b2s_create_channel_done_s))
+                                .is_ok();
+                            let attached = sent && b2s_create_channel_done_r.await.is_ok();
+                            if attached {
+                                info!(?cid, ?pid, "attached channel to existing participant");
+                            } else {
+                                debug!(?cid, ?pid, "participant failed before it was resumed");
+                            }
+                            if let Some(S2aConnectResult::Resume(_, s)) = s2a_return_pid_s {
+                                let _ = s.send(if attached {
+                                    Ok(())
+                                } else {
+                                    Err(NetworkConnectError::SessionExpired)
+                                });
+                            }
                         }
                         //From now on this CHANNEL can receiver other frames!
                         // move directly to participant!
@@ -674,9 +1194,7 @@ impl Scheduler {
                         if let Some(pid_oneshot) = s2a_return_pid_s {
                             // someone is waiting with `connect`, so give them their Error
                             trace!(?cid, "returning the Err to api who requested the connect");
-                            pid_oneshot
-                                .send(Err(NetworkConnectError::Handshake(e)))
-                                .unwrap();
+                            pid_oneshot.fail(NetworkConnectError::Handshake(e));
                         }
                     },
                 }
@@ -685,3 +1203,50 @@ impl Scheduler {
         ); /*WORKAROUND FOR SPAN NOT TO GET LOST*/
     }
 }
+
+/// Splits `global` bytes per second between participants: those demanding less
+/// than an equal split get their demand, the rest is split equally between the
+/// others.
+fn fair_shares(global: Bandwidth, mut demands: Vec<(Pid, Bandwidth)>) -> Vec<(Pid, Bandwidth)> {
+    demands.sort_unstable_by_key(|(_, demand)| *demand);
+    let cnt = demands.len() as u64;
+    let mut left = global;
+    demands
+        .into_iter()
+        .enumerate()
+        .map(|(i, (pid, demand))| {
+            let share = demand.min(left / (cnt - i as u64));
+            left -= share;
+            (pid, share)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fair_shares_keep_global() {
+        let demands = vec![
+            (Pid::fake(0), 10_000),
+            (Pid::fake(1), u64::MAX),
+            (Pid::fake(2), u64::MAX),
+            (Pid::fake(3), 500_000),
+        ];
+        let shares = fair_shares(1_000_000, demands)
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        assert_eq!(shares[&Pid::fake(0)], 10_000);
+        // 330_000 each, as the 500_000 can't be satisfied
+        assert_eq!(shares[&Pid::fake(1)], 330_000);
+        assert_eq!(shares[&Pid::fake(2)], 330_000);
+        assert_eq!(shares[&Pid::fake(3)], 330_000);
+        assert!(shares.values().sum::<u64>() <= 1_000_000);
+    }
+
+    #[test]
+    fn fair_shares_without_participants() {
+        assert!(fair_shares(1_000_000, vec![]).is_empty());
+    }
+}
@@ -61,6 +61,10 @@ pub fn network_participant_stream(
     let (n_a, p1_a, s1_a, n_b, p1_b, s1_b) = runtime.block_on(async {
         let n_a = Network::new(Pid::fake(0), &runtime);
         let n_b = Network::new(Pid::fake(1), &runtime);
+        #[cfg(feature = "quic")]
+        if let ProtocolAddr::Quic(_) = addr {
+            quic_config(&n_a, &n_b).await;
+        }
 
         n_a.listen(addr.clone()).await.unwrap();
         let p1_b = n_b.connect(addr).await.unwrap();
@@ -100,3 +104,34 @@ pub fn mpsc() -> ProtocolAddr {
     let port = PORTS.fetch_add(1, Ordering::Relaxed);
     ProtocolAddr::Mpsc(port)
 }
+
+#[cfg(feature = "quic")]
+#[allow(dead_code)]
+pub fn quic() -> ProtocolAddr {
+    lazy_static! {
+        // QUIC runs over UDP, so it mustn't share ports with `udp()`
+        static ref PORTS: AtomicU16 = AtomicU16::new(6000);
+    }
+    let port = PORTS.fetch_add(1, Ordering::Relaxed);
+    ProtocolAddr::Quic(SocketAddr::from(([127, 0, 0, 1], port)))
+}
+
+/// `server` gets a self-signed certificate for "localhost", which `client`
+/// trusts
+#[cfg(feature = "quic")]
+#[allow(dead_code)]
+pub async fn quic_config(server: &Network, client: &Network) {
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let key = quinn::PrivateKey::from_der(&cert.serialize_private_key_der()).unwrap();
+    let cert = quinn::Certificate::from_der(&cert.serialize_der().unwrap()).unwrap();
+    let mut server_config = quinn::ServerConfigBuilder::default();
+    server_config
+        .certificate(quinn::CertificateChain::from_certs(vec![cert.clone()]), key)
+        .unwrap();
+    let mut client_config = quinn::ClientConfigBuilder::default();
+    client_config.add_certificate_authority(cert).unwrap();
+    server.set_quic_server_config(server_config.build()).await;
+    client
+        .set_quic_client_config(client_config.build(), "localhost".to_string())
+        .await;
+}
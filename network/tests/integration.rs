@@ -1,10 +1,10 @@
 use std::sync::Arc;
 use tokio::runtime::Runtime;
-use veloren_network::{NetworkError, StreamError};
+use veloren_network::{NetworkConnectError, NetworkError, StreamError};
 mod helper;
 use helper::{mpsc, network_participant_stream, tcp, udp};
 use std::io::ErrorKind;
-use veloren_network::{Network, Pid, Promises, ProtocolAddr};
+use veloren_network::{Identity, Network, Pid, Promises, ProtocolAddr};
 
 #[test]
 #[ignore]
@@ -74,7 +74,6 @@ fn stream_simple_mpsc_3msg() {
 }
 
 #[test]
-#[ignore]
 fn stream_simple_udp() {
     let (_, _) = helper::setup(false, 0);
     let (r, _n_a, _p_a, mut s1_a, _n_b, _p_b, mut s1_b) = network_participant_stream(udp());
@@ -85,7 +84,6 @@ fn stream_simple_udp() {
 }
 
 #[test]
-#[ignore]
 fn stream_simple_udp_3msg() {
     let (_, _) = helper::setup(false, 0);
     let (r, _n_a, _p_a, mut s1_a, _n_b, _p_b, mut s1_b) = network_participant_stream(udp());
@@ -99,6 +97,276 @@ fn stream_simple_udp_3msg() {
     drop((_n_a, _n_b, _p_a, _p_b)); //clean teardown
 }
 
+#[cfg(feature = "quic")]
+#[test]
+fn stream_simple_quic() {
+    let (_, _) = helper::setup(false, 0);
+    let (r, _n_a, _p_a, mut s1_a, _n_b, _p_b, mut s1_b) =
+        network_participant_stream(helper::quic());
+
+    s1_a.send("Hello World").unwrap();
+    assert_eq!(r.block_on(s1_b.recv()), Ok("Hello World".to_string()));
+    drop((_n_a, _n_b, _p_a, _p_b)); //clean teardown
+}
+
+#[cfg(feature = "quic")]
+#[test]
+fn stream_quic_multiple_streams() {
+    let (_, _) = helper::setup(false, 0);
+    let (r, _n_a, p_a, mut s1_a, _n_b, p_b, mut s1_b) = network_participant_stream(helper::quic());
+
+    let mut s2_a = r
+        .block_on(p_a.open(2, Promises::ORDERED | Promises::ENCRYPTED, 0))
+        .unwrap();
+    let mut s2_b = r.block_on(p_b.opened()).unwrap();
+    s1_a.send(vec![42u8; 100_000]).unwrap();
+    s2_a.send("Hello World").unwrap();
+    s1_a.send(1337).unwrap();
+    assert_eq!(r.block_on(s2_b.recv()), Ok("Hello World".to_string()));
+    assert_eq!(r.block_on(s1_b.recv()), Ok(vec![42u8; 100_000]));
+    assert_eq!(r.block_on(s1_b.recv()), Ok(1337));
+    drop(s2_a);
+    assert_eq!(
+        r.block_on(s2_b.recv::<String>()),
+        Err(StreamError::StreamClosed)
+    );
+    drop((_n_a, _n_b, p_a, p_b)); //clean teardown
+}
+
+#[cfg(feature = "quic")]
+#[test]
+fn failed_quic_listen_without_config() {
+    let (_, _) = helper::setup(false, 0);
+    let r = Arc::new(Runtime::new().unwrap());
+    let network = Network::new(Pid::new(), &r);
+    let e = r.block_on(network.listen(helper::quic()));
+    match e {
+        Err(NetworkError::ListenFailed(e)) if e.kind() == ErrorKind::InvalidInput => (),
+        _ => panic!(),
+    };
+}
+
+#[test]
+fn reconnect_attaches_channel() {
+    let (_, _) = helper::setup(false, 0);
+    let addr = tcp();
+    let (r, _n_a, p_a, mut s1_a, n_b, p_b, mut s1_b) = network_participant_stream(addr.clone());
+
+    let mut s2_a = r
+        .block_on(p_a.open(4, Promises::ORDERED | Promises::ENCRYPTED, 0))
+        .unwrap();
+    let mut s2_b = r.block_on(p_b.opened()).unwrap();
+    r.block_on(n_b.reconnect(p_b.remote_pid(), addr)).unwrap();
+
+    // the new channel is used for existing streams
+    s1_a.send("Hello World").unwrap();
+    s2_b.send(1337).unwrap();
+    assert_eq!(r.block_on(s1_b.recv()), Ok("Hello World".to_string()));
+    assert_eq!(r.block_on(s2_a.recv()), Ok(1337));
+    drop((_n_a, n_b, p_a, p_b)); //clean teardown
+}
+
+#[test]
+fn channel_stats_tcp() {
+    let (_, _) = helper::setup(false, 0);
+    let (r, _n_a, p_a, mut s1_a, _n_b, _p_b, mut s1_b) = network_participant_stream(tcp());
+
+    s1_a.send("Hello World").unwrap();
+    assert_eq!(r.block_on(s1_b.recv()), Ok("Hello World".to_string()));
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    let stats = p_a.channel_stats();
+    assert_eq!(stats.len(), 1);
+    assert!(stats[0].rtt().is_some());
+    assert!(stats[0].sent_bytes(4) > 0);
+    assert_eq!(stats[0].queued_messages(), 0);
+    drop((_n_a, _n_b, p_a, _p_b)); //clean teardown
+}
+
+#[cfg(feature = "fault_injection")]
+#[test]
+fn mpsc_with_faults() {
+    use veloren_network::{Delay, FaultConfig};
+    let (_, _) = helper::setup(false, 0);
+    let r = Arc::new(Runtime::new().unwrap());
+    let n_a = Network::new(Pid::fake(0), &r);
+    let n_b = Network::new(Pid::fake(1), &r);
+    let config = FaultConfig {
+        drop_rate: 0.1,
+        delay: Delay::Uniform(
+            std::time::Duration::from_millis(5),
+            std::time::Duration::from_millis(20),
+        ),
+        reorder_rate: 0.1,
+        seed: 3,
+        ..Default::default()
+    };
+    let addr = mpsc();
+    r.block_on(n_b.set_fault_injection(Some(config)));
+    r.block_on(n_a.listen(addr.clone())).unwrap();
+    let p_b = r.block_on(n_b.connect(addr)).unwrap();
+    let p_a = r.block_on(n_a.connected()).unwrap();
+    let mut s1_a = r
+        .block_on(p_a.open(4, Promises::ORDERED | Promises::GUARANTEED_DELIVERY, 0))
+        .unwrap();
+    let mut s1_b = r.block_on(p_b.opened()).unwrap();
+
+    for i in 0..100u32 {
+        s1_a.send(vec![i; 100]).unwrap();
+    }
+    for i in 0..100u32 {
+        assert_eq!(r.block_on(s1_b.recv()), Ok(vec![i; 100]));
+    }
+    assert!(p_a.channel_stats()[0].retransmissions() > 0);
+    drop((n_a, n_b, p_a, p_b)); //clean teardown
+}
+
+/// Sends a large message on the prio 4 stream and a small one on a prio 3
+/// stream, which must not wait for the large one
+fn lower_prio_is_deferred(
+    r: &Runtime,
+    p_a: &veloren_network::Participant,
+    p_b: &veloren_network::Participant,
+    s1_a: &mut veloren_network::Stream,
+    s1_b: &mut veloren_network::Stream,
+) {
+    let mut s2_a = r.block_on(p_a.open(3, Promises::ORDERED, 10_000)).unwrap();
+    let mut s2_b = r.block_on(p_b.opened()).unwrap();
+    s1_a.send(vec![4u8; 100_000]).unwrap();
+    s2_a.send("Hello World").unwrap();
+    assert_eq!(r.block_on(s2_b.recv()), Ok("Hello World".to_string()));
+    // 100kB at 20kB/s take 5 seconds
+    assert_eq!(s1_b.try_recv::<Vec<u8>>(), Ok(None));
+}
+
+#[test]
+fn participant_bandwidth_limit() {
+    let (_, _) = helper::setup(false, 0);
+    let (r, _n_a, p_a, mut s1_a, _n_b, p_b, mut s1_b) = network_participant_stream(tcp());
+
+    p_a.set_bandwidth_limit(Some(20_000));
+    assert_eq!(p_a.bandwidth_limit(), Some(20_000));
+    lower_prio_is_deferred(&r, &p_a, &p_b, &mut s1_a, &mut s1_b);
+    drop((_n_a, _n_b, p_a, p_b)); //clean teardown
+}
+
+#[test]
+fn global_bandwidth_limit() {
+    let (_, _) = helper::setup(false, 0);
+    let (r, n_a, p_a, mut s1_a, _n_b, p_b, mut s1_b) = network_participant_stream(tcp());
+
+    r.block_on(n_a.set_global_bandwidth_limit(Some(20_000)));
+    // wait till the scheduler split the global limit
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    assert_eq!(p_a.bandwidth_limit(), None);
+    lower_prio_is_deferred(&r, &p_a, &p_b, &mut s1_a, &mut s1_b);
+    drop((n_a, _n_b, p_a, p_b)); //clean teardown
+}
+
+#[test]
+fn failed_reconnect_to_unknown_participant() {
+    let (_, _) = helper::setup(false, 0);
+    let addr = tcp();
+    let (r, _n_a, _p_a, _, n_b, _p_b, _) = network_participant_stream(addr.clone());
+
+    match r.block_on(n_b.reconnect(Pid::fake(2), addr)) {
+        Err(NetworkError::ConnectFailed(NetworkConnectError::SessionExpired)) => (),
+        _ => panic!(),
+    };
+    drop((_n_a, n_b, _p_a, _p_b)); //clean teardown
+}
+
+#[test]
+fn connect_authenticated_rejects_other_identities() {
+    let (_, _) = helper::setup(false, 0);
+    let r = Arc::new(Runtime::new().unwrap());
+    let server_identity = Identity::generate();
+    let expected = server_identity.public_key();
+    // The server loads its identity again, as it would after a restart
+    let server = Network::new(Pid::fake(0), &r);
+    r.block_on(server.set_identity(Identity::from_pkcs8(server_identity.to_pkcs8()).unwrap()));
+    let impostor = Network::new(Pid::fake(1), &r);
+    let impostor_identity = Identity::generate();
+    let impostor_key = impostor_identity.public_key();
+    r.block_on(impostor.set_identity(impostor_identity));
+    let client = Network::new(Pid::fake(2), &r);
+
+    let (server_addr, impostor_addr) = (tcp(), tcp());
+    r.block_on(server.listen(server_addr.clone())).unwrap();
+    r.block_on(impostor.listen(impostor_addr.clone())).unwrap();
+
+    let p = r
+        .block_on(client.connect_authenticated(server_addr, expected))
+        .unwrap();
+    assert_eq!(p.remote_identity(), expected);
+    match r.block_on(client.connect_authenticated(impostor_addr, expected)) {
+        Err(NetworkError::ConnectFailed(NetworkConnectError::UnexpectedIdentity(key))) => {
+            assert_eq!(key, impostor_key)
+        },
+        _ => panic!("connection to an unexpected identity wasn't rejected"),
+    };
+    drop((server, impostor, client, p)); //clean teardown
+}
+
+#[test]
+fn record_and_replay() {
+    use veloren_network::{read_records, RecordEvent};
+    let (_, _) = helper::setup(false, 0);
+    let dir = std::env::temp_dir().join(format!("veloren-network-record-{}", Pid::new()));
+    let r = Arc::new(Runtime::new().unwrap());
+    let n_a = Network::new(Pid::fake(0), &r);
+    let n_b = Network::new(Pid::fake(1), &r);
+    let addr = tcp();
+    r.block_on(n_b.set_record_dir(Some(dir.clone())));
+    r.block_on(n_a.listen(addr.clone())).unwrap();
+    let p_b = r.block_on(n_b.connect(addr)).unwrap();
+    let p_a = r.block_on(n_a.connected()).unwrap();
+    let mut s1_b = r.block_on(p_b.open(4, Promises::ORDERED, 0)).unwrap();
+    let mut s1_a = r.block_on(p_a.opened()).unwrap();
+    let mut s2_a = r.block_on(p_a.open(3, Promises::ORDERED, 0)).unwrap();
+    s1_b.send("Ping").unwrap();
+    assert_eq!(r.block_on(s1_a.recv()), Ok("Ping".to_string()));
+    s1_a.send("Hello World").unwrap();
+    s2_a.send(1337).unwrap();
+    let mut s2_b = r.block_on(p_b.opened()).unwrap();
+    assert_eq!(r.block_on(s1_b.recv()), Ok("Hello World".to_string()));
+    assert_eq!(r.block_on(s2_b.recv()), Ok(1337));
+    drop((s1_a, s2_a, s1_b, s2_b, p_a, p_b));
+    drop((n_a, n_b));
+    // the recording is written in the background
+    std::thread::sleep(std::time::Duration::from_millis(200));
+
+    let file = std::fs::read_dir(&dir)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    let records = read_records(&file).unwrap();
+    let count = |f: fn(&RecordEvent) -> bool| records.iter().filter(|r| f(&r.event)).count();
+    assert_eq!(
+        count(|e| matches!(e, RecordEvent::Opened { local: true, .. })),
+        1
+    );
+    assert_eq!(
+        count(|e| matches!(e, RecordEvent::Opened { local: false, .. })),
+        1
+    );
+    assert_eq!(count(|e| matches!(e, RecordEvent::Sent { .. })), 1);
+    assert_eq!(count(|e| matches!(e, RecordEvent::Received { .. })), 2);
+
+    // the replay sends what the recorded participant received
+    let n_c = Network::new(Pid::fake(2), &r);
+    let p_c = r.block_on(n_c.connect(ProtocolAddr::Replay(file))).unwrap();
+    let mut s1_c = r.block_on(p_c.open(4, Promises::ORDERED, 0)).unwrap();
+    s1_c.send("Ping").unwrap();
+    let mut s2_c = r.block_on(p_c.opened()).unwrap();
+    assert_eq!(r.block_on(s1_c.recv()), Ok("Hello World".to_string()));
+    assert_eq!(r.block_on(s2_c.recv()), Ok(1337));
+    assert_eq!(s2_c.promises(), Promises::ORDERED);
+    drop((s1_c, s2_c, p_c, n_c)); //clean teardown
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
 #[test]
 #[ignore]
 fn tcp_and_udp_2_connections() -> std::result::Result<(), Box<dyn std::error::Error>> {
@@ -127,7 +395,6 @@ fn tcp_and_udp_2_connections() -> std::result::Result<(), Box<dyn std::error::Er
 }
 
 #[test]
-#[ignore]
 fn failed_listen_on_used_ports() -> std::result::Result<(), Box<dyn std::error::Error>> {
     let (_, _) = helper::setup(false, 0);
     let r = Arc::new(Runtime::new().unwrap());
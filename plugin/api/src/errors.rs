@@ -9,6 +9,7 @@ pub enum RetrieveError {
     DataReadError,
     BincodeError(String),
     InvalidType,
+    StorageError(StorageError),
 }
 
 impl core::fmt::Display for RetrieveError {
@@ -35,6 +36,9 @@ impl core::fmt::Display for RetrieveError {
                     "RetrieveError: This type wasn't expected as the result for this Retrieve"
                 )
             },
+            RetrieveError::StorageError(e) => {
+                write!(f, "RetrieveError: {}", e)
+            },
         }
     }
 }
@@ -69,3 +73,32 @@ impl core::fmt::Display for EcsAccessError {
         }
     }
 }
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum StorageError {
+    /// The game has no persistent storage, e.g. on clients
+    NotAvailable,
+    KeyTooLong(usize),
+    /// Storing the value would exceed the entries or bytes a plugin may store
+    QuotaExceeded,
+    BackendError(String),
+}
+
+impl core::fmt::Display for StorageError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            StorageError::NotAvailable => {
+                write!(f, "StorageError persistent storage isn't available")
+            },
+            StorageError::KeyTooLong(max) => {
+                write!(f, "StorageError keys can't be longer than {} bytes", max)
+            },
+            StorageError::QuotaExceeded => {
+                write!(f, "StorageError the plugin exceeded its storage quota")
+            },
+            StorageError::BackendError(e) => {
+                write!(f, "StorageError can't access the storage: {}", e)
+            },
+        }
+    }
+}
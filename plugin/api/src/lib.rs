@@ -1,9 +1,10 @@
 pub extern crate common;
 
-pub use common::comp::Health;
+pub use common::comp::{buff::BuffKind, Health};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 pub use common::{resources::GameMode, uid::Uid};
+pub use vek::{Rgb, Vec3};
 
 mod errors;
 
@@ -29,12 +30,57 @@ pub use event::*;
 /// // You can also use this to only send one action
 /// emit_action(Action::KillEntity(Uid(1)));
 /// ```
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub enum Action {
     ServerClose,
     Print(String),
     PlayerSendMessage(Uid, String),
     KillEntity(Uid),
+    SpawnNpc(NpcSpawn),
+    Teleport(Uid, Vec3<f32>),
+    /// Gives `amount` of the item with this asset specifier, e.g.
+    /// `common.items.food.apple`
+    GiveItem(Uid, String, u32),
+    /// Removes up to `amount` of the item with this asset specifier
+    RemoveItem(Uid, String, u32),
+    /// Applies a buff with a strength for a duration in seconds, or until it
+    /// is removed if the duration is `None`
+    ApplyBuff(Uid, BuffKind, f32, Option<f32>),
+    SetBlock(Vec3<i32>, Block),
+    /// Sends a message to every player
+    BroadcastMessage(String),
+}
+
+/// Describes a non-player character to spawn with [`Action::SpawnNpc`]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct NpcSpawn {
+    pub pos: Vec3<f32>,
+    /// The body like in the `/spawn` command, e.g. `wolf` or `humanoid`
+    pub body: String,
+    /// Defaults to a random name fitting the body
+    pub name: Option<String>,
+    pub alignment: NpcAlignment,
+    /// The asset specifier of the main weapon, defaults to the one of the body
+    pub main_tool: Option<String>,
+    /// Whether the npc moves and fights on its own
+    pub agent: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum NpcAlignment {
+    Wild,
+    Enemy,
+    Npc,
+    Tame,
+    Passive,
+}
+
+/// Some of an item in an inventory
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+pub struct ItemStack {
+    /// The asset specifier of the item, e.g. `common.items.food.apple`
+    pub item: String,
+    pub amount: u32,
 }
 
 /// The [`Retrieve`] enum represents read of the ECS is sync and blocking.
@@ -62,10 +108,24 @@ pub enum Action {
 /// };
 /// // Do something with life
 /// ```
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub enum Retrieve {
     GetPlayerName(Uid),
     GetEntityHealth(Uid),
+    GetEntityPosition(Uid),
+    GetInventory(Uid),
+    /// Entities within a radius around a position, the nearest first
+    GetNearbyEntities(Vec3<f32>, f32),
+    GetBlock(Vec3<i32>),
+    GetTimeOfDay,
+    /// The value stored under a key in the persistent storage of the plugin
+    GetStorage(String),
+    /// Stores a value in the persistent storage of the plugin, replacing the
+    /// previous value of the key
+    SetStorage(String, Vec<u8>),
+    RemoveStorage(String),
+    /// The keys in the persistent storage of the plugin starting with a prefix
+    GetStorageKeys(String),
 }
 
 /// The [`RetrieveResult`] struct is generated while using the `retrieve_action`
@@ -91,6 +151,53 @@ pub enum Retrieve {
 pub enum RetrieveResult {
     GetPlayerName(String),
     GetEntityHealth(Health),
+    GetEntityPosition(Vec3<f32>),
+    GetInventory(Vec<ItemStack>),
+    GetNearbyEntities(Vec<Uid>),
+    GetBlock(Block),
+    /// In in-game seconds, the time of the current day is this modulo
+    /// `24.0 * 3600.0`
+    GetTimeOfDay(f64),
+    GetStorage(Option<Vec<u8>>),
+    SetStorage,
+    /// Whether the key was stored
+    RemoveStorage(bool),
+    GetStorageKeys(Vec<String>),
+}
+
+/// A block of the terrain. Sprites are not exposed to plugins.
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct Block {
+    /// The name of the block kind, e.g. `Rock`, `Wood`, `Air` or `Water`
+    pub kind: String,
+    /// The color of solid blocks
+    pub color: Option<Rgb<u8>>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl From<common::terrain::Block> for Block {
+    fn from(block: common::terrain::Block) -> Self {
+        Self {
+            kind: block.kind().to_string(),
+            color: block.get_color(),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Block {
+    /// Returns `None` if `kind` isn't the name of a block kind
+    pub fn to_terrain_block(&self) -> Option<common::terrain::Block> {
+        use common::terrain::{BlockKind, SpriteKind};
+        use std::convert::TryFrom;
+
+        let kind = BlockKind::try_from(self.kind.as_str()).ok()?;
+        Some(match kind {
+            BlockKind::Air => common::terrain::Block::air(SpriteKind::Empty),
+            BlockKind::Water => common::terrain::Block::water(SpriteKind::Empty),
+            kind => common::terrain::Block::new(kind, self.color.unwrap_or_else(Rgb::zero)),
+        })
+    }
 }
 
 /// This trait is implement by all events and ensure type safety of FFI.
@@ -216,6 +323,253 @@ pub mod event {
         fn get_event_name(&self) -> String { "on_load".to_owned() }
     }
 
+    /// This event is called before the plugin is unloaded, either by an admin
+    /// or because the server shuts down. Your event should be named
+    /// `on_unload`
+    ///
+    /// Actions emitted while handling it are still applied.
+    #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct PluginUnloadEvent {
+        /// Whether a new version of the plugin is loaded in its place
+        pub reloading: bool,
+    }
+
+    impl Event for PluginUnloadEvent {
+        type Response = ();
+
+        fn get_event_name(&self) -> String { "on_unload".to_owned() }
+    }
+
+    /// This event is emitted once every tick, after the systems ran
+    ///
+    /// A plugin that takes too long to handle its events is paused for some
+    /// ticks, so timed mechanics should rely on `time` instead of counting
+    /// ticks.
+    ///
+    /// # Example
+    /// ```ignore
+    /// #[event_handler]
+    /// pub fn on_tick(tick: TickEvent, state: &mut State) {
+    ///     if tick.time - state.last_reward > 60.0 {
+    ///         state.last_reward = tick.time;
+    ///         emit_action(Action::BroadcastMessage("A minute passed".to_owned()));
+    ///     }
+    /// }
+    /// ```
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct TickEvent {
+        /// Seconds since the last tick
+        pub dt: f32,
+        /// Seconds since the start of the game
+        pub time: f64,
+    }
+
+    impl Event for TickEvent {
+        type Response = ();
+
+        fn get_event_name(&self) -> String { "on_tick".to_owned() }
+    }
+
+    /// This is the return type of events that can be cancelled
+    ///
+    /// Variants:
+    ///  - `Cancel` will prevent what caused the event, if any plugin returns
+    ///    it.
+    ///  - `None` will let it happen.
+    #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub enum CancelResult {
+        Cancel,
+        None,
+    }
+
+    impl Default for CancelResult {
+        fn default() -> Self { Self::None }
+    }
+
+    /// This event is called when an entity dies.
+    /// Your event should be named `on_death`
+    ///
+    /// `killer` is the entity that dealt the final damage, if there was one
+    ///
+    /// # Example
+    /// ```ignore
+    /// #[event_handler]
+    /// pub fn on_death(death: EntityDeathEvent) {
+    ///     emit_action(Action::Print(format!("{} died", death.entity)));
+    /// }
+    /// ```
+    #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct EntityDeathEvent {
+        pub entity: Uid,
+        pub killer: Option<Uid>,
+    }
+
+    impl Event for EntityDeathEvent {
+        type Response = ();
+
+        fn get_event_name(&self) -> String { "on_death".to_owned() }
+    }
+
+    /// This event is called when an entity is about to take damage.
+    /// Your event should be named `on_damage`
+    ///
+    /// Returning [`CancelResult::Cancel`] prevents the damage
+    ///
+    /// # Example
+    /// ```ignore
+    /// #[event_handler]
+    /// pub fn on_damage(damage: EntityDamageEvent) -> CancelResult {
+    ///     if damage.attacker.is_none() {
+    ///         CancelResult::Cancel
+    ///     } else {
+    ///         CancelResult::None
+    ///     }
+    /// }
+    /// ```
+    #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct EntityDamageEvent {
+        pub target: Uid,
+        pub attacker: Option<Uid>,
+        /// The health the target will lose
+        pub amount: i32,
+    }
+
+    impl Event for EntityDamageEvent {
+        type Response = CancelResult;
+
+        fn get_event_name(&self) -> String { "on_damage".to_owned() }
+    }
+
+    /// This event is called when a player picks up an item.
+    /// Your event should be named `on_item_pickup`
+    ///
+    /// Returning [`CancelResult::Cancel`] leaves the item where it is
+    #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct ItemPickupEvent {
+        pub player: Player,
+        /// The asset specifier of the item, e.g. `common.items.food.apple`
+        pub item: String,
+        pub amount: u32,
+    }
+
+    impl Event for ItemPickupEvent {
+        type Response = CancelResult;
+
+        fn get_event_name(&self) -> String { "on_item_pickup".to_owned() }
+    }
+
+    /// This event is called when a player drops an item.
+    /// Your event should be named `on_item_drop`
+    ///
+    /// Returning [`CancelResult::Cancel`] keeps the item in the inventory
+    #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct ItemDropEvent {
+        pub player: Player,
+        /// The asset specifier of the item, e.g. `common.items.food.apple`
+        pub item: String,
+        pub amount: u32,
+    }
+
+    impl Event for ItemDropEvent {
+        type Response = CancelResult;
+
+        fn get_event_name(&self) -> String { "on_item_drop".to_owned() }
+    }
+
+    /// This event is called when a player sends a chat message.
+    /// Your event should be named `on_chat_message`
+    ///
+    /// # Example
+    /// ```ignore
+    /// #[event_handler]
+    /// pub fn on_chat_message(chat: ChatMessageEvent) -> ChatMessageResult {
+    ///     ChatMessageResult::Rewrite(chat.message.replace("darn", "****"))
+    /// }
+    /// ```
+    #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct ChatMessageEvent {
+        pub player: Player,
+        pub message: String,
+    }
+
+    impl Event for ChatMessageEvent {
+        type Response = ChatMessageResult;
+
+        fn get_event_name(&self) -> String { "on_chat_message".to_owned() }
+    }
+
+    /// This is the return type of an `on_chat_message` event. See
+    /// [`ChatMessageEvent`]
+    ///
+    /// Variants:
+    ///  - `Cancel` will not send the message.
+    ///  - `Rewrite` will send the given text instead.
+    ///  - `None` will send the message as it is.
+    #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub enum ChatMessageResult {
+        Cancel,
+        Rewrite(String),
+        None,
+    }
+
+    impl Default for ChatMessageResult {
+        fn default() -> Self { Self::None }
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+    pub enum BlockChangeKind {
+        Placed,
+        Broken,
+    }
+
+    /// This event is called when a player places or breaks a block.
+    /// Your event should be named `on_block_change`
+    ///
+    /// `block` is the block that is placed or the one that is broken.
+    /// Returning [`CancelResult::Cancel`] leaves the terrain as it is.
+    #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct BlockChangeEvent {
+        pub player: Player,
+        pub pos: Vec3<i32>,
+        pub block: Block,
+        pub kind: BlockChangeKind,
+    }
+
+    impl Event for BlockChangeEvent {
+        type Response = CancelResult;
+
+        fn get_event_name(&self) -> String { "on_block_change".to_owned() }
+    }
+
+    /// This event is called when a player disconnects.
+    /// Your event should be named `on_leave`
+    #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct PlayerLeaveEvent {
+        pub player: Player,
+        pub player_name: String,
+    }
+
+    impl Event for PlayerLeaveEvent {
+        type Response = ();
+
+        fn get_event_name(&self) -> String { "on_leave".to_owned() }
+    }
+
+    /// This event is called when a player selected a character and enters the
+    /// world with it.
+    /// Your event should be named `on_character_selected`
+    #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct CharacterSelectedEvent {
+        pub player: Player,
+        pub character_id: i64,
+    }
+
+    impl Event for CharacterSelectedEvent {
+        type Response = ();
+
+        fn get_event_name(&self) -> String { "on_character_selected".to_owned() }
+    }
+
     // impl Default for PlayerJoinResult {
     //     fn default() -> Self {
     //         Self::None
@@ -0,0 +1,80 @@
+use veloren_plugin_rt::{
+    api::{event::*, Action, BuffKind, NpcAlignment, NpcSpawn, Vec3},
+    *,
+};
+
+#[event_handler]
+pub fn on_command_gift(command: ChatCommandEvent) -> Result<Vec<String>, String> {
+    let player = command.player;
+    let pos = player
+        .get_entity_position()
+        .map_err(|e| format!("Can't get player position: {:?}", e))?;
+    emit_actions(vec![
+        Action::GiveItem(player.id, "common.items.food.apple".to_owned(), 5),
+        Action::ApplyBuff(player.id, BuffKind::Saturation, 10.0, Some(30.0)),
+        Action::SpawnNpc(NpcSpawn {
+            pos: pos + Vec3::unit_x() * 3.0,
+            body: "wolf".to_owned(),
+            name: Some("Gift Wolf".to_owned()),
+            alignment: NpcAlignment::Tame,
+            main_tool: None,
+            agent: true,
+        }),
+    ]);
+    // Player uids change across restarts, names don't
+    let name = player
+        .get_player_name()
+        .map_err(|e| format!("Can't get player name: {:?}", e))?;
+    let key = format!("gifts.{}", name);
+    let gifts = storage::get::<u32>(&key)
+        .map_err(|e| format!("Can't read gift count: {:?}", e))?
+        .unwrap_or(0)
+        + 1;
+    storage::set(&key, &gifts).map_err(|e| format!("Can't store gift count: {:?}", e))?;
+    Ok(vec![format!("Enjoy your gifts! ({} so far)", gifts)])
+}
+
+#[event_handler]
+pub fn on_command_whereami(command: ChatCommandEvent) -> Result<Vec<String>, String> {
+    let pos = command
+        .player
+        .get_entity_position()
+        .map_err(|e| format!("Can't get player position: {:?}", e))?;
+    let block = get_block(pos.map(|e| e.floor() as i32) - Vec3::unit_z())
+        .map_err(|e| format!("Can't get block: {:?}", e))?;
+    let nearby = get_nearby_entities(pos, 20.0)
+        .map_err(|e| format!("Can't get nearby entities: {:?}", e))?;
+    let time = get_time_of_day().map_err(|e| format!("Can't get time: {:?}", e))?;
+    Ok(vec![format!(
+        "You are standing on {} at {:?} with {} entities around at {:.0}s",
+        block.kind,
+        pos,
+        nearby.len().saturating_sub(1),
+        time % (24.0 * 3600.0)
+    )])
+}
+
+#[global_state]
+#[derive(Default)]
+struct State {
+    last_announcement: f64,
+}
+
+#[event_handler]
+pub fn on_tick(tick: TickEvent, state: &mut State) {
+    if tick.time - state.last_announcement >= 600.0 {
+        state.last_announcement = tick.time;
+        emit_action(Action::BroadcastMessage(
+            "Type /gift to receive a gift!".to_owned(),
+        ));
+    }
+}
+
+#[event_handler]
+pub fn on_unload(unload: PluginUnloadEvent) {
+    if !unload.reloading {
+        emit_action(Action::BroadcastMessage(
+            "Gifts are no longer available".to_owned(),
+        ));
+    }
+}
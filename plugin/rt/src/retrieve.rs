@@ -1,4 +1,4 @@
-use plugin_api::{Health, RetrieveError};
+use plugin_api::{Block, Health, ItemStack, RetrieveError, Uid, Vec3};
 
 use crate::api::{Retrieve, RetrieveResult};
 
@@ -33,3 +33,62 @@ impl GetPlayerName for crate::api::event::Player {
         }
     }
 }
+
+pub trait GetEntityPosition {
+    fn get_entity_position(&self) -> Result<Vec3<f32>, RetrieveError>;
+}
+
+pub trait GetInventory {
+    fn get_inventory(&self) -> Result<Vec<ItemStack>, RetrieveError>;
+}
+
+impl GetEntityPosition for crate::api::event::Player {
+    fn get_entity_position(&self) -> Result<Vec3<f32>, RetrieveError> {
+        if let RetrieveResult::GetEntityPosition(e) =
+            crate::retrieve_action(&Retrieve::GetEntityPosition(self.id))?
+        {
+            Ok(e)
+        } else {
+            Err(RetrieveError::InvalidType)
+        }
+    }
+}
+
+impl GetInventory for crate::api::event::Player {
+    fn get_inventory(&self) -> Result<Vec<ItemStack>, RetrieveError> {
+        if let RetrieveResult::GetInventory(e) =
+            crate::retrieve_action(&Retrieve::GetInventory(self.id))?
+        {
+            Ok(e)
+        } else {
+            Err(RetrieveError::InvalidType)
+        }
+    }
+}
+
+/// Entities within `radius` around `pos`, the nearest first
+pub fn get_nearby_entities(pos: Vec3<f32>, radius: f32) -> Result<Vec<Uid>, RetrieveError> {
+    if let RetrieveResult::GetNearbyEntities(e) =
+        crate::retrieve_action(&Retrieve::GetNearbyEntities(pos, radius))?
+    {
+        Ok(e)
+    } else {
+        Err(RetrieveError::InvalidType)
+    }
+}
+
+pub fn get_block(pos: Vec3<i32>) -> Result<Block, RetrieveError> {
+    if let RetrieveResult::GetBlock(e) = crate::retrieve_action(&Retrieve::GetBlock(pos))? {
+        Ok(e)
+    } else {
+        Err(RetrieveError::InvalidType)
+    }
+}
+
+pub fn get_time_of_day() -> Result<f64, RetrieveError> {
+    if let RetrieveResult::GetTimeOfDay(e) = crate::retrieve_action(&Retrieve::GetTimeOfDay)? {
+        Ok(e)
+    } else {
+        Err(RetrieveError::InvalidType)
+    }
+}
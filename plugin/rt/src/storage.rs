@@ -0,0 +1,60 @@
+//! Persistent key value storage of the plugin, values are kept across server
+//! restarts and only visible to the plugin that stored them.
+//!
+//! The storage is provided by the server once it started, so plugins loaded
+//! with the server can't use it in `on_load` yet.
+//!
+//! # Example
+//! ```ignore
+//! let score = storage::get::<u32>("score.alice")?.unwrap_or(0);
+//! storage::set("score.alice", &(score + 1))?;
+//! ```
+
+use plugin_api::{Retrieve, RetrieveError, RetrieveResult};
+use serde::{de::DeserializeOwned, Serialize};
+
+pub fn get<T: DeserializeOwned>(key: &str) -> Result<Option<T>, RetrieveError> {
+    if let RetrieveResult::GetStorage(e) =
+        crate::retrieve_action(&Retrieve::GetStorage(key.to_owned()))?
+    {
+        e.map(|data| {
+            bincode::deserialize(&data).map_err(|e| RetrieveError::BincodeError(e.to_string()))
+        })
+        .transpose()
+    } else {
+        Err(RetrieveError::InvalidType)
+    }
+}
+
+pub fn set<T: Serialize>(key: &str, value: &T) -> Result<(), RetrieveError> {
+    let data = bincode::serialize(value).map_err(|e| RetrieveError::BincodeError(e.to_string()))?;
+    if let RetrieveResult::SetStorage =
+        crate::retrieve_action(&Retrieve::SetStorage(key.to_owned(), data))?
+    {
+        Ok(())
+    } else {
+        Err(RetrieveError::InvalidType)
+    }
+}
+
+/// Returns whether a value was stored under the key
+pub fn remove(key: &str) -> Result<bool, RetrieveError> {
+    if let RetrieveResult::RemoveStorage(e) =
+        crate::retrieve_action(&Retrieve::RemoveStorage(key.to_owned()))?
+    {
+        Ok(e)
+    } else {
+        Err(RetrieveError::InvalidType)
+    }
+}
+
+/// The stored keys starting with `prefix`, in order
+pub fn keys(prefix: &str) -> Result<Vec<String>, RetrieveError> {
+    if let RetrieveResult::GetStorageKeys(e) =
+        crate::retrieve_action(&Retrieve::GetStorageKeys(prefix.to_owned()))?
+    {
+        Ok(e)
+    } else {
+        Err(RetrieveError::InvalidType)
+    }
+}
@@ -1,3 +1,4 @@
+use common::comp::AdminRole;
 use std::sync::Arc;
 use tokio::runtime::Runtime;
 
@@ -16,7 +17,22 @@ pub fn admin_subcommand(
     match sub_m.subcommand() {
         ("add", Some(sub_m)) => {
             if let Some(username) = sub_m.value_of("username") {
-                server::add_admin(username, &login_provider, editable_settings, data_dir);
+                match sub_m
+                    .value_of("role")
+                    .unwrap_or("admin")
+                    .parse::<AdminRole>()
+                {
+                    Ok(role) => {
+                        server::add_admin(
+                            username,
+                            role,
+                            &login_provider,
+                            editable_settings,
+                            data_dir,
+                        );
+                    },
+                    Err(()) => tracing::error!("Role must be one of moderator, admin or owner"),
+                }
             }
         },
         ("remove", Some(sub_m)) => {
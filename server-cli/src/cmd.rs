@@ -1,15 +1,19 @@
+use common::comp::AdminRole;
 use core::time::Duration;
+use serde::{Deserialize, Serialize};
 use std::sync::mpsc::Sender;
 use tracing::{error, info, warn};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Message {
     AbortShutdown,
     Shutdown { grace_period: Duration },
     Quit,
-    AddAdmin(String),
+    AddAdmin(String, AdminRole),
     RemoveAdmin(String),
     LoadArea(u32),
+    Backup,
 }
 
 struct Command<'a> {
@@ -18,16 +22,19 @@ struct Command<'a> {
     // Whether or not the command splits the arguments on whitespace
     split_spaces: bool,
     args: usize,
+    // How many arguments after the required ones may be given
+    optional_args: usize,
     cmd: fn(Vec<String>, &mut Sender<Message>),
 }
 
 // TODO: maybe we could be using clap here?
-const COMMANDS: [Command; 6] = [
+const COMMANDS: [Command; 7] = [
     Command {
         name: "quit",
         description: "Closes the server",
         split_spaces: true,
         args: 0,
+        optional_args: 0,
         cmd: |_, sender| sender.send(Message::Quit).unwrap(),
     },
     Command {
@@ -36,6 +43,7 @@ const COMMANDS: [Command; 6] = [
                       of seconds before shutting down",
         split_spaces: true,
         args: 1,
+        optional_args: 0,
         cmd: |args, sender| {
             if let Ok(grace_period) = args.first().unwrap().parse::<u64>() {
                 sender
@@ -54,6 +62,7 @@ const COMMANDS: [Command; 6] = [
                       to keep them from despawning",
         split_spaces: true,
         args: 1,
+        optional_args: 0,
         cmd: |args, sender| {
             if let Ok(view_distance) = args.first().unwrap().parse::<u32>() {
                 sender.send(Message::LoadArea(view_distance)).unwrap();
@@ -62,21 +71,39 @@ const COMMANDS: [Command; 6] = [
             }
         },
     },
+    Command {
+        name: "backup",
+        description: "Takes a backup of the character database in the background",
+        split_spaces: true,
+        args: 0,
+        optional_args: 0,
+        cmd: |_, sender| sender.send(Message::Backup).unwrap(),
+    },
     Command {
         name: "abortshutdown",
         description: "Aborts a shutdown if one is in progress",
         split_spaces: false,
         args: 0,
+        optional_args: 0,
         cmd: |_, sender| sender.send(Message::AbortShutdown).unwrap(),
     },
     Command {
         name: "admin",
-        description: "Add or remove an admin via \'admin add/remove <username>\'",
+        description: "Add or remove an admin via \'admin add/remove <username> [role]\'",
         split_spaces: true,
         args: 2,
+        optional_args: 1,
         cmd: |args, sender| match args.get(..2) {
             Some([op, username]) if op == "add" => {
-                sender.send(Message::AddAdmin(username.clone())).unwrap()
+                match args
+                    .get(2)
+                    .map_or(Ok(AdminRole::Admin), |role| role.parse())
+                {
+                    Ok(role) => sender
+                        .send(Message::AddAdmin(username.clone(), role))
+                        .unwrap(),
+                    Err(()) => error!("Role must be one of moderator, admin or owner"),
+                }
             },
             Some([op, username]) if op == "remove" => {
                 sender.send(Message::RemoveAdmin(username.clone())).unwrap()
@@ -90,6 +117,7 @@ const COMMANDS: [Command; 6] = [
         description: "List all command available",
         split_spaces: true,
         args: 0,
+        optional_args: 0,
         cmd: |_, _| {
             info!("===== Help =====");
             for command in COMMANDS.iter() {
@@ -118,23 +146,41 @@ pub fn parse_command(input: &str, msg_s: &mut Sender<Message>) {
                 (0, vec![args.into_iter().collect::<String>()])
             };
 
-            use core::cmp::Ordering;
-            match arg_len.cmp(&cmd.args) {
-                Ordering::Less => error!("{} takes {} arguments", cmd_name, cmd.args),
-                Ordering::Greater => {
-                    warn!("{} only takes {} arguments", cmd_name, cmd.args);
-                    let cmd = cmd.cmd;
-
-                    cmd(args, msg_s)
-                },
-                Ordering::Equal => {
-                    let cmd = cmd.cmd;
+            let max_args = cmd.args + cmd.optional_args;
+            if arg_len < cmd.args {
+                error!("{} takes {} arguments", cmd_name, cmd.args);
+            } else {
+                if arg_len > max_args {
+                    warn!("{} only takes {} arguments", cmd_name, max_args);
+                }
+                let cmd = cmd.cmd;
 
-                    cmd(args, msg_s)
-                },
+                cmd(args, msg_s)
             }
         } else {
             error!("{} not found", cmd_name);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn admin_takes_an_optional_role() {
+        let (mut sender, receiver) = mpsc::channel();
+        parse_command("admin add foo", &mut sender);
+        parse_command("admin add bar moderator", &mut sender);
+        parse_command("admin add", &mut sender);
+        let messages = receiver.try_iter().collect::<Vec<_>>();
+        assert!(matches!(
+            messages.as_slice(),
+            [
+                Message::AddAdmin(foo, AdminRole::Admin),
+                Message::AddAdmin(bar, AdminRole::Moderator),
+            ] if foo == "foo" && bar == "bar"
+        ));
+    }
+}
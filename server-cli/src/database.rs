@@ -0,0 +1,112 @@
+use server::persistence::{
+    backup,
+    character_export::{self, CharacterExport},
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::runtime::Runtime;
+use tracing::{error, info};
+
+pub fn backup_subcommand(data_dir: &Path) {
+    match backup::backup_database(
+        &data_dir.join(server::persistence::PERSISTENCE_DB_DIR),
+        &data_dir.join(server::persistence::BACKUP_DIR),
+    ) {
+        Ok(path) => info!("Database backed up to {}", path.display()),
+        Err(e) => error!(?e, "Database backup failed"),
+    }
+}
+
+pub fn export_character_subcommand(sub_m: &clap::ArgMatches, data_dir: &Path) {
+    let character_id = match sub_m.value_of("character_id").unwrap().parse() {
+        Ok(id) => id,
+        Err(_) => {
+            error!("Character ID must be an integer");
+            return;
+        },
+    };
+    let path = PathBuf::from(sub_m.value_of("file").unwrap());
+
+    let export = match character_export::export_from_database(
+        &data_dir.join(server::persistence::PERSISTENCE_DB_DIR),
+        character_id,
+    ) {
+        Ok(export) => export,
+        Err(e) => {
+            error!(?e, ?character_id, "Failed to export character");
+            return;
+        },
+    };
+
+    let serialized = if is_json(&path) {
+        serde_json::to_string_pretty(&export).map_err(|e| e.to_string())
+    } else {
+        ron::ser::to_string_pretty(&export, ron::ser::PrettyConfig::default())
+            .map_err(|e| e.to_string())
+    };
+    match serialized.and_then(|s| fs::write(&path, s).map_err(|e| e.to_string())) {
+        Ok(()) => info!(
+            "Exported character {} ({}) to {}",
+            export.alias,
+            character_id,
+            path.display()
+        ),
+        Err(e) => error!(?e, ?path, "Failed to write character export"),
+    }
+}
+
+pub fn import_character_subcommand(
+    runtime: Arc<Runtime>,
+    sub_m: &clap::ArgMatches,
+    server_settings: &server::Settings,
+    data_dir: &Path,
+) {
+    let path = PathBuf::from(sub_m.value_of("file").unwrap());
+    let username = sub_m.value_of("username").unwrap();
+
+    let export = match fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|s| {
+            if is_json(&path) {
+                serde_json::from_str::<CharacterExport>(&s).map_err(|e| e.to_string())
+            } else {
+                ron::de::from_str::<CharacterExport>(&s).map_err(|e| e.to_string())
+            }
+        }) {
+        Ok(export) => export,
+        Err(e) => {
+            error!(?e, ?path, "Failed to read character export");
+            return;
+        },
+    };
+
+    let login_provider = server::login_provider::LoginProvider::new(
+        server_settings.auth_server_address.clone(),
+        runtime,
+    );
+    let uuid = match login_provider.username_to_uuid(username) {
+        Ok(uuid) => uuid,
+        Err(e) => {
+            error!(?e, "Could not find uuid for {}", username);
+            return;
+        },
+    };
+
+    match character_export::import_into_database(
+        &data_dir.join(server::persistence::PERSISTENCE_DB_DIR),
+        &uuid.to_string(),
+        &export,
+    ) {
+        Ok(character_id) => info!(
+            "Imported character {} for {} with ID {}",
+            export.alias, username, character_id
+        ),
+        Err(e) => error!(?e, ?path, "Failed to import character"),
+    }
+}
+
+/// Character exports are written as RON unless a `.json` file is given
+fn is_json(path: &Path) -> bool { path.extension().map_or(false, |ext| ext == "json") }
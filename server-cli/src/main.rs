@@ -6,16 +6,26 @@ mod admin;
 /// `server-cli` interface commands not to be confused with the commands sent
 /// from the client to the server
 mod cmd;
+mod database;
 mod logging;
+mod remote;
+mod schedule;
 mod settings;
 mod shutdown_coordinator;
 mod tui_runner;
 mod tuilog;
 
-use crate::{cmd::Message, shutdown_coordinator::ShutdownCoordinator, tui_runner::Tui};
+use crate::{
+    cmd::Message,
+    remote::{Command, PendingCommand, RemoteAdmin, Response},
+    schedule::Announcer,
+    shutdown_coordinator::ShutdownCoordinator,
+    tui_runner::Tui,
+};
 use clap::{App, Arg, SubCommand};
-use common::clock::Clock;
+use common::{clock::Clock, comp::ChatType};
 use common_base::span;
+use common_net::msg::ServerGeneral;
 use core::sync::atomic::{AtomicUsize, Ordering};
 use server::{Event, Input, Server};
 use std::{
@@ -23,7 +33,7 @@ use std::{
     sync::{atomic::AtomicBool, mpsc, Arc},
     time::Duration,
 };
-use tracing::info;
+use tracing::{error, info};
 
 const TPS: u64 = 30;
 
@@ -50,11 +60,16 @@ fn main() -> io::Result<()> {
             SubCommand::with_name("admin")
                 .about("Add or remove admins")
                 .subcommands(vec![
-                    SubCommand::with_name("add").about("Adds an admin").arg(
-                        Arg::with_name("username")
-                            .help("Name of the admin to add")
-                            .required(true),
-                    ),
+                    SubCommand::with_name("add")
+                        .about("Adds an admin or changes their role")
+                        .args(&[
+                            Arg::with_name("username")
+                                .help("Name of the admin to add")
+                                .required(true),
+                            Arg::with_name("role")
+                                .help("Role to give the admin, defaults to admin")
+                                .possible_values(&["moderator", "admin", "owner"]),
+                        ]),
                     SubCommand::with_name("remove")
                         .about("Removes an admin")
                         .arg(
@@ -64,13 +79,43 @@ fn main() -> io::Result<()> {
                         ),
                 ]),
         )
+        .subcommand(
+            SubCommand::with_name("backup")
+                .about("Takes a backup of the character database, even if the server is running"),
+        )
+        .subcommand(
+            SubCommand::with_name("export-character")
+                .about("Exports a character to a RON file, or JSON if the file ends in .json")
+                .args(&[
+                    Arg::with_name("character_id")
+                        .help("ID of the character to export")
+                        .required(true),
+                    Arg::with_name("file")
+                        .help("File to export the character to")
+                        .required(true),
+                ]),
+        )
+        .subcommand(
+            SubCommand::with_name("import-character")
+                .about("Imports an exported character as a new character of a player")
+                .args(&[
+                    Arg::with_name("file")
+                        .help("File containing the exported character")
+                        .required(true),
+                    Arg::with_name("username")
+                        .help("Name of the player to give the character to")
+                        .required(true),
+                ]),
+        )
         .get_matches();
 
     let basic = matches.is_present("basic")
         // Default to basic with these subcommands
         || matches
             .subcommand_name()
-            .filter(|name| ["admin"].contains(name))
+            .filter(|name| {
+                ["admin", "backup", "export-character", "import-character"].contains(name)
+            })
             .is_some();
     let interactive = matches.is_present("interactive");
     let no_auth = matches.is_present("no-auth");
@@ -108,7 +153,6 @@ fn main() -> io::Result<()> {
     // Load server settings
     let mut server_settings = server::Settings::load(&server_data_dir);
     let mut editable_settings = server::EditableSettings::load(&server_data_dir);
-    #[allow(clippy::single_match)] // Note: remove this when there are more subcommands
     match matches.subcommand() {
         ("admin", Some(sub_m)) => {
             admin::admin_subcommand(
@@ -120,6 +164,23 @@ fn main() -> io::Result<()> {
             );
             return Ok(());
         },
+        ("backup", Some(_)) => {
+            database::backup_subcommand(&server_data_dir);
+            return Ok(());
+        },
+        ("export-character", Some(sub_m)) => {
+            database::export_character_subcommand(sub_m, &server_data_dir);
+            return Ok(());
+        },
+        ("import-character", Some(sub_m)) => {
+            database::import_character_subcommand(
+                runtime,
+                sub_m,
+                &server_settings,
+                &server_data_dir,
+            );
+            return Ok(());
+        },
         _ => {},
     }
 
@@ -159,6 +220,22 @@ fn main() -> io::Result<()> {
     );
 
     let mut shutdown_coordinator = ShutdownCoordinator::new(Arc::clone(&sigusr1_signal));
+    schedule::validate_restart_times(&settings.daily_restart_times);
+    let mut announcer = Announcer::new(&settings.announcements);
+
+    let remote_admin = match (
+        settings.remote_admin_address,
+        settings.remote_admin_token.clone(),
+    ) {
+        (Some(addr), Some(token)) => RemoteAdmin::run(addr, token)
+            .map_err(|e| error!(?e, ?addr, "Failed to start remote administration"))
+            .ok(),
+        (Some(_), None) => {
+            error!("Remote administration is disabled as no remote_admin_token is set");
+            None
+        },
+        (None, _) => None,
+    };
 
     // Set up an fps clock
     let mut clock = Clock::new(Duration::from_secs_f64(1.0 / TPS as f64));
@@ -171,6 +248,8 @@ fn main() -> io::Result<()> {
             break;
         }
 
+        announcer.tick(&mut server);
+
         let events = server
             .tick(Input::default(), clock.dt())
             .expect("Failed to tick server");
@@ -188,33 +267,33 @@ fn main() -> io::Result<()> {
 
         if let Some(tui) = tui.as_ref() {
             match tui.msg_r.try_recv() {
-                Ok(msg) => match msg {
-                    Message::AbortShutdown => shutdown_coordinator.abort_shutdown(&mut server),
-                    Message::Shutdown { grace_period } => {
-                        // TODO: The TUI parser doesn't support quoted strings so it is not
-                        // currently possible to provide a shutdown reason
-                        // from the console.
-                        let message = "The server is shutting down".to_owned();
-                        shutdown_coordinator.initiate_shutdown(&mut server, grace_period, message);
-                    },
-                    Message::Quit => {
-                        info!("Closing the server");
+                Ok(msg) => {
+                    if handle_message(&mut server, &mut shutdown_coordinator, msg) {
                         break;
-                    },
-                    Message::AddAdmin(username) => {
-                        server.add_admin(&username);
-                    },
-                    Message::RemoveAdmin(username) => {
-                        server.remove_admin(&username);
-                    },
-                    Message::LoadArea(view_distance) => {
-                        server.create_centered_persister(view_distance);
-                    },
+                    }
                 },
                 Err(mpsc::TryRecvError::Empty) | Err(mpsc::TryRecvError::Disconnected) => {},
             }
         }
 
+        if let Some(remote_admin) = remote_admin.as_ref() {
+            let mut quit = false;
+            for PendingCommand {
+                command,
+                response_s,
+            } in remote_admin.command_r.try_iter()
+            {
+                let (response, stop) =
+                    run_remote_command(&mut server, &mut shutdown_coordinator, command);
+                // The client may have disconnected in the meantime
+                let _ = response_s.send(response);
+                quit |= stop;
+            }
+            if quit {
+                break;
+            }
+        }
+
         drop(guard);
         // Wait for the next tick.
         clock.tick();
@@ -222,5 +301,72 @@ fn main() -> io::Result<()> {
         common_base::tracy_client::finish_continuous_frame!();
     }
 
+    // Save all characters now, rather than losing the progress since the last
+    // periodic save
+    info!("Saving all characters");
+    server.save_all_characters();
+
     Ok(())
 }
+
+/// Handles a command of the server-cli console, returning whether the server
+/// should stop
+fn handle_message(
+    server: &mut Server,
+    shutdown_coordinator: &mut ShutdownCoordinator,
+    msg: Message,
+) -> bool {
+    match msg {
+        Message::AbortShutdown => shutdown_coordinator.abort_shutdown(server),
+        Message::Shutdown { grace_period } => {
+            // TODO: The TUI parser doesn't support quoted strings so it is not
+            // currently possible to provide a shutdown reason
+            // from the console.
+            let message = "The server is shutting down".to_owned();
+            shutdown_coordinator.initiate_shutdown(server, grace_period, message);
+        },
+        Message::Quit => {
+            info!("Closing the server");
+            return true;
+        },
+        Message::AddAdmin(username, role) => {
+            server.add_admin(&username, role);
+        },
+        Message::RemoveAdmin(username) => {
+            server.remove_admin(&username);
+        },
+        Message::LoadArea(view_distance) => {
+            server.create_centered_persister(view_distance);
+        },
+        Message::Backup => {
+            info!("Starting database backup");
+            server.backup_database();
+        },
+    }
+    false
+}
+
+/// Runs a command received through remote administration, returning the
+/// response to it and whether the server should stop
+fn run_remote_command(
+    server: &mut Server,
+    shutdown_coordinator: &mut ShutdownCoordinator,
+    command: Command,
+) -> (Response, bool) {
+    match command {
+        Command::Message(msg) => (
+            Response::Ok(Vec::new()),
+            handle_message(server, shutdown_coordinator, msg),
+        ),
+        Command::ChatCommand(cmd) => (Response::Ok(server.execute_console_command(&cmd)), false),
+        Command::ListPlayers => (Response::Players(server.player_names()), false),
+        Command::Kick { player, reason } => {
+            let cmd = format!("kick {} {}", player, reason);
+            (Response::Ok(server.execute_console_command(&cmd)), false)
+        },
+        Command::Broadcast(msg) => {
+            server.notify_players(ServerGeneral::server_msg(ChatType::Meta, msg));
+            (Response::Ok(Vec::new()), false)
+        },
+    }
+}
@@ -0,0 +1,293 @@
+//! Remote administration of the server, for servers running without a
+//! terminal (e.g. in containers).
+//!
+//! Clients connect over TCP and send one JSON encoded [`Request`] per line,
+//! each of which is answered with one JSON encoded [`Response`] line. Requests
+//! are only executed if they carry the token from the settings.
+//!
+//! The token is sent in plain text, so the interface refuses to listen on
+//! anything but a loopback address. Remote machines should reach it through an
+//! encrypted tunnel (e.g. SSH port forwarding) instead.
+
+use crate::cmd::Message;
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+    time::Duration,
+};
+use tracing::{debug, info, warn};
+
+/// Longest request line that is accepted, longer lines close the connection
+const MAX_LINE: u64 = 64 * 1024;
+/// Connections beyond this are closed right away
+const MAX_CONNECTIONS: usize = 4;
+/// Connections are closed after this many requests with a wrong token
+const MAX_INVALID_TOKENS: u32 = 3;
+/// Delay before answering a request with a wrong token, doubled for every
+/// further wrong token on the same connection
+const INVALID_TOKEN_DELAY: Duration = Duration::from_secs(1);
+/// Shortest token that is accepted, so that it can't be guessed
+const MIN_TOKEN_LEN: usize = 16;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Request {
+    pub token: String,
+    pub command: Command,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Command {
+    /// One of the commands of the server-cli console
+    Message(Message),
+    /// A chat command, run as the server console with owner permissions
+    ChatCommand(String),
+    ListPlayers,
+    Kick {
+        player: String,
+        reason: String,
+    },
+    Broadcast(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Response {
+    /// The command was run, producing the given output
+    Ok(Vec<String>),
+    Players(Vec<String>),
+    Error(String),
+}
+
+/// A command received from a client, waiting to be run by the main loop
+pub struct PendingCommand {
+    pub command: Command,
+    pub response_s: mpsc::Sender<Response>,
+}
+
+pub struct RemoteAdmin {
+    pub command_r: mpsc::Receiver<PendingCommand>,
+    pub addr: SocketAddr,
+}
+
+impl RemoteAdmin {
+    /// Starts listening for remote administration connections on `addr`,
+    /// which has to be a loopback address. `token` has to be at least
+    /// [`MIN_TOKEN_LEN`] bytes long.
+    pub fn run(addr: SocketAddr, token: String) -> io::Result<Self> {
+        if token.len() < MIN_TOKEN_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "the remote administration token has to be at least {} bytes long",
+                    MIN_TOKEN_LEN
+                ),
+            ));
+        }
+        if !addr.ip().is_loopback() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "remote administration only listens on loopback addresses, as the token is not \
+                 encrypted",
+            ));
+        }
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let (command_s, command_r) = mpsc::channel();
+        let connections = Arc::new(AtomicUsize::new(0));
+
+        thread::Builder::new()
+            .name("remote-admin".to_owned())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    match stream {
+                        Ok(stream) => {
+                            if connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                                connections.fetch_sub(1, Ordering::SeqCst);
+                                warn!("Too many remote admin connections, closing new connection");
+                                continue;
+                            }
+                            let connections = Arc::clone(&connections);
+                            let command_s = command_s.clone();
+                            let token = token.clone();
+                            thread::spawn(move || {
+                                if let Err(e) = handle_connection(stream, &token, &command_s) {
+                                    debug!(?e, "Remote admin connection closed");
+                                }
+                                connections.fetch_sub(1, Ordering::SeqCst);
+                            });
+                        },
+                        Err(e) => warn!(?e, "Failed to accept remote admin connection"),
+                    }
+                }
+            })?;
+
+        info!(?addr, "Remote administration is enabled");
+        Ok(Self { command_r, addr })
+    }
+}
+
+fn handle_connection(
+    stream: TcpStream,
+    token: &str,
+    command_s: &mpsc::Sender<PendingCommand>,
+) -> io::Result<()> {
+    let peer = stream.peer_addr()?;
+    info!(?peer, "Remote admin connected");
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let mut invalid_tokens = 0;
+
+    loop {
+        let mut line = String::new();
+        // Limit how much is buffered before the token has been checked
+        if (&mut reader).take(MAX_LINE).read_line(&mut line)? == 0 {
+            break;
+        }
+        if !line.ends_with('\n') && line.len() as u64 >= MAX_LINE {
+            warn!(
+                ?peer,
+                "Remote admin request is too long, closing connection"
+            );
+            write_response(&mut writer, &Response::Error("Request too long".to_owned()))?;
+            break;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) if tokens_match(&request.token, token) => {
+                info!(?peer, ?request.command, "Running remote admin command");
+                let (response_s, response_r) = mpsc::channel();
+                let pending = PendingCommand {
+                    command: request.command,
+                    response_s,
+                };
+                if command_s.send(pending).is_err() {
+                    // The server has stopped
+                    return Ok(());
+                }
+                response_r.recv().unwrap_or_else(|_| {
+                    Response::Error("The server stopped before running the command".to_owned())
+                })
+            },
+            Ok(_) => {
+                warn!(?peer, "Remote admin request with an invalid token");
+                // Slow down guessing of the token
+                thread::sleep(INVALID_TOKEN_DELAY * 2u32.pow(invalid_tokens));
+                invalid_tokens += 1;
+                if invalid_tokens >= MAX_INVALID_TOKENS {
+                    write_response(&mut writer, &Response::Error("Invalid token".to_owned()))?;
+                    break;
+                }
+                Response::Error("Invalid token".to_owned())
+            },
+            Err(e) => Response::Error(format!("Invalid request: {}", e)),
+        };
+        write_response(&mut writer, &response)?;
+    }
+
+    Ok(())
+}
+
+fn write_response(writer: &mut impl Write, response: &Response) -> io::Result<()> {
+    let mut response = serde_json::to_string(response)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    response.push('\n');
+    writer.write_all(response.as_bytes())
+}
+
+/// Compares tokens without returning early, so that how long the comparison
+/// takes doesn't reveal how much of the token was guessed correctly
+fn tokens_match(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: &str = "0123456789abcdef";
+
+    fn send(stream: &mut BufReader<TcpStream>, request: &Request) -> Response {
+        let mut line = serde_json::to_string(request).unwrap();
+        line.push('\n');
+        stream.get_mut().write_all(line.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_line(&mut response).unwrap();
+        serde_json::from_str(&response).unwrap()
+    }
+
+    #[test]
+    fn only_loopback_addresses_are_allowed() {
+        assert!(RemoteAdmin::run("0.0.0.0:0".parse().unwrap(), TOKEN.to_owned()).is_err());
+    }
+
+    #[test]
+    fn short_tokens_are_refused() {
+        let addr = "127.0.0.1:0".parse().unwrap();
+        assert!(RemoteAdmin::run(addr, String::new()).is_err());
+        assert!(RemoteAdmin::run(addr, TOKEN[1..].to_owned()).is_err());
+        assert!(RemoteAdmin::run(addr, TOKEN.to_owned()).is_ok());
+    }
+
+    #[test]
+    fn overlong_requests_are_refused() {
+        let remote = RemoteAdmin::run("127.0.0.1:0".parse().unwrap(), TOKEN.to_owned()).unwrap();
+        let mut stream = BufReader::new(TcpStream::connect(remote.addr).unwrap());
+
+        stream
+            .get_mut()
+            .write_all(&vec![b'a'; MAX_LINE as usize])
+            .unwrap();
+        let mut response = String::new();
+        stream.read_line(&mut response).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Response>(&response).unwrap(),
+            Response::Error("Request too long".to_owned())
+        );
+    }
+
+    #[test]
+    fn requests_need_the_token() {
+        let remote = RemoteAdmin::run("127.0.0.1:0".parse().unwrap(), TOKEN.to_owned()).unwrap();
+        let mut stream = BufReader::new(TcpStream::connect(remote.addr).unwrap());
+
+        // Stand in for the main loop of the server
+        let command_r = remote.command_r;
+        thread::spawn(move || {
+            for pending in command_r {
+                let response = match pending.command {
+                    Command::ListPlayers => Response::Players(vec!["Player".to_owned()]),
+                    _ => Response::Ok(Vec::new()),
+                };
+                pending.response_s.send(response).unwrap();
+            }
+        });
+
+        let mut request = Request {
+            token: "wrong".to_owned(),
+            command: Command::ListPlayers,
+        };
+        assert_eq!(
+            send(&mut stream, &request),
+            Response::Error("Invalid token".to_owned())
+        );
+
+        request.token = TOKEN.to_owned();
+        assert_eq!(
+            send(&mut stream, &request),
+            Response::Players(vec!["Player".to_owned()])
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use common::comp::chat::ChatType;
+use common_net::msg::ServerGeneral;
+use serde::{Deserialize, Serialize};
+use server::Server;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// A time of day in UTC
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DailyTime {
+    pub hour: u8,
+    pub minute: u8,
+}
+
+impl DailyTime {
+    /// Seconds since midnight, or `None` if this isn't a valid time of day
+    fn secs_of_day(self) -> Option<u64> {
+        (self.hour < 24 && self.minute < 60)
+            .then(|| u64::from(self.hour) * 3600 + u64::from(self.minute) * 60)
+    }
+}
+
+/// A message that is broadcast to all players every `interval_secs`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Announcement {
+    pub message: String,
+    pub interval_secs: u32,
+}
+
+/// Calculates the time until the next of the given times of day, starting
+/// `secs_of_day` seconds after midnight. A time that is reached right now is
+/// treated as being a day away, as it has already been dealt with.
+fn time_until_next(times: &[DailyTime], secs_of_day: u64) -> Option<Duration> {
+    times
+        .iter()
+        .filter_map(|time| time.secs_of_day())
+        .map(
+            |secs| match (secs + SECS_PER_DAY - secs_of_day) % SECS_PER_DAY {
+                0 => SECS_PER_DAY,
+                until => until,
+            },
+        )
+        .min()
+        .map(Duration::from_secs)
+}
+
+/// The time until the next of the scheduled restarts, and the UNIX time at
+/// which it happens
+pub fn next_restart(times: &[DailyTime]) -> Option<(Duration, u64)> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    time_until_next(times, now % SECS_PER_DAY).map(|until| (until, now + until.as_secs()))
+}
+
+/// Warns about any invalid entries in the restart schedule
+pub fn validate_restart_times(times: &[DailyTime]) {
+    for time in times.iter().filter(|time| time.secs_of_day().is_none()) {
+        warn!(?time, "Ignoring invalid scheduled restart time");
+    }
+}
+
+/// Broadcasts the announcements from the settings at their intervals
+pub struct Announcer {
+    announcements: Vec<(Announcement, Instant)>,
+}
+
+impl Announcer {
+    pub fn new(announcements: &[Announcement]) -> Self {
+        let now = Instant::now();
+        Self {
+            announcements: announcements
+                .iter()
+                .filter(|announcement| {
+                    if announcement.interval_secs == 0 {
+                        warn!(
+                            ?announcement.message,
+                            "Ignoring announcement with an interval of 0 seconds"
+                        );
+                    }
+                    announcement.interval_secs > 0
+                })
+                .map(|announcement| {
+                    let interval = Duration::from_secs(u64::from(announcement.interval_secs));
+                    (announcement.clone(), now + interval)
+                })
+                .collect(),
+        }
+    }
+
+    /// Called once per tick to send any announcements that are due
+    pub fn tick(&mut self, server: &mut Server) {
+        let now = Instant::now();
+        for (announcement, next) in self.announcements.iter_mut() {
+            if *next <= now {
+                info!("Announcement: {}", announcement.message);
+                server.notify_players(ServerGeneral::server_msg(
+                    ChatType::Meta,
+                    announcement.message.clone(),
+                ));
+                *next = now + Duration::from_secs(u64::from(announcement.interval_secs));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_restart_wraps_around_midnight() {
+        let times = [DailyTime { hour: 4, minute: 0 }, DailyTime {
+            hour: 16,
+            minute: 30,
+        }];
+        let at = |hour: u64, minute: u64| hour * 3600 + minute * 60;
+
+        assert_eq!(
+            time_until_next(&times, at(3, 0)),
+            Some(Duration::from_secs(3600))
+        );
+        assert_eq!(
+            time_until_next(&times, at(12, 0)),
+            Some(Duration::from_secs(at(4, 30)))
+        );
+        assert_eq!(
+            time_until_next(&times, at(23, 0)),
+            Some(Duration::from_secs(at(5, 0)))
+        );
+        // A restart that is due right now has already happened
+        assert_eq!(
+            time_until_next(&times, at(4, 0)),
+            Some(Duration::from_secs(at(12, 30)))
+        );
+    }
+
+    #[test]
+    fn invalid_times_are_ignored() {
+        let times = [DailyTime {
+            hour: 24,
+            minute: 0,
+        }];
+        assert_eq!(time_until_next(&times, 0), None);
+    }
+}
@@ -1,5 +1,6 @@
+use crate::schedule::{Announcement, DailyTime};
 use serde::{Deserialize, Serialize};
-use std::{fs, path::PathBuf};
+use std::{fs, net::SocketAddr, path::PathBuf};
 use tracing::warn;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -7,6 +8,23 @@ use tracing::warn;
 pub struct Settings {
     pub update_shutdown_grace_period_secs: u32,
     pub update_shutdown_message: String,
+    /// Times of day, in UTC, at which the server restarts every day
+    pub daily_restart_times: Vec<DailyTime>,
+    /// How long before a scheduled restart players start being warned about it
+    pub scheduled_restart_grace_period_secs: u32,
+    pub scheduled_restart_message: String,
+    /// How many seconds before any shutdown players are warned about it, in
+    /// addition to the warning when the shutdown is initiated
+    pub shutdown_warning_secs: Vec<u32>,
+    /// Messages that are broadcast to all players at a fixed interval
+    pub announcements: Vec<Announcement>,
+    /// Address to listen on for remote administration, disabled if `None`.
+    /// Anyone who can connect to it and knows the token can run any command,
+    /// and the token isn't encrypted, so this has to be a loopback address.
+    pub remote_admin_address: Option<SocketAddr>,
+    /// Token that remote administration requests have to include, at least
+    /// 16 bytes long
+    pub remote_admin_token: Option<String>,
 }
 
 impl Default for Settings {
@@ -14,6 +32,13 @@ impl Default for Settings {
         Self {
             update_shutdown_grace_period_secs: 120,
             update_shutdown_message: "The server is restarting for an update".to_owned(),
+            daily_restart_times: Vec::new(),
+            scheduled_restart_grace_period_secs: 600,
+            scheduled_restart_message: "The server is restarting".to_owned(),
+            shutdown_warning_secs: vec![300, 240, 180, 120, 90, 60, 30, 10, 5, 4, 3, 2, 1],
+            announcements: Vec::new(),
+            remote_admin_address: None,
+            remote_admin_token: None,
         }
     }
 }
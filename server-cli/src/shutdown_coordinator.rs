@@ -1,4 +1,4 @@
-use crate::settings::Settings;
+use crate::{schedule, settings::Settings};
 use common::comp::chat::ChatType;
 use common_net::msg::ServerGeneral;
 use server::Server;
@@ -13,14 +13,16 @@ use std::{
 use tracing::{error, info};
 
 /// Coordinates the shutdown procedure for the server, which can be initiated by
-/// either the TUI console interface or by sending the server the SIGUSR1 signal
-/// which indicates the server is restarting due to an update.
+/// either the TUI console interface, the restart schedule in the settings or by
+/// sending the server the SIGUSR1 signal which indicates the server is
+/// restarting due to an update.
 pub(crate) struct ShutdownCoordinator {
-    /// The instant that the last shutdown message was sent, used for
-    /// calculating when to send the next shutdown message
-    last_shutdown_msg: Instant,
-    /// The interval that shutdown warning messages are sent at
-    msg_interval: Duration,
+    /// The time until shutdown when the last shutdown message was sent, used
+    /// for calculating when to send the next shutdown message
+    last_warning: Duration,
+    /// The UNIX time of the last scheduled restart that was initiated, so that
+    /// it isn't initiated again if it gets aborted
+    initiated_restart: Option<u64>,
     /// The instant that shudown was initiated at
     shutdown_initiated_at: Option<Instant>,
     /// The period to wait before shutting down after shutdown is initiated
@@ -35,8 +37,8 @@ pub(crate) struct ShutdownCoordinator {
 impl ShutdownCoordinator {
     pub fn new(sigusr1_signal: Arc<AtomicBool>) -> Self {
         Self {
-            last_shutdown_msg: Instant::now(),
-            msg_interval: Duration::from_secs(30),
+            last_warning: Duration::from_secs(0),
+            initiated_restart: None,
             shutdown_initiated_at: None,
             shutdown_grace_period: Duration::from_secs(0),
             shutdown_message: String::new(),
@@ -84,6 +86,8 @@ impl ShutdownCoordinator {
         // Check whether SIGUSR1 has been set
         self.check_sigusr1_signal(server, settings);
 
+        self.check_restart_schedule(server, settings);
+
         // If a shutdown is in progress, check whether it's time to send another warning
         // message or shut down if the grace period has expired.
         if let Some(shutdown_initiated_at) = self.shutdown_initiated_at {
@@ -92,18 +96,17 @@ impl ShutdownCoordinator {
                 return true;
             }
 
-            // In the last 10 seconds start sending messages every 1 second
+            // Send another shutdown warning message to all connected clients if one
+            // of the warning times has been passed since the last one
             if let Some(time_until_shutdown) = self.time_until_shutdown() {
-                if time_until_shutdown <= Duration::from_secs(10) {
-                    self.msg_interval = Duration::from_secs(1);
+                if warning_due(
+                    &settings.shutdown_warning_secs,
+                    self.last_warning,
+                    time_until_shutdown,
+                ) {
+                    self.send_shutdown_msg(server);
                 }
             }
-
-            // Send another shutdown warning message to all connected clients if
-            // msg_interval has expired
-            if self.last_shutdown_msg + self.msg_interval <= Instant::now() {
-                self.send_shutdown_msg(server);
-            }
         }
 
         false
@@ -128,6 +131,26 @@ impl ShutdownCoordinator {
         }
     }
 
+    /// Initiates a shutdown once the next scheduled restart is within its grace
+    /// period
+    fn check_restart_schedule(&mut self, server: &mut Server, settings: &Settings) {
+        if self.shutdown_initiated_at.is_some() {
+            return;
+        }
+        if let Some((time_until_restart, restart_at)) =
+            schedule::next_restart(&settings.daily_restart_times)
+        {
+            let grace_period =
+                Duration::from_secs(u64::from(settings.scheduled_restart_grace_period_secs));
+            if time_until_restart <= grace_period && self.initiated_restart != Some(restart_at) {
+                info!("Initiating scheduled restart");
+                self.initiated_restart = Some(restart_at);
+                let message = settings.scheduled_restart_message.to_owned();
+                self.initiate_shutdown(server, time_until_restart, message);
+            }
+        }
+    }
+
     /// Constructs a formatted shutdown message and sends it to all connected
     /// clients
     fn send_shutdown_msg(&mut self, server: &mut Server) {
@@ -138,7 +161,7 @@ impl ShutdownCoordinator {
                 ShutdownCoordinator::duration_to_text(time_until_shutdown)
             );
             ShutdownCoordinator::send_msg(server, msg);
-            self.last_shutdown_msg = Instant::now();
+            self.last_warning = time_until_shutdown;
         }
     }
 
@@ -176,3 +199,35 @@ impl ShutdownCoordinator {
         text
     }
 }
+
+/// Whether any of the warning times (in seconds before shutdown) has been
+/// reached since the last warning was sent
+fn warning_due(
+    warning_secs: &[u32],
+    last_warning: Duration,
+    time_until_shutdown: Duration,
+) -> bool {
+    warning_secs.iter().any(|secs| {
+        let warning = Duration::from_secs(u64::from(*secs));
+        time_until_shutdown <= warning && warning < last_warning
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warnings_are_sent_once_per_warning_time() {
+        let warnings = [60, 10, 1];
+        let secs = Duration::from_secs_f32;
+
+        // Initial warning was sent with 120 seconds left
+        assert!(!warning_due(&warnings, secs(120.0), secs(60.5)));
+        assert!(warning_due(&warnings, secs(120.0), secs(59.9)));
+        assert!(!warning_due(&warnings, secs(59.9), secs(30.0)));
+        // Passing several warning times at once only warns once
+        assert!(warning_due(&warnings, secs(30.0), secs(0.5)));
+        assert!(!warning_due(&warnings, secs(0.5), secs(0.1)));
+    }
+}
@@ -1,5 +1,7 @@
-use crate::persistence::character_loader::CharacterLoader;
-use common::comp::{inventory::loadout_builder::LoadoutBuilder, Body, Inventory, Item, Stats};
+use crate::persistence::{character_loader::CharacterLoader, PersistedState};
+use common::comp::{
+    inventory::loadout_builder::LoadoutBuilder, Bank, Body, Inventory, Item, Stats,
+};
 use specs::{Entity, ReadExpect};
 
 const VALID_STARTER_ITEMS: [&str; 6] = [
@@ -52,6 +54,13 @@ pub fn create_character(
         entity,
         player_uuid,
         character_alias,
-        (body, stats, inventory, waypoint),
+        (
+            body,
+            stats,
+            inventory,
+            Bank::default(),
+            waypoint,
+            PersistedState::default(),
+        ),
     );
 }
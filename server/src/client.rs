@@ -92,9 +92,9 @@ impl Client {
                     | ServerGeneral::Outcomes(_)
                     | ServerGeneral::Knockback(_)
                     | ServerGeneral::UpdatePendingTrade(_, _)
-                    | ServerGeneral::FinishedTrade(_) => {
-                        self.in_game_stream.lock().unwrap().send(g)
-                    },
+                    | ServerGeneral::FinishedTrade(_)
+                    | ServerGeneral::Mail(_)
+                    | ServerGeneral::BankUpdate(_) => self.in_game_stream.lock().unwrap().send(g),
                     //Ingame related, terrain
                     ServerGeneral::TerrainChunkUpdate { .. }
                     | ServerGeneral::TerrainBlockUpdates(_) => {
@@ -108,6 +108,7 @@ impl Client {
                     | ServerGeneral::TimeOfDay(_)
                     | ServerGeneral::EntitySync(_)
                     | ServerGeneral::CompSync(_)
+                    | ServerGeneral::PhysicsDelta(_)
                     | ServerGeneral::CreateEntity(_)
                     | ServerGeneral::DeleteEntity(_)
                     | ServerGeneral::Disconnect(_)
@@ -163,9 +164,9 @@ impl Client {
                     | ServerGeneral::Knockback(_)
                     | ServerGeneral::SiteEconomy(_)
                     | ServerGeneral::UpdatePendingTrade(_, _)
-                    | ServerGeneral::FinishedTrade(_) => {
-                        PreparedMsg::new(2, &g, &self.in_game_stream)
-                    },
+                    | ServerGeneral::FinishedTrade(_)
+                    | ServerGeneral::Mail(_)
+                    | ServerGeneral::BankUpdate(_) => PreparedMsg::new(2, &g, &self.in_game_stream),
                     //Ingame related, terrain
                     ServerGeneral::TerrainChunkUpdate { .. }
                     | ServerGeneral::TerrainBlockUpdates(_) => {
@@ -179,6 +180,7 @@ impl Client {
                     | ServerGeneral::TimeOfDay(_)
                     | ServerGeneral::EntitySync(_)
                     | ServerGeneral::CompSync(_)
+                    | ServerGeneral::PhysicsDelta(_)
                     | ServerGeneral::CreateEntity(_)
                     | ServerGeneral::DeleteEntity(_)
                     | ServerGeneral::Disconnect(_)
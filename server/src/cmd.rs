@@ -3,10 +3,14 @@
 //! `CHAT_COMMANDS` and provide a handler function.
 
 use crate::{
-    settings::{BanRecord, EditableSetting},
+    chunk_generator::ChunkGenerator,
+    movement_validator::MovementValidation,
+    settings::{Admins, BanRecord, EditableSetting, UnbanRecord},
+    terrain_persistence::TerrainPersistence,
     Server, SpawnPoint, StateExt,
 };
-use chrono::{NaiveTime, Timelike};
+use authc::Uuid;
+use chrono::{NaiveTime, Timelike, Utc};
 use common::{
     cmd::{ChatCommand, CHAT_COMMANDS, CHAT_SHORTCUTS},
     comp::{
@@ -21,7 +25,8 @@ use common::{
     event::{EventBus, ServerEvent},
     npc::{self, get_npc_name},
     resources::TimeOfDay,
-    terrain::{Block, BlockKind, SpriteKind, TerrainChunkSize},
+    slowjob::SlowJobPool,
+    terrain::{Block, BlockKind, SpriteKind, TerrainChunkSize, TerrainGrid},
     uid::Uid,
     vol::RectVolSize,
     Damage, DamageSource, Explosion, LoadoutBuilder, RadiusEffect,
@@ -30,9 +35,10 @@ use common_net::{
     msg::{DisconnectReason, Notification, PlayerListUpdate, ServerGeneral},
     sync::WorldSyncExt,
 };
+use network::ProtocolAddr;
 use rand::Rng;
 use specs::{Builder, Entity as EcsEntity, Join, WorldExt};
-use std::{convert::TryFrom, time::Duration};
+use std::{convert::TryFrom, sync::Arc, time::Duration};
 use vek::*;
 use world::util::Sampler;
 
@@ -46,7 +52,7 @@ pub trait ChatCommandExt {
 impl ChatCommandExt for ChatCommand {
     #[allow(clippy::needless_return)] // TODO: Pending review in #587
     fn execute(&self, server: &mut Server, entity: EcsEntity, args: String) {
-        if self.needs_admin() && !server.entity_is_admin(entity) {
+        if !server.entity_can_use_command(entity, self) {
             server.notify_client(
                 entity,
                 ServerGeneral::server_msg(
@@ -110,8 +116,10 @@ fn get_handler(cmd: &ChatCommand) -> CommandHandler {
         ChatCommand::Motd => handle_motd,
         ChatCommand::Object => handle_object,
         ChatCommand::Players => handle_players,
+        ChatCommand::Plugin => handle_plugin,
         ChatCommand::Region => handle_region,
         ChatCommand::RemoveLights => handle_remove_lights,
+        ChatCommand::ResetTerrain => handle_reset_terrain,
         ChatCommand::Safezone => handle_safezone,
         ChatCommand::Say => handle_say,
         ChatCommand::SetMotd => handle_set_motd,
@@ -123,6 +131,7 @@ fn get_handler(cmd: &ChatCommand) -> CommandHandler {
         ChatCommand::Tp => handle_tp,
         ChatCommand::Unban => handle_unban,
         ChatCommand::Version => handle_version,
+        ChatCommand::Violations => handle_violations,
         ChatCommand::Waypoint => handle_waypoint,
         ChatCommand::Whitelist => handle_whitelist,
         ChatCommand::World => handle_world,
@@ -136,12 +145,17 @@ fn handle_drop_all(
     _args: String,
     _action: &ChatCommand,
 ) {
-    let pos = server
+    let pos = match server
         .state
         .ecs()
         .read_storage::<comp::Pos>()
         .get(client)
-        .cloned();
+        .cloned()
+    {
+        Some(pos) => pos,
+        // The console has nowhere to drop items
+        None => return,
+    };
 
     let mut items = Vec::new();
     if let Some(mut inventory) = server
@@ -155,7 +169,6 @@ fn handle_drop_all(
 
     let mut rng = rand::thread_rng();
 
-    let pos = pos.expect("expected pos for entity using dropall command");
     for item in items {
         let vel = Vec3::new(rng.gen_range(-0.1..0.1), rng.gen_range(-0.1..0.1), 0.5);
 
@@ -1141,6 +1154,122 @@ fn handle_players(
     );
 }
 
+#[cfg(feature = "plugins")]
+fn handle_plugin(
+    server: &mut Server,
+    client: EcsEntity,
+    _target: EcsEntity,
+    args: String,
+    action: &ChatCommand,
+) {
+    use common::resources::GameMode;
+
+    let (plugin_action, name) = scan_fmt_some!(&args, &action.arg_fmt(), String, String);
+    let game_mode = *server.state.ecs().read_resource::<GameMode>();
+    let result = match (plugin_action.as_deref(), name) {
+        (Some("list"), _) => server.state.with_plugins(|plugin_mgr, _| {
+            Ok(plugin_mgr.plugins().fold(
+                format!("{} loaded plugins:", plugin_mgr.plugins().count()),
+                |s, plugin| {
+                    format!(
+                        "{}\n{} ({} modules){}",
+                        s,
+                        plugin.name(),
+                        plugin.module_count(),
+                        if plugin.is_disabled() {
+                            " [disabled]"
+                        } else {
+                            ""
+                        }
+                    )
+                },
+            ))
+        }),
+        (Some("load"), Some(name)) => server.state.with_plugins(|plugin_mgr, ecs_world| {
+            plugin_mgr
+                .load(ecs_world, game_mode, &name)
+                .map(|()| format!("Loaded plugin file '{}'", name))
+        }),
+        (Some("unload"), Some(name)) => server.state.with_plugins(|plugin_mgr, ecs_world| {
+            plugin_mgr
+                .unload(ecs_world, &name)
+                .map(|()| format!("Unloaded plugin '{}'", name))
+        }),
+        (Some("reload"), Some(name)) => server.state.with_plugins(|plugin_mgr, ecs_world| {
+            plugin_mgr
+                .reload(ecs_world, game_mode, &name)
+                .map(|()| format!("Reloaded plugin '{}'", name))
+        }),
+        _ => {
+            server.notify_client(
+                client,
+                ServerGeneral::server_msg(ChatType::CommandError, action.help_string()),
+            );
+            return;
+        },
+    };
+    let msg = match result {
+        Ok(msg) => ServerGeneral::server_msg(ChatType::CommandInfo, msg),
+        Err(e) => ServerGeneral::server_msg(ChatType::CommandError, format!("{:?}", e)),
+    };
+    server.notify_client(client, msg);
+}
+
+#[cfg(not(feature = "plugins"))]
+fn handle_plugin(
+    server: &mut Server,
+    client: EcsEntity,
+    _target: EcsEntity,
+    _args: String,
+    _action: &ChatCommand,
+) {
+    server.notify_client(
+        client,
+        ServerGeneral::server_msg(
+            ChatType::CommandError,
+            "This server was built without plugin support",
+        ),
+    );
+}
+
+fn handle_violations(
+    server: &mut Server,
+    client: EcsEntity,
+    _target: EcsEntity,
+    _args: String,
+    _action: &ChatCommand,
+) {
+    let ecs = server.state.ecs();
+
+    let entity_tuples = (
+        &ecs.read_storage::<comp::Player>(),
+        &ecs.read_storage::<MovementValidation>(),
+    );
+    let mut msg = String::from("Players with rejected movement:");
+    let mut count = 0;
+    for (player, validation) in entity_tuples.join() {
+        if validation.total_violations() > 0 {
+            msg.push_str(&format!(
+                "\n{}: {} (speed {}, flight {}, collision {})",
+                player.alias,
+                validation.total_violations(),
+                validation.speed_violations,
+                validation.flight_violations,
+                validation.collision_violations,
+            ));
+            count += 1;
+        }
+    }
+    if count == 0 {
+        msg = String::from("No player movement has been rejected.");
+    }
+
+    server.notify_client(
+        client,
+        ServerGeneral::server_msg(ChatType::CommandInfo, msg),
+    );
+}
+
 fn handle_build(
     server: &mut Server,
     client: EcsEntity,
@@ -1191,7 +1320,7 @@ fn handle_help(
     } else {
         let mut message = String::new();
         for cmd in CHAT_COMMANDS.iter() {
-            if !cmd.needs_admin() || server.entity_is_admin(client) {
+            if server.entity_can_use_command(client, cmd) {
                 message += &cmd.help_string();
                 message += "\n";
             }
@@ -1531,7 +1660,6 @@ fn handle_waypoint(
     }
 }
 
-#[allow(clippy::useless_conversion)] // TODO: Pending review in #587
 fn handle_adminify(
     server: &mut Server,
     client: EcsEntity,
@@ -1539,7 +1667,18 @@ fn handle_adminify(
     args: String,
     action: &ChatCommand,
 ) {
-    if let Ok(alias) = scan_fmt!(&args, &action.arg_fmt(), String) {
+    if let (Some(alias), role_opt) = scan_fmt_some!(&args, &action.arg_fmt(), String, String) {
+        let role = match role_opt.map(|role| role.parse::<comp::AdminRole>()) {
+            Some(Ok(role)) => Some(role),
+            Some(Err(())) => {
+                server.notify_client(
+                    client,
+                    ServerGeneral::server_msg(ChatType::CommandError, action.help_string()),
+                );
+                return;
+            },
+            None => None,
+        };
         let ecs = server.state.ecs();
         let opt_player = (&ecs.entities(), &ecs.read_storage::<comp::Player>())
             .join()
@@ -1547,22 +1686,41 @@ fn handle_adminify(
             .map(|(entity, _)| entity);
         match opt_player {
             Some(player) => {
-                let is_admin = if server
-                    .state
-                    .read_component_copied::<comp::Admin>(player)
-                    .is_some()
-                {
-                    ecs.write_storage::<comp::Admin>().remove(player);
-                    false
-                } else {
-                    ecs.write_storage().insert(player, comp::Admin).is_ok()
+                let client_role = server.entity_admin_role(client);
+                let current_role = server.entity_admin_role(player);
+                // Without a role argument the command toggles between admin and no role
+                let new_role = match (role, current_role) {
+                    (Some(role), _) => Some(role),
+                    (None, Some(_)) => None,
+                    (None, None) => Some(comp::AdminRole::Admin),
                 };
-                // Update player list so the player shows up as admin in client chat.
+                // Nobody can hand out a role above their own or change the role of someone
+                // ranked above them
+                if new_role > client_role || current_role > client_role {
+                    server.notify_client(
+                        client,
+                        ServerGeneral::server_msg(
+                            ChatType::CommandError,
+                            "You can't change the role of players ranked above you or give out \
+                             roles above your own",
+                        ),
+                    );
+                    return;
+                }
+                match new_role {
+                    Some(role) => {
+                        let _ = ecs.write_storage().insert(player, comp::Admin(role));
+                    },
+                    None => {
+                        ecs.write_storage::<comp::Admin>().remove(player);
+                    },
+                }
+                // Update player list so the player shows up with their role in client chat.
                 let msg = ServerGeneral::PlayerListUpdate(PlayerListUpdate::Admin(
                     *ecs.read_storage::<Uid>()
                         .get(player)
                         .expect("Player should have uid"),
-                    is_admin,
+                    new_role,
                 ));
                 server.state.notify_players(msg);
             },
@@ -2288,6 +2446,77 @@ fn handle_remove_lights(
     );
 }
 
+fn handle_reset_terrain(
+    server: &mut Server,
+    client: EcsEntity,
+    target: EcsEntity,
+    args: String,
+    action: &ChatCommand,
+) {
+    // Every chunk in the area is regenerated on the main thread, so keep it small
+    let radius = scan_fmt_some!(&args, &action.arg_fmt(), i32)
+        .unwrap_or(1)
+        .max(0)
+        .min(8);
+    let pos = match server.state.read_component_copied::<comp::Pos>(target) {
+        Some(pos) => pos,
+        None => {
+            server.notify_client(
+                client,
+                ServerGeneral::server_msg(ChatType::CommandError, "You have no position."),
+            );
+            return;
+        },
+    };
+
+    let ecs = server.state.ecs();
+    let mut terrain_persistence = match ecs.try_fetch_mut::<TerrainPersistence>() {
+        Some(terrain_persistence) => terrain_persistence,
+        None => {
+            server.notify_client(
+                client,
+                ServerGeneral::server_msg(
+                    ChatType::CommandError,
+                    "Terrain persistence is not enabled on this server.",
+                ),
+            );
+            return;
+        },
+    };
+    let mut chunk_generator = ecs.write_resource::<ChunkGenerator>();
+    let slow_jobs = ecs.read_resource::<SlowJobPool>();
+    let terrain = ecs.read_resource::<TerrainGrid>();
+
+    let center = TerrainGrid::chunk_key(pos.0.map(|e| e.floor() as i32));
+    let mut count = 0;
+    for x in -radius..=radius {
+        for y in -radius..=radius {
+            let key = center + Vec2::new(x, y);
+            terrain_persistence.clear_chunk(key);
+            // Regenerate loaded chunks so that players see the pristine terrain right away
+            if terrain.get_key(key).is_some() {
+                chunk_generator.generate_chunk(
+                    None,
+                    key,
+                    &slow_jobs,
+                    Arc::clone(&server.world),
+                    server.index.clone(),
+                );
+            }
+            count += 1;
+        }
+    }
+    drop((terrain_persistence, chunk_generator, slow_jobs, terrain));
+
+    server.notify_client(
+        client,
+        ServerGeneral::server_msg(
+            ChatType::CommandInfo,
+            format!("Reset the terrain of {} chunks.", count),
+        ),
+    );
+}
+
 fn handle_sudo(
     server: &mut Server,
     client: EcsEntity,
@@ -2299,7 +2528,18 @@ fn handle_sudo(
         scan_fmt_some!(&args, &action.arg_fmt(), String, String, String)
     {
         let cmd_args = cmd_args.unwrap_or_else(|| String::from(""));
-        if let Ok(action) = cmd.parse() {
+        if let Ok(action) = cmd.parse::<ChatCommand>() {
+            // Sudo must not let anyone run commands their role doesn't allow
+            if !server.entity_can_use_command(client, &action) {
+                server.notify_client(
+                    client,
+                    ServerGeneral::server_msg(
+                        ChatType::CommandError,
+                        format!("You don't have permission to use '/{}'.", action.keyword()),
+                    ),
+                );
+                return;
+            }
             let ecs = server.state.ecs();
             let entity_opt = (&ecs.entities(), &ecs.read_storage::<comp::Player>())
                 .join()
@@ -2448,6 +2688,19 @@ fn handle_kick(
             .map(|(entity, _)| entity);
 
         if let Some(target_player) = target_player_opt {
+            if !outranks(
+                server.entity_admin_role(client),
+                server.entity_admin_role(target_player),
+            ) {
+                server.notify_client(
+                    client,
+                    ServerGeneral::server_msg(
+                        ChatType::CommandError,
+                        "You can't kick players ranked the same as or above you",
+                    ),
+                );
+                return;
+            }
             kick_player(server, target_player, &reason);
             server.notify_client(
                 client,
@@ -2483,10 +2736,28 @@ fn handle_ban(
     args: String,
     action: &ChatCommand,
 ) {
-    if let (Some(target_alias), reason_opt) =
-        scan_fmt_some!(&args, &action.arg_fmt(), String, String)
+    if let (Some(target_alias), duration_opt, reason_opt) =
+        scan_fmt_some!(&args, &action.arg_fmt(), String, String, String)
     {
-        let reason = reason_opt.unwrap_or_default();
+        // The duration is optional, so if it can't be parsed it is the first word of
+        // the reason
+        let (duration, reason) = match duration_opt {
+            Some(duration) => match parse_duration(&duration) {
+                Some(Ok(duration)) => (Some(duration), reason_opt.unwrap_or_default()),
+                Some(Err(e)) => {
+                    server.notify_client(
+                        client,
+                        ServerGeneral::server_msg(ChatType::CommandError, e),
+                    );
+                    return;
+                },
+                None => (None, match reason_opt {
+                    Some(reason) => format!("{} {}", duration, reason),
+                    None => duration,
+                }),
+            },
+            None => (None, reason_opt.unwrap_or_default()),
+        };
         let uuid_result = server
             .state
             .ecs()
@@ -2494,7 +2765,34 @@ fn handle_ban(
             .username_to_uuid(&target_alias);
 
         if let Ok(uuid) = uuid_result {
-            if server.editable_settings().banlist.contains_key(&uuid) {
+            let online_role = {
+                let ecs = server.state.ecs();
+                (
+                    &ecs.read_storage::<comp::Player>(),
+                    &ecs.read_storage::<comp::Admin>(),
+                )
+                    .join()
+                    .find(|(player, _)| player.uuid() == uuid)
+                    .map(|(_, admin)| admin.0)
+            };
+            let target_role = target_role(&server.editable_settings().admins, &uuid, online_role);
+            if !outranks(server.entity_admin_role(client), target_role) {
+                server.notify_client(
+                    client,
+                    ServerGeneral::server_msg(
+                        ChatType::CommandError,
+                        "You can't ban players ranked the same as or above you",
+                    ),
+                );
+                return;
+            }
+            let now = Utc::now();
+            if server
+                .editable_settings()
+                .banlist
+                .active_ban(&uuid, now)
+                .is_some()
+            {
                 server.notify_client(
                     client,
                     ServerGeneral::server_msg(
@@ -2503,32 +2801,75 @@ fn handle_ban(
                     ),
                 )
             } else {
+                let ecs = server.state.ecs();
+                let banned_by = ecs
+                    .read_storage::<comp::Player>()
+                    .get(client)
+                    .map(|player| player.uuid());
+                let target_player_opt = (&ecs.entities(), &ecs.read_storage::<comp::Player>())
+                    .join()
+                    .find(|(_, player)| player.alias == target_alias)
+                    .map(|(entity, _)| entity);
+                let ip_addr = target_player_opt
+                    .and_then(|target_player| {
+                        ecs.read_storage::<Client>()
+                            .get(target_player)
+                            .and_then(|client| client.participant.as_ref())
+                            .map(|participant| participant.remote_address().clone())
+                    })
+                    .and_then(|addr| match addr {
+                        ProtocolAddr::Tcp(addr)
+                        | ProtocolAddr::Udp(addr)
+                        | ProtocolAddr::Quic(addr) => Some(addr.ip()),
+                        ProtocolAddr::Mpsc(_) | ProtocolAddr::Replay(_) => None,
+                    });
+                let end_date = match duration {
+                    Some(duration) => match now.checked_add_signed(duration) {
+                        Some(end_date) => Some(end_date),
+                        None => {
+                            server.notify_client(
+                                client,
+                                ServerGeneral::server_msg(
+                                    ChatType::CommandError,
+                                    "Ban duration is out of range".to_string(),
+                                ),
+                            );
+                            return;
+                        },
+                    },
+                    None => None,
+                };
+
                 server
                     .editable_settings_mut()
                     .banlist
                     .edit(server.data_dir().as_ref(), |b| {
-                        b.insert(uuid, BanRecord {
+                        b.ban(uuid, BanRecord {
                             username_when_banned: target_alias.clone(),
                             reason: reason.clone(),
+                            banned_by,
+                            banned_at: Some(now),
+                            end_date,
+                            ip_addr,
                         });
                     });
                 server.notify_client(
                     client,
-                    ServerGeneral::server_msg(
-                        ChatType::CommandInfo,
-                        format!(
+                    ServerGeneral::server_msg(ChatType::CommandInfo, match end_date {
+                        Some(end_date) => format!(
+                            "Added {} to the banlist until {} UTC with reason: {}",
+                            target_alias,
+                            end_date.format("%Y-%m-%d %H:%M"),
+                            reason
+                        ),
+                        None => format!(
                             "Added {} to the banlist with reason: {}",
                             target_alias, reason
                         ),
-                    ),
+                    }),
                 );
 
                 // If the player is online kick them
-                let ecs = server.state.ecs();
-                let target_player_opt = (&ecs.entities(), &ecs.read_storage::<comp::Player>())
-                    .join()
-                    .find(|(_, player)| player.alias == target_alias)
-                    .map(|(entity, _)| entity);
                 if let Some(target_player) = target_player_opt {
                     kick_player(server, target_player, &reason);
                 }
@@ -2565,18 +2906,35 @@ fn handle_unban(
             .username_to_uuid(&username);
 
         if let Ok(uuid) = uuid_result {
-            server
-                .editable_settings_mut()
-                .banlist
-                .edit(server.data_dir().as_ref(), |b| {
-                    b.remove(&uuid);
-                });
+            let unbanned_by = server
+                .state
+                .ecs()
+                .read_storage::<comp::Player>()
+                .get(client)
+                .map(|player| player.uuid());
+            let unbanned =
+                server
+                    .editable_settings_mut()
+                    .banlist
+                    .edit(server.data_dir().as_ref(), |b| {
+                        b.unban(&uuid, UnbanRecord {
+                            unbanned_by,
+                            unbanned_at: Utc::now(),
+                        })
+                    });
             server.notify_client(
                 client,
-                ServerGeneral::server_msg(
-                    ChatType::CommandInfo,
-                    format!("{} was successfully unbanned", username),
-                ),
+                if unbanned {
+                    ServerGeneral::server_msg(
+                        ChatType::CommandInfo,
+                        format!("{} was successfully unbanned", username),
+                    )
+                } else {
+                    ServerGeneral::server_msg(
+                        ChatType::CommandError,
+                        format!("{} is not banned", username),
+                    )
+                },
             );
         } else {
             server.notify_client(
@@ -2594,3 +2952,104 @@ fn handle_unban(
         );
     }
 }
+
+/// Whether a player with the role `issuer` may kick or ban a player with the
+/// role `target`, which needs a role ranked above the one of the target
+fn outranks(issuer: Option<comp::AdminRole>, target: Option<comp::AdminRole>) -> bool {
+    target.map_or(true, |target| {
+        issuer.map_or(false, |issuer| issuer > target)
+    })
+}
+
+/// The role of a player who might be offline: the one stored in the admin
+/// list, or the one they currently have if that ranks higher
+fn target_role(
+    admins: &Admins,
+    uuid: &Uuid,
+    online_role: Option<comp::AdminRole>,
+) -> Option<comp::AdminRole> {
+    online_role.max(admins.get(uuid).copied())
+}
+
+/// Longest duration a timed ban can have, anything longer should be a
+/// permanent ban
+const MAX_BAN_DURATION_SECS: i64 = 100 * 365 * 24 * 60 * 60;
+
+/// Parses durations like `30m`, `12h` or `7d`, supported units are seconds,
+/// minutes, hours, days and weeks. Returns `None` if `duration` isn't a
+/// duration at all and an error if it is one but longer than
+/// [`MAX_BAN_DURATION_SECS`]
+fn parse_duration(duration: &str) -> Option<Result<chrono::Duration, String>> {
+    let unit_start = duration.find(|c: char| !c.is_ascii_digit())?;
+    if unit_start == 0 {
+        return None;
+    }
+    let unit_secs: i64 = match &duration[unit_start..] {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+    let out_of_range = || {
+        format!(
+            "Ban duration {} is too long, the maximum is {} days",
+            duration,
+            MAX_BAN_DURATION_SECS / (24 * 60 * 60)
+        )
+    };
+    Some(
+        duration[..unit_start]
+            .parse::<i64>()
+            .ok()
+            .and_then(|amount| amount.checked_mul(unit_secs))
+            .filter(|secs| *secs <= MAX_BAN_DURATION_SECS)
+            .map(chrono::Duration::seconds)
+            .ok_or_else(out_of_range),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use comp::AdminRole;
+
+    #[test]
+    fn kicking_needs_a_higher_role() {
+        assert!(outranks(Some(AdminRole::Moderator), None));
+        assert!(outranks(Some(AdminRole::Owner), Some(AdminRole::Admin)));
+        assert!(!outranks(
+            Some(AdminRole::Moderator),
+            Some(AdminRole::Moderator)
+        ));
+        assert!(!outranks(
+            Some(AdminRole::Moderator),
+            Some(AdminRole::Admin)
+        ));
+        assert!(!outranks(Some(AdminRole::Admin), Some(AdminRole::Owner)));
+        assert!(!outranks(None, Some(AdminRole::Moderator)));
+    }
+
+    #[test]
+    fn banning_offline_players_uses_the_admin_list() {
+        let owner = Uuid::from_u128(1);
+        let player = Uuid::from_u128(2);
+        let mut admins = Admins::default();
+        admins.insert(owner, AdminRole::Owner);
+
+        // offline
+        let role = target_role(&admins, &owner, None);
+        assert_eq!(role, Some(AdminRole::Owner));
+        assert!(!outranks(Some(AdminRole::Moderator), role));
+        assert!(!outranks(Some(AdminRole::Admin), role));
+        assert!(outranks(
+            Some(AdminRole::Admin),
+            target_role(&admins, &player, None)
+        ));
+
+        // online with a role given by /adminify
+        let role = target_role(&admins, &player, Some(AdminRole::Admin));
+        assert!(!outranks(Some(AdminRole::Moderator), role));
+    }
+}
@@ -0,0 +1,24 @@
+//! Chat commands run by the operator of the server rather than by a player,
+//! e.g. through the remote administration interface of `server-cli`.
+
+use specs::Component;
+use specs_idvs::IdvStorage;
+
+/// Marks the entity that console commands are executed as. The entity has
+/// no body in the world and the owner role, and the messages that commands
+/// send to it are collected here instead of being sent to a client.
+#[derive(Debug, Default)]
+pub struct Console {
+    output: Vec<String>,
+}
+
+impl Console {
+    pub fn push_output(&mut self, msg: String) { self.output.push(msg); }
+
+    /// Takes the messages sent to the console since the last call
+    pub fn take_output(&mut self) -> Vec<String> { std::mem::take(&mut self.output) }
+}
+
+impl Component for Console {
+    type Storage = IdvStorage<Self>;
+}
@@ -0,0 +1,271 @@
+use crate::client::Client;
+use common::{
+    comp::{
+        self,
+        inventory::{
+            bank::stack_or_swap,
+            slot::{BankSlotId, Slot},
+            InvSlot,
+        },
+        item::MaterialStatManifest,
+        Item,
+    },
+    util::find_dist,
+    vol::ReadVol,
+};
+use common_net::msg::ServerGeneral;
+use common_sys::state::State;
+use specs::{Component, Entity as EcsEntity, WorldExt};
+use specs_idvs::IdvStorage;
+use tracing::{debug, error};
+use vek::Vec3;
+
+use super::inventory_manip::within_pickup_range;
+
+/// Marks an entity as having its bank open at the bank sprite at `pos`.
+///
+/// Access is checked again on every bank operation, so walking away from the
+/// bank or the bank being destroyed closes it.
+#[derive(Debug)]
+pub struct BankAccess {
+    pub pos: Vec3<i32>,
+}
+
+impl Component for BankAccess {
+    type Storage = IdvStorage<Self>;
+}
+
+fn bank_in_range(
+    state: &State,
+    pos: Vec3<i32>,
+    entity_cylinder: Option<find_dist::Cylinder>,
+) -> bool {
+    state
+        .terrain()
+        .get(pos)
+        .map_or(false, |block| block.is_bank())
+        && within_pickup_range(entity_cylinder, || {
+            Some(find_dist::Cube {
+                min: pos.as_(),
+                side_length: 1.0,
+            })
+        })
+}
+
+/// Sends the bank of an entity to its client, or `None` if it doesn't have
+/// access to it
+fn send_bank(state: &State, entity: EcsEntity) {
+    let ecs = state.ecs();
+    let bank = if ecs.read_storage::<BankAccess>().contains(entity) {
+        ecs.read_storage::<comp::Bank>().get(entity).cloned()
+    } else {
+        None
+    };
+    if let Some(client) = ecs.read_storage::<Client>().get(entity) {
+        client.send_fallible(ServerGeneral::BankUpdate(bank));
+    }
+}
+
+/// Checks that an entity may still use its bank, revoking access otherwise
+fn check_access(
+    state: &State,
+    entity: EcsEntity,
+    entity_cylinder: Option<find_dist::Cylinder>,
+) -> bool {
+    let pos = match state.ecs().read_storage::<BankAccess>().get(entity) {
+        Some(access) => access.pos,
+        None => return false,
+    };
+    if bank_in_range(state, pos, entity_cylinder) {
+        true
+    } else {
+        debug!(
+            ?entity_cylinder,
+            "Bank is no longer in range, bank pos: {}", pos
+        );
+        state.ecs().write_storage::<BankAccess>().remove(entity);
+        send_bank(state, entity);
+        false
+    }
+}
+
+/// Items holding other items can't be stored in the bank, since it has no
+/// way of showing their contents
+fn is_bankable(slot: &InvSlot) -> bool {
+    slot.as_ref()
+        .map_or(true, |item| item.slots().iter().all(Option::is_none))
+}
+
+/// Puts `item` into `slot` if it is empty or holds a stack the item can be
+/// added to, handing the item back otherwise
+fn insert_or_stack(slot: &mut InvSlot, item: Item) -> Result<(), Item> {
+    match slot {
+        None => {
+            *slot = Some(item);
+            Ok(())
+        },
+        Some(slot_item) if slot_item == &item && item.is_stackable() => {
+            slot_item.increase_amount(item.amount()).map_err(|_| item)
+        },
+        Some(_) => Err(item),
+    }
+}
+
+fn slot_mut<'a>(
+    bank: &'a mut comp::Bank,
+    inventory: &'a mut comp::Inventory,
+    slot: Slot,
+) -> Option<&'a mut InvSlot> {
+    match slot {
+        Slot::Bank(slot) => bank.slot_mut(slot),
+        Slot::Inventory(slot) => inventory.slot_mut(slot),
+        Slot::Equip(_) => None,
+    }
+}
+
+pub fn handle_open_bank(
+    state: &State,
+    entity: EcsEntity,
+    pos: Vec3<i32>,
+    entity_cylinder: Option<find_dist::Cylinder>,
+) {
+    if !bank_in_range(state, pos, entity_cylinder) {
+        debug!(
+            ?entity_cylinder,
+            "Failed to open bank as not within range, bank pos: {}", pos
+        );
+        return;
+    }
+    if !state.ecs().read_storage::<comp::Bank>().contains(entity) {
+        return;
+    }
+    if let Err(e) = state
+        .ecs()
+        .write_storage::<BankAccess>()
+        .insert(entity, BankAccess { pos })
+    {
+        error!(?e, ?entity, "Failed to give entity access to its bank");
+        return;
+    }
+    send_bank(state, entity);
+}
+
+pub fn handle_close_bank(state: &State, entity: EcsEntity) {
+    state.ecs().write_storage::<BankAccess>().remove(entity);
+}
+
+/// Moves items between two slots, at least one of which is in the bank
+pub fn handle_bank_swap(
+    state: &mut State,
+    entity: EcsEntity,
+    a: Slot,
+    b: Slot,
+    entity_cylinder: Option<find_dist::Cylinder>,
+) {
+    if !check_access(state, entity, entity_cylinder) {
+        return;
+    }
+
+    {
+        let mut banks = state.ecs().write_storage::<comp::Bank>();
+        let mut inventories = state.ecs().write_storage::<comp::Inventory>();
+        let (bank, mut inventory) = match (banks.get_mut(entity), inventories.get_mut(entity)) {
+            (Some(bank), Some(inventory)) => (bank, inventory),
+            _ => return,
+        };
+
+        match (a, b) {
+            (Slot::Bank(a), Slot::Bank(b)) => bank.move_slot(a, b),
+            (Slot::Inventory(inv_slot), Slot::Bank(bank_slot)) => {
+                swap_with_inventory(bank, &mut inventory, bank_slot, inv_slot, false)
+            },
+            (Slot::Bank(bank_slot), Slot::Inventory(inv_slot)) => {
+                swap_with_inventory(bank, &mut inventory, bank_slot, inv_slot, true)
+            },
+            _ => debug!("Can't swap equipped items with the bank"),
+        }
+    }
+
+    state.write_component(
+        entity,
+        comp::InventoryUpdate::new(comp::InventoryUpdateEvent::Swapped),
+    );
+    send_bank(state, entity);
+}
+
+fn swap_with_inventory(
+    bank: &mut comp::Bank,
+    inventory: &mut comp::Inventory,
+    bank_slot: BankSlotId,
+    inv_slot: comp::slot::InvSlotId,
+    from_bank: bool,
+) {
+    if let (Some(bank_slot), Some(inv_slot)) =
+        (bank.slot_mut(bank_slot), inventory.slot_mut(inv_slot))
+    {
+        // Whatever is in the bank already was checked when it was put there
+        if !is_bankable(inv_slot) {
+            return;
+        }
+        if from_bank {
+            stack_or_swap(bank_slot, inv_slot);
+        } else {
+            stack_or_swap(inv_slot, bank_slot);
+        }
+    }
+}
+
+/// Moves half of a stack between two slots, at least one of which is in the
+/// bank
+pub fn handle_bank_split_swap(
+    state: &mut State,
+    entity: EcsEntity,
+    slot: Slot,
+    target: Slot,
+    entity_cylinder: Option<find_dist::Cylinder>,
+) {
+    if !check_access(state, entity, entity_cylinder) {
+        return;
+    }
+
+    {
+        let msm = state.ecs().read_resource::<MaterialStatManifest>();
+        let mut banks = state.ecs().write_storage::<comp::Bank>();
+        let mut inventories = state.ecs().write_storage::<comp::Inventory>();
+        let (bank, mut inventory) = match (banks.get_mut(entity), inventories.get_mut(entity)) {
+            (Some(bank), Some(inventory)) => (bank, inventory),
+            _ => return,
+        };
+
+        let item = match slot {
+            Slot::Bank(slot) => bank.take_half(slot, &msm),
+            Slot::Inventory(slot) if inventory.slot(slot).map_or(false, is_bankable) => {
+                inventory.take_half(slot, &msm)
+            },
+            _ => None,
+        };
+
+        if let Some(item) = item {
+            let result = match slot_mut(bank, &mut inventory, target) {
+                Some(target) => insert_or_stack(target, item),
+                None => Err(item),
+            };
+            // Put the items back where they came from if the target can't hold them
+            if let Err(item) = result {
+                let returned = match slot_mut(bank, &mut inventory, slot) {
+                    Some(slot) => insert_or_stack(slot, item),
+                    None => Err(item),
+                };
+                if let Err(item) = returned {
+                    error!(?item, "Failed to return split items to their slot");
+                }
+            }
+        }
+    }
+
+    state.write_component(
+        entity,
+        comp::InventoryUpdate::new(comp::InventoryUpdateEvent::Swapped),
+    );
+    send_bank(state, entity);
+}
@@ -13,8 +13,10 @@ use common::{
     },
     outcome::Outcome,
     rtsim::RtSimEntity,
+    uid::Uid,
     util::Dir,
 };
+use plugin_api::event::{CharacterSelectedEvent, Player};
 use specs::{Builder, Entity as EcsEntity, WorldExt};
 use std::time::Duration;
 use vek::{Rgb, Vec3};
@@ -25,6 +27,12 @@ pub fn handle_initialize_character(
     character_id: CharacterId,
 ) {
     server.state.initialize_character_data(entity, character_id);
+    if let Some(uid) = server.state.read_component_copied::<Uid>(entity) {
+        server.state.plugin_event(&CharacterSelectedEvent {
+            player: Player { id: uid },
+            character_id,
+        });
+    }
 }
 
 pub fn handle_loaded_character_data(
@@ -4,6 +4,7 @@ use crate::{
         biped_large, quadruped_low, quadruped_medium, quadruped_small, skills::SkillGroupKind,
         theropod, PhysicsState,
     },
+    movement_validator::MovementValidation,
     rtsim::RtSim,
     Server, SpawnPoint, StateExt,
 };
@@ -31,6 +32,7 @@ use common::{
 use common_net::{msg::ServerGeneral, sync::WorldSyncExt};
 use common_sys::state::BlockChange;
 use hashbrown::HashSet;
+use plugin_api::event::{CancelResult, EntityDamageEvent, EntityDeathEvent};
 use rand::prelude::*;
 use specs::{join::Join, saveload::MarkerAllocator, Entity as EcsEntity, WorldExt};
 use tracing::error;
@@ -55,6 +57,25 @@ pub fn handle_poise(
 
 pub fn handle_damage(server: &Server, entity: EcsEntity, change: HealthChange) {
     let ecs = &server.state.ecs();
+    if change.amount < 0 {
+        if let Some(target) = ecs.uid_from_entity(entity) {
+            let attacker = match change.cause {
+                HealthSource::Damage { by, .. } => by,
+                _ => None,
+            };
+            if server
+                .state
+                .plugin_event(&EntityDamageEvent {
+                    target,
+                    attacker,
+                    amount: -change.amount,
+                })
+                .contains(&CancelResult::Cancel)
+            {
+                return;
+            }
+        }
+    }
     if let Some(mut health) = ecs.write_storage::<Health>().get_mut(entity) {
         health.change_by(change);
     }
@@ -79,6 +100,9 @@ pub fn handle_knockback(server: &Server, entity: EcsEntity, impulse: Vec3<f32>)
         if let Some(vel) = velocities.get_mut(entity) {
             vel.0 += impulse;
         }
+        if let Some(validation) = ecs.write_storage::<MovementValidation>().get_mut(entity) {
+            validation.apply_impulse(impulse);
+        }
         if let Some(client) = clients.get(entity) {
             client.send_fallible(ServerGeneral::Knockback(impulse));
         }
@@ -107,6 +131,17 @@ pub fn handle_destroy(server: &mut Server, entity: EcsEntity, cause: HealthSourc
         return;
     }
 
+    if let Some(uid) = state.ecs().uid_from_entity(entity) {
+        let killer = match cause {
+            HealthSource::Damage { by, .. } => by,
+            _ => None,
+        };
+        state.plugin_event(&EntityDeathEvent {
+            entity: uid,
+            killer,
+        });
+    }
+
     let get_attacker_name = |cause_of_death: KillType, by: Uid| -> KillSource {
         // Get attacker entity
         if let Some(char_entity) = state.ecs().entity_from_uid(by.into()) {
@@ -4,9 +4,14 @@ use tracing::error;
 use common::{
     comp::{self, agent::AgentEvent, inventory::slot::EquipSlot, item, slot::Slot, Inventory, Pos},
     consts::MAX_MOUNT_RANGE,
+    terrain::{Block, TerrainGrid},
     uid::Uid,
+    vol::ReadVol,
 };
 use common_net::{msg::ServerGeneral, sync::WorldSyncExt};
+use common_sys::state::{BlockChange, State};
+use plugin_api::event::{BlockChangeEvent, BlockChangeKind, CancelResult, Player};
+use vek::Vec3;
 
 use crate::{
     client::Client,
@@ -155,7 +160,7 @@ pub fn handle_possess(server: &Server, possessor_uid: Uid, possesse_uid: Uid) {
                             common_net::msg::server::PlayerInfo {
                                 player_alias: possessor_player.alias.clone(),
                                 is_online: true,
-                                is_admin: admins.get(possessor).is_some(),
+                                admin_role: admins.get(possessor).map(|admin| admin.0),
                                 character: ecs.read_storage::<comp::Stats>().get(possesse).map(
                                     |s| common_net::msg::CharacterInfo {
                                         name: s.name.clone(),
@@ -254,3 +259,48 @@ fn within_mounting_range(player_position: Option<&Pos>, mount_position: Option<&
         _ => false,
     }
 }
+
+pub fn handle_break_block(server: &mut Server, entity: EcsEntity, pos: Vec3<i32>) {
+    let state = server.state();
+    let block = match state.ecs().read_resource::<TerrainGrid>().get(pos) {
+        Ok(block) => *block,
+        Err(_) => return,
+    };
+    if plugins_allow_block_change(state, entity, pos, block, BlockChangeKind::Broken) {
+        state
+            .ecs()
+            .write_resource::<BlockChange>()
+            .set(pos, block.into_vacant());
+    }
+}
+
+pub fn handle_place_block(server: &mut Server, entity: EcsEntity, pos: Vec3<i32>, block: Block) {
+    let state = server.state();
+    if plugins_allow_block_change(state, entity, pos, block, BlockChangeKind::Placed) {
+        state
+            .ecs()
+            .write_resource::<BlockChange>()
+            .try_set(pos, block);
+    }
+}
+
+fn plugins_allow_block_change(
+    state: &State,
+    entity: EcsEntity,
+    pos: Vec3<i32>,
+    block: Block,
+    kind: BlockChangeKind,
+) -> bool {
+    state
+        .read_component_copied::<Uid>(entity)
+        .map_or(false, |uid| {
+            !state
+                .plugin_event(&BlockChangeEvent {
+                    player: Player { id: uid },
+                    pos,
+                    block: block.into(),
+                    kind,
+                })
+                .contains(&CancelResult::Cancel)
+        })
+}
@@ -19,7 +19,9 @@ use common::{
 use common_net::{msg::ServerGeneral, sync::WorldSyncExt};
 use common_sys::state::State;
 use comp::LightEmitter;
+use plugin_api::event::{CancelResult, ItemDropEvent, ItemPickupEvent, Player};
 
+use super::bank;
 use crate::{client::Client, Server, StateExt};
 
 pub fn swap_lantern(
@@ -55,6 +57,7 @@ pub fn handle_inventory(server: &mut Server, entity: EcsEntity, manip: comp::Inv
         }
     }
 
+    let player = Player { id: uid };
     let mut dropped_items = Vec::new();
     let mut thrown_items = Vec::new();
 
@@ -77,6 +80,26 @@ pub fn handle_inventory(server: &mut Server, entity: EcsEntity, manip: comp::Inv
 
     match manip {
         comp::InventoryManip::Pickup(uid) => {
+            let pickup = state
+                .ecs()
+                .entity_from_uid(uid.into())
+                .and_then(|item_entity| {
+                    state
+                        .ecs()
+                        .read_storage::<comp::Item>()
+                        .get(item_entity)
+                        .map(|item| ItemPickupEvent {
+                            player: player.clone(),
+                            item: item.item_definition_id().to_owned(),
+                            amount: item.amount(),
+                        })
+                });
+            if let Some(pickup) = pickup {
+                if state.plugin_event(&pickup).contains(&CancelResult::Cancel) {
+                    return;
+                }
+            }
+
             let picked_up_item: Option<comp::Item>;
             let item_entity = if let (Some((item, item_entity)), Some(mut inv)) = (
                 state
@@ -408,6 +431,8 @@ pub fn handle_inventory(server: &mut Server, entity: EcsEntity, manip: comp::Inv
                     }
                     Some(comp::InventoryUpdateEvent::Used)
                 },
+                // Items in the bank have to be taken out before they can be used
+                Slot::Bank(_) => None,
             };
 
             drop(inventories);
@@ -421,6 +446,17 @@ pub fn handle_inventory(server: &mut Server, entity: EcsEntity, manip: comp::Inv
                 state.write_component(entity, comp::InventoryUpdate::new(event));
             }
         },
+        comp::InventoryManip::OpenBank(pos) => {
+            let entity_cylinder = get_cylinder(state, entity);
+            bank::handle_open_bank(state, entity, pos, entity_cylinder);
+        },
+        comp::InventoryManip::CloseBank => bank::handle_close_bank(state, entity),
+        comp::InventoryManip::Swap(a, b)
+            if matches!(a, Slot::Bank(_)) || matches!(b, Slot::Bank(_)) =>
+        {
+            let entity_cylinder = get_cylinder(state, entity);
+            bank::handle_bank_swap(state, entity, a, b, entity_cylinder);
+        }
         comp::InventoryManip::Swap(a, b) => {
             let ecs = state.ecs();
 
@@ -455,6 +491,12 @@ pub fn handle_inventory(server: &mut Server, entity: EcsEntity, manip: comp::Inv
                 comp::InventoryUpdate::new(comp::InventoryUpdateEvent::Swapped),
             );
         },
+        comp::InventoryManip::SplitSwap(slot, target)
+            if matches!(slot, Slot::Bank(_)) || matches!(target, Slot::Bank(_)) =>
+        {
+            let entity_cylinder = get_cylinder(state, entity);
+            bank::handle_bank_split_swap(state, entity, slot, target, entity_cylinder);
+        }
         comp::InventoryManip::SplitSwap(slot, target) => {
             let msm = state.ecs().read_resource::<MaterialStatManifest>();
             let mut inventories = state.ecs().write_storage::<comp::Inventory>();
@@ -486,7 +528,7 @@ pub fn handle_inventory(server: &mut Server, entity: EcsEntity, manip: comp::Inv
 
             let item = match slot {
                 Slot::Inventory(slot) => inventory.take_half(slot, &msm),
-                Slot::Equip(_) => None,
+                Slot::Equip(_) | Slot::Bank(_) => None,
             };
 
             if let Some(item) = item {
@@ -504,6 +546,9 @@ pub fn handle_inventory(server: &mut Server, entity: EcsEntity, manip: comp::Inv
             );
         },
         comp::InventoryManip::Drop(slot) => {
+            if !plugins_allow_drop(state, entity, &player, slot, false) {
+                return;
+            }
             let item = match slot {
                 Slot::Inventory(slot) => state
                     .ecs()
@@ -515,6 +560,7 @@ pub fn handle_inventory(server: &mut Server, entity: EcsEntity, manip: comp::Inv
                     .write_storage::<comp::Inventory>()
                     .get_mut(entity)
                     .and_then(|mut inv| inv.replace_loadout_item(slot, None)),
+                Slot::Bank(_) => None,
             };
 
             // FIXME: We should really require the drop and write to be atomic!
@@ -536,6 +582,9 @@ pub fn handle_inventory(server: &mut Server, entity: EcsEntity, manip: comp::Inv
             );
         },
         comp::InventoryManip::SplitDrop(slot) => {
+            if !plugins_allow_drop(state, entity, &player, slot, true) {
+                return;
+            }
             let msm = state.ecs().read_resource::<MaterialStatManifest>();
             let item = match slot {
                 Slot::Inventory(slot) => state
@@ -543,7 +592,7 @@ pub fn handle_inventory(server: &mut Server, entity: EcsEntity, manip: comp::Inv
                     .write_storage::<comp::Inventory>()
                     .get_mut(entity)
                     .and_then(|mut inv| inv.take_half(slot, &msm)),
-                Slot::Equip(_) => None,
+                Slot::Equip(_) | Slot::Bank(_) => None,
             };
 
             // FIXME: We should really require the drop and write to be atomic!
@@ -682,7 +731,38 @@ pub fn handle_inventory(server: &mut Server, entity: EcsEntity, manip: comp::Inv
     }
 }
 
-fn within_pickup_range<S: FindDist<find_dist::Cylinder>>(
+/// Asks the plugins whether `entity` may drop the item in `slot`, or half of it
+/// if `split`
+fn plugins_allow_drop(
+    state: &State,
+    entity: EcsEntity,
+    player: &Player,
+    slot: Slot,
+    split: bool,
+) -> bool {
+    let event = {
+        let inventories = state.ecs().read_storage::<comp::Inventory>();
+        let item = inventories.get(entity).and_then(|inv| match slot {
+            Slot::Inventory(slot) => inv.get(slot),
+            Slot::Equip(slot) => inv.equipped(slot),
+            Slot::Bank(_) => None,
+        });
+        item.map(|item| ItemDropEvent {
+            player: player.clone(),
+            item: item.item_definition_id().to_owned(),
+            amount: if split {
+                item.amount() / 2
+            } else {
+                item.amount()
+            },
+        })
+    };
+    event.map_or(true, |event| {
+        !state.plugin_event(&event).contains(&CancelResult::Cancel)
+    })
+}
+
+pub(super) fn within_pickup_range<S: FindDist<find_dist::Cylinder>>(
     entity_cylinder: Option<find_dist::Cylinder>,
     shape_fn: impl FnOnce() -> Option<S>,
 ) -> bool {
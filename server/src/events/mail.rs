@@ -0,0 +1,455 @@
+use crate::{
+    client::Client,
+    persistence::{
+        character_loader::{CharacterLoader, MailRecipientResult, PendingMail},
+        character_updater::{
+            CharacterUpdateData, CharacterUpdater, ClaimedMail, MailboxResult, OutgoingMail,
+            SentMail,
+        },
+        PersistedState,
+    },
+    presence::Presence,
+    Server,
+};
+use common::{
+    character::CharacterId,
+    comp::{self, inventory::slot::InvSlotId, Inventory},
+    mail::{validate_mail, Mail, MailAction, MailError, MailId, MailUpdate},
+};
+use common_net::msg::{PresenceKind, ServerGeneral};
+use hashbrown::HashSet;
+use specs::{Component, Entity as EcsEntity, Join, WorldExt};
+use specs_idvs::IdvStorage;
+use tracing::error;
+
+/// The cached mailbox of a character.
+///
+/// Claiming and deleting mail is checked against this cache, which is kept in
+/// sync with the database by applying the same changes to both. While the
+/// mailbox is being (re)loaded or mail is being claimed these operations are
+/// refused, since the loaded mailbox replaces the cache.
+#[derive(Debug, Default)]
+pub struct Mailbox {
+    mails: Vec<Mail>,
+    pending_loads: u32,
+    pending_claims: u32,
+}
+
+impl Component for Mailbox {
+    type Storage = IdvStorage<Self>;
+}
+
+fn character_id(server: &Server, entity: EcsEntity) -> Option<CharacterId> {
+    match server
+        .state
+        .ecs()
+        .read_storage::<Presence>()
+        .get(entity)?
+        .kind
+    {
+        PresenceKind::Character(character_id) => Some(character_id),
+        PresenceKind::Spectator => None,
+    }
+}
+
+fn notify_mail(server: &Server, entity: EcsEntity, update: MailUpdate) {
+    server.notify_client(entity, ServerGeneral::Mail(update));
+}
+
+/// Gathers the components of a character that are saved alongside a mail
+/// operation
+fn character_update_data(server: &Server, entity: EcsEntity) -> Option<CharacterUpdateData> {
+    let ecs = server.state.ecs();
+    Some((
+        ecs.read_storage::<comp::Stats>().get(entity)?.clone(),
+        ecs.read_storage::<comp::Inventory>().get(entity)?.clone(),
+        ecs.read_storage::<comp::Bank>().get(entity)?.clone(),
+        ecs.read_storage::<comp::Waypoint>().get(entity).cloned(),
+        PersistedState::from_components(
+            ecs.read_storage::<comp::Health>().get(entity),
+            ecs.read_storage::<comp::Energy>().get(entity),
+            ecs.read_storage::<comp::Buffs>().get(entity),
+            ecs.read_storage::<comp::Pos>().get(entity),
+        ),
+    ))
+}
+
+/// Request a (re)load of the mailbox of a character, creating its cache if
+/// there isn't one yet
+fn load_mailbox(server: &Server, entity: EcsEntity, character_id: CharacterId) {
+    let mut mailboxes = server.state.ecs().write_storage::<Mailbox>();
+    match mailboxes.entry(entity) {
+        Ok(entry) => entry.or_insert_with(Mailbox::default).pending_loads += 1,
+        Err(e) => {
+            error!(?e, "Could not create mailbox for entity");
+            return;
+        },
+    }
+    server
+        .state
+        .ecs()
+        .read_resource::<CharacterUpdater>()
+        .load_mailbox(entity, character_id);
+}
+
+/// Checks that the items to be attached to a mail exist, are all different
+/// and can be stored in the database as part of a mail
+fn validate_mail_items(inventory: &Inventory, slots: &[InvSlotId]) -> bool {
+    let mut seen = HashSet::new();
+    slots.iter().all(|slot| {
+        seen.insert(*slot)
+            && inventory
+                .get(*slot)
+                .map_or(false, |item| item.slots().iter().all(Option::is_none))
+    })
+}
+
+pub fn handle_mail_action(server: &mut Server, entity: EcsEntity, action: MailAction) {
+    let character_id = match character_id(server, entity) {
+        Some(character_id) => character_id,
+        None => return,
+    };
+
+    match action {
+        MailAction::RequestMailbox => load_mailbox(server, entity, character_id),
+        MailAction::Send {
+            recipient,
+            subject,
+            body,
+            items,
+        } => {
+            let result = validate_mail(&subject, &body, items.len()).and_then(|()| {
+                let inventories = server.state.ecs().read_storage::<Inventory>();
+                match inventories.get(entity) {
+                    Some(inventory) if validate_mail_items(inventory, &items) => Ok(()),
+                    _ => Err(MailError::InvalidItems),
+                }
+            });
+            match result {
+                Ok(()) => server
+                    .state
+                    .ecs()
+                    .read_resource::<CharacterLoader>()
+                    .find_mail_recipient(entity, PendingMail {
+                        recipient_alias: recipient,
+                        subject,
+                        body,
+                        items,
+                    }),
+                Err(error) => notify_mail(server, entity, MailUpdate::Error(error)),
+            }
+        },
+        MailAction::Read(mail_id) => {
+            let result = with_mail(server, entity, mail_id, |mailbox, index| {
+                mailbox.mails[index].read = true;
+                Ok(())
+            });
+            if result.is_ok() {
+                server
+                    .state
+                    .ecs()
+                    .read_resource::<CharacterUpdater>()
+                    .mark_mail_read(character_id, mail_id);
+            }
+            send_mailbox_or_error(server, entity, result);
+        },
+        MailAction::Claim(mail_id) => {
+            let mut claim = None;
+            let result = with_mail(server, entity, mail_id, |mailbox, index| {
+                let mut data =
+                    character_update_data(server, entity).ok_or(MailError::Unavailable)?;
+                let inventory = &mut data.1;
+                let items = &mut mailbox.mails[index].items;
+                if items.is_empty() {
+                    return Err(MailError::InvalidItems);
+                }
+                if inventory.capacity() - inventory.populated_slots() < items.len() {
+                    return Err(MailError::NotEnoughSpace);
+                }
+                // Only the saved inventory gets the items for now, they are moved to
+                // the character once the claim has been saved
+                inventory
+                    .push_all(items.iter().cloned())
+                    .map_err(|_| MailError::NotEnoughSpace)?;
+                mailbox.pending_claims += 1;
+                claim = Some((data, items.drain(..).collect::<Vec<_>>()));
+                Ok(())
+            });
+            match claim {
+                Some((data, items)) => server
+                    .state
+                    .ecs()
+                    .read_resource::<CharacterUpdater>()
+                    .claim_mail(entity, character_id, data, mail_id, items),
+                None => send_mailbox_or_error(server, entity, result),
+            }
+        },
+        MailAction::Delete(mail_id) => {
+            let result = with_mail(server, entity, mail_id, |mailbox, index| {
+                if !mailbox.mails[index].items.is_empty() {
+                    return Err(MailError::HasItems);
+                }
+                mailbox.mails.remove(index);
+                Ok(())
+            });
+            if result.is_ok() {
+                server
+                    .state
+                    .ecs()
+                    .read_resource::<CharacterUpdater>()
+                    .delete_mail(character_id, mail_id);
+            }
+            send_mailbox_or_error(server, entity, result);
+        },
+    }
+}
+
+/// Runs `f` with the cached mailbox of `entity` and the index of the mail with
+/// the given ID in it
+fn with_mail(
+    server: &Server,
+    entity: EcsEntity,
+    mail_id: MailId,
+    f: impl FnOnce(&mut Mailbox, usize) -> Result<(), MailError>,
+) -> Result<(), MailError> {
+    let mut mailboxes = server.state.ecs().write_storage::<Mailbox>();
+    let mailbox = mailboxes
+        .get_mut(entity)
+        .filter(|mailbox| mailbox.pending_loads == 0 && mailbox.pending_claims == 0)
+        .ok_or(MailError::Unavailable)?;
+    let index = mailbox
+        .mails
+        .iter()
+        .position(|mail| mail.id == mail_id)
+        .ok_or(MailError::MailNotFound)?;
+    f(mailbox, index)
+}
+
+fn send_mailbox_or_error(server: &Server, entity: EcsEntity, result: Result<(), MailError>) {
+    let update = match result {
+        Ok(()) => match server.state.ecs().read_storage::<Mailbox>().get(entity) {
+            Some(mailbox) => MailUpdate::Mailbox(mailbox.mails.clone()),
+            None => return,
+        },
+        Err(error) => MailUpdate::Error(error),
+    };
+    notify_mail(server, entity, update);
+}
+
+/// Invoked once the recipient of a mail has been looked up, sends the mail if
+/// they were found
+pub fn handle_mail_recipient(
+    server: &Server,
+    entity: EcsEntity,
+    result: MailRecipientResult,
+    pending: PendingMail,
+) {
+    let character_id = match character_id(server, entity) {
+        Some(character_id) => character_id,
+        None => return,
+    };
+
+    let recipient = match result {
+        Ok(Ok(recipient)) => recipient,
+        Ok(Err(error)) => return notify_mail(server, entity, MailUpdate::Error(error)),
+        Err(e) => {
+            error!(?e, "Failed to look up mail recipient");
+            return notify_mail(server, entity, MailUpdate::Error(MailError::Unavailable));
+        },
+    };
+
+    let sender_alias = match server.state.ecs().read_storage::<comp::Stats>().get(entity) {
+        Some(stats) => stats.name.clone(),
+        None => return,
+    };
+
+    let items = {
+        let mut inventories = server.state.ecs().write_storage::<Inventory>();
+        let inventory = match inventories.get_mut(entity) {
+            Some(inventory) => inventory,
+            None => return,
+        };
+        // The inventory may have changed while the recipient was looked up
+        if !validate_mail_items(inventory, &pending.items) {
+            return notify_mail(server, entity, MailUpdate::Error(MailError::InvalidItems));
+        }
+        pending
+            .items
+            .iter()
+            .filter_map(|slot| inventory.remove(*slot))
+            .collect()
+    };
+
+    let data = match character_update_data(server, entity) {
+        Some(data) => data,
+        None => return,
+    };
+    // The items are only handed to the recipient, or back to the sender, once
+    // the outcome of the transaction is known
+    server
+        .state
+        .ecs()
+        .read_resource::<CharacterUpdater>()
+        .send_mail(entity, character_id, data, OutgoingMail {
+            recipient,
+            sender_alias,
+            subject: pending.subject,
+            body: pending.body,
+            items,
+        });
+}
+
+/// Invoked once a mail has been stored in the database, or failed to be.
+/// Mail that couldn't be sent has its items returned to the sender.
+pub fn handle_sent_mail(server: &Server, sent_mail: SentMail) {
+    let SentMail {
+        entity,
+        sender,
+        recipient,
+        result,
+    } = sent_mail;
+
+    let (error, items) = match result {
+        Ok(()) => {
+            if character_id(server, entity) == Some(sender) {
+                notify_mail(server, entity, MailUpdate::Sent);
+            }
+            notify_recipient(server, recipient);
+            return;
+        },
+        Err(failure) => failure,
+    };
+
+    if character_id(server, entity) == Some(sender) {
+        let leftovers: Vec<comp::Item> = match server
+            .state
+            .ecs()
+            .write_storage::<Inventory>()
+            .get_mut(entity)
+        {
+            Some(inventory) => items
+                .into_iter()
+                .filter_map(|item| inventory.push(item))
+                .collect(),
+            None => items,
+        };
+        if !leftovers.is_empty() {
+            return_mail(server, sender, leftovers);
+        }
+        notify_mail(server, entity, MailUpdate::Error(error));
+    } else if !items.is_empty() {
+        // The sender went offline, so their inventory isn't available anymore
+        return_mail(server, sender, items);
+    }
+}
+
+/// Invoked once the items of a mail have been claimed in the database, or
+/// failed to be. The items are only moved to the recipient's inventory now, so
+/// that a failed claim can't duplicate them.
+pub fn handle_claimed_mail(server: &Server, claimed_mail: ClaimedMail) {
+    let ClaimedMail {
+        entity,
+        recipient,
+        result,
+    } = claimed_mail;
+
+    if character_id(server, entity) != Some(recipient) {
+        // The recipient went offline before the claim was saved. The saved
+        // inventory may have been overwritten since, so the items are mailed
+        // to them again
+        if let Ok(items) = result {
+            return_mail(server, recipient, items);
+        }
+        return;
+    }
+
+    if let Some(mailbox) = server
+        .state
+        .ecs()
+        .write_storage::<Mailbox>()
+        .get_mut(entity)
+    {
+        mailbox.pending_claims = mailbox.pending_claims.saturating_sub(1);
+    }
+    match result {
+        Ok(items) => {
+            let leftovers = match server
+                .state
+                .ecs()
+                .write_storage::<Inventory>()
+                .get_mut(entity)
+            {
+                Some(inventory) => match inventory.push_all(items.into_iter()) {
+                    Ok(()) => Vec::new(),
+                    Err(comp::inventory::Error::Full(leftovers)) => leftovers,
+                },
+                None => items,
+            };
+            if !leftovers.is_empty() {
+                return_mail(server, recipient, leftovers);
+            }
+        },
+        Err(error) => notify_mail(server, entity, MailUpdate::Error(error)),
+    }
+    // Replace the cache with the mailbox as it was saved
+    load_mailbox(server, entity, recipient);
+}
+
+fn return_mail(server: &Server, sender: CharacterId, items: Vec<comp::Item>) {
+    server
+        .state
+        .ecs()
+        .read_resource::<CharacterUpdater>()
+        .return_mail(sender, "Returned mail".to_string(), items);
+}
+
+/// Let the recipient of a mail know if they are online, reloading their
+/// mailbox if they have it open
+fn notify_recipient(server: &Server, recipient: CharacterId) {
+    let recipient_entity = (
+        &server.state.ecs().entities(),
+        &server.state.ecs().read_storage::<Presence>(),
+        &server.state.ecs().read_storage::<Client>(),
+    )
+        .join()
+        .find(|(_, presence, _)| presence.kind == PresenceKind::Character(recipient))
+        .map(|(entity, _, _)| entity);
+    if let Some(recipient_entity) = recipient_entity {
+        if server
+            .state
+            .ecs()
+            .read_storage::<Mailbox>()
+            .contains(recipient_entity)
+        {
+            load_mailbox(server, recipient_entity, recipient);
+        }
+        notify_mail(server, recipient_entity, MailUpdate::NewMail);
+    }
+}
+
+/// Invoked when a mailbox has been loaded from the database
+pub fn handle_loaded_mailbox(server: &Server, entity: EcsEntity, result: MailboxResult) {
+    let update = {
+        let mut mailboxes = server.state.ecs().write_storage::<Mailbox>();
+        let mailbox = match mailboxes.get_mut(entity) {
+            Some(mailbox) => mailbox,
+            None => return,
+        };
+        mailbox.pending_loads = mailbox.pending_loads.saturating_sub(1);
+        match result {
+            Ok(mails) => {
+                mailbox.mails = mails;
+                if mailbox.pending_loads > 0 {
+                    // A newer version of the mailbox is on its way
+                    return;
+                }
+                MailUpdate::Mailbox(mailbox.mails.clone())
+            },
+            Err(e) => {
+                error!(?e, "Failed to load mailbox");
+                MailUpdate::Error(MailError::Unavailable)
+            },
+        }
+    };
+    notify_mail(server, entity, update);
+}
@@ -1,4 +1,5 @@
 use crate::{state_ext::StateExt, Server};
+pub(crate) use bank::BankAccess;
 use common::event::{EventBus, ServerEvent};
 use common_base::span;
 use entity_creation::{
@@ -13,14 +14,22 @@ use entity_manipulation::{
 use group_manip::handle_group;
 use information::handle_site_info;
 use interaction::{
-    handle_lantern, handle_mount, handle_npc_interaction, handle_possess, handle_unmount,
+    handle_break_block, handle_lantern, handle_mount, handle_npc_interaction, handle_place_block,
+    handle_possess, handle_unmount,
 };
 use inventory_manip::handle_inventory;
 use invite::{handle_invite, handle_invite_response};
-use player::{handle_client_disconnect, handle_exit_ingame};
+use mail::handle_mail_action;
+pub(crate) use mail::{
+    handle_claimed_mail, handle_loaded_mailbox, handle_mail_recipient, handle_sent_mail, Mailbox,
+};
+use player::{handle_chat_plugins, handle_client_disconnect, handle_exit_ingame};
+#[cfg(feature = "plugins")]
+use plugin::handle_plugin_action;
 use specs::{Entity as EcsEntity, WorldExt};
 use trade::handle_process_trade_action;
 
+mod bank;
 mod entity_creation;
 mod entity_manipulation;
 mod group_manip;
@@ -28,7 +37,9 @@ mod information;
 mod interaction;
 mod inventory_manip;
 mod invite;
+mod mail;
 mod player;
+#[cfg(feature = "plugins")] mod plugin;
 mod trade;
 
 pub enum Event {
@@ -120,6 +131,9 @@ impl Server {
                 ServerEvent::ProcessTradeAction(entity, trade_id, action) => {
                     handle_process_trade_action(self, entity, trade_id, action);
                 },
+                ServerEvent::MailAction { entity, action } => {
+                    handle_mail_action(self, entity, action)
+                },
                 ServerEvent::Mount(mounter, mountee) => handle_mount(self, mounter, mountee),
                 ServerEvent::Unmount(mounter) => handle_unmount(self, mounter),
                 ServerEvent::Possess(possessor_uid, possesse_uid) => {
@@ -190,6 +204,10 @@ impl Server {
                     handle_combo_change(&self, entity, change)
                 },
                 ServerEvent::RequestSiteInfo { entity, id } => handle_site_info(&self, entity, id),
+                ServerEvent::BreakBlock { entity, pos } => handle_break_block(self, entity, pos),
+                ServerEvent::PlaceBlock { entity, pos, block } => {
+                    handle_place_block(self, entity, pos, block)
+                },
             }
         }
 
@@ -203,9 +221,29 @@ impl Server {
         }
 
         for msg in chat_messages {
-            self.state.send_chat(msg);
+            if let Some(msg) = handle_chat_plugins(&self.state, msg) {
+                self.state.send_chat(msg);
+            }
         }
 
         frontend_events
     }
+
+    /// Applies the actions plugins emitted since the last tick
+    #[cfg(feature = "plugins")]
+    pub fn handle_plugin_actions(&mut self) {
+        span!(
+            _guard,
+            "handle_plugin_actions",
+            "Server::handle_plugin_actions"
+        );
+        let actions = self
+            .state
+            .ecs()
+            .write_resource::<common_sys::plugin::PluginMgr>()
+            .take_actions();
+        for action in actions {
+            handle_plugin_action(self, action);
+        }
+    }
 }
@@ -9,8 +9,12 @@ use common::{
     uid::{Uid, UidAllocator},
 };
 use common_base::span;
-use common_net::msg::{PlayerListUpdate, PresenceKind, ServerGeneral};
+use common_net::{
+    msg::{PlayerListUpdate, PresenceKind, ServerGeneral},
+    sync::WorldSyncExt,
+};
 use common_sys::state::State;
+use plugin_api::event::{ChatMessageEvent, ChatMessageResult, PlayerLeaveEvent};
 use specs::{saveload::MarkerAllocator, Builder, Entity as EcsEntity, WorldExt};
 use tracing::{debug, error, trace, warn, Instrument};
 
@@ -132,10 +136,15 @@ pub fn handle_client_disconnect(server: &mut Server, entity: EcsEntity) -> Event
 
     // Tell other clients to remove from player list
     // And send a disconnected message
-    if let (Some(uid), Some(_)) = (
+    if let (Some(uid), Some(player)) = (
         state.read_storage::<Uid>().get(entity),
         state.read_storage::<comp::Player>().get(entity),
     ) {
+        state.plugin_event(&PlayerLeaveEvent {
+            player: plugin_api::event::Player { id: *uid },
+            player_name: player.alias.clone(),
+        });
+
         state.notify_players(ServerGeneral::server_msg(comp::ChatType::Offline(*uid), ""));
 
         state.notify_players(ServerGeneral::PlayerListUpdate(PlayerListUpdate::Remove(
@@ -160,11 +169,44 @@ pub fn handle_client_disconnect(server: &mut Server, entity: EcsEntity) -> Event
     Event::ClientDisconnected { entity }
 }
 
+/// Lets the plugins cancel or rewrite chat messages sent by players
+pub fn handle_chat_plugins(
+    state: &State,
+    mut msg: comp::UnresolvedChatMsg,
+) -> Option<comp::UnresolvedChatMsg> {
+    let sender = match msg.uid() {
+        Some(uid) => uid,
+        None => return Some(msg),
+    };
+    let is_player = state
+        .ecs()
+        .entity_from_uid(sender.into())
+        .map_or(false, |entity| {
+            state.read_storage::<comp::Player>().contains(entity)
+        });
+    if !is_player {
+        return Some(msg);
+    }
+    let responses = state.plugin_event(&ChatMessageEvent {
+        player: plugin_api::event::Player { id: sender },
+        message: msg.message.clone(),
+    });
+    for response in responses {
+        match response {
+            ChatMessageResult::Cancel => return None,
+            ChatMessageResult::Rewrite(message) => msg.message = message,
+            ChatMessageResult::None => {},
+        }
+    }
+    Some(msg)
+}
+
 fn persist_entity(state: &mut State, entity: EcsEntity) -> EcsEntity {
-    if let (Some(presences), Some(stats), Some(inventory), updater) = (
+    if let (Some(presences), Some(stats), Some(inventory), Some(bank), updater) = (
         state.read_storage::<Presence>().get(entity),
         state.read_storage::<comp::Stats>().get(entity),
         state.read_storage::<comp::Inventory>().get(entity),
+        state.read_storage::<comp::Bank>().get(entity),
         state
             .ecs()
             .read_resource::<persistence::character_updater::CharacterUpdater>(),
@@ -172,7 +214,20 @@ fn persist_entity(state: &mut State, entity: EcsEntity) -> EcsEntity {
         if let PresenceKind::Character(character_id) = presences.kind {
             let waypoint_read = state.read_storage::<comp::Waypoint>();
             let waypoint = waypoint_read.get(entity);
-            updater.update(character_id, stats, inventory, waypoint);
+            let persisted_state = persistence::PersistedState::from_components(
+                state.read_storage::<comp::Health>().get(entity),
+                state.read_storage::<comp::Energy>().get(entity),
+                state.read_storage::<comp::Buffs>().get(entity),
+                state.read_storage::<comp::Pos>().get(entity),
+            );
+            updater.update(
+                character_id,
+                stats,
+                inventory,
+                bank,
+                waypoint,
+                persisted_state,
+            );
         }
     }
 
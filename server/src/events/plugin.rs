@@ -0,0 +1,242 @@
+use std::{str::FromStr, time::Duration};
+
+use common::{
+    comp::{
+        self,
+        buff::{Buff, BuffChange, BuffData, BuffSource},
+        inventory::loadout_builder::LoadoutBuilder,
+        item::MaterialStatManifest,
+        ChatType, Inventory, Item,
+    },
+    npc::{self, get_npc_name},
+    uid::Uid,
+};
+use common_net::{msg::ServerGeneral, sync::WorldSyncExt};
+use common_sys::state::BlockChange;
+use plugin_api::{Action, NpcAlignment, NpcSpawn};
+use specs::{world::WorldExt, Builder, Entity as EcsEntity};
+use tracing::{error, warn};
+use vek::Vec3;
+
+use super::entity_manipulation::handle_buff;
+use crate::{state_ext::StateExt, Server};
+
+/// The strongest buff a plugin may apply, matching the strongest buffs used by
+/// items
+const MAX_BUFF_STRENGTH: f32 = 1000.0;
+/// Longer durations would overflow a `Duration`, use no duration for buffs
+/// that should last until they are removed
+const MAX_BUFF_DURATION: f32 = u32::MAX as f32;
+
+/// Applies an action a plugin emitted, the actions that don't need to write to
+/// the ECS are already handled when they are emitted
+pub fn handle_plugin_action(server: &mut Server, action: Action) {
+    match action {
+        Action::PlayerSendMessage(uid, msg) => {
+            if let Some(entity) = entity(server, uid) {
+                server.notify_client(
+                    entity,
+                    ServerGeneral::server_msg(ChatType::CommandInfo, msg),
+                );
+            }
+        },
+        Action::BroadcastMessage(msg) => {
+            server.notify_players(ServerGeneral::server_msg(ChatType::CommandInfo, msg));
+        },
+        Action::KillEntity(uid) => {
+            if let Some(entity) = entity(server, uid) {
+                server
+                    .state
+                    .ecs()
+                    .write_storage::<comp::Health>()
+                    .get_mut(entity)
+                    .map(|mut h| h.set_to(0, comp::HealthSource::Command));
+            }
+        },
+        Action::SpawnNpc(spawn) => handle_spawn_npc(server, spawn),
+        Action::Teleport(uid, pos) => {
+            if !is_finite(pos) {
+                warn!(
+                    ?uid,
+                    ?pos,
+                    "Plugin tried to teleport to an invalid position"
+                );
+            } else if let Some(entity) = entity(server, uid) {
+                server.state.write_component(entity, comp::Pos(pos));
+                server.state.write_component(entity, comp::ForceUpdate);
+            }
+        },
+        Action::GiveItem(uid, item, amount) => handle_give_item(server, uid, &item, amount),
+        Action::RemoveItem(uid, item, amount) => handle_remove_item(server, uid, &item, amount),
+        Action::ApplyBuff(uid, kind, strength, duration) => {
+            if duration.map_or(false, |d| !d.is_finite() || d < 0.0) {
+                warn!(
+                    ?uid,
+                    ?duration,
+                    "Plugin tried to apply a buff with an invalid duration"
+                );
+            } else if let Some(entity) = entity(server, uid) {
+                // `max` also replaces NaN
+                let strength = strength.max(0.0).min(MAX_BUFF_STRENGTH);
+                handle_buff(
+                    server,
+                    entity,
+                    BuffChange::Add(Buff::new(
+                        kind,
+                        BuffData::new(
+                            strength,
+                            duration.map(|d| Duration::from_secs_f32(d.min(MAX_BUFF_DURATION))),
+                        ),
+                        Vec::new(),
+                        BuffSource::World,
+                    )),
+                );
+            }
+        },
+        Action::SetBlock(pos, block) => match block.to_terrain_block() {
+            Some(block) => {
+                server
+                    .state
+                    .ecs()
+                    .write_resource::<BlockChange>()
+                    .set(pos, block);
+            },
+            None => warn!(?block, "Plugin tried to set an invalid block"),
+        },
+        // Handled in `raw_emit_actions`
+        Action::ServerClose | Action::Print(_) => {},
+    }
+}
+
+fn entity(server: &Server, uid: Uid) -> Option<EcsEntity> {
+    let entity = server.state.ecs().entity_from_uid(uid.into());
+    if entity.is_none() {
+        warn!(?uid, "Plugin action targets an entity that doesn't exist");
+    }
+    entity
+}
+
+fn is_finite(pos: Vec3<f32>) -> bool { pos.map(f32::is_finite).reduce_and() }
+
+fn handle_spawn_npc(server: &mut Server, spawn: NpcSpawn) {
+    if !is_finite(spawn.pos) {
+        error!(?spawn.pos, "Plugin tried to spawn an npc at an invalid position");
+        return;
+    }
+    let npc::NpcBody(id, mut body) = match npc::NpcBody::from_str(&spawn.body) {
+        Ok(body) => body,
+        Err(()) => {
+            error!(?spawn.body, "Plugin tried to spawn an unknown body");
+            return;
+        },
+    };
+    let body = body();
+    let main_tool = match spawn.main_tool.as_deref().map(Item::new_from_asset) {
+        Some(Ok(item)) => Some(item),
+        Some(Err(e)) => {
+            error!(?e, ?spawn.main_tool, "Plugin tried to spawn an npc with an unknown tool");
+            return;
+        },
+        None => None,
+    };
+    let alignment = match spawn.alignment {
+        NpcAlignment::Wild => comp::Alignment::Wild,
+        NpcAlignment::Enemy => comp::Alignment::Enemy,
+        NpcAlignment::Npc => comp::Alignment::Npc,
+        NpcAlignment::Tame => comp::Alignment::Tame,
+        NpcAlignment::Passive => comp::Alignment::Passive,
+    };
+    let name = spawn
+        .name
+        .unwrap_or_else(|| get_npc_name(id, npc::BodyType::from_body(body)));
+    let loadout = LoadoutBuilder::build_loadout(body, main_tool, None, None).build();
+
+    let mut entity_base = server
+        .state
+        .create_npc(
+            comp::Pos(spawn.pos),
+            comp::Stats::new(name),
+            comp::Health::new(body, 1),
+            comp::Poise::new(body),
+            Inventory::new_with_loadout(loadout),
+            body,
+        )
+        .with(comp::Vel(Vec3::zero()))
+        .with(comp::MountState::Unmounted)
+        .with(alignment);
+    if spawn.agent {
+        entity_base = entity_base.with(comp::Agent::default().with_patrol_origin(spawn.pos));
+    }
+    entity_base.build();
+}
+
+fn handle_give_item(server: &mut Server, uid: Uid, item_name: &str, amount: u32) {
+    if amount == 0 {
+        warn!(?uid, ?item_name, "Plugin tried to give zero items");
+        return;
+    }
+    let entity = match entity(server, uid) {
+        Some(entity) => entity,
+        None => return,
+    };
+    let mut item = match Item::new_from_asset(item_name) {
+        Ok(item) => item,
+        Err(e) => {
+            error!(?e, ?item_name, "Plugin tried to give an unknown item");
+            return;
+        },
+    };
+    let ecs = server.state.ecs();
+    if let Some(mut inventory) = ecs.write_storage::<Inventory>().get_mut(entity) {
+        let full = if item.set_amount(amount).is_ok() {
+            inventory.push(item).is_some()
+        } else {
+            // This item can't stack, give each item on its own
+            let msm = ecs.read_resource::<MaterialStatManifest>();
+            (0..amount).any(|_| inventory.push(item.duplicate(&msm)).is_some())
+        };
+        if full {
+            warn!(?uid, ?item_name, "Inventory full, couldn't give all items");
+        }
+        let _ = ecs.write_storage::<comp::InventoryUpdate>().insert(
+            entity,
+            comp::InventoryUpdate::new(comp::InventoryUpdateEvent::Given),
+        );
+    }
+}
+
+fn handle_remove_item(server: &mut Server, uid: Uid, item_name: &str, amount: u32) {
+    let entity = match entity(server, uid) {
+        Some(entity) => entity,
+        None => return,
+    };
+    let ecs = server.state.ecs();
+    if let Some(mut inventory) = ecs.write_storage::<Inventory>().get_mut(entity) {
+        let slots = inventory
+            .slots_with_id()
+            .filter(|(_, slot)| {
+                slot.as_ref()
+                    .map_or(false, |item| item.item_definition_id() == item_name)
+            })
+            .map(|(slot, _)| slot)
+            .collect::<Vec<_>>();
+        let mut remaining = amount;
+        for slot in slots {
+            if remaining == 0 {
+                break;
+            }
+            if let Some(Some(item)) = inventory.slot_mut(slot) {
+                if item.amount() > remaining && item.decrease_amount(remaining).is_ok() {
+                    remaining = 0;
+                    continue;
+                }
+            }
+            remaining =
+                remaining.saturating_sub(inventory.remove(slot).map_or(0, |item| item.amount()));
+        }
+        let _ = ecs.write_storage::<comp::InventoryUpdate>().insert(
+            entity,
+            comp::InventoryUpdate::new(comp::InventoryUpdateEvent::Gave),
+        );
+    }
+}
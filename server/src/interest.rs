@@ -0,0 +1,246 @@
+//! Decides how often a client is told about the physics of the entities
+//! around it. Every entity has an update interval depending on its distance
+//! to the client and how important it is to the client, and a client only gets
+//! a limited number of updates per tick. When more updates are due than that,
+//! the most important and most overdue ones are sent first and the others are
+//! deferred to the next tick.
+
+use common::{
+    comp::{Alignment, Body, Group},
+    uid::Uid,
+};
+use common_net::sync::PhysicsUpdate;
+use hashbrown::HashMap;
+use specs::Component;
+use specs_idvs::IdvStorage;
+
+/// Longest interval between two updates of the same entity, in ticks
+const MAX_INTERVAL: u64 = 64;
+/// Updates every client gets per tick, regardless of its view distance
+const BASE_BUDGET: usize = 64;
+/// Additional updates per tick for every chunk of view distance
+const BUDGET_PER_VIEW_DISTANCE: usize = 16;
+/// Entities that weren't sent for this many ticks are forgotten, they start
+/// with a new stagger when they are relevant again
+const FORGET_TICKS: u64 = MAX_INTERVAL * 4;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Importance {
+    /// Things like birds and items lying around
+    Low,
+    Normal,
+    /// Entities hostile to the client or in its group
+    High,
+}
+
+impl Importance {
+    pub fn of(
+        body: Option<&Body>,
+        alignment: Option<&Alignment>,
+        group: Option<&Group>,
+        client_alignment: Option<&Alignment>,
+        client_group: Option<&Group>,
+    ) -> Self {
+        let hostile = alignment
+            .zip(client_alignment)
+            .map_or(false, |(a, b)| a.hostile_towards(*b));
+        let grouped = group.is_some() && group == client_group;
+        if hostile || grouped {
+            Self::High
+        } else if matches!(
+            body,
+            Some(Body::BirdSmall(_) | Body::BirdMedium(_) | Body::FishSmall(_) | Body::Object(_))
+        ) {
+            Self::Low
+        } else {
+            Self::Normal
+        }
+    }
+
+    /// Ticks between two updates of an entity at this distance
+    pub fn interval(self, distance_sq: f32) -> u64 {
+        // More entities farther away so checks start there
+        let interval = if distance_sq > 500.0f32.powi(2) {
+            32
+        } else if distance_sq > 300.0f32.powi(2) {
+            16
+        } else if distance_sq > 200.0f32.powi(2) {
+            8
+        } else if distance_sq > 120.0f32.powi(2) {
+            6
+        } else if distance_sq > 64.0f32.powi(2) {
+            3
+        } else if distance_sq > 24.0f32.powi(2) {
+            2
+        } else {
+            1
+        };
+        match self {
+            Self::Low => (interval * 2).min(MAX_INTERVAL),
+            Self::Normal => interval,
+            Self::High if distance_sq > 64.0f32.powi(2) => (interval / 2).max(1),
+            Self::High => 1,
+        }
+    }
+
+    fn weight(self) -> f32 {
+        match self {
+            Self::Low => 1.0,
+            Self::Normal => 2.0,
+            Self::High => 4.0,
+        }
+    }
+}
+
+/// Updates per tick a client with this view distance gets
+pub fn budget(view_distance: u32) -> usize {
+    BASE_BUDGET + BUDGET_PER_VIEW_DISTANCE * view_distance as usize
+}
+
+/// Physics updates that are due for a client this tick
+#[derive(Debug, Default)]
+pub struct Candidates {
+    /// Sent regardless of the budget, e.g. new components
+    forced: Vec<PhysicsUpdate>,
+    ranked: Vec<(f32, PhysicsUpdate)>,
+}
+
+impl Candidates {
+    pub fn force(&mut self, update: PhysicsUpdate) { self.forced.push(update); }
+
+    pub fn push(&mut self, priority: f32, update: PhysicsUpdate) {
+        self.ranked.push((priority, update));
+    }
+}
+
+/// When the entities were last sent to a client
+#[derive(Debug, Default)]
+pub struct Interest {
+    last_sent: HashMap<Uid, u64>,
+}
+
+impl Component for Interest {
+    type Storage = IdvStorage<Self>;
+}
+
+impl Interest {
+    /// Returns the priority of the entity if an update is due. Entities the
+    /// client didn't get updates of yet are staggered by `stagger`, so that
+    /// not all of them are due in the same tick.
+    pub fn due(
+        &mut self,
+        uid: Uid,
+        stagger: u64,
+        tick: u64,
+        importance: Importance,
+        interval: u64,
+    ) -> Option<f32> {
+        let last_sent = *self
+            .last_sent
+            .entry(uid)
+            .or_insert_with(|| (tick + stagger % interval).saturating_sub(interval));
+        let overdue = tick.saturating_sub(last_sent);
+        (overdue >= interval).then(|| importance.weight() * overdue as f32 / interval as f32)
+    }
+
+    /// Picks the updates to send this tick, at most `budget` besides the
+    /// forced ones
+    pub fn select(
+        &mut self,
+        tick: u64,
+        budget: usize,
+        candidates: Candidates,
+    ) -> Vec<PhysicsUpdate> {
+        let Candidates {
+            mut forced,
+            mut ranked,
+        } = candidates;
+        if ranked.len() > budget {
+            ranked.sort_unstable_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap());
+            ranked.truncate(budget);
+        }
+        forced.extend(ranked.into_iter().map(|(_, update)| update));
+        for update in &forced {
+            self.last_sent.insert(update.uid, tick);
+        }
+        if tick % MAX_INTERVAL == 0 {
+            self.last_sent
+                .retain(|_, last_sent| tick.saturating_sub(*last_sent) < FORGET_TICKS);
+        }
+        forced
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(uid: u64) -> PhysicsUpdate {
+        PhysicsUpdate {
+            uid: Uid(uid),
+            pos: None,
+            vel: None,
+            ori: None,
+        }
+    }
+
+    #[test]
+    fn important_entities_are_sent_more_often() {
+        for distance in &[0.0f32, 50.0, 100.0, 400.0, 1000.0] {
+            let distance_sq = distance.powi(2);
+            assert!(
+                Importance::High.interval(distance_sq) <= Importance::Normal.interval(distance_sq)
+            );
+            assert!(
+                Importance::Normal.interval(distance_sq) <= Importance::Low.interval(distance_sq)
+            );
+        }
+        assert_eq!(Importance::High.interval(50.0f32.powi(2)), 1);
+    }
+
+    #[test]
+    fn budget_defers_least_important() {
+        let mut interest = Interest::default();
+        let mut candidates = Candidates::default();
+        for uid in 0..10 {
+            let importance = if uid < 5 {
+                Importance::High
+            } else {
+                Importance::Low
+            };
+            let priority = interest.due(Uid(uid), 0, 100, importance, 1).unwrap();
+            candidates.push(priority, update(uid));
+        }
+        candidates.force(update(10));
+        let mut sent = interest
+            .select(100, 5, candidates)
+            .into_iter()
+            .map(|u| u.uid.0)
+            .collect::<Vec<_>>();
+        sent.sort_unstable();
+        assert_eq!(sent, vec![0, 1, 2, 3, 4, 10]);
+
+        // The deferred ones get more urgent every tick until they are sent
+        let mut sent = Vec::new();
+        for tick in 101..110 {
+            let mut candidates = Candidates::default();
+            for uid in 0..10 {
+                let importance = if uid < 5 {
+                    Importance::High
+                } else {
+                    Importance::Low
+                };
+                if let Some(priority) = interest.due(Uid(uid), 0, tick, importance, 1) {
+                    candidates.push(priority, update(uid));
+                }
+            }
+            sent.extend(
+                interest
+                    .select(tick, 5, candidates)
+                    .into_iter()
+                    .map(|u| u.uid.0),
+            );
+        }
+        assert!((5..10).all(|uid| sent.contains(&uid)));
+    }
+}
@@ -17,18 +17,22 @@ pub mod chunk_generator;
 pub mod client;
 pub mod cmd;
 pub mod connection_handler;
+mod console;
 mod data_dir;
 pub mod error;
 pub mod events;
 pub mod input;
+pub mod interest;
 pub mod login_provider;
 pub mod metrics;
+pub mod movement_validator;
 pub mod persistence;
 pub mod presence;
 pub mod rtsim;
 pub mod settings;
 pub mod state_ext;
 pub mod sys;
+pub mod terrain_persistence;
 #[cfg(not(feature = "worldgen"))] mod test_world;
 
 // Reexports
@@ -46,12 +50,14 @@ use crate::{
     client::Client,
     cmd::ChatCommandExt,
     connection_handler::ConnectionHandler,
+    console::Console,
     data_dir::DataDir,
     login_provider::LoginProvider,
     presence::{Presence, RegionSubscription},
     rtsim::RtSim,
     state_ext::StateExt,
     sys::sentinel::{DeletedEntities, TrackedComps},
+    terrain_persistence::TerrainPersistence,
 };
 #[cfg(not(feature = "worldgen"))]
 use common::grid::Grid;
@@ -72,18 +78,20 @@ use common::{
 use common_ecs::run_now;
 use common_net::{
     msg::{
-        ClientType, DisconnectReason, ServerGeneral, ServerInfo, ServerInit, ServerMsg, WorldMapMsg,
+        ClientType, DisconnectReason, PlayerListUpdate, ServerGeneral, ServerInfo, ServerInit,
+        ServerMsg, WorldMapMsg,
     },
     sync::WorldSyncExt,
 };
 #[cfg(feature = "plugins")]
 use common_sys::plugin::memory_manager::EcsWorld;
 #[cfg(feature = "plugins")]
-use common_sys::plugin::PluginMgr;
+use common_sys::plugin::{storage::PluginStorage, PluginMgr};
 use common_sys::state::State;
 use metrics::{EcsSystemMetrics, PhysicsMetrics, TickMetrics};
 use network::{Network, Pid, ProtocolAddr};
 use persistence::{
+    backup::DatabaseBackups,
     character_loader::{CharacterLoader, CharacterLoaderResponseKind},
     character_updater::CharacterUpdater,
 };
@@ -158,9 +166,7 @@ impl Server {
             info!("Authentication is disabled");
         }
 
-        // Relative to data_dir
-        const PERSISTENCE_DB_DIR: &str = "saves";
-        let persistence_db_dir = data_dir.join(PERSISTENCE_DB_DIR);
+        let persistence_db_dir = data_dir.join(persistence::PERSISTENCE_DB_DIR);
 
         // Run pending DB migrations (if any)
         debug!("Running DB migrations...");
@@ -220,6 +226,35 @@ impl Server {
         state
             .ecs_mut()
             .insert(CharacterLoader::new(&persistence_db_dir)?);
+        state.ecs_mut().insert(DatabaseBackups::new(
+            &persistence_db_dir,
+            data_dir.join(persistence::BACKUP_DIR),
+            settings.database_backup_interval,
+            settings.database_backups_kept,
+        ));
+
+        // Player modifications to the terrain are stored alongside the character DB
+        if settings.terrain_persistence {
+            state
+                .ecs_mut()
+                .insert(TerrainPersistence::new(persistence_db_dir.join("terrain")));
+        }
+
+        #[cfg(feature = "plugins")]
+        {
+            // Plugin data is stored alongside the character DB
+            state.ecs_mut().insert(PluginStorage::new(
+                persistence::plugin_storage::DbPluginStorage::new(&persistence_db_dir)?,
+            ));
+            if settings.plugin_hot_reload {
+                if let Err(e) = state.ecs().write_resource::<PluginMgr>().watch() {
+                    tracing::warn!(
+                        ?e,
+                        "Failed to watch the plugin directory, plugins won't be hot reloaded"
+                    );
+                }
+            }
+        }
 
         // System schedulers to control execution of systems
         state
@@ -230,6 +265,18 @@ impl Server {
         state.ecs_mut().register::<RegionSubscription>();
         state.ecs_mut().register::<Client>();
         state.ecs_mut().register::<Presence>();
+        state
+            .ecs_mut()
+            .register::<movement_validator::MovementValidation>();
+        state
+            .ecs_mut()
+            .register::<sys::safe_spawn::PendingSafeSpawn>();
+        state.ecs_mut().register::<sys::entity_sync::PhysicsDelta>();
+        state.ecs_mut().register::<interest::Interest>();
+        state.ecs_mut().register::<events::Mailbox>();
+        state.ecs_mut().register::<events::BankAccess>();
+        state.ecs_mut().register::<Console>();
+        state.ecs_mut().register::<comp::Bank>();
         state.ecs_mut().register::<comp::HomeChunk>();
         state.ecs_mut().register::<login_provider::PendingLogin>();
 
@@ -363,6 +410,22 @@ impl Server {
         state.ecs_mut().insert(DeletedEntities::default());
 
         let network = Network::new_with_registry(Pid::new(), &runtime, &registry);
+        let identity = settings::load_or_create_identity(data_dir)
+            .map_err(|e| Error::Other(format!("Failed to load the server identity: {}", e)))?;
+        info!(
+            "Server identity: {}",
+            identity
+                .public_key()
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>()
+        );
+        runtime.block_on(network.set_identity(identity));
+        // Clients whose connection broke keep their entity till they time out, so they
+        // can attach a new connection in the meantime
+        runtime.block_on(network.set_resume_timeout(settings.client_timeout));
+        runtime.block_on(network.set_participant_bandwidth_limit(settings.max_player_bandwidth));
+        runtime.block_on(network.set_global_bandwidth_limit(settings.max_bandwidth));
         let metrics_shutdown = Arc::new(Notify::new());
         let metrics_shutdown_clone = Arc::clone(&metrics_shutdown);
         let addr = settings.metrics_address;
@@ -376,6 +439,26 @@ impl Server {
         });
         runtime.block_on(network.listen(ProtocolAddr::Tcp(settings.gameserver_address)))?;
         runtime.block_on(network.listen(ProtocolAddr::Mpsc(14004)))?;
+        #[cfg(feature = "quic")]
+        if let Some(quic_files) = &settings.quic_files {
+            match quic_files.quic_server_config() {
+                Ok(config) => {
+                    runtime.block_on(network.set_quic_server_config(config));
+                    runtime.block_on(
+                        network.listen(ProtocolAddr::Quic(settings.gameserver_address)),
+                    )?;
+                },
+                Err(e) => error!(
+                    ?e,
+                    ?quic_files,
+                    "Failed to load the certificate, QUIC is disabled"
+                ),
+            }
+        }
+        #[cfg(not(feature = "quic"))]
+        if settings.quic_files.is_some() {
+            tracing::warn!("The server was built without the quic feature, QUIC is disabled");
+        }
         let connection_handler = ConnectionHandler::new(network, &runtime);
 
         // Initiate real-time world simulation
@@ -489,7 +572,9 @@ impl Server {
         // 1) Build up a list of events for this frame, to be passed to the frontend.
         let mut frontend_events = Vec::new();
 
-        // 2)
+        // 2) Apply the actions plugins emitted since the last tick
+        #[cfg(feature = "plugins")]
+        self.handle_plugin_actions();
 
         let before_new_connections = Instant::now();
 
@@ -526,6 +611,16 @@ impl Server {
         self.state.update_region_map();
         self.state.apply_terrain_changes();
 
+        // Record the block changes made this tick so they survive restarts
+        if let Some(mut terrain_persistence) =
+            self.state.ecs().try_fetch_mut::<TerrainPersistence>()
+        {
+            for (pos, block) in self.state.terrain_changes().modified_blocks.iter() {
+                terrain_persistence.set_block(*pos, *block);
+            }
+            terrain_persistence.maintain();
+        }
+
         let before_sync = Instant::now();
 
         // 6) Synchronise clients with the new state of the world.
@@ -643,8 +738,29 @@ impl Server {
                         .read_resource::<EventBus<ServerEvent>>()
                         .emit_now(message);
                 },
+                CharacterLoaderResponseKind::MailRecipient(result, pending) => {
+                    events::handle_mail_recipient(self, query_result.entity, result, pending)
+                },
             });
 
+        self.state
+            .ecs()
+            .read_resource::<persistence::character_updater::CharacterUpdater>()
+            .mailboxes()
+            .for_each(|(entity, result)| events::handle_loaded_mailbox(self, entity, result));
+
+        self.state
+            .ecs()
+            .read_resource::<persistence::character_updater::CharacterUpdater>()
+            .sent_mails()
+            .for_each(|sent_mail| events::handle_sent_mail(self, sent_mail));
+
+        self.state
+            .ecs()
+            .read_resource::<persistence::character_updater::CharacterUpdater>()
+            .claimed_mails()
+            .for_each(|claimed_mail| events::handle_claimed_mail(self, claimed_mail));
+
         {
             // Check for new chunks; cancel and regenerate all chunks if the asset has been
             // reloaded. Note that all of these assignments are no-ops, so the
@@ -806,6 +922,12 @@ impl Server {
     where
         S: Into<ServerMsg>,
     {
+        if let Some(console) = self.state.ecs().write_storage::<Console>().get_mut(entity) {
+            if let ServerMsg::General(ServerGeneral::ChatMsg(msg)) = msg.into() {
+                console.push_output(msg.message);
+            }
+            return;
+        }
         self.state
             .ecs()
             .read_storage::<Client>()
@@ -848,6 +970,15 @@ impl Server {
                     uid: self.state.ecs().read_component().into(),
                     uid_allocator: &self.state.ecs().read_resource::<UidAllocator>().into(),
                     player: self.state.ecs().read_component().into(),
+                    pos: self.state.ecs().read_component().into(),
+                    inventory: self.state.ecs().read_component().into(),
+                    terrain: &self
+                        .state
+                        .ecs()
+                        .read_resource::<common::terrain::TerrainGrid>()
+                        .into(),
+                    time_of_day: &self.state.ecs().read_resource::<TimeOfDay>().into(),
+                    storage: &self.state.ecs().read_resource::<PluginStorage>().into(),
                 };
                 let rs = plugin_manager.execute_event(
                     &ecs_world,
@@ -928,23 +1059,31 @@ impl Server {
         }
     }
 
-    fn entity_is_admin(&self, entity: EcsEntity) -> bool {
+    fn entity_admin_role(&self, entity: EcsEntity) -> Option<comp::AdminRole> {
         self.state
             .read_storage::<comp::Admin>()
             .get(entity)
-            .is_some()
+            .map(|admin| admin.0)
+    }
+
+    /// Whether the role of `entity` allows it to use `cmd`
+    fn entity_can_use_command(&self, entity: EcsEntity, cmd: &ChatCommand) -> bool {
+        self.editable_settings()
+            .command_permissions
+            .allows(cmd, self.entity_admin_role(entity))
     }
 
     pub fn number_of_players(&self) -> i64 {
         self.state.ecs().read_storage::<Client>().join().count() as i64
     }
 
-    pub fn add_admin(&self, username: &str) {
+    pub fn add_admin(&self, username: &str, role: comp::AdminRole) {
         let mut editable_settings = self.editable_settings_mut();
         let login_provider = self.state.ecs().fetch::<LoginProvider>();
         let data_dir = self.data_dir();
-        if let Some(entity) = add_admin(
+        if let Some((entity, uid)) = add_admin(
             username,
+            role,
             &login_provider,
             &mut editable_settings,
             &data_dir.path,
@@ -954,13 +1093,23 @@ impl Server {
             (
                 &state.ecs().entities(),
                 &state.read_storage::<comp::Player>(),
+                &state.read_storage::<Uid>(),
             )
                 .join()
-                .find(|(_, player)| player.uuid() == uuid)
-                .map(|(e, _)| e)
+                .find(|(_, player, _)| player.uuid() == uuid)
+                .map(|(e, _, uid)| (e, *uid))
         }) {
             // Add admin component if the player is ingame
-            let _ = self.state.ecs().write_storage().insert(entity, comp::Admin);
+            let _ = self
+                .state
+                .ecs()
+                .write_storage()
+                .insert(entity, comp::Admin(role));
+            self.state
+                .notify_players(ServerGeneral::PlayerListUpdate(PlayerListUpdate::Admin(
+                    uid,
+                    Some(role),
+                )));
         };
     }
 
@@ -968,7 +1117,7 @@ impl Server {
         let mut editable_settings = self.editable_settings_mut();
         let login_provider = self.state.ecs().fetch::<LoginProvider>();
         let data_dir = self.data_dir();
-        if let Some(entity) = remove_admin(
+        if let Some((entity, uid)) = remove_admin(
             username,
             &login_provider,
             &mut editable_settings,
@@ -979,10 +1128,11 @@ impl Server {
             (
                 &state.ecs().entities(),
                 &state.read_storage::<comp::Player>(),
+                &state.read_storage::<Uid>(),
             )
                 .join()
-                .find(|(_, player)| player.uuid() == uuid)
-                .map(|(e, _)| e)
+                .find(|(_, player, _)| player.uuid() == uuid)
+                .map(|(e, _, uid)| (e, *uid))
         }) {
             // Remove admin component if the player is ingame
             let _ = self
@@ -990,9 +1140,73 @@ impl Server {
                 .ecs()
                 .write_storage::<comp::Admin>()
                 .remove(entity);
+            self.state
+                .notify_players(ServerGeneral::PlayerListUpdate(PlayerListUpdate::Admin(
+                    uid, None,
+                )));
         };
     }
 
+    /// Saves all characters that are logged in right away rather than waiting
+    /// for the next periodic save, e.g. before shutting down
+    pub fn save_all_characters(&self) {
+        sys::persistence::save_characters(self.state.ecs().system_data());
+    }
+
+    /// Take a backup of the character database in the background
+    pub fn backup_database(&self) {
+        self.state
+            .ecs()
+            .read_resource::<DatabaseBackups>()
+            .request_backup();
+    }
+
+    /// Executes a chat command (with or without the leading `/`) on behalf of
+    /// the operator of the server, with the permissions of an owner. Returns
+    /// the messages the command responded with.
+    ///
+    /// Commands that act on the player using them, such as `/tp`, have no
+    /// effect since the console has no body in the world.
+    pub fn execute_console_command(&mut self, cmd: &str) -> Vec<String> {
+        let entity = self.console_entity();
+        self.process_chat_cmd(entity, cmd.trim().trim_start_matches('/').to_owned());
+        self.state
+            .ecs()
+            .write_storage::<Console>()
+            .get_mut(entity)
+            .map(Console::take_output)
+            .unwrap_or_default()
+    }
+
+    /// Get the entity console commands are executed as, creating it if needed
+    fn console_entity(&mut self) -> EcsEntity {
+        let existing = (
+            &self.state.ecs().entities(),
+            &self.state.ecs().read_storage::<Console>(),
+        )
+            .join()
+            .map(|(entity, _)| entity)
+            .next();
+        existing.unwrap_or_else(|| {
+            self.state
+                .ecs_mut()
+                .create_entity_synced()
+                .with(comp::Admin(comp::AdminRole::Owner))
+                .with(Console::default())
+                .build()
+        })
+    }
+
+    /// The names of the players that are currently online
+    pub fn player_names(&self) -> Vec<String> {
+        self.state
+            .ecs()
+            .read_storage::<comp::Player>()
+            .join()
+            .map(|player| player.alias.clone())
+            .collect()
+    }
+
     /// Useful for testing without a client
     /// view_distance: distance in chunks that are persisted, this acts like the
     /// player view distance so it is actually a bit farther due to a buffer
@@ -1017,6 +1231,13 @@ impl Server {
 impl Drop for Server {
     fn drop(&mut self) {
         self.metrics_shutdown.notify_one();
+        // Give plugins a last chance to message the players
+        #[cfg(feature = "plugins")]
+        {
+            self.state
+                .with_plugins(|plugin_mgr, ecs_world| plugin_mgr.unload_all(ecs_world));
+            self.handle_plugin_actions();
+        }
         self.state
             .notify_players(ServerGeneral::Disconnect(DisconnectReason::Shutdown));
     }
@@ -1025,21 +1246,34 @@ impl Drop for Server {
 /// If successful returns the Some(uuid) of the added admin
 pub fn add_admin(
     username: &str,
+    role: comp::AdminRole,
     login_provider: &LoginProvider,
     editable_settings: &mut EditableSettings,
     data_dir: &std::path::Path,
 ) -> Option<common::uuid::Uuid> {
     use crate::settings::EditableSetting;
     match login_provider.username_to_uuid(username) {
-        Ok(uuid) => editable_settings.admins.edit(data_dir, |admins| {
-            if admins.insert(uuid) {
-                info!("Successfully added {} ({}) as an admin!", username, uuid);
-                Some(uuid)
-            } else {
-                info!("{} ({}) is already an admin!", username, uuid);
-                None
-            }
-        }),
+        Ok(uuid) => {
+            editable_settings
+                .admins
+                .edit(data_dir, |admins| match admins.insert(uuid, role) {
+                    Some(old_role) if old_role == role => {
+                        info!("{} ({}) is already a {}!", username, uuid, role);
+                        None
+                    },
+                    Some(old_role) => {
+                        info!(
+                            "Successfully changed the role of {} ({}) from {} to {}!",
+                            username, uuid, old_role, role
+                        );
+                        Some(uuid)
+                    },
+                    None => {
+                        info!("Successfully added {} ({}) as a {}!", username, uuid, role);
+                        Some(uuid)
+                    },
+                })
+        },
         Err(err) => {
             error!(
                 ?err,
@@ -1061,7 +1295,7 @@ pub fn remove_admin(
     use crate::settings::EditableSetting;
     match login_provider.username_to_uuid(username) {
         Ok(uuid) => editable_settings.admins.edit(data_dir, |admins| {
-            if admins.remove(&uuid) {
+            if admins.remove(&uuid).is_some() {
                 info!(
                     "Successfully removed {} ({}) from the admins",
                     username, uuid
@@ -1,5 +1,7 @@
-use crate::settings::BanRecord;
+use crate::settings::{Banlist, EditableSetting};
 use authc::{AuthClient, AuthClientError, AuthToken, Uuid};
+use chrono::Utc;
+use common::comp::AdminRole;
 use common_net::msg::RegisterError;
 #[cfg(feature = "plugins")]
 use common_sys::plugin::memory_manager::EcsWorld;
@@ -9,9 +11,9 @@ use hashbrown::{HashMap, HashSet};
 use plugin_api::event::{PlayerJoinEvent, PlayerJoinResult};
 use specs::Component;
 use specs_idvs::IdvStorage;
-use std::{str::FromStr, sync::Arc};
+use std::{net::IpAddr, path::Path, str::FromStr, sync::Arc};
 use tokio::{runtime::Runtime, sync::oneshot};
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 fn derive_uuid(username: &str) -> Uuid {
     let mut state = 144066263297769815596495629667062367629;
@@ -109,21 +111,49 @@ impl LoginProvider {
         pending: &mut PendingLogin,
         #[cfg(feature = "plugins")] world: &EcsWorld,
         #[cfg(feature = "plugins")] plugin_manager: &PluginMgr,
-        admins: &HashSet<Uuid>,
+        admins: &HashMap<Uuid, AdminRole>,
         whitelist: &HashSet<Uuid>,
-        banlist: &HashMap<Uuid, BanRecord>,
+        banlist: &mut Banlist,
+        data_dir: &Path,
+        ip_addr: Option<IpAddr>,
     ) -> Option<Result<(String, Uuid), RegisterError>> {
         match pending.pending_r.try_recv() {
             Ok(Err(e)) => Some(Err(e)),
             Ok(Ok((username, uuid))) => {
-                if let Some(ban_record) = banlist.get(&uuid) {
+                let now = Utc::now();
+                if let Some(ban_record) = banlist.active_ban(&uuid, now) {
                     // Pull reason string out of ban record and send a copy of it
-                    return Some(Err(RegisterError::Banned(ban_record.reason.clone())));
+                    return Some(Err(RegisterError::Banned(ban_record.login_message())));
+                }
+                if banlist
+                    .get(&uuid)
+                    .map_or(false, |entry| entry.current.is_some())
+                {
+                    info!(?username, "Ban has expired, lifting it");
+                    banlist.edit(data_dir, |b| b.lift_expired(&uuid, now));
+                }
+
+                // Refuse other accounts connecting from the address a banned player
+                // used, admins are exempt so that they can't lock themselves out
+                if let Some((banned_uuid, ban_record)) = ip_addr
+                    .filter(|_| !admins.contains_key(&uuid))
+                    .and_then(|ip_addr| banlist.linked_ban(ip_addr, now))
+                {
+                    warn!(
+                        ?username,
+                        ?banned_uuid,
+                        banned_username = ?ban_record.username_when_banned,
+                        "Refusing login from the address of a banned player"
+                    );
+                    return Some(Err(RegisterError::Banned(ban_record.login_message())));
                 }
 
                 // user can only join if he is admin, the whitelist is empty (everyone can join)
                 // or his name is in the whitelist
-                if !whitelist.is_empty() && !whitelist.contains(&uuid) && !admins.contains(&uuid) {
+                if !whitelist.is_empty()
+                    && !whitelist.contains(&uuid)
+                    && !admins.contains_key(&uuid)
+                {
                     return Some(Err(RegisterError::NotOnWhitelist));
                 }
                 #[cfg(feature = "plugins")]
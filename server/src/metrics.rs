@@ -22,12 +22,18 @@ pub struct EcsSystemMetrics {
     // need the Historgram
     pub system_length_hist: HistogramVec,
     pub system_length_count: IntCounterVec,
+    pub plugin_length_time: IntGaugeVec,
+    pub plugin_length_count: IntCounterVec,
+    pub plugin_calls_count: IntCounterVec,
+    pub plugin_fuel_count: IntCounterVec,
+    pub plugin_state: IntGaugeVec, // 0 running, 1 throttled, 2 disabled
 }
 
 pub struct PlayerMetrics {
     pub clients_connected: IntCounter,
     pub players_connected: IntCounter,
     pub clients_disconnected: IntCounterVec, // timeout, network_error, gracefully
+    pub movement_violations: IntCounterVec,  // speed, flight, collision
 }
 
 pub struct NetworkRequestMetrics {
@@ -125,11 +131,46 @@ impl EcsSystemMetrics {
             &["system"],
         )?;
 
+        let plugin_length_time = IntGaugeVec::new(
+            Opts::new(
+                "plugin_length_time",
+                "time in ns spent per plugin handling events in a tick",
+            ),
+            &["plugin"],
+        )?;
+        let plugin_length_count = IntCounterVec::new(
+            Opts::new(
+                "plugin_length_count",
+                "shows the time in ns spent per plugin handling events",
+            ),
+            &["plugin"],
+        )?;
+        let plugin_calls_count = IntCounterVec::new(
+            Opts::new("plugin_calls_count", "events handled per plugin"),
+            &["plugin"],
+        )?;
+        let plugin_fuel_count = IntCounterVec::new(
+            Opts::new("plugin_fuel_count", "wasm instructions executed per plugin"),
+            &["plugin"],
+        )?;
+        let plugin_state = IntGaugeVec::new(
+            Opts::new(
+                "plugin_state",
+                "whether a plugin is running (0), throttled (1) or disabled (2)",
+            ),
+            &["plugin"],
+        )?;
+
         registry.register(Box::new(system_length_hist.clone()))?;
         registry.register(Box::new(system_length_count.clone()))?;
         registry.register(Box::new(system_start_time.clone()))?;
         registry.register(Box::new(system_length_time.clone()))?;
         registry.register(Box::new(system_thread_avg.clone()))?;
+        registry.register(Box::new(plugin_length_time.clone()))?;
+        registry.register(Box::new(plugin_length_count.clone()))?;
+        registry.register(Box::new(plugin_calls_count.clone()))?;
+        registry.register(Box::new(plugin_fuel_count.clone()))?;
+        registry.register(Box::new(plugin_state.clone()))?;
 
         Ok(Self {
             system_length_hist,
@@ -137,6 +178,11 @@ impl EcsSystemMetrics {
             system_start_time,
             system_length_time,
             system_thread_avg,
+            plugin_length_time,
+            plugin_length_count,
+            plugin_calls_count,
+            plugin_fuel_count,
+            plugin_state,
         })
     }
 }
@@ -159,15 +205,24 @@ impl PlayerMetrics {
             ),
             &["reason"],
         )?;
+        let movement_violations = IntCounterVec::new(
+            Opts::new(
+                "movement_violations",
+                "shows the number of rejected player movement updates and the reason",
+            ),
+            &["kind"],
+        )?;
 
         registry.register(Box::new(clients_connected.clone()))?;
         registry.register(Box::new(players_connected.clone()))?;
         registry.register(Box::new(clients_disconnected.clone()))?;
+        registry.register(Box::new(movement_violations.clone()))?;
 
         Ok(Self {
             clients_connected,
             players_connected,
             clients_disconnected,
+            movement_violations,
         })
     }
 }
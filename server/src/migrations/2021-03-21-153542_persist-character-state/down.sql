@@ -0,0 +1,25 @@
+-- SQLite does not support dropping columns on tables so the entire table must be
+-- dropped and recreated without the new columns
+CREATE TABLE "_character_new" (
+	"character_id"	INT NOT NULL,
+	"player_uuid"	TEXT NOT NULL,
+	"alias"	TEXT NOT NULL,
+	"waypoint" TEXT,
+	PRIMARY KEY("character_id"),
+	FOREIGN KEY("character_id") REFERENCES "body"("body_id"),
+	FOREIGN KEY("character_id") REFERENCES "item"("item_id")
+);
+
+INSERT INTO _character_new
+SELECT  character_id,
+        player_uuid,
+        alias,
+        waypoint
+FROM    character;
+
+PRAGMA foreign_keys = OFF;
+
+DROP TABLE character;
+ALTER TABLE _character_new RENAME TO character;
+
+PRAGMA foreign_keys = ON;
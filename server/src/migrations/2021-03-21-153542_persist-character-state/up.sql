@@ -0,0 +1,6 @@
+-- Adds columns for the state of a character that is restored when logging back in: current health
+-- and energy, active buffs and the last position of the character
+ALTER TABLE character ADD COLUMN health INT NULL;
+ALTER TABLE character ADD COLUMN energy INT NULL;
+ALTER TABLE character ADD COLUMN buffs TEXT NULL;
+ALTER TABLE character ADD COLUMN last_position TEXT NULL;
@@ -0,0 +1,17 @@
+-- Deletes the mail pseudo-containers and the items in them before dropping
+-- the mail table
+WITH RECURSIVE
+parents AS (
+    SELECT  mail_id AS item_id
+    FROM    mail
+    UNION ALL
+    SELECT  item.item_id
+    FROM    item,
+            parents
+    WHERE   item.parent_container_item_id = parents.item_id
+)
+DELETE
+FROM    item
+WHERE EXISTS (SELECT 1 FROM parents WHERE parents.item_id = item.item_id);
+
+DROP TABLE mail;
@@ -0,0 +1,17 @@
+-- Creates the mail table. The items attached to a mail are stored in the item
+-- table below a pseudo-container item whose ID is the mail_id, which itself
+-- is a child of the recipient's character pseudo-container.
+CREATE TABLE mail (
+	mail_id	INTEGER NOT NULL,
+	recipient_id	INTEGER NOT NULL,
+	sender_alias	TEXT NOT NULL,
+	subject	TEXT NOT NULL,
+	body	TEXT NOT NULL,
+	sent_at	INTEGER NOT NULL,
+	is_read	BOOLEAN NOT NULL DEFAULT 0,
+	PRIMARY KEY(mail_id),
+	FOREIGN KEY(mail_id) REFERENCES item(item_id),
+	FOREIGN KEY(recipient_id) REFERENCES character(character_id)
+);
+
+CREATE INDEX idx_mail_recipient ON mail(recipient_id);
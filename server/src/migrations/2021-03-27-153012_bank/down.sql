@@ -0,0 +1,15 @@
+-- Deletes the bank pseudo-containers and the items in them
+WITH RECURSIVE
+parents AS (
+    SELECT  item_id
+    FROM    item
+    WHERE   item_definition_id = 'veloren.core.pseudo_containers.bank'
+    UNION ALL
+    SELECT  item.item_id
+    FROM    item,
+            parents
+    WHERE   item.parent_container_item_id = parents.item_id
+)
+DELETE
+FROM    item
+WHERE EXISTS (SELECT 1 FROM parents WHERE parents.item_id = item.item_id);
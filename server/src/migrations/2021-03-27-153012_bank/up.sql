@@ -0,0 +1,24 @@
+-- Creates a bank pseudo-container for each existing character. The bank items
+-- are stored below it, and it is itself a child of the character's
+-- pseudo-container, like the inventory and loadout pseudo-containers.
+CREATE TEMP TABLE _bank_containers AS
+SELECT  c.character_id,
+        (SELECT seq FROM sqlite_sequence WHERE name = 'entity')
+            + ROW_NUMBER() OVER (ORDER BY c.character_id) AS bank_container_id
+FROM    character c;
+
+INSERT
+INTO    entity (entity_id)
+SELECT  bank_container_id
+FROM    _bank_containers;
+
+INSERT
+INTO    item (item_id, parent_container_item_id, item_definition_id, stack_size, position)
+SELECT  bank_container_id,
+        character_id,
+        'veloren.core.pseudo_containers.bank',
+        1,
+        'bank'
+FROM    _bank_containers;
+
+DROP TABLE _bank_containers;
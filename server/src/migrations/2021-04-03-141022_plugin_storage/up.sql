@@ -0,0 +1,8 @@
+-- Creates the table plugins persist their data in. Each plugin has its own
+-- namespace of keys, the values are serialized by the plugin.
+CREATE TABLE plugin_storage (
+	plugin_name	TEXT NOT NULL,
+	storage_key	TEXT NOT NULL,
+	value	BLOB NOT NULL,
+	PRIMARY KEY(plugin_name, storage_key)
+);
@@ -0,0 +1,347 @@
+use common::{
+    comp::{Body, CharacterState, PhysicsState},
+    terrain::{Block, TerrainGrid},
+    vol::ReadVol,
+};
+use specs::Component;
+use specs_idvs::IdvStorage;
+use std::fmt::{self, Display};
+use vek::*;
+
+/// How much faster than the expected maximum speed a client may move before
+/// it is considered a violation. Covers prediction differences between the
+/// client and the server.
+const SPEED_TOLERANCE: f32 = 1.25;
+/// How many seconds worth of movement a client may catch up on at once, to
+/// allow for network jitter and messages arriving in bursts.
+const BURST_TIME: f32 = 0.5;
+/// Distance in blocks that is always allowed, regardless of speed.
+const DISTANCE_TOLERANCE: f32 = 1.0;
+/// Movement abilities (rolls, dashes, leaps, ...) apply forced movement that
+/// can greatly exceed the regular running speed.
+const ABILITY_SPEED_FACTOR: f32 = 3.0;
+const CLIMB_SPEED: f32 = 5.0;
+/// Fraction of a server-applied impulse that still counts towards the limits
+/// after one second.
+const IMPULSE_DECAY: f32 = 0.25;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// Moved further horizontally than possible
+    Speed,
+    /// Gained more height than possible, e.g. by flying or climbing without
+    /// a wall
+    Flight,
+    /// Moved into solid terrain
+    Collision,
+}
+
+impl Violation {
+    /// Label used for metrics
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Speed => "speed",
+            Self::Flight => "flight",
+            Self::Collision => "collision",
+        }
+    }
+}
+
+impl Display for Violation {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Speed => write!(formatter, "moved too fast"),
+            Self::Flight => write!(formatter, "gained height without support"),
+            Self::Collision => write!(formatter, "moved into terrain"),
+        }
+    }
+}
+
+/// The fastest a character is expected to move, in blocks per second
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MovementLimits {
+    pub horizontal: f32,
+    pub up: f32,
+}
+
+impl MovementLimits {
+    /// Estimate the limits from the server's view of the character. The
+    /// velocity reported by the client is deliberately not taken into account,
+    /// since it would allow the client to raise its own limits; impulses
+    /// applied by the server are tracked by [`MovementValidation`] instead.
+    pub fn new(
+        body: &Body,
+        character_state: Option<&CharacterState>,
+        physics_state: Option<&PhysicsState>,
+    ) -> Self {
+        let base_speed = body.max_speed_approx();
+        let jump = body.jump_impulse().unwrap_or(0.0);
+        let in_liquid = physics_state.map_or(false, |p| p.in_liquid.is_some());
+        let ground_vel = physics_state.map_or(Vec3::zero(), |p| p.ground_vel);
+
+        let (horizontal, up) = if body.can_fly().is_some() || in_liquid {
+            (base_speed, base_speed)
+        } else {
+            match character_state {
+                Some(CharacterState::Glide) => (base_speed, 0.0),
+                Some(CharacterState::Climb) => (base_speed, CLIMB_SPEED.max(jump)),
+                Some(CharacterState::Roll(_))
+                | Some(CharacterState::Boost(_))
+                | Some(CharacterState::DashMelee(_))
+                | Some(CharacterState::LeapMelee(_))
+                | Some(CharacterState::ComboMelee(_))
+                | Some(CharacterState::SpinMelee(_))
+                | Some(CharacterState::ChargedMelee(_))
+                | Some(CharacterState::Stunned(_)) => (
+                    base_speed * ABILITY_SPEED_FACTOR,
+                    jump * ABILITY_SPEED_FACTOR,
+                ),
+                _ => (base_speed, jump),
+            }
+        };
+
+        Self {
+            horizontal: horizontal + Vec2::<f32>::from(ground_vel).magnitude(),
+            up: up + ground_vel.z.max(0.0),
+        }
+    }
+}
+
+/// Per-client movement validation state, tracking how much movement the
+/// client has left and how often it tried to exceed that.
+#[derive(Clone, Debug, Default)]
+pub struct MovementValidation {
+    last_update: Option<f64>,
+    horizontal_budget: f32,
+    up_budget: f32,
+    /// Velocity the server added to the character (e.g. knockback) that the
+    /// client may still be moving with
+    impulse: Vec3<f32>,
+    pub speed_violations: u32,
+    pub flight_violations: u32,
+    pub collision_violations: u32,
+}
+
+impl MovementValidation {
+    pub fn total_violations(&self) -> u32 {
+        self.speed_violations + self.flight_violations + self.collision_violations
+    }
+
+    /// Allow for velocity the server applied to the character itself, which
+    /// may take the client beyond its regular limits for a short while.
+    pub fn apply_impulse(&mut self, impulse: Vec3<f32>) { self.impulse += impulse; }
+
+    /// Check a client-reported movement from `old_pos` to `new_pos` at time
+    /// `time` (in seconds). The movement is only deducted from the budget if
+    /// it is valid.
+    pub fn validate(
+        &mut self,
+        time: f64,
+        old_pos: Vec3<f32>,
+        new_pos: Vec3<f32>,
+        new_vel: Vec3<f32>,
+        limits: MovementLimits,
+    ) -> Result<(), Violation> {
+        let dt = self
+            .last_update
+            .map_or(BURST_TIME, |last| (time - last).max(0.0) as f32);
+        self.last_update = Some(time);
+
+        self.impulse *= IMPULSE_DECAY.powf(dt);
+        let limits = MovementLimits {
+            horizontal: limits.horizontal + Vec2::<f32>::from(self.impulse).magnitude(),
+            up: limits.up + self.impulse.z.max(0.0),
+        };
+
+        let refill = |budget: f32, limit: f32| {
+            let limit = limit * SPEED_TOLERANCE;
+            (budget + limit * dt).min(limit * BURST_TIME + DISTANCE_TOLERANCE)
+        };
+        self.horizontal_budget = refill(self.horizontal_budget, limits.horizontal);
+        self.up_budget = refill(self.up_budget, limits.up);
+
+        let horizontal = Vec2::<f32>::from(new_pos - old_pos).magnitude();
+        let up = (new_pos.z - old_pos.z).max(0.0);
+
+        if horizontal > self.horizontal_budget
+            || Vec2::<f32>::from(new_vel).magnitude() > limits.horizontal * SPEED_TOLERANCE
+        {
+            Err(Violation::Speed)
+        } else if up > self.up_budget || new_vel.z > limits.up * SPEED_TOLERANCE {
+            Err(Violation::Flight)
+        } else {
+            self.horizontal_budget -= horizontal;
+            self.up_budget -= up;
+            Ok(())
+        }
+    }
+
+    pub fn record(&mut self, violation: Violation) {
+        match violation {
+            Violation::Speed => self.speed_violations += 1,
+            Violation::Flight => self.flight_violations += 1,
+            Violation::Collision => self.collision_violations += 1,
+        }
+    }
+}
+
+impl Component for MovementValidation {
+    type Storage = IdvStorage<Self>;
+}
+
+/// Check that a position reported by a client is possible given the terrain:
+/// the body must not be inside solid blocks, and climbing requires a wall to
+/// climb on.
+pub fn check_terrain(
+    terrain: &TerrainGrid,
+    body: &Body,
+    character_state: Option<&CharacterState>,
+    old_pos: Vec3<f32>,
+    new_pos: Vec3<f32>,
+) -> Result<(), Violation> {
+    let is_filled = |pos: Vec3<f32>| {
+        terrain
+            .get(pos.map(|e| e.floor() as i32))
+            .map_or(false, |block: &Block| block.is_filled())
+    };
+
+    // Only the centre of the body is checked, since physics allows the edges
+    // of the collider to overlap blocks slightly
+    let height = body.height();
+    let bottom = new_pos.z + 0.5;
+    let top = (new_pos.z + height - 0.5).max(bottom);
+    let mut z = bottom;
+    while z <= top {
+        if is_filled(Vec3::new(new_pos.x, new_pos.y, z)) {
+            return Err(Violation::Collision);
+        }
+        z += 1.0;
+    }
+
+    if matches!(character_state, Some(CharacterState::Climb)) && new_pos.z > old_pos.z {
+        let reach = body.radius() + 1.0;
+        let has_wall = [
+            Vec2::unit_x(),
+            -Vec2::unit_x(),
+            Vec2::unit_y(),
+            -Vec2::unit_y(),
+        ]
+        .iter()
+        .any(|dir: &Vec2<f32>| {
+            (0..height.ceil().max(1.0) as i32).any(|dz| {
+                is_filled(new_pos + Vec3::<f32>::from(*dir * reach) + Vec3::unit_z() * dz as f32)
+            })
+        });
+        if !has_wall {
+            return Err(Violation::Flight);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMITS: MovementLimits = MovementLimits {
+        horizontal: 10.0,
+        up: 16.0,
+    };
+
+    fn walk(
+        validation: &mut MovementValidation,
+        steps: usize,
+        speed: f32,
+    ) -> Result<(), Violation> {
+        let dt = 1.0 / 30.0;
+        let mut pos = Vec3::zero();
+        for i in 1..=steps {
+            let new_pos = pos + Vec3::unit_x() * speed * dt as f32;
+            validation.validate(
+                i as f64 * dt,
+                pos,
+                new_pos,
+                Vec3::unit_x() * LIMITS.horizontal,
+                LIMITS,
+            )?;
+            pos = new_pos;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn normal_movement_is_accepted() {
+        let mut validation = MovementValidation::default();
+        assert_eq!(walk(&mut validation, 300, LIMITS.horizontal), Ok(()));
+    }
+
+    #[test]
+    fn sustained_speed_hack_is_rejected() {
+        let mut validation = MovementValidation::default();
+        assert_eq!(
+            walk(&mut validation, 300, LIMITS.horizontal * 2.0),
+            Err(Violation::Speed)
+        );
+    }
+
+    #[test]
+    fn escalating_velocity_is_rejected() {
+        let mut validation = MovementValidation::default();
+        let dt = 1.0 / 30.0;
+        let mut pos = Vec3::zero();
+        let result: Result<(), Violation> = (1..=300).try_for_each(|i| {
+            let speed = LIMITS.horizontal + i as f32 * 0.1;
+            let new_pos = pos + Vec3::unit_x() * speed * dt as f32;
+            validation.validate(i as f64 * dt, pos, new_pos, Vec3::unit_x() * speed, LIMITS)?;
+            pos = new_pos;
+            Ok(())
+        });
+        assert_eq!(result, Err(Violation::Speed));
+    }
+
+    #[test]
+    fn server_impulses_are_allowed_and_decay() {
+        let mut validation = MovementValidation::default();
+        validation.apply_impulse(Vec3::unit_x() * LIMITS.horizontal * 2.0);
+        let knocked_back = Vec3::unit_x() * LIMITS.horizontal * 2.0;
+        assert_eq!(
+            validation.validate(0.0, Vec3::zero(), Vec3::zero(), knocked_back, LIMITS),
+            Ok(())
+        );
+        assert_eq!(
+            validation.validate(5.0, Vec3::zero(), Vec3::zero(), knocked_back, LIMITS),
+            Err(Violation::Speed)
+        );
+    }
+
+    #[test]
+    fn teleport_is_rejected() {
+        let mut validation = MovementValidation::default();
+        let result = validation.validate(
+            0.0,
+            Vec3::zero(),
+            Vec3::new(100.0, 0.0, 0.0),
+            Vec3::zero(),
+            LIMITS,
+        );
+        assert_eq!(result, Err(Violation::Speed));
+    }
+
+    #[test]
+    fn rising_without_support_is_rejected() {
+        let mut validation = MovementValidation::default();
+        let gliding = MovementLimits {
+            horizontal: LIMITS.horizontal,
+            up: 0.0,
+        };
+        let result = validation.validate(
+            0.0,
+            Vec3::zero(),
+            Vec3::new(0.0, 0.0, 5.0),
+            Vec3::zero(),
+            gliding,
+        );
+        assert_eq!(result, Err(Violation::Flight));
+    }
+}
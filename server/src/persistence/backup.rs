@@ -0,0 +1,191 @@
+//! Online backups of the character database.
+//!
+//! Backups are taken with SQLite's online backup API, which produces a
+//! consistent snapshot even while the server keeps writing to the database, so
+//! the server doesn't need to be stopped to take one.
+
+use crate::persistence::{error::Error, DB_FILENAME};
+use chrono::Utc;
+use crossbeam_channel::RecvTimeoutError;
+use rusqlite::{Connection, DatabaseName};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tracing::{error, info, warn};
+
+const BACKUP_FILE_PREFIX: &str = "db-";
+const BACKUP_FILE_EXTENSION: &str = "sqlite";
+/// Shortest allowed time between periodic backups, so that a misconfigured
+/// interval can't keep the backup thread copying the database nonstop
+const MIN_BACKUP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Copy the database in `db_dir` to a new timestamped file in `backup_dir`,
+/// returning the path of the backup.
+pub fn backup_database(db_dir: &Path, backup_dir: &Path) -> Result<PathBuf, Error> {
+    let db_path = db_dir.join(DB_FILENAME);
+    // Opening a connection would otherwise create an empty database
+    if !db_path.exists() {
+        return Err(Error::OtherError(format!(
+            "No database found at {}",
+            db_path.display()
+        )));
+    }
+
+    fs::create_dir_all(backup_dir)?;
+    let backup_path = backup_dir.join(format!(
+        "{}{}.{}",
+        BACKUP_FILE_PREFIX,
+        Utc::now().format("%Y-%m-%d_%H-%M-%S"),
+        BACKUP_FILE_EXTENSION
+    ));
+    // Back up to a temporary file first so that an interrupted backup can't be
+    // mistaken for a complete one
+    let tmp_path = backup_path.with_extension("sqlite.tmp");
+
+    let connection = Connection::open(&db_path)?;
+    connection.busy_timeout(Duration::from_millis(250))?;
+    connection.backup(DatabaseName::Main, &tmp_path, None)?;
+    fs::rename(&tmp_path, &backup_path)?;
+
+    Ok(backup_path)
+}
+
+/// Delete the oldest backups in `backup_dir` so that at most `keep` remain.
+pub fn prune_backups(backup_dir: &Path, keep: usize) -> Result<(), Error> {
+    let mut backups = fs::read_dir(backup_dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| is_backup_file(path))
+        .collect::<Vec<_>>();
+
+    // The timestamp in the file name sorts chronologically
+    backups.sort();
+    let excess = backups.len().saturating_sub(keep);
+    for path in backups.into_iter().take(excess) {
+        fs::remove_file(&path)?;
+        info!(?path, "Removed old database backup");
+    }
+
+    Ok(())
+}
+
+fn is_backup_file(path: &Path) -> bool {
+    path.extension()
+        .map_or(false, |ext| ext == BACKUP_FILE_EXTENSION)
+        && path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map_or(false, |name| name.starts_with(BACKUP_FILE_PREFIX))
+}
+
+fn clamp_interval(interval: Duration) -> Duration {
+    if interval < MIN_BACKUP_INTERVAL {
+        warn!(
+            ?interval,
+            ?MIN_BACKUP_INTERVAL,
+            "Database backup interval is too short, using the minimum instead"
+        );
+        MIN_BACKUP_INTERVAL
+    } else {
+        interval
+    }
+}
+
+/// A resource that takes database backups in a background thread, both
+/// periodically and on request.
+pub struct DatabaseBackups {
+    request_tx: Option<crossbeam_channel::Sender<()>>,
+    handle: Option<std::thread::JoinHandle<()>>,
+}
+
+impl DatabaseBackups {
+    /// `interval` is the time between periodic backups (`None` disables
+    /// them, intervals below [`MIN_BACKUP_INTERVAL`] are raised to it), and
+    /// `keep` the number of backups to keep around.
+    pub fn new(
+        db_dir: &Path,
+        backup_dir: PathBuf,
+        interval: Option<Duration>,
+        keep: usize,
+    ) -> Self {
+        let interval = interval.map(clamp_interval);
+        let (request_tx, request_rx) = crossbeam_channel::unbounded::<()>();
+        let db_dir = db_dir.to_owned();
+
+        let builder = std::thread::Builder::new().name("persistence_backup".into());
+        let handle = builder
+            .spawn(move || {
+                loop {
+                    let request = match interval {
+                        Some(interval) => request_rx.recv_timeout(interval),
+                        None => request_rx
+                            .recv()
+                            .map_err(|_| RecvTimeoutError::Disconnected),
+                    };
+                    if let Err(RecvTimeoutError::Disconnected) = request {
+                        break;
+                    }
+
+                    match backup_database(&db_dir, &backup_dir) {
+                        Ok(path) => {
+                            info!(?path, "Database backup finished");
+                            if let Err(e) = prune_backups(&backup_dir, keep) {
+                                error!(?e, "Failed to remove old database backups");
+                            }
+                        },
+                        Err(e) => error!(?e, "Database backup failed"),
+                    }
+                }
+            })
+            .unwrap();
+
+        Self {
+            request_tx: Some(request_tx),
+            handle: Some(handle),
+        }
+    }
+
+    /// Take a backup as soon as possible
+    pub fn request_backup(&self) {
+        if let Err(e) = self.request_tx.as_ref().unwrap().send(()) {
+            error!(?e, "Could not request database backup");
+        }
+    }
+}
+
+impl Drop for DatabaseBackups {
+    fn drop(&mut self) {
+        drop(self.request_tx.take());
+        if let Err(e) = self.handle.take().unwrap().join() {
+            error!(?e, "Error from joining database backup thread");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_backups_are_recognised() {
+        assert!(is_backup_file(Path::new(
+            "backups/db-2021-03-21_15-35-42.sqlite"
+        )));
+        assert!(!is_backup_file(Path::new(
+            "backups/db-2021-03-21_15-35-42.sqlite.tmp"
+        )));
+        assert!(!is_backup_file(Path::new("backups/db.sqlite-wal")));
+        assert!(!is_backup_file(Path::new("backups/notes.sqlite")));
+    }
+
+    #[test]
+    fn short_intervals_are_clamped() {
+        assert_eq!(clamp_interval(Duration::from_secs(0)), MIN_BACKUP_INTERVAL);
+        assert_eq!(
+            clamp_interval(Duration::from_secs(3600)),
+            Duration::from_secs(3600)
+        );
+    }
+}
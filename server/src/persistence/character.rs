@@ -12,19 +12,28 @@ use crate::{
     comp::{item::MaterialStatManifest, Inventory},
     persistence::{
         character::conversions::{
-            convert_body_from_database, convert_body_to_database_json,
-            convert_character_from_database, convert_inventory_from_database_items,
-            convert_items_to_database_items, convert_loadout_from_database_items,
-            convert_skill_groups_to_database, convert_skills_to_database,
-            convert_stats_from_database, convert_waypoint_from_database_json,
-            convert_waypoint_to_database_json,
+            convert_bank_from_database_items, convert_body_from_database,
+            convert_body_to_database_json, convert_buffs_from_database_json,
+            convert_buffs_to_database_json, convert_character_from_database,
+            convert_inventory_from_database_items, convert_items_from_export,
+            convert_items_to_database_items, convert_items_to_export,
+            convert_loadout_from_database_items, convert_mail_items_from_database,
+            convert_mail_items_to_database, convert_position_from_database_json,
+            convert_position_to_database_json, convert_skill_groups_to_database,
+            convert_skills_to_database, convert_stats_from_database,
+            convert_waypoint_from_database_json, convert_waypoint_to_database_json, item_tree_size,
+            validate_exported_items,
         },
+        character_export::{CharacterExport, ExportedItem, CHARACTER_EXPORT_VERSION},
         character_loader::{CharacterCreationResult, CharacterDataResult, CharacterListResult},
         error::Error::DatabaseError,
-        PersistedComponents,
+        PersistedComponents, PersistedState,
     },
 };
-use common::character::{CharacterId, CharacterItem, MAX_CHARACTERS_PER_PLAYER};
+use common::{
+    character::{CharacterId, CharacterItem, MAX_CHARACTERS_PER_PLAYER},
+    mail::{MailError, MailId, MAX_MAILBOX_SIZE},
+};
 use core::ops::Range;
 use diesel::{prelude::*, sql_query, sql_types::BigInt};
 use std::{collections::VecDeque, sync::Arc};
@@ -40,14 +49,21 @@ pub(crate) type EntityId = i64;
 const CHARACTER_PSEUDO_CONTAINER_DEF_ID: &str = "veloren.core.pseudo_containers.character";
 const INVENTORY_PSEUDO_CONTAINER_DEF_ID: &str = "veloren.core.pseudo_containers.inventory";
 const LOADOUT_PSEUDO_CONTAINER_DEF_ID: &str = "veloren.core.pseudo_containers.loadout";
+const MAIL_PSEUDO_CONTAINER_DEF_ID: &str = "veloren.core.pseudo_containers.mail";
+/// Sender of the mails that return undeliverable items
+const RETURNED_MAIL_SENDER: &str = "Postmaster";
+const BANK_PSEUDO_CONTAINER_DEF_ID: &str = "veloren.core.pseudo_containers.bank";
 const INVENTORY_PSEUDO_CONTAINER_POSITION: &str = "inventory";
 const LOADOUT_PSEUDO_CONTAINER_POSITION: &str = "loadout";
+const BANK_PSEUDO_CONTAINER_POSITION: &str = "bank";
 const WORLD_PSEUDO_CONTAINER_ID: EntityId = 1;
 
 #[derive(Clone, Copy)]
 struct CharacterContainers {
+    character_id: CharacterId,
     inventory_container_id: EntityId,
     loadout_container_id: EntityId,
+    bank_container_id: EntityId,
 }
 
 /// BFS the inventory/loadout to ensure that each is topologically sorted in the
@@ -86,6 +102,7 @@ pub fn load_character_data(
 
     let inventory_items = load_items_bfs(connection, character_containers.inventory_container_id)?;
     let loadout_items = load_items_bfs(connection, character_containers.loadout_container_id)?;
+    let bank_items = load_items_bfs(connection, character_containers.bank_container_id)?;
 
     let character_data = character
         .filter(
@@ -112,6 +129,37 @@ pub fn load_character_data(
         }
     });
 
+    let char_buffs = character_data.buffs.as_ref().map_or_else(Vec::new, |x| {
+        convert_buffs_from_database_json(&x).unwrap_or_else(|e| {
+            warn!(
+                "Error reading buffs from database for character ID {}, error: {}",
+                char_id, e
+            );
+            Vec::new()
+        })
+    });
+
+    let char_position =
+        character_data.last_position.as_ref().and_then(
+            |x| match convert_position_from_database_json(&x) {
+                Ok(pos) => Some(pos),
+                Err(e) => {
+                    warn!(
+                        "Error reading position from database for character ID {}, error: {}",
+                        char_id, e
+                    );
+                    None
+                },
+            },
+        );
+
+    let char_state = PersistedState {
+        health: character_data.health.map(|h| h as u32),
+        energy: character_data.energy.map(|e| e as u32),
+        buffs: char_buffs,
+        position: char_position,
+    };
+
     let skill_data = schema::skill::dsl::skill
         .filter(schema::skill::dsl::entity_id.eq(char_id))
         .load::<Skill>(&*connection)?;
@@ -130,7 +178,9 @@ pub fn load_character_data(
             &loadout_items,
             msm,
         )?,
+        convert_bank_from_database_items(character_containers.bank_container_id, &bank_items, msm)?,
         char_waypoint,
+        char_state,
     ))
 }
 
@@ -197,49 +247,24 @@ pub fn create_character(
 
     use schema::{body, character, skill_group};
 
-    let (body, stats, inventory, waypoint) = persisted_components;
+    // A new character has no state worth restoring yet
+    let (body, stats, inventory, bank, waypoint, _) = persisted_components;
 
-    // Fetch new entity IDs for character, inventory and loadout
-    let mut new_entity_ids = get_new_entity_ids(connection, |next_id| next_id + 3)?;
+    // Fetch new entity IDs for character, inventory, loadout and bank
+    let mut new_entity_ids = get_new_entity_ids(connection, |next_id| next_id + 4)?;
 
     // Create pseudo-container items for character
     let character_id = new_entity_ids.next().unwrap();
     let inventory_container_id = new_entity_ids.next().unwrap();
     let loadout_container_id = new_entity_ids.next().unwrap();
+    let bank_container_id = new_entity_ids.next().unwrap();
 
-    let pseudo_containers = vec![
-        Item {
-            stack_size: 1,
-            item_id: character_id,
-            parent_container_item_id: WORLD_PSEUDO_CONTAINER_ID,
-            item_definition_id: CHARACTER_PSEUDO_CONTAINER_DEF_ID.to_owned(),
-            position: character_id.to_string(),
-        },
-        Item {
-            stack_size: 1,
-            item_id: inventory_container_id,
-            parent_container_item_id: character_id,
-            item_definition_id: INVENTORY_PSEUDO_CONTAINER_DEF_ID.to_owned(),
-            position: INVENTORY_PSEUDO_CONTAINER_POSITION.to_owned(),
-        },
-        Item {
-            stack_size: 1,
-            item_id: loadout_container_id,
-            parent_container_item_id: character_id,
-            item_definition_id: LOADOUT_PSEUDO_CONTAINER_DEF_ID.to_owned(),
-            position: LOADOUT_PSEUDO_CONTAINER_POSITION.to_owned(),
-        },
-    ];
-    let pseudo_container_count = diesel::insert_into(item)
-        .values(pseudo_containers)
-        .execute(&*connection)?;
-
-    if pseudo_container_count != 3 {
-        return Err(Error::OtherError(format!(
-            "Error inserting initial pseudo containers for character id {} (expected 3, actual {})",
-            character_id, pseudo_container_count
-        )));
-    }
+    insert_pseudo_containers(connection, CharacterContainers {
+        character_id,
+        inventory_container_id,
+        loadout_container_id,
+        bank_container_id,
+    })?;
 
     let skill_set = stats.skill_set;
 
@@ -299,6 +324,8 @@ pub fn create_character(
             loadout_container_id,
             &inventory,
             inventory_container_id,
+            &bank,
+            bank_container_id,
             &mut next_id,
         );
         inserts = inserts_;
@@ -376,6 +403,11 @@ pub fn delete_character(
         )));
     }
 
+    // Delete the character's mail. The mail pseudo-containers and the items in
+    // them are deleted along with the rest of the character's items below.
+    diesel::delete(schema::mail::dsl::mail.filter(schema::mail::dsl::recipient_id.eq(char_id)))
+        .execute(&*connection)?;
+
     // Delete all items, recursively walking all containers starting from the
     // "character" pseudo-container that is the root for all items owned by
     // a character.
@@ -399,9 +431,9 @@ pub fn delete_character(
     ))
     .execute(&*connection)?;
 
-    if item_count < 3 {
+    if item_count < 4 {
         return Err(Error::OtherError(format!(
-            "Error deleting from item table for char_id {} (expected at least 3 deletions, found \
+            "Error deleting from item table for char_id {} (expected at least 4 deletions, found \
              {})",
             char_id, item_count
         )));
@@ -410,6 +442,437 @@ pub fn delete_character(
     load_character_list(requesting_player_uuid, connection, msm)
 }
 
+/// Loads the mailbox of a character, oldest mail first
+pub fn load_mailbox(
+    char_id: CharacterId,
+    connection: VelorenTransaction,
+    msm: &MaterialStatManifest,
+) -> Result<Vec<common::mail::Mail>, Error> {
+    use schema::mail::dsl::*;
+
+    let mails = mail
+        .filter(recipient_id.eq(char_id))
+        .order(mail_id.asc())
+        .load::<Mail>(&*connection)?;
+
+    mails
+        .into_iter()
+        .map(|db_mail| {
+            let mail_items = load_items_bfs(connection, db_mail.mail_id)?;
+            Ok(common::mail::Mail {
+                id: db_mail.mail_id,
+                sender: db_mail.sender_alias,
+                subject: db_mail.subject,
+                body: db_mail.body,
+                sent_at: db_mail.sent_at,
+                read: db_mail.is_read,
+                items: convert_mail_items_from_database(db_mail.mail_id, &mail_items, msm)?,
+            })
+        })
+        .collect()
+}
+
+/// Finds the character that mail addressed to `recipient_alias` should be
+/// delivered to, checking that their mailbox has room for it
+pub fn find_mail_recipient(
+    recipient_alias: &str,
+    connection: VelorenTransaction,
+) -> Result<Result<CharacterId, MailError>, Error> {
+    let recipients = schema::character::dsl::character
+        .select(schema::character::dsl::character_id)
+        .filter(schema::character::dsl::alias.eq(recipient_alias))
+        .load::<CharacterId>(&*connection)?;
+
+    let recipient = match recipients.as_slice() {
+        [] => return Ok(Err(MailError::RecipientNotFound)),
+        [recipient] => *recipient,
+        _ => return Ok(Err(MailError::RecipientAmbiguous)),
+    };
+
+    if mailbox_full(recipient, connection)? {
+        Ok(Err(MailError::RecipientMailboxFull))
+    } else {
+        Ok(Ok(recipient))
+    }
+}
+
+fn mailbox_full(recipient: CharacterId, connection: VelorenTransaction) -> Result<bool, Error> {
+    use diesel::dsl::count_star;
+
+    let mail_count = schema::mail::dsl::mail
+        .select(count_star())
+        .filter(schema::mail::dsl::recipient_id.eq(recipient))
+        .first::<i64>(&*connection)?;
+
+    Ok(mail_count as usize >= MAX_MAILBOX_SIZE)
+}
+
+/// Stores a new mail, and the items attached to it, in the mailbox of
+/// `recipient`, unless their mailbox is full.
+///
+/// The mailbox size is checked again here, as the recipient's mailbox may have
+/// filled up since they were looked up.
+pub fn insert_mail(
+    recipient: CharacterId,
+    sender_alias: &str,
+    subject: &str,
+    body: &str,
+    items: &[comp::Item],
+    connection: VelorenTransaction,
+) -> Result<Result<MailId, MailError>, Error> {
+    if mailbox_full(recipient, connection)? {
+        return Ok(Err(MailError::RecipientMailboxFull));
+    }
+
+    store_mail(recipient, sender_alias, subject, body, items, connection).map(Ok)
+}
+
+/// Stores a mail with items that couldn't be delivered in the mailbox of their
+/// sender. This ignores the mailbox size, as the items would be lost otherwise.
+pub fn return_mail(
+    sender: CharacterId,
+    subject: &str,
+    items: &[comp::Item],
+    connection: VelorenTransaction,
+) -> Result<MailId, Error> {
+    store_mail(
+        sender,
+        RETURNED_MAIL_SENDER,
+        subject,
+        "The items attached to this mail could not be delivered.",
+        items,
+        connection,
+    )
+}
+
+fn store_mail(
+    recipient: CharacterId,
+    sender_alias: &str,
+    subject: &str,
+    body: &str,
+    items: &[comp::Item],
+    connection: VelorenTransaction,
+) -> Result<MailId, Error> {
+    let item_count = items.iter().map(item_tree_size).sum::<usize>();
+    let mut new_entity_ids =
+        get_new_entity_ids(connection, |next_id| next_id + 1 + item_count as i64)?;
+    let mail_id = new_entity_ids.next().unwrap();
+
+    // The mail's pseudo-container belongs to the recipient's character so that
+    // it is deleted along with the character
+    let pseudo_container = Item {
+        stack_size: 1,
+        item_id: mail_id,
+        parent_container_item_id: recipient,
+        item_definition_id: MAIL_PSEUDO_CONTAINER_DEF_ID.to_owned(),
+        position: mail_id.to_string(),
+    };
+    diesel::insert_into(schema::item::table)
+        .values(&pseudo_container)
+        .execute(&*connection)?;
+
+    let new_mail = Mail {
+        mail_id,
+        recipient_id: recipient,
+        sender_alias: sender_alias.to_owned(),
+        subject: subject.to_owned(),
+        body: body.to_owned(),
+        sent_at: chrono::Utc::now().timestamp(),
+        is_read: false,
+    };
+    let mail_count = diesel::insert_into(schema::mail::table)
+        .values(&new_mail)
+        .execute(&*connection)?;
+
+    if mail_count != 1 {
+        return Err(Error::OtherError(format!(
+            "Error inserting into mail table for mail_id {}",
+            mail_id
+        )));
+    }
+
+    let db_items = convert_mail_items_to_database(mail_id, items, &mut new_entity_ids)?;
+    let inserted_count = diesel::insert_into(schema::item::table)
+        .values(&db_items)
+        .execute(&*connection)?;
+
+    if inserted_count != item_count {
+        return Err(Error::OtherError(format!(
+            "Expected insertions={}, actual={}, for mail_id {}--unsafe to continue transaction.",
+            item_count, inserted_count, mail_id
+        )));
+    }
+
+    Ok(mail_id)
+}
+
+/// Fails unless `mail_id` is a mail in the mailbox of `char_id`
+fn check_mail_owner(
+    char_id: CharacterId,
+    mail_id: MailId,
+    connection: VelorenTransaction,
+) -> Result<(), Error> {
+    use schema::mail::dsl::*;
+
+    mail.filter(
+        schema::mail::dsl::mail_id
+            .eq(mail_id)
+            .and(recipient_id.eq(char_id)),
+    )
+    .first::<Mail>(&*connection)?;
+
+    Ok(())
+}
+
+/// Deletes all items below a container item, recursively walking containers
+/// inside of it, and returns the number of deleted items
+fn delete_items_in_container(
+    container_id: EntityId,
+    connection: VelorenTransaction,
+) -> Result<usize, Error> {
+    Ok(diesel::sql_query(format!(
+        "
+    WITH RECURSIVE
+    parents AS (
+        SELECT  item_id
+        FROM    item
+        WHERE   item.parent_container_item_id = {}
+        UNION ALL
+        SELECT  item.item_id
+        FROM    item,
+                parents
+        WHERE   item.parent_container_item_id = parents.item_id
+    )
+    DELETE
+    FROM    item
+    WHERE EXISTS (SELECT 1 FROM parents WHERE parents.item_id = item.item_id)",
+        container_id
+    ))
+    .execute(&*connection)?)
+}
+
+/// Removes the items from a mail after they have been claimed
+pub fn claim_mail_items(
+    char_id: CharacterId,
+    mail_id: MailId,
+    connection: VelorenTransaction,
+) -> Result<(), Error> {
+    check_mail_owner(char_id, mail_id, connection)?;
+
+    if delete_items_in_container(mail_id, connection)? == 0 {
+        // Claiming an empty mail would hand out items that don't exist anymore
+        return Err(Error::OtherError(format!(
+            "Mail {} of char_id {} has no items to claim",
+            mail_id, char_id
+        )));
+    }
+
+    Ok(())
+}
+
+pub fn mark_mail_read(
+    char_id: CharacterId,
+    mail_id: MailId,
+    connection: VelorenTransaction,
+) -> Result<(), Error> {
+    use schema::mail::dsl::*;
+
+    let mail_count = diesel::update(
+        mail.filter(
+            schema::mail::dsl::mail_id
+                .eq(mail_id)
+                .and(recipient_id.eq(char_id)),
+        ),
+    )
+    .set(is_read.eq(true))
+    .execute(&*connection)?;
+
+    if mail_count != 1 {
+        return Err(Error::OtherError(format!(
+            "Error marking mail {} of char_id {} as read",
+            mail_id, char_id
+        )));
+    }
+
+    Ok(())
+}
+
+/// Deletes a mail along with any items still attached to it
+pub fn delete_mail(
+    char_id: CharacterId,
+    mail_id: MailId,
+    connection: VelorenTransaction,
+) -> Result<(), Error> {
+    check_mail_owner(char_id, mail_id, connection)?;
+
+    delete_items_in_container(mail_id, connection)?;
+    diesel::delete(schema::mail::dsl::mail.filter(schema::mail::dsl::mail_id.eq(mail_id)))
+        .execute(&*connection)?;
+    diesel::delete(schema::item::dsl::item.filter(schema::item::dsl::item_id.eq(mail_id)))
+        .execute(&*connection)?;
+
+    Ok(())
+}
+
+/// Export a character's body, skills and items in a format that can be
+/// imported into another database
+pub fn export_character(
+    char_id: CharacterId,
+    connection: VelorenTransaction,
+) -> Result<CharacterExport, Error> {
+    use schema::{body::dsl::*, character::dsl::*, skill_group::dsl::*};
+
+    let character_data = character
+        .filter(schema::character::dsl::character_id.eq(char_id))
+        .first::<Character>(&*connection)?;
+
+    let char_body = body
+        .filter(schema::body::dsl::body_id.eq(char_id))
+        .first::<Body>(&*connection)?;
+
+    let skill_data = schema::skill::dsl::skill
+        .filter(schema::skill::dsl::entity_id.eq(char_id))
+        .load::<Skill>(&*connection)?;
+
+    let skill_group_data = skill_group
+        .filter(schema::skill_group::dsl::entity_id.eq(char_id))
+        .load::<SkillGroup>(&*connection)?;
+
+    let character_containers = get_pseudo_containers(connection, char_id)?;
+    let inventory_items = load_items_bfs(connection, character_containers.inventory_container_id)?;
+    let loadout_items = load_items_bfs(connection, character_containers.loadout_container_id)?;
+    let bank_items = load_items_bfs(connection, character_containers.bank_container_id)?;
+
+    let stats = convert_stats_from_database(character_data.alias, &skill_data, &skill_group_data);
+
+    Ok(CharacterExport {
+        version: CHARACTER_EXPORT_VERSION,
+        alias: stats.name,
+        body: convert_body_from_database(&char_body)?,
+        skill_groups: stats.skill_set.skill_groups,
+        skills: stats.skill_set.skills.into_iter().collect(),
+        inventory: convert_items_to_export(
+            character_containers.inventory_container_id,
+            &inventory_items,
+        ),
+        loadout: convert_items_to_export(character_containers.loadout_container_id, &loadout_items),
+        bank: convert_items_to_export(character_containers.bank_container_id, &bank_items),
+    })
+}
+
+/// Import an exported character as a new character of the given player,
+/// returning the ID of the new character
+pub fn import_character(
+    uuid: &str,
+    export: &CharacterExport,
+    connection: VelorenTransaction,
+) -> Result<CharacterId, Error> {
+    use schema::{body, character, item, skill, skill_group};
+
+    check_character_limit(uuid, connection)?;
+
+    if !matches!(export.body, comp::Body::Humanoid(_)) {
+        return Err(Error::OtherError(
+            "Only humanoid characters can be imported".to_string(),
+        ));
+    }
+    validate_exported_items(&export.inventory)?;
+    validate_exported_items(&export.loadout)?;
+    validate_exported_items(&export.bank)?;
+
+    let item_count = export
+        .inventory
+        .iter()
+        .chain(export.loadout.iter())
+        .chain(export.bank.iter())
+        .map(ExportedItem::count)
+        .sum::<usize>();
+    let mut new_entity_ids =
+        get_new_entity_ids(connection, |next_id| next_id + 4 + item_count as i64)?;
+
+    let character_id = new_entity_ids.next().unwrap();
+    let inventory_container_id = new_entity_ids.next().unwrap();
+    let loadout_container_id = new_entity_ids.next().unwrap();
+    let bank_container_id = new_entity_ids.next().unwrap();
+
+    insert_pseudo_containers(connection, CharacterContainers {
+        character_id,
+        inventory_container_id,
+        loadout_container_id,
+        bank_container_id,
+    })?;
+
+    let new_body = Body {
+        body_id: character_id,
+        body_data: convert_body_to_database_json(&export.body)?,
+        variant: "humanoid".to_string(),
+    };
+    diesel::insert_into(body::table)
+        .values(&new_body)
+        .execute(&*connection)?;
+
+    let new_character = NewCharacter {
+        character_id,
+        player_uuid: uuid,
+        alias: &export.alias,
+        waypoint: None,
+    };
+    let character_count = diesel::insert_into(character::table)
+        .values(&new_character)
+        .execute(&*connection)?;
+
+    if character_count != 1 {
+        return Err(Error::OtherError(format!(
+            "Error inserting into character table for char_id {}",
+            character_id
+        )));
+    }
+
+    let db_skill_groups =
+        convert_skill_groups_to_database(character_id, export.skill_groups.clone());
+    diesel::insert_into(skill_group::table)
+        .values(&db_skill_groups)
+        .execute(&*connection)?;
+
+    let db_skills =
+        convert_skills_to_database(character_id, export.skills.iter().copied().collect());
+    diesel::insert_into(skill::table)
+        .values(&db_skills)
+        .execute(&*connection)?;
+
+    let mut db_items = Vec::with_capacity(item_count);
+    convert_items_from_export(
+        inventory_container_id,
+        &export.inventory,
+        &mut new_entity_ids,
+        &mut db_items,
+    )?;
+    convert_items_from_export(
+        loadout_container_id,
+        &export.loadout,
+        &mut new_entity_ids,
+        &mut db_items,
+    )?;
+    convert_items_from_export(
+        bank_container_id,
+        &export.bank,
+        &mut new_entity_ids,
+        &mut db_items,
+    )?;
+    let inserted_count = diesel::insert_into(item::table)
+        .values(&db_items)
+        .execute(&*connection)?;
+
+    if inserted_count != item_count {
+        return Err(Error::OtherError(format!(
+            "Expected insertions={}, actual={}, for char_id {}--unsafe to continue transaction.",
+            item_count, inserted_count, character_id
+        )));
+    }
+
+    Ok(character_id)
+}
+
 /// Before creating a character, we ensure that the limit on the number of
 /// characters has not been exceeded
 pub fn check_character_limit(uuid: &str, connection: VelorenTransaction) -> Result<(), Error> {
@@ -496,12 +959,71 @@ fn get_new_entity_ids(
     Ok(new_ids)
 }
 
+/// Inserts the pseudo-containers that a character's items are stored in
+fn insert_pseudo_containers(
+    connection: VelorenTransaction,
+    containers: CharacterContainers,
+) -> Result<(), Error> {
+    use schema::item::dsl::*;
+
+    let CharacterContainers {
+        character_id,
+        inventory_container_id,
+        loadout_container_id,
+        bank_container_id,
+    } = containers;
+
+    let pseudo_containers = vec![
+        Item {
+            stack_size: 1,
+            item_id: character_id,
+            parent_container_item_id: WORLD_PSEUDO_CONTAINER_ID,
+            item_definition_id: CHARACTER_PSEUDO_CONTAINER_DEF_ID.to_owned(),
+            position: character_id.to_string(),
+        },
+        Item {
+            stack_size: 1,
+            item_id: inventory_container_id,
+            parent_container_item_id: character_id,
+            item_definition_id: INVENTORY_PSEUDO_CONTAINER_DEF_ID.to_owned(),
+            position: INVENTORY_PSEUDO_CONTAINER_POSITION.to_owned(),
+        },
+        Item {
+            stack_size: 1,
+            item_id: loadout_container_id,
+            parent_container_item_id: character_id,
+            item_definition_id: LOADOUT_PSEUDO_CONTAINER_DEF_ID.to_owned(),
+            position: LOADOUT_PSEUDO_CONTAINER_POSITION.to_owned(),
+        },
+        Item {
+            stack_size: 1,
+            item_id: bank_container_id,
+            parent_container_item_id: character_id,
+            item_definition_id: BANK_PSEUDO_CONTAINER_DEF_ID.to_owned(),
+            position: BANK_PSEUDO_CONTAINER_POSITION.to_owned(),
+        },
+    ];
+    let pseudo_container_count = diesel::insert_into(item)
+        .values(pseudo_containers)
+        .execute(&*connection)?;
+
+    if pseudo_container_count != 4 {
+        return Err(Error::OtherError(format!(
+            "Error inserting initial pseudo containers for character id {} (expected 4, actual {})",
+            character_id, pseudo_container_count
+        )));
+    }
+
+    Ok(())
+}
+
 /// Fetches the pseudo_container IDs for a character
 fn get_pseudo_containers(
     connection: VelorenTransaction,
     character_id: CharacterId,
 ) -> Result<CharacterContainers, Error> {
     let character_containers = CharacterContainers {
+        character_id,
         loadout_container_id: get_pseudo_container_id(
             connection,
             character_id,
@@ -512,6 +1034,11 @@ fn get_pseudo_containers(
             character_id,
             INVENTORY_PSEUDO_CONTAINER_POSITION,
         )?,
+        bank_container_id: get_pseudo_container_id(
+            connection,
+            character_id,
+            BANK_PSEUDO_CONTAINER_POSITION,
+        )?,
     };
 
     Ok(character_containers)
@@ -549,7 +1076,9 @@ pub fn update(
     char_id: CharacterId,
     char_stats: comp::Stats,
     inventory: comp::Inventory,
+    bank: comp::Bank,
     char_waypoint: Option<comp::Waypoint>,
+    char_state: PersistedState,
     connection: VelorenTransaction,
 ) -> Result<Vec<Arc<common::comp::item::ItemId>>, Error> {
     use super::schema::{character::dsl::*, item::dsl::*, skill_group::dsl::*};
@@ -565,6 +1094,8 @@ pub fn update(
             pseudo_containers.loadout_container_id,
             &inventory,
             pseudo_containers.inventory_container_id,
+            &bank,
+            pseudo_containers.bank_container_id,
             &mut next_id,
         );
         upserts = upserts_;
@@ -576,6 +1107,7 @@ pub fn update(
     let mut existing_item_ids: Vec<i64> = vec![
         pseudo_containers.inventory_container_id,
         pseudo_containers.loadout_container_id,
+        pseudo_containers.bank_container_id,
     ];
     for it in load_items_bfs(connection, pseudo_containers.inventory_container_id)? {
         existing_item_ids.push(it.item_id);
@@ -583,6 +1115,9 @@ pub fn update(
     for it in load_items_bfs(connection, pseudo_containers.loadout_container_id)? {
         existing_item_ids.push(it.item_id);
     }
+    for it in load_items_bfs(connection, pseudo_containers.bank_container_id)? {
+        existing_item_ids.push(it.item_id);
+    }
     let existing_items = parent_container_item_id.eq_any(existing_item_ids);
     let non_upserted_items = item_id.ne_all(
         upserts
@@ -666,12 +1201,23 @@ pub fn update(
         .execute(&*connection)?;
 
     let db_waypoint = convert_waypoint_to_database_json(char_waypoint);
-    let waypoint_count =
+    let db_buffs = if char_state.buffs.is_empty() {
+        None
+    } else {
+        Some(convert_buffs_to_database_json(&char_state.buffs)?)
+    };
+    let character_count =
         diesel::update(character.filter(schema::character::dsl::character_id.eq(char_id)))
-            .set(waypoint.eq(db_waypoint))
+            .set((
+                waypoint.eq(db_waypoint),
+                health.eq(char_state.health.map(|h| h as i32)),
+                energy.eq(char_state.energy.map(|e| e as i32)),
+                buffs.eq(db_buffs),
+                last_position.eq(convert_position_to_database_json(char_state.position)),
+            ))
             .execute(&*connection)?;
 
-    if waypoint_count != 1 {
+    if character_count != 1 {
         return Err(Error::OtherError(format!(
             "Error updating character table for char_id {}",
             char_id
@@ -4,8 +4,9 @@ use crate::persistence::{
 };
 
 use crate::persistence::{
+    character_export::ExportedItem,
     error::Error,
-    json_models::{self, CharacterPosition, HumanoidBody},
+    json_models::{self, CharacterLastPosition, CharacterPosition, DatabaseBuff, HumanoidBody},
 };
 use common::{
     character::CharacterId,
@@ -14,7 +15,7 @@ use common::{
             item::MaterialStatManifest,
             loadout::{Loadout, LoadoutError},
             loadout_builder::LoadoutBuilder,
-            slot::InvSlotId,
+            slot::{BankSlotId, InvSlotId},
         },
         skills, Body as CompBody, Waypoint, *,
     },
@@ -22,7 +23,7 @@ use common::{
 };
 use core::{convert::TryFrom, num::NonZeroU64};
 use hashbrown::HashMap;
-use std::{collections::VecDeque, sync::Arc};
+use std::{collections::VecDeque, sync::Arc, time::Duration};
 
 #[derive(Debug)]
 pub struct ItemModelPair {
@@ -38,10 +39,15 @@ pub struct ItemModelPair {
 /// inventories. Although loadout items do store items inside them this does
 /// not currently utilise `parent_container_id` - all loadout items have the
 /// loadout pseudo-container as their parent.
+///
+/// The bank is converted together with the inventory so that items which were
+/// moved between the two keep their IDs.
 pub fn convert_items_to_database_items(
     loadout_container_id: EntityId,
     inventory: &Inventory,
     inventory_container_id: EntityId,
+    bank: &Bank,
+    bank_container_id: EntityId,
     next_id: &mut i64,
 ) -> Vec<ItemModelPair> {
     let loadout = inventory
@@ -57,13 +63,23 @@ pub fn convert_items_to_database_items(
         )
     });
 
+    // Bank slots.
+    let bank = bank.slots_with_id().map(|(pos, item)| {
+        (
+            serde_json::to_string(&pos).expect("failed to serialize BankSlotId"),
+            item.as_ref(),
+            bank_container_id,
+        )
+    });
+
     // Use Breadth-first search to recurse into containers/modular weapons to store
     // their parts
-    let mut bfs_queue: VecDeque<_> = inventory.chain(loadout).collect();
+    let mut bfs_queue: VecDeque<_> = inventory.chain(loadout).chain(bank).collect();
     let mut upserts = Vec::new();
     let mut depth = HashMap::new();
     depth.insert(inventory_container_id, 0);
     depth.insert(loadout_container_id, 0);
+    depth.insert(bank_container_id, 0);
     while let Some((position, item, parent_container_item_id)) = bfs_queue.pop_front() {
         // Construct new items.
         if let Some(item) = item {
@@ -216,6 +232,81 @@ pub fn convert_waypoint_from_database_json(position: &str) -> Result<Waypoint, E
     Ok(Waypoint::new(character_position.waypoint, Time(0.0)))
 }
 
+pub fn convert_position_to_database_json(pos: Option<Pos>) -> Option<String> {
+    pos.and_then(|pos| {
+        serde_json::to_string(&CharacterLastPosition { pos: pos.0 })
+            .map_err(|err| Error::ConversionError(format!("Error encoding position: {:?}", err)))
+            .ok()
+    })
+}
+
+pub fn convert_position_from_database_json(position: &str) -> Result<Pos, Error> {
+    let last_position =
+        serde_json::de::from_str::<CharacterLastPosition>(position).map_err(|err| {
+            Error::ConversionError(format!(
+                "Error de-serializing position: {} err: {}",
+                position, err
+            ))
+        })?;
+    Ok(Pos(last_position.pos))
+}
+
+/// Natural and aura buffs are not persisted, as they are reapplied by the
+/// world, and are skipped.
+pub fn convert_buffs_to_database_json(buffs: &[Buff]) -> Result<String, Error> {
+    let db_buffs = buffs
+        .iter()
+        .filter_map(|buff| {
+            Some(DatabaseBuff {
+                kind: json_models::buff_kind_to_db_string(buff.kind),
+                strength: buff.data.strength,
+                duration: buff.data.duration.map(|d| d.as_secs_f32()),
+                remaining: buff.time.map(|t| t.as_secs_f32()),
+                categories: buff
+                    .cat_ids
+                    .iter()
+                    .map(|cat| json_models::buff_category_to_db_string(*cat))
+                    .collect::<Option<_>>()?,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    serde_json::to_string(&db_buffs).map_err(Error::SerializationError)
+}
+
+/// Buffs of kinds that no longer exist are skipped.
+pub fn convert_buffs_from_database_json(buffs: &str) -> Result<Vec<Buff>, Error> {
+    let db_buffs = serde_json::de::from_str::<Vec<DatabaseBuff>>(buffs).map_err(|err| {
+        Error::ConversionError(format!(
+            "Error de-serializing buffs: {} err: {}",
+            buffs, err
+        ))
+    })?;
+
+    Ok(db_buffs
+        .into_iter()
+        .filter_map(|db_buff| {
+            let kind = json_models::db_string_to_buff_kind(&db_buff.kind)?;
+            let cat_ids = db_buff
+                .categories
+                .iter()
+                .filter_map(|cat| json_models::db_string_to_buff_category(cat))
+                .collect();
+            let mut buff = Buff::new(
+                kind,
+                BuffData::new(
+                    db_buff.strength,
+                    db_buff.duration.map(Duration::from_secs_f32),
+                ),
+                cat_ids,
+                BuffSource::Unknown,
+            );
+            buff.time = db_buff.remaining.map(Duration::from_secs_f32);
+            Some(buff)
+        })
+        .collect())
+}
+
 /// Properly-recursive items (currently modular weapons) occupy the same
 /// inventory slot as their parent. The caller is responsible for ensuring that
 /// inventory_items and loadout_items are topologically sorted (i.e. forall i,
@@ -315,6 +406,85 @@ pub fn convert_inventory_from_database_items(
     Ok(inventory)
 }
 
+/// Converts the items in a bank back into a bank. Like
+/// `convert_inventory_from_database_items`, the caller is responsible for
+/// ensuring that the items are topologically sorted.
+pub fn convert_bank_from_database_items(
+    bank_container_id: EntityId,
+    database_items: &[Item],
+    msm: &MaterialStatManifest,
+) -> Result<Bank, Error> {
+    let mut bank = Bank::default();
+    let mut item_indices = HashMap::new();
+
+    for (i, db_item) in database_items.iter().enumerate() {
+        item_indices.insert(db_item.item_id, i);
+
+        let mut item = get_item_from_asset(db_item.item_definition_id.as_str())?;
+
+        // NOTE: Since this is freshly loaded, the atomic is *unique.*
+        let comp = item.get_item_id_for_database();
+        comp.store(Some(NonZeroU64::try_from(db_item.item_id as u64).map_err(
+            |_| Error::ConversionError("Item with zero item_id".to_owned()),
+        )?));
+
+        if db_item.stack_size == 1 || item.is_stackable() {
+            item.set_amount(u32::try_from(db_item.stack_size).map_err(|_| {
+                Error::ConversionError(format!(
+                    "Invalid item stack size for stackable={}: {}",
+                    item.is_stackable(),
+                    &db_item.stack_size
+                ))
+            })?)
+            .map_err(|_| Error::ConversionError("Error setting amount for item".to_owned()))?;
+        }
+
+        let slot = |s: &str| {
+            serde_json::from_str::<BankSlotId>(s).map_err(|_| {
+                Error::ConversionError(format!(
+                    "Failed to parse bank item position: {:?}",
+                    &db_item.position
+                ))
+            })
+        };
+
+        if db_item.parent_container_item_id == bank_container_id {
+            let slot = slot(&db_item.position)?;
+            match bank.insert_at(slot, item) {
+                Ok(None) => {},
+                Ok(Some(_)) => {
+                    return Err(Error::ConversionError(
+                        "Inserted an item into the same bank slot twice".to_string(),
+                    ));
+                },
+                Err(_) => {
+                    return Err(Error::ConversionError(format!(
+                        "Error inserting item into bank, position: {:?}",
+                        slot
+                    )));
+                },
+            }
+        } else if let Some(&j) = item_indices.get(&db_item.parent_container_item_id) {
+            if let Some(Some(parent)) = bank.slot_mut(slot(&database_items[j].position)?) {
+                parent.add_component(item, msm);
+            } else {
+                return Err(Error::ConversionError(format!(
+                    "Parent slot {} for component {} was empty even though it occurred earlier in \
+                     the loop?",
+                    db_item.parent_container_item_id, db_item.item_id
+                )));
+            }
+        } else {
+            return Err(Error::ConversionError(format!(
+                "Couldn't find parent item {} before item {} in bank",
+                db_item.parent_container_item_id, db_item.item_id
+            )));
+        }
+    }
+
+    Ok(bank)
+}
+
 pub fn convert_loadout_from_database_items(
     loadout_container_id: i64,
     database_items: &[Item],
@@ -494,3 +664,266 @@ pub fn convert_skills_to_database(
         })
         .collect()
 }
+
+/// The number of database items needed to store an item, including its
+/// components
+pub fn item_tree_size(item: &common::comp::Item) -> usize {
+    1 + item.components().iter().map(item_tree_size).sum::<usize>()
+}
+
+/// Converts the items attached to a mail into new database items below the
+/// mail's pseudo-container, taking their IDs from `new_ids`
+pub fn convert_mail_items_to_database(
+    mail_id: EntityId,
+    items: &[common::comp::Item],
+    new_ids: &mut impl Iterator<Item = EntityId>,
+) -> Result<Vec<Item>, Error> {
+    let mut bfs_queue = items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| (i.to_string(), item, mail_id))
+        .collect::<VecDeque<_>>();
+    let mut db_items = Vec::new();
+
+    while let Some((position, item, parent_container_item_id)) = bfs_queue.pop_front() {
+        let item_id = new_ids
+            .next()
+            .ok_or_else(|| Error::OtherError("Ran out of entity IDs for mail items".to_string()))?;
+
+        for (i, component) in item.components().iter().enumerate() {
+            bfs_queue.push_back((format!("component_{}", i), component, item_id));
+        }
+
+        db_items.push(Item {
+            item_id,
+            parent_container_item_id,
+            item_definition_id: item.item_definition_id().to_owned(),
+            stack_size: if item.is_stackable() {
+                item.amount() as i32
+            } else {
+                1
+            },
+            position,
+        });
+    }
+
+    Ok(db_items)
+}
+
+/// Converts the database items attached to a mail, as loaded by
+/// `load_items_bfs`, back into items. Like loadout items, only a single level
+/// of components is supported.
+///
+/// The items don't keep their database IDs, since claiming them moves them
+/// into an inventory as new items.
+pub fn convert_mail_items_from_database(
+    mail_id: EntityId,
+    database_items: &[Item],
+    msm: &MaterialStatManifest,
+) -> Result<Vec<common::comp::Item>, Error> {
+    let mut items: Vec<(EntityId, common::comp::Item)> = Vec::new();
+
+    for db_item in database_items {
+        let mut item = get_item_from_asset(db_item.item_definition_id.as_str())?;
+
+        if db_item.stack_size == 1 || item.is_stackable() {
+            item.set_amount(u32::try_from(db_item.stack_size).map_err(|_| {
+                Error::ConversionError(format!(
+                    "Invalid item stack size for stackable={}: {}",
+                    item.is_stackable(),
+                    &db_item.stack_size
+                ))
+            })?)
+            .map_err(|_| Error::ConversionError("Error setting amount for item".to_owned()))?;
+        }
+
+        if db_item.parent_container_item_id == mail_id {
+            items.push((db_item.item_id, item));
+        } else if let Some((_, parent)) = items
+            .iter_mut()
+            .find(|(item_id, _)| *item_id == db_item.parent_container_item_id)
+        {
+            parent.add_component(item, msm);
+        } else {
+            return Err(Error::ConversionError(format!(
+                "Couldn't find parent item {} before item {} in mail",
+                db_item.parent_container_item_id, db_item.item_id
+            )));
+        }
+    }
+
+    Ok(items.into_iter().map(|(_, item)| item).collect())
+}
+
+/// Builds the item trees below `parent` from a list of database items, as
+/// loaded by `load_items_bfs`
+pub fn convert_items_to_export(parent: EntityId, items: &[Item]) -> Vec<ExportedItem> {
+    items
+        .iter()
+        .filter(|item| item.parent_container_item_id == parent)
+        .map(|item| ExportedItem {
+            item_definition_id: item.item_definition_id.clone(),
+            stack_size: item.stack_size as u32,
+            position: item.position.clone(),
+            contents: convert_items_to_export(item.item_id, items),
+        })
+        .collect()
+}
+
+/// Flattens exported item trees into database items below `parent`, taking
+/// the IDs of the new items from `new_ids`. Parents are always added before
+/// their contents.
+pub fn convert_items_from_export(
+    parent: EntityId,
+    items: &[ExportedItem],
+    new_ids: &mut impl Iterator<Item = EntityId>,
+    db_items: &mut Vec<Item>,
+) -> Result<(), Error> {
+    for exported in items {
+        let item_id = new_ids
+            .next()
+            .ok_or_else(|| Error::OtherError("Ran out of entity IDs for items".to_string()))?;
+        db_items.push(Item {
+            item_id,
+            parent_container_item_id: parent,
+            item_definition_id: exported.item_definition_id.clone(),
+            stack_size: i32::try_from(exported.stack_size).map_err(|_| {
+                Error::OtherError(format!(
+                    "Stack size {} of {} is out of range",
+                    exported.stack_size, exported.item_definition_id
+                ))
+            })?,
+            position: exported.position.clone(),
+        });
+        convert_items_from_export(item_id, &exported.contents, new_ids, db_items)?;
+    }
+    Ok(())
+}
+
+/// Checks that all exported items exist on this server, so that a character
+/// can't be imported with items that would prevent it from loading
+pub fn validate_exported_items(items: &[ExportedItem]) -> Result<(), Error> {
+    for exported in items {
+        get_item_from_asset(&exported.item_definition_id)?;
+        validate_exported_items(&exported.contents)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vek::Vec3;
+
+    #[test]
+    fn position_round_trip() {
+        let pos = Pos(Vec3::new(1234.5, -42.25, 301.0));
+        let json = convert_position_to_database_json(Some(pos)).unwrap();
+
+        assert_eq!(convert_position_from_database_json(&json).unwrap(), pos);
+        assert_eq!(convert_position_to_database_json(None), None);
+    }
+
+    #[test]
+    fn invalid_position_is_an_error() {
+        assert!(convert_position_from_database_json("{\"waypoint\": 1}").is_err());
+    }
+
+    #[test]
+    fn buffs_round_trip() {
+        let mut potion = Buff::new(
+            BuffKind::Potion,
+            BuffData::new(50.0, Some(Duration::from_secs(10))),
+            vec![BuffCategory::Magical],
+            BuffSource::Item,
+        );
+        potion.time = Some(Duration::from_secs_f32(3.5));
+        let cursed = Buff::new(
+            BuffKind::Cursed,
+            BuffData::new(0.5, None),
+            vec![BuffCategory::PersistOnDeath, BuffCategory::Divine],
+            BuffSource::Command,
+        );
+
+        let json = convert_buffs_to_database_json(&[potion.clone(), cursed.clone()]).unwrap();
+        let buffs = convert_buffs_from_database_json(&json).unwrap();
+
+        assert_eq!(buffs.len(), 2);
+        for (original, loaded) in [potion, cursed].iter().zip(buffs.iter()) {
+            assert_eq!(loaded.kind, original.kind);
+            assert_eq!(loaded.data, original.data);
+            assert_eq!(loaded.cat_ids, original.cat_ids);
+            assert_eq!(loaded.time, original.time);
+        }
+    }
+
+    #[test]
+    fn natural_and_aura_buffs_are_not_persisted() {
+        let natural = Buff::new(
+            BuffKind::Regeneration,
+            BuffData::new(1.0, None),
+            vec![BuffCategory::Natural],
+            BuffSource::World,
+        );
+        let aura = Buff::new(
+            BuffKind::ProtectingWard,
+            BuffData::new(1.0, None),
+            vec![BuffCategory::Magical, BuffCategory::FromAura(true)],
+            BuffSource::World,
+        );
+
+        let json = convert_buffs_to_database_json(&[natural, aura]).unwrap();
+
+        assert!(convert_buffs_from_database_json(&json).unwrap().is_empty());
+    }
+
+    #[test]
+    fn unknown_buffs_are_skipped() {
+        let json = r#"[{"kind":"Removed","strength":1.0,"duration":null,"remaining":null,"categories":[]}]"#;
+
+        assert!(convert_buffs_from_database_json(json).unwrap().is_empty());
+    }
+
+    #[test]
+    fn exported_items_round_trip() {
+        let item = |item_definition_id: &str, position: &str, contents| ExportedItem {
+            item_definition_id: item_definition_id.to_string(),
+            stack_size: 1,
+            position: position.to_string(),
+            contents,
+        };
+        let exported = vec![
+            item("common.items.armor.misc.back.backpack", "back", vec![item(
+                "common.items.food.apple",
+                "0",
+                Vec::new(),
+            )]),
+            item(
+                "common.items.weapons.sword.starter",
+                "main_weapon",
+                Vec::new(),
+            ),
+        ];
+
+        let mut db_items = Vec::new();
+        convert_items_from_export(10, &exported, &mut (100..), &mut db_items).unwrap();
+
+        assert_eq!(db_items.len(), 3);
+        // The bag's contents refer to the bag's new ID
+        assert_eq!(db_items[1].parent_container_item_id, db_items[0].item_id);
+        assert_eq!(convert_items_to_export(10, &db_items), exported);
+    }
+
+    #[test]
+    fn oversized_stacks_fail_the_import() {
+        let exported = vec![ExportedItem {
+            item_definition_id: "common.items.food.apple".to_string(),
+            stack_size: u32::MAX,
+            position: "0".to_string(),
+            contents: Vec::new(),
+        }];
+
+        let mut db_items = Vec::new();
+        assert!(convert_items_from_export(10, &exported, &mut (100..), &mut db_items).is_err());
+    }
+}
@@ -0,0 +1,85 @@
+//! Exporting characters to, and importing them from, a format that doesn't
+//! depend on the entity IDs of a particular database, so that characters can
+//! be moved between servers.
+//!
+//! Only the character's progression (body, skills and items) is exported.
+//! Waypoints, positions and other state that only makes sense in the world the
+//! character was exported from are left behind.
+
+use crate::persistence::{
+    character::{export_character, import_character},
+    error::Error,
+    establish_connection,
+};
+use common::{
+    character::CharacterId,
+    comp::{
+        self,
+        skills::{Skill, SkillGroup},
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Version of the export format, increased whenever the format changes in a
+/// way that older versions can't read.
+pub const CHARACTER_EXPORT_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CharacterExport {
+    pub version: u32,
+    pub alias: String,
+    pub body: comp::Body,
+    pub skill_groups: Vec<SkillGroup>,
+    /// Unlocked skills and their levels. Stored as a list rather than a map
+    /// since JSON only allows string keys.
+    pub skills: Vec<(Skill, Option<u16>)>,
+    pub inventory: Vec<ExportedItem>,
+    pub loadout: Vec<ExportedItem>,
+    /// Missing from exports made before banks were added
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bank: Vec<ExportedItem>,
+}
+
+/// An item and, for containers such as bags, the items inside of it
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ExportedItem {
+    pub item_definition_id: String,
+    pub stack_size: u32,
+    /// Slot of the item in its parent, in the format used by the database
+    pub position: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub contents: Vec<ExportedItem>,
+}
+
+impl ExportedItem {
+    /// The number of items in this item's tree, including itself
+    pub fn count(&self) -> usize { 1 + self.contents.iter().map(Self::count).sum::<usize>() }
+}
+
+/// Export the character with the given ID from the database in `db_dir`
+pub fn export_from_database(
+    db_dir: &Path,
+    character_id: CharacterId,
+) -> Result<CharacterExport, Error> {
+    let mut connection = establish_connection(db_dir)?;
+    connection.transaction(|transaction| export_character(character_id, transaction))
+}
+
+/// Import a character into the database in `db_dir` as a new character of the
+/// player with the given UUID, returning the ID of the new character
+pub fn import_into_database(
+    db_dir: &Path,
+    player_uuid: &str,
+    export: &CharacterExport,
+) -> Result<CharacterId, Error> {
+    if export.version != CHARACTER_EXPORT_VERSION {
+        return Err(Error::OtherError(format!(
+            "Unsupported character export version {} (expected {})",
+            export.version, CHARACTER_EXPORT_VERSION
+        )));
+    }
+
+    let mut connection = establish_connection(db_dir)?;
+    connection.transaction(|transaction| import_character(player_uuid, export, transaction))
+}
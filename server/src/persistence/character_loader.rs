@@ -1,11 +1,15 @@
 use crate::persistence::{
-    character::{create_character, delete_character, load_character_data, load_character_list},
+    character::{
+        create_character, delete_character, find_mail_recipient, load_character_data,
+        load_character_list,
+    },
     error::Error,
     establish_connection, PersistedComponents,
 };
 use common::{
     character::{CharacterId, CharacterItem},
-    comp::inventory::item::MaterialStatManifest,
+    comp::inventory::{item::MaterialStatManifest, slot::InvSlotId},
+    mail::MailError,
 };
 use crossbeam_channel::{self, TryIter};
 use lazy_static::lazy_static;
@@ -15,6 +19,7 @@ use tracing::error;
 pub(crate) type CharacterListResult = Result<Vec<CharacterItem>, Error>;
 pub(crate) type CharacterCreationResult = Result<(CharacterId, Vec<CharacterItem>), Error>;
 pub(crate) type CharacterDataResult = Result<PersistedComponents, Error>;
+pub(crate) type MailRecipientResult = Result<Result<CharacterId, MailError>, Error>;
 type CharacterLoaderRequest = (specs::Entity, CharacterLoaderRequestKind);
 
 /// Available database operations when modifying a player's character list
@@ -35,6 +40,19 @@ enum CharacterLoaderRequestKind {
         player_uuid: String,
         character_id: CharacterId,
     },
+    FindMailRecipient {
+        pending: PendingMail,
+    },
+}
+
+/// A mail which is waiting for its recipient to be looked up before it can be
+/// sent
+#[derive(Debug)]
+pub struct PendingMail {
+    pub recipient_alias: String,
+    pub subject: String,
+    pub body: String,
+    pub items: Vec<InvSlotId>,
 }
 
 /// Wrapper for results for character actions. Can be a list of
@@ -44,6 +62,7 @@ pub enum CharacterLoaderResponseKind {
     CharacterList(CharacterListResult),
     CharacterData(Box<CharacterDataResult>),
     CharacterCreation(CharacterCreationResult),
+    MailRecipient(MailRecipientResult, PendingMail),
 }
 
 /// Common message format dispatched in response to an update request
@@ -153,6 +172,14 @@ impl CharacterLoader {
                                 }
                                 CharacterLoaderResponseKind::CharacterData(Box::new(result))
                             },
+                            CharacterLoaderRequestKind::FindMailRecipient { pending } => {
+                                CharacterLoaderResponseKind::MailRecipient(
+                                    conn.transaction(|txn| {
+                                        find_mail_recipient(&pending.recipient_alias, txn)
+                                    }),
+                                    pending,
+                                )
+                            },
                         },
                     }) {
                         error!(?e, "Could not send send persistence request");
@@ -237,6 +264,19 @@ impl CharacterLoader {
         }
     }
 
+    /// Looks up the recipient of a mail, which is handed back alongside the
+    /// result so that it can then be sent
+    pub fn find_mail_recipient(&self, entity: specs::Entity, pending: PendingMail) {
+        if let Err(e) = self
+            .update_tx
+            .send((entity, CharacterLoaderRequestKind::FindMailRecipient {
+                pending,
+            }))
+        {
+            error!(?e, "Could not send mail recipient request");
+        }
+    }
+
     /// Returns a non-blocking iterator over CharacterLoaderResponse messages
     pub fn messages(&self) -> TryIter<CharacterLoaderResponse> { self.update_rx.try_iter() }
 }
@@ -1,42 +1,210 @@
 use crate::comp;
-use common::{character::CharacterId, comp::item::ItemId};
+use common::{
+    character::CharacterId,
+    comp::item::ItemId,
+    mail::{Mail, MailError, MailId},
+};
 
-use crate::persistence::{establish_connection, VelorenConnection};
+use crate::persistence::{
+    character_loader::MATERIAL_STATS_MANIFEST, error::Error, establish_connection, PersistedState,
+    VelorenConnection,
+};
+use crossbeam_channel::TryIter;
 use std::{path::Path, sync::Arc};
 use tracing::{error, trace};
 
-pub type CharacterUpdateData = (comp::Stats, comp::Inventory, Option<comp::Waypoint>);
+pub type CharacterUpdateData = (
+    comp::Stats,
+    comp::Inventory,
+    comp::Bank,
+    Option<comp::Waypoint>,
+    PersistedState,
+);
 
-/// A unidirectional messaging resource for saving characters in a
-/// background thread.
+pub(crate) type MailboxResult = Result<Vec<Mail>, Error>;
+
+/// The outcome of sending a mail. If it couldn't be sent the attached items
+/// are handed back, so that they can be returned to the sender.
+pub(crate) struct SentMail {
+    pub entity: specs::Entity,
+    pub sender: CharacterId,
+    pub recipient: CharacterId,
+    pub result: Result<(), (MailError, Vec<comp::Item>)>,
+}
+
+/// The outcome of claiming the items of a mail. The items are only handed to
+/// the recipient once they have been claimed in the database.
+pub(crate) struct ClaimedMail {
+    pub entity: specs::Entity,
+    pub recipient: CharacterId,
+    pub result: Result<Vec<comp::Item>, MailError>,
+}
+
+/// A mail that is ready to be delivered, with its items already taken out of
+/// the sender's inventory
+pub struct OutgoingMail {
+    pub recipient: CharacterId,
+    pub sender_alias: String,
+    pub subject: String,
+    pub body: String,
+    pub items: Vec<comp::Item>,
+}
+
+enum CharacterUpdaterEvent {
+    BatchUpdate(Vec<(CharacterId, CharacterUpdateData)>),
+    /// Deliver a mail, saving the sender in the same transaction so that the
+    /// attached items can't be duplicated or lost
+    SendMail {
+        entity: specs::Entity,
+        sender: (CharacterId, CharacterUpdateData),
+        mail: OutgoingMail,
+    },
+    /// Store items that couldn't be delivered in a mail to their sender
+    ReturnMail {
+        sender: CharacterId,
+        subject: String,
+        items: Vec<comp::Item>,
+    },
+    /// Remove the items of a mail, saving the recipient (with the items added
+    /// to their inventory) in the same transaction
+    ClaimMail {
+        entity: specs::Entity,
+        recipient: (CharacterId, CharacterUpdateData),
+        mail_id: MailId,
+        items: Vec<comp::Item>,
+    },
+    MarkMailRead {
+        character_id: CharacterId,
+        mail_id: MailId,
+    },
+    DeleteMail {
+        character_id: CharacterId,
+        mail_id: MailId,
+    },
+    /// Mailboxes are loaded here rather than by the `CharacterLoader` so that
+    /// the result reflects every mail operation queued before it
+    LoadMailbox {
+        entity: specs::Entity,
+        character_id: CharacterId,
+    },
+}
+
+/// A messaging resource for saving characters in a background thread.
 ///
 /// This is used to make updates to a character and their persisted components,
-/// such as inventory, loadout, etc...
+/// such as inventory, loadout, etc... The only responses are loaded mailboxes
+/// and the outcome of sent and claimed mails.
 pub struct CharacterUpdater {
-    update_tx: Option<crossbeam_channel::Sender<Vec<(CharacterId, CharacterUpdateData)>>>,
+    update_tx: Option<crossbeam_channel::Sender<CharacterUpdaterEvent>>,
+    mailbox_rx: crossbeam_channel::Receiver<(specs::Entity, MailboxResult)>,
+    sent_mail_rx: crossbeam_channel::Receiver<SentMail>,
+    claimed_mail_rx: crossbeam_channel::Receiver<ClaimedMail>,
     handle: Option<std::thread::JoinHandle<()>>,
 }
 
 impl CharacterUpdater {
     pub fn new(db_dir: &Path) -> diesel::QueryResult<Self> {
-        let (update_tx, update_rx) =
-            crossbeam_channel::unbounded::<Vec<(CharacterId, CharacterUpdateData)>>();
+        let (update_tx, update_rx) = crossbeam_channel::unbounded::<CharacterUpdaterEvent>();
+        let (mailbox_tx, mailbox_rx) =
+            crossbeam_channel::unbounded::<(specs::Entity, MailboxResult)>();
+        let (sent_mail_tx, sent_mail_rx) = crossbeam_channel::unbounded::<SentMail>();
+        let (claimed_mail_tx, claimed_mail_rx) = crossbeam_channel::unbounded::<ClaimedMail>();
 
         let mut conn = establish_connection(db_dir)?;
 
         let builder = std::thread::Builder::new().name("persistence_updater".into());
         let handle = builder
             .spawn(move || {
-                while let Ok(updates) = update_rx.recv() {
-                    trace!("Persistence batch update starting");
-                    execute_batch_update(updates, &mut conn);
-                    trace!("Persistence batch update finished");
+                while let Ok(event) = update_rx.recv() {
+                    match event {
+                        CharacterUpdaterEvent::BatchUpdate(updates) => {
+                            trace!("Persistence batch update starting");
+                            execute_batch_update(updates, &mut conn);
+                            trace!("Persistence batch update finished");
+                        },
+                        CharacterUpdaterEvent::SendMail {
+                            entity,
+                            sender,
+                            mail,
+                        } => {
+                            let sent_mail = execute_send_mail(entity, sender, mail, &mut conn);
+                            if let Err(e) = sent_mail_tx.send(sent_mail) {
+                                error!(?e, "Could not send the outcome of a sent mail");
+                            }
+                        },
+                        CharacterUpdaterEvent::ReturnMail {
+                            sender,
+                            subject,
+                            items,
+                        } => {
+                            if let Err(e) = conn.transaction(|txn| {
+                                super::character::return_mail(sender, &subject, &items, txn)
+                            }) {
+                                error!(
+                                    ?e,
+                                    ?sender,
+                                    ?items,
+                                    "Error returning undelivered mail items, they are lost"
+                                );
+                            }
+                        },
+                        CharacterUpdaterEvent::ClaimMail {
+                            entity,
+                            recipient,
+                            mail_id,
+                            items,
+                        } => {
+                            let claimed_mail =
+                                execute_claim_mail(entity, recipient, mail_id, items, &mut conn);
+                            if let Err(e) = claimed_mail_tx.send(claimed_mail) {
+                                error!(?e, "Could not send the outcome of a claimed mail");
+                            }
+                        },
+                        CharacterUpdaterEvent::MarkMailRead {
+                            character_id,
+                            mail_id,
+                        } => {
+                            if let Err(e) = conn.transaction(|txn| {
+                                super::character::mark_mail_read(character_id, mail_id, txn)
+                            }) {
+                                error!(?e, ?mail_id, "Error marking mail as read");
+                            }
+                        },
+                        CharacterUpdaterEvent::DeleteMail {
+                            character_id,
+                            mail_id,
+                        } => {
+                            if let Err(e) = conn.transaction(|txn| {
+                                super::character::delete_mail(character_id, mail_id, txn)
+                            }) {
+                                error!(?e, ?mail_id, "Error deleting mail");
+                            }
+                        },
+                        CharacterUpdaterEvent::LoadMailbox {
+                            entity,
+                            character_id,
+                        } => {
+                            let result = conn.transaction(|txn| {
+                                super::character::load_mailbox(
+                                    character_id,
+                                    txn,
+                                    &MATERIAL_STATS_MANIFEST,
+                                )
+                            });
+                            if let Err(e) = mailbox_tx.send((entity, result)) {
+                                error!(?e, "Could not send loaded mailbox");
+                            }
+                        },
+                    }
                 }
             })
             .unwrap();
 
         Ok(Self {
             update_tx: Some(update_tx),
+            mailbox_rx,
+            sent_mail_rx,
+            claimed_mail_rx,
             handle: Some(handle),
         })
     }
@@ -49,22 +217,28 @@ impl CharacterUpdater {
                 CharacterId,
                 &'a comp::Stats,
                 &'a comp::Inventory,
+                &'a comp::Bank,
                 Option<&'a comp::Waypoint>,
+                PersistedState,
             ),
         >,
     ) {
         let updates = updates
-            .map(|(character_id, stats, inventory, waypoint)| {
+            .map(|(character_id, stats, inventory, bank, waypoint, state)| {
                 (
                     character_id,
-                    (stats.clone(), inventory.clone(), waypoint.cloned()),
+                    (
+                        stats.clone(),
+                        inventory.clone(),
+                        bank.clone(),
+                        waypoint.cloned(),
+                        state,
+                    ),
                 )
             })
             .collect::<Vec<_>>();
 
-        if let Err(e) = self.update_tx.as_ref().unwrap().send(updates) {
-            error!(?e, "Could not send stats updates");
-        }
+        self.send(CharacterUpdaterEvent::BatchUpdate(updates));
     }
 
     /// Updates a single character based on their id and components
@@ -73,9 +247,103 @@ impl CharacterUpdater {
         character_id: CharacterId,
         stats: &comp::Stats,
         inventory: &comp::Inventory,
+        bank: &comp::Bank,
         waypoint: Option<&comp::Waypoint>,
+        state: PersistedState,
+    ) {
+        self.batch_update(std::iter::once((
+            character_id,
+            stats,
+            inventory,
+            bank,
+            waypoint,
+            state,
+        )));
+    }
+
+    /// Delivers a mail, saving the sender's components at the same time. The
+    /// outcome can be polled with [`CharacterUpdater::sent_mails`]
+    pub fn send_mail(
+        &self,
+        entity: specs::Entity,
+        character_id: CharacterId,
+        sender: CharacterUpdateData,
+        mail: OutgoingMail,
+    ) {
+        self.send(CharacterUpdaterEvent::SendMail {
+            entity,
+            sender: (character_id, sender),
+            mail,
+        });
+    }
+
+    /// Puts items that couldn't be delivered into a mail to their sender, for
+    /// when the sender's inventory isn't available to return them to
+    pub fn return_mail(&self, sender: CharacterId, subject: String, items: Vec<comp::Item>) {
+        self.send(CharacterUpdaterEvent::ReturnMail {
+            sender,
+            subject,
+            items,
+        });
+    }
+
+    /// Removes the items of a mail, saving the recipient's components (which
+    /// must already include `items`) at the same time. The outcome can be
+    /// polled with [`CharacterUpdater::claimed_mails`]
+    pub fn claim_mail(
+        &self,
+        entity: specs::Entity,
+        character_id: CharacterId,
+        recipient: CharacterUpdateData,
+        mail_id: MailId,
+        items: Vec<comp::Item>,
     ) {
-        self.batch_update(std::iter::once((character_id, stats, inventory, waypoint)));
+        self.send(CharacterUpdaterEvent::ClaimMail {
+            entity,
+            recipient: (character_id, recipient),
+            mail_id,
+            items,
+        });
+    }
+
+    pub fn mark_mail_read(&self, character_id: CharacterId, mail_id: MailId) {
+        self.send(CharacterUpdaterEvent::MarkMailRead {
+            character_id,
+            mail_id,
+        });
+    }
+
+    pub fn delete_mail(&self, character_id: CharacterId, mail_id: MailId) {
+        self.send(CharacterUpdaterEvent::DeleteMail {
+            character_id,
+            mail_id,
+        });
+    }
+
+    /// Loads the mailbox of a character, the result can be polled with
+    /// [`CharacterUpdater::mailboxes`]
+    pub fn load_mailbox(&self, entity: specs::Entity, character_id: CharacterId) {
+        self.send(CharacterUpdaterEvent::LoadMailbox {
+            entity,
+            character_id,
+        });
+    }
+
+    /// Returns a non-blocking iterator over loaded mailboxes
+    pub fn mailboxes(&self) -> TryIter<(specs::Entity, MailboxResult)> {
+        self.mailbox_rx.try_iter()
+    }
+
+    /// Returns a non-blocking iterator over the outcomes of sent mails
+    pub(crate) fn sent_mails(&self) -> TryIter<SentMail> { self.sent_mail_rx.try_iter() }
+
+    /// Returns a non-blocking iterator over the outcomes of claimed mails
+    pub(crate) fn claimed_mails(&self) -> TryIter<ClaimedMail> { self.claimed_mail_rx.try_iter() }
+
+    fn send(&self, event: CharacterUpdaterEvent) {
+        if let Err(e) = self.update_tx.as_ref().unwrap().send(event) {
+            error!(?e, "Could not send character update event");
+        }
     }
 }
 
@@ -86,12 +354,14 @@ fn execute_batch_update(
     let mut inserted_items = Vec::<Arc<ItemId>>::new();
 
     if let Err(e) = connection.transaction::<_, super::error::Error, _>(|txn| {
-        for (character_id, (stats, inventory, waypoint)) in updates {
+        for (character_id, (stats, inventory, bank, waypoint, state)) in updates {
             inserted_items.append(&mut super::character::update(
                 character_id,
                 stats,
                 inventory,
+                bank,
                 waypoint,
+                state,
                 txn,
             )?);
         }
@@ -105,6 +375,74 @@ fn execute_batch_update(
     // internally.
 }
 
+fn execute_send_mail(
+    entity: specs::Entity,
+    (character_id, (stats, inventory, bank, waypoint, state)): (CharacterId, CharacterUpdateData),
+    mail: OutgoingMail,
+    connection: &mut VelorenConnection,
+) -> SentMail {
+    let result = connection.transaction::<_, Error, _>(|txn| {
+        if let Err(error) = super::character::insert_mail(
+            mail.recipient,
+            &mail.sender_alias,
+            &mail.subject,
+            &mail.body,
+            &mail.items,
+            txn,
+        )? {
+            return Ok(Err(error));
+        }
+        super::character::update(character_id, stats, inventory, bank, waypoint, state, txn)?;
+        Ok(Ok(()))
+    });
+
+    SentMail {
+        entity,
+        sender: character_id,
+        recipient: mail.recipient,
+        result: match result {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(error)) => Err((error, mail.items)),
+            Err(e) => {
+                error!(?e, ?character_id, "Error during send mail transaction");
+                Err((MailError::Unavailable, mail.items))
+            },
+        },
+    }
+}
+
+fn execute_claim_mail(
+    entity: specs::Entity,
+    (character_id, (stats, inventory, bank, waypoint, state)): (CharacterId, CharacterUpdateData),
+    mail_id: MailId,
+    items: Vec<comp::Item>,
+    connection: &mut VelorenConnection,
+) -> ClaimedMail {
+    let result = connection.transaction::<_, Error, _>(|txn| {
+        super::character::claim_mail_items(character_id, mail_id, txn)?;
+        super::character::update(character_id, stats, inventory, bank, waypoint, state, txn)?;
+        Ok(())
+    });
+
+    ClaimedMail {
+        entity,
+        recipient: character_id,
+        result: match result {
+            Ok(()) => Ok(items),
+            Err(e) => {
+                error!(
+                    ?e,
+                    ?character_id,
+                    ?mail_id,
+                    "Error during claim mail transaction"
+                );
+                // The items are still stored in the mail
+                Err(MailError::Unavailable)
+            },
+        },
+    }
+}
+
 impl Drop for CharacterUpdater {
     fn drop(&mut self) {
         drop(self.update_tx.take());
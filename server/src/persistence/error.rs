@@ -19,6 +19,9 @@ pub enum Error {
     // Unable to load body or stats for a character
     CharacterDataError,
     SerializationError(serde_json::Error),
+    // An error occurred while taking a backup of the database
+    BackupError(rusqlite::Error),
+    IoError(std::io::Error),
     ConversionError(String),
     OtherError(String),
 }
@@ -33,6 +36,8 @@ impl fmt::Display for Error {
             Self::DatabaseMigrationError(error) => error.to_string(),
             Self::CharacterDataError => String::from("Error while loading character data"),
             Self::SerializationError(error) => error.to_string(),
+            Self::BackupError(error) => error.to_string(),
+            Self::IoError(error) => error.to_string(),
             Self::ConversionError(error) => error.to_string(),
             Self::OtherError(error) => error.to_string(),
         })
@@ -56,3 +61,11 @@ impl From<diesel_migrations::RunMigrationsError> for Error {
         Error::DatabaseMigrationError(error)
     }
 }
+
+impl From<rusqlite::Error> for Error {
+    fn from(error: rusqlite::Error) -> Error { Error::BackupError(error) }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Error { Error::IoError(error) }
+}
@@ -36,6 +36,82 @@ pub struct CharacterPosition {
     pub waypoint: Vec3<f32>,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct CharacterLastPosition {
+    pub pos: Vec3<f32>,
+}
+
+/// Durations are stored in seconds
+#[derive(Serialize, Deserialize)]
+pub struct DatabaseBuff {
+    pub kind: String,
+    pub strength: f32,
+    pub duration: Option<f32>,
+    pub remaining: Option<f32>,
+    pub categories: Vec<String>,
+}
+
+pub fn buff_kind_to_db_string(kind: comp::BuffKind) -> String {
+    use comp::BuffKind::*;
+    let kind_string = match kind {
+        Regeneration => "Regeneration",
+        Saturation => "Saturation",
+        Bleeding => "Bleeding",
+        Cursed => "Cursed",
+        Potion => "Potion",
+        CampfireHeal => "CampfireHeal",
+        IncreaseMaxEnergy => "IncreaseMaxEnergy",
+        IncreaseMaxHealth => "IncreaseMaxHealth",
+        Invulnerability => "Invulnerability",
+        ProtectingWard => "ProtectingWard",
+    };
+    kind_string.to_string()
+}
+
+/// Buffs that no longer exist are dropped rather than failing to load the
+/// character
+pub fn db_string_to_buff_kind(kind_string: &str) -> Option<comp::BuffKind> {
+    use comp::BuffKind::*;
+    Some(match kind_string {
+        "Regeneration" => Regeneration,
+        "Saturation" => Saturation,
+        "Bleeding" => Bleeding,
+        "Cursed" => Cursed,
+        "Potion" => Potion,
+        "CampfireHeal" => CampfireHeal,
+        "IncreaseMaxEnergy" => IncreaseMaxEnergy,
+        "IncreaseMaxHealth" => IncreaseMaxHealth,
+        "Invulnerability" => Invulnerability,
+        "ProtectingWard" => ProtectingWard,
+        _ => return None,
+    })
+}
+
+/// Natural and aura categories belong to buffs that are reapplied by the world
+/// rather than persisted, so they have no database representation
+pub fn buff_category_to_db_string(category: comp::BuffCategory) -> Option<String> {
+    use comp::BuffCategory::*;
+    let category_string = match category {
+        Physical => "Physical",
+        Magical => "Magical",
+        Divine => "Divine",
+        PersistOnDeath => "PersistOnDeath",
+        Natural | FromAura(_) => return None,
+    };
+    Some(category_string.to_string())
+}
+
+pub fn db_string_to_buff_category(category_string: &str) -> Option<comp::BuffCategory> {
+    use comp::BuffCategory::*;
+    Some(match category_string {
+        "Physical" => Physical,
+        "Magical" => Magical,
+        "Divine" => Divine,
+        "PersistOnDeath" => PersistOnDeath,
+        _ => return None,
+    })
+}
+
 pub fn skill_to_db_string(skill: comp::skills::Skill) -> String {
     use comp::{
         item::tool::ToolKind,
@@ -6,26 +6,40 @@
 //! - [`diesel-cli`](https://github.com/diesel-rs/diesel/tree/master/diesel_cli/)
 //!   for generating and testing migrations
 
+pub mod backup;
 pub(in crate::persistence) mod character;
+pub mod character_export;
 pub mod character_loader;
 pub mod character_updater;
 mod error;
 mod json_models;
 mod models;
+#[cfg(feature = "plugins")]
+pub mod plugin_storage;
 mod schema;
 
+pub use common::character::PersistedState;
 use common::comp;
 use diesel::{connection::SimpleConnection, prelude::*};
 use diesel_migrations::embed_migrations;
+pub use error::Error;
 use std::{fs, path::Path};
 use tracing::info;
 
+/// Directory the database is stored in, relative to the server's data dir
+pub const PERSISTENCE_DB_DIR: &str = "saves";
+/// Directory database backups are stored in, relative to the server's data dir
+pub const BACKUP_DIR: &str = "backups";
+const DB_FILENAME: &str = "db.sqlite";
+
 /// A tuple of the components that are persisted to the DB for each character
 pub type PersistedComponents = (
     comp::Body,
     comp::Stats,
     comp::Inventory,
+    comp::Bank,
     Option<comp::Waypoint>,
+    PersistedState,
 );
 
 // See: https://docs.rs/diesel_migrations/1.4.0/diesel_migrations/macro.embed_migrations.html
@@ -33,7 +47,7 @@ pub type PersistedComponents = (
 // for the `embedded_migrations` call below.
 //
 // NOTE: Adding a useless comment to trigger the migrations being run. Alter
-// when needed.
+// when needed (last altered for plugin storage).
 embed_migrations!();
 
 struct TracingOut;
@@ -94,7 +108,7 @@ impl<'a> core::ops::Deref for VelorenTransaction<'a> {
 }
 
 pub fn establish_connection(db_dir: &Path) -> QueryResult<VelorenConnection> {
-    let database_url = format!("{}/db.sqlite", db_dir.display());
+    let database_url = format!("{}/{}", db_dir.display(), DB_FILENAME);
 
     let connection = SqliteConnection::establish(&database_url)
         .unwrap_or_else(|_| panic!("Error connecting to {}", database_url));
@@ -1,6 +1,6 @@
 extern crate serde_json;
 
-use super::schema::{body, character, entity, item, skill, skill_group};
+use super::schema::{body, character, entity, item, mail, skill, skill_group};
 
 #[derive(Debug, Insertable, PartialEq)]
 #[table_name = "entity"]
@@ -25,6 +25,10 @@ pub struct Character {
     pub player_uuid: String,
     pub alias: String,
     pub waypoint: Option<String>,
+    pub health: Option<i32>,
+    pub energy: Option<i32>,
+    pub buffs: Option<String>,
+    pub last_position: Option<String>,
 }
 
 #[primary_key(item_id)]
@@ -66,3 +70,16 @@ pub struct SkillGroup {
     pub available_sp: i32,
     pub earned_sp: i32,
 }
+
+#[derive(Identifiable, Insertable, Queryable, Debug)]
+#[primary_key(mail_id)]
+#[table_name = "mail"]
+pub struct Mail {
+    pub mail_id: i64,
+    pub recipient_id: i64,
+    pub sender_alias: String,
+    pub subject: String,
+    pub body: String,
+    pub sent_at: i64,
+    pub is_read: bool,
+}
@@ -0,0 +1,90 @@
+//! Database backed storage of plugin data
+//!
+//! Plugins call into the storage synchronously while handling events, so it
+//! keeps its own connection instead of going through the character updater.
+
+use super::{establish_connection, schema::plugin_storage::dsl::*, VelorenConnection};
+use common_sys::plugin::storage::StorageBackend;
+use diesel::{dsl::sql, prelude::*, sql_types::BigInt};
+use std::{path::Path, sync::Mutex};
+
+pub struct DbPluginStorage {
+    connection: Mutex<VelorenConnection>,
+}
+
+impl DbPluginStorage {
+    pub fn new(db_dir: &Path) -> QueryResult<Self> {
+        Ok(Self {
+            connection: Mutex::new(establish_connection(db_dir)?),
+        })
+    }
+
+    fn transaction<T>(
+        &self,
+        f: impl FnOnce(&SqliteConnection) -> QueryResult<T>,
+    ) -> Result<T, String> {
+        self.connection
+            .lock()
+            .unwrap()
+            .transaction(|connection| f(&*connection))
+            .map_err(|e: diesel::result::Error| e.to_string())
+    }
+}
+
+impl StorageBackend for DbPluginStorage {
+    fn get(&self, plugin: &str, key: &str) -> Result<Option<Vec<u8>>, String> {
+        self.transaction(|connection| {
+            plugin_storage
+                .filter(plugin_name.eq(plugin))
+                .filter(storage_key.eq(key))
+                .select(value)
+                .first::<Vec<u8>>(connection)
+                .optional()
+        })
+    }
+
+    fn set(&self, plugin: &str, key: &str, data: &[u8]) -> Result<(), String> {
+        self.transaction(|connection| {
+            diesel::replace_into(plugin_storage)
+                .values((plugin_name.eq(plugin), storage_key.eq(key), value.eq(data)))
+                .execute(connection)
+                .map(|_| ())
+        })
+    }
+
+    fn remove(&self, plugin: &str, key: &str) -> Result<bool, String> {
+        self.transaction(|connection| {
+            diesel::delete(
+                plugin_storage
+                    .filter(plugin_name.eq(plugin))
+                    .filter(storage_key.eq(key)),
+            )
+            .execute(connection)
+            .map(|count| count > 0)
+        })
+    }
+
+    fn keys(&self, plugin: &str, prefix: &str) -> Result<Vec<String>, String> {
+        // Filtered here since LIKE ignores the case of ASCII characters
+        self.transaction(|connection| {
+            plugin_storage
+                .filter(plugin_name.eq(plugin))
+                .select(storage_key)
+                .order(storage_key)
+                .load::<String>(connection)
+        })
+        .map(|keys| keys.into_iter().filter(|k| k.starts_with(prefix)).collect())
+    }
+
+    fn usage(&self, plugin: &str) -> Result<(u64, u64), String> {
+        self.transaction(|connection| {
+            plugin_storage
+                .filter(plugin_name.eq(plugin))
+                .select(sql::<(BigInt, BigInt)>(
+                    "COUNT(*), COALESCE(SUM(LENGTH(CAST(storage_key AS BLOB)) + LENGTH(value)), 0)",
+                ))
+                .first::<(i64, i64)>(connection)
+        })
+        .map(|(entries, bytes)| (entries as u64, bytes as u64))
+    }
+}
@@ -12,6 +12,10 @@ table! {
         player_uuid -> Text,
         alias -> Text,
         waypoint -> Nullable<Text>,
+        health -> Nullable<Integer>,
+        energy -> Nullable<Integer>,
+        buffs -> Nullable<Text>,
+        last_position -> Nullable<Text>,
     }
 }
 
@@ -31,6 +35,26 @@ table! {
     }
 }
 
+table! {
+    mail (mail_id) {
+        mail_id -> BigInt,
+        recipient_id -> BigInt,
+        sender_alias -> Text,
+        subject -> Text,
+        body -> Text,
+        sent_at -> BigInt,
+        is_read -> Bool,
+    }
+}
+
+table! {
+    plugin_storage (plugin_name, storage_key) {
+        plugin_name -> Text,
+        storage_key -> Text,
+        value -> Binary,
+    }
+}
+
 table! {
     skill (entity_id, skill_type) {
         entity_id -> BigInt,
@@ -51,5 +75,6 @@ table! {
 }
 
 joinable!(character -> body (character_id));
+joinable!(mail -> character (recipient_id));
 
-allow_tables_to_appear_in_same_query!(body, character, entity, item);
+allow_tables_to_appear_in_same_query!(body, character, entity, item, mail);
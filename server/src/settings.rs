@@ -3,12 +3,15 @@ mod editable;
 pub use editable::EditableSetting;
 
 use authc::Uuid;
+use chrono::{DateTime, Utc};
+use common::{cmd::ChatCommand, comp::AdminRole};
 use hashbrown::{HashMap, HashSet};
+use network::Identity;
 use portpicker::pick_unused_port;
 use serde::{Deserialize, Serialize};
 use std::{
     fs,
-    net::SocketAddr,
+    net::{IpAddr, SocketAddr},
     ops::{Deref, DerefMut},
     path::{Path, PathBuf},
     time::Duration,
@@ -23,6 +26,8 @@ const WHITELIST_FILENAME: &str = "whitelist.ron";
 const BANLIST_FILENAME: &str = "banlist.ron";
 const SERVER_DESCRIPTION_FILENAME: &str = "description.ron";
 const ADMINS_FILENAME: &str = "admins.ron";
+const COMMAND_PERMISSIONS_FILENAME: &str = "command_permissions.ron";
+const IDENTITY_FILENAME: &str = "identity.pk8";
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(default)]
@@ -42,6 +47,27 @@ pub struct Settings {
     pub banned_words_files: Vec<PathBuf>,
     pub max_player_group_size: u32,
     pub client_timeout: Duration,
+    /// Whether changes that players make to the terrain are kept across
+    /// restarts
+    pub terrain_persistence: bool,
+    /// Time between automatic backups of the character database, or `None`
+    /// to only take backups on request
+    pub database_backup_interval: Option<Duration>,
+    /// Number of database backups to keep before deleting the oldest ones
+    pub database_backups_kept: usize,
+    /// Certificate and private key of the server. When set, clients can also
+    /// connect via QUIC on the port of `gameserver_address` if the server is
+    /// built with the `quic` feature
+    pub quic_files: Option<X509FilePair>,
+    /// Bytes per second the server sends to each player at most, terrain is
+    /// deferred first when it's reached. `None` means unlimited
+    pub max_player_bandwidth: Option<u64>,
+    /// Bytes per second the server sends to all players together at most,
+    /// `None` means unlimited
+    pub max_bandwidth: Option<u64>,
+    /// Whether plugins are reloaded when their files in the plugin directory
+    /// change, new plugin files are loaded as well
+    pub plugin_hot_reload: bool,
 }
 
 impl Default for Settings {
@@ -59,6 +85,13 @@ impl Default for Settings {
             banned_words_files: Vec::new(),
             max_player_group_size: 6,
             client_timeout: Duration::from_secs(40),
+            terrain_persistence: false,
+            database_backup_interval: None,
+            database_backups_kept: 10,
+            quic_files: None,
+            max_player_bandwidth: None,
+            max_bandwidth: None,
+            plugin_hot_reload: false,
         }
     }
 }
@@ -153,10 +186,128 @@ fn with_config_dir(path: &Path) -> PathBuf {
     path
 }
 
-#[derive(Deserialize, Serialize)]
+/// PEM encoded certificate chain and PKCS#8 or RSA private key
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct X509FilePair {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+#[cfg(feature = "quic")]
+impl X509FilePair {
+    /// Loads both files into a QUIC server config
+    pub fn quic_server_config(&self) -> std::io::Result<quinn::ServerConfig> {
+        use std::io::{Error, ErrorKind};
+
+        let certs = quinn::CertificateChain::from_pem(&fs::read(&self.cert)?)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        if certs.iter().next().is_none() {
+            return Err(Error::new(ErrorKind::InvalidData, "no certificate found"));
+        }
+        let key = quinn::PrivateKey::from_pem(&fs::read(&self.key)?)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+
+        let mut config = quinn::ServerConfigBuilder::default();
+        config
+            .certificate(certs, key)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        Ok(config.build())
+    }
+}
+
+/// Loads the key pair the server proves to own when clients connect, which
+/// clients remember to recognize the server. A new one is generated and stored
+/// in the data dir on the first start.
+pub fn load_or_create_identity(data_dir: &Path) -> std::io::Result<Identity> {
+    use std::io::{Error, ErrorKind, Write};
+
+    let path = data_dir.join(IDENTITY_FILENAME);
+    match fs::read(&path) {
+        Ok(pkcs8) => Identity::from_pkcs8(&pkcs8)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "invalid identity file")),
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            let identity = Identity::generate();
+            fs::create_dir_all(data_dir)?;
+            let mut options = fs::OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::OpenOptionsExt;
+                // It's a private key
+                options.mode(0o600);
+            }
+            options.open(&path)?.write_all(identity.to_pkcs8())?;
+            Ok(identity)
+        },
+        Err(e) => Err(e),
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct BanRecord {
     pub username_when_banned: String,
     pub reason: String,
+    /// Uuid of the admin that issued the ban, `None` if it was issued from the
+    /// server console or predates this field
+    pub banned_by: Option<Uuid>,
+    pub banned_at: Option<DateTime<Utc>>,
+    /// When the ban is lifted automatically, `None` for permanent bans
+    pub end_date: Option<DateTime<Utc>>,
+    /// Ip address the player was connected from when they were banned, used to
+    /// detect other accounts evading the ban
+    pub ip_addr: Option<IpAddr>,
+}
+
+impl BanRecord {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.end_date.map_or(false, |end_date| end_date <= now)
+    }
+
+    /// Message shown to the banned player when they try to log in
+    pub fn login_message(&self) -> String {
+        match self.end_date {
+            Some(end_date) => format!(
+                "{} (until {} UTC)",
+                self.reason,
+                end_date.format("%Y-%m-%d %H:%M")
+            ),
+            None => self.reason.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct UnbanRecord {
+    /// Uuid of the admin that lifted the ban, `None` if it was lifted from the
+    /// server console
+    pub unbanned_by: Option<Uuid>,
+    pub unbanned_at: DateTime<Utc>,
+}
+
+/// A ban that is no longer in effect
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PastBan {
+    pub ban: BanRecord,
+    /// `None` if the ban expired on its own
+    pub unban: Option<UnbanRecord>,
+}
+
+/// All bans a single account has received
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct BanEntry {
+    /// The ban in effect, if its `end_date` has passed it is moved to the
+    /// history the next time the player tries to log in
+    pub current: Option<BanRecord>,
+    /// Bans that were lifted or expired, oldest first
+    pub history: Vec<PastBan>,
+}
+
+impl BanEntry {
+    /// Returns the current ban if it has not expired yet
+    pub fn active_ban(&self, now: DateTime<Utc>) -> Option<&BanRecord> {
+        self.current.as_ref().filter(|ban| !ban.is_expired(now))
+    }
 }
 
 #[derive(Deserialize, Serialize, Default)]
@@ -165,7 +316,69 @@ pub struct Whitelist(HashSet<Uuid>);
 
 #[derive(Deserialize, Serialize, Default)]
 #[serde(transparent)]
-pub struct Banlist(HashMap<Uuid, BanRecord>);
+pub struct Banlist(HashMap<Uuid, BanEntry>);
+
+impl Banlist {
+    /// Returns the ban currently in effect for `uuid`, expired bans are
+    /// ignored
+    pub fn active_ban(&self, uuid: &Uuid, now: DateTime<Utc>) -> Option<&BanRecord> {
+        self.0.get(uuid).and_then(|entry| entry.active_ban(now))
+    }
+
+    /// Finds an active ban of an account that was connected from `ip_addr`
+    /// when it got banned
+    pub fn linked_ban(&self, ip_addr: IpAddr, now: DateTime<Utc>) -> Option<(Uuid, &BanRecord)> {
+        self.0.iter().find_map(|(uuid, entry)| {
+            entry
+                .active_ban(now)
+                .filter(|ban| ban.ip_addr == Some(ip_addr))
+                .map(|ban| (*uuid, ban))
+        })
+    }
+
+    /// Bans `uuid`, a previous ban that is replaced is moved to the history
+    pub fn ban(&mut self, uuid: Uuid, record: BanRecord) {
+        let entry = self.0.entry(uuid).or_default();
+        if let Some(ban) = entry.current.replace(record) {
+            entry.history.push(PastBan { ban, unban: None });
+        }
+    }
+
+    /// Lifts the current ban of `uuid`, returns false if there was none
+    pub fn unban(&mut self, uuid: &Uuid, unban: UnbanRecord) -> bool {
+        match self.0.get_mut(uuid) {
+            Some(entry) => match entry.current.take() {
+                Some(ban) => {
+                    entry.history.push(PastBan {
+                        ban,
+                        unban: Some(unban),
+                    });
+                    true
+                },
+                None => false,
+            },
+            None => false,
+        }
+    }
+
+    /// Moves the current ban of `uuid` to the history if it has expired,
+    /// returns whether a ban was lifted
+    pub fn lift_expired(&mut self, uuid: &Uuid, now: DateTime<Utc>) -> bool {
+        if let Some(entry) = self.0.get_mut(uuid) {
+            if entry
+                .current
+                .as_ref()
+                .map_or(false, |ban| ban.is_expired(now))
+            {
+                if let Some(ban) = entry.current.take() {
+                    entry.history.push(PastBan { ban, unban: None });
+                }
+                return true;
+            }
+        }
+        false
+    }
+}
 
 #[derive(Deserialize, Serialize)]
 #[serde(transparent)]
@@ -176,7 +389,32 @@ impl Default for ServerDescription {
 
 #[derive(Deserialize, Serialize, Default)]
 #[serde(transparent)]
-pub struct Admins(HashSet<Uuid>);
+pub struct Admins(HashMap<Uuid, AdminRole>);
+
+/// Overrides the role needed to use a chat command, keyed by the command
+/// keyword. A value of `None` allows everyone to use the command. Commands
+/// that are not listed keep their default role.
+#[derive(Deserialize, Serialize, Default)]
+#[serde(transparent)]
+pub struct CommandPermissions(HashMap<String, Option<AdminRole>>);
+
+impl CommandPermissions {
+    /// The least privileged role that may use `cmd`, `None` if everyone can
+    pub fn needed_role(&self, cmd: &ChatCommand) -> Option<AdminRole> {
+        self.0
+            .get(cmd.keyword())
+            .copied()
+            .unwrap_or_else(|| cmd.needs_role())
+    }
+
+    /// Whether a player with the given role may use `cmd`
+    pub fn allows(&self, cmd: &ChatCommand, role: Option<AdminRole>) -> bool {
+        match self.needed_role(cmd) {
+            Some(needed_role) => role.map_or(false, |role| role >= needed_role),
+            None => true,
+        }
+    }
+}
 
 /// Combines all the editable settings into one struct that is stored in the ecs
 pub struct EditableSettings {
@@ -184,6 +422,7 @@ pub struct EditableSettings {
     pub banlist: Banlist,
     pub server_description: ServerDescription,
     pub admins: Admins,
+    pub command_permissions: CommandPermissions,
 }
 
 impl EditableSettings {
@@ -193,6 +432,7 @@ impl EditableSettings {
             banlist: Banlist::load(data_dir),
             server_description: ServerDescription::load(data_dir),
             admins: Admins::load(data_dir),
+            command_permissions: CommandPermissions::load(data_dir),
         }
     }
 
@@ -202,7 +442,11 @@ impl EditableSettings {
             server_description: ServerDescription("Who needs friends anyway?".into()),
             // TODO: Let the player choose if they want to use admin commands or not
             admins: Admins(
-                std::iter::once(crate::login_provider::derive_singleplayer_uuid()).collect(),
+                std::iter::once((
+                    crate::login_provider::derive_singleplayer_uuid(),
+                    AdminRole::Owner,
+                ))
+                .collect(),
             ),
             ..load
         }
@@ -215,6 +459,35 @@ impl EditableSetting for Whitelist {
 
 impl EditableSetting for Banlist {
     const FILENAME: &'static str = BANLIST_FILENAME;
+
+    fn migrate(contents: &str) -> Option<Self> {
+        /// Banlist entries before bans could expire and kept a history
+        #[derive(Deserialize)]
+        struct LegacyBanRecord {
+            username_when_banned: String,
+            reason: String,
+        }
+
+        let legacy: HashMap<Uuid, LegacyBanRecord> = ron::de::from_str(contents).ok()?;
+        Some(Self(
+            legacy
+                .into_iter()
+                .map(|(uuid, record)| {
+                    (uuid, BanEntry {
+                        current: Some(BanRecord {
+                            username_when_banned: record.username_when_banned,
+                            reason: record.reason,
+                            banned_by: None,
+                            banned_at: None,
+                            end_date: None,
+                            ip_addr: None,
+                        }),
+                        history: Vec::new(),
+                    })
+                })
+                .collect(),
+        ))
+    }
 }
 
 impl EditableSetting for ServerDescription {
@@ -223,6 +496,21 @@ impl EditableSetting for ServerDescription {
 
 impl EditableSetting for Admins {
     const FILENAME: &'static str = ADMINS_FILENAME;
+
+    fn migrate(contents: &str) -> Option<Self> {
+        // Admins used to be a plain set without roles, keep their permissions
+        let legacy: HashSet<Uuid> = ron::de::from_str(contents).ok()?;
+        Some(Self(
+            legacy
+                .into_iter()
+                .map(|uuid| (uuid, AdminRole::Admin))
+                .collect(),
+        ))
+    }
+}
+
+impl EditableSetting for CommandPermissions {
+    const FILENAME: &'static str = COMMAND_PERMISSIONS_FILENAME;
 }
 
 impl Deref for Whitelist {
@@ -236,7 +524,7 @@ impl DerefMut for Whitelist {
 }
 
 impl Deref for Banlist {
-    type Target = HashMap<Uuid, BanRecord>;
+    type Target = HashMap<Uuid, BanEntry>;
 
     fn deref(&self) -> &Self::Target { &self.0 }
 }
@@ -256,7 +544,7 @@ impl DerefMut for ServerDescription {
 }
 
 impl Deref for Admins {
-    type Target = HashSet<Uuid>;
+    type Target = HashMap<Uuid, AdminRole>;
 
     fn deref(&self) -> &Self::Target { &self.0 }
 }
@@ -264,3 +552,61 @@ impl Deref for Admins {
 impl DerefMut for Admins {
     fn deref_mut(&mut self) -> &mut Self::Target { &mut self.0 }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrate_legacy_banlist() {
+        let legacy = r#"{
+            "00000000-0000-0000-0000-000000000001": (
+                username_when_banned: "griefer",
+                reason: "Griefing",
+            ),
+        }"#;
+        assert!(ron::de::from_str::<Banlist>(legacy).is_err());
+
+        let banlist = Banlist::migrate(legacy).expect("Failed to migrate legacy banlist");
+        let uuid = Uuid::from_u128(1);
+        let ban = banlist
+            .active_ban(&uuid, Utc::now())
+            .expect("Migrated ban should be active");
+        assert_eq!(ban.username_when_banned, "griefer");
+        assert_eq!(ban.reason, "Griefing");
+        assert!(ban.end_date.is_none());
+    }
+
+    #[test]
+    fn expired_bans_move_to_history() {
+        let uuid = Uuid::from_u128(1);
+        let now = Utc::now();
+        let mut banlist = Banlist::default();
+        banlist.ban(uuid, BanRecord {
+            username_when_banned: "griefer".to_owned(),
+            reason: "Griefing".to_owned(),
+            banned_by: None,
+            banned_at: Some(now),
+            end_date: Some(now + chrono::Duration::days(1)),
+            ip_addr: Some(IpAddr::from([127, 0, 0, 1])),
+        });
+
+        assert!(banlist.active_ban(&uuid, now).is_some());
+        assert!(
+            banlist
+                .linked_ban(IpAddr::from([127, 0, 0, 1]), now)
+                .is_some()
+        );
+        assert!(!banlist.lift_expired(&uuid, now));
+
+        let later = now + chrono::Duration::days(2);
+        assert!(banlist.active_ban(&uuid, later).is_none());
+        assert!(banlist.lift_expired(&uuid, later));
+        assert!(banlist[&uuid].current.is_none());
+        assert_eq!(banlist[&uuid].history.len(), 1);
+        assert!(!banlist.unban(&uuid, UnbanRecord {
+            unbanned_by: None,
+            unbanned_at: later,
+        }));
+    }
+}
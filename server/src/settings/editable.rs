@@ -3,18 +3,41 @@ use std::{
     fs,
     path::{Path, PathBuf},
 };
-use tracing::{error, warn};
+use tracing::{error, info, warn};
 
 pub trait EditableSetting: Serialize + DeserializeOwned + Default {
     const FILENAME: &'static str;
 
+    /// Tries to convert the contents of a settings file that was written in an
+    /// older format into the current one. Called when the file fails to parse
+    /// as `Self`, return `None` if there is nothing to migrate from.
+    fn migrate(_contents: &str) -> Option<Self> { None }
+
     fn load(data_dir: &Path) -> Self {
         let path = Self::get_path(data_dir);
 
-        if let Ok(file) = fs::File::open(&path) {
-            match ron::de::from_reader(file) {
+        if let Ok(contents) = fs::read_to_string(&path) {
+            match ron::de::from_str(&contents) {
                 Ok(setting) => setting,
                 Err(e) => {
+                    if let Some(setting) = Self::migrate(&contents) {
+                        // Keep the old file around in case the migration lost something
+                        let legacy_path = path.with_extension("legacy.ron");
+                        info!(
+                            ?path,
+                            ?legacy_path,
+                            "Migrated setting file from an older format, saving it in the new \
+                             format"
+                        );
+                        if let Err(e) = fs::copy(&path, &legacy_path) {
+                            warn!(?e, ?legacy_path, "Failed to back up old setting file.");
+                        }
+                        if let Err(e) = save_to_file(&setting, &path) {
+                            warn!(?e, ?path, "Failed to save migrated setting file.");
+                        }
+                        return setting;
+                    }
+
                     warn!(
                         ?e,
                         "Failed to parse setting file! Falling back to default and moving \
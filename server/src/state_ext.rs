@@ -1,6 +1,9 @@
 use crate::{
-    client::Client, persistence::PersistedComponents, presence::Presence,
-    sys::sentinel::DeletedEntities, SpawnPoint,
+    client::Client,
+    persistence::PersistedComponents,
+    presence::Presence,
+    sys::{safe_spawn::PendingSafeSpawn, sentinel::DeletedEntities},
+    SpawnPoint,
 };
 use common::{
     character::CharacterId,
@@ -14,10 +17,14 @@ use common::{
     slowjob::SlowJobPool,
     uid::{Uid, UidAllocator},
 };
+#[cfg(feature = "plugins")]
+use common::{resources::TimeOfDay, terrain::TerrainGrid};
 use common_net::{
     msg::{CharacterInfo, PlayerListUpdate, PresenceKind, ServerGeneral},
     sync::WorldSyncExt,
 };
+#[cfg(feature = "plugins")]
+use common_sys::plugin::{memory_manager::EcsWorld, storage::PluginStorage, PluginMgr};
 use common_sys::state::State;
 use rand::prelude::*;
 use specs::{
@@ -96,6 +103,10 @@ pub trait StateExt {
         &mut self,
         entity: EcsEntity,
     ) -> Result<(), specs::error::WrongGeneration>;
+    /// Runs an event in all plugins and returns the responses of the ones that
+    /// handle it. No component storage may be borrowed mutably while calling
+    /// this.
+    fn plugin_event<T: plugin_api::Event>(&self, event: &T) -> Vec<T::Response>;
 }
 
 impl StateExt for State {
@@ -399,7 +410,7 @@ impl StateExt for State {
     }
 
     fn update_character_data(&mut self, entity: EcsEntity, components: PersistedComponents) {
-        let (body, stats, inventory, waypoint) = components;
+        let (body, stats, inventory, bank, waypoint, persisted_state) = components;
 
         if let Some(player_uid) = self.read_component_copied::<Uid>(entity) {
             // Notify clients of a player list update
@@ -427,18 +438,46 @@ impl StateExt for State {
                     .unwrap_or(None)
                     .unwrap_or(0),
             );
-            self.write_component(entity, comp::Health::new(body, health_level));
-            self.write_component(entity, comp::Energy::new(body, energy_level));
+            let mut health = comp::Health::new(body, health_level);
+            let mut energy = comp::Energy::new(body, energy_level);
+            // Characters that were saved while dead come back at full health
+            if let Some(current) = persisted_state.health.filter(|h| *h > 0) {
+                health.set_to(current, comp::HealthSource::Unknown);
+            }
+            if let Some(current) = persisted_state.energy {
+                energy.set_to(current, comp::EnergySource::Unknown);
+            }
+            self.write_component(entity, health);
+            self.write_component(entity, energy);
             self.write_component(entity, comp::Poise::new(body));
+            let mut buffs = comp::Buffs::default();
+            for buff in persisted_state.buffs {
+                buffs.insert(buff);
+            }
+            self.write_component(entity, buffs);
             self.write_component(entity, stats);
             self.write_component(entity, inventory);
             self.write_component(
                 entity,
                 comp::InventoryUpdate::new(comp::InventoryUpdateEvent::default()),
             );
+            self.write_component(entity, bank);
 
             if let Some(waypoint) = waypoint {
                 self.write_component(entity, waypoint);
+            }
+
+            if let Some(pos) = persisted_state.position {
+                // The terrain at the restored position may have changed since the character
+                // was saved, so fall back to the waypoint if it turns out to be obstructed
+                let fallback = waypoint
+                    .map(|waypoint| waypoint.get_pos())
+                    .unwrap_or_else(|| self.ecs().read_resource::<SpawnPoint>().0);
+                self.write_component(entity, pos);
+                self.write_component(entity, comp::Vel(Vec3::zero()));
+                self.write_component(entity, comp::ForceUpdate);
+                self.write_component(entity, PendingSafeSpawn { fallback });
+            } else if let Some(waypoint) = waypoint {
                 self.write_component(entity, comp::Pos(waypoint.get_pos()));
                 self.write_component(entity, comp::Vel(Vec3::zero()));
                 self.write_component(entity, comp::ForceUpdate);
@@ -655,4 +694,38 @@ impl StateExt for State {
         }
         res
     }
+
+    fn plugin_event<T: plugin_api::Event>(&self, event: &T) -> Vec<T::Response> {
+        #[cfg(feature = "plugins")]
+        {
+            let ecs_world = EcsWorld {
+                entities: &self.ecs().entities(),
+                health: self.ecs().read_component().into(),
+                uid: self.ecs().read_component().into(),
+                uid_allocator: &self.ecs().read_resource::<UidAllocator>().into(),
+                player: self.ecs().read_component().into(),
+                pos: self.ecs().read_component().into(),
+                inventory: self.ecs().read_component().into(),
+                terrain: &self.ecs().read_resource::<TerrainGrid>().into(),
+                time_of_day: &self.ecs().read_resource::<TimeOfDay>().into(),
+                storage: &self.ecs().read_resource::<PluginStorage>().into(),
+            };
+            match self
+                .ecs()
+                .read_resource::<PluginMgr>()
+                .execute_event(&ecs_world, event)
+            {
+                Ok(responses) => responses,
+                Err(e) => {
+                    tracing::error!(?e, "Failed to run plugin event {}", event.get_event_name());
+                    Vec::new()
+                },
+            }
+        }
+        #[cfg(not(feature = "plugins"))]
+        {
+            let _ = event;
+            Vec::new()
+        }
+    }
 }
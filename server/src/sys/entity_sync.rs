@@ -1,11 +1,15 @@
 use super::sentinel::{DeletedEntities, ReadTrackers, TrackedComps};
 use crate::{
     client::Client,
+    interest::{self, Candidates, Importance, Interest},
     presence::{Presence, RegionSubscription},
     Tick,
 };
 use common::{
-    comp::{Collider, ForceUpdate, Inventory, InventoryUpdate, Last, Ori, Pos, Vel},
+    comp::{
+        Alignment, Body, Collider, ForceUpdate, Group, Inventory, InventoryUpdate, Last, Ori, Pos,
+        Vel,
+    },
     outcome::Outcome,
     region::{Event as RegionEvent, RegionMap},
     resources::TimeOfDay,
@@ -14,10 +18,36 @@ use common::{
     vol::RectVolSize,
 };
 use common_ecs::{Job, Origin, Phase, System};
-use common_net::{msg::ServerGeneral, sync::CompSyncPackage};
-use specs::{Entities, Join, Read, ReadExpect, ReadStorage, Write, WriteStorage};
+use common_net::{
+    msg::ServerGeneral,
+    sync::{PhysicsDeltaEncoder, PhysicsUpdate},
+};
+use hashbrown::HashMap;
+use specs::{
+    shred::ResourceId, Component, Entities, Join, Read, ReadExpect, ReadStorage, SystemData, World,
+    Write, WriteStorage,
+};
+use specs_idvs::IdvStorage;
 use vek::*;
 
+/// The physics updates of a client are delta encoded against the last ones it
+/// acknowledged
+#[derive(Debug, Default)]
+pub struct PhysicsDelta(pub PhysicsDeltaEncoder);
+
+impl Component for PhysicsDelta {
+    type Storage = IdvStorage<Self>;
+}
+
+/// Data needed to decide how often a client gets physics updates of an entity
+#[derive(SystemData)]
+pub struct InterestData<'a> {
+    interests: WriteStorage<'a, Interest>,
+    bodies: ReadStorage<'a, Body>,
+    alignments: ReadStorage<'a, Alignment>,
+    groups: ReadStorage<'a, Group>,
+}
+
 /// This system will send physics updates to the client
 #[derive(Default)]
 pub struct Sys;
@@ -42,6 +72,8 @@ impl<'a> System<'a> for Sys {
         ReadStorage<'a, Client>,
         WriteStorage<'a, ForceUpdate>,
         WriteStorage<'a, InventoryUpdate>,
+        WriteStorage<'a, PhysicsDelta>,
+        InterestData<'a>,
         Write<'a, DeletedEntities>,
         Write<'a, Vec<Outcome>>,
         TrackedComps<'a>,
@@ -73,6 +105,8 @@ impl<'a> System<'a> for Sys {
             clients,
             mut force_updates,
             mut inventory_updates,
+            mut physics_deltas,
+            mut interest_data,
             mut deleted_entities,
             mut outcomes,
             tracked_comps,
@@ -95,10 +129,12 @@ impl<'a> System<'a> for Sys {
         //       client per entity event)
         // 4. Iterate through entities in that region
         // 5. Inform clients of the component changes for that entity
-        //     - Throttle update rate base on distance to each client
+        //     - Throttle update rate based on distance to and importance for each
+        //       client, within an update budget per client
 
         // Sync physics
         // via iterating through regions
+        let mut physics_updates: HashMap<specs::Entity, Candidates> = HashMap::new();
         for (key, region) in region_map.iter() {
             // Assemble subscriber list for this region by iterating through clients and
             // checking if they are subscribed to this region
@@ -199,8 +235,14 @@ impl<'a> System<'a> for Sys {
                     .map(|msg| client.send_prepared(&msg));
             });
 
-            for (client, _, client_entity, client_pos) in &mut subscribers {
-                let mut comp_sync_package = CompSyncPackage::new();
+            for (_, _, client_entity, client_pos) in &mut subscribers {
+                let candidates = physics_updates.entry(*client_entity).or_default();
+                let interest = match interest_data.interests.entry(*client_entity) {
+                    Ok(entry) => entry.or_insert_with(Interest::default),
+                    Err(_) => continue,
+                };
+                let client_alignment = interest_data.alignments.get(*client_entity);
+                let client_group = interest_data.groups.get(*client_entity);
 
                 for (_, entity, &uid, &pos, vel, ori, force_update, collider) in (
                     region.entities(),
@@ -214,62 +256,49 @@ impl<'a> System<'a> for Sys {
                 )
                     .join()
                 {
+                    // New components are always sent
+                    let new_pos = last_pos.get(entity).is_none();
+                    let new_vel = vel.is_some() && last_vel.get(entity).is_none();
+                    let new_ori = ori.is_some() && last_ori.get(entity).is_none();
+
                     // Decide how regularly to send physics updates.
-                    let send_now = if client_entity == &entity {
+                    let (forced, priority) = if client_entity == &entity {
                         // Don't send client physics updates about itself unless force update is set
-                        force_update.is_some()
+                        (force_update.is_some(), None)
                     } else if matches!(collider, Some(Collider::Voxel { .. })) {
                         // Things with a voxel collider (airships, etc.) need to have very stable
                         // physics so we always send updated for these where
                         // we can.
-                        true
+                        (true, None)
                     } else {
-                        // Throttle update rates for all other entities based on distance to client
-                        let distance_sq = client_pos.0.distance_squared(pos.0);
-                        let id_staggered_tick = tick + entity.id() as u64;
-
-                        // More entities farther away so checks start there
-                        if distance_sq > 500.0f32.powi(2) {
-                            id_staggered_tick % 32 == 0
-                        } else if distance_sq > 300.0f32.powi(2) {
-                            id_staggered_tick % 16 == 0
-                        } else if distance_sq > 200.0f32.powi(2) {
-                            id_staggered_tick % 8 == 0
-                        } else if distance_sq > 120.0f32.powi(2) {
-                            id_staggered_tick % 6 == 0
-                        } else if distance_sq > 64.0f32.powi(2) {
-                            id_staggered_tick % 3 == 0
-                        } else if distance_sq > 24.0f32.powi(2) {
-                            id_staggered_tick % 2 == 0
-                        } else {
-                            true
-                        }
+                        // Throttle update rates for all other entities based on distance to
+                        // client and how important they are to it
+                        let importance = Importance::of(
+                            interest_data.bodies.get(entity),
+                            interest_data.alignments.get(entity),
+                            interest_data.groups.get(entity),
+                            client_alignment,
+                            client_group,
+                        );
+                        let interval = importance.interval(client_pos.0.distance_squared(pos.0));
+                        let priority =
+                            interest.due(uid, entity.id() as u64, tick, importance, interval);
+                        (false, priority)
                     };
 
-                    if last_pos.get(entity).is_none() {
-                        comp_sync_package.comp_inserted(uid, pos);
-                    } else if send_now {
-                        comp_sync_package.comp_modified(uid, pos);
+                    let send_now = forced || priority.is_some();
+                    let update = PhysicsUpdate {
+                        uid,
+                        pos: Some(pos).filter(|_| send_now || new_pos),
+                        vel: vel.copied().filter(|_| send_now || new_vel),
+                        ori: ori.copied().filter(|_| send_now || new_ori),
+                    };
+                    if forced || new_pos || new_vel || new_ori {
+                        candidates.force(update);
+                    } else if let Some(priority) = priority {
+                        candidates.push(priority, update);
                     }
-
-                    vel.map(|v| {
-                        if last_vel.get(entity).is_none() {
-                            comp_sync_package.comp_inserted(uid, *v);
-                        } else if send_now {
-                            comp_sync_package.comp_modified(uid, *v);
-                        }
-                    });
-
-                    ori.map(|o| {
-                        if last_ori.get(entity).is_none() {
-                            comp_sync_package.comp_inserted(uid, *o);
-                        } else if send_now {
-                            comp_sync_package.comp_modified(uid, *o);
-                        }
-                    });
                 }
-
-                client.send_fallible(ServerGeneral::CompSync(comp_sync_package));
             }
 
             // Update the last physics components for each entity
@@ -288,6 +317,31 @@ impl<'a> System<'a> for Sys {
             }
         }
 
+        // Send the physics updates of all regions at once, as many as the budget of
+        // the client allows, delta encoded against what the client acknowledged
+        for (client_entity, candidates) in physics_updates {
+            if let (Some(client), Some(presence), Some(interest), Ok(entry)) = (
+                clients.get(client_entity),
+                presences.get(client_entity),
+                interest_data.interests.get_mut(client_entity),
+                physics_deltas.entry(client_entity),
+            ) {
+                let budget = interest::budget(presence.view_distance);
+                let updates = interest.select(tick, budget, candidates);
+                let delta = entry.or_insert_with(PhysicsDelta::default);
+                client.send_fallible(ServerGeneral::PhysicsDelta(delta.0.encode(tick, updates)));
+            }
+        }
+        // Clients start over when they enter the game again
+        let left = (&entities, &physics_deltas, !&presences)
+            .join()
+            .map(|(entity, _, _)| entity)
+            .collect::<Vec<_>>();
+        for entity in left {
+            physics_deltas.remove(entity);
+            interest_data.interests.remove(entity);
+        }
+
         // Handle entity deletion in regions that don't exist in RegionMap
         // (theoretically none)
         for (region_key, deleted) in deleted_entities.take_remaining_deleted() {
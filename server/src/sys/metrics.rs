@@ -83,6 +83,35 @@ impl<'a> System<'a> for Sys {
                 .observe(len as f64 / NANOSEC_PER_SEC);
         }
 
+        for (name, stat) in sys_metrics.plugins.lock().unwrap().iter() {
+            export_ecs
+                .plugin_length_time
+                .with_label_values(&[name])
+                .set(stat.length_ns as i64);
+            export_ecs
+                .plugin_length_count
+                .with_label_values(&[name])
+                .inc_by(stat.length_ns);
+            export_ecs
+                .plugin_calls_count
+                .with_label_values(&[name])
+                .inc_by(stat.calls);
+            export_ecs
+                .plugin_fuel_count
+                .with_label_values(&[name])
+                .inc_by(stat.fuel);
+            export_ecs
+                .plugin_state
+                .with_label_values(&[name])
+                .set(if stat.disabled {
+                    2
+                } else if stat.throttled {
+                    1
+                } else {
+                    0
+                });
+        }
+
         // Report other info
         export_tick.time_of_day.set(time_of_day.0);
         if tick.0.rem_euclid(100) == 0 {
@@ -5,6 +5,7 @@ pub mod metrics;
 pub mod msg;
 pub mod object;
 pub mod persistence;
+pub mod safe_spawn;
 pub mod sentinel;
 pub mod subscription;
 pub mod terrain;
@@ -26,6 +27,7 @@ pub fn add_server_systems(dispatch_builder: &mut DispatcherBuilder) {
     dispatch::<waypoint::Sys>(dispatch_builder, &[]);
     dispatch::<invite_timeout::Sys>(dispatch_builder, &[]);
     dispatch::<persistence::Sys>(dispatch_builder, &[]);
+    dispatch::<safe_spawn::Sys>(dispatch_builder, &[]);
     dispatch::<object::Sys>(dispatch_builder, &[]);
 }
 
@@ -1,14 +1,26 @@
-use crate::{client::Client, presence::Presence, Settings};
+use crate::{
+    client::Client,
+    metrics::PlayerMetrics,
+    movement_validator::{self, MovementLimits, MovementValidation},
+    presence::Presence,
+    sys::entity_sync::PhysicsDelta,
+    Settings,
+};
 use common::{
-    comp::{CanBuild, ControlEvent, Controller, ForceUpdate, Health, Ori, Pos, Stats, Vel},
+    comp::{
+        Body, CanBuild, CharacterState, ControlEvent, Controller, ForceUpdate, Health, Mounting,
+        Ori, PhysicsState, Pos, Stats, Vel,
+    },
     event::{EventBus, ServerEvent},
+    resources::Time,
     terrain::TerrainGrid,
-    vol::ReadVol,
 };
 use common_ecs::{Job, Origin, Phase, System};
 use common_net::msg::{ClientGeneral, PresenceKind, ServerGeneral};
-use common_sys::state::BlockChange;
-use specs::{Entities, Join, Read, ReadExpect, ReadStorage, Write, WriteStorage};
+use specs::{
+    shred::ResourceId, Entities, Join, Read, ReadExpect, ReadStorage, SystemData, World,
+    WriteStorage,
+};
 use tracing::{debug, trace};
 
 impl Sys {
@@ -20,14 +32,16 @@ impl Sys {
         maybe_presence: &mut Option<&mut Presence>,
         terrain: &ReadExpect<'_, TerrainGrid>,
         can_build: &ReadStorage<'_, CanBuild>,
-        force_updates: &ReadStorage<'_, ForceUpdate>,
+        force_updates: &mut WriteStorage<'_, ForceUpdate>,
         stats: &mut WriteStorage<'_, Stats>,
         healths: &ReadStorage<'_, Health>,
-        block_changes: &mut Write<'_, BlockChange>,
         positions: &mut WriteStorage<'_, Pos>,
         velocities: &mut WriteStorage<'_, Vel>,
         orientations: &mut WriteStorage<'_, Ori>,
         controllers: &mut WriteStorage<'_, Controller>,
+        movement_validations: &mut WriteStorage<'_, MovementValidation>,
+        physics_deltas: &mut WriteStorage<'_, PhysicsDelta>,
+        movement: &MovementData<'_>,
         settings: &Read<'_, Settings>,
         msg: ClientGeneral,
     ) -> Result<(), crate::error::Error> {
@@ -96,19 +110,43 @@ impl Sys {
                     && force_updates.get(entity).is_none()
                     && healths.get(entity).map_or(true, |h| !h.is_dead)
                 {
+                    if let Err(violation) = Self::validate_movement(
+                        entity,
+                        terrain,
+                        positions,
+                        movement_validations,
+                        movement,
+                        pos,
+                        vel,
+                    ) {
+                        trace!(?entity, %violation, "Rejected player movement");
+                        movement
+                            .player_metrics
+                            .movement_violations
+                            .with_label_values(&[violation.as_str()])
+                            .inc();
+                        // Rubber-band the client back to the server's position
+                        let _ = force_updates.insert(entity, ForceUpdate);
+                        return Ok(());
+                    }
                     let _ = positions.insert(entity, pos);
                     let _ = velocities.insert(entity, vel);
                     let _ = orientations.insert(entity, ori);
                 }
             },
+            ClientGeneral::AckPhysicsDelta(tick) => {
+                if let Some(physics_delta) = physics_deltas.get_mut(entity) {
+                    physics_delta.0.ack(tick);
+                }
+            },
             ClientGeneral::BreakBlock(pos) => {
-                if let Some(block) = can_build.get(entity).and_then(|_| terrain.get(pos).ok()) {
-                    block_changes.set(pos, block.into_vacant());
+                if can_build.get(entity).is_some() {
+                    server_emitter.emit(ServerEvent::BreakBlock { entity, pos });
                 }
             },
             ClientGeneral::PlaceBlock(pos, block) => {
                 if can_build.get(entity).is_some() {
-                    block_changes.try_set(pos, block);
+                    server_emitter.emit(ServerEvent::PlaceBlock { entity, pos, block });
                 }
             },
             ClientGeneral::UnlockSkill(skill) => {
@@ -129,10 +167,66 @@ impl Sys {
             ClientGeneral::RequestSiteInfo(id) => {
                 server_emitter.emit(ServerEvent::RequestSiteInfo { entity, id });
             },
+            ClientGeneral::MailAction(action) => {
+                server_emitter.emit(ServerEvent::MailAction { entity, action });
+            },
             _ => tracing::error!("not a client_in_game msg"),
         }
         Ok(())
     }
+
+    /// Compare a movement reported by a client with what the server expects
+    /// to be possible for the character.
+    #[allow(clippy::too_many_arguments)]
+    fn validate_movement(
+        entity: specs::Entity,
+        terrain: &TerrainGrid,
+        positions: &WriteStorage<'_, Pos>,
+        movement_validations: &mut WriteStorage<'_, MovementValidation>,
+        movement: &MovementData<'_>,
+        pos: Pos,
+        vel: Vel,
+    ) -> Result<(), movement_validator::Violation> {
+        // Mounted entities are moved by their mount
+        if movement.mountings.get(entity).is_some() {
+            return Ok(());
+        }
+        let (old_pos, body) = match (positions.get(entity), movement.bodies.get(entity)) {
+            (Some(old_pos), Some(body)) => (old_pos.0, body),
+            _ => return Ok(()),
+        };
+        let character_state = movement.character_states.get(entity);
+        let limits =
+            MovementLimits::new(body, character_state, movement.physics_states.get(entity));
+        let movement_validation = match movement_validations
+            .entry(entity)
+            .map(|e| e.or_insert_with(MovementValidation::default))
+        {
+            Ok(movement_validation) => movement_validation,
+            Err(_) => return Ok(()),
+        };
+
+        let result = movement_validation
+            .validate(movement.time.0, old_pos, pos.0, vel.0, limits)
+            .and_then(|_| {
+                movement_validator::check_terrain(terrain, body, character_state, old_pos, pos.0)
+            });
+        if let Err(violation) = result {
+            movement_validation.record(violation);
+        }
+        result
+    }
+}
+
+/// Data needed to validate the movement reported by clients
+#[derive(SystemData)]
+pub struct MovementData<'a> {
+    time: Read<'a, Time>,
+    bodies: ReadStorage<'a, Body>,
+    character_states: ReadStorage<'a, CharacterState>,
+    physics_states: ReadStorage<'a, PhysicsState>,
+    mountings: ReadStorage<'a, Mounting>,
+    player_metrics: ReadExpect<'a, PlayerMetrics>,
 }
 
 /// This system will handle new messages from clients
@@ -145,16 +239,18 @@ impl<'a> System<'a> for Sys {
         Read<'a, EventBus<ServerEvent>>,
         ReadExpect<'a, TerrainGrid>,
         ReadStorage<'a, CanBuild>,
-        ReadStorage<'a, ForceUpdate>,
+        WriteStorage<'a, ForceUpdate>,
         WriteStorage<'a, Stats>,
         ReadStorage<'a, Health>,
-        Write<'a, BlockChange>,
         WriteStorage<'a, Pos>,
         WriteStorage<'a, Vel>,
         WriteStorage<'a, Ori>,
         WriteStorage<'a, Presence>,
         WriteStorage<'a, Client>,
         WriteStorage<'a, Controller>,
+        WriteStorage<'a, MovementValidation>,
+        WriteStorage<'a, PhysicsDelta>,
+        MovementData<'a>,
         Read<'a, Settings>,
     );
 
@@ -169,16 +265,18 @@ impl<'a> System<'a> for Sys {
             server_event_bus,
             terrain,
             can_build,
-            force_updates,
+            mut force_updates,
             mut stats,
             healths,
-            mut block_changes,
             mut positions,
             mut velocities,
             mut orientations,
             mut presences,
             mut clients,
             mut controllers,
+            mut movement_validations,
+            mut physics_deltas,
+            movement,
             settings,
         ): Self::SystemData,
     ) {
@@ -195,14 +293,16 @@ impl<'a> System<'a> for Sys {
                     &mut maybe_presence.as_deref_mut(),
                     &terrain,
                     &can_build,
-                    &force_updates,
+                    &mut force_updates,
                     &mut stats,
                     &healths,
-                    &mut block_changes,
                     &mut positions,
                     &mut velocities,
                     &mut orientations,
                     &mut controllers,
+                    &mut movement_validations,
+                    &mut physics_deltas,
+                    &movement,
                     &settings,
                     msg,
                 )
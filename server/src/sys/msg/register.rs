@@ -1,11 +1,14 @@
 use crate::{
     client::Client,
+    data_dir::DataDir,
     login_provider::{LoginProvider, PendingLogin},
     metrics::PlayerMetrics,
     EditableSettings,
 };
 use common::{
-    comp::{Admin, Player, Stats},
+    comp::{Admin, Inventory, Player, Pos, Stats},
+    resources::TimeOfDay,
+    terrain::TerrainGrid,
     uid::{Uid, UidAllocator},
 };
 use common_ecs::{Job, Origin, Phase, System};
@@ -14,6 +17,7 @@ use common_net::msg::{
     ServerRegisterAnswer,
 };
 use hashbrown::HashMap;
+use network::ProtocolAddr;
 use plugin_api::Health;
 use specs::{Entities, Join, Read, ReadExpect, ReadStorage, WriteExpect, WriteStorage};
 use tracing::trace;
@@ -22,12 +26,16 @@ use tracing::trace;
 use common_sys::plugin::memory_manager::EcsWorld;
 
 #[cfg(feature = "plugins")]
-use common_sys::plugin::PluginMgr;
+use common_sys::plugin::{storage::PluginStorage, PluginMgr};
 
 #[cfg(feature = "plugins")]
 type ReadPlugin<'a> = Read<'a, PluginMgr>;
 #[cfg(not(feature = "plugins"))]
 type ReadPlugin<'a> = Option<Read<'a, ()>>;
+#[cfg(feature = "plugins")]
+type ReadPluginStorage<'a> = Read<'a, PluginStorage>;
+#[cfg(not(feature = "plugins"))]
+type ReadPluginStorage<'a> = Option<Read<'a, ()>>;
 
 /// This system will handle new messages from clients
 #[derive(Default)]
@@ -47,7 +55,13 @@ impl<'a> System<'a> for Sys {
         ReadStorage<'a, Stats>,
         WriteExpect<'a, LoginProvider>,
         WriteStorage<'a, Admin>,
-        ReadExpect<'a, EditableSettings>,
+        WriteExpect<'a, EditableSettings>,
+        ReadExpect<'a, DataDir>,
+        ReadStorage<'a, Pos>,
+        ReadStorage<'a, Inventory>,
+        ReadExpect<'a, TerrainGrid>,
+        Read<'a, TimeOfDay>,
+        ReadPluginStorage<'a>,
     );
 
     const NAME: &'static str = "msg::register";
@@ -69,7 +83,13 @@ impl<'a> System<'a> for Sys {
             stats,
             mut login_provider,
             mut admins,
-            editable_settings,
+            mut editable_settings,
+            data_dir,
+            positions,
+            inventories,
+            terrain,
+            time_of_day,
+            plugin_storage,
         ): Self::SystemData,
     ) {
         // Player list to send new players.
@@ -78,7 +98,7 @@ impl<'a> System<'a> for Sys {
             .map(|(uid, player, stats, admin)| {
                 (*uid, PlayerInfo {
                     is_online: true,
-                    is_admin: admin.is_some(),
+                    admin_role: admin.map(|admin| admin.0),
                     player_alias: player.alias.clone(),
                     character: stats.map(|stats| CharacterInfo {
                         name: stats.name.clone(),
@@ -99,6 +119,7 @@ impl<'a> System<'a> for Sys {
             });
         }
 
+        let editable_settings = &mut *editable_settings;
         let mut finished_pending = vec![];
         for (entity, client, mut pending) in (&entities, &clients, &mut pending_logins).join() {
             if let Err(e) = || -> std::result::Result<(), crate::error::Error> {
@@ -109,8 +130,23 @@ impl<'a> System<'a> for Sys {
                     uid: (&uids).into(),
                     player: (&players).into(),
                     uid_allocator: &uid_allocator,
+                    pos: (&positions).into(),
+                    inventory: (&inventories).into(),
+                    terrain: &terrain,
+                    time_of_day: &time_of_day,
+                    storage: &plugin_storage,
                 };
 
+                let ip_addr = client
+                    .participant
+                    .as_ref()
+                    .and_then(|participant| match participant.remote_address() {
+                        ProtocolAddr::Tcp(addr)
+                        | ProtocolAddr::Udp(addr)
+                        | ProtocolAddr::Quic(addr) => Some(addr.ip()),
+                        ProtocolAddr::Mpsc(_) | ProtocolAddr::Replay(_) => None,
+                    });
+
                 let (username, uuid) = match login_provider.try_login(
                     &mut pending,
                     #[cfg(feature = "plugins")]
@@ -119,7 +155,9 @@ impl<'a> System<'a> for Sys {
                     &plugin_mgr,
                     &*editable_settings.admins,
                     &*editable_settings.whitelist,
-                    &*editable_settings.banlist,
+                    &mut editable_settings.banlist,
+                    data_dir.as_ref(),
+                    ip_addr,
                 ) {
                     None => return Ok(()),
                     Some(r) => {
@@ -136,7 +174,7 @@ impl<'a> System<'a> for Sys {
                 };
 
                 let player = Player::new(username, uuid);
-                let is_admin = editable_settings.admins.contains(&uuid);
+                let admin_role = editable_settings.admins.get(&uuid).copied();
 
                 if !player.is_valid() {
                     // Invalid player
@@ -151,8 +189,8 @@ impl<'a> System<'a> for Sys {
 
                     // Give the Admin component to the player if their name exists in
                     // admin list
-                    if is_admin {
-                        let _ = admins.insert(entity, Admin);
+                    if let Some(admin_role) = admin_role {
+                        let _ = admins.insert(entity, Admin(admin_role));
                     }
 
                     // Tell the client its request was successful.
@@ -186,7 +224,7 @@ impl<'a> System<'a> for Sys {
                             PlayerListUpdate::Add(*uid, PlayerInfo {
                                 player_alias: player.alias.clone(),
                                 is_online: true,
-                                is_admin: admins.get(entity).is_some(),
+                                admin_role: admins.get(entity).map(|admin| admin.0),
                                 character: None, // new players will be on character select.
                             }),
                         )));
@@ -1,54 +1,87 @@
-use crate::{persistence::character_updater, presence::Presence, sys::SysScheduler};
-use common::comp::{Inventory, Stats, Waypoint};
+use crate::{
+    persistence::{character_updater, PersistedState},
+    presence::Presence,
+    sys::SysScheduler,
+};
+use common::comp::{Bank, Buffs, Energy, Health, Inventory, Pos, Stats, Waypoint};
 use common_ecs::{Job, Origin, Phase, System};
 use common_net::msg::PresenceKind;
 use specs::{Join, ReadExpect, ReadStorage, Write};
 
+/// The components of characters that are saved, along with the updater to
+/// save them with
+pub type CharacterData<'a> = (
+    ReadStorage<'a, Presence>,
+    ReadStorage<'a, Stats>,
+    ReadStorage<'a, Inventory>,
+    ReadStorage<'a, Bank>,
+    ReadStorage<'a, Waypoint>,
+    ReadStorage<'a, Health>,
+    ReadStorage<'a, Energy>,
+    ReadStorage<'a, Buffs>,
+    ReadStorage<'a, Pos>,
+    ReadExpect<'a, character_updater::CharacterUpdater>,
+);
+
+/// Queues a save of all characters that are currently logged in
+pub fn save_characters(
+    (
+        presences,
+        player_stats,
+        player_inventories,
+        player_banks,
+        player_waypoint,
+        player_healths,
+        player_energies,
+        player_buffs,
+        player_positions,
+        updater,
+    ): CharacterData,
+) {
+    updater.batch_update(
+        (
+            &presences,
+            &player_stats,
+            &player_inventories,
+            &player_banks,
+            player_waypoint.maybe(),
+            player_healths.maybe(),
+            player_energies.maybe(),
+            player_buffs.maybe(),
+            player_positions.maybe(),
+        )
+            .join()
+            .filter_map(
+                |(presence, stats, inventory, bank, waypoint, health, energy, buffs, pos)| {
+                    match presence.kind {
+                        PresenceKind::Character(id) => Some((
+                            id,
+                            stats,
+                            inventory,
+                            bank,
+                            waypoint,
+                            PersistedState::from_components(health, energy, buffs, pos),
+                        )),
+                        PresenceKind::Spectator => None,
+                    }
+                },
+            ),
+    );
+}
+
 #[derive(Default)]
 pub struct Sys;
 
 impl<'a> System<'a> for Sys {
-    #[allow(clippy::type_complexity)]
-    type SystemData = (
-        ReadStorage<'a, Presence>,
-        ReadStorage<'a, Stats>,
-        ReadStorage<'a, Inventory>,
-        ReadStorage<'a, Waypoint>,
-        ReadExpect<'a, character_updater::CharacterUpdater>,
-        Write<'a, SysScheduler<Self>>,
-    );
+    type SystemData = (CharacterData<'a>, Write<'a, SysScheduler<Self>>);
 
     const NAME: &'static str = "persistence";
     const ORIGIN: Origin = Origin::Server;
     const PHASE: Phase = Phase::Create;
 
-    fn run(
-        _job: &mut Job<Self>,
-        (
-            presences,
-            player_stats,
-            player_inventories,
-            player_waypoint,
-            updater,
-            mut scheduler,
-        ): Self::SystemData,
-    ) {
+    fn run(_job: &mut Job<Self>, (characters, mut scheduler): Self::SystemData) {
         if scheduler.should_run() {
-            updater.batch_update(
-                (
-                    &presences,
-                    &player_stats,
-                    &player_inventories,
-                    player_waypoint.maybe(),
-                )
-                    .join()
-                    .filter_map(
-                        |(presence, stats, inventory, waypoint)| match presence.kind {
-                            PresenceKind::Character(id) => Some((id, stats, inventory, waypoint)),
-                            PresenceKind::Spectator => None,
-                        },
-                    ),
-            );
+            save_characters(characters);
         }
     }
 }
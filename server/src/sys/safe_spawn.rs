@@ -0,0 +1,82 @@
+use crate::movement_validator;
+use common::{
+    comp::{Body, ForceUpdate, Pos, Vel},
+    terrain::TerrainGrid,
+};
+use common_ecs::{Job, Origin, Phase, System};
+use specs::{Component, Entities, Join, ReadExpect, ReadStorage, WriteStorage};
+use specs_idvs::IdvStorage;
+use vek::*;
+
+/// A character placed at a position restored from the database, which still
+/// has to be checked against the terrain once the chunk it lies in is loaded.
+pub struct PendingSafeSpawn {
+    pub fallback: Vec3<f32>,
+}
+
+impl Component for PendingSafeSpawn {
+    type Storage = IdvStorage<Self>;
+}
+
+/// This system moves characters whose restored position turned out to be
+/// inside terrain (e.g. because the chunk changed since they logged out) to
+/// their fallback position
+#[derive(Default)]
+pub struct Sys;
+impl<'a> System<'a> for Sys {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, TerrainGrid>,
+        ReadStorage<'a, Body>,
+        WriteStorage<'a, Pos>,
+        WriteStorage<'a, Vel>,
+        WriteStorage<'a, ForceUpdate>,
+        WriteStorage<'a, PendingSafeSpawn>,
+    );
+
+    const NAME: &'static str = "safe_spawn";
+    const ORIGIN: Origin = Origin::Server;
+    const PHASE: Phase = Phase::Create;
+
+    fn run(
+        _job: &mut Job<Self>,
+        (
+            entities,
+            terrain,
+            bodies,
+            mut positions,
+            mut velocities,
+            mut force_updates,
+            mut pending_safe_spawns,
+        ): Self::SystemData,
+    ) {
+        let mut checked = Vec::new();
+
+        for (entity, pending, body, pos, vel) in (
+            &entities,
+            &pending_safe_spawns,
+            &bodies,
+            &mut positions,
+            &mut velocities,
+        )
+            .join()
+        {
+            let chunk_key = terrain.pos_key(pos.0.map(|e| e.floor() as i32));
+            if terrain.get_key(chunk_key).is_none() {
+                continue;
+            }
+
+            if movement_validator::check_terrain(&terrain, body, None, pos.0, pos.0).is_err() {
+                pos.0 = pending.fallback;
+                vel.0 = Vec3::zero();
+                let _ = force_updates.insert(entity, ForceUpdate);
+            }
+            checked.push(entity);
+        }
+
+        for entity in checked {
+            pending_safe_spawns.remove(entity);
+        }
+    }
+}
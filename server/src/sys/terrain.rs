@@ -1,5 +1,6 @@
 use crate::{
-    chunk_generator::ChunkGenerator, client::Client, presence::Presence, rtsim::RtSim, Tick,
+    chunk_generator::ChunkGenerator, client::Client, presence::Presence, rtsim::RtSim,
+    terrain_persistence::TerrainPersistence, Tick,
 };
 use common::{
     comp::{self, bird_medium, inventory::loadout_builder::LoadoutConfig, Alignment, Pos},
@@ -22,6 +23,9 @@ use vek::*;
 ///     2. Sends new chunks to nearby clients
 ///     3. Handles the chunk's supplement (e.g. npcs)
 ///     4. Removes chunks outside the range of players
+///
+/// If terrain persistence is enabled, persisted block changes are applied to
+/// chunks before they are inserted and written back out when they unload.
 #[derive(Default)]
 pub struct Sys;
 impl<'a> System<'a> for Sys {
@@ -33,6 +37,7 @@ impl<'a> System<'a> for Sys {
         WriteExpect<'a, TerrainGrid>,
         Write<'a, TerrainChanges>,
         WriteExpect<'a, RtSim>,
+        Option<Write<'a, TerrainPersistence>>,
         ReadStorage<'a, Pos>,
         ReadStorage<'a, Presence>,
         ReadStorage<'a, Client>,
@@ -51,6 +56,7 @@ impl<'a> System<'a> for Sys {
             mut terrain,
             mut terrain_changes,
             mut rtsim,
+            mut terrain_persistence,
             positions,
             presences,
             clients,
@@ -61,7 +67,7 @@ impl<'a> System<'a> for Sys {
         // Fetch any generated `TerrainChunk`s and insert them into the terrain.
         // Also, send the chunk data to anybody that is close by.
         'insert_terrain_chunks: while let Some((key, res)) = chunk_generator.recv_new_chunk() {
-            let (chunk, supplement) = match res {
+            let (mut chunk, supplement) = match res {
                 Ok((chunk, supplement)) => (chunk, supplement),
                 Err(Some(entity)) => {
                     if let Some(client) = clients.get(entity) {
@@ -76,6 +82,12 @@ impl<'a> System<'a> for Sys {
                     continue 'insert_terrain_chunks;
                 },
             };
+
+            // Apply changes from terrain persistence to this chunk
+            if let Some(terrain_persistence) = terrain_persistence.as_mut() {
+                terrain_persistence.apply_changes(key, &mut chunk);
+            }
+
             // Send the chunk to all nearby players.
             for (presence, pos, client) in (&presences, &positions, &clients).join() {
                 let chunk_pos = terrain.pos_key(pos.0.map(|e| e as i32));
@@ -238,6 +250,10 @@ impl<'a> System<'a> for Sys {
             if terrain.remove(key).is_some() {
                 terrain_changes.removed_chunks.insert(key);
                 rtsim.hook_unload_chunk(key);
+
+                if let Some(terrain_persistence) = terrain_persistence.as_mut() {
+                    terrain_persistence.unload_chunk(key);
+                }
             }
 
             chunk_generator.cancel_if_pending(key);
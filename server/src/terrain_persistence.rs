@@ -0,0 +1,310 @@
+use common::{
+    terrain::{Block, TerrainChunk, TerrainChunkSize, TerrainGrid},
+    vol::{RectVolSize, WriteVol},
+};
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use tracing::{debug, error, warn};
+use vek::*;
+
+/// Stores the blocks that players (and server events) have changed, so that
+/// they can be re-applied on top of freshly generated chunks.
+///
+/// Each chunk's changes live in their own file so that only chunks that are
+/// actually loaded need to be kept in memory.
+pub struct TerrainPersistence {
+    path: PathBuf,
+    chunks: HashMap<Vec2<i32>, LoadedChunk>,
+    last_flush: Instant,
+}
+
+/// How often the changes of loaded chunks are written to disk, so that a crash
+/// only loses the changes made since the last flush.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Default)]
+struct LoadedChunk {
+    chunk: Chunk,
+    modified: bool,
+}
+
+impl TerrainPersistence {
+    /// Create a new terrain persistence store, using `path` as the directory
+    /// that chunk diffs are stored in.
+    pub fn new(path: PathBuf) -> Self {
+        if let Err(e) = std::fs::create_dir_all(&path) {
+            error!(?e, ?path, "Failed to create terrain persistence directory");
+        }
+        debug!(?path, "Loading terrain persistence");
+
+        Self {
+            path,
+            chunks: HashMap::default(),
+            last_flush: Instant::now(),
+        }
+    }
+
+    fn path_for(&self, key: Vec2<i32>) -> PathBuf { chunk_path(&self.path, key) }
+
+    fn load_chunk(&mut self, key: Vec2<i32>) -> &mut LoadedChunk {
+        let path = self.path_for(key);
+        self.chunks.entry(key).or_insert_with(|| {
+            File::open(&path)
+                .ok()
+                .and_then(|f| {
+                    bincode::deserialize_from::<_, VersionedChunk>(BufReader::new(f))
+                        .map_err(|e| {
+                            warn!(
+                                ?e,
+                                ?path,
+                                "Failed to read persisted terrain chunk, ignoring"
+                            );
+                        })
+                        .ok()
+                })
+                .map(|chunk| LoadedChunk {
+                    chunk: chunk.into(),
+                    modified: false,
+                })
+                .unwrap_or_default()
+        })
+    }
+
+    /// Apply the persisted changes of a chunk to a freshly generated chunk.
+    pub fn apply_changes(&mut self, key: Vec2<i32>, terrain_chunk: &mut TerrainChunk) {
+        let loaded_chunk = self.load_chunk(key);
+
+        for (rpos, block) in loaded_chunk.chunk.blocks() {
+            if let Err(e) = terrain_chunk.set(rpos, block) {
+                warn!(?e, ?rpos, "Failed to apply persisted terrain change");
+            }
+        }
+    }
+
+    /// Record a block change so that it can be restored later.
+    pub fn set_block(&mut self, pos: Vec3<i32>, block: Block) {
+        let key = TerrainGrid::chunk_key(pos);
+        let loaded_chunk = self.load_chunk(key);
+        loaded_chunk
+            .chunk
+            .blocks
+            .insert(TerrainGrid::chunk_offs(pos), block);
+        loaded_chunk.modified = true;
+    }
+
+    /// Write the changes of a chunk to disk (if there are any) and stop
+    /// keeping them in memory.
+    pub fn unload_chunk(&mut self, key: Vec2<i32>) {
+        if let Some(loaded_chunk) = self.chunks.remove(&key) {
+            if loaded_chunk.modified {
+                if let Err(e) = write_chunk(&self.path_for(key), &loaded_chunk.chunk) {
+                    error!(?e, ?key, "Failed to persist terrain chunk");
+                }
+            }
+        }
+    }
+
+    /// Write the changes of loaded chunks to disk if [`FLUSH_INTERVAL`] has
+    /// passed since the last time this happened. Should be called every tick.
+    pub fn maintain(&mut self) {
+        if self.last_flush.elapsed() >= FLUSH_INTERVAL {
+            self.flush();
+        }
+    }
+
+    /// Write the changes of all loaded chunks to disk, keeping them loaded.
+    pub fn flush(&mut self) {
+        for (key, loaded_chunk) in self.chunks.iter_mut() {
+            if loaded_chunk.modified {
+                match write_chunk(&chunk_path(&self.path, *key), &loaded_chunk.chunk) {
+                    Ok(()) => loaded_chunk.modified = false,
+                    Err(e) => error!(?e, ?key, "Failed to persist terrain chunk"),
+                }
+            }
+        }
+        self.last_flush = Instant::now();
+    }
+
+    /// Write the changes of all loaded chunks to disk.
+    pub fn unload_all(&mut self) {
+        let keys = self.chunks.keys().copied().collect::<Vec<_>>();
+        for key in keys {
+            self.unload_chunk(key);
+        }
+    }
+
+    /// Forget all changes made to a chunk, restoring it to the state produced
+    /// by worldgen the next time it is generated.
+    pub fn clear_chunk(&mut self, key: Vec2<i32>) {
+        self.chunks.remove(&key);
+        let path = self.path_for(key);
+        if path.exists() {
+            if let Err(e) = std::fs::remove_file(&path) {
+                error!(?e, ?path, "Failed to remove persisted terrain chunk");
+            }
+        }
+    }
+}
+
+fn chunk_path(dir: &Path, key: Vec2<i32>) -> PathBuf {
+    dir.join(format!("chunk_{}_{}.dat", key.x, key.y))
+}
+
+fn write_chunk(path: &Path, chunk: &Chunk) -> io::Result<()> {
+    // Write to a temporary file first so that a crash mid-write can't leave a
+    // truncated chunk behind
+    let tmp_path = path.with_extension("dat.tmp");
+    {
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        bincode::serialize_into(&mut writer, &VersionedChunkRef::V1(chunk))
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    }
+    std::fs::rename(&tmp_path, path)
+}
+
+impl Drop for TerrainPersistence {
+    fn drop(&mut self) { self.unload_all(); }
+}
+
+/// The blocks of a chunk that differ from worldgen, indexed by their position
+/// relative to the chunk (with an absolute z coordinate).
+#[derive(Default, Serialize, Deserialize)]
+struct Chunk {
+    blocks: HashMap<Vec3<i32>, Block>,
+}
+
+impl Chunk {
+    fn blocks(&self) -> impl Iterator<Item = (Vec3<i32>, Block)> + '_ {
+        self.blocks
+            .iter()
+            .filter(|(rpos, _)| {
+                rpos.x >= 0
+                    && rpos.y >= 0
+                    && rpos.x < TerrainChunkSize::RECT_SIZE.x as i32
+                    && rpos.y < TerrainChunkSize::RECT_SIZE.y as i32
+            })
+            .map(|(rpos, block)| (*rpos, *block))
+    }
+}
+
+/// On-disk representation of a chunk. New variants should be added when the
+/// format changes so that older files can still be read.
+#[derive(Serialize, Deserialize)]
+enum VersionedChunk {
+    V1(Chunk),
+}
+
+/// Borrowed counterpart of [`VersionedChunk`], serializes to the same bytes.
+#[derive(Serialize)]
+enum VersionedChunkRef<'a> {
+    V1(&'a Chunk),
+}
+
+impl From<VersionedChunk> for Chunk {
+    fn from(chunk: VersionedChunk) -> Self {
+        match chunk {
+            VersionedChunk::V1(chunk) => chunk,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::{
+        terrain::{BlockKind, SpriteKind, TerrainChunkMeta},
+        vol::ReadVol,
+    };
+
+    fn test_dir(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "veloren-terrain-persistence-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&path);
+        path
+    }
+
+    fn fresh_chunk() -> TerrainChunk {
+        TerrainChunk::new(
+            0,
+            Block::new(BlockKind::Rock, Rgb::new(100, 100, 100)),
+            Block::air(SpriteKind::Empty),
+            TerrainChunkMeta::void(),
+        )
+    }
+
+    fn rock() -> Block { Block::new(BlockKind::Rock, Rgb::new(100, 100, 100)) }
+
+    #[test]
+    fn changes_survive_a_restart() {
+        let path = test_dir("restart");
+        let pos = Vec3::new(3, 5, 10);
+        {
+            let mut terrain_persistence = TerrainPersistence::new(path.clone());
+            terrain_persistence.set_block(pos, rock());
+        }
+
+        let mut terrain_persistence = TerrainPersistence::new(path.clone());
+        let mut chunk = fresh_chunk();
+        terrain_persistence.apply_changes(TerrainGrid::chunk_key(pos), &mut chunk);
+        assert_eq!(chunk.get(TerrainGrid::chunk_offs(pos)).ok(), Some(&rock()));
+
+        drop(terrain_persistence);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn flush_writes_without_unloading() {
+        let path = test_dir("flush");
+        let pos = Vec3::new(-40, 7, 3);
+        let key = TerrainGrid::chunk_key(pos);
+        let mut terrain_persistence = TerrainPersistence::new(path.clone());
+        terrain_persistence.set_block(pos, rock());
+        terrain_persistence.flush();
+        assert!(terrain_persistence.path_for(key).exists());
+        assert!(terrain_persistence.chunks.contains_key(&key));
+
+        // Simulate a crash, nothing is written on drop
+        std::mem::forget(terrain_persistence);
+        let mut terrain_persistence = TerrainPersistence::new(path.clone());
+        let mut chunk = fresh_chunk();
+        terrain_persistence.apply_changes(key, &mut chunk);
+        assert_eq!(chunk.get(TerrainGrid::chunk_offs(pos)).ok(), Some(&rock()));
+
+        drop(terrain_persistence);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn cleared_chunks_are_pristine() {
+        let path = test_dir("clear");
+        let pos = Vec3::new(1, 1, 1);
+        let key = TerrainGrid::chunk_key(pos);
+        {
+            let mut terrain_persistence = TerrainPersistence::new(path.clone());
+            terrain_persistence.set_block(pos, rock());
+            terrain_persistence.unload_chunk(key);
+            terrain_persistence.clear_chunk(key);
+            assert!(!terrain_persistence.path_for(key).exists());
+        }
+
+        let mut terrain_persistence = TerrainPersistence::new(path.clone());
+        let mut chunk = fresh_chunk();
+        terrain_persistence.apply_changes(key, &mut chunk);
+        assert_eq!(
+            chunk.get(TerrainGrid::chunk_offs(pos)).ok(),
+            Some(&Block::empty())
+        );
+
+        drop(terrain_persistence);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+}
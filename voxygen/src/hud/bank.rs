@@ -0,0 +1,261 @@
+use super::{
+    get_quality_col,
+    img_ids::{Imgs, ImgsRot},
+    item_imgs::ItemImgs,
+    slots::SlotManager,
+    Show, TEXT_COLOR, UI_HIGHLIGHT_0, UI_MAIN,
+};
+use crate::{
+    i18n::Localization,
+    ui::{
+        fonts::Fonts,
+        slot::{ContentSize, SlotMaker},
+        ImageFrame, Tooltip, TooltipManager, Tooltipable,
+    },
+};
+use common::comp::{
+    item::{MaterialStatManifest, Quality},
+    Bank,
+};
+use conrod_core::{
+    color,
+    widget::{self, Button, Image, Rectangle, Text},
+    widget_ids, Color, Colorable, Positionable, Sizeable, Widget, WidgetCommon,
+};
+use vek::*;
+
+/// Number of bank slots in each row of the window
+const COLUMNS: usize = 8;
+
+widget_ids! {
+    pub struct Ids {
+        frame,
+        close,
+        title_align,
+        title,
+        bg,
+        icon,
+        slots_align,
+        slots[],
+        space_txt,
+    }
+}
+
+pub struct State {
+    ids: Ids,
+}
+
+#[derive(WidgetCommon)]
+pub struct BankWindow<'a> {
+    show: &'a Show,
+    bank: &'a Bank,
+    imgs: &'a Imgs,
+    item_imgs: &'a ItemImgs,
+    fonts: &'a Fonts,
+    rot_imgs: &'a ImgsRot,
+    tooltip_manager: &'a mut TooltipManager,
+    slot_manager: &'a mut SlotManager,
+    localized_strings: &'a Localization,
+    msm: &'a MaterialStatManifest,
+    pulse: f32,
+
+    #[conrod(common_builder)]
+    common: widget::CommonBuilder,
+}
+
+impl<'a> BankWindow<'a> {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        show: &'a Show,
+        bank: &'a Bank,
+        imgs: &'a Imgs,
+        item_imgs: &'a ItemImgs,
+        fonts: &'a Fonts,
+        rot_imgs: &'a ImgsRot,
+        tooltip_manager: &'a mut TooltipManager,
+        slot_manager: &'a mut SlotManager,
+        localized_strings: &'a Localization,
+        msm: &'a MaterialStatManifest,
+        pulse: f32,
+    ) -> Self {
+        Self {
+            show,
+            bank,
+            imgs,
+            item_imgs,
+            fonts,
+            rot_imgs,
+            tooltip_manager,
+            slot_manager,
+            localized_strings,
+            msm,
+            pulse,
+            common: widget::CommonBuilder::default(),
+        }
+    }
+}
+
+pub enum Event {
+    Close,
+}
+
+impl<'a> Widget for BankWindow<'a> {
+    type Event = Option<Event>;
+    type State = State;
+    type Style = ();
+
+    fn init_state(&self, id_gen: widget::id::Generator) -> Self::State {
+        State {
+            ids: Ids::new(id_gen),
+        }
+    }
+
+    #[allow(clippy::unused_unit)] // TODO: Pending review in #587
+    fn style(&self) -> Self::Style { () }
+
+    fn update(self, args: widget::UpdateArgs<Self>) -> Self::Event {
+        let widget::UpdateArgs { state, ui, .. } = args;
+        let mut event = None;
+        let i18n = self.localized_strings;
+        let item_tooltip = Tooltip::new({
+            // Edge images [t, b, r, l]
+            // Corner images [tr, tl, br, bl]
+            let edge = &self.rot_imgs.tt_side;
+            let corner = &self.rot_imgs.tt_corner;
+            ImageFrame::new(
+                [edge.cw180, edge.none, edge.cw270, edge.cw90],
+                [corner.none, corner.cw270, corner.cw90, corner.cw180],
+                Color::Rgba(0.08, 0.07, 0.04, 1.0),
+                5.0,
+            )
+        })
+        .title_font_size(self.fonts.cyri.scale(15))
+        .parent(ui.window)
+        .desc_font_size(self.fonts.cyri.scale(12))
+        .font_id(self.fonts.cyri.conrod_id)
+        .desc_text_color(TEXT_COLOR);
+
+        let rows = (self.bank.capacity() + COLUMNS - 1) / COLUMNS;
+        let height = 90.0 + rows as f64 * 40.0;
+
+        // Window frame and BG
+        let pos = if self.show.social { 308.0 } else { 25.0 };
+        Image::new(self.imgs.social_bg_on)
+            .bottom_left_with_margins_on(ui.window, 308.0, pos)
+            .color(Some(UI_MAIN))
+            .w_h(340.0, height)
+            .set(state.ids.bg, ui);
+        Image::new(self.imgs.social_frame_on)
+            .middle_of(state.ids.bg)
+            .color(Some(UI_HIGHLIGHT_0))
+            .w_h(340.0, height)
+            .set(state.ids.frame, ui);
+        // Icon
+        Image::new(self.imgs.bag_bg)
+            .w_h(30.0, 30.0)
+            .top_left_with_margins_on(state.ids.frame, 6.0, 6.0)
+            .set(state.ids.icon, ui);
+        // X-Button
+        if Button::image(self.imgs.close_button)
+            .w_h(24.0, 25.0)
+            .hover_image(self.imgs.close_button_hover)
+            .press_image(self.imgs.close_button_press)
+            .top_right_with_margins_on(state.ids.frame, 0.0, 0.0)
+            .set(state.ids.close, ui)
+            .was_clicked()
+        {
+            event = Some(Event::Close);
+        }
+
+        // Title
+        Rectangle::fill_with([272.0, 42.0], color::TRANSPARENT)
+            .top_left_with_margins_on(state.ids.frame, 2.0, 44.0)
+            .set(state.ids.title_align, ui);
+        Text::new(&i18n.get("hud.bank"))
+            .middle_of(state.ids.title_align)
+            .font_id(self.fonts.cyri.conrod_id)
+            .font_size(self.fonts.cyri.scale(20))
+            .color(TEXT_COLOR)
+            .set(state.ids.title, ui);
+
+        // Slots
+        Rectangle::fill_with(
+            [COLUMNS as f64 * 40.0, rows as f64 * 40.0],
+            color::TRANSPARENT,
+        )
+        .top_left_with_margins_on(state.ids.frame, 50.0, 10.0)
+        .set(state.ids.slots_align, ui);
+        if state.ids.slots.len() < self.bank.capacity() {
+            state.update(|s| {
+                s.ids
+                    .slots
+                    .resize(self.bank.capacity(), &mut ui.widget_id_generator())
+            });
+        }
+        let mut slot_maker = SlotMaker {
+            empty_slot: self.imgs.inv_slot,
+            filled_slot: self.imgs.inv_slot,
+            selected_slot: self.imgs.inv_slot_sel,
+            background_color: Some(UI_MAIN),
+            content_size: ContentSize {
+                width_height_ratio: 1.0,
+                max_fraction: 0.75,
+            },
+            selected_content_scale: 1.067,
+            amount_font: self.fonts.cyri.conrod_id,
+            amount_margins: Vec2::new(-4.0, 0.0),
+            amount_font_size: self.fonts.cyri.scale(12),
+            amount_text_color: TEXT_COLOR,
+            content_source: self.bank,
+            image_source: self.item_imgs,
+            slot_manager: Some(self.slot_manager),
+            pulse: self.pulse,
+        };
+        for (i, (pos, item)) in self.bank.slots_with_id().enumerate() {
+            let x = i % COLUMNS;
+            let y = i / COLUMNS;
+            let slot_widget = slot_maker
+                .fabricate(pos, [40.0; 2])
+                .top_left_with_margins_on(state.ids.slots_align, y as f64 * 40.0, x as f64 * 40.0);
+            if let Some(item) = item {
+                let (title, desc) = super::util::item_text(item, self.msm);
+                let quality_col_img = match item.quality() {
+                    Quality::Low => self.imgs.inv_slot_grey,
+                    Quality::Common => self.imgs.inv_slot,
+                    Quality::Moderate => self.imgs.inv_slot_green,
+                    Quality::High => self.imgs.inv_slot_blue,
+                    Quality::Epic => self.imgs.inv_slot_purple,
+                    Quality::Legendary => self.imgs.inv_slot_gold,
+                    Quality::Artifact => self.imgs.inv_slot_orange,
+                    _ => self.imgs.inv_slot_red,
+                };
+                slot_widget
+                    .filled_slot(quality_col_img)
+                    .with_tooltip(
+                        self.tooltip_manager,
+                        title,
+                        &*desc,
+                        &item_tooltip,
+                        get_quality_col(item),
+                    )
+                    .set(state.ids.slots[i], ui);
+            } else {
+                slot_widget.set(state.ids.slots[i], ui);
+            }
+        }
+
+        // Used space
+        Text::new(&format!(
+            "{}/{}",
+            self.bank.populated_slots(),
+            self.bank.capacity()
+        ))
+        .bottom_right_with_margins_on(state.ids.frame, 12.0, 14.0)
+        .font_id(self.fonts.cyri.conrod_id)
+        .font_size(self.fonts.cyri.scale(14))
+        .color(TEXT_COLOR)
+        .set(state.ids.space_txt, ui);
+
+        event
+    }
+}
@@ -0,0 +1,621 @@
+use super::{
+    img_ids::{Imgs, ImgsRot},
+    item_imgs::{ItemImgs, ItemKey},
+    slots::{MailSlot, SlotManager},
+    Show, TEXT_COLOR, TEXT_COLOR_3, UI_HIGHLIGHT_0, UI_MAIN,
+};
+use crate::{
+    i18n::Localization,
+    ui::{
+        fonts::Fonts,
+        slot::{ContentSize, SlotMaker},
+        ImageFrame, Tooltip, TooltipManager, Tooltipable,
+    },
+};
+use client::Client;
+use common::{
+    comp::{inventory::slot::InvSlotId, Inventory},
+    mail::{Mail, MailId, MAX_MAIL_BODY_LENGTH, MAX_MAIL_ITEMS, MAX_MAIL_SUBJECT_LENGTH},
+};
+use conrod_core::{
+    color,
+    widget::{self, Button, Image, Rectangle, Scrollbar, Text, TextEdit},
+    widget_ids, Color, Colorable, Labelable, Positionable, Sizeable, Widget, WidgetCommon,
+};
+use vek::*;
+
+widget_ids! {
+    pub struct Ids {
+        frame,
+        close,
+        title_align,
+        title,
+        bg,
+        icon,
+        list_align,
+        scrollbar,
+        mail_buttons[],
+        empty_txt,
+        compose_button,
+        back_button,
+        sender_txt,
+        subject_txt,
+        body_align,
+        body_txt,
+        item_slots[],
+        item_imgs[],
+        claim_button,
+        delete_button,
+        recipient_label,
+        recipient_bg,
+        recipient_input,
+        subject_label,
+        subject_bg,
+        subject_input,
+        body_label,
+        body_bg,
+        body_input,
+        attachments_label,
+        attachment_slots[],
+        send_button,
+    }
+}
+
+#[derive(Clone, Copy)]
+enum View {
+    List,
+    Read(MailId),
+    Compose,
+}
+
+pub struct State {
+    ids: Ids,
+    view: View,
+    recipient: String,
+    subject: String,
+    body: String,
+}
+
+#[derive(WidgetCommon)]
+pub struct MailWindow<'a> {
+    show: &'a Show,
+    client: &'a Client,
+    imgs: &'a Imgs,
+    item_imgs: &'a ItemImgs,
+    fonts: &'a Fonts,
+    rot_imgs: &'a ImgsRot,
+    tooltip_manager: &'a mut TooltipManager,
+    slot_manager: &'a mut SlotManager,
+    localized_strings: &'a Localization,
+    attachments: &'a [InvSlotId],
+    pulse: f32,
+
+    #[conrod(common_builder)]
+    common: widget::CommonBuilder,
+}
+
+impl<'a> MailWindow<'a> {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        show: &'a Show,
+        client: &'a Client,
+        imgs: &'a Imgs,
+        item_imgs: &'a ItemImgs,
+        fonts: &'a Fonts,
+        rot_imgs: &'a ImgsRot,
+        tooltip_manager: &'a mut TooltipManager,
+        slot_manager: &'a mut SlotManager,
+        localized_strings: &'a Localization,
+        attachments: &'a [InvSlotId],
+        pulse: f32,
+    ) -> Self {
+        Self {
+            show,
+            client,
+            imgs,
+            item_imgs,
+            fonts,
+            rot_imgs,
+            tooltip_manager,
+            slot_manager,
+            localized_strings,
+            attachments,
+            pulse,
+            common: widget::CommonBuilder::default(),
+        }
+    }
+}
+
+pub enum Event {
+    Close,
+    Read(MailId),
+    Claim(MailId),
+    Delete(MailId),
+    /// Send a mail with the items in `attachments` attached
+    Send {
+        recipient: String,
+        subject: String,
+        body: String,
+    },
+}
+
+impl<'a> Widget for MailWindow<'a> {
+    type Event = Vec<Event>;
+    type State = State;
+    type Style = ();
+
+    fn init_state(&self, id_gen: widget::id::Generator) -> Self::State {
+        State {
+            ids: Ids::new(id_gen),
+            view: View::List,
+            recipient: String::new(),
+            subject: String::new(),
+            body: String::new(),
+        }
+    }
+
+    #[allow(clippy::unused_unit)] // TODO: Pending review in #587
+    fn style(&self) -> Self::Style { () }
+
+    fn update(mut self, args: widget::UpdateArgs<Self>) -> Self::Event {
+        let widget::UpdateArgs { state, ui, .. } = args;
+        let mut events = Vec::new();
+        let i18n = self.localized_strings;
+        let item_tooltip = Tooltip::new({
+            // Edge images [t, b, r, l]
+            // Corner images [tr, tl, br, bl]
+            let edge = &self.rot_imgs.tt_side;
+            let corner = &self.rot_imgs.tt_corner;
+            ImageFrame::new(
+                [edge.cw180, edge.none, edge.cw270, edge.cw90],
+                [corner.none, corner.cw270, corner.cw90, corner.cw180],
+                Color::Rgba(0.08, 0.07, 0.04, 1.0),
+                5.0,
+            )
+        })
+        .title_font_size(self.fonts.cyri.scale(15))
+        .parent(ui.window)
+        .desc_font_size(self.fonts.cyri.scale(12))
+        .font_id(self.fonts.cyri.conrod_id)
+        .desc_text_color(TEXT_COLOR);
+
+        // Window frame and BG
+        let pos = if self.show.social { 308.0 } else { 25.0 };
+        Image::new(self.imgs.social_bg_on)
+            .bottom_left_with_margins_on(ui.window, 308.0, pos)
+            .color(Some(UI_MAIN))
+            .w_h(280.0, 460.0)
+            .set(state.ids.bg, ui);
+        Image::new(self.imgs.social_frame_on)
+            .middle_of(state.ids.bg)
+            .color(Some(UI_HIGHLIGHT_0))
+            .w_h(280.0, 460.0)
+            .set(state.ids.frame, ui);
+        // Icon
+        Image::new(self.imgs.social)
+            .w_h(30.0, 30.0)
+            .top_left_with_margins_on(state.ids.frame, 6.0, 6.0)
+            .set(state.ids.icon, ui);
+        // X-Button
+        if Button::image(self.imgs.close_button)
+            .w_h(24.0, 25.0)
+            .hover_image(self.imgs.close_button_hover)
+            .press_image(self.imgs.close_button_press)
+            .top_right_with_margins_on(state.ids.frame, 0.0, 0.0)
+            .set(state.ids.close, ui)
+            .was_clicked()
+        {
+            events.push(Event::Close);
+        }
+
+        // Title
+        Rectangle::fill_with([212.0, 42.0], color::TRANSPARENT)
+            .top_left_with_margins_on(state.ids.frame, 2.0, 44.0)
+            .set(state.ids.title_align, ui);
+        Text::new(&i18n.get("hud.mail"))
+            .middle_of(state.ids.title_align)
+            .font_id(self.fonts.cyri.conrod_id)
+            .font_size(self.fonts.cyri.scale(20))
+            .color(TEXT_COLOR)
+            .set(state.ids.title, ui);
+
+        // The selected mail may have been deleted in the meantime
+        let client = self.client;
+        let mailbox = client.mailbox();
+        let selected = match state.view {
+            View::Read(id) => match mailbox.iter().find(|mail| mail.id == id) {
+                Some(mail) => Some(mail),
+                None => {
+                    state.update(|s| s.view = View::List);
+                    None
+                },
+            },
+            _ => None,
+        };
+
+        match (state.view, selected) {
+            (View::Read(_), Some(mail)) => {
+                if self
+                    .button(&i18n.get("hud.mail.back"))
+                    .bottom_left_with_margins_on(state.ids.frame, 9.0, 7.0)
+                    .set(state.ids.back_button, ui)
+                    .was_clicked()
+                {
+                    state.update(|s| s.view = View::List);
+                }
+                self.read_view(state, ui, mail, &item_tooltip, &mut events);
+            },
+            (View::Compose, _) => {
+                if self
+                    .button(&i18n.get("hud.mail.back"))
+                    .bottom_left_with_margins_on(state.ids.frame, 9.0, 7.0)
+                    .set(state.ids.back_button, ui)
+                    .was_clicked()
+                {
+                    state.update(|s| s.view = View::List);
+                }
+                self.compose_view(state, ui, &mut events);
+            },
+            _ => {
+                if self
+                    .button(&i18n.get("hud.mail.compose"))
+                    .bottom_right_with_margins_on(state.ids.frame, 9.0, 7.0)
+                    .set(state.ids.compose_button, ui)
+                    .was_clicked()
+                {
+                    state.update(|s| s.view = View::Compose);
+                }
+                self.list_view(state, ui, mailbox, &mut events);
+            },
+        }
+
+        events
+    }
+}
+
+impl<'a> MailWindow<'a> {
+    fn button<'b>(&self, label: &'b str) -> Button<'b, widget::button::Image> {
+        Button::image(self.imgs.button)
+            .w_h(106.0, 26.0)
+            .hover_image(self.imgs.button_hover)
+            .press_image(self.imgs.button_press)
+            .label(label)
+            .label_y(conrod_core::position::Relative::Scalar(3.0))
+            .label_color(TEXT_COLOR)
+            .label_font_size(self.fonts.cyri.scale(15))
+            .label_font_id(self.fonts.cyri.conrod_id)
+    }
+
+    fn list_view(
+        &self,
+        state: &mut widget::State<'_, State>,
+        ui: &mut conrod_core::UiCell<'_>,
+        mailbox: &[Mail],
+        events: &mut Vec<Event>,
+    ) {
+        Rectangle::fill_with([270.0, 346.0], color::TRANSPARENT)
+            .mid_top_with_margin_on(state.ids.frame, 52.0)
+            .scroll_kids_vertically()
+            .set(state.ids.list_align, ui);
+        Scrollbar::y_axis(state.ids.list_align)
+            .thickness(4.0)
+            .color(Color::Rgba(0.79, 1.09, 1.09, 0.0))
+            .set(state.ids.scrollbar, ui);
+
+        if mailbox.is_empty() {
+            Text::new(&self.localized_strings.get("hud.mail.empty"))
+                .mid_top_with_margin_on(state.ids.list_align, 10.0)
+                .font_id(self.fonts.cyri.conrod_id)
+                .font_size(self.fonts.cyri.scale(14))
+                .color(TEXT_COLOR_3)
+                .set(state.ids.empty_txt, ui);
+            return;
+        }
+
+        if state.ids.mail_buttons.len() < mailbox.len() {
+            state.update(|s| {
+                s.ids
+                    .mail_buttons
+                    .resize(mailbox.len(), &mut ui.widget_id_generator())
+            });
+        }
+        // Newest mail first
+        for (i, mail) in mailbox.iter().rev().enumerate() {
+            let button = Button::image(self.imgs.nothing);
+            let button = if i == 0 {
+                button.mid_top_with_margin_on(state.ids.list_align, 1.0)
+            } else {
+                button.down_from(state.ids.mail_buttons[i - 1], 1.0)
+            };
+            let parcel = if mail.items.is_empty() { "" } else { " [+]" };
+            if button
+                .w_h(260.0, 20.0)
+                .hover_image(self.imgs.selection_hover)
+                .press_image(self.imgs.selection_press)
+                .label(&format!("{} - {}{}", mail.sender, mail.subject, parcel))
+                .label_font_size(self.fonts.cyri.scale(14))
+                .label_y(conrod_core::position::Relative::Scalar(1.0))
+                .label_font_id(self.fonts.cyri.conrod_id)
+                .label_color(if mail.read { TEXT_COLOR_3 } else { TEXT_COLOR })
+                .set(state.ids.mail_buttons[i], ui)
+                .was_clicked()
+            {
+                if !mail.read {
+                    events.push(Event::Read(mail.id));
+                }
+                let id = mail.id;
+                state.update(|s| s.view = View::Read(id));
+            }
+        }
+    }
+
+    fn read_view(
+        &mut self,
+        state: &mut widget::State<'_, State>,
+        ui: &mut conrod_core::UiCell<'_>,
+        mail: &Mail,
+        item_tooltip: &Tooltip,
+        events: &mut Vec<Event>,
+    ) {
+        Text::new(
+            &self
+                .localized_strings
+                .get("hud.mail.from")
+                .replace("{sender}", &mail.sender),
+        )
+        .top_left_with_margins_on(state.ids.frame, 52.0, 10.0)
+        .font_id(self.fonts.cyri.conrod_id)
+        .font_size(self.fonts.cyri.scale(14))
+        .color(TEXT_COLOR_3)
+        .set(state.ids.sender_txt, ui);
+        Text::new(&mail.subject)
+            .down_from(state.ids.sender_txt, 4.0)
+            .w(260.0)
+            .font_id(self.fonts.cyri.conrod_id)
+            .font_size(self.fonts.cyri.scale(16))
+            .color(TEXT_COLOR)
+            .set(state.ids.subject_txt, ui);
+        Rectangle::fill_with([260.0, 220.0], color::TRANSPARENT)
+            .down_from(state.ids.subject_txt, 8.0)
+            .scroll_kids_vertically()
+            .set(state.ids.body_align, ui);
+        Text::new(&mail.body)
+            .top_left_of(state.ids.body_align)
+            .w(256.0)
+            .font_id(self.fonts.opensans.conrod_id)
+            .font_size(self.fonts.opensans.scale(14))
+            .color(TEXT_COLOR)
+            .set(state.ids.body_txt, ui);
+
+        // Attached items
+        let count = mail.items.len();
+        if state.ids.item_slots.len() < count {
+            state.update(|s| {
+                s.ids
+                    .item_slots
+                    .resize(count, &mut ui.widget_id_generator());
+                s.ids.item_imgs.resize(count, &mut ui.widget_id_generator());
+            });
+        }
+        for (i, item) in mail.items.iter().enumerate() {
+            let slot = Image::new(self.imgs.inv_slot)
+                .w_h(40.0, 40.0)
+                .color(Some(UI_MAIN));
+            let slot = if i == 0 {
+                slot.bottom_left_with_margins_on(state.ids.frame, 44.0, 10.0)
+            } else {
+                slot.right_from(state.ids.item_slots[i - 1], 2.0)
+            };
+            slot.set(state.ids.item_slots[i], ui);
+            let img = self
+                .item_imgs
+                .img_ids_or_not_found_img(ItemKey::from(item))
+                .into_iter()
+                .next()
+                .unwrap_or(self.imgs.nothing);
+            let amount = if item.amount() > 1 {
+                format!("{} x{}", item.name(), item.amount())
+            } else {
+                item.name().to_string()
+            };
+            Image::new(img)
+                .w_h(30.0, 30.0)
+                .middle_of(state.ids.item_slots[i])
+                .with_tooltip(
+                    self.tooltip_manager,
+                    &amount,
+                    item.description(),
+                    item_tooltip,
+                    TEXT_COLOR,
+                )
+                .set(state.ids.item_imgs[i], ui);
+        }
+
+        // Mail with items has to be emptied before it can be deleted
+        if mail.items.is_empty() {
+            if self
+                .button(&self.localized_strings.get("hud.mail.delete"))
+                .bottom_right_with_margins_on(state.ids.frame, 9.0, 7.0)
+                .set(state.ids.delete_button, ui)
+                .was_clicked()
+            {
+                events.push(Event::Delete(mail.id));
+                state.update(|s| s.view = View::List);
+            }
+        } else if self
+            .button(&self.localized_strings.get("hud.mail.claim"))
+            .bottom_right_with_margins_on(state.ids.frame, 9.0, 7.0)
+            .set(state.ids.claim_button, ui)
+            .was_clicked()
+        {
+            events.push(Event::Claim(mail.id));
+        }
+    }
+
+    fn compose_view(
+        self,
+        state: &mut widget::State<'_, State>,
+        ui: &mut conrod_core::UiCell<'_>,
+        events: &mut Vec<Event>,
+    ) {
+        let label = |text: &str| {
+            Text::new(text)
+                .font_id(self.fonts.cyri.conrod_id)
+                .font_size(self.fonts.cyri.scale(14))
+                .color(TEXT_COLOR)
+        };
+        let input_bg = |w: f64, h: f64| Rectangle::fill([w, h]).rgba(0.0, 0.0, 0.0, 0.4);
+        let input = |text: &str| {
+            TextEdit::new(text)
+                .color(TEXT_COLOR)
+                .font_size(self.fonts.opensans.scale(14))
+                .font_id(self.fonts.opensans.conrod_id)
+        };
+
+        // Recipient
+        label(&self.localized_strings.get("hud.mail.recipient"))
+            .top_left_with_margins_on(state.ids.frame, 52.0, 10.0)
+            .set(state.ids.recipient_label, ui);
+        input_bg(260.0, 22.0)
+            .down_from(state.ids.recipient_label, 4.0)
+            .set(state.ids.recipient_bg, ui);
+        if let Some(recipient) = input(&state.recipient)
+            .w_h(252.0, 20.0)
+            .middle_of(state.ids.recipient_bg)
+            .set(state.ids.recipient_input, ui)
+        {
+            let recipient = recipient.replace('\n', "");
+            state.update(|s| s.recipient = recipient);
+        }
+
+        // Subject
+        label(&self.localized_strings.get("hud.mail.subject"))
+            .down_from(state.ids.recipient_bg, 6.0)
+            .set(state.ids.subject_label, ui);
+        input_bg(260.0, 22.0)
+            .down_from(state.ids.subject_label, 4.0)
+            .set(state.ids.subject_bg, ui);
+        if let Some(subject) = input(&state.subject)
+            .w_h(252.0, 20.0)
+            .middle_of(state.ids.subject_bg)
+            .set(state.ids.subject_input, ui)
+        {
+            let subject = subject.replace('\n', "");
+            if subject.chars().count() <= MAX_MAIL_SUBJECT_LENGTH {
+                state.update(|s| s.subject = subject);
+            }
+        }
+
+        // Body
+        label(&self.localized_strings.get("hud.mail.body"))
+            .down_from(state.ids.subject_bg, 6.0)
+            .set(state.ids.body_label, ui);
+        input_bg(260.0, 150.0)
+            .down_from(state.ids.body_label, 4.0)
+            .set(state.ids.body_bg, ui);
+        if let Some(body) = input(&state.body)
+            .w_h(252.0, 144.0)
+            .middle_of(state.ids.body_bg)
+            .set(state.ids.body_input, ui)
+        {
+            if body.chars().count() <= MAX_MAIL_BODY_LENGTH {
+                state.update(|s| s.body = body);
+            }
+        }
+
+        // Attachments, dragged here from the inventory
+        label(&self.localized_strings.get("hud.mail.attachments"))
+            .down_from(state.ids.body_bg, 6.0)
+            .set(state.ids.attachments_label, ui);
+        let inventories = self.client.inventories();
+        let inventory = match inventories.get(self.client.entity()) {
+            Some(inventory) => inventory,
+            None => return,
+        };
+        self.attachment_slots(state, ui, inventory);
+
+        let can_send = !state.recipient.trim().is_empty();
+        if Button::image(self.imgs.button)
+            .w_h(106.0, 26.0)
+            .bottom_right_with_margins_on(state.ids.frame, 9.0, 7.0)
+            .hover_image(if can_send {
+                self.imgs.button_hover
+            } else {
+                self.imgs.button
+            })
+            .press_image(if can_send {
+                self.imgs.button_press
+            } else {
+                self.imgs.button
+            })
+            .label(&self.localized_strings.get("hud.mail.send"))
+            .label_y(conrod_core::position::Relative::Scalar(3.0))
+            .label_color(if can_send { TEXT_COLOR } else { TEXT_COLOR_3 })
+            .label_font_size(self.fonts.cyri.scale(15))
+            .label_font_id(self.fonts.cyri.conrod_id)
+            .set(state.ids.send_button, ui)
+            .was_clicked()
+            && can_send
+        {
+            events.push(Event::Send {
+                recipient: state.recipient.trim().to_string(),
+                subject: state.subject.clone(),
+                body: state.body.clone(),
+            });
+            state.update(|s| {
+                s.view = View::List;
+                s.recipient.clear();
+                s.subject.clear();
+                s.body.clear();
+            });
+        }
+    }
+
+    fn attachment_slots(
+        self,
+        state: &mut widget::State<'_, State>,
+        ui: &mut conrod_core::UiCell<'_>,
+        inventory: &Inventory,
+    ) {
+        let mut slot_maker = SlotMaker {
+            empty_slot: self.imgs.inv_slot,
+            filled_slot: self.imgs.inv_slot,
+            selected_slot: self.imgs.inv_slot_sel,
+            background_color: Some(UI_MAIN),
+            content_size: ContentSize {
+                width_height_ratio: 1.0,
+                max_fraction: 0.75,
+            },
+            selected_content_scale: 1.067,
+            amount_font: self.fonts.cyri.conrod_id,
+            amount_margins: Vec2::new(-4.0, 0.0),
+            amount_font_size: self.fonts.cyri.scale(12),
+            amount_text_color: TEXT_COLOR,
+            content_source: inventory,
+            image_source: self.item_imgs,
+            slot_manager: Some(self.slot_manager),
+            pulse: self.pulse,
+        };
+
+        if state.ids.attachment_slots.len() < MAX_MAIL_ITEMS {
+            state.update(|s| {
+                s.ids
+                    .attachment_slots
+                    .resize(MAX_MAIL_ITEMS, &mut ui.widget_id_generator())
+            });
+        }
+        for i in 0..MAX_MAIL_ITEMS {
+            let slot = MailSlot {
+                index: i,
+                invslot: self.attachments.get(i).copied(),
+            };
+            let slot_widget = slot_maker.fabricate(slot, [30.0; 2]);
+            let slot_widget = if i == 0 {
+                slot_widget.down_from(state.ids.attachments_label, 4.0)
+            } else {
+                slot_widget.right_from(state.ids.attachment_slots[i - 1], 2.0)
+            };
+            slot_widget.set(state.ids.attachment_slots[i], ui);
+        }
+    }
+}
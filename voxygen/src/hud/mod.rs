@@ -1,4 +1,5 @@
 mod bag;
+mod bank;
 mod buffs;
 mod buttons;
 mod chat;
@@ -9,6 +10,7 @@ mod group;
 mod hotbar;
 mod img_ids;
 mod item_imgs;
+mod mail;
 mod map;
 mod minimap;
 mod overhead;
@@ -27,6 +29,7 @@ pub use item_imgs::animate_by_pulse;
 pub use settings_window::ScaleChange;
 
 use bag::Bag;
+use bank::BankWindow;
 use buffs::BuffsBar;
 use buttons::Buttons;
 use chat::Chat;
@@ -37,6 +40,7 @@ use esc_menu::EscMenu;
 use group::Group;
 use img_ids::Imgs;
 use item_imgs::ItemImgs;
+use mail::MailWindow;
 use map::Map;
 use minimap::MiniMap;
 use popup::Popup;
@@ -60,7 +64,7 @@ use crate::{
     window::{Event as WinEvent, FullScreenSettings, GameInput},
     GlobalState,
 };
-use client::Client;
+use client::{ChannelStats, Client};
 use common::{
     combat,
     comp::{
@@ -69,6 +73,7 @@ use common::{
         skills::{Skill, SkillGroupKind},
         BuffKind,
     },
+    mail::MailId,
     outcome::Outcome,
     terrain::TerrainChunk,
     trade::TradeAction,
@@ -210,6 +215,7 @@ widget_ids! {
         debug_bg,
         fps_counter,
         ping,
+        link,
         coordinates,
         velocity,
         orientation,
@@ -260,6 +266,8 @@ widget_ids! {
         esc_menu,
         small_window,
         social_window,
+        mail_window,
+        bank_window,
         crafting_window,
         settings_window,
         group_window,
@@ -319,6 +327,7 @@ pub struct DebugInfo {
     pub tps: f64,
     pub frame_time: Duration,
     pub ping_ms: f64,
+    pub link_stats: Option<Arc<ChannelStats>>,
     pub coordinates: Option<comp::Pos>,
     pub velocity: Option<comp::Vel>,
     pub ori: Option<comp::Ori>,
@@ -407,6 +416,17 @@ pub enum Event {
     SplitDropSlot(comp::slot::Slot),
     ChangeHotbarState(Box<HotbarState>),
     TradeAction(TradeAction),
+    RequestMailbox,
+    SendMail {
+        recipient: String,
+        subject: String,
+        body: String,
+        items: Vec<comp::slot::InvSlotId>,
+    },
+    ReadMail(MailId),
+    ClaimMail(MailId),
+    DeleteMail(MailId),
+    CloseBank,
     Ability3(bool),
     Ability4(bool),
     Logout,
@@ -504,6 +524,8 @@ pub struct Show {
     bag_inv: bool,
     trade: bool,
     social: bool,
+    mail: bool,
+    bank: bool,
     diary: bool,
     group: bool,
     group_menu: bool,
@@ -561,6 +583,26 @@ impl Show {
         }
     }
 
+    fn mail(&mut self, open: bool) {
+        if !self.esc_menu {
+            self.mail = open;
+            self.bag = open;
+            self.map = false;
+            self.want_grab = !open;
+        }
+    }
+
+    fn toggle_mail(&mut self) { self.mail(!self.mail) }
+
+    fn bank(&mut self, open: bool) {
+        if !self.esc_menu {
+            self.bank = open;
+            self.bag = open;
+            self.map = false;
+            self.want_grab = !open;
+        }
+    }
+
     fn crafting(&mut self, open: bool) {
         if !self.esc_menu {
             self.crafting = open;
@@ -626,6 +668,8 @@ impl Show {
             || self.esc_menu
             || self.map
             || self.social
+            || self.mail
+            || self.bank
             || self.crafting
             || self.diary
             || self.help
@@ -639,6 +683,8 @@ impl Show {
             self.intro = false;
             self.map = false;
             self.social = false;
+            self.mail = false;
+            self.bank = false;
             self.diary = false;
             self.crafting = false;
             self.open_windows = Windows::None;
@@ -737,6 +783,8 @@ pub struct Hud {
     pulse: f32,
     velocity: f32,
     slot_manager: slots::SlotManager,
+    // Inventory slots of the items attached to the mail being written
+    mail_attachments: Vec<comp::slot::InvSlotId>,
     hotbar: hotbar::State,
     events: Vec<Event>,
     crosshair_opacity: f32,
@@ -824,6 +872,8 @@ impl Hud {
                 crafting: false,
                 ui: true,
                 social: false,
+                mail: false,
+                bank: false,
                 diary: false,
                 group: false,
                 group_menu: false,
@@ -846,6 +896,7 @@ impl Hud {
             pulse: 0.0,
             velocity: 0.0,
             slot_manager,
+            mail_attachments: Vec::new(),
             hotbar: hotbar_state,
             events: Vec::new(),
             crosshair_opacity: 0.0,
@@ -908,6 +959,14 @@ impl Hud {
                 self.show.toggle_trade();
             }
 
+            // The server closes the bank when the player walks away from it, while closing
+            // the window has to be passed on to the server
+            if client.bank().is_none() && self.show.bank {
+                self.show.bank(false);
+            } else if client.bank().is_some() && !self.show.bank {
+                events.push(Event::CloseBank);
+            }
+
             //self.input = client.read_storage::<comp::ControllerInputs>();
             if let Some(health) = healths.get(me) {
                 // Hurt Frame
@@ -1820,6 +1879,25 @@ impl Hud {
                 .font_id(self.fonts.cyri.conrod_id)
                 .font_size(self.fonts.cyri.scale(14))
                 .set(self.ids.ping, ui_widgets);
+            // Link quality of the connection to the server
+            let link_text = match &debug_info.link_stats {
+                Some(stats) => format!(
+                    "Link: {} rtt, {} retransmits, {} msgs ({} B) queued",
+                    stats
+                        .rtt()
+                        .map_or("?".to_owned(), |rtt| format!("{}ms", rtt.as_millis())),
+                    stats.retransmissions(),
+                    stats.queued_messages(),
+                    stats.queued_bytes(),
+                ),
+                None => "Link: not connected".to_owned(),
+            };
+            Text::new(&link_text)
+                .color(TEXT_COLOR)
+                .down_from(self.ids.ping, 5.0)
+                .font_id(self.fonts.cyri.conrod_id)
+                .font_size(self.fonts.cyri.scale(14))
+                .set(self.ids.link, ui_widgets);
             // Player's position
             let coordinates_text = match debug_info.coordinates {
                 Some(coordinates) => format!(
@@ -1830,7 +1908,7 @@ impl Hud {
             };
             Text::new(&coordinates_text)
                 .color(TEXT_COLOR)
-                .down_from(self.ids.ping, 5.0)
+                .down_from(self.ids.link, 5.0)
                 .font_id(self.fonts.cyri.conrod_id)
                 .font_size(self.fonts.cyri.scale(14))
                 .set(self.ids.coordinates, ui_widgets);
@@ -2251,6 +2329,7 @@ impl Hud {
                         self.show.stats = false;
                         self.show.bag(false);
                         self.show.crafting(false);
+                        self.show.bank(false);
                         if !self.show.social {
                             self.show.want_grab = true;
                             self.force_ungrab = false;
@@ -2295,6 +2374,85 @@ impl Hud {
             }
         }
 
+        // Mail window
+        if self.show.mail {
+            // Forget attachments whose items were moved or removed in the meantime
+            if let Some(inventory) = inventories.get(client.entity()) {
+                self.mail_attachments
+                    .retain(|slot| inventory.get(*slot).is_some());
+            }
+            for event in MailWindow::new(
+                &self.show,
+                client,
+                &self.imgs,
+                &self.item_imgs,
+                &self.fonts,
+                &self.rot_imgs,
+                tooltip_manager,
+                &mut self.slot_manager,
+                i18n,
+                &self.mail_attachments,
+                self.pulse,
+            )
+            .set(self.ids.mail_window, ui_widgets)
+            {
+                match event {
+                    mail::Event::Close => {
+                        self.show.mail(false);
+                        self.mail_attachments.clear();
+                        if !self.show.social {
+                            self.show.want_grab = true;
+                            self.force_ungrab = false;
+                        } else {
+                            self.force_ungrab = true
+                        };
+                    },
+                    mail::Event::Read(id) => events.push(Event::ReadMail(id)),
+                    mail::Event::Claim(id) => events.push(Event::ClaimMail(id)),
+                    mail::Event::Delete(id) => events.push(Event::DeleteMail(id)),
+                    mail::Event::Send {
+                        recipient,
+                        subject,
+                        body,
+                    } => events.push(Event::SendMail {
+                        recipient,
+                        subject,
+                        body,
+                        items: std::mem::take(&mut self.mail_attachments),
+                    }),
+                }
+            }
+        }
+
+        // Bank window
+        if self.show.bank {
+            if let Some(bank) = client.bank() {
+                if let Some(bank::Event::Close) = BankWindow::new(
+                    &self.show,
+                    bank,
+                    &self.imgs,
+                    &self.item_imgs,
+                    &self.fonts,
+                    &self.rot_imgs,
+                    tooltip_manager,
+                    &mut self.slot_manager,
+                    i18n,
+                    &msm,
+                    self.pulse,
+                )
+                .set(self.ids.bank_window, ui_widgets)
+                {
+                    self.show.bank(false);
+                    if !self.show.social {
+                        self.show.want_grab = true;
+                        self.force_ungrab = false;
+                    } else {
+                        self.force_ungrab = true
+                    };
+                }
+            }
+        }
+
         // Buffs
         let ecs = client.state().ecs();
         let entity = client.entity();
@@ -2792,6 +2950,8 @@ impl Hud {
                 Equip(e) => Some(Slot::Equip(e)),
                 Hotbar(_) => None,
                 Trade(_) => None,
+                Mail(_) => None,
+                Bank(b) => Some(Slot::Bank(b)),
             };
             match event {
                 slot::Event::Dragged(a, b) => {
@@ -2832,6 +2992,16 @@ impl Hud {
                                 }
                             }
                         }
+                    } else if let (Inventory(InventorySlot { slot, ours: true }), Mail(_)) = (a, b)
+                    {
+                        if !self.mail_attachments.contains(&slot)
+                            && self.mail_attachments.len() < common::mail::MAX_MAIL_ITEMS
+                        {
+                            self.mail_attachments.push(slot);
+                        }
+                    } else if let (Mail(m), Inventory(_)) = (a, b) {
+                        self.mail_attachments
+                            .retain(|slot| Some(*slot) != m.invslot);
                     }
                 },
                 slot::Event::Dropped(from) => {
@@ -2851,6 +3021,9 @@ impl Hud {
                                 }));
                             }
                         }
+                    } else if let Mail(m) = from {
+                        self.mail_attachments
+                            .retain(|slot| Some(*slot) != m.invslot);
                     }
                 },
                 slot::Event::SplitDropped(from) => {
@@ -2902,7 +3075,21 @@ impl Hud {
                 },
                 slot::Event::Used(from) => {
                     // Item used (selected and then clicked again)
-                    if let Some(from) = to_slot(from) {
+                    if let Bank(b) = from {
+                        // Items can't be used from the bank, so move them to the inventory
+                        // instead
+                        if let Some(free) = inventories.get(client.entity()).and_then(|inv| {
+                            inv.slots_with_id()
+                                .find(|(_, slot)| slot.is_none())
+                                .map(|(id, _)| id)
+                        }) {
+                            events.push(Event::SwapSlots {
+                                slot_a: Slot::Bank(b),
+                                slot_b: Slot::Inventory(free),
+                                bypass_dialog: false,
+                            });
+                        }
+                    } else if let Some(from) = to_slot(from) {
                         events.push(Event::UseSlot {
                             slot: from,
                             bypass_dialog: false,
@@ -2934,6 +3121,8 @@ impl Hud {
 
     pub fn new_message(&mut self, msg: comp::ChatMsg) { self.new_messages.push_back(msg); }
 
+    pub fn open_bank(&mut self) { self.show.bank(true); }
+
     pub fn new_notification(&mut self, msg: Notification) { self.new_notifications.push_back(msg); }
 
     pub fn set_scaling_mode(&mut self, scale_mode: ScaleMode) {
@@ -3096,6 +3285,13 @@ impl Hud {
                     self.show.toggle_social();
                     true
                 },
+                GameInput::Mail if state => {
+                    self.show.toggle_mail();
+                    if self.show.mail {
+                        self.events.push(Event::RequestMailbox);
+                    }
+                    true
+                },
                 GameInput::Crafting if state => {
                     self.show.toggle_crafting();
                     true
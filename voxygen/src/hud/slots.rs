@@ -9,8 +9,8 @@ use common::comp::{
         tool::{AbilityMap, Hands, ToolKind},
         ItemKind, MaterialStatManifest,
     },
-    slot::InvSlotId,
-    Energy, Inventory,
+    slot::{BankSlotId, InvSlotId},
+    Bank, Energy, Inventory,
 };
 use conrod_core::{image, Color};
 use specs::Entity as EcsEntity;
@@ -23,6 +23,8 @@ pub enum SlotKind {
     Equip(EquipSlot),
     Hotbar(HotbarSlot),
     Trade(TradeSlot),
+    Mail(MailSlot),
+    Bank(BankSlotId),
     /* Spellbook(SpellbookSlot), TODO */
 }
 
@@ -107,6 +109,50 @@ impl SlotKey<Inventory, ItemImgs> for TradeSlot {
     }
 }
 
+/// An item from the inventory that is attached to the mail being written
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MailSlot {
+    pub index: usize,
+    pub invslot: Option<InvSlotId>,
+}
+
+impl SlotKey<Inventory, ItemImgs> for MailSlot {
+    type ImageKey = ItemKey;
+
+    fn image_key(&self, source: &Inventory) -> Option<(Self::ImageKey, Option<Color>)> {
+        self.invslot
+            .and_then(|slot| InventorySlot { slot, ours: true }.image_key(source))
+    }
+
+    fn amount(&self, source: &Inventory) -> Option<u32> {
+        self.invslot
+            .and_then(|slot| InventorySlot { slot, ours: true }.amount(source))
+    }
+
+    fn image_ids(key: &Self::ImageKey, source: &ItemImgs) -> Vec<image::Id> {
+        source.img_ids_or_not_found_img(key.clone())
+    }
+}
+
+impl SlotKey<Bank, ItemImgs> for BankSlotId {
+    type ImageKey = ItemKey;
+
+    fn image_key(&self, source: &Bank) -> Option<(Self::ImageKey, Option<Color>)> {
+        source.get(*self).map(|i| (i.into(), None))
+    }
+
+    fn amount(&self, source: &Bank) -> Option<u32> {
+        source
+            .get(*self)
+            .map(|item| item.amount())
+            .filter(|amount| *amount > 1)
+    }
+
+    fn image_ids(key: &Self::ImageKey, source: &ItemImgs) -> Vec<image::Id> {
+        source.img_ids_or_not_found_img(key.clone())
+    }
+}
+
 #[derive(Clone, PartialEq)]
 pub enum HotbarImage {
     Item(ItemKey),
@@ -259,6 +305,12 @@ impl From<HotbarSlot> for SlotKind {
 impl From<TradeSlot> for SlotKind {
     fn from(trade: TradeSlot) -> Self { Self::Trade(trade) }
 }
+impl From<MailSlot> for SlotKind {
+    fn from(mail: MailSlot) -> Self { Self::Mail(mail) }
+}
+impl From<BankSlotId> for SlotKind {
+    fn from(bank: BankSlotId) -> Self { Self::Bank(bank) }
+}
 
 impl SumSlot for SlotKind {}
 
@@ -38,6 +38,8 @@ pub struct AuthTrust(String, bool);
 // and create the client (which involves establishing a connection to the
 // server).
 pub struct ClientInit {
+    /// The host that is connected to, `None` if it was resolved already
+    pub host: Option<String>,
     rx: Receiver<Msg>,
     trust_tx: Sender<AuthTrust>,
     cancel: Arc<AtomicBool>,
@@ -51,7 +53,12 @@ impl ClientInit {
         view_distance: Option<u32>,
         password: String,
         runtime: Option<Arc<runtime::Runtime>>,
+        server_identity: Option<[u8; 32]>,
     ) -> Self {
+        let host = match &connection_args {
+            ClientConnArgs::Host(host) => Some(host.clone()),
+            ClientConnArgs::Resolved(_) => None,
+        };
         let (tx, rx) = unbounded();
         let (trust_tx, trust_rx) = unbounded();
         let cancel = Arc::new(AtomicBool::new(false));
@@ -106,6 +113,7 @@ impl ClientInit {
                     connection_args.clone(),
                     view_distance,
                     Arc::clone(&runtime2),
+                    server_identity,
                 )
                 .await
                 {
@@ -139,6 +147,7 @@ impl ClientInit {
         });
 
         ClientInit {
+            host,
             rx,
             trust_tx,
             cancel,
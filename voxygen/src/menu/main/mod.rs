@@ -109,7 +109,15 @@ impl PlayState for MainMenuState {
         // Poll client creation.
         match self.client_init.as_ref().and_then(|init| init.poll()) {
             Some(InitMsg::Done(Ok(mut client))) => {
-                self.client_init = None;
+                // Remember the key of the server, so that it is recognized next time
+                let host = self.client_init.take().and_then(|init| init.host.clone());
+                if let (Some(host), Some(identity)) = (host, client.server_identity()) {
+                    let net_settings = &mut global_state.settings.networking;
+                    if net_settings.server_identity(&host).is_none() {
+                        net_settings.set_server_identity(host, identity);
+                        global_state.settings.save_to_file_warn();
+                    }
+                }
                 self.main_menu_ui.connected();
                 // Register voxygen components / resources
                 crate::ecs::init(client.state_mut().ecs_mut());
@@ -167,6 +175,11 @@ impl PlayState for MainMenuState {
                             )) => localized_strings
                                 .get("main.login.network_wrong_version")
                                 .into(),
+                            client::Error::NetworkErr(NetworkError::ConnectFailed(
+                                NetworkConnectError::UnexpectedIdentity(_),
+                            )) => localized_strings
+                                .get("main.login.server_identity_changed")
+                                .into(),
                             client::Error::NetworkErr(e) => format!(
                                 "{}: {:?}",
                                 localized_strings.get("main.login.network_error"),
@@ -347,12 +360,17 @@ fn attempt_login(
     if comp::Player::alias_is_valid(&username) {
         // Don't try to connect if there is already a connection in progress.
         if client_init.is_none() {
+            let server_identity = match &connection_args {
+                ClientConnArgs::Host(host) => settings.networking.server_identity(host),
+                ClientConnArgs::Resolved(_) => None,
+            };
             *client_init = Some(ClientInit::new(
                 connection_args,
                 username,
                 Some(settings.graphics.view_distance),
                 password,
                 runtime,
+                server_identity,
             ));
         }
     } else {
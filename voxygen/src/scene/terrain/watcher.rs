@@ -111,7 +111,7 @@ impl BlocksOfInterest {
                         _ => {},
                     },
                 }
-                if block.is_collectible() {
+                if block.is_collectible() || block.is_bank() {
                     interactables.push(pos);
                 }
                 if let Some(glow) = block.get_glow() {
@@ -230,6 +230,23 @@ impl SessionState {
                 client::Event::CharacterError(error) => {
                     global_state.client_error = Some(error);
                 },
+                client::Event::MailSent => {
+                    let i18n = global_state.i18n.read();
+                    self.hud
+                        .new_message(ChatType::Meta.chat_msg(i18n.get("hud.mail.sent")));
+                },
+                client::Event::NewMail => {
+                    let i18n = global_state.i18n.read();
+                    self.hud
+                        .new_message(ChatType::Meta.chat_msg(i18n.get("hud.mail.new_mail")));
+                },
+                client::Event::MailError(error) => {
+                    self.hud.new_message(ChatMsg {
+                        chat_type: ChatType::CommandError,
+                        message: error.to_string(),
+                    });
+                },
+                client::Event::BankOpened => self.hud.open_bank(),
             }
         }
 
@@ -524,6 +541,8 @@ impl PlayState for SessionState {
                                             Interactable::Block(block, pos) => {
                                                 if block.is_collectible() {
                                                     client.collect_block(pos);
+                                                } else if block.is_bank() {
+                                                    client.open_bank(pos);
                                                 }
                                             },
                                             Interactable::Entity(entity) => {
@@ -760,6 +779,7 @@ impl PlayState for SessionState {
                     tps: global_state.clock.stats().average_tps,
                     frame_time: global_state.clock.stats().average_busy_dt,
                     ping_ms: self.client.borrow().get_ping_ms_rolling_avg(),
+                    link_stats: self.client.borrow().get_link_stats(),
                     coordinates: self
                         .client
                         .borrow()
@@ -1056,6 +1076,7 @@ impl PlayState for SessionState {
                                             move_allowed = false;
                                         }
                                     },
+                                    comp::slot::Slot::Bank(_) => {},
                                 }
                             };
                         }
@@ -1207,6 +1228,20 @@ impl PlayState for SessionState {
                         let mut client = self.client.borrow_mut();
                         client.perform_trade_action(action);
                     },
+                    HudEvent::RequestMailbox => self.client.borrow_mut().request_mailbox(),
+                    HudEvent::SendMail {
+                        recipient,
+                        subject,
+                        body,
+                        items,
+                    } => {
+                        let mut client = self.client.borrow_mut();
+                        client.send_mail(recipient, subject, body, items);
+                    },
+                    HudEvent::ReadMail(id) => self.client.borrow_mut().read_mail(id),
+                    HudEvent::ClaimMail(id) => self.client.borrow_mut().claim_mail(id),
+                    HudEvent::DeleteMail(id) => self.client.borrow_mut().delete_mail(id),
+                    HudEvent::CloseBank => self.client.borrow_mut().close_bank(),
                     HudEvent::Ability3(state) => {
                         let mut client = self.client.borrow_mut();
                         client.handle_input(InputKind::Ability(0), state);
@@ -1555,7 +1590,7 @@ fn under_cursor(
 
     let cam_ray = terrain
         .ray(cam_pos, cam_pos + cam_dir * 100.0)
-        .until(|block| block.is_filled() || block.is_collectible())
+        .until(|block| block.is_filled() || block.is_collectible() || block.is_bank())
         .cast();
 
     let cam_dist = cam_ray.0;
@@ -1677,7 +1712,7 @@ fn select_interactable(
         .and_then(|(e, dist_to_player)| (dist_to_player < MAX_PICKUP_RANGE).then_some(Interactable::Entity(e)))
         .or_else(|| selected_pos.and_then(|sp|
                 client.state().terrain().get(sp).ok().copied()
-                    .filter(Block::is_collectible).map(|b| Interactable::Block(b, sp))
+                    .filter(|b| b.is_collectible() || b.is_bank()).map(|b| Interactable::Block(b, sp))
         ))
         .or_else(|| {
             let ecs = client.state().ecs();
@@ -144,6 +144,7 @@ impl ControlSettings {
             GameInput::Bag => KeyMouse::Key(VirtualKeyCode::B),
             GameInput::Trade => KeyMouse::Key(VirtualKeyCode::R),
             GameInput::Social => KeyMouse::Key(VirtualKeyCode::O),
+            GameInput::Mail => KeyMouse::Key(VirtualKeyCode::X),
             GameInput::Crafting => KeyMouse::Key(VirtualKeyCode::C),
             GameInput::Spellbook => KeyMouse::Key(VirtualKeyCode::P),
             GameInput::Settings => KeyMouse::Key(VirtualKeyCode::N),
@@ -525,6 +526,10 @@ pub struct NetworkingSettings {
     pub servers: Vec<String>,
     pub default_server: String,
     pub trusted_auth_servers: HashSet<String>,
+    /// Public keys (as hex) the servers proved to own when they were first
+    /// connected to. Connecting fails if a server proves to own another key,
+    /// as someone may be impersonating it.
+    pub server_identities: HashMap<String, String>,
 }
 
 impl Default for NetworkingSettings {
@@ -537,10 +542,38 @@ impl Default for NetworkingSettings {
                 .iter()
                 .map(|s| s.to_string())
                 .collect(),
+            server_identities: HashMap::new(),
         }
     }
 }
 
+impl NetworkingSettings {
+    /// The key `server` proved to own before, if it was connected to before
+    pub fn server_identity(&self, server: &str) -> Option<[u8; 32]> {
+        let hex = self.server_identities.get(server)?;
+        let mut identity = [0u8; 32];
+        if hex.len() != identity.len() * 2 {
+            warn!(?server, "Invalid server identity in settings, ignoring it");
+            return None;
+        }
+        for (i, byte) in identity.iter_mut().enumerate() {
+            *byte = match hex.get(i * 2..i * 2 + 2).map(|b| u8::from_str_radix(b, 16)) {
+                Some(Ok(byte)) => byte,
+                _ => {
+                    warn!(?server, "Invalid server identity in settings, ignoring it");
+                    return None;
+                },
+            };
+        }
+        Some(identity)
+    }
+
+    pub fn set_server_identity(&mut self, server: String, identity: [u8; 32]) {
+        let hex = identity.iter().map(|b| format!("{:02x}", b)).collect();
+        self.server_identities.insert(server, hex);
+    }
+}
+
 /// `Log` stores whether we should create a log file
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(default)]
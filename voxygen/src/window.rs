@@ -55,6 +55,7 @@ pub enum GameInput {
     Bag,
     Trade,
     Social,
+    Mail,
     Crafting,
     Spellbook,
     Settings,
@@ -107,6 +108,7 @@ impl GameInput {
             GameInput::Bag => "gameinput.bag",
             GameInput::Trade => "gameinput.trade",
             GameInput::Social => "gameinput.social",
+            GameInput::Mail => "gameinput.mail",
             GameInput::Crafting => "gameinput.crafting",
             GameInput::Spellbook => "gameinput.spellbook",
             GameInput::Settings => "gameinput.settings",
@@ -167,6 +169,7 @@ impl GameInput {
             GameInput::Bag,
             GameInput::Trade,
             GameInput::Social,
+            GameInput::Mail,
             GameInput::Crafting,
             GameInput::Spellbook,
             GameInput::Settings,
@@ -643,7 +643,12 @@ impl Settlement {
                                 if (col_sample.path.map(|(dist, _, _, _)| dist > 6.0 && dist < 7.0).unwrap_or(false) && is_lamp) //roll(0, 50) == 0)
                                     || (roll(0, 750) == 0 && col_sample.path.map(|(dist, _, _, _)| dist > 20.0).unwrap_or(true))
                                 {
-                                    surface_sprite = Some(SpriteKind::StreetLamp);
+                                    // Some of the lamps make way for banks
+                                    surface_sprite = Some(if roll(1, 25) == 0 {
+                                        SpriteKind::Bank
+                                    } else {
+                                        SpriteKind::StreetLamp
+                                    });
                                 }
                             }
 